@@ -4,12 +4,74 @@
 //! stream in the `/AP` → `/N` (Normal appearance) entry. These functions
 //! generate those content streams.
 
+use crate::font::{
+    EMBEDDED_FONT_NAME, EmbeddedFont, encode_win_ansi, helvetica_text_width, wrap_text,
+};
+use crate::stamp::{STAMP_IMAGE_NAME, image_size};
 use crate::types::*;
-use pdf_writer::Content;
+use pdf_writer::{Content, Name};
+use std::borrow::Cow;
+
+/// The distance between baselines of wrapped text, relative to the font size.
+pub(crate) const LINE_SPACING: f32 = 1.2;
+
+/// The width of the callout line of a free text annotation.
+const CALLOUT_LINE_WIDTH: f32 = 0.5;
+
+/// The ending drawn where the callout line of a free text annotation points
+/// to.
+pub(crate) const CALLOUT_LINE_ENDING: LineEnding = LineEnding::OpenArrow;
+
+/// The font size of the length displayed on a line with a measurement scale.
+const MEASURE_FONT_SIZE: f32 = 9.0;
+
+/// The resource name of the graphics state that sets the blend mode and fill
+/// opacity of an appearance.
+pub(crate) const GRAPHICS_STATE_NAME: &[u8] = b"GS0";
+
+/// Color operators that pick the operator matching the color space of an
+/// [`AnnotColor`].
+trait AnnotColorExt {
+    /// Set the fill color with `g`, `rg` or `k`.
+    fn set_fill_annot_color(&mut self, color: AnnotColor) -> &mut Self;
+    /// Set the stroke color with `G`, `RG` or `K`.
+    fn set_stroke_annot_color(&mut self, color: AnnotColor) -> &mut Self;
+}
+
+impl AnnotColorExt for Content {
+    fn set_fill_annot_color(&mut self, color: AnnotColor) -> &mut Self {
+        match color {
+            AnnotColor::Gray(g) => self.set_fill_gray(g),
+            AnnotColor::Rgb(r, g, b) => self.set_fill_rgb(r, g, b),
+            AnnotColor::Cmyk(c, m, y, k) => self.set_fill_cmyk(c, m, y, k),
+        }
+    }
+
+    fn set_stroke_annot_color(&mut self, color: AnnotColor) -> &mut Self {
+        match color {
+            AnnotColor::Gray(g) => self.set_stroke_gray(g),
+            AnnotColor::Rgb(r, g, b) => self.set_stroke_rgb(r, g, b),
+            AnnotColor::Cmyk(c, m, y, k) => self.set_stroke_cmyk(c, m, y, k),
+        }
+    }
+}
+
+/// The named appearance states of a multi-state annotation, such as a checkbox.
+///
+/// These are written as a dictionary in `/AP` → `/N`, and `/AS` selects the
+/// state that is currently shown.
+#[derive(Debug, Clone)]
+pub struct AppearanceStates {
+    /// The appearance stream of each state, keyed by the state name.
+    pub states: Vec<(String, Vec<u8>)>,
+    /// The name of the currently selected state.
+    pub current: String,
+}
 
 /// Generate the appearance stream for a highlight annotation.
 ///
-/// Draws a semi-transparent colored rectangle over the annotation rect.
+/// Fills each quadrilateral of the quad points, or the whole annotation rect
+/// if there are none.
 pub fn generate_highlight_appearance(annot: &HighlightAnnot) -> Vec<u8> {
     let color = annot.base.color.unwrap_or(AnnotColor::yellow());
     let rect = &annot.base.rect;
@@ -17,85 +79,164 @@ pub fn generate_highlight_appearance(annot: &HighlightAnnot) -> Vec<u8> {
     let height = rect[3] - rect[1];
 
     let mut content = Content::new();
-    content
-        .set_fill_rgb(color.r, color.g, color.b)
-        .rect(0.0, 0.0, width, height)
-        .fill_nonzero();
+    content.set_fill_annot_color(color);
+
+    if annot.quad_points.len() < 8 {
+        content.rect(0.0, 0.0, width, height).fill_nonzero();
+    }
+
+    for quad in annot.quad_points.chunks_exact(8) {
+        // Quad points are usually not in path order, so sort the corners to
+        // avoid filling a self-intersecting "bowtie".
+        let corners = order_quad_corners(quad);
+        content.move_to(corners[0][0] - rect[0], corners[0][1] - rect[1]);
+        for corner in &corners[1..] {
+            content.line_to(corner[0] - rect[0], corner[1] - rect[1]);
+        }
+        content.close_path().fill_nonzero();
+    }
 
     content.finish().into_vec()
 }
 
+/// Sort the four corners of a quad (eight coordinates) counterclockwise
+/// around their centroid, so that they form a convex quadrilateral path.
+fn order_quad_corners(quad: &[f32]) -> [[f32; 2]; 4] {
+    let mut corners = [
+        [quad[0], quad[1]],
+        [quad[2], quad[3]],
+        [quad[4], quad[5]],
+        [quad[6], quad[7]],
+    ];
+    let cx = corners.iter().map(|c| c[0]).sum::<f32>() / 4.0;
+    let cy = corners.iter().map(|c| c[1]).sum::<f32>() / 4.0;
+    corners.sort_by(|a, b| {
+        let angle_a = (a[1] - cy).atan2(a[0] - cx);
+        let angle_b = (b[1] - cy).atan2(b[0] - cx);
+        angle_a.total_cmp(&angle_b)
+    });
+
+    corners
+}
+
 /// Generate the appearance stream for an underline annotation.
 ///
-/// Draws a colored line at the bottom of the annotation rect.
+/// Draws a colored line at the bottom of each quad, or of the annotation rect
+/// if there are no quad points.
 pub fn generate_underline_appearance(annot: &UnderlineAnnot) -> Vec<u8> {
     let color = annot.base.color.unwrap_or(AnnotColor::red());
+
+    let mut content = Content::new();
+    content.set_stroke_annot_color(color).set_line_width(1.0);
+    for [x0, y0, x1, _] in markup_boxes(&annot.base.rect, &annot.quad_points) {
+        content.move_to(x0, y0 + 0.5).line_to(x1, y0 + 0.5);
+    }
+    content.stroke();
+
+    content.finish().into_vec()
+}
+
+/// The boxes `[x0, y0, x1, y1]` that a text markup annotation decorates, in
+/// appearance space.
+///
+/// There is one box per quad, or a single box for the annotation rect if
+/// there are no quad points.
+fn markup_boxes(rect: &[f32; 4], quad_points: &[f32]) -> Vec<[f32; 4]> {
+    if quad_points.len() < 8 {
+        return vec![[0.0, 0.0, rect[2] - rect[0], rect[3] - rect[1]]];
+    }
+
+    quad_points
+        .chunks_exact(8)
+        .map(|quad| {
+            let [x0, y0, x1, y1] = quad_bounds(quad);
+            [x0 - rect[0], y0 - rect[1], x1 - rect[0], y1 - rect[1]]
+        })
+        .collect()
+}
+
+/// Generate the appearance stream for a redaction annotation that hasn't
+/// been applied yet.
+///
+/// Outlines each redacted region, or the annotation rect if there are no quad
+/// points.
+pub fn generate_redact_appearance(annot: &RedactAnnot) -> Vec<u8> {
+    let color = annot.base.color.unwrap_or(AnnotColor::red());
     let rect = &annot.base.rect;
-    let width = rect[2] - rect[0];
+    let (origin_x, origin_y) = (rect[0], rect[1]);
 
     let mut content = Content::new();
-    content
-        .set_stroke_rgb(color.r, color.g, color.b)
-        .set_line_width(1.0)
-        .move_to(0.0, 0.5)
-        .line_to(width, 0.5)
-        .stroke();
+    content.set_stroke_annot_color(color).set_line_width(1.0);
+
+    if annot.quad_points.len() < 8 {
+        content.rect(0.5, 0.5, rect[2] - rect[0] - 1.0, rect[3] - rect[1] - 1.0);
+    } else {
+        for quad in annot.quad_points.chunks_exact(8) {
+            let [x0, y0, x1, y1] = quad_bounds(quad);
+            content.rect(x0 - origin_x, y0 - origin_y, x1 - x0, y1 - y0);
+        }
+    }
+    content.stroke();
 
     content.finish().into_vec()
 }
 
+/// The bounding box `[x0, y0, x1, y1]` of a quadrilateral given as 8 floats.
+pub(crate) fn quad_bounds(quad: &[f32]) -> [f32; 4] {
+    let xs = [quad[0], quad[2], quad[4], quad[6]];
+    let ys = [quad[1], quad[3], quad[5], quad[7]];
+
+    [
+        xs.into_iter().fold(f32::INFINITY, f32::min),
+        ys.into_iter().fold(f32::INFINITY, f32::min),
+        xs.into_iter().fold(f32::NEG_INFINITY, f32::max),
+        ys.into_iter().fold(f32::NEG_INFINITY, f32::max),
+    ]
+}
+
 /// Generate the appearance stream for a strikeout annotation.
 ///
-/// Draws a colored line through the middle of the annotation rect.
+/// Draws a colored line through the middle of each quad, or of the
+/// annotation rect if there are no quad points.
 pub fn generate_strikeout_appearance(annot: &StrikeOutAnnot) -> Vec<u8> {
     let color = annot.base.color.unwrap_or(AnnotColor::red());
-    let rect = &annot.base.rect;
-    let width = rect[2] - rect[0];
-    let height = rect[3] - rect[1];
-    let mid_y = height / 2.0;
 
     let mut content = Content::new();
-    content
-        .set_stroke_rgb(color.r, color.g, color.b)
-        .set_line_width(1.0)
-        .move_to(0.0, mid_y)
-        .line_to(width, mid_y)
-        .stroke();
+    content.set_stroke_annot_color(color).set_line_width(1.0);
+    for [x0, y0, x1, y1] in markup_boxes(&annot.base.rect, &annot.quad_points) {
+        let mid_y = (y0 + y1) / 2.0;
+        content.move_to(x0, mid_y).line_to(x1, mid_y);
+    }
+    content.stroke();
 
     content.finish().into_vec()
 }
 
 /// Generate the appearance stream for a squiggly underline annotation.
 ///
-/// Draws a wavy colored line at the bottom of the annotation rect.
+/// Draws a wavy colored line at the bottom of each quad, or of the annotation
+/// rect if there are no quad points.
 pub fn generate_squiggly_appearance(annot: &SquigglyAnnot) -> Vec<u8> {
     let color = annot.base.color.unwrap_or(AnnotColor::red());
-    let rect = &annot.base.rect;
-    let width = rect[2] - rect[0];
 
     let mut content = Content::new();
-    content
-        .set_stroke_rgb(color.r, color.g, color.b)
-        .set_line_width(0.5);
+    content.set_stroke_annot_color(color).set_line_width(0.5);
 
     // Draw a wavy line using small segments
     let wave_height = 1.5_f32;
     let wave_length = 4.0_f32;
-    let num_waves = (width / wave_length).ceil() as i32;
 
-    content.move_to(0.0, 1.0);
-    for i in 0..num_waves {
-        let x_start = i as f32 * wave_length;
-        let x_mid = x_start + wave_length / 2.0;
-        let x_end = (x_start + wave_length).min(width);
-        content.cubic_to(
-            x_mid,
-            1.0 + wave_height,
-            x_mid,
-            1.0 - wave_height,
-            x_end,
-            1.0,
-        );
+    for [x0, y0, x1, _] in markup_boxes(&annot.base.rect, &annot.quad_points) {
+        let y = y0 + 1.0;
+        let num_waves = ((x1 - x0) / wave_length).ceil() as i32;
+
+        content.move_to(x0, y);
+        for i in 0..num_waves {
+            let x_start = x0 + i as f32 * wave_length;
+            let x_mid = x_start + wave_length / 2.0;
+            let x_end = (x_start + wave_length).min(x1);
+            content.cubic_to(x_mid, y + wave_height, x_mid, y - wave_height, x_end, y);
+        }
     }
     content.stroke();
 
@@ -113,35 +254,148 @@ pub fn generate_ink_appearance(annot: &InkAnnot) -> Vec<u8> {
 
     let mut content = Content::new();
     content
-        .set_stroke_rgb(color.r, color.g, color.b)
+        .set_stroke_annot_color(color)
         .set_line_width(annot.line_width)
         .set_line_cap(pdf_writer::types::LineCapStyle::RoundCap)
         .set_line_join(pdf_writer::types::LineJoinStyle::RoundJoin);
+    if let Some(dash) = annot.dash.as_deref().and_then(valid_dash_pattern) {
+        content.set_dash_pattern(dash, 0.0);
+    }
 
     for path in &annot.ink_list {
-        if let Some(first) = path.first() {
-            content.move_to(first[0] - x_offset, first[1] - y_offset);
-            for point in path.iter().skip(1) {
-                content.line_to(point[0] - x_offset, point[1] - y_offset);
+        let points = path
+            .iter()
+            .map(|p| [p[0] - x_offset, p[1] - y_offset])
+            .collect::<Vec<_>>();
+        let Some(first) = points.first() else {
+            continue;
+        };
+
+        content.move_to(first[0], first[1]);
+        if points.len() < 3 || annot.smoothing <= 0.0 {
+            for point in &points[1..] {
+                content.line_to(point[0], point[1]);
             }
-            content.stroke();
+        } else {
+            draw_smooth_path(&mut content, &points, annot.smoothing);
         }
+        content.stroke();
     }
 
     content.finish().into_vec()
 }
 
+/// Continue the current path through `points` (after the first one) with a
+/// Catmull-Rom spline, converted into cubic Bézier segments.
+///
+/// `tension` scales the tangents, so that 1 yields the standard spline. The
+/// spline passes exactly through every point, including the endpoints.
+fn draw_smooth_path(content: &mut Content, points: &[[f32; 2]], tension: f32) {
+    let last = points.len() - 1;
+    let k = tension / 6.0;
+
+    for i in 0..last {
+        // The endpoints are repeated so that the spline starts and ends there.
+        let p0 = points[i.saturating_sub(1)];
+        let p1 = points[i];
+        let p2 = points[i + 1];
+        let p3 = points[(i + 2).min(last)];
+
+        content.cubic_to(
+            p1[0] + (p2[0] - p0[0]) * k,
+            p1[1] + (p2[1] - p0[1]) * k,
+            p2[0] - (p3[0] - p1[0]) * k,
+            p2[1] - (p3[1] - p1[1]) * k,
+            p2[0],
+            p2[1],
+        );
+    }
+}
+
+/// The font size and text color set by a default appearance (`/DA`) string
+/// like `0 0 0 rg /Helv 12 Tf`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct DefaultAppearance {
+    /// The font size of the last `Tf` operator.
+    pub(crate) font_size: Option<f32>,
+    /// The fill color of the last `g`, `rg` or `k` operator.
+    pub(crate) color: Option<AnnotColor>,
+}
+
+impl DefaultAppearance {
+    /// Parse a default appearance string, ignoring operators it doesn't set.
+    pub(crate) fn parse(da: &str) -> Self {
+        let mut parsed = Self::default();
+        let mut operands = Vec::new();
+
+        for token in da.split_whitespace() {
+            if let Ok(number) = token.parse::<f32>() {
+                operands.push(number);
+                continue;
+            }
+
+            match (token, operands.as_slice()) {
+                ("Tf", [.., size]) if *size > 0.0 => parsed.font_size = Some(*size),
+                ("g", [.., g]) => parsed.color = Some(AnnotColor::gray(*g)),
+                ("rg", [.., r, g, b]) => parsed.color = Some(AnnotColor::new(*r, *g, *b)),
+                ("k", [.., c, m, y, k]) => parsed.color = Some(AnnotColor::cmyk(*c, *m, *y, *k)),
+                _ => {}
+            }
+
+            // Names, such as the font resource, are operands too.
+            if !token.starts_with('/') {
+                operands.clear();
+            }
+        }
+
+        parsed
+    }
+}
+
 /// Generate the appearance stream for a free text annotation.
 ///
-/// Draws a white background with optional border, then renders text.
+/// Draws a white background with optional border, then renders text. The
+/// font size and text color are taken from the default appearance string,
+/// falling back to `font_size` and the annotation color.
 pub fn generate_freetext_appearance(annot: &FreeTextAnnot) -> Vec<u8> {
     let rect = &annot.base.rect;
     let width = rect[2] - rect[0];
     let height = rect[3] - rect[1];
-    let color = annot.base.color.unwrap_or(AnnotColor::black());
+    let da = DefaultAppearance::parse(&annot.default_appearance);
+    let color = da.color.or(annot.base.color).unwrap_or(AnnotColor::black());
 
     let mut content = Content::new();
 
+    // The appearance covers the callout line as well, so draw it relative to
+    // the grown rect and then move the origin to the text box.
+    if let Some(callout) = callout_line(annot) {
+        let outer = freetext_rect(annot);
+        content
+            .set_stroke_rgb(0.0, 0.0, 0.0)
+            .set_line_width(CALLOUT_LINE_WIDTH);
+        content.move_to(callout[0][0] - outer[0], callout[0][1] - outer[1]);
+        for point in &callout[1..] {
+            content.line_to(point[0] - outer[0], point[1] - outer[1]);
+        }
+        content.stroke();
+
+        let tip = [callout[0][0] - outer[0], callout[0][1] - outer[1]];
+        let dx = callout[0][0] - callout[1][0];
+        let dy = callout[0][1] - callout[1][1];
+        let len = dx.hypot(dy);
+        if len > 0.0 {
+            draw_line_ending(
+                &mut content,
+                CALLOUT_LINE_ENDING,
+                tip,
+                [dx / len, dy / len],
+                line_ending_size(CALLOUT_LINE_WIDTH),
+            );
+        }
+
+        content.transform([1.0, 0.0, 0.0, 1.0, rect[0] - outer[0], rect[1] - outer[1]]);
+    }
+
     // Draw white background
     content
         .set_fill_rgb(1.0, 1.0, 1.0)
@@ -156,20 +410,149 @@ pub fn generate_freetext_appearance(annot: &FreeTextAnnot) -> Vec<u8> {
         .stroke();
 
     // Draw text
-    let font_size = annot.font_size;
+    let font_size = da.font_size.unwrap_or(annot.font_size);
     let margin = 2.0_f32;
-    let text_y = height - font_size - margin;
+
+    let embedded = annot
+        .font_data
+        .as_ref()
+        .and_then(|data| EmbeddedFont::new((**data).as_ref()));
+    let font_name = match &embedded {
+        Some(_) => EMBEDDED_FONT_NAME,
+        None => b"Helv".as_slice(),
+    };
+    let encode = |text: &str| match &embedded {
+        Some(font) => font.encode(text),
+        None => encode_win_ansi(text),
+    };
+
+    let lines = if annot.multiline {
+        let max_width = (width - 2.0 * margin) * 1000.0 / font_size.max(f32::EPSILON);
+        match &embedded {
+            Some(font) => wrap_text(&annot.text, max_width, |t| font.text_width(t)),
+            None => wrap_text(&annot.text, max_width, helvetica_text_width),
+        }
+    } else {
+        vec![annot.text.clone()]
+    };
+
+    // The block spans from the top of the first line, one font size above its
+    // baseline, to the baseline of the last line.
+    let leading = font_size * annot.line_spacing;
+    let block_height = font_size + leading * lines.len().saturating_sub(1) as f32;
+    let text_y = match annot.vertical_align {
+        VAlign::Top => height - font_size - margin,
+        VAlign::Middle => (height + block_height) / 2.0 - font_size,
+        VAlign::Bottom => margin + block_height - font_size,
+    };
 
     content.begin_text();
-    content.set_font(pdf_writer::Name(b"Helv"), font_size);
-    content.set_fill_rgb(color.r, color.g, color.b);
+    content.set_font(pdf_writer::Name(font_name), font_size);
+    content.set_fill_annot_color(color);
     content.next_line(margin, text_y);
-    content.show(pdf_writer::Str(annot.text.as_bytes()));
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            content.next_line(0.0, -leading);
+        }
+        content.show(pdf_writer::Str(&encode(line)));
+    }
     content.end_text();
 
     content.finish().into_vec()
 }
 
+/// The callout line of a free text annotation, if it has at least two points.
+fn callout_line(annot: &FreeTextAnnot) -> Option<&[[f32; 2]]> {
+    annot
+        .callout_line
+        .as_deref()
+        .filter(|callout| callout.len() >= 2)
+}
+
+/// The rect of a free text annotation, grown to include its callout line and
+/// the arrowhead at its target.
+pub(crate) fn freetext_rect(annot: &FreeTextAnnot) -> [f32; 4] {
+    let mut rect = annot.base.rect;
+    let callout = callout_line(annot).unwrap_or_default();
+    for (i, point) in callout.iter().enumerate() {
+        let margin = if i == 0 {
+            line_ending_size(CALLOUT_LINE_WIDTH)
+        } else {
+            CALLOUT_LINE_WIDTH
+        };
+        rect[0] = rect[0].min(point[0] - margin);
+        rect[1] = rect[1].min(point[1] - margin);
+        rect[2] = rect[2].max(point[0] + margin);
+        rect[3] = rect[3].max(point[1] + margin);
+    }
+
+    rect
+}
+
+/// The rect covered by the appearance of an annotation.
+///
+/// This is the rect of the annotation, except for free text annotations with
+/// a callout line, whose appearance extends beyond the text box.
+pub(crate) fn annotation_rect(annot: &Annotation) -> [f32; 4] {
+    let annot = normalized(annot);
+    match annot.as_ref() {
+        Annotation::FreeText(free_text) => freetext_rect(free_text),
+        annot => annot.base().rect,
+    }
+}
+
+/// The rotation of an annotation's appearance, if it has one.
+///
+/// Returns the `/Matrix` that rotates the appearance's bbox `[0, 0, width,
+/// height]` around its center and moves the result to the origin, and the rect
+/// the rotated appearance covers on the page.
+pub(crate) fn appearance_rotation(annot: &Annotation) -> Option<([f32; 6], [f32; 4])> {
+    let degrees = annot.base().rotation;
+    if !degrees.is_finite() || degrees.rem_euclid(360.0) == 0.0 {
+        return None;
+    }
+
+    let rect = annotation_rect(annot);
+    let (width, height) = (rect[2] - rect[0], rect[3] - rect[1]);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let rotated_width = width * cos.abs() + height * sin.abs();
+    let rotated_height = width * sin.abs() + height * cos.abs();
+
+    let matrix = [
+        cos,
+        sin,
+        -sin,
+        cos,
+        (rotated_width - cos * width + sin * height) / 2.0,
+        (rotated_height - sin * width - cos * height) / 2.0,
+    ];
+    let (cx, cy) = ((rect[0] + rect[2]) / 2.0, (rect[1] + rect[3]) / 2.0);
+    let bounds = [
+        cx - rotated_width / 2.0,
+        cy - rotated_height / 2.0,
+        cx + rotated_width / 2.0,
+        cy + rotated_height / 2.0,
+    ];
+
+    Some((matrix, bounds))
+}
+
+/// The annotation with its rect normalized, so that its lower-left corner
+/// comes first.
+///
+/// The appearance generators assume a normalized rect, and would otherwise
+/// compute a negative width or height.
+fn normalized(annot: &Annotation) -> Cow<'_, Annotation> {
+    let [x0, y0, x1, y1] = annot.base().rect;
+    if x0 <= x1 && y0 <= y1 {
+        return Cow::Borrowed(annot);
+    }
+
+    let mut annot = annot.clone();
+    annot.base_mut().rect = [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)];
+    Cow::Owned(annot)
+}
+
 /// Generate the appearance stream for a square (rectangle) annotation.
 pub fn generate_square_appearance(annot: &ShapeAnnot) -> Vec<u8> {
     let rect = &annot.base.rect;
@@ -178,39 +561,83 @@ pub fn generate_square_appearance(annot: &ShapeAnnot) -> Vec<u8> {
     let color = annot.base.color.unwrap_or(AnnotColor::black());
     let half_lw = annot.line_width / 2.0;
 
+    let cloud = match annot.border_style {
+        BorderStyle::Cloudy(intensity) => {
+            let radius = cloud_radius(intensity, annot.line_width);
+            let inset = half_lw + radius;
+            Some(rect_cloud_vertices(
+                [
+                    inset,
+                    inset,
+                    (width - inset).max(inset),
+                    (height - inset).max(inset),
+                ],
+                radius,
+            ))
+        }
+        _ => None,
+    };
+    let border_rect = [
+        half_lw,
+        half_lw,
+        width - annot.line_width,
+        height - annot.line_width,
+    ];
+    let draw_border = |content: &mut Content| match &cloud {
+        Some(vertices) => draw_cloud(content, vertices),
+        None if annot.corner_radius > 0.0 => {
+            draw_rounded_rect(content, border_rect, annot.corner_radius);
+        }
+        None => {
+            let [x, y, w, h] = border_rect;
+            content.rect(x, y, w, h);
+        }
+    };
+
     let mut content = Content::new();
 
     // Fill interior if color specified
     if let Some(ic) = &annot.interior_color {
-        content
-            .set_fill_rgb(ic.r, ic.g, ic.b)
-            .rect(
-                half_lw,
-                half_lw,
-                width - annot.line_width,
-                height - annot.line_width,
-            )
-            .fill_nonzero();
+        content.set_fill_annot_color(*ic);
+        draw_border(&mut content);
+        content.fill_nonzero();
     }
 
     // Stroke border
     content
-        .set_stroke_rgb(color.r, color.g, color.b)
-        .set_line_width(annot.line_width)
-        .rect(
-            half_lw,
-            half_lw,
-            width - annot.line_width,
-            height - annot.line_width,
-        )
-        .stroke();
+        .set_stroke_annot_color(color)
+        .set_line_width(annot.line_width);
+    apply_dash_pattern(&mut content, &annot.border_style);
+    draw_border(&mut content);
+    content.stroke();
 
     content.finish().into_vec()
 }
 
+/// Draw a rectangle given as `[x, y, width, height]` with its corners rounded
+/// by quarter circles of `radius`, clamped to half the smaller side.
+fn draw_rounded_rect(content: &mut Content, rect: [f32; 4], radius: f32) {
+    let [x, y, w, h] = rect;
+    let r = radius.min(w.abs() / 2.0).min(h.abs() / 2.0);
+    let k = r * 0.5523;
+    let (x1, y1) = (x + w, y + h);
+
+    content.move_to(x + r, y);
+    content.line_to(x1 - r, y);
+    content.cubic_to(x1 - r + k, y, x1, y + r - k, x1, y + r);
+    content.line_to(x1, y1 - r);
+    content.cubic_to(x1, y1 - r + k, x1 - r + k, y1, x1 - r, y1);
+    content.line_to(x + r, y1);
+    content.cubic_to(x + r - k, y1, x, y1 - r + k, x, y1 - r);
+    content.line_to(x, y + r);
+    content.cubic_to(x, y + r - k, x + r - k, y, x + r, y);
+    content.close_path();
+}
+
 /// Generate the appearance stream for a circle (ellipse) annotation.
 ///
-/// Approximates an ellipse using four cubic Bézier curves.
+/// Approximates an ellipse using four cubic Bézier curves, or eight for
+/// ellipses large enough for the error of four curves to become visible.
 pub fn generate_circle_appearance(annot: &ShapeAnnot) -> Vec<u8> {
     let rect = &annot.base.rect;
     let width = rect[2] - rect[0];
@@ -221,151 +648,681 @@ pub fn generate_circle_appearance(annot: &ShapeAnnot) -> Vec<u8> {
     let cy = height / 2.0;
     let rx = (width - annot.line_width) / 2.0;
     let ry = (height - annot.line_width) / 2.0;
-
-    // Magic number for Bézier circle approximation: 4/3 * (sqrt(2) - 1) ≈ 0.5523
-    let kappa = 0.5523_f32;
-    let kx = rx * kappa;
-    let ky = ry * kappa;
-
-    let mut content = Content::new();
-
+    let curves = ellipse_curves([cx, cy], rx, ry, ellipse_segment_count(rx, ry));
+
+    let cloud = match annot.border_style {
+        BorderStyle::Cloudy(intensity) => {
+            let radius = cloud_radius(intensity, annot.line_width);
+            Some(ellipse_cloud_vertices(
+                [cx, cy],
+                (rx - radius).max(0.0),
+                (ry - radius).max(0.0),
+                radius,
+            ))
+        }
+        _ => None,
+    };
     let draw_ellipse = |content: &mut Content| {
-        content.move_to(cx + rx, cy);
-        content.cubic_to(cx + rx, cy + ky, cx + kx, cy + ry, cx, cy + ry);
-        content.cubic_to(cx - kx, cy + ry, cx - rx, cy + ky, cx - rx, cy);
-        content.cubic_to(cx - rx, cy - ky, cx - kx, cy - ry, cx, cy - ry);
-        content.cubic_to(cx + kx, cy - ry, cx + rx, cy - ky, cx + rx, cy);
+        if let Some(vertices) = &cloud {
+            draw_cloud(content, vertices);
+            return;
+        }
+
+        content.move_to(curves[0][0][0], curves[0][0][1]);
+        for [_, p1, p2, p3] in &curves {
+            content.cubic_to(p1[0], p1[1], p2[0], p2[1], p3[0], p3[1]);
+        }
         content.close_path();
     };
 
+    let mut content = Content::new();
+
     // Fill interior if color specified
     if let Some(ic) = &annot.interior_color {
-        content.set_fill_rgb(ic.r, ic.g, ic.b);
+        content.set_fill_annot_color(*ic);
         draw_ellipse(&mut content);
         content.fill_nonzero();
     }
 
     // Stroke border
     content
-        .set_stroke_rgb(color.r, color.g, color.b)
+        .set_stroke_annot_color(color)
         .set_line_width(annot.line_width);
+    apply_dash_pattern(&mut content, &annot.border_style);
     draw_ellipse(&mut content);
     content.stroke();
 
     content.finish().into_vec()
 }
 
-/// Generate the appearance stream for a line annotation.
-pub fn generate_line_appearance(annot: &LineAnnot) -> Vec<u8> {
-    let color = annot.base.color.unwrap_or(AnnotColor::black());
-    let rect = &annot.base.rect;
-    let x_off = rect[0];
-    let y_off = rect[1];
-
-    let mut content = Content::new();
-    content
-        .set_stroke_rgb(color.r, color.g, color.b)
-        .set_line_width(annot.line_width)
-        .set_line_cap(pdf_writer::types::LineCapStyle::RoundCap)
-        .move_to(annot.start[0] - x_off, annot.start[1] - y_off)
-        .line_to(annot.end[0] - x_off, annot.end[1] - y_off)
-        .stroke();
+/// The dash pattern with invalid lengths removed, or `None` if it doesn't have
+/// any positive length and should be drawn solid.
+pub(crate) fn valid_dash_pattern(dashes: &[f32]) -> Option<Vec<f32>> {
+    let dashes = dashes
+        .iter()
+        .copied()
+        .filter(|d| d.is_finite() && *d >= 0.0)
+        .collect::<Vec<_>>();
 
-    content.finish().into_vec()
+    dashes.iter().any(|d| *d > 0.0).then_some(dashes)
 }
 
-/// Generate the appearance stream for a text (sticky note) annotation.
+/// The radius above which ellipses are drawn with eight instead of four
+/// curves, since the radial error of four curves (about 0.027%) reaches a
+/// tenth of a point.
+const FINE_ELLIPSE_RADIUS: f32 = 360.0;
+
+/// The number of Bézier curves to approximate an ellipse with.
 ///
-/// Draws a simple note icon — a small yellow square with a folded corner.
-pub fn generate_text_appearance(_annot: &TextAnnot) -> Vec<u8> {
-    let size = 24.0_f32;
+/// An ellipse is a scaled circle, so the relative error of the approximation
+/// doesn't depend on its aspect ratio; only the absolute error grows with the
+/// larger radius.
+fn ellipse_segment_count(rx: f32, ry: f32) -> usize {
+    if rx.max(ry) > FINE_ELLIPSE_RADIUS {
+        8
+    } else {
+        4
+    }
+}
 
-    let mut content = Content::new();
+/// Approximate an ellipse with `segments` cubic Bézier curves, returned as
+/// their start, control and end points, counterclockwise from the positive x
+/// axis.
+fn ellipse_curves(center: [f32; 2], rx: f32, ry: f32, segments: usize) -> Vec<[[f32; 2]; 4]> {
+    let step = std::f32::consts::TAU / segments as f32;
+    // The distance of the control points along the tangent, relative to the
+    // radius. For four segments, this is the familiar 0.5523.
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    let point = |angle: f32| [center[0] + rx * angle.cos(), center[1] + ry * angle.sin()];
+    let tangent = |angle: f32| [-rx * angle.sin() * k, ry * angle.cos() * k];
+
+    (0..segments)
+        .map(|i| {
+            let (a0, a1) = (i as f32 * step, (i + 1) as f32 * step);
+            let (p0, p3) = (point(a0), point(a1));
+            let (t0, t1) = (tangent(a0), tangent(a1));
+            [
+                p0,
+                [p0[0] + t0[0], p0[1] + t0[1]],
+                [p3[0] - t1[0], p3[1] - t1[1]],
+                p3,
+            ]
+        })
+        .collect()
+}
 
-    // Yellow background
-    content
-        .set_fill_rgb(1.0, 1.0, 0.8)
-        .rect(0.5, 0.5, size - 1.0, size - 1.0)
-        .fill_nonzero();
+/// Set the dash pattern of a dashed border style.
+fn apply_dash_pattern(content: &mut Content, style: &BorderStyle) {
+    if let BorderStyle::Dashed(dashes) = style
+        && let Some(dashes) = valid_dash_pattern(dashes)
+    {
+        content.set_dash_pattern(dashes, 0.0);
+    }
+}
 
-    // Border
-    content
-        .set_stroke_rgb(0.5, 0.5, 0.0)
-        .set_line_width(0.5)
-        .rect(0.5, 0.5, size - 1.0, size - 1.0)
-        .stroke();
+/// The radius of the scallops of a cloudy border.
+fn cloud_radius(intensity: f32, line_width: f32) -> f32 {
+    (4.0 * intensity).max(line_width)
+}
 
-    // Folded corner triangle
-    let fold = 5.0_f32;
-    content
-        .set_fill_rgb(0.9, 0.9, 0.7)
-        .move_to(size - fold - 0.5, size - 0.5)
-        .line_to(size - 0.5, size - 0.5)
-        .line_to(size - 0.5, size - fold - 0.5)
-        .close_path()
-        .fill_nonzero();
+/// Split the outline of a rectangle into counterclockwise cloud vertices that
+/// are at most `2 * radius` apart.
+fn rect_cloud_vertices(rect: [f32; 4], radius: f32) -> Vec<[f32; 2]> {
+    let corners = [
+        [rect[0], rect[1]],
+        [rect[2], rect[1]],
+        [rect[2], rect[3]],
+        [rect[0], rect[3]],
+    ];
+
+    let mut vertices = Vec::new();
+    for (idx, start) in corners.iter().enumerate() {
+        let end = corners[(idx + 1) % corners.len()];
+        let len = (end[0] - start[0]).hypot(end[1] - start[1]);
+        let segments = (len / (2.0 * radius)).ceil().max(1.0) as usize;
+
+        for k in 0..segments {
+            let t = k as f32 / segments as f32;
+            vertices.push([
+                start[0] + (end[0] - start[0]) * t,
+                start[1] + (end[1] - start[1]) * t,
+            ]);
+        }
+    }
 
-    // Fold line
-    content
-        .set_stroke_rgb(0.5, 0.5, 0.0)
-        .move_to(size - fold - 0.5, size - 0.5)
-        .line_to(size - fold - 0.5, size - fold - 0.5)
-        .line_to(size - 0.5, size - fold - 0.5)
-        .stroke();
+    vertices
+}
 
-    content.finish().into_vec()
+/// Place counterclockwise cloud vertices on an ellipse, roughly `2 * radius` apart.
+fn ellipse_cloud_vertices(center: [f32; 2], rx: f32, ry: f32, radius: f32) -> Vec<[f32; 2]> {
+    // Ramanujan's approximation of the ellipse perimeter.
+    let perimeter =
+        std::f32::consts::PI * (3.0 * (rx + ry) - ((3.0 * rx + ry) * (rx + 3.0 * ry)).sqrt());
+    let count = (perimeter / (2.0 * radius)).ceil().max(4.0) as usize;
+
+    (0..count)
+        .map(|k| {
+            let angle = std::f32::consts::TAU * k as f32 / count as f32;
+            [center[0] + rx * angle.cos(), center[1] + ry * angle.sin()]
+        })
+        .collect()
 }
 
-/// Generate appearance for a text form field widget.
-pub fn generate_text_field_appearance(annot: &TextFieldAnnot) -> Vec<u8> {
+/// Draw a closed cloud outline with a semicircular scallop bulging outwards
+/// between each pair of consecutive counterclockwise vertices.
+fn draw_cloud(content: &mut Content, vertices: &[[f32; 2]]) {
+    let Some(first) = vertices.first() else {
+        return;
+    };
+    content.move_to(first[0], first[1]);
+
+    for (idx, p0) in vertices.iter().enumerate() {
+        let p1 = vertices[(idx + 1) % vertices.len()];
+        let len = (p1[0] - p0[0]).hypot(p1[1] - p0[1]);
+        if len <= f32::EPSILON {
+            continue;
+        }
+
+        // Direction of the chord and its outward normal.
+        let (ux, uy) = ((p1[0] - p0[0]) / len, (p1[1] - p0[1]) / len);
+        let (nx, ny) = (uy, -ux);
+        let r = len / 2.0;
+        let k = r * 0.5523;
+        let apex = [
+            (p0[0] + p1[0]) / 2.0 + nx * r,
+            (p0[1] + p1[1]) / 2.0 + ny * r,
+        ];
+
+        content.cubic_to(
+            p0[0] + nx * k,
+            p0[1] + ny * k,
+            apex[0] - ux * k,
+            apex[1] - uy * k,
+            apex[0],
+            apex[1],
+        );
+        content.cubic_to(
+            apex[0] + ux * k,
+            apex[1] + uy * k,
+            p1[0] + nx * k,
+            p1[1] + ny * k,
+            p1[0],
+            p1[1],
+        );
+    }
+
+    content.close_path();
+}
+
+/// Generate the appearance stream for a line annotation.
+///
+/// Line endings are drawn at each endpoint, oriented along the line direction.
+/// With a measurement scale, the length of the line is displayed above its
+/// midpoint.
+pub fn generate_line_appearance(annot: &LineAnnot) -> Vec<u8> {
+    let color = annot.base.color.unwrap_or(AnnotColor::black());
     let rect = &annot.base.rect;
-    let width = (rect[2] - rect[0]).max(1.0);
-    let height = (rect[3] - rect[1]).max(1.0);
-    let text = annot.value.as_deref().unwrap_or_default();
+    let x_off = rect[0];
+    let y_off = rect[1];
+
+    let start = [annot.start[0] - x_off, annot.start[1] - y_off];
+    let end = [annot.end[0] - x_off, annot.end[1] - y_off];
+
+    let dash = annot.dash.as_deref().and_then(valid_dash_pattern);
 
     let mut content = Content::new();
     content
-        .set_fill_rgb(1.0, 1.0, 1.0)
-        .rect(0.0, 0.0, width, height)
-        .fill_nonzero()
-        .set_stroke_rgb(0.2, 0.2, 0.2)
-        .set_line_width(1.0)
-        .rect(0.5, 0.5, width - 1.0, height - 1.0)
+        .set_stroke_annot_color(color)
+        .set_line_width(annot.line_width)
+        .set_line_cap(pdf_writer::types::LineCapStyle::RoundCap);
+    if let Some(dash) = &dash {
+        content.set_dash_pattern(dash.iter().copied(), 0.0);
+    }
+    content
+        .move_to(start[0], start[1])
+        .line_to(end[0], end[1])
         .stroke();
+    if dash.is_some() {
+        content.set_dash_pattern([], 0.0);
+    }
 
-    if !text.is_empty() {
+    let dx = end[0] - start[0];
+    let dy = end[1] - start[1];
+    let len = (dx * dx + dy * dy).sqrt();
+    if len > 0.0 && (annot.start_style != LineEnding::None || annot.end_style != LineEnding::None) {
+        let dir = [dx / len, dy / len];
+        let size = line_ending_size(annot.line_width);
+
+        content
+            .set_fill_annot_color(color)
+            .set_line_join(pdf_writer::types::LineJoinStyle::MiterJoin);
+        draw_line_ending(
+            &mut content,
+            annot.start_style,
+            start,
+            [-dir[0], -dir[1]],
+            size,
+        );
+        draw_line_ending(&mut content, annot.end_style, end, dir, size);
+    }
+
+    if let Some((label, bbox)) = measure_label(annot) {
         content.begin_text();
-        content.set_font(pdf_writer::Name(b"Helv"), 10.0);
-        content.set_fill_rgb(0.0, 0.0, 0.0);
-        content.next_line(3.0, (height - 12.0).max(2.0));
-        content.show(pdf_writer::Str(text.as_bytes()));
+        content.set_font(Name(b"Helv"), MEASURE_FONT_SIZE);
+        content.set_fill_annot_color(color);
+        // Leave room for descenders below the baseline.
+        content.next_line(bbox[0] - x_off, bbox[1] - y_off + MEASURE_FONT_SIZE * 0.25);
+        content.show(pdf_writer::Str(&encode_win_ansi(&label)));
         content.end_text();
     }
 
     content.finish().into_vec()
 }
 
-/// Generate appearance for a signature form field widget.
-pub fn generate_signature_field_appearance(annot: &SignatureFieldAnnot) -> Vec<u8> {
-    let rect = &annot.base.rect;
-    let width = (rect[2] - rect[0]).max(1.0);
-    let height = (rect[3] - rect[1]).max(1.0);
+/// The length label of a line with a measurement scale, and its box
+/// `[x0, y0, x1, y1]` in PDF coordinates, centered above the line midpoint.
+pub(crate) fn measure_label(annot: &LineAnnot) -> Option<(String, [f32; 4])> {
+    let measure = annot.measure.as_ref()?;
+    let length = (annot.end[0] - annot.start[0]).hypot(annot.end[1] - annot.start[1]);
+    let label = measure.format_length(length);
+    let width = helvetica_text_width(&label) * MEASURE_FONT_SIZE / 1000.0;
+
+    let mid_x = (annot.start[0] + annot.end[0]) / 2.0;
+    let mid_y = (annot.start[1] + annot.end[1]) / 2.0;
+    let y0 = mid_y + annot.line_width / 2.0 + 1.0;
+
+    Some((
+        label,
+        [
+            mid_x - width / 2.0,
+            y0,
+            mid_x + width / 2.0,
+            y0 + MEASURE_FONT_SIZE * 1.2,
+        ],
+    ))
+}
 
-    let mut content = Content::new();
-    content
-        .set_fill_rgb(1.0, 1.0, 1.0)
-        .rect(0.0, 0.0, width, height)
-        .fill_nonzero()
-        .set_stroke_rgb(0.1, 0.2, 0.5)
-        .set_line_width(1.2)
-        .rect(0.6, 0.6, width - 1.2, height - 1.2)
-        .stroke();
+/// The length of a line ending for the given line width.
+pub(crate) fn line_ending_size(line_width: f32) -> f32 {
+    (line_width * 4.0).max(4.0)
+}
 
-    // Signature line.
-    let line_y = (height * 0.35).max(8.0).min(height - 4.0);
-    content
-        .set_stroke_rgb(0.3, 0.3, 0.3)
-        .set_line_width(0.8)
+/// Draw a line ending at `tip`, where `dir` is the unit vector pointing
+/// away from the line.
+fn draw_line_ending(
+    content: &mut Content,
+    style: LineEnding,
+    tip: [f32; 2],
+    dir: [f32; 2],
+    size: f32,
+) {
+    let normal = [-dir[1], dir[0]];
+    let half = size / 2.0;
+    // A point `along` units in the line direction and `across` units to its left.
+    let at = |along: f32, across: f32| {
+        [
+            tip[0] + dir[0] * along + normal[0] * across,
+            tip[1] + dir[1] * along + normal[1] * across,
+        ]
+    };
+
+    let polyline = |content: &mut Content, points: &[[f32; 2]], closed: bool| {
+        content.move_to(points[0][0], points[0][1]);
+        for p in &points[1..] {
+            content.line_to(p[0], p[1]);
+        }
+        if closed {
+            content.close_path();
+        }
+    };
+
+    match style {
+        LineEnding::None => {}
+        LineEnding::OpenArrow => {
+            polyline(content, &[at(-size, half), tip, at(-size, -half)], false);
+            content.stroke();
+        }
+        LineEnding::ClosedArrow => {
+            polyline(content, &[at(-size, half), tip, at(-size, -half)], true);
+            content.fill_nonzero_and_stroke();
+        }
+        LineEnding::ROpenArrow => {
+            polyline(content, &[at(size, half), tip, at(size, -half)], false);
+            content.stroke();
+        }
+        LineEnding::RClosedArrow => {
+            polyline(content, &[at(size, half), tip, at(size, -half)], true);
+            content.fill_nonzero_and_stroke();
+        }
+        LineEnding::Square => {
+            polyline(
+                content,
+                &[
+                    at(half, half),
+                    at(-half, half),
+                    at(-half, -half),
+                    at(half, -half),
+                ],
+                true,
+            );
+            content.stroke();
+        }
+        LineEnding::Diamond => {
+            polyline(
+                content,
+                &[at(half, 0.0), at(0.0, half), at(-half, 0.0), at(0.0, -half)],
+                true,
+            );
+            content.stroke();
+        }
+        LineEnding::Circle => {
+            let (cx, cy, r) = (tip[0], tip[1], half);
+            let k = r * 0.5523;
+            content.move_to(cx + r, cy);
+            content.cubic_to(cx + r, cy + k, cx + k, cy + r, cx, cy + r);
+            content.cubic_to(cx - k, cy + r, cx - r, cy + k, cx - r, cy);
+            content.cubic_to(cx - r, cy - k, cx - k, cy - r, cx, cy - r);
+            content.cubic_to(cx + k, cy - r, cx + r, cy - k, cx + r, cy);
+            content.close_path();
+            content.stroke();
+        }
+        LineEnding::Butt => {
+            polyline(content, &[at(0.0, half), at(0.0, -half)], false);
+            content.stroke();
+        }
+        LineEnding::Slash => {
+            // Slanted 30 degrees from the perpendicular.
+            let (along, across) = (half * 0.5, half * 0.866);
+            polyline(content, &[at(along, across), at(-along, -across)], false);
+            content.stroke();
+        }
+    }
+}
+
+/// Generate the appearance stream for a polygon or polyline annotation.
+///
+/// Polygons are closed and filled with the interior color, if any. Returns an
+/// empty stream if there are fewer than two vertices.
+pub fn generate_poly_appearance(annot: &PolyAnnot, closed: bool) -> Vec<u8> {
+    if annot.vertices.len() < 2 {
+        return Vec::new();
+    }
+
+    let color = annot.base.color.unwrap_or(AnnotColor::black());
+    let rect = &annot.base.rect;
+    let x_off = rect[0];
+    let y_off = rect[1];
+
+    let mut content = Content::new();
+    content
+        .set_stroke_annot_color(color)
+        .set_line_width(annot.line_width)
+        .set_line_join(pdf_writer::types::LineJoinStyle::RoundJoin);
+
+    let first = annot.vertices[0];
+    content.move_to(first[0] - x_off, first[1] - y_off);
+    for vertex in annot.vertices.iter().skip(1) {
+        content.line_to(vertex[0] - x_off, vertex[1] - y_off);
+    }
+
+    match (closed, &annot.interior_color) {
+        (true, Some(ic)) => {
+            content
+                .set_fill_annot_color(*ic)
+                .close_path()
+                .fill_nonzero_and_stroke();
+        }
+        (true, None) => {
+            content.close_path().stroke();
+        }
+        (false, _) => {
+            content.stroke();
+        }
+    }
+
+    content.finish().into_vec()
+}
+
+/// Generate the appearance stream for a text (sticky note) annotation.
+///
+/// The icon is drawn in the color of the annotation, or a light yellow by
+/// default. `Comment`, `Key`, `Help`, `Insert`, `Paragraph` and
+/// `NewParagraph` each have their own symbol; all other icons are drawn as a
+/// note with a folded corner.
+pub fn generate_text_appearance(annot: &TextAnnot) -> Vec<u8> {
+    let size = 24.0_f32;
+    let body = annot.base.color.unwrap_or(AnnotColor::new(1.0, 1.0, 0.8));
+    let ink = darken(body, 0.5);
+
+    let mut content = Content::new();
+    content
+        .set_fill_annot_color(body)
+        .set_stroke_annot_color(ink)
+        .set_line_width(0.5);
+
+    let background = |content: &mut Content| {
+        content
+            .rect(0.5, 0.5, size - 1.0, size - 1.0)
+            .fill_nonzero_and_stroke();
+        content
+            .set_line_width(1.5)
+            .set_line_cap(pdf_writer::types::LineCapStyle::RoundCap);
+    };
+
+    match annot.icon.as_str() {
+        "Comment" => {
+            // A speech bubble with a tail at the bottom left.
+            content
+                .move_to(1.5, 22.5)
+                .line_to(22.5, 22.5)
+                .line_to(22.5, 7.5)
+                .line_to(11.0, 7.5)
+                .line_to(5.0, 1.5)
+                .line_to(6.5, 7.5)
+                .line_to(1.5, 7.5)
+                .close_path()
+                .fill_nonzero_and_stroke();
+            content
+                .set_line_width(1.5)
+                .move_to(5.5, 18.5)
+                .line_to(18.5, 18.5)
+                .move_to(5.5, 15.0)
+                .line_to(18.5, 15.0)
+                .move_to(5.5, 11.5)
+                .line_to(14.0, 11.5)
+                .stroke();
+        }
+        "Key" => {
+            background(&mut content);
+            draw_circle(&mut content, 8.0, 14.0, 4.0);
+            content
+                .move_to(12.0, 14.0)
+                .line_to(20.0, 14.0)
+                .move_to(17.0, 14.0)
+                .line_to(17.0, 10.5)
+                .move_to(20.0, 14.0)
+                .line_to(20.0, 11.0)
+                .stroke();
+        }
+        "Help" => {
+            background(&mut content);
+            draw_circle(&mut content, 12.0, 12.0, 9.0);
+            content
+                .move_to(9.0, 15.0)
+                .cubic_to(9.0, 19.0, 15.0, 19.0, 15.0, 15.0)
+                .cubic_to(15.0, 12.5, 12.0, 12.5, 12.0, 10.0)
+                .stroke();
+            content
+                .set_fill_annot_color(ink)
+                .rect(11.0, 6.0, 2.0, 2.0)
+                .fill_nonzero();
+        }
+        "Insert" => {
+            // A caret pointing up to the insertion point.
+            content
+                .set_line_width(1.0)
+                .move_to(2.0, 3.0)
+                .line_to(12.0, 21.0)
+                .line_to(22.0, 3.0)
+                .line_to(17.0, 3.0)
+                .line_to(12.0, 12.0)
+                .line_to(7.0, 3.0)
+                .close_path()
+                .fill_nonzero_and_stroke();
+        }
+        "Paragraph" => {
+            background(&mut content);
+            content
+                .move_to(19.0, 19.5)
+                .line_to(10.0, 19.5)
+                .move_to(13.0, 19.5)
+                .line_to(13.0, 4.5)
+                .move_to(17.0, 19.5)
+                .line_to(17.0, 4.5)
+                .stroke();
+            content.set_fill_annot_color(ink);
+            draw_circle(&mut content, 10.0, 15.5, 4.0);
+            content.fill_nonzero();
+        }
+        "NewParagraph" => {
+            background(&mut content);
+            content
+                .set_fill_annot_color(ink)
+                .move_to(12.0, 20.5)
+                .line_to(6.0, 12.5)
+                .line_to(18.0, 12.5)
+                .close_path()
+                .fill_nonzero();
+            content
+                .move_to(5.0, 8.5)
+                .line_to(19.0, 8.5)
+                .move_to(5.0, 4.5)
+                .line_to(15.0, 4.5)
+                .stroke();
+        }
+        _ => {
+            content
+                .rect(0.5, 0.5, size - 1.0, size - 1.0)
+                .fill_nonzero_and_stroke();
+
+            // Folded corner triangle
+            let fold = 5.0_f32;
+            content
+                .set_fill_annot_color(darken(body, 0.9))
+                .move_to(size - fold - 0.5, size - 0.5)
+                .line_to(size - 0.5, size - 0.5)
+                .line_to(size - 0.5, size - fold - 0.5)
+                .close_path()
+                .fill_nonzero();
+
+            // Fold line
+            content
+                .move_to(size - fold - 0.5, size - 0.5)
+                .line_to(size - fold - 0.5, size - fold - 0.5)
+                .line_to(size - 0.5, size - fold - 0.5)
+                .stroke();
+        }
+    }
+
+    content.finish().into_vec()
+}
+
+/// Add a circle to the current path.
+fn draw_circle(content: &mut Content, cx: f32, cy: f32, r: f32) {
+    let k = r * 0.5523;
+    content
+        .move_to(cx + r, cy)
+        .cubic_to(cx + r, cy + k, cx + k, cy + r, cx, cy + r)
+        .cubic_to(cx - k, cy + r, cx - r, cy + k, cx - r, cy)
+        .cubic_to(cx - r, cy - k, cx - k, cy - r, cx, cy - r)
+        .cubic_to(cx + k, cy - r, cx + r, cy - k, cx + r, cy)
+        .close_path();
+}
+
+/// Darken a color by scaling its brightness with `factor`.
+fn darken(color: AnnotColor, factor: f32) -> AnnotColor {
+    match color {
+        AnnotColor::Cmyk(c, m, y, k) => AnnotColor::Cmyk(c, m, y, 1.0 - (1.0 - k) * factor),
+        _ => color.map(|c| c * factor),
+    }
+}
+
+/// Generate appearance for a text form field widget.
+pub fn generate_text_field_appearance(annot: &TextFieldAnnot) -> Vec<u8> {
+    let rect = &annot.base.rect;
+    let width = (rect[2] - rect[0]).max(1.0);
+    let height = (rect[3] - rect[1]).max(1.0);
+    let text = annot.value.as_deref().unwrap_or_default();
+
+    let mut content = Content::new();
+    content
+        .set_fill_rgb(1.0, 1.0, 1.0)
+        .rect(0.0, 0.0, width, height)
+        .fill_nonzero()
+        .set_stroke_rgb(0.2, 0.2, 0.2)
+        .set_line_width(1.0)
+        .rect(0.5, 0.5, width - 1.0, height - 1.0)
+        .stroke();
+
+    if !text.is_empty() {
+        let font_size = 10.0;
+        let lines = if annot.multiline {
+            wrap_text(
+                text,
+                (width - 6.0) * 1000.0 / font_size,
+                helvetica_text_width,
+            )
+        } else {
+            vec![text.to_string()]
+        };
+
+        content.begin_text();
+        content.set_font(pdf_writer::Name(b"Helv"), font_size);
+        content.set_fill_rgb(0.0, 0.0, 0.0);
+
+        // `Td` moves relative to the start of the previous line, so keep track
+        // of it to align every line on its own.
+        let top = (height - 12.0).max(2.0);
+        let mut previous = [0.0, 0.0];
+        for (i, line) in lines.iter().enumerate() {
+            let line_width = helvetica_text_width(line) * font_size / 1000.0;
+            let x = match annot.alignment {
+                TextAlign::Left => 3.0,
+                TextAlign::Center => (width - line_width) / 2.0,
+                TextAlign::Right => width - 3.0 - line_width,
+            };
+            let y = top - i as f32 * font_size * LINE_SPACING;
+            content.next_line(x - previous[0], y - previous[1]);
+            content.show(pdf_writer::Str(&encode_win_ansi(line)));
+            previous = [x, y];
+        }
+        content.end_text();
+    }
+
+    content.finish().into_vec()
+}
+
+/// Generate appearance for a signature form field widget.
+pub fn generate_signature_field_appearance(annot: &SignatureFieldAnnot) -> Vec<u8> {
+    let rect = &annot.base.rect;
+    let width = (rect[2] - rect[0]).max(1.0);
+    let height = (rect[3] - rect[1]).max(1.0);
+
+    let mut content = Content::new();
+    content
+        .set_fill_rgb(1.0, 1.0, 1.0)
+        .rect(0.0, 0.0, width, height)
+        .fill_nonzero()
+        .set_stroke_rgb(0.1, 0.2, 0.5)
+        .set_line_width(1.2)
+        .rect(0.6, 0.6, width - 1.2, height - 1.2)
+        .stroke();
+
+    // Signature line.
+    let line_y = (height * 0.35).max(8.0).min(height - 4.0);
+    content
+        .set_stroke_rgb(0.3, 0.3, 0.3)
+        .set_line_width(0.8)
         .move_to(6.0, line_y)
         .line_to((width - 6.0).max(6.0), line_y)
         .stroke();
@@ -380,9 +1337,232 @@ pub fn generate_signature_field_appearance(annot: &SignatureFieldAnnot) -> Vec<u
     content.finish().into_vec()
 }
 
+/// Generate appearance for a checkbox form field widget in either its
+/// checked or its unchecked state.
+pub fn generate_checkbox_appearance(annot: &CheckBoxAnnot, checked: bool) -> Vec<u8> {
+    let rect = &annot.base.rect;
+    let width = (rect[2] - rect[0]).max(1.0);
+    let height = (rect[3] - rect[1]).max(1.0);
+    let color = annot.base.color.unwrap_or(AnnotColor::black());
+
+    let mut content = Content::new();
+    content
+        .set_fill_rgb(1.0, 1.0, 1.0)
+        .rect(0.0, 0.0, width, height)
+        .fill_nonzero()
+        .set_stroke_rgb(0.2, 0.2, 0.2)
+        .set_line_width(1.0)
+        .rect(0.5, 0.5, width - 1.0, height - 1.0)
+        .stroke();
+
+    if checked {
+        // Check mark.
+        content
+            .set_stroke_annot_color(color)
+            .set_line_width((width.min(height) * 0.12).max(1.0))
+            .set_line_cap(pdf_writer::types::LineCapStyle::RoundCap)
+            .set_line_join(pdf_writer::types::LineJoinStyle::RoundJoin)
+            .move_to(width * 0.2, height * 0.5)
+            .line_to(width * 0.42, height * 0.25)
+            .line_to(width * 0.8, height * 0.78)
+            .stroke();
+    }
+
+    content.finish().into_vec()
+}
+
+/// Generate appearance for a choice form field widget.
+///
+/// A dropdown shows the selected option next to a button with a caret, while
+/// a list box shows all options with the selected one highlighted.
+pub fn generate_choice_field_appearance(annot: &ChoiceFieldAnnot) -> Vec<u8> {
+    let rect = &annot.base.rect;
+    let width = (rect[2] - rect[0]).max(1.0);
+    let height = (rect[3] - rect[1]).max(1.0);
+    let font_size = 10.0;
+
+    let mut content = Content::new();
+    content
+        .set_fill_rgb(1.0, 1.0, 1.0)
+        .rect(0.0, 0.0, width, height)
+        .fill_nonzero()
+        .set_stroke_rgb(0.2, 0.2, 0.2)
+        .set_line_width(1.0)
+        .rect(0.5, 0.5, width - 1.0, height - 1.0)
+        .stroke();
+
+    if annot.combo {
+        // The dropdown button, with a caret pointing down.
+        let button = height.min(width / 3.0);
+        let (cx, cy) = (width - button / 2.0, height / 2.0);
+        let size = button * 0.2;
+        content
+            .set_fill_rgb(0.85, 0.85, 0.85)
+            .rect(width - button, 0.5, button - 0.5, height - 1.0)
+            .fill_nonzero()
+            .set_fill_rgb(0.2, 0.2, 0.2)
+            .move_to(cx - size, cy + size / 2.0)
+            .line_to(cx + size, cy + size / 2.0)
+            .line_to(cx, cy - size / 2.0)
+            .close_path()
+            .fill_nonzero();
+
+        if let Some(selected) = &annot.selected {
+            content.begin_text();
+            content.set_font(pdf_writer::Name(b"Helv"), font_size);
+            content.set_fill_rgb(0.0, 0.0, 0.0);
+            content.next_line(3.0, ((height - font_size) / 2.0 + 2.0).max(2.0));
+            content.show(pdf_writer::Str(&encode_win_ansi(selected)));
+            content.end_text();
+        }
+    } else {
+        let line_height = font_size * LINE_SPACING;
+        for (i, option) in annot.options.iter().enumerate() {
+            let top = height - 1.0 - i as f32 * line_height;
+            if top - line_height < 0.0 {
+                break;
+            }
+
+            if annot.selected.as_ref() == Some(option) {
+                content
+                    .set_fill_rgb(0.6, 0.75, 0.95)
+                    .rect(1.0, top - line_height, width - 2.0, line_height)
+                    .fill_nonzero();
+            }
+
+            content.begin_text();
+            content.set_font(pdf_writer::Name(b"Helv"), font_size);
+            content.set_fill_rgb(0.0, 0.0, 0.0);
+            content.next_line(3.0, top - font_size);
+            content.show(pdf_writer::Str(&encode_win_ansi(option)));
+            content.end_text();
+        }
+    }
+
+    content.finish().into_vec()
+}
+
+/// Generate appearance for a stamp annotation, which paints its image
+/// stretched to fill the annotation rect.
+///
+/// The image is referenced as `/Im0`, and the appearance is empty if the
+/// image data can't be read.
+pub fn generate_stamp_appearance(annot: &StampAnnot) -> Vec<u8> {
+    let rect = &annot.base.rect;
+    let width = rect[2] - rect[0];
+    let height = rect[3] - rect[1];
+    if image_size((*annot.image_data).as_ref(), annot.format).is_none() {
+        return Vec::new();
+    }
+
+    let mut content = Content::new();
+    content
+        .save_state()
+        .transform([width, 0.0, 0.0, height, 0.0, 0.0])
+        .x_object(Name(STAMP_IMAGE_NAME))
+        .restore_state();
+
+    content.finish().into_vec()
+}
+
+/// Generate appearance for a file attachment annotation.
+///
+/// The `Graph` and `GraphPushPin` icons show a bar chart, all other icons a
+/// paperclip. The icon is drawn on a 24x24 grid that is scaled to the rect.
+pub fn generate_file_attachment_appearance(annot: &FileAttachmentAnnot) -> Vec<u8> {
+    let size = 24.0_f32;
+    let rect = &annot.base.rect;
+    let width = rect[2] - rect[0];
+    let height = rect[3] - rect[1];
+    let color = annot.base.color.unwrap_or(AnnotColor::new(0.25, 0.25, 0.5));
+
+    let mut content = Content::new();
+    content
+        .save_state()
+        .transform([width / size, 0.0, 0.0, height / size, 0.0, 0.0]);
+
+    if annot.icon.starts_with("Graph") {
+        // Axes
+        content
+            .set_stroke_annot_color(color)
+            .set_line_width(1.5)
+            .move_to(4.0, 21.0)
+            .line_to(4.0, 4.0)
+            .line_to(21.0, 4.0)
+            .stroke();
+
+        // Bars
+        content
+            .set_fill_annot_color(color)
+            .rect(7.0, 4.0, 3.0, 7.0)
+            .rect(12.0, 4.0, 3.0, 13.0)
+            .rect(17.0, 4.0, 3.0, 10.0)
+            .fill_nonzero();
+    } else {
+        // A paperclip made of two nested loops.
+        content
+            .set_stroke_annot_color(color)
+            .set_line_width(1.5)
+            .move_to(10.0, 9.0)
+            .line_to(10.0, 17.0)
+            .cubic_to(10.0, 19.67, 14.0, 19.67, 14.0, 17.0)
+            .line_to(14.0, 6.0)
+            .cubic_to(14.0, 2.0, 7.0, 2.0, 7.0, 6.0)
+            .line_to(7.0, 18.0)
+            .cubic_to(7.0, 23.33, 17.0, 23.33, 17.0, 18.0)
+            .line_to(17.0, 8.0)
+            .stroke();
+    }
+
+    content.restore_state();
+    content.finish().into_vec()
+}
+
+/// Generate the named appearance states for annotation types that can be
+/// toggled between several appearances.
+///
+/// Returns `None` for annotations that only have a single appearance stream.
+pub fn generate_appearance_states(annot: &Annotation) -> Option<AppearanceStates> {
+    let annot = normalized(annot);
+    let annot = annot.as_ref();
+    let mut states = match annot {
+        Annotation::CheckBox(a) => Some(AppearanceStates {
+            states: vec![
+                (
+                    a.export_value.clone(),
+                    generate_checkbox_appearance(a, true),
+                ),
+                ("Off".to_string(), generate_checkbox_appearance(a, false)),
+            ],
+            current: a.state_name().to_string(),
+        }),
+        _ => None,
+    }?;
+
+    for (_, content) in &mut states.states {
+        if is_invisible(annot) {
+            content.clear();
+        } else {
+            *content = with_graphics_state(annot, std::mem::take(content));
+        }
+    }
+
+    Some(states)
+}
+
 /// Generate the appearance stream for any annotation type.
+///
+/// Annotations with an opacity of zero get an empty appearance, so that they
+/// stay invisible even in viewers that ignore `/CA`. Rects whose corners are
+/// swapped are normalized first.
 pub fn generate_appearance(annot: &Annotation) -> Vec<u8> {
-    match annot {
+    let annot = normalized(annot);
+    let annot = annot.as_ref();
+    if is_invisible(annot) {
+        return Vec::new();
+    }
+
+    let content = match annot {
         Annotation::Highlight(a) => generate_highlight_appearance(a),
         Annotation::Underline(a) => generate_underline_appearance(a),
         Annotation::StrikeOut(a) => generate_strikeout_appearance(a),
@@ -392,16 +1572,69 @@ pub fn generate_appearance(annot: &Annotation) -> Vec<u8> {
         Annotation::Square(a) => generate_square_appearance(a),
         Annotation::Circle(a) => generate_circle_appearance(a),
         Annotation::Line(a) => generate_line_appearance(a),
+        Annotation::Polygon(a) => generate_poly_appearance(a, true),
+        Annotation::PolyLine(a) => generate_poly_appearance(a, false),
         Annotation::Text(a) => generate_text_appearance(a),
         Annotation::TextField(a) => generate_text_field_appearance(a),
         Annotation::SignatureField(a) => generate_signature_field_appearance(a),
+        Annotation::CheckBox(a) => generate_checkbox_appearance(a, a.checked),
+        Annotation::Choice(a) => generate_choice_field_appearance(a),
+        Annotation::Stamp(a) => generate_stamp_appearance(a),
+        Annotation::FileAttachment(a) => generate_file_attachment_appearance(a),
+        Annotation::Redact(a) => generate_redact_appearance(a),
         Annotation::Link(_) => {
             // Links typically don't have visible appearance streams
             Vec::new()
         }
+    };
+
+    with_graphics_state(annot, content)
+}
+
+/// The blend mode an annotation's appearance is composited with, if it isn't
+/// the normal one.
+///
+/// Highlights multiply by default, so that the highlighted text shows through.
+pub(crate) fn blend_mode(annot: &Annotation) -> Option<BlendMode> {
+    let mode = match annot.base().blend_mode {
+        Some(mode) => mode,
+        None if matches!(annot, Annotation::Highlight(_)) => BlendMode::Multiply,
+        None => return None,
+    };
+
+    (mode != BlendMode::Normal).then_some(mode)
+}
+
+/// The opacity of the interior of a shape, if it isn't opaque.
+pub(crate) fn fill_opacity(annot: &Annotation) -> Option<f32> {
+    match annot {
+        Annotation::Square(a) | Annotation::Circle(a) if a.fill_opacity < 1.0 => {
+            Some(a.fill_opacity)
+        }
+        _ => None,
     }
 }
 
+/// Prefix a non-empty appearance with a `gs` operator that selects the
+/// graphics state named [`GRAPHICS_STATE_NAME`], if the annotation needs one.
+fn with_graphics_state(annot: &Annotation, content: Vec<u8>) -> Vec<u8> {
+    if content.is_empty() || (blend_mode(annot).is_none() && fill_opacity(annot).is_none()) {
+        return content;
+    }
+
+    let mut prefix = Content::new();
+    prefix.set_parameters(Name(GRAPHICS_STATE_NAME));
+    let mut prefixed = prefix.finish().into_vec();
+    prefixed.push(b'\n');
+    prefixed.extend(content);
+    prefixed
+}
+
+/// Whether the annotation is fully transparent.
+fn is_invisible(annot: &Annotation) -> bool {
+    annot.base().opacity <= 0.0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -410,17 +1643,132 @@ mod tests {
     fn highlight_appearance_contains_fill() {
         let annot = HighlightAnnot {
             base: AnnotationBase {
-                rect: [100.0, 200.0, 300.0, 220.0],
-                color: Some(AnnotColor::yellow()),
+                rect: [100.0, 200.0, 300.0, 220.0],
+                color: Some(AnnotColor::yellow()),
+                ..Default::default()
+            },
+            quad_points: vec![],
+        };
+        let bytes = generate_highlight_appearance(&annot);
+        let s = String::from_utf8_lossy(&bytes);
+        assert!(s.contains("rg"), "should set fill color: {s}");
+        assert!(s.contains("re"), "should draw rectangle: {s}");
+        assert!(s.contains("f"), "should fill: {s}");
+    }
+
+    #[test]
+    fn misordered_quad_is_filled_as_convex_quad() {
+        // Corners in `/QuadPoints` order, which forms a bowtie when connected as is.
+        let quad = [100.0, 220.0, 300.0, 220.0, 100.0, 200.0, 300.0, 200.0];
+        let corners = order_quad_corners(&quad);
+
+        // Shoelace formula; a bowtie would have an area of zero.
+        let area = (0..4)
+            .map(|i| {
+                let [x0, y0] = corners[i];
+                let [x1, y1] = corners[(i + 1) % 4];
+                x0 * y1 - x1 * y0
+            })
+            .sum::<f32>()
+            / 2.0;
+        assert!((area - 200.0 * 20.0).abs() < 0.01, "area={area}");
+
+        let annot = HighlightAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 200.0, 300.0, 220.0],
+                color: Some(AnnotColor::yellow()),
+                ..Default::default()
+            },
+            quad_points: quad.to_vec(),
+        };
+        let bytes = generate_highlight_appearance(&annot);
+        let s = String::from_utf8_lossy(&bytes);
+        assert!(s.contains("0 0 m\n200 0 l\n200 20 l\n0 20 l"), "{s}");
+    }
+
+    #[test]
+    fn tilted_quad_is_filled_as_polygon() {
+        // A selection of text rotated by about 27 degrees.
+        let annot = HighlightAnnot {
+            base: AnnotationBase {
+                rect: [90.0, 200.0, 200.0, 270.0],
+                ..Default::default()
+            },
+            quad_points: vec![90.0, 220.0, 190.0, 270.0, 100.0, 200.0, 200.0, 250.0],
+        };
+        let bytes = generate_highlight_appearance(&annot);
+        let s = String::from_utf8_lossy(&bytes);
+        assert!(
+            !s.contains("re"),
+            "the bounding box should not be filled: {s}"
+        );
+        assert!(
+            s.contains("0 20 m\n10 0 l\n110 50 l\n100 70 l\nh\nf"),
+            "{s}"
+        );
+    }
+
+    #[test]
+    fn multiline_highlight_fills_each_quad() {
+        // Two lines of a selection with a gap between them.
+        let annot = HighlightAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 200.0, 300.0, 250.0],
                 ..Default::default()
             },
-            quad_points: vec![],
+            quad_points: vec![
+                100.0, 250.0, 300.0, 250.0, 100.0, 230.0, 300.0, 230.0, //
+                100.0, 220.0, 180.0, 220.0, 100.0, 200.0, 180.0, 200.0,
+            ],
         };
         let bytes = generate_highlight_appearance(&annot);
         let s = String::from_utf8_lossy(&bytes);
-        assert!(s.contains("rg"), "should set fill color: {s}");
-        assert!(s.contains("re"), "should draw rectangle: {s}");
-        assert!(s.contains("f"), "should fill: {s}");
+        assert_eq!(s.lines().filter(|l| *l == "f").count(), 2, "{s}");
+        assert!(
+            !s.contains("re"),
+            "the whole rect should not be filled: {s}"
+        );
+        assert!(s.contains("0 30 m\n200 30 l\n200 50 l\n0 50 l"), "{s}");
+        assert!(s.contains("0 0 m\n80 0 l\n80 20 l\n0 20 l"), "{s}");
+    }
+
+    #[test]
+    fn text_markup_lines_follow_each_quad() {
+        let base = AnnotationBase {
+            rect: [100.0, 200.0, 300.0, 250.0],
+            ..Default::default()
+        };
+        let quad_points = vec![
+            100.0, 250.0, 300.0, 250.0, 100.0, 230.0, 300.0, 230.0, //
+            100.0, 220.0, 180.0, 220.0, 100.0, 200.0, 180.0, 200.0,
+        ];
+
+        let underline = generate_underline_appearance(&UnderlineAnnot {
+            base: base.clone(),
+            quad_points: quad_points.clone(),
+        });
+        let s = String::from_utf8_lossy(&underline);
+        assert_eq!(s.matches(" m\n").count(), 2, "{s}");
+        assert!(s.contains("0 30.5 m\n200 30.5 l"), "{s}");
+        assert!(s.contains("0 0.5 m\n80 0.5 l"), "{s}");
+
+        let strikeout = generate_strikeout_appearance(&StrikeOutAnnot {
+            base: base.clone(),
+            quad_points: quad_points.clone(),
+        });
+        let s = String::from_utf8_lossy(&strikeout);
+        assert_eq!(s.matches(" m\n").count(), 2, "{s}");
+        assert!(s.contains("0 40 m\n200 40 l"), "{s}");
+        assert!(s.contains("0 10 m\n80 10 l"), "{s}");
+
+        let squiggly = generate_squiggly_appearance(&SquigglyAnnot { base, quad_points });
+        let s = String::from_utf8_lossy(&squiggly);
+        assert_eq!(s.matches(" m\n").count(), 2, "{s}");
+        assert!(s.contains("0 31 m\n"), "{s}");
+        assert!(s.contains("0 1 m\n"), "{s}");
+        // The second line ends at the right edge of its quad.
+        assert!(s.contains(" 80 1 c\n"), "{s}");
+        assert!(!s.contains(" 84 1 c"), "{s}");
     }
 
     #[test]
@@ -433,6 +1781,8 @@ mod tests {
             },
             ink_list: vec![vec![[60.0, 60.0], [100.0, 100.0], [140.0, 60.0]]],
             line_width: 2.0,
+            smoothing: 0.0,
+            dash: None,
         };
         let bytes = generate_ink_appearance(&annot);
         let s = String::from_utf8_lossy(&bytes);
@@ -442,6 +1792,36 @@ mod tests {
         assert!(s.contains("S"), "should stroke: {s}");
     }
 
+    #[test]
+    fn smoothed_ink_appearance_uses_curves() {
+        let mut annot = InkAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 100.0, 100.0],
+                ..Default::default()
+            },
+            ink_list: vec![vec![[10.0, 10.0], [40.0, 60.0], [70.0, 20.0], [90.0, 80.0]]],
+            line_width: 1.0,
+            smoothing: 0.0,
+            dash: None,
+        };
+        let straight = String::from_utf8_lossy(&generate_ink_appearance(&annot)).into_owned();
+        assert_eq!(straight.matches(" l\n").count(), 3, "{straight}");
+        assert!(!straight.contains(" c\n"), "{straight}");
+
+        annot.smoothing = 1.0;
+        let smooth = String::from_utf8_lossy(&generate_ink_appearance(&annot)).into_owned();
+        assert_eq!(smooth.matches(" c\n").count(), 3, "{smooth}");
+        assert!(!smooth.contains(" l\n"), "{smooth}");
+        // The spline starts and ends exactly at the endpoints.
+        assert!(smooth.contains("10 10 m\n"), "{smooth}");
+        assert!(smooth.contains(" 90 80 c\n"), "{smooth}");
+
+        // Two points can't be smoothed.
+        annot.ink_list = vec![vec![[10.0, 10.0], [90.0, 80.0]]];
+        let short = String::from_utf8_lossy(&generate_ink_appearance(&annot)).into_owned();
+        assert!(short.contains("10 10 m\n90 80 l\n"), "{short}");
+    }
+
     #[test]
     fn square_appearance_contains_rect() {
         let annot = ShapeAnnot {
@@ -453,6 +1833,9 @@ mod tests {
             interior_color: None,
             line_width: 1.0,
             is_circle: false,
+            border_style: BorderStyle::Solid,
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
         };
         let bytes = generate_square_appearance(&annot);
         let s = String::from_utf8_lossy(&bytes);
@@ -460,6 +1843,71 @@ mod tests {
         assert!(s.contains("S"), "should stroke: {s}");
     }
 
+    #[test]
+    fn dashed_square_appearance_sets_dash_pattern() {
+        let annot = ShapeAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 100.0, 200.0, 200.0],
+                color: Some(AnnotColor::black()),
+                ..Default::default()
+            },
+            interior_color: None,
+            line_width: 1.0,
+            is_circle: false,
+            border_style: BorderStyle::Dashed(vec![3.0, 2.0]),
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
+        };
+        let bytes = generate_square_appearance(&annot);
+        let s = String::from_utf8_lossy(&bytes);
+        assert!(s.contains("[3 2] 0 d"), "should set dash pattern: {s}");
+        assert!(s.contains("re"), "should draw rectangle: {s}");
+    }
+
+    #[test]
+    fn invalid_dash_patterns_are_drawn_solid() {
+        for dashes in [vec![0.0, 0.0], vec![-3.0, -2.0], vec![f32::NAN]] {
+            let annot = ShapeAnnot {
+                base: AnnotationBase {
+                    rect: [100.0, 100.0, 200.0, 200.0],
+                    color: Some(AnnotColor::black()),
+                    ..Default::default()
+                },
+                interior_color: None,
+                line_width: 1.0,
+                is_circle: false,
+                border_style: BorderStyle::Dashed(dashes),
+                fill_opacity: 1.0,
+                corner_radius: 0.0,
+            };
+            let bytes = generate_square_appearance(&annot);
+            let s = String::from_utf8_lossy(&bytes);
+            assert!(!s.contains(" d\n"), "should not set a dash pattern: {s}");
+            assert!(s.contains("re"), "should draw rectangle: {s}");
+        }
+    }
+
+    #[test]
+    fn cloudy_square_appearance_draws_scallops() {
+        let annot = ShapeAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 100.0, 200.0, 200.0],
+                color: Some(AnnotColor::black()),
+                ..Default::default()
+            },
+            interior_color: None,
+            line_width: 1.0,
+            is_circle: false,
+            border_style: BorderStyle::Cloudy(1.0),
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
+        };
+        let bytes = generate_square_appearance(&annot);
+        let s = String::from_utf8_lossy(&bytes);
+        assert!(!s.contains("re"), "should not draw a plain rectangle: {s}");
+        assert!(s.matches(" c\n").count() > 8, "should draw scallops: {s}");
+    }
+
     #[test]
     fn circle_appearance_contains_curves() {
         let annot = ShapeAnnot {
@@ -471,6 +1919,9 @@ mod tests {
             interior_color: None,
             line_width: 1.0,
             is_circle: true,
+            border_style: BorderStyle::Solid,
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
         };
         let bytes = generate_circle_appearance(&annot);
         let s = String::from_utf8_lossy(&bytes);
@@ -488,6 +1939,13 @@ mod tests {
             text: "Hello".to_string(),
             font_size: 12.0,
             default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+            font_data: None,
+            multiline: false,
+            line_spacing: 1.2,
+            vertical_align: VAlign::Top,
+            callout_line: None,
+            rich_text: None,
+            default_style: None,
         };
         let bytes = generate_freetext_appearance(&annot);
         let s = String::from_utf8_lossy(&bytes);
@@ -497,6 +1955,282 @@ mod tests {
         assert!(s.contains("Tj"), "should show text: {s}");
     }
 
+    #[test]
+    fn multiline_freetext_wraps_to_rect_width() {
+        let mut annot = FreeTextAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 100.0, 100.0],
+                ..Default::default()
+            },
+            text: "The quick brown fox jumps over the lazy dog".to_string(),
+            font_size: 12.0,
+            default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+            font_data: None,
+            multiline: false,
+            line_spacing: 1.2,
+            vertical_align: VAlign::Top,
+            callout_line: None,
+            rich_text: None,
+            default_style: None,
+        };
+        let single = String::from_utf8_lossy(&generate_freetext_appearance(&annot)).into_owned();
+        assert_eq!(single.matches("Tj").count(), 1, "{single}");
+
+        annot.multiline = true;
+        let wrapped = String::from_utf8_lossy(&generate_freetext_appearance(&annot)).into_owned();
+        assert!(wrapped.matches("Tj").count() > 1, "{wrapped}");
+        assert!(
+            wrapped.matches("Td").count() > 1,
+            "each line should be positioned with Td: {wrapped}"
+        );
+        assert!(
+            !wrapped.contains(" ) Tj"),
+            "lines should be trimmed: {wrapped}"
+        );
+    }
+
+    #[test]
+    fn freetext_appearance_follows_default_appearance() {
+        let mut annot = FreeTextAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 200.0, 40.0],
+                color: Some(AnnotColor::black()),
+                ..Default::default()
+            },
+            text: "Hello".to_string(),
+            font_size: 12.0,
+            default_appearance: "1 0 0 rg /Helv 18 Tf".to_string(),
+            font_data: None,
+            multiline: false,
+            line_spacing: 1.2,
+            vertical_align: VAlign::Top,
+            callout_line: None,
+            rich_text: None,
+            default_style: None,
+        };
+        let s = String::from_utf8_lossy(&generate_freetext_appearance(&annot)).into_owned();
+        assert!(s.contains("/Helv 18 Tf"), "{s}");
+        assert!(s.contains("1 0 0 rg"), "{s}");
+
+        // Without a usable DA, the font size and color fields are used.
+        annot.default_appearance = "/Helv Tf".to_string();
+        let s = String::from_utf8_lossy(&generate_freetext_appearance(&annot)).into_owned();
+        assert!(s.contains("/Helv 12 Tf"), "{s}");
+        assert!(s.contains("0 0 0 rg"), "{s}");
+    }
+
+    #[test]
+    fn freetext_text_is_aligned_vertically() {
+        let mut annot = FreeTextAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 200.0, 40.0],
+                ..Default::default()
+            },
+            text: "Hello".to_string(),
+            font_size: 12.0,
+            default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+            font_data: None,
+            multiline: false,
+            line_spacing: 1.2,
+            vertical_align: VAlign::Top,
+            callout_line: None,
+            rich_text: None,
+            default_style: None,
+        };
+        let s = String::from_utf8_lossy(&generate_freetext_appearance(&annot)).into_owned();
+        assert!(s.contains("2 26 Td"), "{s}");
+
+        // A single line is centered by its font size.
+        annot.vertical_align = VAlign::Middle;
+        let s = String::from_utf8_lossy(&generate_freetext_appearance(&annot)).into_owned();
+        assert!(s.contains("2 14 Td"), "{s}");
+
+        annot.vertical_align = VAlign::Bottom;
+        let s = String::from_utf8_lossy(&generate_freetext_appearance(&annot)).into_owned();
+        assert!(s.contains("2 2 Td"), "{s}");
+    }
+
+    #[test]
+    fn freetext_line_spacing_sets_leading() {
+        let mut annot = FreeTextAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 200.0, 100.0],
+                ..Default::default()
+            },
+            text: "one\ntwo".to_string(),
+            font_size: 10.0,
+            default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
+            font_data: None,
+            multiline: true,
+            line_spacing: 1.5,
+            vertical_align: VAlign::Top,
+            callout_line: None,
+            rich_text: None,
+            default_style: None,
+        };
+        let s = String::from_utf8_lossy(&generate_freetext_appearance(&annot)).into_owned();
+        assert!(s.contains("0 -15 Td"), "{s}");
+
+        annot.line_spacing = 2.0;
+        let s = String::from_utf8_lossy(&generate_freetext_appearance(&annot)).into_owned();
+        assert!(s.contains("2 88 Td"), "first line shouldn't move: {s}");
+        assert!(s.contains("0 -20 Td"), "{s}");
+
+        // The whole block is centered, not just the first line.
+        annot.vertical_align = VAlign::Middle;
+        let s = String::from_utf8_lossy(&generate_freetext_appearance(&annot)).into_owned();
+        assert!(s.contains("2 55 Td"), "{s}");
+    }
+
+    #[test]
+    fn default_appearance_parses_color_and_font_size() {
+        let da = DefaultAppearance::parse("0.5 g /Helv 9 Tf 0 0 1 rg");
+        assert_eq!(da.font_size, Some(9.0));
+        assert_eq!(da.color, Some(AnnotColor::new(0.0, 0.0, 1.0)));
+
+        let da = DefaultAppearance::parse("/F1 0 Tf 0 0 0 1 k");
+        assert_eq!(da.font_size, None);
+        assert_eq!(da.color, Some(AnnotColor::cmyk(0.0, 0.0, 0.0, 1.0)));
+
+        assert_eq!(DefaultAppearance::parse(""), DefaultAppearance::default());
+    }
+
+    #[test]
+    fn swapped_rect_corners_are_normalized() {
+        let square = |rect| {
+            Annotation::Square(ShapeAnnot {
+                base: AnnotationBase {
+                    rect,
+                    ..Default::default()
+                },
+                interior_color: None,
+                line_width: 2.0,
+                is_circle: false,
+                border_style: BorderStyle::Solid,
+                fill_opacity: 1.0,
+                corner_radius: 0.0,
+            })
+        };
+        let swapped = square([300.0, 200.0, 100.0, 100.0]);
+
+        let s = String::from_utf8_lossy(&generate_appearance(&swapped)).into_owned();
+        assert!(s.contains("1 1 198 98 re"), "{s}");
+        assert_eq!(
+            generate_appearance(&swapped),
+            generate_appearance(&square([100.0, 100.0, 300.0, 200.0]))
+        );
+        assert_eq!(annotation_rect(&swapped), [100.0, 100.0, 300.0, 200.0]);
+    }
+
+    #[test]
+    fn rounded_squares_draw_corner_curves() {
+        let mut annot = ShapeAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 100.0, 40.0],
+                ..Default::default()
+            },
+            interior_color: Some(AnnotColor::new(1.0, 1.0, 0.0)),
+            line_width: 2.0,
+            is_circle: false,
+            border_style: BorderStyle::Solid,
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
+        };
+        let sharp = String::from_utf8_lossy(&generate_square_appearance(&annot)).into_owned();
+        assert_eq!(sharp.matches(" re\n").count(), 2, "{sharp}");
+        assert!(!sharp.contains(" c\n"), "{sharp}");
+
+        // The radius is clamped to half the height of the border, so the
+        // straight vertical edges vanish.
+        annot.corner_radius = 50.0;
+        let rounded = String::from_utf8_lossy(&generate_square_appearance(&annot)).into_owned();
+        assert!(!rounded.contains(" re\n"), "{rounded}");
+        assert_eq!(rounded.matches(" c\n").count(), 8, "{rounded}");
+        assert!(rounded.contains("20 1 m\n"), "{rounded}");
+        assert!(rounded.contains("\n99 20 l\n"), "{rounded}");
+    }
+
+    #[test]
+    fn large_circles_use_eight_accurate_curves() {
+        let mut annot = ShapeAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 100.0, 60.0],
+                ..Default::default()
+            },
+            interior_color: None,
+            line_width: 0.0,
+            is_circle: true,
+            border_style: BorderStyle::Solid,
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
+        };
+        let s = String::from_utf8_lossy(&generate_circle_appearance(&annot)).into_owned();
+        assert_eq!(s.matches(" c\n").count(), 4, "{s}");
+
+        annot.base.rect = [0.0, 0.0, 2000.0, 300.0];
+        let s = String::from_utf8_lossy(&generate_circle_appearance(&annot)).into_owned();
+        assert_eq!(s.matches(" c\n").count(), 8, "{s}");
+
+        let (rx, ry) = (1000.0, 150.0);
+        for [p0, p1, p2, p3] in ellipse_curves([1000.0, 150.0], rx, ry, 8) {
+            for i in 0..=10 {
+                let t = i as f32 / 10.0;
+                let mt = 1.0 - t;
+                let [x, y] = [0, 1].map(|c| {
+                    mt * mt * mt * p0[c]
+                        + 3.0 * mt * mt * t * p1[c]
+                        + 3.0 * mt * t * t * p2[c]
+                        + t * t * t * p3[c]
+                });
+                let radius = ((x - 1000.0) / rx).hypot((y - 150.0) / ry);
+                assert!((radius - 1.0).abs() < 1e-4, "{radius} at {x} {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn dashed_line_and_ink_set_dash_pattern() {
+        let mut line = LineAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 100.0, 20.0],
+                ..Default::default()
+            },
+            start: [5.0, 10.0],
+            end: [95.0, 10.0],
+            line_width: 1.0,
+            start_style: LineEnding::None,
+            end_style: LineEnding::OpenArrow,
+            measure: None,
+            dash: Some(vec![4.0, 2.0]),
+        };
+        let s = String::from_utf8_lossy(&generate_line_appearance(&line)).into_owned();
+        assert!(s.contains("[4 2] 0 d"), "{s}");
+        // The arrowhead is drawn solid.
+        assert!(s.contains("[] 0 d"), "{s}");
+
+        // A pattern without any positive length would be an invalid `d` operator.
+        line.dash = Some(vec![0.0, 0.0]);
+        let s = String::from_utf8_lossy(&generate_line_appearance(&line)).into_owned();
+        assert!(!s.contains(" d\n"), "{s}");
+
+        let mut ink = InkAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 100.0, 100.0],
+                ..Default::default()
+            },
+            ink_list: vec![vec![[10.0, 10.0], [90.0, 90.0]]],
+            line_width: 1.0,
+            smoothing: 0.0,
+            dash: Some(vec![1.0, f32::NAN, 3.0]),
+        };
+        let s = String::from_utf8_lossy(&generate_ink_appearance(&ink)).into_owned();
+        assert!(s.contains("[1 3] 0 d"), "{s}");
+
+        ink.dash = Some(Vec::new());
+        let s = String::from_utf8_lossy(&generate_ink_appearance(&ink)).into_owned();
+        assert!(!s.contains(" d\n"), "{s}");
+    }
+
     #[test]
     fn line_appearance_contains_line() {
         let annot = LineAnnot {
@@ -508,6 +2242,10 @@ mod tests {
             start: [100.0, 100.0],
             end: [300.0, 200.0],
             line_width: 2.0,
+            start_style: LineEnding::None,
+            end_style: LineEnding::None,
+            measure: None,
+            dash: None,
         };
         let bytes = generate_line_appearance(&annot);
         let s = String::from_utf8_lossy(&bytes);
@@ -516,6 +2254,105 @@ mod tests {
         assert!(s.contains("S"), "should stroke: {s}");
     }
 
+    #[test]
+    fn line_endings_draw_extra_geometry() {
+        let mut annot = LineAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 200.0, 20.0],
+                ..Default::default()
+            },
+            start: [10.0, 10.0],
+            end: [190.0, 10.0],
+            line_width: 1.0,
+            start_style: LineEnding::None,
+            end_style: LineEnding::None,
+            measure: None,
+            dash: None,
+        };
+        let plain = generate_line_appearance(&annot);
+
+        annot.end_style = LineEnding::ClosedArrow;
+        let arrow = String::from_utf8_lossy(&generate_line_appearance(&annot)).into_owned();
+        assert!(arrow.len() > plain.len());
+        assert!(
+            arrow.contains("B"),
+            "closed arrow should be filled: {arrow}"
+        );
+        // The arrowhead points right, so its base lies left of the tip.
+        assert!(arrow.contains("186 12 m"), "arrow base: {arrow}");
+
+        annot.start_style = LineEnding::Circle;
+        let both = String::from_utf8_lossy(&generate_line_appearance(&annot)).into_owned();
+        assert!(
+            both.contains("c"),
+            "circle ending should use curves: {both}"
+        );
+    }
+
+    #[test]
+    fn measured_line_displays_scaled_length() {
+        let annot = LineAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 200.0, 40.0],
+                ..Default::default()
+            },
+            start: [0.0, 10.0],
+            end: [100.0, 10.0],
+            line_width: 1.0,
+            start_style: LineEnding::None,
+            end_style: LineEnding::None,
+            measure: Some(MeasureScale {
+                ratio: "1 in = 10 ft".to_string(),
+                units_per_point: 10.0 / 72.0,
+                unit: "ft".to_string(),
+                precision: 2,
+            }),
+            dash: None,
+        };
+        let bytes = generate_line_appearance(&annot);
+        let s = String::from_utf8_lossy(&bytes);
+        assert!(s.contains("/Helv 9 Tf"), "{s}");
+        assert!(s.contains("(13.89 ft) Tj"), "{s}");
+
+        let (_, bbox) = measure_label(&annot).unwrap();
+        assert!(bbox[0] < 50.0 && bbox[2] > 50.0, "{bbox:?}");
+        assert!(bbox[1] > 10.0, "{bbox:?}");
+    }
+
+    #[test]
+    fn polygon_appearance_is_closed_and_filled() {
+        let annot = PolyAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 100.0, 100.0],
+                ..Default::default()
+            },
+            vertices: vec![[10.0, 10.0], [90.0, 10.0], [50.0, 90.0]],
+            line_width: 1.0,
+            interior_color: Some(AnnotColor::red()),
+        };
+        let bytes = generate_poly_appearance(&annot, true);
+        let s = String::from_utf8_lossy(&bytes);
+        assert!(s.contains("h"), "should close path: {s}");
+        assert!(s.contains("B"), "should fill and stroke: {s}");
+    }
+
+    #[test]
+    fn poly_appearance_with_too_few_vertices_is_empty() {
+        let mut annot = PolyAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 100.0, 100.0],
+                ..Default::default()
+            },
+            vertices: vec![],
+            line_width: 1.0,
+            interior_color: None,
+        };
+        assert!(generate_poly_appearance(&annot, true).is_empty());
+
+        annot.vertices = vec![[10.0, 10.0]];
+        assert!(generate_poly_appearance(&annot, false).is_empty());
+    }
+
     #[test]
     fn text_appearance_generates_icon() {
         let annot = TextAnnot {
@@ -525,6 +2362,8 @@ mod tests {
             },
             open: false,
             icon: "Note".to_string(),
+            rich_text: None,
+            default_style: None,
         };
         let bytes = generate_text_appearance(&annot);
         let s = String::from_utf8_lossy(&bytes);
@@ -532,6 +2371,48 @@ mod tests {
         assert!(s.contains("re"), "should draw rectangle: {s}");
     }
 
+    #[test]
+    fn text_appearance_varies_by_icon_and_color() {
+        let note = |icon: &str, color: Option<AnnotColor>| {
+            generate_text_appearance(&TextAnnot {
+                base: AnnotationBase {
+                    rect: [100.0, 100.0, 124.0, 124.0],
+                    color,
+                    ..Default::default()
+                },
+                open: false,
+                icon: icon.to_string(),
+                rich_text: None,
+                default_style: None,
+            })
+        };
+
+        let icons = [
+            "Note",
+            "Comment",
+            "Key",
+            "Help",
+            "Insert",
+            "Paragraph",
+            "NewParagraph",
+        ];
+        let streams = icons.map(|icon| note(icon, None));
+        for (i, a) in streams.iter().enumerate() {
+            for b in &streams[i + 1..] {
+                assert_ne!(a, b, "{} should have its own symbol", icons[i]);
+            }
+        }
+
+        // Unknown icons fall back to the folded note.
+        assert_eq!(note("Unknown", None), streams[0]);
+
+        let red_help = String::from_utf8_lossy(&note("Help", Some(AnnotColor::red()))).into_owned();
+        assert!(red_help.contains("1 0 0 rg"), "{red_help}");
+        assert!(red_help.contains("0.5 0 0 RG"), "{red_help}");
+        let yellow_note = String::from_utf8_lossy(&streams[0]).into_owned();
+        assert!(yellow_note.contains("1 1 0.8 rg"), "{yellow_note}");
+    }
+
     #[test]
     fn text_field_appearance_contains_border() {
         let annot = TextFieldAnnot {
@@ -547,6 +2428,9 @@ mod tests {
             read_only: false,
             required: false,
             multiline: false,
+            alignment: TextAlign::Left,
+            down_appearance: None,
+            rollover_appearance: None,
         };
         let bytes = generate_text_field_appearance(&annot);
         let s = String::from_utf8_lossy(&bytes);
@@ -554,6 +2438,78 @@ mod tests {
         assert!(s.contains("Tj"), "should render text: {s}");
     }
 
+    #[test]
+    fn text_field_alignment_shifts_text_origin() {
+        let mut annot = TextFieldAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 100.0, 260.0, 132.0],
+                ..Default::default()
+            },
+            field_name: "name".to_string(),
+            value: Some("Hi".to_string()),
+            default_value: None,
+            max_len: None,
+            default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
+            read_only: false,
+            required: false,
+            multiline: false,
+            alignment: TextAlign::Left,
+            down_appearance: None,
+            rollover_appearance: None,
+        };
+
+        let mut text_x = |alignment| {
+            annot.alignment = alignment;
+            let bytes = generate_text_field_appearance(&annot);
+            let s = String::from_utf8_lossy(&bytes).into_owned();
+            let td = s.lines().find(|l| l.ends_with(" Td")).unwrap().to_string();
+            td.split(' ').next().unwrap().parse::<f32>().unwrap()
+        };
+
+        // "Hi" is 9.44pt wide at 10pt.
+        assert!((text_x(TextAlign::Left) - 3.0).abs() < 0.01);
+        assert!((text_x(TextAlign::Center) - 75.28).abs() < 0.01);
+        assert!((text_x(TextAlign::Right) - 147.56).abs() < 0.01);
+    }
+
+    #[test]
+    fn multiline_text_field_aligns_every_line() {
+        let annot = TextFieldAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 100.0, 60.0],
+                ..Default::default()
+            },
+            field_name: "notes".to_string(),
+            value: Some("Hi\nHiHi".to_string()),
+            default_value: None,
+            max_len: None,
+            default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
+            read_only: false,
+            required: false,
+            multiline: true,
+            alignment: TextAlign::Right,
+            down_appearance: None,
+            rollover_appearance: None,
+        };
+
+        let bytes = generate_text_field_appearance(&annot);
+        let s = String::from_utf8_lossy(&bytes);
+        let offsets = s
+            .lines()
+            .filter_map(|l| l.strip_suffix(" Td"))
+            .map(|l| {
+                l.split(' ')
+                    .map(|n| n.parse::<f32>().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // The second line is twice as wide, so it starts 9.44pt further left.
+        assert_eq!(offsets.len(), 2, "{s}");
+        assert!((offsets[1][0] + 9.44).abs() < 0.01, "{s}");
+        assert!((offsets[1][1] + 12.0).abs() < 0.01, "{s}");
+    }
+
     #[test]
     fn signature_field_appearance_contains_label() {
         let annot = SignatureFieldAnnot {
@@ -564,10 +2520,93 @@ mod tests {
             field_name: "signature".to_string(),
             tooltip: None,
             required: false,
+            down_appearance: None,
+            rollover_appearance: None,
         };
         let bytes = generate_signature_field_appearance(&annot);
         let s = String::from_utf8_lossy(&bytes);
         assert!(s.contains("re"), "should draw border: {s}");
         assert!(s.contains("Sign here"), "should include helper label: {s}");
     }
+
+    #[test]
+    fn checkbox_appearance_states_include_off() {
+        let annot = Annotation::CheckBox(CheckBoxAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 100.0, 116.0, 116.0],
+                ..Default::default()
+            },
+            field_name: "agree".to_string(),
+            export_value: "Yes".to_string(),
+            checked: true,
+            read_only: false,
+            required: false,
+            down_appearance: None,
+            rollover_appearance: None,
+        });
+        let states = generate_appearance_states(&annot).expect("checkbox should have states");
+        let names = states
+            .states
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Yes", "Off"]);
+        assert_eq!(states.current, "Yes");
+
+        let on = String::from_utf8_lossy(&states.states[0].1).into_owned();
+        let off = String::from_utf8_lossy(&states.states[1].1).into_owned();
+        assert!(
+            on.contains("S"),
+            "checked state should stroke a check mark: {on}"
+        );
+        assert!(
+            off.len() < on.len(),
+            "unchecked state should not draw a check mark: {off}"
+        );
+    }
+
+    #[test]
+    fn choice_field_appearance_shows_selection() {
+        let mut annot = ChoiceFieldAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 100.0, 220.0, 120.0],
+                ..Default::default()
+            },
+            field_name: "size".to_string(),
+            options: vec!["Small".to_string(), "Large".to_string()],
+            selected: Some("Large".to_string()),
+            combo: true,
+            read_only: false,
+            required: false,
+            down_appearance: None,
+            rollover_appearance: None,
+        };
+        let combo = String::from_utf8_lossy(&generate_choice_field_appearance(&annot)).into_owned();
+        assert_eq!(combo.matches("Tj").count(), 1, "{combo}");
+        assert!(combo.contains("(Large) Tj"), "{combo}");
+        assert!(combo.contains("h\nf"), "should fill the caret: {combo}");
+
+        annot.combo = false;
+        annot.base.rect = [100.0, 100.0, 220.0, 160.0];
+        let list = String::from_utf8_lossy(&generate_choice_field_appearance(&annot)).into_owned();
+        assert_eq!(list.matches("Tj").count(), 2, "{list}");
+        assert!(
+            list.contains("0.6 0.75 0.95 rg"),
+            "should highlight: {list}"
+        );
+    }
+
+    #[test]
+    fn zero_opacity_highlight_has_empty_appearance() {
+        let annot = Annotation::Highlight(HighlightAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 200.0, 300.0, 220.0],
+                color: Some(AnnotColor::yellow()),
+                opacity: 0.0,
+                ..Default::default()
+            },
+            quad_points: vec![100.0, 220.0, 300.0, 220.0, 100.0, 200.0, 300.0, 200.0],
+        });
+        assert!(generate_appearance(&annot).is_empty());
+    }
 }
@@ -0,0 +1,1146 @@
+//! Fluent builders for annotations, as an alternative to struct literals.
+//!
+//! Each annotation type has a constructor like [`HighlightAnnot::builder`]
+//! that takes the fields the annotation can't do without, and every other
+//! field starts out with a sensible default:
+//!
+//! ```
+//! use hayro_annot::{AnnotColor, Annotation, HighlightAnnot};
+//!
+//! let annot: Annotation = HighlightAnnot::builder([72.0, 700.0, 200.0, 714.0])
+//!     .color(AnnotColor::yellow())
+//!     .opacity(0.5)
+//!     .build();
+//! ```
+
+use crate::appearance::LINE_SPACING;
+use crate::{
+    AnnotColor, Annotation, AnnotationBase, AnnotationFlags, BlendMode, BorderStyle, CheckBoxAnnot,
+    ChoiceFieldAnnot, FileAttachmentAnnot, FreeTextAnnot, HighlightAnnot, ImageFormat, InkAnnot,
+    LineAnnot, LineEnding, LinkAnnot, MeasureScale, PolyAnnot, RedactAnnot, ShapeAnnot,
+    SignatureFieldAnnot, SquigglyAnnot, StampAnnot, StrikeOutAnnot, TextAlign, TextAnnot,
+    TextFieldAnnot, UnderlineAnnot, VAlign,
+};
+use std::sync::Arc;
+
+/// A builder for an annotation of type `T`.
+///
+/// The fields of [`AnnotationBase`] can be set for every type, the other
+/// setters depend on `T`. [`AnnotationBuilder::build`] returns the finished
+/// [`Annotation`].
+#[derive(Debug, Clone)]
+pub struct AnnotationBuilder<T> {
+    base: AnnotationBase,
+    annot: T,
+    variant: fn(AnnotationBase, T) -> Annotation,
+}
+
+impl<T> AnnotationBuilder<T> {
+    fn new(rect: [f32; 4], annot: T, variant: fn(AnnotationBase, T) -> Annotation) -> Self {
+        Self {
+            base: AnnotationBase {
+                rect,
+                ..Default::default()
+            },
+            annot,
+            variant,
+        }
+    }
+
+    /// Set the color of the annotation.
+    pub fn color(mut self, color: AnnotColor) -> Self {
+        self.base.color = Some(color);
+        self
+    }
+
+    /// Set the author of the annotation.
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.base.author = Some(author.into());
+        self
+    }
+
+    /// Set the text contents of the annotation.
+    pub fn contents(mut self, contents: impl Into<String>) -> Self {
+        self.base.contents = Some(contents.into());
+        self
+    }
+
+    /// Set the modification date, as a PDF date string.
+    pub fn modified(mut self, date: impl Into<String>) -> Self {
+        self.base.modified = Some(date.into());
+        self
+    }
+
+    /// Set the creation date, as a PDF date string.
+    pub fn created(mut self, date: impl Into<String>) -> Self {
+        self.base.created = Some(date.into());
+        self
+    }
+
+    /// Set the unique name of the annotation on its page.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.base.name = Some(name.into());
+        self
+    }
+
+    /// Set the subject of the annotation.
+    pub fn subject(mut self, subject: impl Into<String>) -> Self {
+        self.base.subject = Some(subject.into());
+        self
+    }
+
+    /// Set the intent of the annotation, such as `FreeTextCallout`.
+    pub fn intent(mut self, intent: impl Into<String>) -> Self {
+        self.base.intent = Some(intent.into());
+        self
+    }
+
+    /// Set the annotation flags, which default to [`AnnotationFlags::PRINT`].
+    pub fn flags(mut self, flags: AnnotationFlags) -> Self {
+        self.base.flags = flags.bits();
+        self
+    }
+
+    /// Set the opacity, from 0.0 (transparent) to 1.0 (opaque).
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.base.opacity = opacity;
+        self
+    }
+
+    /// Set the blend mode the appearance is composited with.
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.base.blend_mode = Some(blend_mode);
+        self
+    }
+
+    /// Set the counterclockwise rotation of the appearance, in degrees.
+    pub fn rotation(mut self, rotation: f32) -> Self {
+        self.base.rotation = rotation;
+        self
+    }
+
+    /// Finish the annotation.
+    pub fn build(self) -> Annotation {
+        (self.variant)(self.base, self.annot)
+    }
+}
+
+/// The quad points of a single quad covering `rect`, in the order expected by
+/// viewers.
+fn rect_quad_points(rect: [f32; 4]) -> Vec<f32> {
+    let [x0, y0, x1, y1] = [
+        rect[0].min(rect[2]),
+        rect[1].min(rect[3]),
+        rect[0].max(rect[2]),
+        rect[1].max(rect[3]),
+    ];
+
+    vec![x0, y1, x1, y1, x0, y0, x1, y0]
+}
+
+/// The given quad points, or a quad covering `rect` if there are none.
+fn quad_points_or_rect(quad_points: Vec<f32>, rect: [f32; 4]) -> Vec<f32> {
+    if quad_points.is_empty() {
+        rect_quad_points(rect)
+    } else {
+        quad_points
+    }
+}
+
+impl HighlightAnnot {
+    /// Start building a highlight covering `rect`.
+    ///
+    /// Unless quad points are set, the whole rect is highlighted.
+    pub fn builder(rect: [f32; 4]) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                quad_points: Vec::new(),
+            },
+            |base, annot| {
+                Annotation::Highlight(Self {
+                    quad_points: quad_points_or_rect(annot.quad_points, base.rect),
+                    base,
+                })
+            },
+        )
+    }
+}
+
+impl AnnotationBuilder<HighlightAnnot> {
+    /// Set the quad points of the highlighted regions.
+    pub fn quad_points(mut self, quad_points: Vec<f32>) -> Self {
+        self.annot.quad_points = quad_points;
+        self
+    }
+}
+
+impl UnderlineAnnot {
+    /// Start building an underline covering `rect`.
+    ///
+    /// Unless quad points are set, the whole rect is underlined.
+    pub fn builder(rect: [f32; 4]) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                quad_points: Vec::new(),
+            },
+            |base, annot| {
+                Annotation::Underline(Self {
+                    quad_points: quad_points_or_rect(annot.quad_points, base.rect),
+                    base,
+                })
+            },
+        )
+    }
+}
+
+impl AnnotationBuilder<UnderlineAnnot> {
+    /// Set the quad points of the underlined regions.
+    pub fn quad_points(mut self, quad_points: Vec<f32>) -> Self {
+        self.annot.quad_points = quad_points;
+        self
+    }
+}
+
+impl StrikeOutAnnot {
+    /// Start building a strikeout covering `rect`.
+    ///
+    /// Unless quad points are set, the whole rect is struck out.
+    pub fn builder(rect: [f32; 4]) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                quad_points: Vec::new(),
+            },
+            |base, annot| {
+                Annotation::StrikeOut(Self {
+                    quad_points: quad_points_or_rect(annot.quad_points, base.rect),
+                    base,
+                })
+            },
+        )
+    }
+}
+
+impl AnnotationBuilder<StrikeOutAnnot> {
+    /// Set the quad points of the struck out regions.
+    pub fn quad_points(mut self, quad_points: Vec<f32>) -> Self {
+        self.annot.quad_points = quad_points;
+        self
+    }
+}
+
+impl SquigglyAnnot {
+    /// Start building a squiggly underline covering `rect`.
+    ///
+    /// Unless quad points are set, the whole rect is underlined.
+    pub fn builder(rect: [f32; 4]) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                quad_points: Vec::new(),
+            },
+            |base, annot| {
+                Annotation::Squiggly(Self {
+                    quad_points: quad_points_or_rect(annot.quad_points, base.rect),
+                    base,
+                })
+            },
+        )
+    }
+}
+
+impl AnnotationBuilder<SquigglyAnnot> {
+    /// Set the quad points of the underlined regions.
+    pub fn quad_points(mut self, quad_points: Vec<f32>) -> Self {
+        self.annot.quad_points = quad_points;
+        self
+    }
+}
+
+impl RedactAnnot {
+    /// Start building a redaction of `rect`.
+    pub fn builder(rect: [f32; 4]) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                quad_points: Vec::new(),
+                overlay_color: None,
+            },
+            |base, annot| Annotation::Redact(Self { base, ..annot }),
+        )
+    }
+}
+
+impl AnnotationBuilder<RedactAnnot> {
+    /// Set the quad points of the redacted regions, to only redact parts of
+    /// the rect.
+    pub fn quad_points(mut self, quad_points: Vec<f32>) -> Self {
+        self.annot.quad_points = quad_points;
+        self
+    }
+
+    /// Set the color the redacted regions are filled with once applied.
+    pub fn overlay_color(mut self, color: AnnotColor) -> Self {
+        self.annot.overlay_color = Some(color);
+        self
+    }
+}
+
+impl FreeTextAnnot {
+    /// Start building a text box in `rect` showing `text`, in 12pt Helvetica
+    /// unless configured otherwise.
+    pub fn builder(rect: [f32; 4], text: impl Into<String>) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                text: text.into(),
+                font_size: 12.0,
+                default_appearance: String::new(),
+                font_data: None,
+                multiline: false,
+                line_spacing: LINE_SPACING,
+                vertical_align: VAlign::Top,
+                callout_line: None,
+                rich_text: None,
+                default_style: None,
+            },
+            |base, annot| {
+                let default_appearance = if annot.default_appearance.is_empty() {
+                    format!("0 0 0 rg /Helv {} Tf", annot.font_size)
+                } else {
+                    annot.default_appearance
+                };
+
+                Annotation::FreeText(Self {
+                    base,
+                    default_appearance,
+                    ..annot
+                })
+            },
+        )
+    }
+}
+
+impl AnnotationBuilder<FreeTextAnnot> {
+    /// Set the font size in points.
+    pub fn font_size(mut self, font_size: f32) -> Self {
+        self.annot.font_size = font_size;
+        self
+    }
+
+    /// Set the default appearance string.
+    ///
+    /// When not set, black Helvetica in the font size is used.
+    pub fn default_appearance(mut self, default_appearance: impl Into<String>) -> Self {
+        self.annot.default_appearance = default_appearance.into();
+        self
+    }
+
+    /// Set a TrueType or OpenType font to render the text with.
+    pub fn font_data(mut self, font_data: Arc<dyn AsRef<[u8]>>) -> Self {
+        self.annot.font_data = Some(font_data);
+        self
+    }
+
+    /// Set whether the text is word-wrapped to the width of the rect.
+    pub fn multiline(mut self, multiline: bool) -> Self {
+        self.annot.multiline = multiline;
+        self
+    }
+
+    /// Set the distance between the baselines of consecutive lines, as a
+    /// multiple of the font size.
+    pub fn line_spacing(mut self, line_spacing: f32) -> Self {
+        self.annot.line_spacing = line_spacing;
+        self
+    }
+
+    /// Set how the text is aligned vertically in the rect.
+    pub fn vertical_align(mut self, vertical_align: VAlign) -> Self {
+        self.annot.vertical_align = vertical_align;
+        self
+    }
+
+    /// Set a callout line of two or three points, starting at the point the
+    /// callout refers to.
+    pub fn callout_line(mut self, callout_line: Vec<[f32; 2]>) -> Self {
+        self.annot.callout_line = Some(callout_line);
+        self
+    }
+
+    /// Set the rich text, an XHTML fragment.
+    pub fn rich_text(mut self, rich_text: impl Into<String>) -> Self {
+        self.annot.rich_text = Some(rich_text.into());
+        self
+    }
+
+    /// Set the default style string for the rich text.
+    pub fn default_style(mut self, default_style: impl Into<String>) -> Self {
+        self.annot.default_style = Some(default_style.into());
+        self
+    }
+}
+
+impl InkAnnot {
+    /// Start building an ink drawing in `rect` made of the given paths.
+    pub fn builder(rect: [f32; 4], ink_list: Vec<Vec<[f32; 2]>>) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                ink_list,
+                line_width: 1.0,
+                smoothing: 0.0,
+                dash: None,
+            },
+            |base, annot| Annotation::Ink(Self { base, ..annot }),
+        )
+    }
+}
+
+impl AnnotationBuilder<InkAnnot> {
+    /// Set the stroke line width, which defaults to 1.
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.annot.line_width = line_width;
+        self
+    }
+
+    /// Set how much the paths are smoothed, between 0 and 1.
+    pub fn smoothing(mut self, smoothing: f32) -> Self {
+        self.annot.smoothing = smoothing;
+        self
+    }
+
+    /// Set a dash pattern of alternating dash and gap lengths.
+    pub fn dash(mut self, dash: Vec<f32>) -> Self {
+        self.annot.dash = Some(dash);
+        self
+    }
+}
+
+impl ShapeAnnot {
+    /// Start building a square (rectangle) filling `rect`.
+    pub fn square_builder(rect: [f32; 4]) -> AnnotationBuilder<Self> {
+        Self::shape_builder(rect, false)
+    }
+
+    /// Start building a circle (ellipse) filling `rect`.
+    pub fn circle_builder(rect: [f32; 4]) -> AnnotationBuilder<Self> {
+        Self::shape_builder(rect, true)
+    }
+
+    fn shape_builder(rect: [f32; 4], is_circle: bool) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                interior_color: None,
+                line_width: 1.0,
+                is_circle,
+                border_style: BorderStyle::Solid,
+                fill_opacity: 1.0,
+                corner_radius: 0.0,
+            },
+            |base, annot| {
+                if annot.is_circle {
+                    Annotation::Circle(Self { base, ..annot })
+                } else {
+                    Annotation::Square(Self { base, ..annot })
+                }
+            },
+        )
+    }
+}
+
+impl AnnotationBuilder<ShapeAnnot> {
+    /// Set the interior (fill) color.
+    pub fn interior_color(mut self, color: AnnotColor) -> Self {
+        self.annot.interior_color = Some(color);
+        self
+    }
+
+    /// Set the border line width, which defaults to 1.
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.annot.line_width = line_width;
+        self
+    }
+
+    /// Set the style of the border.
+    pub fn border_style(mut self, border_style: BorderStyle) -> Self {
+        self.annot.border_style = border_style;
+        self
+    }
+
+    /// Set the opacity of the interior, from 0.0 (transparent) to 1.0
+    /// (opaque).
+    pub fn fill_opacity(mut self, fill_opacity: f32) -> Self {
+        self.annot.fill_opacity = fill_opacity;
+        self
+    }
+
+    /// Set the radius of the corners of a square.
+    pub fn corner_radius(mut self, corner_radius: f32) -> Self {
+        self.annot.corner_radius = corner_radius;
+        self
+    }
+}
+
+impl LineAnnot {
+    /// Start building a line in `rect` from `start` to `end`.
+    pub fn builder(rect: [f32; 4], start: [f32; 2], end: [f32; 2]) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                start,
+                end,
+                line_width: 1.0,
+                start_style: LineEnding::None,
+                end_style: LineEnding::None,
+                measure: None,
+                dash: None,
+            },
+            |base, annot| Annotation::Line(Self { base, ..annot }),
+        )
+    }
+}
+
+impl AnnotationBuilder<LineAnnot> {
+    /// Set the stroke line width, which defaults to 1.
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.annot.line_width = line_width;
+        self
+    }
+
+    /// Set the endings drawn at the start and end point.
+    pub fn line_endings(mut self, start_style: LineEnding, end_style: LineEnding) -> Self {
+        self.annot.start_style = start_style;
+        self.annot.end_style = end_style;
+        self
+    }
+
+    /// Set a measurement scale, to display the length of the line.
+    pub fn measure(mut self, measure: MeasureScale) -> Self {
+        self.annot.measure = Some(measure);
+        self
+    }
+
+    /// Set a dash pattern of alternating dash and gap lengths.
+    pub fn dash(mut self, dash: Vec<f32>) -> Self {
+        self.annot.dash = Some(dash);
+        self
+    }
+}
+
+impl PolyAnnot {
+    /// Start building a closed polygon in `rect` through `vertices`.
+    pub fn polygon_builder(rect: [f32; 4], vertices: Vec<[f32; 2]>) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(rect, Self::with_vertices(vertices), |base, annot| {
+            Annotation::Polygon(Self { base, ..annot })
+        })
+    }
+
+    /// Start building an open polyline in `rect` through `vertices`.
+    pub fn polyline_builder(rect: [f32; 4], vertices: Vec<[f32; 2]>) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(rect, Self::with_vertices(vertices), |base, annot| {
+            Annotation::PolyLine(Self { base, ..annot })
+        })
+    }
+
+    fn with_vertices(vertices: Vec<[f32; 2]>) -> Self {
+        Self {
+            base: AnnotationBase::default(),
+            vertices,
+            line_width: 1.0,
+            interior_color: None,
+        }
+    }
+}
+
+impl AnnotationBuilder<PolyAnnot> {
+    /// Set the stroke line width, which defaults to 1.
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.annot.line_width = line_width;
+        self
+    }
+
+    /// Set the interior (fill) color, which is only used for polygons.
+    pub fn interior_color(mut self, color: AnnotColor) -> Self {
+        self.annot.interior_color = Some(color);
+        self
+    }
+}
+
+impl TextAnnot {
+    /// Start building a sticky note in `rect`, shown with the `Note` icon.
+    pub fn builder(rect: [f32; 4]) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                open: false,
+                icon: "Note".to_string(),
+                rich_text: None,
+                default_style: None,
+            },
+            |base, annot| Annotation::Text(Self { base, ..annot }),
+        )
+    }
+}
+
+impl AnnotationBuilder<TextAnnot> {
+    /// Set whether the popup is initially open.
+    pub fn open(mut self, open: bool) -> Self {
+        self.annot.open = open;
+        self
+    }
+
+    /// Set the icon name, e.g. `"Comment"` or `"Key"`.
+    pub fn icon(mut self, icon: impl Into<String>) -> Self {
+        self.annot.icon = icon.into();
+        self
+    }
+
+    /// Set the rich text, an XHTML fragment.
+    pub fn rich_text(mut self, rich_text: impl Into<String>) -> Self {
+        self.annot.rich_text = Some(rich_text.into());
+        self
+    }
+
+    /// Set the default style string for the rich text.
+    pub fn default_style(mut self, default_style: impl Into<String>) -> Self {
+        self.annot.default_style = Some(default_style.into());
+        self
+    }
+}
+
+impl LinkAnnot {
+    /// Start building a link in `rect` to `uri`.
+    pub fn uri_builder(rect: [f32; 4], uri: impl Into<String>) -> AnnotationBuilder<Self> {
+        Self::link_builder(rect, Some(uri.into()), None)
+    }
+
+    /// Start building a link in `rect` to the page with the (0-based) index
+    /// `page`.
+    pub fn page_builder(rect: [f32; 4], page: usize) -> AnnotationBuilder<Self> {
+        Self::link_builder(rect, None, Some(page))
+    }
+
+    fn link_builder(
+        rect: [f32; 4],
+        uri: Option<String>,
+        dest_page: Option<usize>,
+    ) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                uri,
+                dest_page,
+            },
+            |base, annot| Annotation::Link(Self { base, ..annot }),
+        )
+    }
+}
+
+impl TextFieldAnnot {
+    /// Start building a text field named `field_name` in `rect`, in 12pt
+    /// Helvetica unless configured otherwise.
+    pub fn builder(rect: [f32; 4], field_name: impl Into<String>) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                field_name: field_name.into(),
+                value: None,
+                default_value: None,
+                max_len: None,
+                default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+                read_only: false,
+                required: false,
+                multiline: false,
+                alignment: TextAlign::Left,
+                down_appearance: None,
+                rollover_appearance: None,
+            },
+            |base, annot| Annotation::TextField(Self { base, ..annot }),
+        )
+    }
+}
+
+impl AnnotationBuilder<TextFieldAnnot> {
+    /// Set the current value of the field.
+    pub fn value(mut self, value: impl Into<String>) -> Self {
+        self.annot.value = Some(value.into());
+        self
+    }
+
+    /// Set the default value of the field.
+    pub fn default_value(mut self, default_value: impl Into<String>) -> Self {
+        self.annot.default_value = Some(default_value.into());
+        self
+    }
+
+    /// Set the maximum length of the text.
+    pub fn max_len(mut self, max_len: u32) -> Self {
+        self.annot.max_len = Some(max_len);
+        self
+    }
+
+    /// Set the default appearance string.
+    pub fn default_appearance(mut self, default_appearance: impl Into<String>) -> Self {
+        self.annot.default_appearance = default_appearance.into();
+        self
+    }
+
+    /// Set whether the field is read-only.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.annot.read_only = read_only;
+        self
+    }
+
+    /// Set whether the field is required.
+    pub fn required(mut self, required: bool) -> Self {
+        self.annot.required = required;
+        self
+    }
+
+    /// Set whether the field supports multiple lines.
+    pub fn multiline(mut self, multiline: bool) -> Self {
+        self.annot.multiline = multiline;
+        self
+    }
+
+    /// Set the horizontal alignment of the text.
+    pub fn alignment(mut self, alignment: TextAlign) -> Self {
+        self.annot.alignment = alignment;
+        self
+    }
+
+    /// Set the content stream shown while the mouse button is pressed on the
+    /// widget.
+    pub fn down_appearance(mut self, content: Vec<u8>) -> Self {
+        self.annot.down_appearance = Some(content);
+        self
+    }
+
+    /// Set the content stream shown while the pointer is over the widget.
+    pub fn rollover_appearance(mut self, content: Vec<u8>) -> Self {
+        self.annot.rollover_appearance = Some(content);
+        self
+    }
+}
+
+impl SignatureFieldAnnot {
+    /// Start building a signature field named `field_name` in `rect`.
+    pub fn builder(rect: [f32; 4], field_name: impl Into<String>) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                field_name: field_name.into(),
+                tooltip: None,
+                required: false,
+                down_appearance: None,
+                rollover_appearance: None,
+            },
+            |base, annot| Annotation::SignatureField(Self { base, ..annot }),
+        )
+    }
+}
+
+impl AnnotationBuilder<SignatureFieldAnnot> {
+    /// Set the tooltip of the field.
+    pub fn tooltip(mut self, tooltip: impl Into<String>) -> Self {
+        self.annot.tooltip = Some(tooltip.into());
+        self
+    }
+
+    /// Set whether the field is required.
+    pub fn required(mut self, required: bool) -> Self {
+        self.annot.required = required;
+        self
+    }
+
+    /// Set the content stream shown while the mouse button is pressed on the
+    /// widget.
+    pub fn down_appearance(mut self, content: Vec<u8>) -> Self {
+        self.annot.down_appearance = Some(content);
+        self
+    }
+
+    /// Set the content stream shown while the pointer is over the widget.
+    pub fn rollover_appearance(mut self, content: Vec<u8>) -> Self {
+        self.annot.rollover_appearance = Some(content);
+        self
+    }
+}
+
+impl CheckBoxAnnot {
+    /// Start building an unchecked checkbox named `field_name` in `rect`,
+    /// whose "on" state is `Yes`.
+    pub fn builder(rect: [f32; 4], field_name: impl Into<String>) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                field_name: field_name.into(),
+                export_value: "Yes".to_string(),
+                checked: false,
+                read_only: false,
+                required: false,
+                down_appearance: None,
+                rollover_appearance: None,
+            },
+            |base, annot| Annotation::CheckBox(Self { base, ..annot }),
+        )
+    }
+}
+
+impl AnnotationBuilder<CheckBoxAnnot> {
+    /// Set the name of the "on" state, which is the value of the field when
+    /// the checkbox is checked.
+    pub fn export_value(mut self, export_value: impl Into<String>) -> Self {
+        self.annot.export_value = export_value.into();
+        self
+    }
+
+    /// Set whether the checkbox is checked.
+    pub fn checked(mut self, checked: bool) -> Self {
+        self.annot.checked = checked;
+        self
+    }
+
+    /// Set whether the field is read-only.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.annot.read_only = read_only;
+        self
+    }
+
+    /// Set whether the field is required.
+    pub fn required(mut self, required: bool) -> Self {
+        self.annot.required = required;
+        self
+    }
+
+    /// Set the content stream shown while the mouse button is pressed on the
+    /// widget.
+    pub fn down_appearance(mut self, content: Vec<u8>) -> Self {
+        self.annot.down_appearance = Some(content);
+        self
+    }
+
+    /// Set the content stream shown while the pointer is over the widget.
+    pub fn rollover_appearance(mut self, content: Vec<u8>) -> Self {
+        self.annot.rollover_appearance = Some(content);
+        self
+    }
+}
+
+impl ChoiceFieldAnnot {
+    /// Start building a list box named `field_name` in `rect` with the given
+    /// options, which must not be empty.
+    pub fn builder(
+        rect: [f32; 4],
+        field_name: impl Into<String>,
+        options: Vec<String>,
+    ) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                field_name: field_name.into(),
+                options,
+                selected: None,
+                combo: false,
+                read_only: false,
+                required: false,
+                down_appearance: None,
+                rollover_appearance: None,
+            },
+            |base, annot| Annotation::Choice(Self { base, ..annot }),
+        )
+    }
+}
+
+impl AnnotationBuilder<ChoiceFieldAnnot> {
+    /// Set the selected option.
+    pub fn selected(mut self, selected: impl Into<String>) -> Self {
+        self.annot.selected = Some(selected.into());
+        self
+    }
+
+    /// Set whether the field is a dropdown instead of a list box.
+    pub fn combo(mut self, combo: bool) -> Self {
+        self.annot.combo = combo;
+        self
+    }
+
+    /// Set whether the field is read-only.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.annot.read_only = read_only;
+        self
+    }
+
+    /// Set whether the field is required.
+    pub fn required(mut self, required: bool) -> Self {
+        self.annot.required = required;
+        self
+    }
+
+    /// Set the content stream shown while the mouse button is pressed on the
+    /// widget.
+    pub fn down_appearance(mut self, content: Vec<u8>) -> Self {
+        self.annot.down_appearance = Some(content);
+        self
+    }
+
+    /// Set the content stream shown while the pointer is over the widget.
+    pub fn rollover_appearance(mut self, content: Vec<u8>) -> Self {
+        self.annot.rollover_appearance = Some(content);
+        self
+    }
+}
+
+impl StampAnnot {
+    /// Start building a stamp showing an image, stretched to fill `rect`.
+    pub fn builder(
+        rect: [f32; 4],
+        image_data: Arc<dyn AsRef<[u8]>>,
+        format: ImageFormat,
+    ) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                image_data,
+                format,
+            },
+            |base, annot| Annotation::Stamp(Self { base, ..annot }),
+        )
+    }
+}
+
+impl FileAttachmentAnnot {
+    /// Start building an attachment of the file `file_name` with the contents
+    /// `data`, shown with the `PushPin` icon in `rect`.
+    pub fn builder(
+        rect: [f32; 4],
+        file_name: impl Into<String>,
+        data: Vec<u8>,
+    ) -> AnnotationBuilder<Self> {
+        AnnotationBuilder::new(
+            rect,
+            Self {
+                base: AnnotationBase::default(),
+                file_name: file_name.into(),
+                data,
+                icon: "PushPin".to_string(),
+            },
+            |base, annot| Annotation::FileAttachment(Self { base, ..annot }),
+        )
+    }
+}
+
+impl AnnotationBuilder<FileAttachmentAnnot> {
+    /// Set the icon name (`"PushPin"`, `"Paperclip"`, `"Graph"` or `"Tag"`).
+    pub fn icon(mut self, icon: impl Into<String>) -> Self {
+        self.annot.icon = icon.into();
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Annotations don't implement `PartialEq`, since some hold font or
+    /// image data, so they are compared by their debug output.
+    fn assert_same(built: Annotation, literal: Annotation) {
+        assert_eq!(format!("{built:?}"), format!("{literal:?}"));
+    }
+
+    #[test]
+    fn highlight_builder_matches_struct_literal() {
+        let rect = [72.0, 700.0, 200.0, 714.0];
+        let quad_points = vec![72.0, 714.0, 200.0, 714.0, 72.0, 700.0, 200.0, 700.0];
+
+        assert_same(
+            HighlightAnnot::builder(rect)
+                .color(AnnotColor::yellow())
+                .quad_points(quad_points.clone())
+                .opacity(0.5)
+                .author("Reviewer")
+                .build(),
+            Annotation::Highlight(HighlightAnnot {
+                base: AnnotationBase {
+                    rect,
+                    color: Some(AnnotColor::yellow()),
+                    author: Some("Reviewer".to_string()),
+                    opacity: 0.5,
+                    ..Default::default()
+                },
+                quad_points,
+            }),
+        );
+
+        // Without quad points, the whole rect is highlighted.
+        assert_same(
+            HighlightAnnot::builder(rect)
+                .color(AnnotColor::yellow())
+                .build(),
+            Annotation::Highlight(HighlightAnnot::from_rect(rect, AnnotColor::yellow())),
+        );
+    }
+
+    #[test]
+    fn shape_and_poly_builders_pick_the_variant() {
+        let rect = [10.0, 10.0, 110.0, 60.0];
+        let shape = |is_circle| ShapeAnnot {
+            base: AnnotationBase {
+                rect,
+                color: Some(AnnotColor::red()),
+                flags: (AnnotationFlags::PRINT | AnnotationFlags::LOCKED).bits(),
+                ..Default::default()
+            },
+            interior_color: Some(AnnotColor::gray(0.5)),
+            line_width: 2.0,
+            is_circle,
+            border_style: BorderStyle::Solid,
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
+        };
+        let build = |builder: AnnotationBuilder<ShapeAnnot>| {
+            builder
+                .color(AnnotColor::red())
+                .flags(AnnotationFlags::PRINT | AnnotationFlags::LOCKED)
+                .interior_color(AnnotColor::gray(0.5))
+                .line_width(2.0)
+                .build()
+        };
+
+        assert_same(
+            build(ShapeAnnot::square_builder(rect)),
+            Annotation::Square(shape(false)),
+        );
+        assert_same(
+            build(ShapeAnnot::circle_builder(rect)),
+            Annotation::Circle(shape(true)),
+        );
+
+        let vertices = vec![[10.0, 10.0], [110.0, 10.0], [60.0, 60.0]];
+        let poly = PolyAnnot {
+            base: AnnotationBase {
+                rect,
+                ..Default::default()
+            },
+            vertices: vertices.clone(),
+            line_width: 1.0,
+            interior_color: None,
+        };
+        assert!(matches!(
+            PolyAnnot::polygon_builder(rect, vertices.clone()).build(),
+            Annotation::Polygon(_)
+        ));
+        assert_same(
+            PolyAnnot::polyline_builder(rect, vertices).build(),
+            Annotation::PolyLine(poly),
+        );
+    }
+
+    #[test]
+    fn freetext_builder_derives_default_appearance() {
+        let rect = [50.0, 50.0, 250.0, 100.0];
+
+        assert_same(
+            FreeTextAnnot::builder(rect, "Hello")
+                .font_size(18.0)
+                .multiline(true)
+                .build(),
+            Annotation::FreeText(FreeTextAnnot {
+                base: AnnotationBase {
+                    rect,
+                    ..Default::default()
+                },
+                text: "Hello".to_string(),
+                font_size: 18.0,
+                default_appearance: "0 0 0 rg /Helv 18 Tf".to_string(),
+                font_data: None,
+                multiline: true,
+                line_spacing: 1.2,
+                vertical_align: VAlign::Top,
+                callout_line: None,
+                rich_text: None,
+                default_style: None,
+            }),
+        );
+    }
+
+    #[test]
+    fn field_builders_match_struct_literals() {
+        let rect = [100.0, 600.0, 300.0, 620.0];
+
+        assert_same(
+            TextFieldAnnot::builder(rect, "name")
+                .value("Jane")
+                .max_len(32)
+                .required(true)
+                .alignment(TextAlign::Center)
+                .build(),
+            Annotation::TextField(TextFieldAnnot {
+                base: AnnotationBase {
+                    rect,
+                    ..Default::default()
+                },
+                field_name: "name".to_string(),
+                value: Some("Jane".to_string()),
+                default_value: None,
+                max_len: Some(32),
+                default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+                read_only: false,
+                required: true,
+                multiline: false,
+                alignment: TextAlign::Center,
+                down_appearance: None,
+                rollover_appearance: None,
+            }),
+        );
+
+        assert_same(
+            CheckBoxAnnot::builder(rect, "agree").checked(true).build(),
+            Annotation::CheckBox(CheckBoxAnnot {
+                base: AnnotationBase {
+                    rect,
+                    ..Default::default()
+                },
+                field_name: "agree".to_string(),
+                export_value: "Yes".to_string(),
+                checked: true,
+                read_only: false,
+                required: false,
+                down_appearance: None,
+                rollover_appearance: None,
+            }),
+        );
+
+        assert_same(
+            LinkAnnot::uri_builder(rect, "https://example.com").build(),
+            Annotation::Link(LinkAnnot {
+                base: AnnotationBase {
+                    rect,
+                    ..Default::default()
+                },
+                uri: Some("https://example.com".to_string()),
+                dest_page: None,
+            }),
+        );
+    }
+}
@@ -6,6 +6,17 @@
 //!
 //! These utilities convert between the two coordinate systems.
 
+#[cfg(feature = "std")]
+use crate::reader::inherited;
+#[cfg(feature = "std")]
+use hayro_interpret::TextSpan;
+#[cfg(feature = "std")]
+use hayro_syntax::object::Rect;
+#[cfg(feature = "std")]
+use hayro_syntax::object::dict::keys::{ART_BOX, BLEED_BOX, TRIM_BOX};
+#[cfg(feature = "std")]
+use hayro_syntax::page::Page;
+
 /// Convert screen coordinates to PDF coordinates.
 ///
 /// # Arguments
@@ -32,7 +43,9 @@ pub fn screen_to_pdf(
 
     // The rendered image has page_width_pts × page_height_pts at scale=1.
     // Screen origin is top-left, PDF origin is bottom-left of crop box.
-    // Also need to handle rotation.
+    // Also need to handle rotation. For 90° and 270°, the rendered width spans
+    // the height of the crop box and vice versa, so screen x is measured along
+    // the PDF y axis and screen y along the PDF x axis.
     match rotation % 360 {
         0 => {
             let pdf_x = crop_box[0] + x_pts;
@@ -40,7 +53,8 @@ pub fn screen_to_pdf(
             (pdf_x, pdf_y)
         }
         90 => {
-            // 90° rotation: screen x maps to pdf y, screen y maps to pdf x
+            // 90° rotation: the bottom-left of the crop box is displayed at the
+            // top-left, so screen x maps to pdf y and screen y maps to pdf x.
             let pdf_x = crop_box[0] + y_pts;
             let pdf_y = crop_box[1] + x_pts;
             (pdf_x, pdf_y)
@@ -51,6 +65,8 @@ pub fn screen_to_pdf(
             (pdf_x, pdf_y)
         }
         270 => {
+            // 270° rotation: the top-right of the crop box is displayed at the
+            // top-left.
             let pdf_x = crop_box[2] - y_pts;
             let pdf_y = crop_box[3] - x_pts;
             (pdf_x, pdf_y)
@@ -64,6 +80,31 @@ pub fn screen_to_pdf(
     }
 }
 
+/// Convert PDF coordinates to screen coordinates.
+///
+/// This is the inverse of [`screen_to_pdf`] and takes the same arguments.
+///
+/// # Returns
+/// `(screen_x, screen_y)` in screen pixels (y-down, origin top-left).
+pub fn pdf_to_screen(
+    pdf_x: f32,
+    pdf_y: f32,
+    _page_width_pts: f32,
+    _page_height_pts: f32,
+    crop_box: [f32; 4],
+    rotation: u32,
+    scale: f32,
+) -> (f32, f32) {
+    let (x_pts, y_pts) = match rotation % 360 {
+        90 => (pdf_y - crop_box[1], pdf_x - crop_box[0]),
+        180 => (crop_box[2] - pdf_x, pdf_y - crop_box[1]),
+        270 => (crop_box[3] - pdf_y, crop_box[2] - pdf_x),
+        _ => (pdf_x - crop_box[0], crop_box[3] - pdf_y),
+    };
+
+    (x_pts * scale, y_pts * scale)
+}
+
 /// Convert a screen-space rectangle to a PDF-space rectangle.
 ///
 /// The input `screen_rect` is `[x0, y0, x1, y1]` in screen pixels.
@@ -99,10 +140,165 @@ pub fn screen_rect_to_pdf_rect(
     [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]
 }
 
-#[cfg(test)]
+/// The boundary boxes of a page, each as `[x0, y0, x1, y1]` in PDF coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageBoxes {
+    /// The boundaries of the physical medium.
+    pub media_box: [f32; 4],
+    /// The visible region of the page.
+    pub crop_box: [f32; 4],
+    /// The region to clip the page to in a production environment, if set.
+    pub bleed_box: Option<[f32; 4]>,
+    /// The intended dimensions of the finished page after trimming, if set.
+    pub trim_box: Option<[f32; 4]>,
+    /// The extent of the page's meaningful content, if set.
+    pub art_box: Option<[f32; 4]>,
+    /// The size of a unit of the boxes in multiples of 1/72 inch, which is
+    /// 1.0 unless the page sets `/UserUnit`.
+    pub user_unit: f32,
+}
+
+/// Read the boundary boxes of a page.
+///
+/// Boxes that aren't set on the page itself are inherited from its ancestors
+/// in the page tree. A missing crop box defaults to the media box.
+#[cfg(feature = "std")]
+pub fn page_boxes(page: &Page<'_>) -> PageBoxes {
+    let optional_box = |key: &[u8]| inherited::<Rect>(page.raw(), key).map(rect_to_array);
+
+    PageBoxes {
+        media_box: rect_to_array(page.media_box()),
+        crop_box: rect_to_array(page.crop_box()),
+        bleed_box: optional_box(BLEED_BOX),
+        trim_box: optional_box(TRIM_BOX),
+        art_box: optional_box(ART_BOX),
+        user_unit: page.user_unit(),
+    }
+}
+
+/// Compute highlight quad points for a range of characters in extracted text.
+///
+/// `start` and `end` are character offsets into the text of `spans`,
+/// concatenated in order without separators; `end` is exclusive. Spans are
+/// assumed to have glyphs of equal width, so a span that is only partly
+/// selected is cut proportionally. Selected parts of consecutive spans on the
+/// same line are merged, so the result has one quad (8 floats, in the order
+/// expected by [`HighlightAnnot::quad_points`](crate::HighlightAnnot)) per
+/// line.
+#[cfg(feature = "std")]
+pub fn quads_for_spans(spans: &[TextSpan], start: usize, end: usize) -> Vec<f32> {
+    let mut rects: Vec<[f32; 4]> = Vec::new();
+    let mut offset = 0;
+
+    for span in spans {
+        let len = span.text.chars().count();
+        let span_start = offset;
+        offset += len;
+
+        let (sel_start, sel_end) = (start.max(span_start), end.min(offset));
+        if sel_start >= sel_end {
+            continue;
+        }
+
+        let [x0, y0, x1, y1] = span.bbox;
+        let char_width = (x1 - x0) / len as f32;
+        let rect = [
+            x0 + char_width * (sel_start - span_start) as f32,
+            y0,
+            x0 + char_width * (sel_end - span_start) as f32,
+            y1,
+        ];
+
+        match rects.last_mut() {
+            Some(last) if on_same_line(last, &rect) => {
+                *last = [
+                    last[0].min(rect[0]),
+                    last[1].min(rect[1]),
+                    last[2].max(rect[2]),
+                    last[3].max(rect[3]),
+                ];
+            }
+            _ => rects.push(rect),
+        }
+    }
+
+    rects
+        .into_iter()
+        .flat_map(|[x0, y0, x1, y1]| [x0, y1, x1, y1, x0, y0, x1, y0])
+        .collect()
+}
+
+/// Whether two rectangles overlap vertically by at least half the height of
+/// the smaller one.
+#[cfg(feature = "std")]
+fn on_same_line(a: &[f32; 4], b: &[f32; 4]) -> bool {
+    let overlap = a[3].min(b[3]) - a[1].max(b[1]);
+    let min_height = (a[3] - a[1]).min(b[3] - b[1]);
+
+    overlap >= min_height * 0.5
+}
+
+#[cfg(feature = "std")]
+fn rect_to_array(rect: Rect) -> [f32; 4] {
+    [
+        rect.x0 as f32,
+        rect.y0 as f32,
+        rect.x1 as f32,
+        rect.y1 as f32,
+    ]
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
 
+    fn span(text: &str, bbox: [f32; 4]) -> TextSpan {
+        TextSpan {
+            text: text.to_string(),
+            bbox,
+            baseline: [bbox[0], bbox[1]],
+            font_name: String::new(),
+            font_size: bbox[3] - bbox[1],
+            color: [0.0, 0.0, 0.0],
+            vertical: false,
+        }
+    }
+
+    #[test]
+    fn quads_for_range_inside_one_span() {
+        let spans = [span("Hello World", [0.0, 100.0, 110.0, 112.0])];
+        // "World"
+        let quads = quads_for_spans(&spans, 6, 11);
+        assert_eq!(
+            quads,
+            vec![60.0, 112.0, 110.0, 112.0, 60.0, 100.0, 110.0, 100.0]
+        );
+    }
+
+    #[test]
+    fn quads_for_range_across_lines() {
+        let spans = [
+            span("Hello ", [0.0, 100.0, 60.0, 112.0]),
+            span("there", [60.0, 100.0, 110.0, 112.0]),
+            span("General", [0.0, 80.0, 70.0, 92.0]),
+        ];
+        // "lo there Gen"
+        let quads = quads_for_spans(&spans, 3, 14);
+        assert_eq!(quads.len(), 16);
+        assert_eq!(
+            &quads[..8],
+            &[30.0, 112.0, 110.0, 112.0, 30.0, 100.0, 110.0, 100.0]
+        );
+        assert_eq!(&quads[8..], &[0.0, 92.0, 30.0, 92.0, 0.0, 80.0, 30.0, 80.0]);
+    }
+
+    #[test]
+    fn quads_for_empty_range() {
+        let spans = [span("Hello", [0.0, 100.0, 50.0, 112.0])];
+        assert!(quads_for_spans(&spans, 2, 2).is_empty());
+        assert!(quads_for_spans(&spans, 10, 20).is_empty());
+    }
+
     #[test]
     fn screen_to_pdf_no_rotation() {
         // A4 page: 595 x 842 points, crop box at origin, scale 1.0
@@ -145,6 +341,17 @@ mod tests {
         assert!(result[1] < result[3], "y0 < y1: {:?}", result);
     }
 
+    #[test]
+    fn pdf_to_screen_inverts_screen_to_pdf() {
+        let crop_box = [10.0, 20.0, 210.0, 320.0];
+        for rotation in [0, 90, 180, 270] {
+            let (px, py) = screen_to_pdf(30.0, 70.0, 200.0, 300.0, crop_box, rotation, 2.0);
+            let (sx, sy) = pdf_to_screen(px, py, 200.0, 300.0, crop_box, rotation, 2.0);
+            assert!((sx - 30.0).abs() < 0.01, "rotation {rotation}: sx={sx}");
+            assert!((sy - 70.0).abs() < 0.01, "rotation {rotation}: sy={sy}");
+        }
+    }
+
     #[test]
     fn screen_to_pdf_rotation_90() {
         let crop_box = [0.0, 0.0, 595.0, 842.0];
@@ -153,4 +360,37 @@ mod tests {
         assert!((px - 0.0).abs() < 0.01, "px={px}");
         assert!((py - 0.0).abs() < 0.01, "py={py}");
     }
+
+    #[test]
+    fn screen_to_pdf_rotation_90_with_crop_offset() {
+        // A 300 x 400 crop box, rendered as 400 x 300 when rotated.
+        let crop_box = [50.0, 100.0, 350.0, 500.0];
+        let cases = [
+            ((0.0, 0.0), (50.0, 100.0)),
+            ((400.0, 0.0), (50.0, 500.0)),
+            ((0.0, 300.0), (350.0, 100.0)),
+            ((400.0, 300.0), (350.0, 500.0)),
+        ];
+        for ((sx, sy), (ex, ey)) in cases {
+            let (px, py) = screen_to_pdf(sx, sy, 400.0, 300.0, crop_box, 90, 1.0);
+            assert!((px - ex).abs() < 0.01, "({sx}, {sy}): px={px}");
+            assert!((py - ey).abs() < 0.01, "({sx}, {sy}): py={py}");
+        }
+    }
+
+    #[test]
+    fn screen_to_pdf_rotation_270_with_crop_offset() {
+        let crop_box = [50.0, 100.0, 350.0, 500.0];
+        let cases = [
+            ((0.0, 0.0), (350.0, 500.0)),
+            ((400.0, 0.0), (350.0, 100.0)),
+            ((0.0, 300.0), (50.0, 500.0)),
+            ((400.0, 300.0), (50.0, 100.0)),
+        ];
+        for ((sx, sy), (ex, ey)) in cases {
+            let (px, py) = screen_to_pdf(sx, sy, 400.0, 300.0, crop_box, 270, 1.0);
+            assert!((px - ex).abs() < 0.01, "({sx}, {sy}): px={px}");
+            assert!((py - ey).abs() < 0.01, "({sx}, {sy}): py={py}");
+        }
+    }
 }
@@ -0,0 +1,381 @@
+//! Date strings as used for the `/M` and `/CreationDate` entries of
+//! annotations.
+//!
+//! PDF dates have the form `D:YYYYMMDDHHmmSSOHH'mm'`, where everything after
+//! the year is optional and `O` is the relation to universal time (`+`, `-` or
+//! `Z`).
+
+use core::fmt;
+
+/// A date and time as stored in PDF date strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PdfDate {
+    /// The year, from 0 to 9999.
+    pub year: u16,
+    /// The month, from 1 to 12.
+    pub month: u8,
+    /// The day of the month, from 1 to 31.
+    pub day: u8,
+    /// The hour, from 0 to 23.
+    pub hour: u8,
+    /// The minute, from 0 to 59.
+    pub minute: u8,
+    /// The second, from 0 to 59.
+    pub second: u8,
+    /// The offset of the local time from universal time, in minutes.
+    ///
+    /// `None` if the relation to universal time is unknown.
+    pub utc_offset: Option<i16>,
+}
+
+impl PdfDate {
+    /// The date at `secs` seconds since the Unix epoch, in universal time.
+    pub fn from_unix(secs: i64) -> Self {
+        Self::from_unix_with_offset(secs, 0)
+    }
+
+    /// The date at `secs` seconds since the Unix epoch, in the local time
+    /// `utc_offset` minutes ahead of universal time.
+    pub fn from_unix_with_offset(secs: i64, utc_offset: i16) -> Self {
+        let local = secs + i64::from(utc_offset) * 60;
+        let (days, time) = (local.div_euclid(86_400), local.rem_euclid(86_400));
+        let (year, month, day) = civil_from_days(days);
+
+        Self {
+            year: year.clamp(0, 9999) as u16,
+            month,
+            day,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+            utc_offset: Some(utc_offset),
+        }
+    }
+
+    /// The current date, in universal time.
+    ///
+    /// This relies on the system clock, which isn't available on
+    /// `wasm32-unknown-unknown`; use [`PdfDate::from_unix`] with a timestamp
+    /// from the host there. Only available with the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn now() -> Self {
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        Self::from_unix(secs)
+    }
+
+    /// Parse a PDF date string, returning `None` if it doesn't conform to the
+    /// specification.
+    ///
+    /// Omitted fields take their default values, i.e. the first month and day
+    /// and midnight.
+    pub fn parse(date: &str) -> Option<Self> {
+        let mut cursor = Cursor(date.strip_prefix("D:")?.as_bytes());
+        let parsed = cursor.pdf_date()?;
+
+        cursor.0.is_empty().then_some(parsed)
+    }
+
+    /// Whether `date` is a valid PDF date string.
+    pub fn is_valid(date: &str) -> bool {
+        Self::parse(date).is_some()
+    }
+
+    /// Try to make sense of a malformed date string.
+    ///
+    /// This accepts PDF dates with surrounding whitespace or without the `D:`
+    /// prefix, as well as ISO 8601 dates like `2024-05-01T12:30:00Z`.
+    pub fn parse_lenient(date: &str) -> Option<Self> {
+        let date = date.trim();
+        let mut cursor = Cursor(date.strip_prefix("D:").unwrap_or(date).as_bytes());
+        if let Some(parsed) = cursor.pdf_date()
+            && cursor.0.is_empty()
+        {
+            return Some(parsed);
+        }
+
+        let mut cursor = Cursor(date.as_bytes());
+        let parsed = cursor.iso_date()?;
+
+        cursor.0.is_empty().then_some(parsed)
+    }
+}
+
+impl fmt::Display for PdfDate {
+    /// Format the date as `D:YYYYMMDDHHmmSSOHH'mm'`, leaving out the offset
+    /// if it is unknown.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "D:{:04}{:02}{:02}{:02}{:02}{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )?;
+
+        match self.utc_offset {
+            None => Ok(()),
+            Some(offset) => {
+                let sign = match offset {
+                    0 => 'Z',
+                    1.. => '+',
+                    _ => '-',
+                };
+                let offset = offset.unsigned_abs();
+                write!(f, "{sign}{:02}'{:02}'", offset / 60, offset % 60)
+            }
+        }
+    }
+}
+
+/// Convert days since the Unix epoch to a `(year, month, day)` triple in the
+/// proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    // See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// A cursor over the bytes of a date string.
+struct Cursor<'a>(&'a [u8]);
+
+impl Cursor<'_> {
+    /// Read a number with exactly `len` digits in the range `min..=max`.
+    fn number(&mut self, len: usize, min: u16, max: u16) -> Option<u16> {
+        let digits = self.0.get(..len)?;
+        if !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let value = digits
+            .iter()
+            .fold(0, |acc, d| acc * 10 + u16::from(d - b'0'));
+        if !(min..=max).contains(&value) {
+            return None;
+        }
+
+        self.0 = &self.0[len..];
+        Some(value)
+    }
+
+    /// Read an optional field, which is only present if the next byte is a
+    /// digit.
+    fn optional(&mut self, len: usize, min: u16, max: u16, default: u16) -> Option<u16> {
+        if self.0.first().is_some_and(u8::is_ascii_digit) {
+            self.number(len, min, max)
+        } else {
+            Some(default)
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.0.first() == Some(&byte) {
+            self.0 = &self.0[1..];
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Read the part of a PDF date after the `D:` prefix.
+    fn pdf_date(&mut self) -> Option<PdfDate> {
+        let year = self.number(4, 0, 9999)?;
+        let month = self.optional(2, 1, 12, 1)? as u8;
+        let day = self.optional(2, 1, 31, 1)? as u8;
+        let hour = self.optional(2, 0, 23, 0)? as u8;
+        let minute = self.optional(2, 0, 59, 0)? as u8;
+        let second = self.optional(2, 0, 59, 0)? as u8;
+
+        let utc_offset = if self.eat(b'Z') {
+            // The offset after `Z` is optional, but has to be zero.
+            if !self.0.is_empty() && self.offset()? != 0 {
+                return None;
+            }
+            Some(0)
+        } else if self.eat(b'+') {
+            Some(self.offset()?)
+        } else if self.eat(b'-') {
+            Some(-self.offset()?)
+        } else {
+            None
+        };
+
+        Some(PdfDate {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            utc_offset,
+        })
+    }
+
+    /// Read the `HH'mm'` part of an offset, where the minutes and the
+    /// apostrophes are optional.
+    fn offset(&mut self) -> Option<i16> {
+        let hours = self.number(2, 0, 23)?;
+        let minutes = if self.eat(b'\'') {
+            let minutes = self.optional(2, 0, 59, 0)?;
+            self.eat(b'\'');
+            minutes
+        } else {
+            0
+        };
+
+        Some((hours * 60 + minutes) as i16)
+    }
+
+    /// Read an ISO 8601 date of the form `YYYY-MM-DD[THH:mm[:SS[.sss]]][Z|±HH:mm]`.
+    fn iso_date(&mut self) -> Option<PdfDate> {
+        let year = self.number(4, 0, 9999)?;
+        self.eat(b'-').then_some(())?;
+        let month = self.number(2, 1, 12)? as u8;
+        self.eat(b'-').then_some(())?;
+        let day = self.number(2, 1, 31)? as u8;
+
+        let (mut hour, mut minute, mut second) = (0, 0, 0);
+        if self.eat(b'T') || self.eat(b' ') {
+            hour = self.number(2, 0, 23)? as u8;
+            self.eat(b':').then_some(())?;
+            minute = self.number(2, 0, 59)? as u8;
+            if self.eat(b':') {
+                second = self.number(2, 0, 59)? as u8;
+                if self.eat(b'.') {
+                    while self.0.first().is_some_and(u8::is_ascii_digit) {
+                        self.0 = &self.0[1..];
+                    }
+                }
+            }
+        }
+
+        let sign = if self.eat(b'+') {
+            Some(1)
+        } else if self.eat(b'-') {
+            Some(-1)
+        } else {
+            None
+        };
+        let utc_offset = match sign {
+            Some(sign) => {
+                let hours = self.number(2, 0, 23)?;
+                self.eat(b':');
+                let minutes = self.number(2, 0, 59)?;
+                Some(sign * (hours * 60 + minutes) as i16)
+            }
+            None => self.eat(b'Z').then_some(0),
+        };
+
+        Some(PdfDate {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            utc_offset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_timestamps_are_formatted_with_offset() {
+        // 2024-02-29 13:45:30 UTC.
+        let secs = 1_709_214_330;
+        assert_eq!(
+            PdfDate::from_unix(secs).to_string(),
+            "D:20240229134530Z00'00'"
+        );
+        assert_eq!(
+            PdfDate::from_unix_with_offset(secs, 5 * 60 + 30).to_string(),
+            "D:20240229191530+05'30'"
+        );
+        assert_eq!(
+            PdfDate::from_unix_with_offset(secs, -14 * 60).to_string(),
+            "D:20240228234530-14'00'"
+        );
+        assert_eq!(PdfDate::from_unix(0).to_string(), "D:19700101000000Z00'00'");
+
+        for secs in [0, secs, -86_400] {
+            let formatted = PdfDate::from_unix(secs).to_string();
+            assert!(PdfDate::is_valid(&formatted), "{formatted}");
+        }
+    }
+
+    #[test]
+    fn date_strings_are_validated() {
+        for valid in [
+            "D:2024",
+            "D:20240229",
+            "D:20260217120000Z",
+            "D:20240229134530+05'30'",
+            "D:20240229134530-08'00",
+            "D:19990101",
+        ] {
+            assert!(PdfDate::is_valid(valid), "{valid}");
+        }
+
+        for invalid in [
+            "",
+            "20240229134530Z",
+            "D:24",
+            "D:20241301",
+            "D:20240229256000",
+            "D:20240229134530Z05'00'",
+            "D:20240229134530 ",
+            "yesterday",
+        ] {
+            assert!(!PdfDate::is_valid(invalid), "{invalid}");
+        }
+
+        assert_eq!(
+            PdfDate::parse("D:202402"),
+            Some(PdfDate {
+                year: 2024,
+                month: 2,
+                day: 1,
+                hour: 0,
+                minute: 0,
+                second: 0,
+                utc_offset: None,
+            })
+        );
+    }
+
+    #[test]
+    fn malformed_dates_are_parsed_leniently() {
+        let expected = Some(PdfDate {
+            year: 2024,
+            month: 5,
+            day: 1,
+            hour: 12,
+            minute: 30,
+            second: 0,
+            utc_offset: Some(120),
+        });
+
+        assert_eq!(PdfDate::parse_lenient(" 20240501123000+02'00' "), expected);
+        assert_eq!(
+            PdfDate::parse_lenient("2024-05-01T12:30:00.123+02:00"),
+            expected
+        );
+        assert_eq!(
+            PdfDate::parse_lenient("2024-05-01T10:30:00Z").map(|d| d.to_string()),
+            Some("D:20240501103000Z00'00'".to_string())
+        );
+        assert_eq!(PdfDate::parse_lenient("next tuesday"), None);
+    }
+}
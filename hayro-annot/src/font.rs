@@ -0,0 +1,377 @@
+//! Font handling for text-based appearance streams.
+//!
+//! Text is either shown with the built-in Helvetica font using `WinAnsiEncoding`,
+//! or with a user-supplied TrueType/OpenType font that is embedded as a composite
+//! `Type0` font with an `Identity-H` encoding, where each character is written as
+//! its two-byte glyph ID.
+
+use crate::writer::{RefAllocator, deflate_encode};
+use pdf_writer::types::{CidFontType, FontFlags, SystemInfo};
+use pdf_writer::{Chunk, Filter, Finish, Name, Rect, Ref, Str};
+use skrifa::instance::{LocationRef, Size};
+use skrifa::raw::TableProvider;
+use skrifa::string::StringId;
+use skrifa::{FontRef, GlyphId, MetadataProvider};
+use std::collections::BTreeMap;
+
+/// The resource name of an embedded font in appearance streams.
+pub(crate) const EMBEDDED_FONT_NAME: &[u8] = b"F0";
+
+/// A user-supplied font that can be embedded into the output document.
+pub(crate) struct EmbeddedFont<'a> {
+    data: &'a [u8],
+    font: FontRef<'a>,
+}
+
+impl<'a> EmbeddedFont<'a> {
+    /// Parse the font, returning `None` if it isn't a usable TrueType or OpenType font.
+    pub(crate) fn new(data: &'a [u8]) -> Option<Self> {
+        let font = FontRef::new(data).ok()?;
+        // We need a Unicode cmap to map text to glyphs.
+        font.cmap().ok()?;
+
+        Some(Self { data, font })
+    }
+
+    /// Map a character to its glyph ID, using `.notdef` for missing glyphs.
+    pub(crate) fn glyph(&self, c: char) -> u16 {
+        self.font
+            .charmap()
+            .map(c)
+            .and_then(|g| u16::try_from(g.to_u32()).ok())
+            .unwrap_or(0)
+    }
+
+    /// Encode text as big-endian two-byte glyph IDs.
+    pub(crate) fn encode(&self, text: &str) -> Vec<u8> {
+        text.chars()
+            .flat_map(|c| self.glyph(c).to_be_bytes())
+            .collect()
+    }
+
+    /// The advance width of a glyph in thousandths of a text space unit.
+    pub(crate) fn advance(&self, glyph: u16) -> f32 {
+        let metrics = self
+            .font
+            .glyph_metrics(Size::unscaled(), LocationRef::default());
+        let units_per_em = self.units_per_em();
+
+        metrics
+            .advance_width(GlyphId::new(u32::from(glyph)))
+            .map(|w| w * 1000.0 / units_per_em)
+            .unwrap_or(0.0)
+    }
+
+    /// The width of `text` in thousandths of a text space unit.
+    pub(crate) fn text_width(&self, text: &str) -> f32 {
+        text.chars().map(|c| self.advance(self.glyph(c))).sum()
+    }
+
+    fn units_per_em(&self) -> f32 {
+        self.font
+            .head()
+            .map(|h| f32::from(h.units_per_em()))
+            .unwrap_or(1000.0)
+    }
+
+    fn postscript_name(&self) -> String {
+        let name: String = self
+            .font
+            .localized_strings(StringId::POSTSCRIPT_NAME)
+            .english_or_first()
+            .map(|s| {
+                s.chars()
+                    .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if name.is_empty() {
+            "EmbeddedFont".to_string()
+        } else {
+            name
+        }
+    }
+
+    /// Write the font as a `Type0` font to `font_ref`, including widths and a
+    /// `ToUnicode` map for all characters in `text`.
+    ///
+    /// The whole font program is embedded, but only the glyphs used by `text`
+    /// are listed in the widths array.
+    pub(crate) fn write(
+        &self,
+        chunk: &mut Chunk,
+        allocator: &mut RefAllocator,
+        font_ref: Ref,
+        text: &str,
+    ) {
+        let cid_ref = allocator.alloc();
+        let descriptor_ref = allocator.alloc();
+        let file_ref = allocator.alloc();
+        let to_unicode_ref = allocator.alloc();
+
+        let base_font = self.postscript_name();
+        let is_cff = self.font.cff().is_ok();
+
+        let mut glyphs = BTreeMap::new();
+        for c in text.chars() {
+            glyphs.entry(self.glyph(c)).or_insert(c);
+        }
+
+        chunk
+            .type0_font(font_ref)
+            .base_font(Name(base_font.as_bytes()))
+            .encoding_predefined(Name(b"Identity-H"))
+            .descendant_font(cid_ref)
+            .to_unicode(to_unicode_ref);
+
+        let mut cid = chunk.cid_font(cid_ref);
+        cid.subtype(if is_cff {
+            CidFontType::Type0
+        } else {
+            CidFontType::Type2
+        });
+        cid.base_font(Name(base_font.as_bytes()));
+        cid.system_info(SystemInfo {
+            registry: Str(b"Adobe"),
+            ordering: Str(b"Identity"),
+            supplement: 0,
+        });
+        cid.font_descriptor(descriptor_ref);
+        cid.default_width(self.advance(0));
+        if !is_cff {
+            cid.cid_to_gid_map_predefined(Name(b"Identity"));
+        }
+        let mut widths = cid.widths();
+        for &glyph in glyphs.keys() {
+            widths.consecutive(glyph, [self.advance(glyph)]);
+        }
+        widths.finish();
+        cid.finish();
+
+        let metrics = self.font.metrics(Size::new(1000.0), LocationRef::default());
+        let bbox = metrics
+            .bounds
+            .map(|b| Rect::new(b.x_min, b.y_min, b.x_max, b.y_max))
+            .unwrap_or(Rect::new(0.0, metrics.descent, 1000.0, metrics.ascent));
+
+        let mut descriptor = chunk.font_descriptor(descriptor_ref);
+        descriptor
+            .name(Name(base_font.as_bytes()))
+            .flags(FontFlags::SYMBOLIC)
+            .bbox(bbox)
+            .italic_angle(metrics.italic_angle)
+            .ascent(metrics.ascent)
+            .descent(metrics.descent)
+            .cap_height(metrics.cap_height.unwrap_or(metrics.ascent))
+            .stem_v(80.0);
+        if is_cff {
+            descriptor.font_file3(file_ref);
+        } else {
+            descriptor.font_file2(file_ref);
+        }
+        descriptor.finish();
+
+        let encoded = deflate_encode(self.data);
+        let mut file = chunk.stream(file_ref, &encoded);
+        file.filter(Filter::FlateDecode);
+        if is_cff {
+            file.pair(Name(b"Subtype"), Name(b"OpenType"));
+        } else {
+            file.pair(Name(b"Length1"), self.data.len() as i32);
+        }
+        file.finish();
+
+        let cmap = to_unicode_cmap(&glyphs);
+        chunk.stream(to_unicode_ref, &cmap);
+    }
+}
+
+/// Build a `ToUnicode` CMap for two-byte glyph codes.
+fn to_unicode_cmap(glyphs: &BTreeMap<u16, char>) -> Vec<u8> {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n\
+         12 dict begin\n\
+         begincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n\
+         /CMapType 2 def\n\
+         1 begincodespacerange\n\
+         <0000> <FFFF>\n\
+         endcodespacerange\n",
+    );
+
+    let entries: Vec<_> = glyphs.iter().collect();
+    // A `bfchar` block may hold at most 100 entries.
+    for block in entries.chunks(100) {
+        cmap.push_str(&format!("{} beginbfchar\n", block.len()));
+        for (glyph, c) in block {
+            let mut units = [0_u16; 2];
+            let hex: String = c
+                .encode_utf16(&mut units)
+                .iter()
+                .map(|u| format!("{u:04X}"))
+                .collect();
+            cmap.push_str(&format!("<{glyph:04X}> <{hex}>\n"));
+        }
+        cmap.push_str("endbfchar\n");
+    }
+
+    cmap.push_str(
+        "endcmap\n\
+         CMapName currentdict /CMap defineresource pop\n\
+         end\n\
+         end\n",
+    );
+
+    cmap.into_bytes()
+}
+
+/// Encode text in `WinAnsiEncoding` for use with the built-in Helvetica font.
+///
+/// Characters that cannot be represented are replaced with `?`.
+pub(crate) fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            '\u{20}'..='\u{7E}' | '\u{A0}'..='\u{FF}' => c as u8,
+            '€' => 0x80,
+            '‚' => 0x82,
+            'ƒ' => 0x83,
+            '„' => 0x84,
+            '…' => 0x85,
+            '†' => 0x86,
+            '‡' => 0x87,
+            'ˆ' => 0x88,
+            '‰' => 0x89,
+            'Š' => 0x8A,
+            '‹' => 0x8B,
+            'Œ' => 0x8C,
+            'Ž' => 0x8E,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            '˜' => 0x98,
+            '™' => 0x99,
+            'š' => 0x9A,
+            '›' => 0x9B,
+            'œ' => 0x9C,
+            'ž' => 0x9E,
+            'Ÿ' => 0x9F,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Helvetica glyph widths for `WinAnsiEncoding` codes 32 to 255, taken from the AFM file.
+#[rustfmt::skip]
+const HELVETICA_WIDTHS: [u16; 224] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, 556,
+    556, 556, 222, 556, 333, 1000, 556, 556, 333, 1000, 667, 333, 1000, 556, 611, 556,
+    556, 222, 222, 333, 333, 350, 556, 1000, 333, 1000, 500, 333, 944, 556, 500, 667,
+    278, 333, 556, 556, 556, 556, 260, 556, 333, 737, 370, 556, 584, 333, 737, 333,
+    400, 584, 333, 333, 333, 556, 537, 278, 333, 333, 365, 556, 834, 834, 834, 611,
+    667, 667, 667, 667, 667, 667, 1000, 722, 667, 667, 667, 667, 278, 278, 278, 278,
+    722, 722, 778, 778, 778, 778, 778, 584, 778, 722, 722, 722, 722, 667, 667, 611,
+    556, 556, 556, 556, 556, 556, 889, 500, 556, 556, 556, 556, 278, 278, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 584, 611, 556, 556, 556, 556, 500, 556, 500,
+];
+
+/// The width of `text` in Helvetica, in thousandths of a text space unit.
+pub(crate) fn helvetica_text_width(text: &str) -> f32 {
+    encode_win_ansi(text)
+        .into_iter()
+        .map(|code| f32::from(HELVETICA_WIDTHS[usize::from(code.max(32) - 32)]))
+        .sum()
+}
+
+/// Break `text` into lines that fit into `max_width`, measured with `measure`.
+///
+/// Lines are broken at spaces and at explicit line breaks. A single word that
+/// is wider than `max_width` is kept on its own line rather than split, and
+/// trailing whitespace is trimmed from every line.
+pub(crate) fn wrap_text(text: &str, max_width: f32, measure: impl Fn(&str) -> f32) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+
+        for word in paragraph.split(' ') {
+            if line.is_empty() {
+                line.push_str(word);
+                continue;
+            }
+
+            let candidate = format!("{line} {word}");
+            if measure(candidate.trim_end()) <= max_width {
+                line = candidate;
+            } else {
+                lines.push(line.trim_end().to_string());
+                line = word.to_string();
+            }
+        }
+
+        lines.push(line.trim_end().to_string());
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn win_ansi_maps_latin1_and_specials() {
+        assert_eq!(encode_win_ansi("café"), b"caf\xE9");
+        assert_eq!(encode_win_ansi("€ “x”"), b"\x80 \x93x\x94");
+        assert_eq!(encode_win_ansi("日本"), b"??");
+    }
+
+    #[test]
+    fn to_unicode_cmap_encodes_utf16() {
+        let glyphs = BTreeMap::from([(3, 'é'), (5, '😀')]);
+        let cmap = String::from_utf8(to_unicode_cmap(&glyphs)).unwrap();
+        assert!(cmap.contains("2 beginbfchar"), "{cmap}");
+        assert!(cmap.contains("<0003> <00E9>"), "{cmap}");
+        assert!(cmap.contains("<0005> <D83DDE00>"), "{cmap}");
+    }
+
+    #[test]
+    fn helvetica_width_uses_afm_metrics() {
+        assert_eq!(helvetica_text_width("Hi"), 722.0 + 222.0);
+        assert_eq!(helvetica_text_width("é"), 556.0);
+    }
+
+    #[test]
+    fn wrap_text_breaks_at_spaces_and_trims() {
+        let measure = |s: &str| s.chars().count() as f32;
+        assert_eq!(
+            wrap_text("aaa bbb ccc  ", 7.0, measure),
+            vec!["aaa bbb", "ccc"]
+        );
+        assert_eq!(wrap_text("one\ntwo", 10.0, measure), vec!["one", "two"]);
+    }
+
+    #[test]
+    fn wrap_text_keeps_overlong_words() {
+        let measure = |s: &str| s.chars().count() as f32;
+        assert_eq!(
+            wrap_text("a verylongword b", 4.0, measure),
+            vec!["a", "verylongword", "b"]
+        );
+    }
+
+    #[test]
+    fn invalid_font_data_is_rejected() {
+        assert!(EmbeddedFont::new(b"not a font").is_none());
+    }
+}
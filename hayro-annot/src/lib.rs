@@ -8,17 +8,52 @@ into valid PDF data via incremental save.
 It is designed to work with the `hayro` ecosystem of PDF crates, building
 on `hayro-syntax` for reading existing PDF structure and `pdf-writer` for
 generating new PDF objects.
+
+Reading, rendering and writing documents requires the `std` feature, which is
+enabled by default. Without it, the crate is `no_std` compatible but requires
+an allocator, and only provides the annotation types, [`PdfDate`] and the
+conversions between screen and PDF coordinates. Appearance streams are built
+with `pdf-writer`, which needs `std`, so they aren't part of that subset.
 */
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
 
+extern crate alloc;
+
+#[cfg(feature = "std")]
 mod appearance;
+#[cfg(feature = "std")]
+mod builder;
 mod coord;
+mod date;
+#[cfg(feature = "std")]
+mod font;
+#[cfg(feature = "std")]
+mod reader;
+#[cfg(feature = "std")]
+mod redact;
+#[cfg(feature = "std")]
+mod stamp;
+#[cfg(feature = "std")]
+mod svg;
 mod types;
+#[cfg(feature = "std")]
 mod writer;
 
+#[cfg(feature = "std")]
 pub use appearance::*;
+#[cfg(feature = "std")]
+pub use builder::*;
 pub use coord::*;
+pub use date::*;
+#[cfg(feature = "std")]
+pub use reader::*;
+#[cfg(feature = "std")]
+pub use redact::*;
+#[cfg(feature = "std")]
+pub use svg::*;
 pub use types::*;
+#[cfg(feature = "std")]
 pub use writer::*;
@@ -0,0 +1,879 @@
+//! Reading existing annotations from a PDF.
+//!
+//! This maps the annotation dictionaries of a page back into [`Annotation`]
+//! values, so that annotations already present in a document can be listed
+//! and edited. Annotation types that this crate can't represent are skipped.
+//! The comments of markup annotations, the links of a page and the document
+//! outline can be read as well.
+
+use crate::appearance::{DefaultAppearance, LINE_SPACING};
+use crate::types::*;
+use hayro_syntax::Pdf;
+use hayro_syntax::object;
+use hayro_syntax::object::dict::keys::ANNOTS;
+use hayro_syntax::object::{Array, Dict, MaybeRef, Name, Object, ObjectIdentifier, Rect};
+use std::collections::{HashMap, HashSet};
+
+/// The maximum nesting depth of outlines and name trees, to guard against
+/// cycles.
+const MAX_TREE_DEPTH: usize = 64;
+
+/// Read the annotations of the page with the given index.
+///
+/// Annotations are returned in the order of the page's `/Annots` array.
+/// Annotations with an unsupported `/Subtype`, such as popups, are skipped.
+/// An invalid page index yields an empty list.
+pub fn read_annotations(pdf: &Pdf, page_index: usize) -> Vec<Annotation> {
+    read_page_annotations(pdf, page_index)
+        .into_iter()
+        .map(|(_, _, annotation)| annotation)
+        .collect()
+}
+
+/// Return the positions in the page's `/Annots` array of the annotations
+/// that [`read_annotations`] returns, in the same order.
+pub(crate) fn annotation_positions(pdf: &Pdf, page_index: usize) -> Vec<usize> {
+    read_page_annotations(pdf, page_index)
+        .into_iter()
+        .map(|(position, _, _)| position)
+        .collect()
+}
+
+/// Extract the comments of all markup annotations in the document, with
+/// replies (`/IRT`) nested under the comment they reply to.
+///
+/// Top-level comments are returned in page order. Links and form fields
+/// aren't comments and are skipped, as are unsupported annotation types.
+pub fn extract_comments(pdf: &Pdf) -> Vec<Comment> {
+    let mut comments = Vec::new();
+    // The object identifier of each comment and of the annotation it replies to.
+    let mut ids = Vec::new();
+
+    for page_index in 0..pdf.pages().len() {
+        for (index, (_, dict, annotation)) in read_page_annotations(pdf, page_index)
+            .into_iter()
+            .enumerate()
+        {
+            if matches!(
+                annotation,
+                Annotation::Link(_)
+                    | Annotation::TextField(_)
+                    | Annotation::SignatureField(_)
+                    | Annotation::CheckBox(_)
+                    | Annotation::Choice(_)
+            ) {
+                continue;
+            }
+
+            let base = annotation.base();
+            comments.push(Some(Comment {
+                page: page_index,
+                index,
+                author: base.author.clone(),
+                contents: base.contents.clone(),
+                modified: base.modified.clone(),
+                rect: base.rect,
+                subtype: dict
+                    .get::<Name>(b"Subtype".as_ref())
+                    .map(|n| String::from_utf8_lossy(n.as_ref()).into_owned())
+                    .unwrap_or_default(),
+                in_reply_to: None,
+                replies: Vec::new(),
+            }));
+            ids.push((
+                dict.obj_id(),
+                dict.get_ref(b"IRT".as_ref()).map(ObjectIdentifier::from),
+            ));
+        }
+    }
+
+    let comment_indices = ids
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, (id, _))| Some(((*id)?, idx)))
+        .collect::<HashMap<_, _>>();
+
+    let mut roots = Vec::new();
+    let mut replies = vec![Vec::new(); comments.len()];
+    for (idx, (_, in_reply_to)) in ids.iter().enumerate() {
+        match in_reply_to
+            .and_then(|id| comment_indices.get(&id).copied())
+            .filter(|parent| *parent != idx)
+        {
+            Some(parent) => replies[parent].push(idx),
+            None => roots.push(idx),
+        }
+    }
+
+    roots
+        .into_iter()
+        .filter_map(|idx| thread_comment(idx, &mut comments, &replies))
+        .collect()
+}
+
+/// Take the comment at `idx` and nest its replies under it, recursively.
+fn thread_comment(
+    idx: usize,
+    comments: &mut [Option<Comment>],
+    replies: &[Vec<usize>],
+) -> Option<Comment> {
+    let mut comment = comments[idx].take()?;
+    comment.replies = replies[idx]
+        .iter()
+        .filter_map(|reply| {
+            let mut reply = thread_comment(*reply, comments, replies)?;
+            reply.in_reply_to = Some((comment.page, comment.index));
+            Some(reply)
+        })
+        .collect();
+
+    Some(comment)
+}
+
+/// Read the document outline (bookmarks).
+///
+/// Items link to a page either through an explicit or named destination
+/// (`/Dest`) or through a `GoTo` action. Documents without an outline yield an
+/// empty list.
+pub fn read_outline(pdf: &Pdf) -> Vec<OutlineItem> {
+    let Some(catalog) = pdf.xref().get::<Dict<'_>>(pdf.xref().root_id()) else {
+        return Vec::new();
+    };
+    let Some(outlines) = catalog.get::<Dict<'_>>(b"Outlines".as_ref()) else {
+        return Vec::new();
+    };
+
+    let context = DestinationContext {
+        catalog,
+        page_indices: page_indices(pdf),
+    };
+
+    read_outline_items(&outlines, &context, 0, &mut HashSet::new())
+}
+
+/// Extract the links of the page with the given index, with their targets
+/// resolved.
+///
+/// Links to a URI (a `URI` action) and to a page of the document (a `GoTo`
+/// action or a `/Dest`, which may be a named destination) are returned, in
+/// the order of the page's `/Annots` array. Links with other actions are
+/// skipped.
+pub fn extract_links(pdf: &Pdf, page_index: usize) -> Vec<ExtractedLink> {
+    let Some(page) = pdf.pages().get(page_index) else {
+        return Vec::new();
+    };
+    let Some(annots) = page.raw().get::<Array<'_>>(ANNOTS) else {
+        return Vec::new();
+    };
+    let Some(catalog) = pdf.xref().get::<Dict<'_>>(pdf.xref().root_id()) else {
+        return Vec::new();
+    };
+    let context = DestinationContext {
+        catalog,
+        page_indices: page_indices(pdf),
+    };
+
+    annots
+        .iter::<Dict<'_>>()
+        .filter(|dict| {
+            dict.get::<Name>(b"Subtype".as_ref())
+                .is_some_and(|s| s.as_ref() == b"Link")
+        })
+        .filter_map(|dict| {
+            let rect = dict.get::<Rect>(b"Rect".as_ref())?;
+            let action = dict.get::<Dict<'_>>(b"A".as_ref());
+            let target = match action
+                .as_ref()
+                .and_then(|a| a.get::<Name>(b"S".as_ref()))
+                .as_deref()
+            {
+                Some(b"URI") => LinkTarget::Uri(text_string(action.as_ref()?, b"URI")?),
+                Some(b"GoTo") => LinkTarget::Page(destination_page(
+                    action.as_ref()?.get::<Object<'_>>(b"D".as_ref())?,
+                    &context,
+                    0,
+                )?),
+                Some(_) => return None,
+                None => LinkTarget::Page(destination_page(
+                    dict.get::<Object<'_>>(b"Dest".as_ref())?,
+                    &context,
+                    0,
+                )?),
+            };
+
+            Some(ExtractedLink {
+                rect: [
+                    rect.x0.min(rect.x1) as f32,
+                    rect.y0.min(rect.y1) as f32,
+                    rect.x0.max(rect.x1) as f32,
+                    rect.y0.max(rect.y1) as f32,
+                ],
+                target,
+            })
+        })
+        .collect()
+}
+
+/// The objects needed to resolve destinations to page indices.
+struct DestinationContext<'a> {
+    catalog: Dict<'a>,
+    page_indices: HashMap<ObjectIdentifier, usize>,
+}
+
+/// The index of each page, by the object identifier of its dictionary.
+fn page_indices(pdf: &Pdf) -> HashMap<ObjectIdentifier, usize> {
+    pdf.pages()
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, page)| Some((page.raw().obj_id()?, idx)))
+        .collect()
+}
+
+/// Read the children of an outline item (or of the outline root).
+fn read_outline_items(
+    parent: &Dict<'_>,
+    context: &DestinationContext<'_>,
+    depth: usize,
+    visited: &mut HashSet<ObjectIdentifier>,
+) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    if depth > MAX_TREE_DEPTH {
+        return items;
+    }
+
+    let mut next = parent.get::<Dict<'_>>(b"First".as_ref());
+    while let Some(item) = next {
+        if item.obj_id().is_some_and(|id| !visited.insert(id)) {
+            break;
+        }
+
+        let dest = item.get::<Object<'_>>(b"Dest".as_ref()).or_else(|| {
+            let action = item.get::<Dict<'_>>(b"A".as_ref())?;
+            if action.get::<Name>(b"S".as_ref())?.as_ref() != b"GoTo" {
+                return None;
+            }
+            action.get::<Object<'_>>(b"D".as_ref())
+        });
+
+        items.push(OutlineItem {
+            title: text_string(&item, b"Title").unwrap_or_default(),
+            page: dest.and_then(|dest| destination_page(dest, context, 0)),
+            children: read_outline_items(&item, context, depth + 1, visited),
+        });
+        next = item.get::<Dict<'_>>(b"Next".as_ref());
+    }
+
+    items
+}
+
+/// Resolve an explicit or named destination to the index of its page.
+fn destination_page(
+    dest: Object<'_>,
+    context: &DestinationContext<'_>,
+    depth: usize,
+) -> Option<usize> {
+    if depth > 1 {
+        return None;
+    }
+
+    let target = match dest {
+        Object::Array(array) => {
+            return match array.raw_iter().next()? {
+                MaybeRef::Ref(r) => context
+                    .page_indices
+                    .get(&ObjectIdentifier::from(r))
+                    .copied(),
+                MaybeRef::NotRef(_) => None,
+            };
+        }
+        // Named destinations of PDF 1.1, looked up in the `/Dests` dictionary.
+        Object::Name(name) => context
+            .catalog
+            .get::<Dict<'_>>(b"Dests".as_ref())?
+            .get::<Object<'_>>(name.as_ref())?,
+        // Named destinations looked up in the `/Dests` name tree.
+        Object::String(name) => {
+            let tree = context
+                .catalog
+                .get::<Dict<'_>>(b"Names".as_ref())?
+                .get::<Dict<'_>>(b"Dests".as_ref())?;
+            name_tree_lookup(&tree, name.as_ref(), 0)?
+        }
+        _ => return None,
+    };
+
+    // The target is either the destination array or a dictionary with the
+    // destination in `/D`.
+    let target = match target {
+        Object::Dict(dict) => dict.get::<Object<'_>>(b"D".as_ref())?,
+        other => other,
+    };
+    destination_page(target, context, depth + 1)
+}
+
+/// Look up the value of `key` in a name tree.
+fn name_tree_lookup<'a>(node: &Dict<'a>, key: &[u8], depth: usize) -> Option<Object<'a>> {
+    if depth > MAX_TREE_DEPTH {
+        return None;
+    }
+
+    if let Some(names) = node.get::<Array<'_>>(b"Names".as_ref()) {
+        let mut iter = names.flex_iter();
+        while let Some(name) = iter.next::<object::String>() {
+            let value = iter.next::<Object<'_>>()?;
+            if name.as_ref() == key {
+                return Some(value);
+            }
+        }
+    }
+
+    node.get::<Array<'_>>(b"Kids".as_ref())?
+        .iter::<Dict<'_>>()
+        .find_map(|kid| name_tree_lookup(&kid, key, depth + 1))
+}
+
+/// Read the supported annotations of a page, along with their position in
+/// the page's `/Annots` array and their dictionary.
+fn read_page_annotations<'a>(
+    pdf: &'a Pdf,
+    page_index: usize,
+) -> Vec<(usize, Dict<'a>, Annotation)> {
+    let pages = pdf.pages();
+    let Some(page) = pages.get(page_index) else {
+        return Vec::new();
+    };
+    let Some(annots) = page.raw().get::<Array<'_>>(ANNOTS) else {
+        return Vec::new();
+    };
+
+    let page_indices = page_indices(pdf);
+
+    annots
+        .raw_iter()
+        .enumerate()
+        .filter_map(|(position, entry)| {
+            let dict = match entry {
+                MaybeRef::Ref(r) => pdf.xref().get::<Dict<'_>>(r.into())?,
+                MaybeRef::NotRef(obj) => obj.into_dict()?,
+            };
+            let annotation = read_annotation(&dict, &page_indices)?;
+            Some((position, dict, annotation))
+        })
+        .collect()
+}
+
+/// Convert a single annotation dictionary into an [`Annotation`].
+fn read_annotation(
+    dict: &Dict<'_>,
+    page_indices: &HashMap<ObjectIdentifier, usize>,
+) -> Option<Annotation> {
+    let subtype = dict.get::<Name>(b"Subtype".as_ref())?;
+    let base = read_base(dict);
+
+    let annotation = match subtype.as_ref() {
+        b"Highlight" => Annotation::Highlight(HighlightAnnot {
+            base,
+            quad_points: quad_points(dict),
+        }),
+        b"Underline" => Annotation::Underline(UnderlineAnnot {
+            base,
+            quad_points: quad_points(dict),
+        }),
+        b"StrikeOut" => Annotation::StrikeOut(StrikeOutAnnot {
+            base,
+            quad_points: quad_points(dict),
+        }),
+        b"Squiggly" => Annotation::Squiggly(SquigglyAnnot {
+            base,
+            quad_points: quad_points(dict),
+        }),
+        b"Redact" => Annotation::Redact(RedactAnnot {
+            base,
+            quad_points: quad_points(dict),
+            overlay_color: color(dict, b"IC"),
+        }),
+        b"FreeText" => {
+            let default_appearance = text_string(dict, b"DA").unwrap_or_default();
+            let font_size = DefaultAppearance::parse(&default_appearance)
+                .font_size
+                .unwrap_or(12.0);
+            let text = base.contents.clone().unwrap_or_default();
+            let callout_line = dict
+                .get::<Vec<f32>>(b"CL".as_ref())
+                .map(|cl| cl.chunks_exact(2).map(|p| [p[0], p[1]]).collect());
+
+            // With a callout line, `/RD` locates the text box within the rect.
+            let mut base = base;
+            if callout_line.is_some()
+                && let Some(rd) = dict.get::<[f32; 4]>(b"RD".as_ref())
+            {
+                base.rect[0] += rd[0];
+                base.rect[1] += rd[1];
+                base.rect[2] -= rd[2];
+                base.rect[3] -= rd[3];
+            }
+
+            Annotation::FreeText(FreeTextAnnot {
+                base,
+                text,
+                font_size,
+                default_appearance,
+                font_data: None,
+                multiline: false,
+                line_spacing: LINE_SPACING,
+                vertical_align: VAlign::Top,
+                callout_line,
+                rich_text: rich_text(dict),
+                default_style: text_string(dict, b"DS"),
+            })
+        }
+        b"Ink" => Annotation::Ink(InkAnnot {
+            base,
+            ink_list: dict
+                .get::<Vec<Vec<f32>>>(b"InkList".as_ref())
+                .unwrap_or_default()
+                .iter()
+                .map(|path| path.chunks_exact(2).map(|p| [p[0], p[1]]).collect())
+                .collect(),
+            line_width: border_width(dict),
+            smoothing: 0.0,
+            dash: dash_pattern(dict),
+        }),
+        b"Square" | b"Circle" => {
+            let is_circle = subtype.as_ref() == b"Circle";
+            let shape = ShapeAnnot {
+                base,
+                interior_color: color(dict, b"IC"),
+                line_width: border_width(dict),
+                is_circle,
+                border_style: border_style(dict),
+                fill_opacity: fill_opacity(dict),
+                // Rounded corners are only part of the appearance stream.
+                corner_radius: 0.0,
+            };
+
+            if is_circle {
+                Annotation::Circle(shape)
+            } else {
+                Annotation::Square(shape)
+            }
+        }
+        b"Line" => {
+            let line = dict.get::<[f32; 4]>(b"L".as_ref()).unwrap_or_default();
+            let endings = dict
+                .get::<Array<'_>>(b"LE".as_ref())
+                .map(|a| {
+                    a.iter::<Name>()
+                        .map(|n| line_ending(&n))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            Annotation::Line(LineAnnot {
+                base,
+                start: [line[0], line[1]],
+                end: [line[2], line[3]],
+                line_width: border_width(dict),
+                start_style: endings.first().copied().unwrap_or_default(),
+                end_style: endings.get(1).copied().unwrap_or_default(),
+                measure: measure_scale(dict),
+                dash: dash_pattern(dict),
+            })
+        }
+        b"Polygon" | b"PolyLine" => {
+            let poly = PolyAnnot {
+                base,
+                vertices: dict
+                    .get::<Vec<f32>>(b"Vertices".as_ref())
+                    .unwrap_or_default()
+                    .chunks_exact(2)
+                    .map(|p| [p[0], p[1]])
+                    .collect(),
+                line_width: border_width(dict),
+                interior_color: color(dict, b"IC"),
+            };
+
+            if subtype.as_ref() == b"Polygon" {
+                Annotation::Polygon(poly)
+            } else {
+                Annotation::PolyLine(poly)
+            }
+        }
+        b"Text" => Annotation::Text(TextAnnot {
+            base,
+            open: dict.get::<bool>(b"Open".as_ref()).unwrap_or(false),
+            icon: dict
+                .get::<Name>(b"Name".as_ref())
+                .map(|n| n.as_str().to_string())
+                .unwrap_or_else(|| "Note".to_string()),
+            rich_text: rich_text(dict),
+            default_style: text_string(dict, b"DS"),
+        }),
+        b"Link" => {
+            let action = dict.get::<Dict<'_>>(b"A".as_ref());
+            let uri = action.as_ref().and_then(|a| text_string(a, b"URI"));
+            let dest = dict
+                .get::<Array<'_>>(b"Dest".as_ref())
+                .or_else(|| action.as_ref()?.get::<Array<'_>>(b"D".as_ref()));
+            let dest_page = dest
+                .and_then(|d| d.raw_iter().next())
+                .and_then(|first| match first {
+                    MaybeRef::Ref(r) => page_indices.get(&ObjectIdentifier::from(r)).copied(),
+                    MaybeRef::NotRef(_) => None,
+                });
+
+            Annotation::Link(LinkAnnot {
+                base,
+                uri,
+                dest_page,
+            })
+        }
+        b"FileAttachment" => {
+            let file_spec = dict.get::<Dict<'_>>(b"FS".as_ref())?;
+            let data = file_spec
+                .get::<Dict<'_>>(b"EF".as_ref())?
+                .get::<object::Stream<'_>>(b"F".as_ref())?
+                .decoded()
+                .ok()?;
+
+            Annotation::FileAttachment(FileAttachmentAnnot {
+                base,
+                file_name: text_string(&file_spec, b"UF")
+                    .or_else(|| text_string(&file_spec, b"F"))
+                    .unwrap_or_default(),
+                data,
+                icon: dict
+                    .get::<Name>(b"Name".as_ref())
+                    .map(|n| n.as_str().to_string())
+                    .unwrap_or_else(|| "PushPin".to_string()),
+            })
+        }
+        b"Widget" => read_widget(dict, base)?,
+        _ => return None,
+    };
+
+    Some(annotation)
+}
+
+/// Convert a widget annotation into the form field it belongs to.
+fn read_widget(dict: &Dict<'_>, base: AnnotationBase) -> Option<Annotation> {
+    let field_type = inherited::<Name>(dict, b"FT")?;
+    let field_name = field_name(dict);
+    let flags = inherited::<u32>(dict, b"Ff").unwrap_or(0);
+    let read_only = flags & 1 != 0;
+    let required = flags & (1 << 1) != 0;
+
+    let annotation = match field_type.as_ref() {
+        b"Tx" => Annotation::TextField(TextFieldAnnot {
+            base,
+            field_name,
+            value: inherited_text_string(dict, b"V"),
+            default_value: inherited_text_string(dict, b"DV"),
+            max_len: inherited::<u32>(dict, b"MaxLen"),
+            default_appearance: inherited_text_string(dict, b"DA").unwrap_or_default(),
+            read_only,
+            required,
+            multiline: flags & (1 << 12) != 0,
+            alignment: match inherited::<i32>(dict, b"Q") {
+                Some(1) => TextAlign::Center,
+                Some(2) => TextAlign::Right,
+                _ => TextAlign::Left,
+            },
+            down_appearance: widget_appearance(dict, b"D"),
+            rollover_appearance: widget_appearance(dict, b"R"),
+        }),
+        b"Sig" => Annotation::SignatureField(SignatureFieldAnnot {
+            base,
+            field_name,
+            tooltip: inherited_text_string(dict, b"TU"),
+            required,
+            down_appearance: widget_appearance(dict, b"D"),
+            rollover_appearance: widget_appearance(dict, b"R"),
+        }),
+        // Radio buttons and push buttons can't be represented as checkboxes.
+        b"Btn" if flags & ((1 << 15) | (1 << 16)) == 0 => {
+            let state = dict.get::<Name>(b"AS".as_ref());
+            let export_value = dict
+                .get::<Dict<'_>>(b"AP".as_ref())
+                .and_then(|ap| ap.get::<Dict<'_>>(b"N".as_ref()))
+                .and_then(|n| n.keys().find(|k| k.as_ref() != b"Off"))
+                .map(|n| n.as_str().to_string())
+                .unwrap_or_else(|| "Yes".to_string());
+
+            Annotation::CheckBox(CheckBoxAnnot {
+                base,
+                field_name,
+                checked: state.is_some_and(|s| s.as_ref() != b"Off"),
+                export_value,
+                read_only,
+                required,
+                down_appearance: widget_appearance(dict, b"D"),
+                rollover_appearance: widget_appearance(dict, b"R"),
+            })
+        }
+        b"Ch" => {
+            let options = inherited::<Array<'_>>(dict, b"Opt")
+                .map(|opt| {
+                    opt.iter::<object::Object<'_>>()
+                        .filter_map(|option| match option {
+                            // An option can also be a pair of its export value
+                            // and the text that is shown for it.
+                            object::Object::Array(pair) => pair.iter::<object::String>().next(),
+                            object::Object::String(s) => Some(s),
+                            _ => None,
+                        })
+                        .map(|s| decode_text_string(&s))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Annotation::Choice(ChoiceFieldAnnot {
+                base,
+                field_name,
+                options,
+                selected: inherited_text_string(dict, b"V"),
+                combo: flags & (1 << 17) != 0,
+                read_only,
+                required,
+                down_appearance: widget_appearance(dict, b"D"),
+                rollover_appearance: widget_appearance(dict, b"R"),
+            })
+        }
+        _ => return None,
+    };
+
+    Some(annotation)
+}
+
+fn read_base(dict: &Dict<'_>) -> AnnotationBase {
+    let rect = dict
+        .get::<Rect>(b"Rect".as_ref())
+        .map(|r| [r.x0 as f32, r.y0 as f32, r.x1 as f32, r.y1 as f32])
+        .unwrap_or_default();
+
+    AnnotationBase {
+        rect,
+        color: color(dict, b"C"),
+        author: text_string(dict, b"T"),
+        contents: text_string(dict, b"Contents"),
+        modified: text_string(dict, b"M"),
+        created: text_string(dict, b"CreationDate"),
+        name: text_string(dict, b"NM"),
+        subject: text_string(dict, b"Subj"),
+        intent: dict
+            .get::<Name>(b"IT".as_ref())
+            .map(|n| n.as_str().to_string()),
+        flags: dict.get::<u32>(b"F".as_ref()).unwrap_or(0),
+        opacity: dict.get::<f32>(b"CA".as_ref()).unwrap_or(1.0),
+        blend_mode: appearance_blend_mode(dict),
+        // A rotated appearance is read as-is, with `rect` covering its bounds.
+        rotation: 0.0,
+    }
+}
+
+/// Read the blend mode set by a graphics state in the resources of the
+/// normal appearance, if any.
+fn appearance_blend_mode(dict: &Dict<'_>) -> Option<BlendMode> {
+    appearance_graphics_state(dict, |state| {
+        BlendMode::from_pdf_name(state.get::<Name>(b"BM".as_ref())?.as_ref())
+    })
+}
+
+/// Read the fill opacity set by a graphics state in the resources of the
+/// normal appearance, defaulting to opaque.
+fn fill_opacity(dict: &Dict<'_>) -> f32 {
+    appearance_graphics_state(dict, |state| state.get::<f32>(b"ca".as_ref())).unwrap_or(1.0)
+}
+
+/// Find the first graphics state in the resources of the normal appearance
+/// for which `f` returns a value.
+fn appearance_graphics_state<T>(
+    dict: &Dict<'_>,
+    mut f: impl FnMut(&Dict<'_>) -> Option<T>,
+) -> Option<T> {
+    let states = dict
+        .get::<Dict<'_>>(b"AP".as_ref())?
+        .get::<object::Stream<'_>>(b"N".as_ref())?
+        .dict()
+        .get::<Dict<'_>>(b"Resources".as_ref())?
+        .get::<Dict<'_>>(b"ExtGState".as_ref())?;
+
+    states
+        .keys()
+        .find_map(|key| f(&states.get::<Dict<'_>>(key)?))
+}
+
+/// Read the content of the down (`D`) or rollover (`R`) appearance of a
+/// widget. For appearances with states, the one of the current state (`/AS`)
+/// is read.
+fn widget_appearance(dict: &Dict<'_>, key: &[u8]) -> Option<Vec<u8>> {
+    let appearances = dict.get::<Dict<'_>>(b"AP".as_ref())?;
+    let stream = appearances.get::<object::Stream<'_>>(key).or_else(|| {
+        let state = dict.get::<Name>(b"AS".as_ref())?;
+        appearances
+            .get::<Dict<'_>>(key)?
+            .get::<object::Stream<'_>>(state.as_ref())
+    })?;
+
+    stream.decoded().ok().map(|data| data.to_vec())
+}
+
+fn quad_points(dict: &Dict<'_>) -> Vec<f32> {
+    dict.get::<Vec<f32>>(b"QuadPoints".as_ref())
+        .unwrap_or_default()
+}
+
+/// Read a color array with 1 (gray), 3 (RGB) or 4 (CMYK) components.
+fn color(dict: &Dict<'_>, key: &[u8]) -> Option<AnnotColor> {
+    let components = dict.get::<Vec<f32>>(key)?;
+
+    match components.as_slice() {
+        [g] => Some(AnnotColor::gray(*g)),
+        [r, g, b] => Some(AnnotColor::new(*r, *g, *b)),
+        [c, m, y, k] => Some(AnnotColor::cmyk(*c, *m, *y, *k)),
+        _ => None,
+    }
+}
+
+/// The border width from `/BS` or the legacy `/Border` array.
+fn border_width(dict: &Dict<'_>) -> f32 {
+    dict.get::<Dict<'_>>(b"BS".as_ref())
+        .and_then(|bs| bs.get::<f32>(b"W".as_ref()))
+        .or_else(|| {
+            dict.get::<Vec<f32>>(b"Border".as_ref())
+                .and_then(|b| b.get(2).copied())
+        })
+        .unwrap_or(1.0)
+}
+
+fn border_style(dict: &Dict<'_>) -> BorderStyle {
+    if let Some(be) = dict.get::<Dict<'_>>(b"BE".as_ref())
+        && be.get::<Name>(b"S".as_ref()).as_deref() == Some(&b"C"[..])
+    {
+        return BorderStyle::Cloudy(be.get::<f32>(b"I".as_ref()).unwrap_or(0.0));
+    }
+
+    match dict.get::<Dict<'_>>(b"BS".as_ref()) {
+        Some(bs) if bs.get::<Name>(b"S".as_ref()).as_deref() == Some(&b"D"[..]) => {
+            BorderStyle::Dashed(bs.get::<Vec<f32>>(b"D".as_ref()).unwrap_or(vec![3.0]))
+        }
+        _ => BorderStyle::Solid,
+    }
+}
+
+/// The dash pattern of a dashed `/BS` border style.
+fn dash_pattern(dict: &Dict<'_>) -> Option<Vec<f32>> {
+    match border_style(dict) {
+        BorderStyle::Dashed(dashes) => Some(dashes),
+        _ => None,
+    }
+}
+
+fn line_ending(name: &Name) -> LineEnding {
+    match name.as_ref() {
+        b"Square" => LineEnding::Square,
+        b"Circle" => LineEnding::Circle,
+        b"Diamond" => LineEnding::Diamond,
+        b"OpenArrow" => LineEnding::OpenArrow,
+        b"ClosedArrow" => LineEnding::ClosedArrow,
+        b"Butt" => LineEnding::Butt,
+        b"ROpenArrow" => LineEnding::ROpenArrow,
+        b"RClosedArrow" => LineEnding::RClosedArrow,
+        b"Slash" => LineEnding::Slash,
+        _ => LineEnding::None,
+    }
+}
+
+/// Read the measurement scale (`/Measure`) of a line annotation from its
+/// first `/X` number format.
+fn measure_scale(dict: &Dict<'_>) -> Option<MeasureScale> {
+    let measure = dict.get::<Dict<'_>>(b"Measure".as_ref())?;
+    let format = measure
+        .get::<Array<'_>>(b"X".as_ref())?
+        .iter::<Dict<'_>>()
+        .next()?;
+    let denominator = format.get::<f32>(b"D".as_ref()).unwrap_or(100.0);
+
+    Some(MeasureScale {
+        ratio: text_string(&measure, b"R").unwrap_or_default(),
+        units_per_point: format.get::<f32>(b"C".as_ref()).unwrap_or(1.0),
+        unit: text_string(&format, b"U").unwrap_or_default(),
+        precision: denominator.max(1.0).log10().round() as u32,
+    })
+}
+
+/// Look up a dictionary entry, following the `/Parent` chain for inherited values.
+pub(crate) fn inherited<'a, T>(dict: &Dict<'a>, key: &[u8]) -> Option<T>
+where
+    T: TryFrom<object::Object<'a>>,
+{
+    let mut current = dict.clone();
+
+    // Guard against cyclic parent references.
+    for _ in 0..32 {
+        if let Some(value) = current
+            .get::<object::Object<'a>>(key)
+            .and_then(|o| T::try_from(o).ok())
+        {
+            return Some(value);
+        }
+        current = current.get::<Dict<'a>>(b"Parent".as_ref())?;
+    }
+
+    None
+}
+
+fn inherited_text_string(dict: &Dict<'_>, key: &[u8]) -> Option<String> {
+    inherited::<object::String>(dict, key).map(|s| decode_text_string(&s))
+}
+
+/// The fully qualified field name, joining the partial names of all ancestors.
+fn field_name(dict: &Dict<'_>) -> String {
+    let mut parts = Vec::new();
+    let mut current = Some(dict.clone());
+
+    for _ in 0..32 {
+        let Some(field) = current else {
+            break;
+        };
+        if let Some(name) = text_string(&field, b"T") {
+            parts.push(name);
+        }
+        current = field.get::<Dict<'_>>(b"Parent".as_ref());
+    }
+
+    parts.reverse();
+    parts.join(".")
+}
+
+fn text_string(dict: &Dict<'_>, key: &[u8]) -> Option<String> {
+    dict.get::<object::String>(key)
+        .map(|s| decode_text_string(&s))
+}
+
+/// Read the rich text (`/RC`) of a markup annotation, which is either a text
+/// string or a text stream.
+fn rich_text(dict: &Dict<'_>) -> Option<String> {
+    text_string(dict, b"RC").or_else(|| {
+        let stream = dict.get::<object::Stream<'_>>(b"RC".as_ref())?;
+        Some(decode_text_string(&stream.decoded().ok()?))
+    })
+}
+
+/// Decode a PDF text string, which is either UTF-16BE with a byte order mark,
+/// UTF-8 with a byte order mark, or `PDFDocEncoding`.
+pub(crate) fn decode_text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units = utf16
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>();
+        String::from_utf16_lossy(&units)
+    } else if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        String::from_utf8_lossy(utf8).into_owned()
+    } else {
+        // `PDFDocEncoding` matches Latin-1 for all printable ASCII and most
+        // other characters.
+        bytes.iter().map(|b| char::from(*b)).collect()
+    }
+}
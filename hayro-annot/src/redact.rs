@@ -0,0 +1,695 @@
+//! Applying redactions to page content.
+//!
+//! In contrast to annotations, which are only drawn on top of a page, applying
+//! a redaction rewrites the content stream of the page so that the redacted
+//! content is actually removed from the file. Text glyphs whose bounding box
+//! intersects a redaction area are dropped (the remaining glyphs keep their
+//! positions), images and form `XObjects` touching a redaction area are
+//! removed entirely, and the area is painted over in black (or in the overlay
+//! color of a redaction annotation). Existing annotations that intersect a
+//! redaction area are removed as well, while all others are kept.
+//!
+//! Glyphs are laid out with the font handling of `hayro-interpret`, so their
+//! positions match the rendered page.
+
+use crate::appearance::quad_bounds;
+use crate::reader::read_annotations;
+use crate::types::{AnnotColor, Annotation};
+use crate::writer::{SaveError, WriteOptions, write_document};
+use hayro_interpret::InterpreterSettings;
+use hayro_interpret::font::{StandardFont, TextFont};
+use hayro_syntax::Pdf;
+use hayro_syntax::object::dict::keys::{
+    ANNOTS, BBOX, FORM, IMAGE, MATRIX, PARENT, POPUP, RECT, SUBTYPE,
+};
+use hayro_syntax::object::{Array, Dict, MaybeRef, Name, Object, ObjectIdentifier};
+use hayro_syntax::page::{Page, Resources};
+use kurbo::{Affine, Rect};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Remove all content that intersects the given areas from a PDF.
+///
+/// # Arguments
+/// * `original_data` — the original PDF file bytes
+/// * `redactions` — list of `(page_index, rects)`, where each rect is given as
+///   `[x0, y0, x1, y1]` in PDF user space
+///
+/// # Returns
+/// Complete PDF bytes of the new document, with the redacted content removed
+/// and each redaction area covered by an opaque black box.
+///
+/// Text is removed glyph by glyph. Images, inline images and form `XObjects`
+/// are removed as a whole as soon as their bounds intersect an area, even if
+/// most of them lies outside of it. They aren't clipped to the area instead,
+/// since the redacted part would then still be contained in the file.
+pub fn apply_redactions(
+    original_data: &[u8],
+    redactions: &[(usize, Vec<[f32; 4]>)],
+) -> Result<Vec<u8>, SaveError> {
+    let pdf = Pdf::new(original_data.to_vec()).map_err(|_| SaveError::InvalidPdf)?;
+    let num_pages = pdf.pages().len();
+
+    let mut page_areas: BTreeMap<usize, Vec<(Rect, AnnotColor)>> = BTreeMap::new();
+
+    for (page_idx, rects) in redactions {
+        if *page_idx >= num_pages {
+            return Err(SaveError::InvalidPageIndex(*page_idx));
+        }
+
+        page_areas.entry(*page_idx).or_default().extend(
+            rects
+                .iter()
+                .filter_map(|r| redaction_rect(*r))
+                .map(|r| (r, AnnotColor::black())),
+        );
+    }
+
+    redact_document(&pdf, page_areas, false)
+}
+
+/// Apply the redaction annotations (`/Subtype /Redact`) of a PDF.
+///
+/// The content under each redacted region (see [`RedactAnnot`](crate::RedactAnnot))
+/// is removed like with [`apply_redactions`] and painted over in the overlay
+/// color of the annotation. The redaction annotations themselves are removed
+/// from the new document.
+///
+/// As with [`apply_redactions`], images and form `XObjects` that intersect a
+/// redacted region are removed as a whole.
+pub fn apply_redaction_annotations(original_data: &[u8]) -> Result<Vec<u8>, SaveError> {
+    let pdf = Pdf::new(original_data.to_vec()).map_err(|_| SaveError::InvalidPdf)?;
+
+    let mut page_areas: BTreeMap<usize, Vec<(Rect, AnnotColor)>> = BTreeMap::new();
+
+    for page_idx in 0..pdf.pages().len() {
+        for annotation in read_annotations(&pdf, page_idx) {
+            let Annotation::Redact(redact) = annotation else {
+                continue;
+            };
+
+            let overlay = redact.overlay_color.unwrap_or(AnnotColor::black());
+            let regions = if redact.quad_points.len() < 8 {
+                vec![redact.base.rect]
+            } else {
+                redact
+                    .quad_points
+                    .chunks_exact(8)
+                    .map(quad_bounds)
+                    .collect()
+            };
+
+            page_areas.entry(page_idx).or_default().extend(
+                regions
+                    .into_iter()
+                    .filter_map(redaction_rect)
+                    .map(|r| (r, overlay)),
+            );
+        }
+    }
+
+    redact_document(&pdf, page_areas, true)
+}
+
+/// Convert a redaction rect to a normalized rectangle, dropping it if any of
+/// its coordinates isn't finite.
+fn redaction_rect(r: [f32; 4]) -> Option<Rect> {
+    r.iter()
+        .all(|v| v.is_finite())
+        .then(|| Rect::new(r[0] as f64, r[1] as f64, r[2] as f64, r[3] as f64).abs())
+}
+
+/// Write a new document in which the given areas of each page are redacted
+/// and painted over in their color.
+///
+/// If `remove_redact_annots` is set, the redaction annotations of the document
+/// are removed along with the annotations intersecting a redacted area.
+fn redact_document(
+    pdf: &Pdf,
+    page_areas: BTreeMap<usize, Vec<(Rect, AnnotColor)>>,
+    remove_redact_annots: bool,
+) -> Result<Vec<u8>, SaveError> {
+    let mut options = hayro_write::ExtractionOptions {
+        include_annotations: true,
+        ..Default::default()
+    };
+
+    for (page_idx, page) in pdf.pages().iter().enumerate() {
+        let areas = page_areas.get(&page_idx).map(Vec::as_slice).unwrap_or(&[]);
+
+        let removed = removed_annotations(pdf, page, areas, remove_redact_annots);
+        if !removed.is_empty() {
+            options.removed_annotations.insert(page_idx, removed);
+        }
+
+        if !areas.is_empty() {
+            let content = redact_page_content(page, areas);
+            options.content_overrides.insert(page_idx, content);
+        }
+    }
+
+    write_document(pdf, &[], &options, &WriteOptions::default())
+}
+
+/// Return the positions in the `/Annots` array of a page of the annotations
+/// that intersect one of the areas, and of their popups.
+fn removed_annotations(
+    pdf: &Pdf,
+    page: &Page<'_>,
+    areas: &[(Rect, AnnotColor)],
+    remove_redact_annots: bool,
+) -> HashSet<usize> {
+    let Some(annots) = page.raw().get::<Array<'_>>(ANNOTS) else {
+        return HashSet::new();
+    };
+
+    let entries = annots
+        .raw_iter()
+        .map(|entry| match entry {
+            MaybeRef::Ref(r) => {
+                let id = ObjectIdentifier::from(r);
+                (Some(id), pdf.xref().get::<Dict<'_>>(id))
+            }
+            MaybeRef::NotRef(obj) => (None, obj.into_dict()),
+        })
+        .collect::<Vec<_>>();
+
+    let mut removed = HashSet::new();
+    let mut removed_ids = HashSet::new();
+    for (position, (id, dict)) in entries.iter().enumerate() {
+        let Some(dict) = dict else {
+            continue;
+        };
+
+        let is_redact = dict.get::<Name>(SUBTYPE).as_deref() == Some(b"Redact".as_ref());
+        let intersects = dict
+            .get::<hayro_syntax::object::Rect>(RECT)
+            .is_some_and(|r| {
+                let r = Rect::new(r.x0, r.y0, r.x1, r.y1).abs();
+                areas.iter().any(|(area, _)| {
+                    r.x0 < area.x1 && r.x1 > area.x0 && r.y0 < area.y1 && r.y1 > area.y0
+                })
+            });
+
+        if intersects || (remove_redact_annots && is_redact) {
+            removed.insert(position);
+            removed_ids.extend(*id);
+            if let Some(popup) = dict.get_ref(POPUP) {
+                removed_ids.insert(popup.into());
+            }
+        }
+    }
+
+    // Popups belong to their parent annotation and are removed along with it.
+    for (position, (id, dict)) in entries.iter().enumerate() {
+        let parent_removed = dict
+            .as_ref()
+            .and_then(|dict| dict.get_ref(PARENT))
+            .is_some_and(|parent| removed_ids.contains(&ObjectIdentifier::from(parent)));
+        if parent_removed || id.is_some_and(|id| removed_ids.contains(&id)) {
+            removed.insert(position);
+        }
+    }
+
+    removed
+}
+
+/// Rewrite the content stream of a page so that nothing intersecting the
+/// areas remains, and paint each area in its color.
+fn redact_page_content(page: &Page<'_>, areas: &[(Rect, AnnotColor)]) -> Vec<u8> {
+    let rects = areas.iter().map(|(rect, _)| *rect).collect::<Vec<_>>();
+    let mut filter = ContentFilter::new(page.resources(), &rects);
+    let mut out = b"q\n".to_vec();
+
+    for op in page.operations() {
+        filter.process(op.operator.to_vec(), op.operands().collect(), &mut out);
+    }
+
+    out.extend_from_slice(b"Q\n");
+
+    for (rect, color) in areas {
+        let operator = match color {
+            AnnotColor::Gray(_) => "g",
+            AnnotColor::Rgb(..) => "rg",
+            AnnotColor::Cmyk(..) => "k",
+        };
+        let components = color
+            .components()
+            .into_iter()
+            .map(|c| fmt_num(f64::from(c)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        out.extend_from_slice(
+            format!(
+                "q {components} {operator} {} {} {} {} re f Q\n",
+                fmt_num(rect.x0),
+                fmt_num(rect.y0),
+                fmt_num(rect.width()),
+                fmt_num(rect.height())
+            )
+            .as_bytes(),
+        );
+    }
+
+    out
+}
+
+#[derive(Clone)]
+struct GraphicsState<'a> {
+    ctm: Affine,
+    char_spacing: f64,
+    word_spacing: f64,
+    horizontal_scaling: f64,
+    leading: f64,
+    font: Option<TextFont<'a>>,
+    font_size: f64,
+    rise: f64,
+}
+
+impl Default for GraphicsState<'_> {
+    fn default() -> Self {
+        Self {
+            ctm: Affine::IDENTITY,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            font: None,
+            font_size: 0.0,
+            rise: 0.0,
+        }
+    }
+}
+
+/// A single glyph of a text-showing operator.
+struct Glyph<'a> {
+    bytes: &'a [u8],
+    removed: bool,
+    /// The advance along the writing direction, in text space units.
+    advance: f64,
+}
+
+/// A part of a `TJ` array.
+enum TextPart {
+    Text(Vec<u8>),
+    Adjustment(f64),
+}
+
+/// Filters a content stream, removing everything that intersects the redaction rects.
+struct ContentFilter<'a, 'b> {
+    resources: &'b Resources<'a>,
+    rects: &'b [Rect],
+    settings: InterpreterSettings,
+    state: GraphicsState<'a>,
+    state_stack: Vec<GraphicsState<'a>>,
+    text_matrix: Affine,
+    line_matrix: Affine,
+    fonts: HashMap<Vec<u8>, Option<TextFont<'a>>>,
+}
+
+impl<'a, 'b> ContentFilter<'a, 'b> {
+    fn new(resources: &'b Resources<'a>, rects: &'b [Rect]) -> Self {
+        Self {
+            resources,
+            rects,
+            settings: InterpreterSettings::default(),
+            state: GraphicsState::default(),
+            state_stack: vec![],
+            text_matrix: Affine::IDENTITY,
+            line_matrix: Affine::IDENTITY,
+            fonts: HashMap::new(),
+        }
+    }
+
+    fn intersects(&self, bbox: Rect) -> bool {
+        self.rects
+            .iter()
+            .any(|r| bbox.x0 < r.x1 && bbox.x1 > r.x0 && bbox.y0 < r.y1 && bbox.y1 > r.y0)
+    }
+
+    fn process(&mut self, operator: Vec<u8>, operands: Vec<Object<'_>>, out: &mut Vec<u8>) {
+        let num = |i: usize| {
+            operands
+                .get(i)
+                .and_then(|o| o.clone().into_number())
+                .map(|n| n.as_f64())
+                .unwrap_or(0.0)
+        };
+
+        match operator.as_slice() {
+            b"q" => self.state_stack.push(self.state.clone()),
+            b"Q" => {
+                if let Some(state) = self.state_stack.pop() {
+                    self.state = state;
+                }
+            }
+            b"cm" => {
+                self.state.ctm *= Affine::new([num(0), num(1), num(2), num(3), num(4), num(5)]);
+            }
+            b"BT" => {
+                self.text_matrix = Affine::IDENTITY;
+                self.line_matrix = Affine::IDENTITY;
+            }
+            b"Tc" => self.state.char_spacing = num(0),
+            b"Tw" => self.state.word_spacing = num(0),
+            b"Tz" => self.state.horizontal_scaling = num(0) / 100.0,
+            b"TL" => self.state.leading = num(0),
+            b"Ts" => self.state.rise = num(0),
+            b"Tf" => {
+                self.state.font = operands
+                    .first()
+                    .and_then(|o| o.clone().into_name())
+                    .and_then(|name| self.font(name));
+                self.state.font_size = num(1);
+            }
+            b"Td" => self.move_line(num(0), num(1)),
+            b"TD" => {
+                self.state.leading = -num(1);
+                self.move_line(num(0), num(1));
+            }
+            b"Tm" => {
+                self.line_matrix = Affine::new([num(0), num(1), num(2), num(3), num(4), num(5)]);
+                self.text_matrix = self.line_matrix;
+            }
+            b"T*" => self.next_line(),
+            b"Tj" | b"'" | b"\"" | b"TJ" => {
+                self.show_text(&operator, &operands, out);
+                return;
+            }
+            b"Do" => {
+                if let Some(name) = operands.first().and_then(|o| o.clone().into_name())
+                    && self.x_object_is_redacted(name)
+                {
+                    return;
+                }
+            }
+            b"BI" => {
+                let unit_square = Rect::new(0.0, 0.0, 1.0, 1.0);
+
+                if self.intersects(self.state.ctm.transform_rect_bbox(unit_square)) {
+                    return;
+                }
+
+                if let Some(Object::Stream(stream)) = operands.first() {
+                    // The inline image dictionary data ends with the `ID` operator.
+                    out.extend_from_slice(b"BI ");
+                    out.extend_from_slice(stream.dict().data());
+                    out.push(b' ');
+                    out.extend_from_slice(&stream.raw_data());
+                    out.extend_from_slice(b"\nEI\n");
+                }
+
+                return;
+            }
+            _ => {}
+        }
+
+        write_instruction(&operator, &operands, out);
+    }
+
+    fn move_line(&mut self, tx: f64, ty: f64) {
+        self.line_matrix *= Affine::translate((tx, ty));
+        self.text_matrix = self.line_matrix;
+    }
+
+    fn next_line(&mut self) {
+        self.move_line(0.0, -self.state.leading);
+    }
+
+    /// Load a font resource. Like in the interpreter, fonts that don't exist
+    /// are replaced with Helvetica.
+    fn font(&mut self, name: Name) -> Option<TextFont<'a>> {
+        if let Some(font) = self.fonts.get(name.as_ref()) {
+            return font.clone();
+        }
+
+        let font = match self.resources.get_font(name.clone()) {
+            Some(dict) => TextFont::new(&dict, &self.settings),
+            None => TextFont::new_standard(StandardFont::Helvetica, &self.settings),
+        };
+        self.fonts.insert(name.to_vec(), font.clone());
+
+        font
+    }
+
+    /// The factor that converts `TJ` adjustments into text space units along
+    /// the writing direction, without their sign.
+    fn adjustment_scale(&self) -> f64 {
+        let horizontal = self.state.font.as_ref().is_none_or(TextFont::is_horizontal);
+
+        if horizontal {
+            self.state.font_size * self.state.horizontal_scaling / 1000.0
+        } else {
+            self.state.font_size / 1000.0
+        }
+    }
+
+    /// Advance the text matrix along the writing direction.
+    fn advance(&mut self, advance: f64) {
+        let horizontal = self.state.font.as_ref().is_none_or(TextFont::is_horizontal);
+        let translation = if horizontal {
+            (advance, 0.0)
+        } else {
+            (0.0, advance)
+        };
+
+        self.text_matrix *= Affine::translate(translation);
+    }
+
+    fn x_object_is_redacted(&self, name: Name) -> bool {
+        let Some(stream) = self.resources.get_x_object(name) else {
+            return false;
+        };
+
+        let dict = stream.dict();
+        let subtype = dict.get::<Name>(SUBTYPE);
+
+        let bbox = match subtype.as_deref() {
+            Some(IMAGE) => self
+                .state
+                .ctm
+                .transform_rect_bbox(Rect::new(0.0, 0.0, 1.0, 1.0)),
+            Some(FORM) => {
+                let Some(bbox) = dict.get::<[f64; 4]>(BBOX) else {
+                    return false;
+                };
+                let matrix = dict
+                    .get::<[f64; 6]>(MATRIX)
+                    .map(Affine::new)
+                    .unwrap_or_default();
+
+                (self.state.ctm * matrix)
+                    .transform_rect_bbox(Rect::new(bbox[0], bbox[1], bbox[2], bbox[3]).abs())
+            }
+            _ => return false,
+        };
+
+        self.intersects(bbox)
+    }
+
+    /// Split a string into glyphs, advancing the text matrix.
+    fn glyphs<'c>(&mut self, bytes: &'c [u8]) -> Vec<Glyph<'c>> {
+        let Some(font) = self.state.font.clone() else {
+            return vec![];
+        };
+
+        let fs = self.state.font_size;
+        let th = self.state.horizontal_scaling;
+        let horizontal = font.is_horizontal();
+        let glyph_transform = Affine::new([fs * th, 0.0, 0.0, fs, 0.0, self.state.rise]);
+
+        let mut glyphs = vec![];
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let (code, len) = font.read_code(bytes, offset);
+            let code_bytes = &bytes[offset..(offset + len).min(bytes.len())];
+            offset += len;
+
+            // Glyphs are assumed to reach from a bit below the baseline to the
+            // top of the em box, or to hang from their origin in vertical fonts.
+            let code_advance = font.code_advance(code) / 1000.0;
+            let glyph_box = if horizontal {
+                Rect::new(0.0, -0.25, code_advance.x.max(0.0), 1.0)
+            } else {
+                Rect::new(-0.5, code_advance.y.min(0.0), 0.5, 0.0)
+            };
+            let trm = self.state.ctm * self.text_matrix * glyph_transform;
+            let bbox = trm.transform_rect_bbox(glyph_box);
+
+            // Word spacing only applies to the single-byte code 32.
+            let word_spacing = if code == 32 && len == 1 {
+                self.state.word_spacing
+            } else {
+                0.0
+            };
+            let spacing = self.state.char_spacing + word_spacing;
+            let advance = if horizontal {
+                (code_advance.x * fs + spacing) * th
+            } else {
+                code_advance.y * fs + spacing
+            };
+            self.advance(advance);
+
+            glyphs.push(Glyph {
+                bytes: code_bytes,
+                removed: self.intersects(bbox),
+                advance,
+            });
+        }
+
+        glyphs
+    }
+
+    fn show_text(&mut self, operator: &[u8], operands: &[Object<'_>], out: &mut Vec<u8>) {
+        let (string_operand, prefix) = match operator {
+            b"'" => {
+                self.next_line();
+                (operands.first(), b"T*\n".to_vec())
+            }
+            b"\"" => {
+                let mut prefix = vec![];
+
+                if let (Some(aw), Some(ac)) = (operands.first(), operands.get(1)) {
+                    self.state.word_spacing =
+                        aw.clone().into_number().map(|n| n.as_f64()).unwrap_or(0.0);
+                    self.state.char_spacing =
+                        ac.clone().into_number().map(|n| n.as_f64()).unwrap_or(0.0);
+                    write_object(aw, &mut prefix);
+                    prefix.extend_from_slice(b"Tw ");
+                    write_object(ac, &mut prefix);
+                    prefix.extend_from_slice(b"Tc\n");
+                }
+
+                self.next_line();
+                prefix.extend_from_slice(b"T*\n");
+                (operands.get(2), prefix)
+            }
+            _ => (operands.first(), vec![]),
+        };
+
+        let mut parts = vec![];
+        let mut any_removed = false;
+
+        let mut push_string = |filter: &mut Self, bytes: &[u8], parts: &mut Vec<TextPart>| {
+            let mut current = vec![];
+
+            for glyph in filter.glyphs(bytes) {
+                if glyph.removed {
+                    any_removed = true;
+
+                    if !current.is_empty() {
+                        parts.push(TextPart::Text(std::mem::take(&mut current)));
+                    }
+
+                    let scale = filter.adjustment_scale();
+
+                    if scale != 0.0 {
+                        parts.push(TextPart::Adjustment(-glyph.advance / scale));
+                    }
+                } else {
+                    current.extend_from_slice(glyph.bytes);
+                }
+            }
+
+            if !current.is_empty() {
+                parts.push(TextPart::Text(current));
+            }
+        };
+
+        match string_operand {
+            Some(Object::String(string)) => push_string(self, string.as_bytes(), &mut parts),
+            Some(Object::Array(array)) => {
+                for item in array.iter::<Object<'_>>() {
+                    match item {
+                        Object::String(string) => {
+                            push_string(self, string.as_bytes(), &mut parts);
+                        }
+                        Object::Number(n) => {
+                            let adjustment = n.as_f64();
+                            self.advance(-adjustment * self.adjustment_scale());
+                            parts.push(TextPart::Adjustment(adjustment));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if !any_removed {
+            write_instruction(operator, operands, out);
+            return;
+        }
+
+        out.extend_from_slice(&prefix);
+        out.push(b'[');
+
+        for part in parts {
+            match part {
+                TextPart::Text(bytes) => write_hex_string(&bytes, out),
+                TextPart::Adjustment(n) => out.extend_from_slice(fmt_num(n).as_bytes()),
+            }
+
+            out.push(b' ');
+        }
+
+        out.extend_from_slice(b"] TJ\n");
+    }
+}
+
+fn write_instruction(operator: &[u8], operands: &[Object<'_>], out: &mut Vec<u8>) {
+    for operand in operands {
+        write_object(operand, out);
+        out.push(b' ');
+    }
+
+    out.extend_from_slice(operator);
+    out.push(b'\n');
+}
+
+fn write_object(object: &Object<'_>, out: &mut Vec<u8>) {
+    match object {
+        Object::Null(_) => out.extend_from_slice(b"null"),
+        Object::Boolean(b) => out.extend_from_slice(if *b { &b"true"[..] } else { b"false" }),
+        Object::Number(n) => out.extend_from_slice(fmt_num(n.as_f64()).as_bytes()),
+        Object::String(s) => write_hex_string(s.as_bytes(), out),
+        Object::Name(n) => write_name(n.as_ref(), out),
+        Object::Dict(d) => out.extend_from_slice(d.data()),
+        Object::Array(a) => {
+            out.push(b'[');
+            out.extend_from_slice(a.data());
+            out.push(b']');
+        }
+        // Streams only appear as operands of inline images, which are handled separately.
+        Object::Stream(_) => {}
+    }
+}
+
+fn write_hex_string(bytes: &[u8], out: &mut Vec<u8>) {
+    out.push(b'<');
+
+    for b in bytes {
+        out.extend_from_slice(format!("{b:02X}").as_bytes());
+    }
+
+    out.push(b'>');
+}
+
+fn write_name(name: &[u8], out: &mut Vec<u8>) {
+    out.push(b'/');
+
+    for b in name {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'*' | b'+' | b',') {
+            out.push(*b);
+        } else {
+            out.extend_from_slice(format!("#{b:02X}").as_bytes());
+        }
+    }
+}
+
+fn fmt_num(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{n}")
+    }
+}
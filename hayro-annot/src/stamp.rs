@@ -0,0 +1,228 @@
+//! Image handling for stamp annotations.
+//!
+//! PNG images are decoded and embedded as deflate-compressed samples, with the
+//! alpha channel (if any) as a soft mask. JPEG images are embedded as they are
+//! with a `DCTDecode` filter, so only their header needs to be read.
+
+use crate::types::ImageFormat;
+use crate::writer::{RefAllocator, deflate_encode};
+use pdf_writer::{Chunk, Filter, Finish, Ref};
+
+/// The resource name of the image in the appearance stream of a stamp.
+pub(crate) const STAMP_IMAGE_NAME: &[u8] = b"Im0";
+
+/// The color space of the samples of a [`StampImage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+}
+
+/// An image that can be embedded as an image `XObject`, with 8 bits per
+/// component.
+pub(crate) struct StampImage {
+    width: u32,
+    height: u32,
+    color_space: ImageColorSpace,
+    /// The samples, encoded with `filter`.
+    data: Vec<u8>,
+    filter: Filter,
+    /// The deflate-encoded alpha channel, if the image isn't fully opaque.
+    alpha: Option<Vec<u8>>,
+}
+
+impl StampImage {
+    /// Prepare image data of the given format for embedding, returning `None`
+    /// if it can't be decoded.
+    pub(crate) fn new(data: &[u8], format: ImageFormat) -> Option<Self> {
+        match format {
+            ImageFormat::Png => Self::from_png(data),
+            ImageFormat::Jpeg => {
+                let (width, height, components) = jpeg_info(data)?;
+                let color_space = match components {
+                    1 => ImageColorSpace::Gray,
+                    3 => ImageColorSpace::Rgb,
+                    4 => ImageColorSpace::Cmyk,
+                    _ => return None,
+                };
+
+                Some(Self {
+                    width,
+                    height,
+                    color_space,
+                    data: data.to_vec(),
+                    filter: Filter::DctDecode,
+                    alpha: None,
+                })
+            }
+        }
+    }
+
+    fn from_png(data: &[u8]) -> Option<Self> {
+        let image = image::load_from_memory_with_format(data, image::ImageFormat::Png).ok()?;
+        let color = image.color();
+
+        let alpha = color
+            .has_alpha()
+            .then(|| {
+                image
+                    .to_rgba8()
+                    .pixels()
+                    .map(|pixel| pixel.0[3])
+                    .collect::<Vec<_>>()
+            })
+            .filter(|alpha| alpha.iter().any(|&a| a != u8::MAX));
+        let (color_space, samples) = if color.has_color() {
+            (ImageColorSpace::Rgb, image.to_rgb8().into_raw())
+        } else {
+            (ImageColorSpace::Gray, image.to_luma8().into_raw())
+        };
+
+        Some(Self {
+            width: image.width(),
+            height: image.height(),
+            color_space,
+            data: deflate_encode(&samples),
+            filter: Filter::FlateDecode,
+            alpha: alpha.map(|alpha| deflate_encode(&alpha)),
+        })
+    }
+
+    /// Write the image `XObject` to `image_ref`, along with its soft mask.
+    pub(crate) fn write(&self, chunk: &mut Chunk, allocator: &mut RefAllocator, image_ref: Ref) {
+        let smask_ref = self.alpha.as_ref().map(|alpha| {
+            let smask_ref = allocator.alloc();
+            let mut smask = chunk.image_xobject(smask_ref, alpha);
+            smask
+                .filter(Filter::FlateDecode)
+                .width(self.width as i32)
+                .height(self.height as i32)
+                .bits_per_component(8);
+            smask.color_space().device_gray();
+            smask.finish();
+            smask_ref
+        });
+
+        let mut image = chunk.image_xobject(image_ref, &self.data);
+        image
+            .filter(self.filter)
+            .width(self.width as i32)
+            .height(self.height as i32)
+            .bits_per_component(8);
+        match self.color_space {
+            ImageColorSpace::Gray => image.color_space().device_gray(),
+            ImageColorSpace::Rgb => image.color_space().device_rgb(),
+            ImageColorSpace::Cmyk => image.color_space().device_cmyk(),
+        }
+        if let Some(smask_ref) = smask_ref {
+            image.s_mask(smask_ref);
+        }
+        image.finish();
+    }
+}
+
+/// Read the width and height of an image from its header, without decoding it.
+pub(crate) fn image_size(data: &[u8], format: ImageFormat) -> Option<(u32, u32)> {
+    match format {
+        ImageFormat::Png => {
+            // The signature is followed by the `IHDR` chunk, which starts
+            // with the width and height.
+            const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+            if !data.starts_with(SIGNATURE) || data.get(12..16)? != b"IHDR" {
+                return None;
+            }
+
+            let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+            let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+            Some((width, height))
+        }
+        ImageFormat::Jpeg => jpeg_info(data).map(|(width, height, _)| (width, height)),
+    }
+}
+
+/// Read the width, height and number of components of a JPEG image from its
+/// start-of-frame segment.
+fn jpeg_info(data: &[u8]) -> Option<(u32, u32, u8)> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+
+        let marker = data[pos + 1];
+        // Markers may be preceded by any number of fill bytes.
+        if marker == 0xFF {
+            pos += 1;
+            continue;
+        }
+
+        let len = usize::from(u16::from_be_bytes([data[pos + 2], data[pos + 3]]));
+        // All `SOFn` markers, except for `DHT`, `JPG` and `DAC`.
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let segment = data.get(pos + 4..pos + 10)?;
+            let height = u16::from_be_bytes([segment[1], segment[2]]);
+            let width = u16::from_be_bytes([segment[3], segment[4]]);
+            return Some((u32::from(width), u32::from(height), segment[5]));
+        }
+
+        pos += 2 + len;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(color: image::ColorType) -> Vec<u8> {
+        let mut data = Vec::new();
+        image::write_buffer_with_format(
+            &mut std::io::Cursor::new(&mut data),
+            &vec![128; 3 * 2 * usize::from(color.bytes_per_pixel())],
+            3,
+            2,
+            color,
+            image::ImageFormat::Png,
+        )
+        .unwrap();
+        data
+    }
+
+    #[test]
+    fn png_size_is_read_from_header() {
+        let data = png(image::ColorType::Rgb8);
+        assert_eq!(image_size(&data, ImageFormat::Png), Some((3, 2)));
+        assert_eq!(image_size(&data, ImageFormat::Jpeg), None);
+    }
+
+    #[test]
+    fn png_alpha_becomes_soft_mask() {
+        let image = StampImage::new(&png(image::ColorType::Rgba8), ImageFormat::Png).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.color_space, ImageColorSpace::Rgb);
+        assert!(image.alpha.is_some());
+
+        let image = StampImage::new(&png(image::ColorType::L8), ImageFormat::Png).unwrap();
+        assert_eq!(image.color_space, ImageColorSpace::Gray);
+        assert!(image.alpha.is_none());
+    }
+
+    #[test]
+    fn jpeg_header_is_parsed() {
+        // SOI, an APP0 segment and a baseline SOF0 segment for a 5x4 RGB image.
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00];
+        data.extend([0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00, 0x04, 0x00, 0x05, 0x03]);
+        data.extend([0; 9]);
+
+        assert_eq!(jpeg_info(&data), Some((5, 4, 3)));
+        let image = StampImage::new(&data, ImageFormat::Jpeg).unwrap();
+        assert_eq!(image.color_space, ImageColorSpace::Rgb);
+        assert_eq!(image.data, data);
+    }
+}
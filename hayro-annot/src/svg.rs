@@ -0,0 +1,503 @@
+//! Exporting annotation appearances as SVG.
+//!
+//! The appearance stream of an annotation is translated operator by operator
+//! into SVG elements, so that a frontend can draw crisp, scalable overlays
+//! without rasterizing the page. Only the operators used by the appearance
+//! generators of this crate are supported; others are ignored.
+
+use crate::appearance::{
+    annotation_rect, appearance_rotation, blend_mode, fill_opacity, generate_appearance,
+};
+use crate::font::{EMBEDDED_FONT_NAME, EmbeddedFont, helvetica_text_width};
+use crate::types::{Annotation, BlendMode};
+use hayro_syntax::content::UntypedIter;
+use hayro_syntax::object::Object;
+use kurbo::Affine;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Convert the appearance of an annotation into a standalone SVG document.
+///
+/// The SVG is sized to the annotation rect, with its origin at the top-left
+/// corner of the rect, so it can be positioned directly over the rendered page.
+/// For rotated annotations, the rect is the one written to `/Rect`, which
+/// covers the rotated appearance.
+/// Annotations without a visible appearance (such as links) produce an empty SVG.
+pub fn annotation_to_svg(annot: &Annotation) -> String {
+    let base = annot.base();
+    let rotation = appearance_rotation(annot);
+    let rect = rotation.map_or_else(|| annotation_rect(annot), |(_, bounds)| bounds);
+    let width = (rect[2] - rect[0]).max(0.0);
+    let height = (rect[3] - rect[1]).max(0.0);
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = fmt_num(f64::from(width)),
+        h = fmt_num(f64::from(height)),
+    );
+
+    // Flip the y-axis so that appearance stream coordinates can be used as-is.
+    let _ = write!(
+        svg,
+        r#"<g transform="matrix(1 0 0 -1 0 {})""#,
+        fmt_num(f64::from(height))
+    );
+    if base.opacity < 1.0 {
+        let _ = write!(svg, r#" opacity="{}""#, fmt_num(f64::from(base.opacity)));
+    }
+    if let Some(opacity) = fill_opacity(annot) {
+        let _ = write!(svg, r#" fill-opacity="{}""#, fmt_num(f64::from(opacity)));
+    }
+    if let Some(mode) = blend_mode(annot) {
+        let _ = write!(svg, r#" style="mix-blend-mode:{}""#, css_blend_mode(mode));
+    }
+    svg.push('>');
+
+    let embedded_glyphs = match annot {
+        Annotation::FreeText(free_text) => free_text
+            .font_data
+            .as_ref()
+            .and_then(|data| EmbeddedFont::new((**data).as_ref()))
+            .map(|font| {
+                free_text
+                    .text
+                    .chars()
+                    .map(|c| (font.glyph(c), c))
+                    .collect::<HashMap<_, _>>()
+            }),
+        _ => None,
+    };
+
+    let content = generate_appearance(annot);
+    let mut converter = SvgConverter::new(embedded_glyphs);
+    for op in UntypedIter::new(&content) {
+        let operator = op.operator.to_vec();
+        converter.process(&operator, op.operands().collect());
+    }
+    if let Some((m, _)) = rotation {
+        let _ = write!(
+            svg,
+            r#"<g transform="matrix({} {} {} {} {} {})">"#,
+            fmt_num(f64::from(m[0])),
+            fmt_num(f64::from(m[1])),
+            fmt_num(f64::from(m[2])),
+            fmt_num(f64::from(m[3])),
+            fmt_num(f64::from(m[4])),
+            fmt_num(f64::from(m[5]))
+        );
+        svg.push_str(&converter.out);
+        svg.push_str("</g>");
+    } else {
+        svg.push_str(&converter.out);
+    }
+
+    svg.push_str("</g></svg>");
+    svg
+}
+
+#[derive(Clone)]
+struct GraphicsState {
+    transform: Affine,
+    fill: String,
+    stroke: String,
+    line_width: f64,
+    line_cap: &'static str,
+    line_join: &'static str,
+    font: Vec<u8>,
+    font_size: f64,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        Self {
+            transform: Affine::IDENTITY,
+            fill: "#000000".to_string(),
+            stroke: "#000000".to_string(),
+            line_width: 1.0,
+            line_cap: "butt",
+            line_join: "miter",
+            font: Vec::new(),
+            font_size: 0.0,
+        }
+    }
+}
+
+/// A path under construction, remembering whether it is a single rectangle.
+#[derive(Default)]
+struct PathBuilder {
+    data: String,
+    rect: Option<[f64; 4]>,
+    segments: usize,
+}
+
+struct SvgConverter {
+    state: GraphicsState,
+    state_stack: Vec<GraphicsState>,
+    path: PathBuilder,
+    text_matrix: Affine,
+    line_matrix: Affine,
+    /// Maps glyph IDs of an embedded font back to characters.
+    embedded_glyphs: Option<HashMap<u16, char>>,
+    out: String,
+}
+
+impl SvgConverter {
+    fn new(embedded_glyphs: Option<HashMap<u16, char>>) -> Self {
+        Self {
+            state: GraphicsState::default(),
+            state_stack: Vec::new(),
+            path: PathBuilder::default(),
+            text_matrix: Affine::IDENTITY,
+            line_matrix: Affine::IDENTITY,
+            embedded_glyphs,
+            out: String::new(),
+        }
+    }
+
+    fn process(&mut self, operator: &[u8], operands: Vec<Object<'_>>) {
+        let num = |i: usize| {
+            operands
+                .get(i)
+                .and_then(|o| o.clone().into_number())
+                .map(|n| n.as_f64())
+                .unwrap_or(0.0)
+        };
+
+        match operator {
+            b"q" => self.state_stack.push(self.state.clone()),
+            b"Q" => {
+                if let Some(state) = self.state_stack.pop() {
+                    self.state = state;
+                }
+            }
+            b"cm" => {
+                self.state.transform *=
+                    Affine::new([num(0), num(1), num(2), num(3), num(4), num(5)]);
+            }
+            b"w" => self.state.line_width = num(0),
+            b"J" => {
+                self.state.line_cap = match num(0) as i32 {
+                    1 => "round",
+                    2 => "square",
+                    _ => "butt",
+                };
+            }
+            b"j" => {
+                self.state.line_join = match num(0) as i32 {
+                    1 => "round",
+                    2 => "bevel",
+                    _ => "miter",
+                };
+            }
+            b"rg" => self.state.fill = rgb_color(num(0), num(1), num(2)),
+            b"RG" => self.state.stroke = rgb_color(num(0), num(1), num(2)),
+            b"g" => self.state.fill = rgb_color(num(0), num(0), num(0)),
+            b"G" => self.state.stroke = rgb_color(num(0), num(0), num(0)),
+            b"k" => self.state.fill = cmyk_color(num(0), num(1), num(2), num(3)),
+            b"K" => self.state.stroke = cmyk_color(num(0), num(1), num(2), num(3)),
+            b"m" => self.path_op('M', &[num(0), num(1)]),
+            b"l" => self.path_op('L', &[num(0), num(1)]),
+            b"c" => self.path_op('C', &[num(0), num(1), num(2), num(3), num(4), num(5)]),
+            b"h" => self.path_op('Z', &[]),
+            b"re" => {
+                let (x, y, w, h) = (num(0), num(1), num(2), num(3));
+                let is_first = self.path.segments == 0;
+                self.path_op('M', &[x, y]);
+                self.path_op('L', &[x + w, y]);
+                self.path_op('L', &[x + w, y + h]);
+                self.path_op('L', &[x, y + h]);
+                self.path_op('Z', &[]);
+                self.path.rect = is_first.then_some([x, y, w, h]);
+            }
+            b"f" | b"F" => self.paint(true, false, false),
+            b"f*" => self.paint(true, false, true),
+            b"S" => self.paint(false, true, false),
+            b"s" => {
+                self.path_op('Z', &[]);
+                self.paint(false, true, false);
+            }
+            b"B" => self.paint(true, true, false),
+            b"B*" => self.paint(true, true, true),
+            b"b" => {
+                self.path_op('Z', &[]);
+                self.paint(true, true, false);
+            }
+            b"b*" => {
+                self.path_op('Z', &[]);
+                self.paint(true, true, true);
+            }
+            b"n" => self.path = PathBuilder::default(),
+            b"BT" => {
+                self.text_matrix = Affine::IDENTITY;
+                self.line_matrix = Affine::IDENTITY;
+            }
+            b"Tf" => {
+                if let Some(Object::Name(name)) = operands.first() {
+                    self.state.font = name.as_ref().to_vec();
+                }
+                self.state.font_size = num(1);
+            }
+            b"Td" | b"TD" => {
+                self.line_matrix *= Affine::translate((num(0), num(1)));
+                self.text_matrix = self.line_matrix;
+            }
+            b"Tm" => {
+                self.line_matrix = Affine::new([num(0), num(1), num(2), num(3), num(4), num(5)]);
+                self.text_matrix = self.line_matrix;
+            }
+            b"Tj" => {
+                if let Some(Object::String(string)) = operands.first() {
+                    self.show_text(string.as_bytes());
+                }
+            }
+            b"TJ" => {
+                if let Some(Object::Array(array)) = operands.first() {
+                    for item in array.iter::<Object<'_>>() {
+                        match item {
+                            Object::String(string) => self.show_text(string.as_bytes()),
+                            Object::Number(n) => {
+                                let tx = -n.as_f64() / 1000.0 * self.state.font_size;
+                                self.text_matrix *= Affine::translate((tx, 0.0));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn path_op(&mut self, command: char, coords: &[f64]) {
+        self.path.data.push(command);
+        for (i, c) in coords.iter().enumerate() {
+            if i > 0 {
+                self.path.data.push(' ');
+            }
+            self.path.data.push_str(&fmt_num(*c));
+        }
+        self.path.segments += 1;
+        self.path.rect = None;
+    }
+
+    fn paint(&mut self, fill: bool, stroke: bool, even_odd: bool) {
+        let path = std::mem::take(&mut self.path);
+        if path.data.is_empty() {
+            return;
+        }
+
+        let mut element = match path.rect {
+            Some([x, y, w, h]) => format!(
+                r#"<rect x="{}" y="{}" width="{}" height="{}""#,
+                fmt_num(x.min(x + w)),
+                fmt_num(y.min(y + h)),
+                fmt_num(w.abs()),
+                fmt_num(h.abs())
+            ),
+            None => format!(r#"<path d="{}""#, path.data),
+        };
+
+        if self.state.transform != Affine::IDENTITY {
+            let c = self.state.transform.as_coeffs();
+            let _ = write!(
+                element,
+                r#" transform="matrix({} {} {} {} {} {})""#,
+                fmt_num(c[0]),
+                fmt_num(c[1]),
+                fmt_num(c[2]),
+                fmt_num(c[3]),
+                fmt_num(c[4]),
+                fmt_num(c[5])
+            );
+        }
+
+        if fill {
+            let _ = write!(element, r#" fill="{}""#, self.state.fill);
+            if even_odd {
+                element.push_str(r#" fill-rule="evenodd""#);
+            }
+        } else {
+            element.push_str(r#" fill="none""#);
+        }
+
+        if stroke {
+            let _ = write!(
+                element,
+                r#" stroke="{}" stroke-width="{}" stroke-linecap="{}" stroke-linejoin="{}""#,
+                self.state.stroke,
+                fmt_num(self.state.line_width),
+                self.state.line_cap,
+                self.state.line_join
+            );
+        }
+
+        element.push_str("/>");
+        self.out.push_str(&element);
+    }
+
+    fn show_text(&mut self, bytes: &[u8]) {
+        let is_embedded = self.state.font == EMBEDDED_FONT_NAME;
+        let text: String = match (&self.embedded_glyphs, is_embedded) {
+            (Some(glyphs), true) => bytes
+                .chunks_exact(2)
+                .map(|g| {
+                    glyphs
+                        .get(&u16::from_be_bytes([g[0], g[1]]))
+                        .copied()
+                        .unwrap_or('\u{FFFD}')
+                })
+                .collect(),
+            // The built-in font uses `WinAnsiEncoding`, which matches Latin-1 for all
+            // characters that we write except for a few punctuation marks.
+            _ => bytes.iter().map(|b| char::from(*b)).collect(),
+        };
+
+        if text.is_empty() {
+            return;
+        }
+
+        // Undo the y-flip of the enclosing group so that glyphs are upright.
+        let size = self.state.font_size;
+        let matrix = self.state.transform
+            * self.text_matrix
+            * Affine::new([size, 0.0, 0.0, -size, 0.0, 0.0]);
+        let c = matrix.as_coeffs();
+        let _ = write!(
+            self.out,
+            r#"<text transform="matrix({} {} {} {} {} {})" font-size="1" font-family="Helvetica, Arial, sans-serif" fill="{}" xml:space="preserve">{}</text>"#,
+            fmt_num(c[0]),
+            fmt_num(c[1]),
+            fmt_num(c[2]),
+            fmt_num(c[3]),
+            fmt_num(c[4]),
+            fmt_num(c[5]),
+            self.state.fill,
+            escape_xml(&text)
+        );
+
+        let advance = if is_embedded {
+            // Embedded glyph widths aren't needed for the appearances we generate,
+            // which position every line explicitly.
+            0.0
+        } else {
+            f64::from(helvetica_text_width(&text))
+        };
+        self.text_matrix *= Affine::translate((advance / 1000.0 * size, 0.0));
+    }
+}
+
+/// The CSS `mix-blend-mode` value of a blend mode.
+fn css_blend_mode(mode: BlendMode) -> &'static str {
+    match mode {
+        BlendMode::Normal => "normal",
+        BlendMode::Multiply => "multiply",
+        BlendMode::Screen => "screen",
+        BlendMode::Overlay => "overlay",
+        BlendMode::Darken => "darken",
+        BlendMode::Lighten => "lighten",
+        BlendMode::ColorDodge => "color-dodge",
+        BlendMode::ColorBurn => "color-burn",
+        BlendMode::HardLight => "hard-light",
+        BlendMode::SoftLight => "soft-light",
+        BlendMode::Difference => "difference",
+        BlendMode::Exclusion => "exclusion",
+        BlendMode::Hue => "hue",
+        BlendMode::Saturation => "saturation",
+        BlendMode::Color => "color",
+        BlendMode::Luminosity => "luminosity",
+    }
+}
+
+fn rgb_color(r: f64, g: f64, b: f64) -> String {
+    let channel = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b))
+}
+
+fn cmyk_color(c: f64, m: f64, y: f64, k: f64) -> String {
+    rgb_color(
+        (1.0 - c) * (1.0 - k),
+        (1.0 - m) * (1.0 - k),
+        (1.0 - y) * (1.0 - k),
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn fmt_num(n: f64) -> String {
+    let rounded = (n * 1000.0).round() / 1000.0;
+    if rounded.fract() == 0.0 {
+        format!("{}", rounded as i64)
+    } else {
+        format!("{rounded}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        AnnotColor, AnnotationBase, BorderStyle, FreeTextAnnot, ShapeAnnot, VAlign,
+    };
+
+    #[test]
+    fn square_produces_rect_with_matching_dimensions() {
+        let annot = Annotation::Square(ShapeAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 100.0, 180.0, 140.0],
+                color: Some(AnnotColor::red()),
+                ..Default::default()
+            },
+            interior_color: None,
+            line_width: 2.0,
+            is_circle: false,
+            border_style: BorderStyle::Solid,
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
+        });
+
+        let svg = annotation_to_svg(&annot);
+        assert!(svg.starts_with("<svg"), "{svg}");
+        assert!(svg.contains(r#"width="80" height="40""#), "{svg}");
+        assert!(
+            svg.contains(r#"<rect x="1" y="1" width="78" height="38""#),
+            "{svg}"
+        );
+        assert!(svg.contains(r##"stroke="#ff0000""##), "{svg}");
+        assert!(svg.ends_with("</g></svg>"), "{svg}");
+    }
+
+    #[test]
+    fn freetext_produces_escaped_text() {
+        let annot = Annotation::FreeText(FreeTextAnnot {
+            base: AnnotationBase {
+                rect: [0.0, 0.0, 200.0, 40.0],
+                ..Default::default()
+            },
+            text: "a < b & café".to_string(),
+            font_size: 12.0,
+            default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+            font_data: None,
+            multiline: false,
+            line_spacing: 1.2,
+            vertical_align: VAlign::Top,
+            callout_line: None,
+            rich_text: None,
+            default_style: None,
+        });
+
+        let svg = annotation_to_svg(&annot);
+        assert!(svg.contains("a &lt; b &amp; café</text>"), "{svg}");
+    }
+}
@@ -1,20 +1,55 @@
 //! Annotation type definitions.
 
-/// An RGB color with components in the 0.0..1.0 range.
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use bitflags::bitflags;
+use core::fmt::{Debug, Formatter};
+
+/// A color with components in the 0.0..1.0 range.
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub struct AnnotColor {
-    /// Red component.
-    pub r: f32,
-    /// Green component.
-    pub g: f32,
-    /// Blue component.
-    pub b: f32,
+pub enum AnnotColor {
+    /// A `DeviceGray` color.
+    Gray(f32),
+    /// A `DeviceRGB` color with red, green and blue components.
+    Rgb(f32, f32, f32),
+    /// A `DeviceCMYK` color with cyan, magenta, yellow and black components.
+    Cmyk(f32, f32, f32, f32),
 }
 
 impl AnnotColor {
-    /// Create a new color.
+    /// Create a new RGB color.
     pub fn new(r: f32, g: f32, b: f32) -> Self {
-        Self { r, g, b }
+        Self::Rgb(r, g, b)
+    }
+
+    /// Create a new gray color.
+    pub fn gray(v: f32) -> Self {
+        Self::Gray(v)
+    }
+
+    /// Create a new CMYK color.
+    pub fn cmyk(c: f32, m: f32, y: f32, k: f32) -> Self {
+        Self::Cmyk(c, m, y, k)
+    }
+
+    /// The components of the color, as written to a color array like `/C`.
+    pub fn components(&self) -> Vec<f32> {
+        match *self {
+            Self::Gray(g) => vec![g],
+            Self::Rgb(r, g, b) => vec![r, g, b],
+            Self::Cmyk(c, m, y, k) => vec![c, m, y, k],
+        }
+    }
+
+    /// Apply `f` to every component of the color.
+    pub fn map(self, f: impl Fn(f32) -> f32) -> Self {
+        match self {
+            Self::Gray(g) => Self::Gray(f(g)),
+            Self::Rgb(r, g, b) => Self::Rgb(f(r), f(g), f(b)),
+            Self::Cmyk(c, m, y, k) => Self::Cmyk(f(c), f(m), f(y), f(k)),
+        }
     }
 
     /// Yellow color (default for highlights).
@@ -44,13 +79,44 @@ pub struct AnnotationBase {
     pub author: Option<String>,
     /// The text contents of the annotation.
     pub contents: Option<String>,
-    /// The modification date as a PDF date string.
+    /// The modification date as a PDF date string, see [`PdfDate`](crate::PdfDate).
+    ///
+    /// Malformed dates are normalized or left out when writing.
     pub modified: Option<String>,
+    /// The creation date as a PDF date string (`/CreationDate`).
+    pub created: Option<String>,
+    /// The unique name of the annotation on its page (`/NM`).
+    ///
+    /// When not set, a UUID-style name derived from the annotation is
+    /// generated on save.
+    pub name: Option<String>,
+    /// A short description of the subject of the annotation (`/Subj`).
+    pub subject: Option<String>,
+    /// The intent of the annotation (`/IT`), such as `FreeTextCallout`.
+    ///
+    /// When not set, an intent matching the annotation is written where the
+    /// type has one, e.g. `LineArrow` for lines with an arrowhead.
+    pub intent: Option<String>,
     /// Annotation flags bitmask (see PDF spec Table 165).
     /// Bit 2 (value 4) = Print flag.
+    ///
+    /// [`AnnotationFlags`] can be used to compose the bitmask.
     pub flags: u32,
     /// Opacity from 0.0 (transparent) to 1.0 (opaque). Default is 1.0.
     pub opacity: f32,
+    /// The blend mode the appearance is composited with.
+    ///
+    /// When not set, highlights use [`BlendMode::Multiply`] so that the
+    /// highlighted text shows through, and all other types use normal
+    /// compositing.
+    pub blend_mode: Option<BlendMode>,
+    /// The counterclockwise rotation of the appearance around the center of
+    /// `rect`, in degrees.
+    ///
+    /// The appearance is drawn unrotated in `rect` and rotated with the
+    /// `/Matrix` of its form `XObject`. The written `/Rect` is grown to the
+    /// bounds of the rotated appearance, so it isn't clipped.
+    pub rotation: f32,
 }
 
 impl Default for AnnotationBase {
@@ -61,12 +127,209 @@ impl Default for AnnotationBase {
             author: None,
             contents: None,
             modified: None,
-            flags: 4, // Print flag set by default
+            created: None,
+            name: None,
+            subject: None,
+            intent: None,
+            flags: AnnotationFlags::PRINT.bits(),
             opacity: 1.0,
+            blend_mode: None,
+            rotation: 0.0,
         }
     }
 }
 
+impl AnnotationBase {
+    /// Set the annotation flags.
+    pub fn with_flags(mut self, flags: AnnotationFlags) -> Self {
+        self.flags = flags.bits();
+        self
+    }
+
+    /// The annotation flags, including any bits without a name.
+    pub fn annotation_flags(&self) -> AnnotationFlags {
+        AnnotationFlags::from_bits_retain(self.flags)
+    }
+}
+
+bitflags! {
+    /// The flags of an annotation (`/F`), which can be combined with `|`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct AnnotationFlags: u32 {
+        /// Hide the annotation if its type isn't supported by the viewer.
+        const INVISIBLE = 1 << 0;
+        /// Neither display nor print the annotation.
+        const HIDDEN = 1 << 1;
+        /// Print the annotation along with the page.
+        const PRINT = 1 << 2;
+        /// Don't scale the appearance with the page magnification.
+        const NO_ZOOM = 1 << 3;
+        /// Don't rotate the appearance with the page.
+        const NO_ROTATE = 1 << 4;
+        /// Don't display the annotation, but still allow printing it.
+        const NO_VIEW = 1 << 5;
+        /// Don't allow the user to interact with the annotation.
+        const READ_ONLY = 1 << 6;
+        /// Don't allow the annotation to be moved, resized or deleted.
+        const LOCKED = 1 << 7;
+        /// Invert the `NO_VIEW` flag for certain events.
+        const TOGGLE_NO_VIEW = 1 << 8;
+        /// Don't allow the contents of the annotation to be modified.
+        const LOCKED_CONTENTS = 1 << 9;
+    }
+}
+
+impl Default for AnnotationFlags {
+    /// Annotations are printed by default.
+    fn default() -> Self {
+        Self::PRINT
+    }
+}
+
+impl From<AnnotationFlags> for u32 {
+    fn from(flags: AnnotationFlags) -> Self {
+        flags.bits()
+    }
+}
+
+/// A blend mode for compositing an annotation appearance with the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// The appearance is painted over the page.
+    #[default]
+    Normal,
+    /// The colors are multiplied, so the result is never lighter.
+    Multiply,
+    /// The complements of the colors are multiplied, so the result is never darker.
+    Screen,
+    /// Multiplies or screens the colors, depending on the backdrop.
+    Overlay,
+    /// The darker of the colors is kept.
+    Darken,
+    /// The lighter of the colors is kept.
+    Lighten,
+    /// The backdrop is brightened to reflect the appearance.
+    ColorDodge,
+    /// The backdrop is darkened to reflect the appearance.
+    ColorBurn,
+    /// Multiplies or screens the colors, depending on the appearance.
+    HardLight,
+    /// Darkens or lightens the colors, depending on the appearance.
+    SoftLight,
+    /// The darker color is subtracted from the lighter one.
+    Difference,
+    /// Like `Difference`, but with lower contrast.
+    Exclusion,
+    /// The hue of the appearance with the saturation and luminosity of the backdrop.
+    Hue,
+    /// The saturation of the appearance with the hue and luminosity of the backdrop.
+    Saturation,
+    /// The hue and saturation of the appearance with the luminosity of the backdrop.
+    Color,
+    /// The luminosity of the appearance with the hue and saturation of the backdrop.
+    Luminosity,
+}
+
+impl BlendMode {
+    /// The PDF name of this blend mode, as written to `/BM`.
+    pub fn pdf_name(self) -> &'static [u8] {
+        match self {
+            Self::Normal => b"Normal",
+            Self::Multiply => b"Multiply",
+            Self::Screen => b"Screen",
+            Self::Overlay => b"Overlay",
+            Self::Darken => b"Darken",
+            Self::Lighten => b"Lighten",
+            Self::ColorDodge => b"ColorDodge",
+            Self::ColorBurn => b"ColorBurn",
+            Self::HardLight => b"HardLight",
+            Self::SoftLight => b"SoftLight",
+            Self::Difference => b"Difference",
+            Self::Exclusion => b"Exclusion",
+            Self::Hue => b"Hue",
+            Self::Saturation => b"Saturation",
+            Self::Color => b"Color",
+            Self::Luminosity => b"Luminosity",
+        }
+    }
+
+    /// Parse a blend mode from its PDF name.
+    pub fn from_pdf_name(name: &[u8]) -> Option<Self> {
+        Some(match name {
+            b"Normal" | b"Compatible" => Self::Normal,
+            b"Multiply" => Self::Multiply,
+            b"Screen" => Self::Screen,
+            b"Overlay" => Self::Overlay,
+            b"Darken" => Self::Darken,
+            b"Lighten" => Self::Lighten,
+            b"ColorDodge" => Self::ColorDodge,
+            b"ColorBurn" => Self::ColorBurn,
+            b"HardLight" => Self::HardLight,
+            b"SoftLight" => Self::SoftLight,
+            b"Difference" => Self::Difference,
+            b"Exclusion" => Self::Exclusion,
+            b"Hue" => Self::Hue,
+            b"Saturation" => Self::Saturation,
+            b"Color" => Self::Color,
+            b"Luminosity" => Self::Luminosity,
+            _ => return None,
+        })
+    }
+}
+
+/// A comment of a markup annotation, as extracted by `extract_comments`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// The index of the page the annotation is on.
+    pub page: usize,
+    /// The index of the annotation in the list `read_annotations` returns for its page.
+    pub index: usize,
+    /// The author of the comment (`/T`).
+    pub author: Option<String>,
+    /// The text of the comment (`/Contents`).
+    pub contents: Option<String>,
+    /// The modification date as a PDF date string (`/M`).
+    pub modified: Option<String>,
+    /// The annotation rectangle in PDF coordinates `[x0, y0, x1, y1]`.
+    pub rect: [f32; 4],
+    /// The annotation subtype, for example `Text` or `Highlight`.
+    pub subtype: String,
+    /// The page and annotation index of the comment this one replies to.
+    pub in_reply_to: Option<(usize, usize)>,
+    /// The replies to this comment.
+    pub replies: Vec<Self>,
+}
+
+/// An item of the document outline (bookmarks), as read by `read_outline`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    /// The title shown for the item (`/Title`).
+    pub title: String,
+    /// The index of the page the item links to, if it links to a page of the
+    /// document.
+    pub page: Option<usize>,
+    /// The nested items.
+    pub children: Vec<Self>,
+}
+
+/// A link of a page, as read by `extract_links`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedLink {
+    /// The clickable area in page coordinates `[x0, y0, x1, y1]`.
+    pub rect: [f32; 4],
+    /// Where the link leads.
+    pub target: LinkTarget,
+}
+
+/// The target of an [`ExtractedLink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    /// A URI, usually of a web page.
+    Uri(String),
+    /// The index of a page of the document.
+    Page(usize),
+}
+
 /// A PDF annotation that can be created and written.
 #[derive(Debug, Clone)]
 pub enum Annotation {
@@ -88,6 +351,10 @@ pub enum Annotation {
     Circle(ShapeAnnot),
     /// A line annotation.
     Line(LineAnnot),
+    /// A closed polygon annotation.
+    Polygon(PolyAnnot),
+    /// An open polyline annotation.
+    PolyLine(PolyAnnot),
     /// A text (sticky note) annotation.
     Text(TextAnnot),
     /// A link annotation.
@@ -96,6 +363,16 @@ pub enum Annotation {
     TextField(TextFieldAnnot),
     /// A signature form field widget annotation.
     SignatureField(SignatureFieldAnnot),
+    /// A checkbox form field widget annotation.
+    CheckBox(CheckBoxAnnot),
+    /// A choice (dropdown or list box) form field widget annotation.
+    Choice(ChoiceFieldAnnot),
+    /// A rubber stamp annotation showing an image.
+    Stamp(StampAnnot),
+    /// A file attachment annotation with an embedded file.
+    FileAttachment(FileAttachmentAnnot),
+    /// A redaction annotation, marking content to be removed.
+    Redact(RedactAnnot),
 }
 
 impl Annotation {
@@ -111,10 +388,17 @@ impl Annotation {
             Self::Square(a) => &a.base,
             Self::Circle(a) => &a.base,
             Self::Line(a) => &a.base,
+            Self::Polygon(a) => &a.base,
+            Self::PolyLine(a) => &a.base,
             Self::Text(a) => &a.base,
             Self::Link(a) => &a.base,
             Self::TextField(a) => &a.base,
             Self::SignatureField(a) => &a.base,
+            Self::CheckBox(a) => &a.base,
+            Self::Choice(a) => &a.base,
+            Self::Stamp(a) => &a.base,
+            Self::FileAttachment(a) => &a.base,
+            Self::Redact(a) => &a.base,
         }
     }
 
@@ -130,10 +414,17 @@ impl Annotation {
             Self::Square(a) => &mut a.base,
             Self::Circle(a) => &mut a.base,
             Self::Line(a) => &mut a.base,
+            Self::Polygon(a) => &mut a.base,
+            Self::PolyLine(a) => &mut a.base,
             Self::Text(a) => &mut a.base,
             Self::Link(a) => &mut a.base,
             Self::TextField(a) => &mut a.base,
             Self::SignatureField(a) => &mut a.base,
+            Self::CheckBox(a) => &mut a.base,
+            Self::Choice(a) => &mut a.base,
+            Self::Stamp(a) => &mut a.base,
+            Self::FileAttachment(a) => &mut a.base,
+            Self::Redact(a) => &mut a.base,
         }
     }
 
@@ -149,10 +440,17 @@ impl Annotation {
             Self::Square(_) => "square",
             Self::Circle(_) => "circle",
             Self::Line(_) => "line",
+            Self::Polygon(_) => "polygon",
+            Self::PolyLine(_) => "polyline",
             Self::Text(_) => "text",
             Self::Link(_) => "link",
             Self::TextField(_) => "textfield",
             Self::SignatureField(_) => "signaturefield",
+            Self::CheckBox(_) => "checkbox",
+            Self::Choice(_) => "choice",
+            Self::Stamp(_) => "stamp",
+            Self::FileAttachment(_) => "fileattachment",
+            Self::Redact(_) => "redact",
         }
     }
 }
@@ -164,9 +462,36 @@ pub struct HighlightAnnot {
     pub base: AnnotationBase,
     /// `QuadPoints` — groups of 8 floats defining the highlighted regions.
     /// Each group of 8 defines 4 points (x1,y1, x2,y2, x3,y3, x4,y4).
+    ///
+    /// The points of a group are the upper-left, upper-right, lower-left and
+    /// lower-right corner of the region, where "upper" is the top of the text.
+    /// This is the order viewers such as Acrobat expect, even though it differs
+    /// from the counterclockwise order described in the specification.
     pub quad_points: Vec<f32>,
 }
 
+impl HighlightAnnot {
+    /// Create a highlight covering `rect`, with a single quad for the rect in
+    /// the correct point order.
+    pub fn from_rect(rect: [f32; 4], color: AnnotColor) -> Self {
+        let [x0, y0, x1, y1] = [
+            rect[0].min(rect[2]),
+            rect[1].min(rect[3]),
+            rect[0].max(rect[2]),
+            rect[1].max(rect[3]),
+        ];
+
+        Self {
+            base: AnnotationBase {
+                rect: [x0, y0, x1, y1],
+                color: Some(color),
+                ..Default::default()
+            },
+            quad_points: vec![x0, y1, x1, y1, x0, y0, x1, y0],
+        }
+    }
+}
+
 /// An underline markup annotation.
 #[derive(Debug, Clone)]
 pub struct UnderlineAnnot {
@@ -194,8 +519,23 @@ pub struct SquigglyAnnot {
     pub quad_points: Vec<f32>,
 }
 
-/// A free text (text box) annotation.
+/// A redaction annotation, marking content that is removed when the
+/// redactions of a document are applied with
+/// [`apply_redaction_annotations`](crate::apply_redaction_annotations).
 #[derive(Debug, Clone)]
+pub struct RedactAnnot {
+    /// Base annotation fields.
+    pub base: AnnotationBase,
+    /// `QuadPoints` — groups of 8 floats defining the redacted regions. If
+    /// empty, the whole annotation rect is redacted.
+    pub quad_points: Vec<f32>,
+    /// The color the redacted regions are filled with once applied (`/IC`).
+    /// Defaults to black.
+    pub overlay_color: Option<AnnotColor>,
+}
+
+/// A free text (text box) annotation.
+#[derive(Clone)]
 pub struct FreeTextAnnot {
     /// Base annotation fields.
     pub base: AnnotationBase,
@@ -205,6 +545,68 @@ pub struct FreeTextAnnot {
     pub font_size: f32,
     /// Default appearance string (e.g. `"0 0 0 rg /Helv 12 Tf"`).
     pub default_appearance: String,
+    /// An optional TrueType or OpenType font used to render the text.
+    ///
+    /// When set, the font is embedded so that text outside `WinAnsiEncoding`
+    /// renders correctly. Otherwise, the built-in Helvetica font is used.
+    pub font_data: Option<Arc<dyn AsRef<[u8]>>>,
+    /// Whether the text is word-wrapped to the width of the rect.
+    pub multiline: bool,
+    /// The distance between the baselines of consecutive lines, as a multiple
+    /// of the font size. The default is 1.2.
+    ///
+    /// PDF has no entry for it, so it is only part of the appearance stream and
+    /// can't be read back.
+    pub line_spacing: f32,
+    /// How the block of text is aligned vertically in the rect.
+    ///
+    /// Like `line_spacing`, this is only part of the appearance stream.
+    pub vertical_align: VAlign,
+    /// An optional callout line of two or three points (`/CL`), starting at
+    /// the point the callout refers to and ending at the text box.
+    ///
+    /// The rect of the annotation holds the text box only; it is grown to
+    /// include the callout line on save.
+    pub callout_line: Option<Vec<[f32; 2]>>,
+    /// Optional rich text (`/RC`), an XHTML fragment such as
+    /// `<p><b>Bold</b> comment</p>`.
+    ///
+    /// Fragments without a `<body>` root are wrapped in one on save. Plain
+    /// text is still written to `/Contents` for viewers that ignore `/RC`.
+    pub rich_text: Option<String>,
+    /// Optional default style string for the rich text (`/DS`), e.g.
+    /// `"font: Helvetica 12pt; color: #FF0000"`.
+    pub default_style: Option<String>,
+}
+
+impl Debug for FreeTextAnnot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FreeTextAnnot")
+            .field("base", &self.base)
+            .field("text", &self.text)
+            .field("font_size", &self.font_size)
+            .field("default_appearance", &self.default_appearance)
+            .field("font_data", &self.font_data.as_ref().map(|_| ".."))
+            .field("multiline", &self.multiline)
+            .field("line_spacing", &self.line_spacing)
+            .field("vertical_align", &self.vertical_align)
+            .field("callout_line", &self.callout_line)
+            .field("rich_text", &self.rich_text)
+            .field("default_style", &self.default_style)
+            .finish()
+    }
+}
+
+/// The vertical alignment of the text in a [`FreeTextAnnot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VAlign {
+    /// Text starts at the top of the rect.
+    #[default]
+    Top,
+    /// Text is centered vertically in the rect.
+    Middle,
+    /// Text ends at the bottom of the rect.
+    Bottom,
 }
 
 /// An ink (freehand drawing) annotation.
@@ -216,6 +618,16 @@ pub struct InkAnnot {
     pub ink_list: Vec<Vec<[f32; 2]>>,
     /// The stroke line width.
     pub line_width: f32,
+    /// How much the paths are smoothed, between 0 and 1.
+    ///
+    /// At 0, points are connected with straight lines. Otherwise, each path is
+    /// drawn as a Catmull-Rom spline through its points, where 1 is the
+    /// standard spline and smaller values keep closer to the straight lines.
+    pub smoothing: f32,
+    /// An optional dash pattern of alternating dash and gap lengths.
+    ///
+    /// A pattern without any positive length is drawn as a solid line.
+    pub dash: Option<Vec<f32>>,
 }
 
 /// A shape (square or circle) annotation.
@@ -229,6 +641,30 @@ pub struct ShapeAnnot {
     pub line_width: f32,
     /// Whether this is a circle (`true`) or square (`false`).
     pub is_circle: bool,
+    /// The style of the border.
+    pub border_style: BorderStyle,
+    /// The opacity of the interior, from 0.0 (transparent) to 1.0 (opaque).
+    ///
+    /// This is applied on top of `base.opacity`, which also covers the border.
+    pub fill_opacity: f32,
+    /// The radius of the corners of a square, or 0.0 for sharp corners.
+    ///
+    /// The radius is clamped to half the smaller side. PDF has no entry for
+    /// it, so it is only part of the appearance stream and can't be read back.
+    /// Ignored for circles and cloudy borders.
+    pub corner_radius: f32,
+}
+
+/// The style of a shape's border.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum BorderStyle {
+    /// A solid border.
+    #[default]
+    Solid,
+    /// A dashed border, with alternating dash and gap lengths.
+    Dashed(Vec<f32>),
+    /// A cloudy border made of scalloped arcs, with an intensity between 0 and 2.
+    Cloudy(f32),
 }
 
 /// A line annotation.
@@ -242,6 +678,99 @@ pub struct LineAnnot {
     pub end: [f32; 2],
     /// The stroke line width.
     pub line_width: f32,
+    /// The ending drawn at the start point.
+    pub start_style: LineEnding,
+    /// The ending drawn at the end point.
+    pub end_style: LineEnding,
+    /// An optional measurement scale (`/Measure`). When set, the length of the
+    /// line in real-world units is displayed at its midpoint.
+    pub measure: Option<MeasureScale>,
+    /// An optional dash pattern of alternating dash and gap lengths, e.g. for
+    /// leader lines. Line endings are always drawn solid.
+    ///
+    /// A pattern without any positive length is drawn as a solid line.
+    pub dash: Option<Vec<f32>>,
+}
+
+/// A measurement scale of a line annotation, which converts the length of the
+/// line into real-world units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeasureScale {
+    /// The scale ratio shown to the user (`/R`), e.g. `"1 in = 10 ft"`.
+    pub ratio: String,
+    /// The number of real-world units per point of line length.
+    pub units_per_point: f32,
+    /// The label of the real-world unit, e.g. `"ft"`.
+    pub unit: String,
+    /// The number of decimal places the length is displayed with.
+    pub precision: u32,
+}
+
+impl MeasureScale {
+    /// Format a length given in points in real-world units, e.g. `"12.50 ft"`.
+    pub fn format_length(&self, length: f32) -> String {
+        let value = length * self.units_per_point;
+        format!("{value:.*} {}", self.precision as usize, self.unit)
+            .trim_end()
+            .to_string()
+    }
+}
+
+/// The style of a line ending, as written to the `/LE` array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// No ending; the line stops at the endpoint.
+    #[default]
+    None,
+    /// A square centered on the endpoint.
+    Square,
+    /// A circle centered on the endpoint.
+    Circle,
+    /// A diamond centered on the endpoint.
+    Diamond,
+    /// Two short lines meeting at the endpoint, forming an open arrowhead.
+    OpenArrow,
+    /// A filled triangular arrowhead pointing at the endpoint.
+    ClosedArrow,
+    /// A short line perpendicular to the line at the endpoint.
+    Butt,
+    /// An open arrowhead pointing back along the line.
+    ROpenArrow,
+    /// A filled triangular arrowhead pointing back along the line.
+    RClosedArrow,
+    /// A short slanted line through the endpoint.
+    Slash,
+}
+
+impl LineEnding {
+    /// The PDF name of this line ending style.
+    pub fn pdf_name(self) -> &'static [u8] {
+        match self {
+            Self::None => b"None",
+            Self::Square => b"Square",
+            Self::Circle => b"Circle",
+            Self::Diamond => b"Diamond",
+            Self::OpenArrow => b"OpenArrow",
+            Self::ClosedArrow => b"ClosedArrow",
+            Self::Butt => b"Butt",
+            Self::ROpenArrow => b"ROpenArrow",
+            Self::RClosedArrow => b"RClosedArrow",
+            Self::Slash => b"Slash",
+        }
+    }
+}
+
+/// A polygon or polyline annotation.
+#[derive(Debug, Clone)]
+pub struct PolyAnnot {
+    /// Base annotation fields.
+    pub base: AnnotationBase,
+    /// The vertices `[x, y]` in PDF coordinates.
+    pub vertices: Vec<[f32; 2]>,
+    /// The stroke line width.
+    pub line_width: f32,
+    /// Optional interior (fill) color. Only used for polygons.
+    pub interior_color: Option<AnnotColor>,
 }
 
 /// A text (sticky note) annotation.
@@ -253,6 +782,15 @@ pub struct TextAnnot {
     pub open: bool,
     /// The icon name (e.g. `"Note"`, `"Comment"`, `"Key"`).
     pub icon: String,
+    /// Optional rich text (`/RC`), an XHTML fragment such as
+    /// `<p><b>Bold</b> comment</p>`.
+    ///
+    /// Fragments without a `<body>` root are wrapped in one on save. Plain
+    /// text is still written to `/Contents` for viewers that ignore `/RC`.
+    pub rich_text: Option<String>,
+    /// Optional default style string for the rich text (`/DS`), e.g.
+    /// `"font: Helvetica 12pt; color: #FF0000"`.
+    pub default_style: Option<String>,
 }
 
 /// A link annotation.
@@ -287,6 +825,26 @@ pub struct TextFieldAnnot {
     pub required: bool,
     /// Whether the field supports multiple lines.
     pub multiline: bool,
+    /// The horizontal alignment of the text (`/Q`).
+    pub alignment: TextAlign,
+    /// The content stream of the appearance shown while the mouse button is
+    /// pressed on the widget (`/AP /D`), if any.
+    pub down_appearance: Option<Vec<u8>>,
+    /// The content stream of the appearance shown while the pointer is over
+    /// the widget (`/AP /R`), if any.
+    pub rollover_appearance: Option<Vec<u8>>,
+}
+
+/// The horizontal alignment (quadding) of the text in a form field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextAlign {
+    /// Left-aligned text.
+    #[default]
+    Left,
+    /// Centered text.
+    Center,
+    /// Right-aligned text.
+    Right,
 }
 
 /// A signature form field (`/FT /Sig`) represented by a widget annotation.
@@ -300,4 +858,120 @@ pub struct SignatureFieldAnnot {
     pub tooltip: Option<String>,
     /// Whether the field is required.
     pub required: bool,
+    /// The content stream of the appearance shown while the mouse button is
+    /// pressed on the widget (`/AP /D`), if any.
+    pub down_appearance: Option<Vec<u8>>,
+    /// The content stream of the appearance shown while the pointer is over
+    /// the widget (`/AP /R`), if any.
+    pub rollover_appearance: Option<Vec<u8>>,
+}
+
+/// A checkbox form field (`/FT /Btn`) represented by a widget annotation.
+#[derive(Debug, Clone)]
+pub struct CheckBoxAnnot {
+    /// Base annotation fields.
+    pub base: AnnotationBase,
+    /// The field name (`/T`).
+    pub field_name: String,
+    /// The name of the "on" appearance state, which is also used as the
+    /// field value when the checkbox is checked (e.g. `"Yes"`).
+    pub export_value: String,
+    /// Whether the checkbox is checked.
+    pub checked: bool,
+    /// Whether the field is read-only.
+    pub read_only: bool,
+    /// Whether the field is required.
+    pub required: bool,
+    /// The content stream of the appearance shown while the mouse button is
+    /// pressed on the widget (`/AP /D`), if any. It is used for both states.
+    pub down_appearance: Option<Vec<u8>>,
+    /// The content stream of the appearance shown while the pointer is over
+    /// the widget (`/AP /R`), if any. It is used for both states.
+    pub rollover_appearance: Option<Vec<u8>>,
+}
+
+impl CheckBoxAnnot {
+    /// The name of the appearance state that is currently selected.
+    ///
+    /// This is used both for the widget's `/AS` entry and the field's `/V`
+    /// entry, so that the two always agree.
+    pub fn state_name(&self) -> &str {
+        if self.checked {
+            &self.export_value
+        } else {
+            "Off"
+        }
+    }
+}
+
+/// A choice form field (`/FT /Ch`) represented by a widget annotation, shown
+/// either as a dropdown or as a list box.
+#[derive(Debug, Clone)]
+pub struct ChoiceFieldAnnot {
+    /// Base annotation fields.
+    pub base: AnnotationBase,
+    /// The field name (`/T`).
+    pub field_name: String,
+    /// The options to choose from (`/Opt`), which must not be empty.
+    pub options: Vec<String>,
+    /// The selected option, which is written as both the value (`/V`) and the
+    /// default value (`/DV`) of the field.
+    pub selected: Option<String>,
+    /// Whether the field is a dropdown (combo box) instead of a list box.
+    pub combo: bool,
+    /// Whether the field is read-only.
+    pub read_only: bool,
+    /// Whether the field is required.
+    pub required: bool,
+    /// The content stream of the appearance shown while the mouse button is
+    /// pressed on the widget (`/AP /D`), if any.
+    pub down_appearance: Option<Vec<u8>>,
+    /// The content stream of the appearance shown while the pointer is over
+    /// the widget (`/AP /R`), if any.
+    pub rollover_appearance: Option<Vec<u8>>,
+}
+
+/// The encoding of the image of a [`StampAnnot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// A PNG image, which is decoded and re-encoded when embedding it.
+    Png,
+    /// A JPEG image, which is embedded as is.
+    Jpeg,
+}
+
+/// A rubber stamp annotation (`/Subtype /Stamp`) whose appearance is an image
+/// stretched to fill the annotation rect.
+#[derive(Clone)]
+pub struct StampAnnot {
+    /// Base annotation fields.
+    pub base: AnnotationBase,
+    /// The encoded image data.
+    pub image_data: Arc<dyn AsRef<[u8]>>,
+    /// The format of `image_data`.
+    pub format: ImageFormat,
+}
+
+impl Debug for StampAnnot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StampAnnot")
+            .field("base", &self.base)
+            .field("image_data", &"..")
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+/// A file attachment annotation (`/Subtype /FileAttachment`) that embeds a
+/// file in the document and shows it as an icon on the page.
+#[derive(Debug, Clone)]
+pub struct FileAttachmentAnnot {
+    /// Base annotation fields.
+    pub base: AnnotationBase,
+    /// The name of the attached file.
+    pub file_name: String,
+    /// The contents of the attached file.
+    pub data: Vec<u8>,
+    /// The icon name (`"PushPin"`, `"Paperclip"`, `"Graph"` or `"Tag"`).
+    pub icon: String,
 }
@@ -5,12 +5,26 @@
 //! preserved and new objects (annotation dictionaries, appearance streams) are
 //! appended at the end, along with a new cross-reference table and trailer.
 
-use crate::appearance::generate_appearance;
+use crate::appearance::{
+    CALLOUT_LINE_ENDING, GRAPHICS_STATE_NAME, LINE_SPACING, annotation_rect, appearance_rotation,
+    blend_mode, fill_opacity, generate_appearance, generate_appearance_states, line_ending_size,
+    measure_label, valid_dash_pattern,
+};
+use crate::coord::page_boxes;
+use crate::date::PdfDate;
+use crate::font::{EMBEDDED_FONT_NAME, EmbeddedFont, encode_win_ansi};
+use crate::reader::annotation_positions;
+use crate::stamp::{STAMP_IMAGE_NAME, StampImage};
 use crate::types::*;
 use flate2::Compression;
 use flate2::write::ZlibEncoder;
+use hayro_syntax::object::dict::keys::{
+    ACRO_FORM, ANNOTS, FIELDS, IRT, KIDS, PARENT, SUBTYPE, WIDGET,
+};
+use hayro_syntax::object::{Array, Dict, MaybeRef, ObjectIdentifier};
+use log::warn;
 use pdf_writer::{Chunk, Filter, Finish, Name, Rect, Ref};
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::io::Write;
 
 /// An error that occurred during annotation saving.
@@ -22,10 +36,16 @@ pub enum SaveError {
     InvalidPageIndex(usize),
     /// An invalid link destination page index was specified.
     InvalidDestinationPage(usize),
+    /// An invalid annotation index (page index, annotation index) was specified.
+    InvalidAnnotationIndex(usize, usize),
     /// A form field name was invalid.
     InvalidFieldName,
     /// A form field name appeared more than once.
     DuplicateFieldName(String),
+    /// The choice field with the given name has no options.
+    EmptyChoiceOptions(String),
+    /// The PDF is encrypted, which incremental saving doesn't support.
+    EncryptedPdf,
     /// An I/O error occurred.
     IoError(String),
 }
@@ -36,22 +56,112 @@ impl core::fmt::Display for SaveError {
             Self::InvalidPdf => write!(f, "invalid PDF"),
             Self::InvalidPageIndex(i) => write!(f, "invalid page index: {i}"),
             Self::InvalidDestinationPage(i) => write!(f, "invalid destination page index: {i}"),
+            Self::InvalidAnnotationIndex(page, i) => {
+                write!(f, "invalid annotation index {i} on page {page}")
+            }
             Self::InvalidFieldName => write!(f, "invalid form field name"),
             Self::DuplicateFieldName(name) => write!(f, "duplicate form field name: {name}"),
+            Self::EmptyChoiceOptions(name) => write!(f, "choice field without options: {name}"),
+            Self::EncryptedPdf => write!(f, "encrypted PDFs are not supported"),
             Self::IoError(s) => write!(f, "I/O error: {s}"),
         }
     }
 }
 
-/// Deflate-compress data.
-fn deflate_encode(data: &[u8]) -> Vec<u8> {
-    let mut e = ZlibEncoder::new(Vec::new(), Compression::new(6));
+/// A compact sRGB ICC profile, used for the optional output intent.
+const SRGB_ICC_PROFILE: &[u8] = include_bytes!("../assets/sRGB-v2.icc");
+
+/// Options that control how a document with annotations is written.
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// Whether to add an sRGB `/OutputIntents` entry to the catalog, so that
+    /// viewers render annotation colors consistently.
+    pub add_srgb_output_intent: bool,
+    /// How the `/Annots` array of each page is written.
+    pub annots_array_style: AnnotsArrayStyle,
+    /// Whether to copy the original page content streams byte-for-byte instead
+    /// of re-encoding them, for example to keep hashes of them stable.
+    pub preserve_content_streams: bool,
+    /// The zlib compression level (0-9) of annotation appearance streams.
+    pub compression_level: u32,
+    /// Whether to compress annotation appearance streams at all. Uncompressed
+    /// streams are written without a `/Filter`, which makes them easy to
+    /// inspect.
+    pub compress_streams: bool,
+    /// Whether to pack all objects other than streams into compressed object
+    /// streams and write a cross-reference stream instead of a classic `xref`
+    /// table. This requires PDF 1.5, but can make heavily annotated documents
+    /// considerably smaller.
+    pub use_object_streams: bool,
+    /// Whether to clamp annotation rects and quad points to the visible area
+    /// of their page. Annotations that lie entirely outside of it are skipped.
+    pub clamp_to_page: bool,
+    /// Rotations in degrees that replace the `/Rotate` entry of the page with
+    /// the given index, normalized to 0, 90, 180 or 270.
+    ///
+    /// Only [`save_annotations_with_options`] applies these.
+    pub page_rotations: HashMap<usize, i32>,
+    /// The indices of the original pages to write, in the order they appear
+    /// in the new document. Pages that aren't listed are dropped.
+    ///
+    /// When set, the page indices of annotations and `page_rotations` refer
+    /// to the pages of the new document. Only [`save_annotations_with_options`]
+    /// applies this.
+    pub page_order: Option<Vec<usize>>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            add_srgb_output_intent: false,
+            annots_array_style: AnnotsArrayStyle::default(),
+            preserve_content_streams: false,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            compress_streams: true,
+            use_object_streams: false,
+            clamp_to_page: false,
+            page_rotations: HashMap::new(),
+            page_order: None,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// The compression level of appearance streams, or `None` if they are
+    /// written uncompressed.
+    fn appearance_compression(&self) -> Option<u32> {
+        self.compress_streams
+            .then_some(self.compression_level.min(9))
+    }
+}
+
+/// How a page's `/Annots` array is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnnotsArrayStyle {
+    /// Write the array as a separate indirect object referenced from the page.
+    #[default]
+    Indirect,
+    /// Write the array directly into the page dictionary.
+    Inline,
+}
+
+/// The zlib compression level used unless configured otherwise.
+const DEFAULT_COMPRESSION_LEVEL: u32 = 6;
+
+/// Deflate-compress data with the default compression level.
+pub(crate) fn deflate_encode(data: &[u8]) -> Vec<u8> {
+    deflate_encode_with_level(data, DEFAULT_COMPRESSION_LEVEL)
+}
+
+/// Deflate-compress data with the given compression level.
+fn deflate_encode_with_level(data: &[u8], level: u32) -> Vec<u8> {
+    let mut e = ZlibEncoder::new(Vec::new(), Compression::new(level));
     e.write_all(data).unwrap();
     e.finish().unwrap()
 }
 
 /// A reference allocator that tracks the next available object number.
-struct RefAllocator {
+pub(crate) struct RefAllocator {
     next: i32,
 }
 
@@ -60,7 +170,7 @@ impl RefAllocator {
         Self { next: start }
     }
 
-    fn alloc(&mut self) -> Ref {
+    pub(crate) fn alloc(&mut self) -> Ref {
         let r = Ref::new(self.next);
         self.next += 1;
         r
@@ -74,6 +184,12 @@ impl RefAllocator {
 /// annotations on top. For simplicity and correctness, rather than implementing
 /// a full incremental save (which requires rewriting xref tables), we create a
 /// new standalone PDF that includes all original pages and the new annotations.
+/// Use [`save_annotations_incremental`] to keep the original bytes instead.
+///
+/// The annotations of each page are appended to its `/Annots` array in the
+/// given order, after any annotations the page already has. Entries for the
+/// same page are merged in order. Since viewers draw annotations in `/Annots`
+/// order, later annotations are drawn on top of earlier ones.
 ///
 /// # Arguments
 /// * `original_data` — the original PDF file bytes
@@ -84,46 +200,823 @@ impl RefAllocator {
 pub fn save_annotations(
     original_data: &[u8],
     page_annotations: &[(usize, Vec<Annotation>)],
+) -> Result<Vec<u8>, SaveError> {
+    save_annotations_with_options(original_data, page_annotations, &WriteOptions::default())
+}
+
+/// Save annotations to a PDF like [`save_annotations`], using custom write options.
+pub fn save_annotations_with_options(
+    original_data: &[u8],
+    page_annotations: &[(usize, Vec<Annotation>)],
+    options: &WriteOptions,
 ) -> Result<Vec<u8>, SaveError> {
     use hayro_syntax::Pdf;
 
     let pdf = Pdf::new(original_data.to_vec()).map_err(|_| SaveError::InvalidPdf)?;
+    let page_indices = options
+        .page_order
+        .clone()
+        .unwrap_or_else(|| (0..pdf.pages().len()).collect());
+
+    let mut rotation_overrides = HashMap::new();
+    for (&page_idx, &degrees) in &options.page_rotations {
+        let source_idx = page_indices
+            .get(page_idx)
+            .ok_or(SaveError::InvalidPageIndex(page_idx))?;
+        rotation_overrides.insert(*source_idx, degrees);
+    }
+
+    write_pages(
+        &pdf,
+        &page_indices,
+        page_annotations,
+        &hayro_write::ExtractionOptions {
+            preserve_content_streams: options.preserve_content_streams,
+            include_annotations: true,
+            rotation_overrides,
+            ..Default::default()
+        },
+        options,
+    )
+}
+
+/// Save annotations to a PDF using a true incremental update.
+///
+/// Unlike [`save_annotations`], the original file is kept byte-for-byte. Only
+/// the new annotation objects, the patched page (and, for form fields, catalog)
+/// dictionaries and a new cross-reference section whose `/Prev` points at the
+/// previous one are appended. Anything in the original document, including
+/// objects this crate doesn't understand, is therefore preserved.
+///
+/// Encrypted documents are not supported, since the appended objects would
+/// need to be encrypted as well.
+pub fn save_annotations_incremental(
+    original_data: &[u8],
+    page_annotations: &[(usize, Vec<Annotation>)],
+) -> Result<Vec<u8>, SaveError> {
+    let mut pdf_bytes = Vec::new();
+    save_annotations_to(&mut pdf_bytes, original_data, page_annotations)?;
+
+    Ok(pdf_bytes)
+}
+
+/// Save annotations to a PDF like [`save_annotations_incremental`], writing
+/// the output to `writer` instead of returning it.
+///
+/// Since an incremental update keeps the original bytes, the original document
+/// is copied to `writer` as is, followed by the update. The output is never
+/// assembled into one buffer, but `original_data` is still copied once while
+/// it is parsed.
+///
+/// Only incremental updates are streamed. The full rewrite done by
+/// [`save_annotations`] patches the finished document, so it is always built
+/// in memory.
+///
+/// Nothing is written if the annotations can't be saved. On an I/O error, the
+/// output is incomplete.
+pub fn save_annotations_to<W: Write>(
+    mut writer: W,
+    original_data: &[u8],
+    page_annotations: &[(usize, Vec<Annotation>)],
+) -> Result<(), SaveError> {
+    let update = incremental_update(original_data, page_annotations)?;
+
+    writer
+        .write_all(original_data)
+        .and_then(|_| writer.write_all(&update))
+        .and_then(|_| writer.flush())
+        .map_err(|e| SaveError::IoError(e.to_string()))
+}
+
+/// Build the bytes that [`save_annotations_incremental`] appends to
+/// `original_data`.
+fn incremental_update(
+    original_data: &[u8],
+    page_annotations: &[(usize, Vec<Annotation>)],
+) -> Result<Vec<u8>, SaveError> {
+    use hayro_syntax::Pdf;
+
+    let pdf = Pdf::new(original_data.to_vec()).map_err(|_| SaveError::InvalidPdf)?;
+    let trailer = last_trailer_dict(original_data).ok_or(SaveError::InvalidPdf)?;
+    if dict_entry(trailer, b"Encrypt").is_some() {
+        return Err(SaveError::EncryptedPdf);
+    }
+
     let pages = pdf.pages();
-    let num_pages = pages.len();
     let merged_page_annotations = merge_page_annotations(page_annotations);
+    validate_page_annotations(&merged_page_annotations, pages.len())?;
 
-    // Validate all page indices
-    let mut form_field_names = HashSet::new();
-    for (page_idx, annots) in &merged_page_annotations {
-        if *page_idx >= num_pages {
-            return Err(SaveError::InvalidPageIndex(*page_idx));
+    if merged_page_annotations.iter().all(|(_, a)| a.is_empty()) {
+        return Ok(Vec::new());
+    }
+
+    let page_refs = pages
+        .iter()
+        .map(|page| {
+            let id = page.raw().obj_id().ok_or(SaveError::InvalidPdf)?;
+            Ok(Ref::new(id.obj_number))
+        })
+        .collect::<Result<Vec<_>, SaveError>>()?;
+    let pages_with_annots = pages_with_annots(&pdf);
+
+    // New objects are numbered after the highest existing object number.
+    let size = first_free_object_number(original_data, trailer);
+    let mut allocator = RefAllocator::new(size);
+
+    let root_id = pdf.xref().root_id();
+    let catalog = pdf
+        .xref()
+        .get::<Dict<'_>>(root_id)
+        .ok_or(SaveError::InvalidPdf)?;
+    let acro_form = catalog.get::<Dict<'_>>(ACRO_FORM);
+
+    let mut chunk = Chunk::new();
+    let AnnotationObjects {
+        page_annots,
+        acro_form: acro_form_update,
+    } = write_annotation_objects(
+        &mut chunk,
+        &mut allocator,
+        &merged_page_annotations,
+        &page_refs,
+        &pages_with_annots,
+        AnnotsArrayStyle::Indirect,
+        WriteOptions::default().appearance_compression(),
+        acro_form.is_some(),
+    );
+
+    // Offsets in the update are relative to the start of the file.
+    let base_offset = original_data.len();
+    let mut update = Vec::new();
+    if !original_data.ends_with(b"\n") {
+        update.push(b'\n');
+    }
+
+    let chunk_start = base_offset + update.len();
+    update.extend_from_slice(chunk.as_bytes());
+    let mut new_objects: BTreeMap<i32, (i32, usize)> = collect_object_offsets(chunk.as_bytes())
+        .into_iter()
+        .map(|(id, offset)| (id, (0, chunk_start + offset)))
+        .collect();
+
+    for (page_idx, annots_update) in &page_annots {
+        let page = pages[*page_idx].raw();
+        let (id, object) = match annots_update {
+            AnnotsUpdate::Insert(annots_value) => patch_dict_object(page, |bytes, page_ref| {
+                inject_annots_into_page(bytes, page_ref, annots_value)
+            }),
+            // An indirect `/Annots` array gets a new revision, the page itself is unchanged.
+            AnnotsUpdate::Append(refs) => match page.get_ref(ANNOTS) {
+                Some(array_ref) => patch_array_object(&pdf, array_ref.into(), refs),
+                None => patch_dict_object(page, |bytes, page_ref| {
+                    append_to_page_annots(bytes, page_ref, refs)
+                }),
+            },
         }
+        .ok_or(SaveError::InvalidPdf)?;
+        new_objects.insert(id.obj_number, (id.gen_number, base_offset + update.len()));
+        update.extend_from_slice(&object);
+    }
 
-        for annot in annots {
-            if let Annotation::Link(link) = annot
-                && let Some(dest_page) = link.dest_page
-                && dest_page >= num_pages
-            {
-                return Err(SaveError::InvalidDestinationPage(dest_page));
+    let acro_form_object = match (acro_form_update, acro_form) {
+        (Some(AcroFormUpdate::Insert(acro_form_ref)), _) => {
+            Some(patch_dict_object(&catalog, |bytes, catalog_ref| {
+                inject_acro_form_into_catalog(bytes, catalog_ref, acro_form_ref)
+            }))
+        }
+        (Some(AcroFormUpdate::Append(refs)), Some(acro_form)) => {
+            Some(append_acro_form_fields(&catalog, &acro_form, &refs))
+        }
+        _ => None,
+    };
+    if let Some(object) = acro_form_object {
+        let (id, object) = object.ok_or(SaveError::InvalidPdf)?;
+        new_objects.insert(id.obj_number, (id.gen_number, base_offset + update.len()));
+        update.extend_from_slice(&object);
+    }
+
+    let trailer = incremental_trailer(original_data, allocator.next.max(size), root_id)?;
+    append_incremental_xref(
+        &mut update,
+        base_offset,
+        &new_objects,
+        &BTreeMap::new(),
+        &trailer,
+    );
+
+    Ok(update)
+}
+
+/// Remove existing annotations from a PDF using an incremental update.
+///
+/// Each removal is a `(page_index, annotation_index)` pair, where the
+/// annotation index refers to the list returned by [`read_annotations`] for
+/// that page. The affected `/Annots` arrays are rewritten without the removed
+/// annotations (and their popups), and the removed annotation objects are
+/// marked as free in the appended cross-reference section.
+///
+/// Removed widget annotations are also dropped from the `/Fields` of the
+/// document's form and from the `/Kids` of their parent field, and replies
+/// (`/IRT`) to removed annotations are turned into standalone annotations, so
+/// that nothing refers to a freed object.
+///
+/// Like [`save_annotations_incremental`], this keeps the original bytes and
+/// doesn't support encrypted documents.
+pub fn remove_annotations(
+    original_data: &[u8],
+    removals: &[(usize, usize)],
+) -> Result<Vec<u8>, SaveError> {
+    use hayro_syntax::Pdf;
+
+    let pdf = Pdf::new(original_data.to_vec()).map_err(|_| SaveError::InvalidPdf)?;
+    let trailer = last_trailer_dict(original_data).ok_or(SaveError::InvalidPdf)?;
+    if dict_entry(trailer, b"Encrypt").is_some() {
+        return Err(SaveError::EncryptedPdf);
+    }
+
+    let pages = pdf.pages();
+    let mut removals_by_page: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+    for &(page_idx, annot_idx) in removals {
+        if page_idx >= pages.len() {
+            return Err(SaveError::InvalidPageIndex(page_idx));
+        }
+        removals_by_page
+            .entry(page_idx)
+            .or_default()
+            .insert(annot_idx);
+    }
+
+    if removals_by_page.is_empty() {
+        return Ok(original_data.to_vec());
+    }
+
+    let mut pdf_bytes = original_data.to_vec();
+    if !pdf_bytes.ends_with(b"\n") {
+        pdf_bytes.push(b'\n');
+    }
+
+    let mut new_objects = BTreeMap::new();
+    let mut free_objects = BTreeMap::new();
+    let mut removed_ids = HashSet::new();
+
+    for (page_idx, annot_indices) in removals_by_page {
+        let positions = annotation_positions(&pdf, page_idx);
+        let removed_positions = annot_indices
+            .iter()
+            .map(|idx| {
+                positions
+                    .get(*idx)
+                    .copied()
+                    .ok_or(SaveError::InvalidAnnotationIndex(page_idx, *idx))
+            })
+            .collect::<Result<HashSet<_>, _>>()?;
+
+        let page = pages[page_idx].raw();
+        let annots = page.get::<Array<'_>>(ANNOTS).ok_or(SaveError::InvalidPdf)?;
+        let entries = annots.raw_iter().collect::<Vec<_>>();
+
+        // Popups belong to their parent annotation and are removed along with it.
+        let mut page_removed_ids = HashSet::new();
+        for position in &removed_positions {
+            if let MaybeRef::Ref(r) = &entries[*position] {
+                let id = ObjectIdentifier::from(*r);
+                page_removed_ids.insert(id);
+                if let Some(popup) = pdf
+                    .xref()
+                    .get::<Dict<'_>>(id)
+                    .and_then(|dict| dict.get_ref(b"Popup".as_ref()))
+                {
+                    page_removed_ids.insert(popup.into());
+                }
             }
+        }
 
-            let field_name = match annot {
-                Annotation::TextField(field) => Some(field.field_name.trim()),
-                Annotation::SignatureField(field) => Some(field.field_name.trim()),
-                _ => None,
+        let kept = array_without(&annots, &page_removed_ids, &removed_positions);
+        let (id, object) = patch_dict_array(page, ANNOTS, &kept).ok_or(SaveError::InvalidPdf)?;
+        new_objects.insert(id.obj_number, (id.gen_number, pdf_bytes.len()));
+        pdf_bytes.extend_from_slice(&object);
+
+        for id in &page_removed_ids {
+            // The next use of a freed object number gets an incremented generation.
+            free_objects.insert(id.obj_number, (id.gen_number + 1).min(65535));
+        }
+        removed_ids.extend(page_removed_ids);
+    }
+
+    for (id, object) in unlink_removed_annotations(&pdf, &removed_ids)? {
+        new_objects.insert(id.obj_number, (id.gen_number, pdf_bytes.len()));
+        pdf_bytes.extend_from_slice(&object);
+    }
+
+    let trailer = incremental_trailer(
+        original_data,
+        first_free_object_number(original_data, trailer),
+        pdf.xref().root_id(),
+    )?;
+    append_incremental_xref(&mut pdf_bytes, 0, &new_objects, &free_objects, &trailer);
+
+    Ok(pdf_bytes)
+}
+
+/// Update the objects other than `/Annots` arrays that refer to removed
+/// annotations, returning their new revisions.
+///
+/// Removed widgets are dropped from the `/Fields` of the form and from the
+/// `/Kids` of their parent field, and the `/IRT` of replies to removed
+/// annotations is cleared.
+fn unlink_removed_annotations(
+    pdf: &hayro_syntax::Pdf,
+    removed_ids: &HashSet<ObjectIdentifier>,
+) -> Result<Vec<(ObjectIdentifier, Vec<u8>)>, SaveError> {
+    let xref = pdf.xref();
+    let no_positions = HashSet::new();
+    let contains_removed = |array: &Array<'_>| {
+        array.raw_iter().any(|entry| {
+            entry
+                .as_obj_ref()
+                .is_some_and(|r| removed_ids.contains(&ObjectIdentifier::from(r)))
+        })
+    };
+    let mut objects = Vec::new();
+
+    // Widgets are either fields themselves, or kids of a parent field.
+    let mut parents = removed_ids
+        .iter()
+        .filter_map(|id| xref.get::<Dict<'_>>(*id))
+        .filter(|widget| {
+            widget.get::<hayro_syntax::object::Name>(SUBTYPE).as_deref() == Some(WIDGET)
+        })
+        .filter_map(|widget| widget.get_ref(PARENT))
+        .map(ObjectIdentifier::from)
+        .collect::<Vec<_>>();
+    parents.sort_by_key(|id| (id.obj_number, id.gen_number));
+    parents.dedup();
+
+    for parent_id in parents {
+        let Some(parent) = xref.get::<Dict<'_>>(parent_id) else {
+            continue;
+        };
+        if let Some(kids) = parent.get::<Array<'_>>(KIDS)
+            && contains_removed(&kids)
+        {
+            let kept = array_without(&kids, removed_ids, &no_positions);
+            objects.push(patch_dict_array(&parent, KIDS, &kept).ok_or(SaveError::InvalidPdf)?);
+        }
+    }
+
+    let catalog = xref
+        .get::<Dict<'_>>(xref.root_id())
+        .ok_or(SaveError::InvalidPdf)?;
+    if let Some(acro_form) = catalog.get::<Dict<'_>>(ACRO_FORM)
+        && let Some(fields) = acro_form.get::<Array<'_>>(FIELDS)
+        && contains_removed(&fields)
+    {
+        let kept = array_without(&fields, removed_ids, &no_positions);
+        // An inline form dictionary is updated as part of the catalog.
+        let object = patch_dict_array(&acro_form, FIELDS, &kept)
+            .or_else(|| {
+                patch_dict_object(&catalog, |bytes, catalog_ref| {
+                    replace_dict_array(bytes, catalog_ref, FIELDS, &kept)
+                })
+            })
+            .ok_or(SaveError::InvalidPdf)?;
+        objects.push(object);
+    }
+
+    for page in pdf.pages().iter() {
+        let Some(annots) = page.raw().get::<Array<'_>>(ANNOTS) else {
+            continue;
+        };
+
+        for entry in annots.raw_iter() {
+            let MaybeRef::Ref(r) = entry else {
+                continue;
             };
+            let id = ObjectIdentifier::from(r);
+            if removed_ids.contains(&id) {
+                continue;
+            }
 
-            if let Some(field_name) = field_name {
-                if field_name.is_empty() {
-                    return Err(SaveError::InvalidFieldName);
-                }
+            if let Some(reply) = xref.get::<Dict<'_>>(id)
+                && reply
+                    .get_ref(IRT)
+                    .is_some_and(|irt| removed_ids.contains(&ObjectIdentifier::from(irt)))
+            {
+                let object = patch_dict_object(&reply, |bytes, reply_ref| {
+                    replace_dict_ref(bytes, reply_ref, IRT, b"null")
+                })
+                .ok_or(SaveError::InvalidPdf)?;
+                objects.push(object);
+            }
+        }
+    }
 
-                if !form_field_names.insert(field_name.to_string()) {
-                    return Err(SaveError::DuplicateFieldName(field_name.to_string()));
+    Ok(objects)
+}
+
+/// The first object number that is free in all revisions of `original_data`,
+/// given its last trailer dictionary.
+fn first_free_object_number(original_data: &[u8], trailer: &[u8]) -> i32 {
+    let size = dict_entry(trailer, b"Size")
+        .and_then(|v| std::str::from_utf8(v).ok()?.parse::<i32>().ok())
+        .unwrap_or(0);
+    let max_existing = collect_object_offsets(original_data)
+        .keys()
+        .next_back()
+        .copied()
+        .unwrap_or(0);
+
+    size.max(max_existing + 1)
+}
+
+/// The trailer of an incremental update.
+struct IncrementalTrailer {
+    /// The trailer entries other than `/Size`.
+    entries: Vec<String>,
+    /// The number of objects in the document, not counting a cross-reference
+    /// stream written for the update.
+    size: i32,
+    /// Whether the update ends with a cross-reference stream instead of a
+    /// classic `xref` table and `trailer`.
+    xref_stream: bool,
+}
+
+/// Keys of the previous trailer that aren't copied into the trailer of an
+/// incremental update, either because they are replaced or because they
+/// only describe the previous cross-reference stream.
+const REPLACED_TRAILER_KEYS: &[&[u8]] = &[
+    b"Size",
+    b"Root",
+    b"Prev",
+    b"XRefStm",
+    b"Type",
+    b"W",
+    b"Index",
+    b"Length",
+    b"Filter",
+    b"DecodeParms",
+    b"F",
+    b"FFilter",
+    b"FDecodeParms",
+    b"DL",
+];
+
+/// Build the trailer of an incremental update of `original_data`.
+///
+/// All entries of the previous trailer, like `/Info`, `/ID` and `/Encrypt`, are
+/// carried over. If the previous section is a cross-reference stream, the
+/// update ends with one as well.
+fn incremental_trailer(
+    original_data: &[u8],
+    size: i32,
+    root_id: ObjectIdentifier,
+) -> Result<IncrementalTrailer, SaveError> {
+    let trailer = last_trailer_dict(original_data).ok_or(SaveError::InvalidPdf)?;
+    let prev = find_last_startxref(original_data).ok_or(SaveError::InvalidPdf)?;
+    let xref_stream = !original_data
+        .get(prev..)
+        .is_some_and(|section| section.trim_ascii_start().starts_with(b"xref"));
+
+    let mut entries = vec![format!(
+        "/Root {} {} R",
+        root_id.obj_number, root_id.gen_number
+    )];
+    for (key, value) in dict_entries(trailer).ok_or(SaveError::InvalidPdf)? {
+        if !REPLACED_TRAILER_KEYS.contains(&key) {
+            entries.push(format!(
+                "/{} {}",
+                String::from_utf8_lossy(key),
+                String::from_utf8_lossy(value)
+            ));
+        }
+    }
+    entries.push(format!("/Prev {prev}"));
+
+    Ok(IncrementalTrailer {
+        entries,
+        size,
+        xref_stream,
+    })
+}
+
+/// Serialize an updated copy of an existing dictionary object, keeping its
+/// object number and applying `patch` to it.
+fn patch_dict_object(
+    dict: &Dict<'_>,
+    patch: impl FnOnce(&mut Vec<u8>, Ref) -> bool,
+) -> Option<(ObjectIdentifier, Vec<u8>)> {
+    let id = dict.obj_id()?;
+
+    // The injection helpers look for an object header with generation zero.
+    let header = format!("{} 0 obj\n", id.obj_number);
+    let mut bytes = header.clone().into_bytes();
+    bytes.extend_from_slice(dict.data());
+    bytes.extend_from_slice(b"\nendobj\n");
+    if !patch(&mut bytes, Ref::new(id.obj_number)) {
+        return None;
+    }
+
+    let mut object = format!("{} {} obj\n", id.obj_number, id.gen_number).into_bytes();
+    object.extend_from_slice(&bytes[header.len()..]);
+    Some((id, object))
+}
+
+/// Serialize an updated copy of an existing array object with `refs` appended.
+fn patch_array_object(
+    pdf: &hayro_syntax::Pdf,
+    id: ObjectIdentifier,
+    refs: &[Ref],
+) -> Option<(ObjectIdentifier, Vec<u8>)> {
+    let array = pdf.xref().get::<Array<'_>>(id)?;
+
+    let mut object = format!("{} {} obj\n", id.obj_number, id.gen_number).into_bytes();
+    let array_start = object.len();
+    object.extend_from_slice(array.data());
+    object.extend_from_slice(b"\nendobj\n");
+
+    append_refs_to_array(&mut object, array_start, refs).then_some((id, object))
+}
+
+/// Serialize an updated copy of the object that holds the array under `key` of
+/// `dict`, replacing the array with `array`.
+///
+/// If the entry references an array object, that object is replaced.
+/// Otherwise, `dict` itself is updated, which requires it to be an indirect
+/// object.
+fn patch_dict_array(
+    dict: &Dict<'_>,
+    key: &[u8],
+    array: &[u8],
+) -> Option<(ObjectIdentifier, Vec<u8>)> {
+    match dict.get_ref(key) {
+        Some(array_ref) => {
+            let id = ObjectIdentifier::from(array_ref);
+            let mut object = format!("{} {} obj\n", id.obj_number, id.gen_number).into_bytes();
+            object.extend_from_slice(array);
+            object.extend_from_slice(b"\nendobj\n");
+            Some((id, object))
+        }
+        None => patch_dict_object(dict, |bytes, obj_ref| {
+            replace_dict_array(bytes, obj_ref, key, array)
+        }),
+    }
+}
+
+/// Serialize the entries of `array`, leaving out the entries at
+/// `removed_positions` and all references to `removed_ids`.
+///
+/// Direct entries are only kept if they are dictionaries.
+fn array_without(
+    array: &Array<'_>,
+    removed_ids: &HashSet<ObjectIdentifier>,
+    removed_positions: &HashSet<usize>,
+) -> Vec<u8> {
+    let mut kept = b"[".to_vec();
+    for (position, entry) in array.raw_iter().enumerate() {
+        match entry {
+            MaybeRef::Ref(r) => {
+                let id = ObjectIdentifier::from(r);
+                if !removed_ids.contains(&id) {
+                    kept.extend_from_slice(
+                        format!(" {} {} R", id.obj_number, id.gen_number).as_bytes(),
+                    );
+                }
+            }
+            MaybeRef::NotRef(obj) => {
+                if !removed_positions.contains(&position)
+                    && let Some(dict) = obj.into_dict()
+                {
+                    kept.push(b' ');
+                    kept.extend_from_slice(dict.data());
                 }
             }
         }
     }
+    kept.extend_from_slice(b" ]");
+
+    kept
+}
+
+/// Serialize an updated copy of the object that holds the `/Fields` of the
+/// existing form dictionary `acro_form`, with `refs` appended.
+///
+/// An inline form dictionary is updated as part of `catalog`.
+fn append_acro_form_fields(
+    catalog: &Dict<'_>,
+    acro_form: &Dict<'_>,
+    refs: &[Ref],
+) -> Option<(ObjectIdentifier, Vec<u8>)> {
+    let Some(fields) = acro_form.get::<Array<'_>>(FIELDS) else {
+        // `/Fields` is required, but add it to a form dictionary without one.
+        let fields = refs
+            .iter()
+            .map(|r| format!(" {} 0 R", r.get()))
+            .collect::<String>();
+        let entry = format!("/Fields [{fields} ]");
+        return patch_dict_object(acro_form, |bytes, acro_form_ref| {
+            inject_entry_into_dict(bytes, acro_form_ref, None, &entry)
+        })
+        .or_else(|| {
+            patch_dict_object(catalog, |bytes, catalog_ref| {
+                inject_entry_into_dict(bytes, catalog_ref, Some(ACRO_FORM), &entry)
+            })
+        });
+    };
+
+    let mut array = fields.data().to_vec();
+    if !append_refs_to_array(&mut array, 0, refs) {
+        return None;
+    }
+    patch_dict_array(acro_form, FIELDS, &array).or_else(|| {
+        patch_dict_object(catalog, |bytes, catalog_ref| {
+            replace_dict_array(bytes, catalog_ref, FIELDS, &array)
+        })
+    })
+}
+
+/// Return the indices of all pages that already have an `/Annots` entry.
+fn pages_with_annots(pdf: &hayro_syntax::Pdf) -> HashSet<usize> {
+    pdf.pages()
+        .iter()
+        .enumerate()
+        .filter(|(_, page)| page.raw().contains_key(ANNOTS))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// Remove the encryption of a PDF, given its user or owner password.
+///
+/// The pages are written into a new, unencrypted document like with
+/// [`save_annotations`], so that the result can be edited without the
+/// password. Documents that aren't encrypted are returned unchanged.
+pub fn decrypt_pdf(original_data: &[u8], password: &str) -> Result<Vec<u8>, SaveError> {
+    use hayro_syntax::{LoadPdfError, Pdf};
+
+    let pdf = Pdf::new_with_password(original_data.to_vec(), password).map_err(|e| match e {
+        LoadPdfError::Decryption(_) => SaveError::EncryptedPdf,
+        LoadPdfError::Invalid => SaveError::InvalidPdf,
+    })?;
+
+    let trailer = last_trailer_dict(original_data).ok_or(SaveError::InvalidPdf)?;
+    if dict_entry(trailer, b"Encrypt").is_none() {
+        return Ok(original_data.to_vec());
+    }
+
+    write_document(
+        &pdf,
+        &[],
+        &hayro_write::ExtractionOptions {
+            include_annotations: true,
+            ..Default::default()
+        },
+        &WriteOptions::default(),
+    )
+}
+
+/// Extract the pages with the given indices into a new PDF, in the given order.
+///
+/// The pages keep their media box, crop box and rotation, as well as their
+/// existing annotations.
+pub fn extract_pages(original_data: &[u8], page_indices: &[usize]) -> Result<Vec<u8>, SaveError> {
+    use hayro_syntax::Pdf;
+
+    let pdf = Pdf::new(original_data.to_vec()).map_err(|_| SaveError::InvalidPdf)?;
+
+    write_pages(
+        &pdf,
+        page_indices,
+        &[],
+        &hayro_write::ExtractionOptions {
+            include_annotations: true,
+            ..Default::default()
+        },
+        &WriteOptions::default(),
+    )
+}
+
+/// Concatenate the pages of several PDFs into a new document, in the order
+/// the documents are given.
+///
+/// The pages of each document are extracted with their existing annotations
+/// and referenced from an intermediate page tree node, so the new document has
+/// a single page tree whose `/Count` is the total number of pages.
+///
+/// Only the pages are kept: the new document has a fresh catalog, so the form
+/// fields (`/AcroForm`), outlines and incremental history of the documents are
+/// dropped.
+pub fn concat_pdfs(documents: &[&[u8]]) -> Result<Vec<u8>, SaveError> {
+    use hayro_syntax::Pdf;
+
+    let mut allocator = RefAllocator::new(1);
+    let catalog_ref = allocator.alloc();
+    let page_tree_ref = allocator.alloc();
+
+    let mut out_pdf = pdf_writer::Pdf::new();
+    let mut kids = Vec::with_capacity(documents.len());
+    let mut count = 0;
+
+    for data in documents {
+        let pdf = Pdf::new(data.to_vec()).map_err(|_| SaveError::InvalidPdf)?;
+        let queries: Vec<hayro_write::ExtractionQuery> = (0..pdf.pages().len())
+            .map(hayro_write::ExtractionQuery::new_page)
+            .collect();
+
+        let extracted = hayro_write::extract_with_options(
+            &pdf,
+            Box::new(|| allocator.alloc()),
+            &queries,
+            &hayro_write::ExtractionOptions {
+                include_annotations: true,
+                ..Default::default()
+            },
+        )
+        .map_err(|_| SaveError::InvalidPdf)?;
+
+        let page_refs: Vec<Ref> = extracted
+            .root_refs
+            .iter()
+            .map(|r| r.as_ref().map_err(|_| SaveError::InvalidPdf).copied())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The extracted pages point to their own parent node, which is
+        // attached to the shared page tree.
+        out_pdf
+            .pages(extracted.page_tree_parent_ref)
+            .parent(page_tree_ref)
+            .kids(page_refs.iter().copied())
+            .count(page_refs.len() as i32);
+        out_pdf.extend(&extracted.chunk);
+
+        kids.push(extracted.page_tree_parent_ref);
+        count += page_refs.len() as i32;
+    }
+
+    out_pdf.catalog(catalog_ref).pages(page_tree_ref);
+    out_pdf.pages(page_tree_ref).kids(kids).count(count);
+
+    Ok(out_pdf.finish())
+}
+
+/// Write all pages of `pdf` into a new document, adding the given annotations
+/// and applying the extraction and write options.
+pub(crate) fn write_document(
+    pdf: &hayro_syntax::Pdf,
+    page_annotations: &[(usize, Vec<Annotation>)],
+    extraction_options: &hayro_write::ExtractionOptions,
+    write_options: &WriteOptions,
+) -> Result<Vec<u8>, SaveError> {
+    let page_indices = (0..pdf.pages().len()).collect::<Vec<_>>();
+
+    write_pages(
+        pdf,
+        &page_indices,
+        page_annotations,
+        extraction_options,
+        write_options,
+    )
+}
+
+/// Write the pages of `pdf` with the given indices into a new document, like
+/// [`write_document`].
+///
+/// The page indices of `page_annotations` refer to the position of a page in
+/// `page_indices`, i.e. to the pages of the new document.
+fn write_pages(
+    pdf: &hayro_syntax::Pdf,
+    page_indices: &[usize],
+    page_annotations: &[(usize, Vec<Annotation>)],
+    extraction_options: &hayro_write::ExtractionOptions,
+    write_options: &WriteOptions,
+) -> Result<Vec<u8>, SaveError> {
+    let pages = pdf.pages();
+    if let Some(&invalid) = page_indices.iter().find(|&&idx| idx >= pages.len()) {
+        return Err(SaveError::InvalidPageIndex(invalid));
+    }
+
+    let num_pages = page_indices.len();
+    let mut merged_page_annotations = merge_page_annotations(page_annotations);
+
+    validate_page_annotations(&merged_page_annotations, num_pages)?;
+
+    if write_options.clamp_to_page {
+        for (page_idx, annots) in &mut merged_page_annotations {
+            let boxes = page_boxes(&pages[page_indices[*page_idx]]);
+            let bounds = intersect_rects(boxes.media_box, boxes.crop_box);
+            *annots = annots
+                .iter()
+                .filter_map(|annot| {
+                    let clamped = clamp_annotation(annot, bounds);
+                    if clamped.is_none() {
+                        warn!(
+                            "skipping {} annotation outside of page {page_idx}",
+                            annot.type_name()
+                        );
+                    }
+                    clamped
+                })
+                .collect();
+        }
+    }
 
     // Use hayro-write to extract all pages, then add annotations on top
     let mut next_ref = Ref::new(1);
@@ -135,20 +1028,28 @@ pub fn save_annotations(
 
     let catalog_ref = alloc();
     let page_tree_ref = alloc();
+    let output_intent_refs = if write_options.add_srgb_output_intent {
+        Some((alloc(), alloc()))
+    } else {
+        None
+    };
 
-    // Extract all pages using hayro-write
-    let queries: Vec<hayro_write::ExtractionQuery> = (0..num_pages)
+    // Extract the pages using hayro-write
+    let queries: Vec<hayro_write::ExtractionQuery> = page_indices
+        .iter()
+        .copied()
         .map(hayro_write::ExtractionQuery::new_page)
         .collect();
 
-    let extracted = hayro_write::extract(
-        &pdf,
+    let extracted = hayro_write::extract_with_options(
+        pdf,
         Box::new(|| {
             let r = next_ref;
             next_ref = Ref::new(next_ref.get() + 1);
             r
         }),
         &queries,
+        extraction_options,
     )
     .map_err(|_| SaveError::InvalidPdf)?;
 
@@ -156,7 +1057,19 @@ pub fn save_annotations(
     let mut out_pdf = pdf_writer::Pdf::new();
 
     // Write catalog
-    out_pdf.catalog(catalog_ref).pages(page_tree_ref);
+    let mut catalog = out_pdf.catalog(catalog_ref);
+    catalog.pages(page_tree_ref);
+    if let Some((output_intent_ref, _)) = output_intent_refs {
+        catalog
+            .insert(Name(b"OutputIntents"))
+            .array()
+            .item(output_intent_ref);
+    }
+    catalog.finish();
+
+    if let Some((output_intent_ref, profile_ref)) = output_intent_refs {
+        write_srgb_output_intent(&mut out_pdf, output_intent_ref, profile_ref);
+    }
 
     // Collect page refs
     let page_refs: Vec<Ref> = extracted
@@ -165,84 +1078,294 @@ pub fn save_annotations(
         .map(|r| r.as_ref().map_err(|_| SaveError::InvalidPdf).copied())
         .collect::<Result<Vec<_>, _>>()?;
 
-    // For each page that has annotations, write the annotation objects
-    // and create /Annots arrays
-    let mut page_annot_arrays: HashMap<usize, Ref> = HashMap::new();
-    let mut acro_field_refs: Vec<Ref> = Vec::new();
-    let mut has_signature_fields = false;
-
     // Use a chunk for annotation objects since we need fresh refs
     let mut annot_chunk = Chunk::new();
     let mut annot_refs_allocator = RefAllocator::new(next_ref.get());
+    // Existing annotations are only carried over if the extraction includes them.
+    let pages_with_annots = if extraction_options.include_annotations {
+        let source_pages = pages_with_annots(pdf);
+        (0..num_pages)
+            .filter(|&idx| source_pages.contains(&page_indices[idx]))
+            .collect()
+    } else {
+        HashSet::new()
+    };
+    // The new catalog never has a form, so one is written if needed.
+    let AnnotationObjects {
+        page_annots,
+        acro_form,
+    } = write_annotation_objects(
+        &mut annot_chunk,
+        &mut annot_refs_allocator,
+        &merged_page_annotations,
+        &page_refs,
+        &pages_with_annots,
+        write_options.annots_array_style,
+        write_options.appearance_compression(),
+        false,
+    );
+
+    // Write page tree
+    let count = page_refs.len() as i32;
+    out_pdf
+        .pages(page_tree_ref)
+        .kids(page_refs.iter().copied())
+        .count(count);
+
+    // Extend with extracted page content
+    out_pdf.extend(&extracted.chunk);
+
+    // Extend with annotation objects
+    out_pdf.extend(&annot_chunk);
+
+    // Add /Annots to extracted page dictionaries in a post-processing pass.
+    // We then append a fresh xref/trailer section with corrected offsets.
+    let mut pdf_bytes = out_pdf.finish();
+
+    // Post-process: inject /Annots references into page dictionaries.
+    // This modifies object byte lengths, so we append an updated cross-reference
+    // table/trailer afterwards to keep offsets valid.
+    for (page_idx, update) in &page_annots {
+        let page_ref = page_refs[*page_idx];
+        let injected = match update {
+            AnnotsUpdate::Insert(annots_value) => {
+                inject_annots_into_page(&mut pdf_bytes, page_ref, annots_value)
+            }
+            AnnotsUpdate::Append(refs) => append_to_page_annots(&mut pdf_bytes, page_ref, refs),
+        };
+        if !injected {
+            return Err(SaveError::InvalidPdf);
+        }
+    }
+
+    if let Some(AcroFormUpdate::Insert(acro_form_ref)) = acro_form
+        && !inject_acro_form_into_catalog(&mut pdf_bytes, catalog_ref, acro_form_ref)
+    {
+        return Err(SaveError::InvalidPdf);
+    }
+
+    if !page_annots.is_empty() || acro_form.is_some() {
+        append_updated_xref_and_trailer(&mut pdf_bytes, catalog_ref);
+    }
+
+    if write_options.use_object_streams {
+        pdf_bytes = pack_object_streams(&pdf_bytes, catalog_ref).ok_or(SaveError::InvalidPdf)?;
+    }
+
+    Ok(pdf_bytes)
+}
+
+/// Check that all page indices, link destinations and form field names are valid.
+fn validate_page_annotations(
+    page_annotations: &[(usize, Vec<Annotation>)],
+    num_pages: usize,
+) -> Result<(), SaveError> {
+    let mut form_field_names = HashSet::new();
+    for (page_idx, annots) in page_annotations {
+        if *page_idx >= num_pages {
+            return Err(SaveError::InvalidPageIndex(*page_idx));
+        }
 
-    for (page_idx, annots) in &merged_page_annotations {
+        for annot in annots {
+            if let Annotation::Link(link) = annot
+                && let Some(dest_page) = link.dest_page
+                && dest_page >= num_pages
+            {
+                return Err(SaveError::InvalidDestinationPage(dest_page));
+            }
+
+            let field_name = match annot {
+                Annotation::TextField(field) => Some(field.field_name.trim()),
+                Annotation::SignatureField(field) => Some(field.field_name.trim()),
+                Annotation::CheckBox(field) => Some(field.field_name.trim()),
+                Annotation::Choice(field) => Some(field.field_name.trim()),
+                _ => None,
+            };
+
+            if let Some(field_name) = field_name {
+                if field_name.is_empty() {
+                    return Err(SaveError::InvalidFieldName);
+                }
+
+                if !form_field_names.insert(field_name.to_string()) {
+                    return Err(SaveError::DuplicateFieldName(field_name.to_string()));
+                }
+            }
+
+            if let Annotation::Choice(field) = annot
+                && field.options.is_empty()
+            {
+                return Err(SaveError::EmptyChoiceOptions(
+                    field.field_name.trim().to_string(),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The objects written for a set of annotations that still need to be
+/// referenced from the page and catalog dictionaries.
+struct AnnotationObjects {
+    /// How the `/Annots` entry of each page index needs to be updated.
+    page_annots: HashMap<usize, AnnotsUpdate>,
+    /// How the interactive form needs to be updated, if any form fields were written.
+    acro_form: Option<AcroFormUpdate>,
+}
+
+/// An update to the `/Annots` entry of a page.
+enum AnnotsUpdate {
+    /// The page has no annotations yet, and gets the given serialized `/Annots` value.
+    Insert(String),
+    /// The page already has an `/Annots` array, which the references are appended to.
+    Append(Vec<Ref>),
+}
+
+/// An update to the interactive form of the document.
+enum AcroFormUpdate {
+    /// The document has no form yet, and gets the given form dictionary.
+    Insert(Ref),
+    /// The document already has a form, whose `/Fields` the references are appended to.
+    Append(Vec<Ref>),
+}
+
+/// Write the annotation dictionaries, appearance streams and form fields of
+/// all pages into `chunk`.
+///
+/// `pages_with_annots` are the indices of pages that already have an `/Annots`
+/// entry, which is extended instead of replaced. The annotations of a page are
+/// added in the given order, since that order is also their z-order. Appearance streams are
+/// compressed with the `compression` level, or written as is for `None`.
+///
+/// If `has_acro_form` is set, the form fields are added to the existing form
+/// of the document instead of a new form dictionary.
+#[allow(clippy::too_many_arguments)]
+fn write_annotation_objects(
+    chunk: &mut Chunk,
+    allocator: &mut RefAllocator,
+    page_annotations: &[(usize, Vec<Annotation>)],
+    page_refs: &[Ref],
+    pages_with_annots: &HashSet<usize>,
+    annots_array_style: AnnotsArrayStyle,
+    compression: Option<u32>,
+    has_acro_form: bool,
+) -> AnnotationObjects {
+    let mut page_annots: HashMap<usize, AnnotsUpdate> = HashMap::new();
+    let mut acro_field_refs: Vec<Ref> = Vec::new();
+    let mut has_signature_fields = false;
+    let mut needs_appearances = false;
+
+    for (page_idx, annots) in page_annotations {
         let mut this_page_annot_refs: Vec<Ref> = Vec::new();
         let page_ref = page_refs[*page_idx];
 
         for annot in annots.iter() {
-            let sanitized = sanitize_annotation(annot);
-            let annot_ref = annot_refs_allocator.alloc();
+            let mut sanitized = sanitize_annotation(annot);
+            if is_degenerate_rect(sanitized.base().rect) {
+                warn!(
+                    "skipping {} annotation with an empty rect on page {page_idx}",
+                    annot.type_name()
+                );
+                continue;
+            }
+            let annot_ref = allocator.alloc();
+            if sanitized.base().name.is_none() {
+                let name =
+                    annotation_name(*page_idx, this_page_annot_refs.len(), annot_ref, &sanitized);
+                sanitized.base_mut().name = Some(name);
+            }
             let field_ref = match sanitized {
-                Annotation::TextField(_) | Annotation::SignatureField(_) => {
-                    Some(annot_refs_allocator.alloc())
-                }
+                Annotation::TextField(_)
+                | Annotation::SignatureField(_)
+                | Annotation::CheckBox(_)
+                | Annotation::Choice(_) => Some(allocator.alloc()),
                 _ => None,
             };
-            let ap_stream_ref = annot_refs_allocator.alloc();
-
-            // Generate appearance stream
-            let ap_content = generate_appearance(&sanitized);
-
-            if !ap_content.is_empty() {
-                let encoded = deflate_encode(&ap_content);
-                let base = sanitized.base();
-                let bbox = Rect::new(
-                    0.0,
-                    0.0,
-                    base.rect[2] - base.rect[0],
-                    base.rect[3] - base.rect[1],
-                );
+            let file_ref =
+                matches!(sanitized, Annotation::FileAttachment(_)).then(|| allocator.alloc());
+
+            // Generate appearance streams
+            let appearance = if let Some(states) = generate_appearance_states(&sanitized) {
+                let mut state_refs = Vec::with_capacity(states.states.len());
+
+                for (name, content) in &states.states {
+                    let state_ref = allocator.alloc();
+                    write_appearance_stream(
+                        chunk,
+                        allocator,
+                        state_ref,
+                        &sanitized,
+                        content,
+                        compression,
+                    );
+                    state_refs.push((name.clone(), state_ref));
+                }
 
-                let mut xobj = annot_chunk.form_xobject(ap_stream_ref, &encoded);
-                xobj.bbox(bbox);
-                xobj.filter(Filter::FlateDecode);
-
-                // For text-based appearances include Helvetica font resource.
-                if matches!(
-                    &sanitized,
-                    Annotation::FreeText(_)
-                        | Annotation::TextField(_)
-                        | Annotation::SignatureField(_)
-                ) {
-                    let font_ref = annot_refs_allocator.alloc();
-                    xobj.resources().fonts().pair(Name(b"Helv"), font_ref);
-                    xobj.finish();
-
-                    // Write Helvetica font dictionary
-                    let mut font_dict = annot_chunk.indirect(font_ref).dict();
-                    font_dict.pair(Name(b"Type"), Name(b"Font"));
-                    font_dict.pair(Name(b"Subtype"), Name(b"Type1"));
-                    font_dict.pair(Name(b"BaseFont"), Name(b"Helvetica"));
-                    font_dict.finish();
+                AppearanceRefs::States {
+                    states: state_refs,
+                    current: states.current,
+                }
+            } else {
+                let ap_stream_ref = allocator.alloc();
+                let ap_content = generate_appearance(&sanitized);
+
+                // Invisible annotations keep their empty appearance, so that
+                // viewers don't generate a visible one instead.
+                if ap_content.is_empty() && sanitized.base().opacity > 0.0 {
+                    AppearanceRefs::None
                 } else {
-                    xobj.finish();
+                    write_appearance_stream(
+                        chunk,
+                        allocator,
+                        ap_stream_ref,
+                        &sanitized,
+                        &ap_content,
+                        compression,
+                    );
+                    AppearanceRefs::Stream(ap_stream_ref)
+                }
+            };
+
+            // Custom down and rollover appearances of form field widgets.
+            let mut extra_appearances = Vec::new();
+            if !matches!(appearance, AppearanceRefs::None) && sanitized.base().opacity > 0.0 {
+                for (key, content) in widget_appearances(&sanitized) {
+                    let stream_ref = allocator.alloc();
+                    write_appearance_stream(
+                        chunk,
+                        allocator,
+                        stream_ref,
+                        &sanitized,
+                        content,
+                        compression,
+                    );
+                    extra_appearances.push((key, stream_ref));
                 }
             }
 
             // Write annotation dictionary
             write_annotation_dict(
-                &mut annot_chunk,
+                chunk,
                 annot_ref,
                 &sanitized,
-                ap_stream_ref,
-                !ap_content.is_empty(),
-                &page_refs,
+                &appearance,
+                &extra_appearances,
+                page_refs,
                 page_ref,
                 field_ref,
+                file_ref,
             );
 
+            if let Annotation::FileAttachment(attachment) = &sanitized
+                && let Some(file_ref) = file_ref
+            {
+                write_embedded_file(chunk, file_ref, &attachment.data, compression);
+            }
+
             if let Some(field_ref) = field_ref {
                 acro_field_refs.push(field_ref);
+                needs_appearances |= matches!(appearance, AppearanceRefs::None);
             }
             if matches!(sanitized, Annotation::SignatureField(_)) {
                 has_signature_fields = true;
@@ -251,85 +1374,262 @@ pub fn save_annotations(
         }
 
         // Write /Annots array for this page
-        if !this_page_annot_refs.is_empty() {
-            let annots_arr_ref = annot_refs_allocator.alloc();
-            let mut arr = annot_chunk.indirect(annots_arr_ref).array();
-            for r in &this_page_annot_refs {
-                arr.item(*r);
-            }
-            arr.finish();
-            page_annot_arrays.insert(*page_idx, annots_arr_ref);
+        if !this_page_annot_refs.is_empty() && pages_with_annots.contains(page_idx) {
+            page_annots.insert(*page_idx, AnnotsUpdate::Append(this_page_annot_refs));
+        } else if !this_page_annot_refs.is_empty() {
+            let annots_value = match annots_array_style {
+                AnnotsArrayStyle::Indirect => {
+                    let annots_arr_ref = allocator.alloc();
+                    let mut arr = chunk.indirect(annots_arr_ref).array();
+                    for r in &this_page_annot_refs {
+                        arr.item(*r);
+                    }
+                    arr.finish();
+                    format!("{} 0 R", annots_arr_ref.get())
+                }
+                AnnotsArrayStyle::Inline => {
+                    let items: Vec<String> = this_page_annot_refs
+                        .iter()
+                        .map(|r| format!("{} 0 R", r.get()))
+                        .collect();
+                    format!("[{}]", items.join(" "))
+                }
+            };
+            page_annots.insert(*page_idx, AnnotsUpdate::Insert(annots_value));
         }
     }
 
-    let mut acro_form_ref = None;
-    if !acro_field_refs.is_empty() {
-        let acro_ref = annot_refs_allocator.alloc();
-        let mut acro_dict = annot_chunk.indirect(acro_ref).dict();
+    let mut acro_form = None;
+    if has_acro_form && !acro_field_refs.is_empty() {
+        acro_form = Some(AcroFormUpdate::Append(acro_field_refs));
+    } else if !acro_field_refs.is_empty() {
+        let acro_ref = allocator.alloc();
+        let font_ref = allocator.alloc();
+        let mut acro_dict = chunk.indirect(acro_ref).dict();
         let mut fields = acro_dict.insert(Name(b"Fields")).array();
         for field_ref in &acro_field_refs {
             fields.item(*field_ref);
         }
         fields.finish();
-        acro_dict.pair(Name(b"NeedAppearances"), true);
+        // Viewers only need to regenerate appearances for widgets that were
+        // written without one.
+        acro_dict.pair(Name(b"NeedAppearances"), needs_appearances);
         acro_dict.pair(Name(b"DA"), pdf_writer::Str(b"0 0 0 rg /Helv 10 Tf"));
+        acro_dict
+            .insert(Name(b"DR"))
+            .dict()
+            .insert(Name(b"Font"))
+            .dict()
+            .pair(Name(b"Helv"), font_ref);
         if has_signature_fields {
             acro_dict.pair(Name(b"SigFlags"), 3_i32);
         }
         acro_dict.finish();
-        acro_form_ref = Some(acro_ref);
+        write_helvetica_font(chunk, font_ref);
+        acro_form = Some(AcroFormUpdate::Insert(acro_ref));
     }
 
-    // Write page tree
-    let count = page_refs.len() as i32;
-    out_pdf
-        .pages(page_tree_ref)
-        .kids(page_refs.iter().copied())
-        .count(count);
+    AnnotationObjects {
+        page_annots,
+        acro_form,
+    }
+}
 
-    // Extend with extracted page content
-    out_pdf.extend(&extracted.chunk);
+/// Write an sRGB output intent dictionary and its ICC profile stream.
+fn write_srgb_output_intent(chunk: &mut Chunk, output_intent_ref: Ref, profile_ref: Ref) {
+    let encoded = deflate_encode(SRGB_ICC_PROFILE);
+    let mut profile = chunk.stream(profile_ref, &encoded);
+    profile.filter(Filter::FlateDecode);
+    profile.pair(Name(b"N"), 3_i32);
+    profile.finish();
+
+    let mut intent = chunk.indirect(output_intent_ref).dict();
+    intent.pair(Name(b"Type"), Name(b"OutputIntent"));
+    intent.pair(Name(b"S"), Name(b"GTS_PDFA1"));
+    intent.pair(
+        Name(b"OutputConditionIdentifier"),
+        pdf_writer::TextStr("sRGB IEC61966-2.1"),
+    );
+    intent.pair(
+        Name(b"RegistryName"),
+        pdf_writer::TextStr("http://www.color.org"),
+    );
+    intent.pair(Name(b"Info"), pdf_writer::TextStr("sRGB IEC61966-2.1"));
+    intent.pair(Name(b"DestOutputProfile"), profile_ref);
+    intent.finish();
+}
 
-    // Extend with annotation objects
-    out_pdf.extend(&annot_chunk);
+/// The custom down (`/D`) and rollover (`/R`) appearances of a form field
+/// widget, with the keys they are written under.
+fn widget_appearances(annot: &Annotation) -> Vec<(&'static [u8], &[u8])> {
+    let (down, rollover) = match annot {
+        Annotation::TextField(a) => (&a.down_appearance, &a.rollover_appearance),
+        Annotation::SignatureField(a) => (&a.down_appearance, &a.rollover_appearance),
+        Annotation::CheckBox(a) => (&a.down_appearance, &a.rollover_appearance),
+        Annotation::Choice(a) => (&a.down_appearance, &a.rollover_appearance),
+        _ => return Vec::new(),
+    };
 
-    // Add /Annots to extracted page dictionaries in a post-processing pass.
-    // We then append a fresh xref/trailer section with corrected offsets.
-    let mut pdf_bytes = out_pdf.finish();
+    [(b"D".as_ref(), down), (b"R".as_ref(), rollover)]
+        .into_iter()
+        .filter_map(|(key, content)| Some((key, content.as_deref()?)))
+        .collect()
+}
+
+/// The references to the normal appearance of an annotation.
+enum AppearanceRefs {
+    /// The annotation has no appearance stream.
+    None,
+    /// A single appearance stream.
+    Stream(Ref),
+    /// Named appearance states, with the currently selected state.
+    States {
+        states: Vec<(String, Ref)>,
+        current: String,
+    },
+}
+
+/// Write an appearance stream as a form `XObject` sized to the annotation rect.
+///
+/// The content is compressed with the `compression` level, if any.
+fn write_appearance_stream(
+    chunk: &mut Chunk,
+    allocator: &mut RefAllocator,
+    stream_ref: Ref,
+    annot: &Annotation,
+    content: &[u8],
+    compression: Option<u32>,
+) {
+    /// An object referenced from the resources, which can only be written
+    /// once the form `XObject` is finished.
+    enum ResourceObject<'a> {
+        EmbeddedFont(EmbeddedFont<'a>, Ref, &'a str),
+        Image(StampImage, Ref),
+        Helvetica(Ref),
+    }
+
+    let encoded = compression.map(|level| deflate_encode_with_level(content, level));
+    let rect = annotation_rect(annot);
+    let bbox = Rect::new(0.0, 0.0, rect[2] - rect[0], rect[3] - rect[1]);
+
+    let mut xobj = chunk.form_xobject(stream_ref, encoded.as_deref().unwrap_or(content));
+    xobj.bbox(bbox);
+    if let Some((matrix, _)) = appearance_rotation(annot) {
+        xobj.matrix(matrix);
+    }
+    if encoded.is_some() {
+        xobj.filter(Filter::FlateDecode);
+    }
 
-    // Post-process: inject /Annots references into page dictionaries.
-    // This modifies object byte lengths, so we append an updated cross-reference
-    // table/trailer afterwards to keep offsets valid.
-    for (page_idx, annots_ref) in &page_annot_arrays {
-        let page_ref = page_refs[*page_idx];
-        if !inject_annots_into_page(&mut pdf_bytes, page_ref, *annots_ref) {
-            return Err(SaveError::InvalidPdf);
+    let mut resource_object = None;
+    let mut resources = xobj.resources();
+
+    let blend_mode = blend_mode(annot);
+    let fill_opacity = fill_opacity(annot);
+    if !content.is_empty() && (blend_mode.is_some() || fill_opacity.is_some()) {
+        let mut states = resources.insert(Name(b"ExtGState")).dict();
+        let mut state = states.insert(Name(GRAPHICS_STATE_NAME)).dict();
+        state.pair(Name(b"Type"), Name(b"ExtGState"));
+        if let Some(mode) = blend_mode {
+            state.pair(Name(b"BM"), Name(mode.pdf_name()));
+        }
+        if let Some(opacity) = fill_opacity {
+            state.pair(Name(b"ca"), opacity);
         }
+        state.finish();
+        states.finish();
     }
 
-    if let Some(acro_form_ref) = acro_form_ref
-        && !inject_acro_form_into_catalog(&mut pdf_bytes, catalog_ref, acro_form_ref)
+    // Free text with a user-supplied font embeds it as `/F0`.
+    if let Annotation::FreeText(free_text) = annot
+        && let Some(data) = &free_text.font_data
+        && let Some(font) = EmbeddedFont::new((**data).as_ref())
     {
-        return Err(SaveError::InvalidPdf);
+        let font_ref = allocator.alloc();
+        resources.fonts().pair(Name(EMBEDDED_FONT_NAME), font_ref);
+        resource_object = Some(ResourceObject::EmbeddedFont(
+            font,
+            font_ref,
+            &free_text.text,
+        ));
+    } else if let Annotation::Stamp(stamp) = annot
+        && !content.is_empty()
+        && let Some(image) = StampImage::new((*stamp.image_data).as_ref(), stamp.format)
+    {
+        let image_ref = allocator.alloc();
+        resources
+            .x_objects()
+            .pair(Name(STAMP_IMAGE_NAME), image_ref);
+        resource_object = Some(ResourceObject::Image(image, image_ref));
+    } else if matches!(
+        annot,
+        Annotation::FreeText(_)
+            | Annotation::TextField(_)
+            | Annotation::SignatureField(_)
+            | Annotation::Choice(_)
+            | Annotation::Line(LineAnnot {
+                measure: Some(_),
+                ..
+            })
+    ) {
+        let font_ref = allocator.alloc();
+        resources.fonts().pair(Name(b"Helv"), font_ref);
+        resource_object = Some(ResourceObject::Helvetica(font_ref));
     }
 
-    if !page_annot_arrays.is_empty() || acro_form_ref.is_some() {
-        append_updated_xref_and_trailer(&mut pdf_bytes, catalog_ref);
+    resources.finish();
+    xobj.finish();
+
+    match resource_object {
+        Some(ResourceObject::EmbeddedFont(font, font_ref, text)) => {
+            font.write(chunk, allocator, font_ref, text);
+        }
+        Some(ResourceObject::Image(image, image_ref)) => {
+            image.write(chunk, allocator, image_ref);
+        }
+        Some(ResourceObject::Helvetica(font_ref)) => write_helvetica_font(chunk, font_ref),
+        None => {}
     }
+}
 
-    Ok(pdf_bytes)
+/// Write the contents of an attached file as an embedded file stream.
+///
+/// The data is compressed with the `compression` level, if any.
+fn write_embedded_file(chunk: &mut Chunk, file_ref: Ref, data: &[u8], compression: Option<u32>) {
+    let encoded = compression.map(|level| deflate_encode_with_level(data, level));
+    let mut stream = chunk.stream(file_ref, encoded.as_deref().unwrap_or(data));
+    stream.pair(Name(b"Type"), Name(b"EmbeddedFile"));
+    if encoded.is_some() {
+        stream.filter(Filter::FlateDecode);
+    }
+    stream
+        .insert(Name(b"Params"))
+        .dict()
+        .pair(Name(b"Size"), data.len() as i32);
+    stream.finish();
+}
+
+/// Write a WinAnsi-encoded Helvetica font dictionary.
+fn write_helvetica_font(chunk: &mut Chunk, font_ref: Ref) {
+    let mut font_dict = chunk.indirect(font_ref).dict();
+    font_dict.pair(Name(b"Type"), Name(b"Font"));
+    font_dict.pair(Name(b"Subtype"), Name(b"Type1"));
+    font_dict.pair(Name(b"BaseFont"), Name(b"Helvetica"));
+    font_dict.pair(Name(b"Encoding"), Name(b"WinAnsiEncoding"));
+    font_dict.finish();
 }
 
-/// Inject an /Annots reference into a page dictionary in the raw PDF bytes.
+/// Inject an /Annots entry into a page dictionary in the raw PDF bytes.
 ///
 /// This searches for the page object by its reference number and inserts
-/// the /Annots key before the end of the dictionary.
-fn inject_annots_into_page(pdf_bytes: &mut Vec<u8>, page_ref: Ref, annots_ref: Ref) -> bool {
+/// the /Annots key with the serialized `annots_value` (an indirect reference
+/// or an inline array) before the end of the dictionary.
+fn inject_annots_into_page(pdf_bytes: &mut Vec<u8>, page_ref: Ref, annots_value: &str) -> bool {
     let page_obj_marker = format!("{} 0 obj", page_ref.get());
-    let annots_entry = format!("/Annots {} 0 R", annots_ref.get());
+    let annots_entry = format!("/Annots {annots_value}");
 
     // Find the page object
-    let Some(obj_pos) = find_bytes(pdf_bytes, page_obj_marker.as_bytes()) else {
+    let Some(obj_pos) = find_object(pdf_bytes, page_ref.get(), 0) else {
         return false;
     };
 
@@ -357,7 +1657,157 @@ fn inject_annots_into_page(pdf_bytes: &mut Vec<u8>, page_ref: Ref, annots_ref: R
     true
 }
 
+/// Append annotation references to the existing `/Annots` entry of a page.
+///
+/// An inline array is extended in place. If the entry references an array
+/// object, that object is extended instead.
+fn append_to_page_annots(pdf_bytes: &mut Vec<u8>, page_ref: Ref, refs: &[Ref]) -> bool {
+    let Some(obj_pos) = find_object(pdf_bytes, page_ref.get(), 0) else {
+        return false;
+    };
+    let Some(dict_start_rel) = find_bytes(&pdf_bytes[obj_pos..], b"<<") else {
+        return false;
+    };
+    let dict_start = obj_pos + dict_start_rel;
+    let Some(dict_end) = find_matching_dict_end(pdf_bytes, dict_start) else {
+        return false;
+    };
+    let Some(key_pos) = find_bytes(&pdf_bytes[dict_start..dict_end], b"/Annots") else {
+        return false;
+    };
+
+    let mut value_pos = dict_start + key_pos + b"/Annots".len();
+    while value_pos < dict_end && pdf_bytes[value_pos].is_ascii_whitespace() {
+        value_pos += 1;
+    }
+
+    if pdf_bytes.get(value_pos) == Some(&b'[') {
+        return append_refs_to_array(pdf_bytes, value_pos, refs);
+    }
+
+    // Otherwise, the value is a reference like `12 0 R`.
+    let mut parts = pdf_bytes[value_pos..dict_end]
+        .split(|b| b.is_ascii_whitespace())
+        .filter(|p| !p.is_empty())
+        .map(|p| std::str::from_utf8(p).ok()?.parse::<i32>().ok());
+    let (Some(Some(obj_number)), Some(Some(gen_number))) = (parts.next(), parts.next()) else {
+        return false;
+    };
+
+    let Some(array_obj_pos) = find_object(pdf_bytes, obj_number, gen_number) else {
+        return false;
+    };
+    let Some(array_start_rel) = find_bytes(&pdf_bytes[array_obj_pos..], b"[") else {
+        return false;
+    };
+    append_refs_to_array(pdf_bytes, array_obj_pos + array_start_rel, refs)
+}
+
+/// Find the `/key` entry of the dictionary object `obj_ref`, returning the
+/// position right after the key and the end of the dictionary.
+fn find_dict_key(pdf_bytes: &[u8], obj_ref: Ref, key: &[u8]) -> Option<(usize, usize)> {
+    let obj_pos = find_object(pdf_bytes, obj_ref.get(), 0)?;
+    let dict_start = obj_pos + find_bytes(&pdf_bytes[obj_pos..], b"<<")?;
+    let dict_end = find_matching_dict_end(pdf_bytes, dict_start)?;
+    let name = [b"/".as_slice(), key].concat();
+    let key_pos = find_bytes(&pdf_bytes[dict_start..dict_end], &name)?;
+
+    Some((dict_start + key_pos + name.len(), dict_end))
+}
+
+/// Replace the inline array under `key` of a dictionary object with `value`.
+fn replace_dict_array(pdf_bytes: &mut Vec<u8>, obj_ref: Ref, key: &[u8], value: &[u8]) -> bool {
+    let Some((value_pos, dict_end)) = find_dict_key(pdf_bytes, obj_ref, key) else {
+        return false;
+    };
+    let Some(array_start_rel) = find_bytes(&pdf_bytes[value_pos..dict_end], b"[") else {
+        return false;
+    };
+    let array_start = value_pos + array_start_rel;
+    let Some(array_end) = find_matching_array_end(pdf_bytes, array_start) else {
+        return false;
+    };
+
+    pdf_bytes.splice(array_start..array_end + 1, value.iter().copied());
+    true
+}
+
+/// Replace the reference under `key` of a dictionary object with `value`.
+fn replace_dict_ref(pdf_bytes: &mut Vec<u8>, obj_ref: Ref, key: &[u8], value: &[u8]) -> bool {
+    let Some((value_pos, dict_end)) = find_dict_key(pdf_bytes, obj_ref, key) else {
+        return false;
+    };
+    // A reference like `12 0 R` ends with the first `R` after the key.
+    let Some(ref_end_rel) = find_bytes(&pdf_bytes[value_pos..dict_end], b"R") else {
+        return false;
+    };
+    let ref_end = value_pos + ref_end_rel + 1;
+    let is_ref = pdf_bytes[value_pos..ref_end - 1]
+        .iter()
+        .all(|b| b.is_ascii_digit() || b.is_ascii_whitespace());
+    if !is_ref {
+        return false;
+    }
+
+    let mut replacement = b" ".to_vec();
+    replacement.extend_from_slice(value);
+    pdf_bytes.splice(value_pos..ref_end, replacement);
+    true
+}
+
+/// Find the byte index of the matching `]` for the array starting at `array_start`.
+fn find_matching_array_end(bytes: &[u8], array_start: usize) -> Option<usize> {
+    let mut depth = 0_i32;
+    for (idx, b) in bytes.iter().enumerate().skip(array_start) {
+        match b {
+            b'[' => depth += 1,
+            b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Insert references before the matching `]` of the array starting at `array_start`.
+fn append_refs_to_array(bytes: &mut Vec<u8>, array_start: usize, refs: &[Ref]) -> bool {
+    let Some(array_end) = find_matching_array_end(bytes, array_start) else {
+        return false;
+    };
+
+    let insert_bytes = refs
+        .iter()
+        .map(|r| format!(" {} 0 R", r.get()))
+        .collect::<String>()
+        .into_bytes();
+    bytes.splice(array_end..array_end, insert_bytes);
+    true
+}
+
+/// Find the header of an indirect object, making sure that e.g. `2 0 obj`
+/// doesn't match `12 0 obj`.
+fn find_object(pdf_bytes: &[u8], obj_number: i32, gen_number: i32) -> Option<usize> {
+    let marker = format!("{obj_number} {gen_number} obj");
+    let mut search_start = 0;
+
+    loop {
+        let pos = search_start + find_bytes(&pdf_bytes[search_start..], marker.as_bytes())?;
+        if pos == 0 || !pdf_bytes[pos - 1].is_ascii_digit() {
+            return Some(pos);
+        }
+        search_start = pos + 1;
+    }
+}
+
 /// Inject an `/AcroForm` reference into the catalog dictionary.
+///
+/// Fails if the catalog already has a form, whose fields would otherwise be
+/// lost.
 fn inject_acro_form_into_catalog(
     pdf_bytes: &mut Vec<u8>,
     catalog_ref: Ref,
@@ -366,7 +1816,7 @@ fn inject_acro_form_into_catalog(
     let catalog_obj_marker = format!("{} 0 obj", catalog_ref.get());
     let acro_entry = format!("/AcroForm {} 0 R", acro_form_ref.get());
 
-    let Some(obj_pos) = find_bytes(pdf_bytes, catalog_obj_marker.as_bytes()) else {
+    let Some(obj_pos) = find_object(pdf_bytes, catalog_ref.get(), 0) else {
         return false;
     };
 
@@ -380,7 +1830,7 @@ fn inject_acro_form_into_catalog(
     };
 
     if find_bytes(&pdf_bytes[dict_start..dict_end], b"/AcroForm").is_some() {
-        return true;
+        return false;
     }
 
     let insert_bytes = format!("\n  {acro_entry}\n").into_bytes();
@@ -391,6 +1841,43 @@ fn inject_acro_form_into_catalog(
     true
 }
 
+/// Insert `entry` at the start of the dictionary object `obj_ref`, or of the
+/// inline dictionary under `key` of that object.
+fn inject_entry_into_dict(
+    pdf_bytes: &mut Vec<u8>,
+    obj_ref: Ref,
+    key: Option<&[u8]>,
+    entry: &str,
+) -> bool {
+    let dict_start = match key {
+        Some(key) => {
+            let Some((mut value_pos, dict_end)) = find_dict_key(pdf_bytes, obj_ref, key) else {
+                return false;
+            };
+            while value_pos < dict_end && pdf_bytes[value_pos].is_ascii_whitespace() {
+                value_pos += 1;
+            }
+            if !pdf_bytes[value_pos..dict_end].starts_with(b"<<") {
+                return false;
+            }
+            value_pos
+        }
+        None => {
+            let Some(obj_pos) = find_object(pdf_bytes, obj_ref.get(), 0) else {
+                return false;
+            };
+            let Some(dict_start_rel) = find_bytes(&pdf_bytes[obj_pos..], b"<<") else {
+                return false;
+            };
+            obj_pos + dict_start_rel
+        }
+    };
+
+    let insert_pos = dict_start + 2;
+    pdf_bytes.splice(insert_pos..insert_pos, format!(" {entry} ").into_bytes());
+    true
+}
+
 /// Find the byte index of the matching closing ">>" for a dictionary.
 ///
 /// Returns the position of the first '>' in the closing ">>".
@@ -483,9 +1970,202 @@ fn append_updated_xref_and_trailer(pdf_bytes: &mut Vec<u8>, catalog_ref: Ref) {
     pdf_bytes.extend_from_slice(b"%%EOF");
 }
 
+/// The maximum number of objects packed into a single object stream.
+const OBJECTS_PER_STREAM: usize = 100;
+
+/// The location of an object, as recorded in a cross-reference stream.
+enum XrefStreamEntry {
+    /// An uncompressed object at the given byte offset.
+    InUse { offset: usize, generation: u16 },
+    /// An object stored in the object stream with the given object number.
+    Compressed { stream: i32, index: usize },
+}
+
+/// Rewrite a complete document so that all objects other than streams are
+/// packed into object streams, and the cross-reference table is replaced by a
+/// cross-reference stream.
+///
+/// Only the most recent definition of each object is kept, so any previous
+/// cross-reference sections are dropped. The objects are taken from the list
+/// built by [`object_spans`], which skips over stream data, so streams that
+/// happen to contain object headers are copied as a whole.
+fn pack_object_streams(pdf_bytes: &[u8], catalog_ref: Ref) -> Option<Vec<u8>> {
+    let mut latest = BTreeMap::new();
+    for span in object_spans(pdf_bytes) {
+        latest.insert(span.id, span);
+    }
+    let mut objects = latest.into_values().collect::<Vec<_>>();
+    objects.sort_by_key(|span| span.start);
+    let first_offset = objects.first()?.start;
+
+    let mut out = pdf_bytes[..first_offset].to_vec();
+    let mut entries: BTreeMap<i32, XrefStreamEntry> = BTreeMap::new();
+    let mut packed: Vec<(i32, &[u8])> = Vec::new();
+
+    for span in &objects {
+        let id = span.id;
+        let object = &pdf_bytes[span.start..span.end?];
+
+        let header_end = find_bytes(object, b"obj")? + 3;
+        let generation = std::str::from_utf8(&object[..header_end])
+            .ok()?
+            .split_ascii_whitespace()
+            .nth(1)?
+            .parse::<u16>()
+            .ok()?;
+        let body_end = object.len() - b"endobj".len();
+        let body = object[header_end..body_end].trim_ascii();
+
+        // Streams and objects with a non-zero generation can't be stored in
+        // object streams.
+        if generation != 0 || body.ends_with(b"endstream") {
+            entries.insert(
+                id,
+                XrefStreamEntry::InUse {
+                    offset: out.len(),
+                    generation,
+                },
+            );
+            out.extend_from_slice(&object[..body_end + 6]);
+            out.push(b'\n');
+        } else {
+            packed.push((id, body));
+        }
+    }
+
+    let mut next_id = objects.iter().map(|span| span.id).max()? + 1;
+    for chunk in packed.chunks(OBJECTS_PER_STREAM) {
+        let stream_id = next_id;
+        next_id += 1;
+
+        let mut header = String::new();
+        let mut data = Vec::new();
+        for (index, (id, body)) in chunk.iter().enumerate() {
+            header.push_str(&format!("{id} {} ", data.len()));
+            data.extend_from_slice(body);
+            data.push(b'\n');
+            entries.insert(
+                *id,
+                XrefStreamEntry::Compressed {
+                    stream: stream_id,
+                    index,
+                },
+            );
+        }
+
+        let first = header.len();
+        let mut content = header.into_bytes();
+        content.extend_from_slice(&data);
+        let encoded = deflate_encode(&content);
+
+        entries.insert(
+            stream_id,
+            XrefStreamEntry::InUse {
+                offset: out.len(),
+                generation: 0,
+            },
+        );
+        out.extend_from_slice(
+            format!(
+                "{stream_id} 0 obj\n<< /Type /ObjStm /N {} /First {first} /Filter /FlateDecode /Length {} >>\nstream\n",
+                chunk.len(),
+                encoded.len()
+            )
+            .as_bytes(),
+        );
+        out.extend_from_slice(&encoded);
+        out.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    let xref_id = next_id;
+    let xref_offset = out.len();
+    entries.insert(
+        xref_id,
+        XrefStreamEntry::InUse {
+            offset: xref_offset,
+            generation: 0,
+        },
+    );
+
+    // Offsets and object stream numbers share the second field, which is as
+    // wide as the largest of them needs.
+    let size = xref_id + 1;
+    let field_width = xref_field_width(xref_offset.max(xref_id as usize));
+    let mut data = Vec::with_capacity(size as usize * (field_width + 3));
+    for id in 0..size {
+        let (kind, field, generation) = match entries.get(&id) {
+            Some(XrefStreamEntry::InUse { offset, generation }) => (1_u8, *offset, *generation),
+            Some(XrefStreamEntry::Compressed { stream, index }) => {
+                (2, *stream as usize, *index as u16)
+            }
+            None if id == 0 => (0, 0, u16::MAX),
+            None => (0, 0, 0),
+        };
+        push_xref_stream_row(&mut data, field_width, kind, field, generation);
+    }
+
+    let encoded = deflate_encode(&data);
+    out.extend_from_slice(
+        format!(
+            "{xref_id} 0 obj\n<< /Type /XRef /Size {size} /W [1 {field_width} 2] /Root {} 0 R /Filter /FlateDecode /Length {} >>\nstream\n",
+            catalog_ref.get(),
+            encoded.len()
+        )
+        .as_bytes(),
+    );
+    out.extend_from_slice(&encoded);
+    out.extend_from_slice(b"\nendstream\nendobj\n");
+    out.extend_from_slice(format!("startxref\n{xref_offset}\n%%EOF").as_bytes());
+
+    Some(out)
+}
+
+/// The number of bytes of a cross-reference stream field that holds values up
+/// to `max_field`.
+fn xref_field_width(max_field: usize) -> usize {
+    (usize::BITS - max_field.leading_zeros()).div_ceil(8).max(1) as usize
+}
+
+/// Append a row of a cross-reference stream with the field widths
+/// `[1 field_width 2]`.
+fn push_xref_stream_row(
+    data: &mut Vec<u8>,
+    field_width: usize,
+    kind: u8,
+    field: usize,
+    generation: u16,
+) {
+    data.push(kind);
+    data.extend_from_slice(&field.to_be_bytes()[size_of::<usize>() - field_width..]);
+    data.extend_from_slice(&generation.to_be_bytes());
+}
+
 /// Collect all indirect object offsets by scanning object headers.
 fn collect_object_offsets(pdf_bytes: &[u8]) -> BTreeMap<i32, usize> {
-    let mut offsets = BTreeMap::new();
+    object_spans(pdf_bytes)
+        .into_iter()
+        .map(|span| (span.id, span.start))
+        .collect()
+}
+
+/// The location of an indirect object in a serialized document.
+struct ObjectSpan {
+    /// The object number.
+    id: i32,
+    /// The offset of the object header.
+    start: usize,
+    /// The offset right after `endobj`, if the end of the object was found.
+    end: Option<usize>,
+}
+
+/// List all indirect objects of a serialized document in the order they
+/// appear in.
+///
+/// Objects are found by their header lines. Once an object is found, the
+/// scan continues after its `endobj`, so stream data that contains text
+/// looking like an object header isn't mistaken for an object.
+fn object_spans(pdf_bytes: &[u8]) -> Vec<ObjectSpan> {
+    let mut spans = Vec::new();
     let mut line_start = 0;
     let mut idx = 0;
 
@@ -497,7 +2177,18 @@ fn collect_object_offsets(pdf_bytes: &[u8]) -> BTreeMap<i32, usize> {
         }
 
         if let Some(id) = parse_obj_header(&pdf_bytes[line_start..idx]) {
-            offsets.insert(id, line_start);
+            let end = object_end(pdf_bytes, idx);
+            spans.push(ObjectSpan {
+                id,
+                start: line_start,
+                end,
+            });
+
+            if let Some(end) = end {
+                idx = end;
+                line_start = end;
+                continue;
+            }
         }
 
         if idx < pdf_bytes.len()
@@ -514,7 +2205,51 @@ fn collect_object_offsets(pdf_bytes: &[u8]) -> BTreeMap<i32, usize> {
         line_start = idx;
     }
 
-    offsets
+    spans
+}
+
+/// Find the end of the object whose body starts at `body_start`, i.e. the
+/// offset right after its `endobj` keyword.
+///
+/// The data of streams with a direct `/Length` is skipped without looking at
+/// it. For other streams, the first `endstream` keyword marks the end of the
+/// data.
+fn object_end(pdf_bytes: &[u8], body_start: usize) -> Option<usize> {
+    let body = &pdf_bytes[body_start..];
+    let endobj = find_bytes(body, b"endobj");
+
+    let endobj_from = |start: usize| {
+        find_bytes(&body[start..], b"endobj").map(|pos| body_start + start + pos + b"endobj".len())
+    };
+
+    let Some(stream) = find_bytes(body, b"stream").filter(|&pos| {
+        endobj.is_none_or(|end| pos < end) && body[..pos].trim_ascii_end().ends_with(b">>")
+    }) else {
+        return endobj.map(|pos| body_start + pos + b"endobj".len());
+    };
+
+    let mut data_start = stream + b"stream".len();
+    if body[data_start..].starts_with(b"\r\n") {
+        data_start += 2;
+    } else if body[data_start..].starts_with(b"\n") || body[data_start..].starts_with(b"\r") {
+        data_start += 1;
+    }
+
+    let data_end = dict_entry(&body[..stream], b"Length")
+        .and_then(|length| std::str::from_utf8(length).ok()?.parse::<usize>().ok())
+        .map(|length| data_start + length)
+        .filter(|&end| {
+            body.get(end..)
+                .is_some_and(|rest| rest.trim_ascii_start().starts_with(b"endstream"))
+        });
+
+    match data_end {
+        Some(end) => endobj_from(end),
+        None => {
+            let endstream = data_start + find_bytes(&body[data_start..], b"endstream")?;
+            endobj_from(endstream)
+        }
+    }
 }
 
 /// Parse an indirect object header line like "12 0 obj".
@@ -537,49 +2272,472 @@ fn parse_obj_header(line: &[u8]) -> Option<i32> {
         .parse::<i32>()
         .ok()?;
 
-    if i + 6 > line.len() || &line[i..i + 6] != b" 0 obj" {
-        return None;
+    if i + 6 > line.len() || &line[i..i + 6] != b" 0 obj" {
+        return None;
+    }
+    i += 6;
+
+    while i < line.len() {
+        if !line[i].is_ascii_whitespace() {
+            return None;
+        }
+        i += 1;
+    }
+
+    Some(id)
+}
+
+/// Parse the last startxref value from the current PDF bytes.
+fn find_last_startxref(pdf_bytes: &[u8]) -> Option<usize> {
+    let marker = b"startxref";
+    let marker_pos = pdf_bytes.windows(marker.len()).rposition(|w| w == marker)?;
+    let mut idx = marker_pos + marker.len();
+
+    while idx < pdf_bytes.len() && pdf_bytes[idx].is_ascii_whitespace() {
+        idx += 1;
+    }
+
+    let start = idx;
+    while idx < pdf_bytes.len() && pdf_bytes[idx].is_ascii_digit() {
+        idx += 1;
+    }
+
+    if start == idx {
+        return None;
+    }
+
+    std::str::from_utf8(&pdf_bytes[start..idx])
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Append a cross-reference section for an incremental update, covering only
+/// the objects in `new_objects` (object number to generation and offset) and
+/// `free_objects` (object number to the generation of its next use).
+///
+/// `base_offset` is the offset of `pdf_bytes` in the file, for updates that are
+/// built separately from the original bytes.
+fn append_incremental_xref(
+    pdf_bytes: &mut Vec<u8>,
+    base_offset: usize,
+    new_objects: &BTreeMap<i32, (i32, usize)>,
+    free_objects: &BTreeMap<i32, i32>,
+    trailer: &IncrementalTrailer,
+) {
+    // Free entries form a linked list that starts at object 0.
+    let free_ids = free_objects.keys().copied().collect::<Vec<_>>();
+    let next_free = |id: i32| {
+        free_ids
+            .iter()
+            .find(|free_id| **free_id > id)
+            .copied()
+            .unwrap_or(0)
+    };
+
+    let xref_offset = base_offset + pdf_bytes.len();
+    // The cross-reference stream is an object of its own, after all others.
+    let xref_id = trailer.xref_stream.then_some(trailer.size);
+
+    // Each row is an entry type, the offset or next free object, and the
+    // generation.
+    let mut rows = BTreeMap::new();
+    rows.insert(0, (0_u8, next_free(0) as usize, 65535_i32));
+    for (id, generation) in free_objects {
+        rows.insert(*id, (0, next_free(*id) as usize, *generation));
+    }
+    for (id, (generation, offset)) in new_objects {
+        rows.insert(*id, (1, *offset, *generation));
+    }
+    if let Some(xref_id) = xref_id {
+        rows.insert(xref_id, (1, xref_offset, 0));
+    }
+
+    // Group consecutive object numbers into subsections.
+    let ids = rows.keys().copied().collect::<Vec<_>>();
+    let mut subsections = Vec::new();
+    let mut start = 0;
+    while start < ids.len() {
+        let mut end = start + 1;
+        while end < ids.len() && ids[end] == ids[end - 1] + 1 {
+            end += 1;
+        }
+        subsections.push(&ids[start..end]);
+        start = end;
+    }
+
+    if let Some(xref_id) = xref_id {
+        let max_field = rows.values().map(|(_, field, _)| *field).max().unwrap_or(0);
+        let field_width = xref_field_width(max_field);
+        let mut data = Vec::with_capacity(rows.len() * (field_width + 3));
+        for (kind, field, generation) in rows.values() {
+            push_xref_stream_row(&mut data, field_width, *kind, *field, *generation as u16);
+        }
+        let encoded = deflate_encode(&data);
+
+        let index = subsections
+            .iter()
+            .map(|ids| format!("{} {}", ids[0], ids.len()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        pdf_bytes.extend_from_slice(
+            format!(
+                "{xref_id} 0 obj\n<< /Type /XRef /Size {} /W [1 {field_width} 2] /Index [{index}] {} /Filter /FlateDecode /Length {} >>\nstream\n",
+                xref_id + 1,
+                trailer.entries.join(" "),
+                encoded.len()
+            )
+            .as_bytes(),
+        );
+        pdf_bytes.extend_from_slice(&encoded);
+        pdf_bytes.extend_from_slice(b"\nendstream\nendobj\n");
+    } else {
+        pdf_bytes.extend_from_slice(b"xref\n");
+        for ids in &subsections {
+            pdf_bytes.extend_from_slice(format!("{} {}\n", ids[0], ids.len()).as_bytes());
+            for id in *ids {
+                let (kind, field, generation) = rows[id];
+                let kind = if kind == 0 { 'f' } else { 'n' };
+                pdf_bytes.extend_from_slice(
+                    format!("{field:010} {generation:05} {kind}\r\n").as_bytes(),
+                );
+            }
+        }
+
+        pdf_bytes.extend_from_slice(b"trailer\n<<\n");
+        pdf_bytes.extend_from_slice(format!("  /Size {}\n", trailer.size).as_bytes());
+        for entry in &trailer.entries {
+            pdf_bytes.extend_from_slice(format!("  {entry}\n").as_bytes());
+        }
+        pdf_bytes.extend_from_slice(b">>\n");
+    }
+
+    pdf_bytes.extend_from_slice(b"startxref\n");
+    pdf_bytes.extend_from_slice(format!("{xref_offset}\n").as_bytes());
+    pdf_bytes.extend_from_slice(b"%%EOF\n");
+}
+
+/// Return the raw bytes of the most recent trailer dictionary, which is either
+/// a classic `trailer` dictionary or the dictionary of a cross-reference stream.
+fn last_trailer_dict(pdf_bytes: &[u8]) -> Option<&[u8]> {
+    let xref_pos = find_last_startxref(pdf_bytes)?;
+    let section = pdf_bytes.get(xref_pos..)?;
+
+    let search_start = if section.trim_ascii_start().starts_with(b"xref") {
+        xref_pos + find_bytes(section, b"trailer")?
+    } else {
+        xref_pos
+    };
+    let dict_start = search_start + find_bytes(&pdf_bytes[search_start..], b"<<")?;
+    let dict_end = find_matching_dict_end(pdf_bytes, dict_start)?;
+
+    Some(&pdf_bytes[dict_start..dict_end + 2])
+}
+
+/// Return the raw keys and values of all top-level entries in a serialized
+/// dictionary, or `None` if it can't be parsed.
+fn dict_entries(dict: &[u8]) -> Option<Vec<(&[u8], &[u8])>> {
+    let end = find_matching_dict_end(dict, 0)?;
+    let mut entries = Vec::new();
+    let mut idx = 2;
+
+    loop {
+        idx += dict[idx..end]
+            .iter()
+            .take_while(|b| b.is_ascii_whitespace())
+            .count();
+        if idx == end {
+            return Some(entries);
+        }
+
+        let key_end = name_end(dict, idx)?;
+        let value_start = key_end
+            + dict[key_end..end]
+                .iter()
+                .take_while(|b| b.is_ascii_whitespace())
+                .count();
+        let value_end = value_end(dict, value_start)?.min(end);
+        entries.push((&dict[idx + 1..key_end], &dict[value_start..value_end]));
+        idx = value_end;
+    }
+}
+
+/// The end of the name starting at `start`, or `None` if there is no name.
+fn name_end(bytes: &[u8], start: usize) -> Option<usize> {
+    if bytes.get(start) != Some(&b'/') {
+        return None;
+    }
+
+    Some(
+        start
+            + 1
+            + bytes[start + 1..]
+                .iter()
+                .take_while(|b| !b.is_ascii_whitespace() && !b"/[]<>(){}%".contains(b))
+                .count(),
+    )
+}
+
+/// The end of the serialized object starting at `start`.
+fn value_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let token_end = |start: usize| {
+        start
+            + bytes[start..]
+                .iter()
+                .take_while(|b| !b.is_ascii_whitespace() && !b"/[]<>(){}%".contains(b))
+                .count()
+    };
+
+    match *bytes.get(start)? {
+        b'/' => name_end(bytes, start),
+        b'[' => Some(find_matching_array_end(bytes, start)? + 1),
+        b'<' if bytes.get(start + 1) == Some(&b'<') => {
+            Some(find_matching_dict_end(bytes, start)? + 2)
+        }
+        b'<' => Some(start + find_bytes(&bytes[start..], b">")? + 1),
+        b'(' => {
+            let mut depth = 0_i32;
+            let mut idx = start;
+            while idx < bytes.len() {
+                match bytes[idx] {
+                    b'\\' => idx += 1,
+                    b'(' => depth += 1,
+                    b')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(idx + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                idx += 1;
+            }
+            None
+        }
+        _ => {
+            let end = token_end(start);
+            if end == start {
+                return None;
+            }
+
+            // An indirect reference like `12 0 R`.
+            let skip_whitespace = |idx: usize| {
+                idx + bytes[idx..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_whitespace())
+                    .count()
+            };
+            let generation_start = skip_whitespace(end);
+            let generation_end = token_end(generation_start);
+            let r_start = skip_whitespace(generation_end);
+            let is_ref = [start..end, generation_start..generation_end]
+                .into_iter()
+                .all(|range| !range.is_empty() && bytes[range].iter().all(u8::is_ascii_digit))
+                && bytes.get(r_start) == Some(&b'R')
+                && token_end(r_start) == r_start + 1;
+
+            Some(if is_ref { r_start + 1 } else { end })
+        }
+    }
+}
+
+/// Return the raw value of a top-level entry in a serialized dictionary that
+/// only holds simple values, such as a trailer.
+fn dict_entry<'a>(dict: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    let mut search_start = 0;
+
+    loop {
+        let key_pos = search_start + find_bytes(&dict[search_start..], b"/")?;
+        let name_end = key_pos
+            + 1
+            + dict[key_pos + 1..]
+                .iter()
+                .position(|b| b.is_ascii_whitespace() || b"/[]<>()".contains(b))
+                .unwrap_or(dict.len() - key_pos - 1);
+        search_start = name_end;
+
+        if &dict[key_pos + 1..name_end] != key {
+            continue;
+        }
+
+        let value = dict[name_end..].trim_ascii_start();
+        let len = if value.starts_with(b"[") {
+            value.iter().position(|b| *b == b']')? + 1
+        } else if value.starts_with(b"<<") {
+            find_matching_dict_end(value, 0)? + 2
+        } else {
+            value
+                .iter()
+                .position(|b| *b == b'/' || *b == b'>')
+                .unwrap_or(value.len())
+        };
+
+        return Some(value[..len].trim_ascii_end());
+    }
+}
+
+/// Find the position of a byte pattern in a byte slice.
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Write the width and, if set, the dash pattern of a stroked annotation to
+/// `/BS`.
+fn write_stroke_border(
+    annot_dict: &mut pdf_writer::writers::Annotation<'_>,
+    width: f32,
+    dash: Option<&[f32]>,
+) {
+    let mut border_style = annot_dict.border_style();
+    border_style.width(width);
+    if let Some(dash) = dash {
+        border_style
+            .style(pdf_writer::types::BorderType::Dashed)
+            .dashes(dash.iter().copied());
     }
-    i += 6;
+}
 
-    while i < line.len() {
-        if !line[i].is_ascii_whitespace() {
-            return None;
-        }
-        i += 1;
+/// Write the border style of a shape annotation to `/BS` and, for cloudy
+/// borders, the `/BE` border effect dictionary.
+fn write_shape_border(annot_dict: &mut pdf_writer::writers::Annotation<'_>, shape: &ShapeAnnot) {
+    let mut border_style = annot_dict.border_style();
+    border_style.width(shape.line_width);
+    if let BorderStyle::Dashed(dashes) = &shape.border_style {
+        border_style
+            .style(pdf_writer::types::BorderType::Dashed)
+            .dashes(dashes.iter().copied());
+    }
+    border_style.finish();
+
+    if let BorderStyle::Cloudy(intensity) = shape.border_style {
+        annot_dict
+            .insert(Name(b"BE"))
+            .dict()
+            .pair(Name(b"S"), Name(b"C"))
+            .pair(Name(b"I"), intensity);
     }
-
-    Some(id)
 }
 
-/// Parse the last startxref value from the current PDF bytes.
-fn find_last_startxref(pdf_bytes: &[u8]) -> Option<usize> {
-    let marker = b"startxref";
-    let marker_pos = pdf_bytes.windows(marker.len()).rposition(|w| w == marker)?;
-    let mut idx = marker_pos + marker.len();
+/// Write the measurement scale of a line annotation, and show the measured
+/// length as its caption.
+///
+/// The scale is written as the `/X` number format, and the distance and area
+/// formats required by the spec are expressed relative to it.
+fn write_measure(
+    annot_dict: &mut pdf_writer::writers::Annotation<'_>,
+    measure: &MeasureScale,
+    label: &str,
+) {
+    let precision = 10_i32.pow(measure.precision);
+    let number_format = |array: &mut pdf_writer::writers::Array<'_>, factor: f32, unit: &str| {
+        let mut format = array.push().dict();
+        format.pair(Name(b"Type"), Name(b"NumberFormat"));
+        format.pair(Name(b"U"), pdf_writer::TextStr(unit));
+        format.pair(Name(b"C"), factor);
+        format.pair(Name(b"F"), Name(b"D"));
+        format.pair(Name(b"D"), precision);
+    };
 
-    while idx < pdf_bytes.len() && pdf_bytes[idx].is_ascii_whitespace() {
-        idx += 1;
+    let mut dict = annot_dict.insert(Name(b"Measure")).dict();
+    dict.pair(Name(b"Type"), Name(b"Measure"));
+    dict.pair(Name(b"Subtype"), Name(b"RL"));
+    dict.pair(Name(b"R"), pdf_writer::TextStr(&measure.ratio));
+    number_format(
+        &mut dict.insert(Name(b"X")).array(),
+        measure.units_per_point,
+        &measure.unit,
+    );
+    number_format(&mut dict.insert(Name(b"D")).array(), 1.0, &measure.unit);
+    number_format(
+        &mut dict.insert(Name(b"A")).array(),
+        1.0,
+        &format!("sq {}", measure.unit),
+    );
+    dict.finish();
+
+    annot_dict.pair(Name(b"Cap"), true);
+    if let Some(rich_text) = sanitize_rich_text(label.to_string()) {
+        annot_dict.pair(Name(b"RC"), pdf_writer::TextStr(&rich_text));
     }
+}
 
-    let start = idx;
-    while idx < pdf_bytes.len() && pdf_bytes[idx].is_ascii_digit() {
-        idx += 1;
+/// Write the rich text (`/RC`) and default style (`/DS`) of a markup annotation.
+fn write_rich_text(
+    annot_dict: &mut pdf_writer::writers::Annotation<'_>,
+    rich_text: Option<&str>,
+    default_style: Option<&str>,
+) {
+    if let Some(rich_text) = rich_text {
+        annot_dict.pair(Name(b"RC"), pdf_writer::TextStr(rich_text));
     }
+    if let Some(default_style) = default_style {
+        annot_dict.pair(Name(b"DS"), pdf_writer::TextStr(default_style));
+    }
+}
 
-    if start == idx {
+/// Reduce a rich text fragment to the plain text written to `/Contents`.
+fn rich_text_to_plain(rich_text: &str) -> String {
+    let mut plain = String::new();
+    let mut rest = rich_text;
+
+    while let Some(start) = rest.find('<') {
+        plain.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim().to_ascii_lowercase();
+        let block_end = ["/p", "/div", "br"].iter().any(|t| {
+            tag.strip_prefix(t)
+                .is_some_and(|r| r.is_empty() || r.starts_with([' ', '/']))
+        });
+        if block_end && !plain.is_empty() && !plain.ends_with('\n') {
+            plain.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    plain.push_str(rest);
+
+    plain
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .trim_end()
+        .to_string()
+}
+
+/// Wrap a rich text fragment into the XHTML `<body>` root required for `/RC`.
+///
+/// Text without any markup is escaped and placed in a paragraph, so that
+/// stray `<` and `&` characters do not produce malformed XML.
+fn sanitize_rich_text(rich_text: String) -> Option<String> {
+    let trimmed = rich_text.trim();
+    if trimmed.is_empty() {
         return None;
     }
 
-    std::str::from_utf8(&pdf_bytes[start..idx])
-        .ok()?
-        .parse()
-        .ok()
-}
+    if trimmed.starts_with("<?xml") || trimmed.starts_with("<body") {
+        return Some(trimmed.to_string());
+    }
 
-/// Find the position of a byte pattern in a byte slice.
-fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
-    haystack.windows(needle.len()).position(|w| w == needle)
+    let fragment = if trimmed.starts_with('<') && trimmed.ends_with('>') {
+        trimmed.to_string()
+    } else {
+        let escaped = trimmed
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        format!("<p>{escaped}</p>")
+    };
+
+    Some(format!(
+        "<?xml version=\"1.0\"?><body xmlns=\"http://www.w3.org/1999/xhtml\" \
+         xmlns:xfa=\"http://www.xfa.org/schema/xfa-data/1.0/\" \
+         xfa:APIVersion=\"Acrobat:7.0.0\" xfa:spec=\"2.0.2\">{fragment}</body>"
+    ))
 }
 
 /// Write an annotation dictionary to a chunk.
@@ -587,11 +2745,12 @@ fn write_annotation_dict(
     chunk: &mut Chunk,
     annot_ref: Ref,
     annot: &Annotation,
-    ap_stream_ref: Ref,
-    has_appearance: bool,
+    appearance: &AppearanceRefs,
+    extra_appearances: &[(&[u8], Ref)],
     page_refs: &[Ref],
     page_ref: Ref,
     field_ref: Option<Ref>,
+    file_ref: Option<Ref>,
 ) {
     enum PendingFieldWrite {
         Text {
@@ -609,24 +2768,37 @@ fn write_annotation_dict(
             tooltip: Option<String>,
             required: bool,
         },
+        CheckBox {
+            field_ref: Ref,
+            field_name: String,
+            state: String,
+            flags: u32,
+        },
+        Choice {
+            field_ref: Ref,
+            field_name: String,
+            options: Vec<String>,
+            selected: Option<String>,
+            flags: u32,
+        },
     }
 
     let mut pending_field_write = None;
     let mut annot_dict = chunk.annotation(annot_ref);
     let base = annot.base();
+    let rect = appearance_rotation(annot).map_or_else(|| annotation_rect(annot), |(_, r)| r);
 
-    annot_dict.rect(Rect::new(
-        base.rect[0],
-        base.rect[1],
-        base.rect[2],
-        base.rect[3],
-    ));
+    annot_dict.rect(Rect::new(rect[0], rect[1], rect[2], rect[3]));
     annot_dict.flags(pdf_writer::types::AnnotationFlags::from_bits_truncate(
         base.flags,
     ));
 
     if let Some(color) = &base.color {
-        annot_dict.color_rgb(color.r, color.g, color.b);
+        match *color {
+            AnnotColor::Gray(g) => annot_dict.color_gray(g),
+            AnnotColor::Rgb(r, g, b) => annot_dict.color_rgb(r, g, b),
+            AnnotColor::Cmyk(c, m, y, k) => annot_dict.color_cmyk(c, m, y, k),
+        };
     }
 
     if let Some(author) = &base.author {
@@ -637,17 +2809,60 @@ fn write_annotation_dict(
         annot_dict.contents(pdf_writer::TextStr(contents));
     }
 
-    if let Some(modified) = &base.modified {
-        annot_dict.pair(Name(b"M"), pdf_writer::TextStr(modified));
+    if let Some(modified) = base.modified.as_deref().and_then(normalize_date) {
+        annot_dict.pair(Name(b"M"), pdf_writer::TextStr(&modified));
+    }
+
+    if let Some(created) = base.created.as_deref().and_then(normalize_date) {
+        annot_dict.pair(Name(b"CreationDate"), pdf_writer::TextStr(&created));
+    }
+
+    if let Some(name) = &base.name {
+        annot_dict.pair(Name(b"NM"), pdf_writer::TextStr(name));
+    }
+
+    if let Some(subject) = &base.subject {
+        annot_dict.pair(Name(b"Subj"), pdf_writer::TextStr(subject));
+    }
+
+    if let Some(intent) = base.intent.as_deref().or_else(|| default_intent(annot)) {
+        annot_dict.pair(Name(b"IT"), Name(intent.as_bytes()));
     }
+
     annot_dict.pair(Name(b"P"), page_ref);
 
     if base.opacity < 1.0 {
         annot_dict.pair(Name(b"CA"), base.opacity);
     }
 
-    if has_appearance {
-        annot_dict.appearance().normal().stream(ap_stream_ref);
+    match appearance {
+        AppearanceRefs::None => {}
+        AppearanceRefs::Stream(ap_stream_ref) => {
+            let mut ap = annot_dict.insert(Name(b"AP")).dict();
+            ap.pair(Name(b"N"), *ap_stream_ref);
+            for (key, stream_ref) in extra_appearances {
+                ap.pair(Name(key), *stream_ref);
+            }
+            ap.finish();
+        }
+        AppearanceRefs::States { states, current } => {
+            let mut ap = annot_dict.insert(Name(b"AP")).dict();
+            let mut normal = ap.insert(Name(b"N")).dict();
+            for (name, state_ref) in states {
+                normal.pair(Name(name.as_bytes()), *state_ref);
+            }
+            normal.finish();
+            // The same down and rollover appearance is used for every state.
+            for (key, stream_ref) in extra_appearances {
+                let mut entry = ap.insert(Name(key)).dict();
+                for (name, _) in states {
+                    entry.pair(Name(name.as_bytes()), *stream_ref);
+                }
+                entry.finish();
+            }
+            ap.finish();
+            annot_dict.pair(Name(b"AS"), Name(current.as_bytes()));
+        }
     }
 
     match annot {
@@ -681,8 +2896,31 @@ fn write_annotation_dict(
                 Name(b"DA"),
                 pdf_writer::Str(ft.default_appearance.as_bytes()),
             );
-            if base.contents.is_none() && !ft.text.is_empty() {
-                annot_dict.contents(pdf_writer::TextStr(&ft.text));
+            if base.contents.is_none() {
+                if !ft.text.is_empty() {
+                    annot_dict.contents(pdf_writer::TextStr(&ft.text));
+                } else if let Some(rich_text) = &ft.rich_text {
+                    annot_dict.contents(pdf_writer::TextStr(&rich_text_to_plain(rich_text)));
+                }
+            }
+            write_rich_text(
+                &mut annot_dict,
+                ft.rich_text.as_deref(),
+                ft.default_style.as_deref(),
+            );
+            if let Some(callout) = &ft.callout_line {
+                annot_dict
+                    .insert(Name(b"CL"))
+                    .array()
+                    .items(callout.iter().flatten().copied());
+                annot_dict.pair(Name(b"LE"), Name(CALLOUT_LINE_ENDING.pdf_name()));
+                // The text box within the rect that was grown for the callout.
+                annot_dict.insert(Name(b"RD")).array().items([
+                    base.rect[0] - rect[0],
+                    base.rect[1] - rect[1],
+                    rect[2] - base.rect[2],
+                    rect[3] - base.rect[3],
+                ]);
             }
         }
         Annotation::Ink(ink) => {
@@ -696,7 +2934,7 @@ fn write_annotation_dict(
                 }
             }
             ink_list_arr.finish();
-            annot_dict.border_style().width(ink.line_width);
+            write_stroke_border(&mut annot_dict, ink.line_width, ink.dash.as_deref());
         }
         Annotation::Square(shape) => {
             annot_dict.subtype(pdf_writer::types::AnnotationType::Square);
@@ -704,9 +2942,9 @@ fn write_annotation_dict(
                 annot_dict
                     .insert(Name(b"IC"))
                     .array()
-                    .items([ic.r, ic.g, ic.b]);
+                    .items(ic.components());
             }
-            annot_dict.border_style().width(shape.line_width);
+            write_shape_border(&mut annot_dict, shape);
         }
         Annotation::Circle(shape) => {
             annot_dict.subtype(pdf_writer::types::AnnotationType::Circle);
@@ -714,14 +2952,47 @@ fn write_annotation_dict(
                 annot_dict
                     .insert(Name(b"IC"))
                     .array()
-                    .items([ic.r, ic.g, ic.b]);
+                    .items(ic.components());
             }
-            annot_dict.border_style().width(shape.line_width);
+            write_shape_border(&mut annot_dict, shape);
         }
         Annotation::Line(line) => {
             annot_dict.subtype(pdf_writer::types::AnnotationType::Line);
             annot_dict.line_to(line.start[0], line.start[1], line.end[0], line.end[1]);
-            annot_dict.border_style().width(line.line_width);
+            write_stroke_border(&mut annot_dict, line.line_width, line.dash.as_deref());
+            if line.start_style != LineEnding::None || line.end_style != LineEnding::None {
+                annot_dict.insert(Name(b"LE")).array().items([
+                    Name(line.start_style.pdf_name()),
+                    Name(line.end_style.pdf_name()),
+                ]);
+            }
+            if let Some(measure) = &line.measure
+                && let Some((label, _)) = measure_label(line)
+            {
+                if base.contents.is_none() {
+                    annot_dict.contents(pdf_writer::TextStr(&label));
+                }
+                write_measure(&mut annot_dict, measure, &label);
+            }
+        }
+        Annotation::Polygon(poly) | Annotation::PolyLine(poly) => {
+            let subtype = if matches!(annot, Annotation::Polygon(_)) {
+                Name(b"Polygon")
+            } else {
+                Name(b"PolyLine")
+            };
+            annot_dict.pair(Name(b"Subtype"), subtype);
+            annot_dict
+                .insert(Name(b"Vertices"))
+                .array()
+                .items(poly.vertices.iter().flatten().copied());
+            if let Some(ic) = &poly.interior_color {
+                annot_dict
+                    .insert(Name(b"IC"))
+                    .array()
+                    .items(ic.components());
+            }
+            annot_dict.border_style().width(poly.line_width);
         }
         Annotation::Text(text) => {
             annot_dict.subtype(pdf_writer::types::AnnotationType::Text);
@@ -738,6 +3009,16 @@ fn write_annotation_dict(
                 _ => pdf_writer::types::AnnotationIcon::Note,
             };
             annot_dict.icon(icon);
+            if base.contents.is_none()
+                && let Some(rich_text) = &text.rich_text
+            {
+                annot_dict.contents(pdf_writer::TextStr(&rich_text_to_plain(rich_text)));
+            }
+            write_rich_text(
+                &mut annot_dict,
+                text.rich_text.as_deref(),
+                text.default_style.as_deref(),
+            );
         }
         Annotation::Link(link) => {
             annot_dict.subtype(pdf_writer::types::AnnotationType::Link);
@@ -754,6 +3035,39 @@ fn write_annotation_dict(
                 dest.finish();
             }
         }
+        Annotation::Stamp(_) => {
+            annot_dict.pair(Name(b"Subtype"), Name(b"Stamp"));
+        }
+        Annotation::FileAttachment(attachment) => {
+            annot_dict.pair(Name(b"Subtype"), Name(b"FileAttachment"));
+            annot_dict.pair(Name(b"Name"), Name(attachment.icon.as_bytes()));
+            let mut file_spec = annot_dict.insert(Name(b"FS")).dict();
+            file_spec.pair(Name(b"Type"), Name(b"Filespec"));
+            file_spec.pair(
+                Name(b"F"),
+                pdf_writer::Str(&encode_win_ansi(&attachment.file_name)),
+            );
+            file_spec.pair(Name(b"UF"), pdf_writer::TextStr(&attachment.file_name));
+            if let Some(file_ref) = file_ref {
+                file_spec
+                    .insert(Name(b"EF"))
+                    .dict()
+                    .pair(Name(b"F"), file_ref);
+            }
+            file_spec.finish();
+        }
+        Annotation::Redact(r) => {
+            annot_dict.pair(Name(b"Subtype"), Name(b"Redact"));
+            if !r.quad_points.is_empty() {
+                annot_dict.quad_points(r.quad_points.iter().copied());
+            }
+            if let Some(ic) = &r.overlay_color {
+                annot_dict
+                    .insert(Name(b"IC"))
+                    .array()
+                    .items(ic.components());
+            }
+        }
         Annotation::TextField(field) => {
             annot_dict.pair(Name(b"Subtype"), Name(b"Widget"));
             if let Some(field_ref) = field_ref {
@@ -764,6 +3078,13 @@ fn write_annotation_dict(
                 annot_dict.pair(Name(b"MaxLen"), max_len as i32);
             }
 
+            let quadding = match field.alignment {
+                TextAlign::Left => 0_i32,
+                TextAlign::Center => 1,
+                TextAlign::Right => 2,
+            };
+            annot_dict.pair(Name(b"Q"), quadding);
+
             if let Some(field_ref) = field_ref {
                 let mut flags = 0_u32;
                 if field.read_only {
@@ -801,6 +3122,54 @@ fn write_annotation_dict(
                 });
             }
         }
+        Annotation::CheckBox(field) => {
+            annot_dict.pair(Name(b"Subtype"), Name(b"Widget"));
+            if let Some(field_ref) = field_ref {
+                annot_dict.pair(Name(b"Parent"), field_ref);
+            }
+
+            if let Some(field_ref) = field_ref {
+                let mut flags = 0_u32;
+                if field.read_only {
+                    flags |= 1;
+                }
+                if field.required {
+                    flags |= 1 << 1;
+                }
+                pending_field_write = Some(PendingFieldWrite::CheckBox {
+                    field_ref,
+                    field_name: field.field_name.clone(),
+                    state: field.state_name().to_string(),
+                    flags,
+                });
+            }
+        }
+        Annotation::Choice(field) => {
+            annot_dict.pair(Name(b"Subtype"), Name(b"Widget"));
+            if let Some(field_ref) = field_ref {
+                annot_dict.pair(Name(b"Parent"), field_ref);
+            }
+
+            if let Some(field_ref) = field_ref {
+                let mut flags = 0_u32;
+                if field.read_only {
+                    flags |= 1;
+                }
+                if field.required {
+                    flags |= 1 << 1;
+                }
+                if field.combo {
+                    flags |= 1 << 17;
+                }
+                pending_field_write = Some(PendingFieldWrite::Choice {
+                    field_ref,
+                    field_name: field.field_name.clone(),
+                    options: field.options.clone(),
+                    selected: field.selected.clone(),
+                    flags,
+                });
+            }
+        }
     }
 
     annot_dict.finish();
@@ -857,6 +3226,51 @@ fn write_annotation_dict(
                 }
                 field_dict.finish();
             }
+            PendingFieldWrite::CheckBox {
+                field_ref,
+                field_name,
+                state,
+                flags,
+            } => {
+                let mut field_dict = chunk.indirect(field_ref).dict();
+                field_dict.pair(Name(b"FT"), Name(b"Btn"));
+                field_dict.pair(Name(b"T"), pdf_writer::TextStr(&field_name));
+                let mut kids = field_dict.insert(Name(b"Kids")).array();
+                kids.item(annot_ref);
+                kids.finish();
+                // Must match the widget's `/AS` entry.
+                field_dict.pair(Name(b"V"), Name(state.as_bytes()));
+                if flags != 0 {
+                    field_dict.pair(Name(b"Ff"), flags as i32);
+                }
+                field_dict.finish();
+            }
+            PendingFieldWrite::Choice {
+                field_ref,
+                field_name,
+                options,
+                selected,
+                flags,
+            } => {
+                let mut field_dict = chunk.indirect(field_ref).dict();
+                field_dict.pair(Name(b"FT"), Name(b"Ch"));
+                field_dict.pair(Name(b"T"), pdf_writer::TextStr(&field_name));
+                let mut kids = field_dict.insert(Name(b"Kids")).array();
+                kids.item(annot_ref);
+                kids.finish();
+                field_dict
+                    .insert(Name(b"Opt"))
+                    .array()
+                    .items(options.iter().map(|o| pdf_writer::TextStr(o)));
+                if let Some(selected) = &selected {
+                    field_dict.pair(Name(b"V"), pdf_writer::TextStr(selected));
+                    field_dict.pair(Name(b"DV"), pdf_writer::TextStr(selected));
+                }
+                if flags != 0 {
+                    field_dict.pair(Name(b"Ff"), flags as i32);
+                }
+                field_dict.finish();
+            }
         }
     }
 }
@@ -880,6 +3294,9 @@ fn merge_page_annotations(
     merged
 }
 
+/// The largest number of decimal places a measured length is displayed with.
+const MAX_MEASURE_PRECISION: u32 = 6;
+
 fn sanitize_annotation(annotation: &Annotation) -> Annotation {
     let mut sanitized = annotation.clone();
 
@@ -891,6 +3308,9 @@ fn sanitize_annotation(annotation: &Annotation) -> Annotation {
             if rem != 0 {
                 a.quad_points.truncate(a.quad_points.len() - rem);
             }
+            a.quad_points
+                .iter_mut()
+                .for_each(|v| *v = finite_or_zero(*v));
         }
         Annotation::Underline(a) => {
             a.base = base;
@@ -898,6 +3318,9 @@ fn sanitize_annotation(annotation: &Annotation) -> Annotation {
             if rem != 0 {
                 a.quad_points.truncate(a.quad_points.len() - rem);
             }
+            a.quad_points
+                .iter_mut()
+                .for_each(|v| *v = finite_or_zero(*v));
         }
         Annotation::StrikeOut(a) => {
             a.base = base;
@@ -905,6 +3328,9 @@ fn sanitize_annotation(annotation: &Annotation) -> Annotation {
             if rem != 0 {
                 a.quad_points.truncate(a.quad_points.len() - rem);
             }
+            a.quad_points
+                .iter_mut()
+                .for_each(|v| *v = finite_or_zero(*v));
         }
         Annotation::Squiggly(a) => {
             a.base = base;
@@ -912,18 +3338,36 @@ fn sanitize_annotation(annotation: &Annotation) -> Annotation {
             if rem != 0 {
                 a.quad_points.truncate(a.quad_points.len() - rem);
             }
+            a.quad_points
+                .iter_mut()
+                .for_each(|v| *v = finite_or_zero(*v));
         }
         Annotation::FreeText(a) => {
             a.base = base;
             if !a.font_size.is_finite() || a.font_size <= 0.0 {
                 a.font_size = 12.0;
             }
+            if !a.line_spacing.is_finite() || a.line_spacing <= 0.0 {
+                a.line_spacing = LINE_SPACING;
+            }
+            if let Some(callout) = &mut a.callout_line {
+                callout.retain(|p| p[0].is_finite() && p[1].is_finite());
+                callout.truncate(3);
+            }
+            a.callout_line = a.callout_line.take().filter(|c| c.len() >= 2);
+            a.rich_text = a.rich_text.take().and_then(sanitize_rich_text);
+            a.default_style = a.default_style.take().filter(|s| !s.trim().is_empty());
         }
         Annotation::Ink(a) => {
             a.base = base;
             if !a.line_width.is_finite() || a.line_width <= 0.0 {
                 a.line_width = 1.0;
             }
+            a.smoothing = clamp_unit_interval(a.smoothing, 0.0);
+            a.dash = a.dash.take().as_deref().and_then(valid_dash_pattern);
+            for point in a.ink_list.iter_mut().flatten() {
+                *point = point.map(finite_or_zero);
+            }
         }
         Annotation::Square(a) | Annotation::Circle(a) => {
             a.base = base;
@@ -933,15 +3377,57 @@ fn sanitize_annotation(annotation: &Annotation) -> Annotation {
             if let Some(ic) = a.interior_color {
                 a.interior_color = Some(clamp_color(ic));
             }
+            a.border_style = sanitize_border_style(std::mem::take(&mut a.border_style));
+            a.fill_opacity = clamp_unit_interval(a.fill_opacity, 1.0);
         }
         Annotation::Line(a) => {
             a.base = base;
             if !a.line_width.is_finite() || a.line_width <= 0.0 {
                 a.line_width = 1.0;
             }
+            a.start = a.start.map(finite_or_zero);
+            a.end = a.end.map(finite_or_zero);
+            a.dash = a.dash.take().as_deref().and_then(valid_dash_pattern);
+            // Grow the rect so that line endings are not clipped by the `/BBox`.
+            if a.start_style != LineEnding::None || a.end_style != LineEnding::None {
+                let size = line_ending_size(a.line_width);
+                let rect = &mut a.base.rect;
+                rect[0] = rect[0].min(a.start[0].min(a.end[0]) - size);
+                rect[1] = rect[1].min(a.start[1].min(a.end[1]) - size);
+                rect[2] = rect[2].max(a.start[0].max(a.end[0]) + size);
+                rect[3] = rect[3].max(a.start[1].max(a.end[1]) + size);
+            }
+            if let Some(measure) = &mut a.measure {
+                if !measure.units_per_point.is_finite() || measure.units_per_point <= 0.0 {
+                    measure.units_per_point = 1.0;
+                }
+                measure.precision = measure.precision.min(MAX_MEASURE_PRECISION);
+            }
+            // Grow the rect to include the displayed length.
+            if let Some((_, label)) = measure_label(a) {
+                let rect = &mut a.base.rect;
+                *rect = [
+                    rect[0].min(label[0]),
+                    rect[1].min(label[1]),
+                    rect[2].max(label[2]),
+                    rect[3].max(label[3]),
+                ];
+            }
+        }
+        Annotation::Polygon(a) | Annotation::PolyLine(a) => {
+            a.base = base;
+            if !a.line_width.is_finite() || a.line_width <= 0.0 {
+                a.line_width = 1.0;
+            }
+            if let Some(ic) = a.interior_color {
+                a.interior_color = Some(clamp_color(ic));
+            }
+            a.vertices.retain(|v| v[0].is_finite() && v[1].is_finite());
         }
         Annotation::Text(a) => {
             a.base = base;
+            a.rich_text = a.rich_text.take().and_then(sanitize_rich_text);
+            a.default_style = a.default_style.take().filter(|s| !s.trim().is_empty());
         }
         Annotation::Link(a) => {
             a.base = base;
@@ -957,11 +3443,159 @@ fn sanitize_annotation(annotation: &Annotation) -> Annotation {
             a.base = base;
             a.field_name = a.field_name.trim().to_string();
         }
+        Annotation::Stamp(a) => {
+            a.base = base;
+        }
+        Annotation::FileAttachment(a) => {
+            a.base = base;
+            if !matches!(
+                a.icon.as_str(),
+                "PushPin" | "Paperclip" | "Graph" | "Tag" | "GraphPushPin" | "PaperclipTag"
+            ) {
+                a.icon = "PushPin".to_string();
+            }
+        }
+        Annotation::Redact(a) => {
+            a.base = base;
+            let rem = a.quad_points.len() % 8;
+            if rem != 0 {
+                a.quad_points.truncate(a.quad_points.len() - rem);
+            }
+            a.quad_points
+                .iter_mut()
+                .for_each(|v| *v = finite_or_zero(*v));
+            if let Some(ic) = a.overlay_color {
+                a.overlay_color = Some(clamp_color(ic));
+            }
+        }
+        Annotation::CheckBox(a) => {
+            a.base = base;
+            a.field_name = a.field_name.trim().to_string();
+            // `Off` is reserved for the unchecked state.
+            a.export_value = a.export_value.trim().to_string();
+            if a.export_value.is_empty() || a.export_value == "Off" {
+                a.export_value = "Yes".to_string();
+            }
+        }
+        Annotation::Choice(a) => {
+            a.base = base;
+            a.field_name = a.field_name.trim().to_string();
+            // Without the `Edit` flag, only one of the options can be selected.
+            if a.selected.as_ref().is_some_and(|s| !a.options.contains(s)) {
+                a.selected = None;
+            }
+        }
     }
 
     sanitized
 }
 
+/// Drop invalid dash lengths and clamp the cloud intensity to the range
+/// allowed by the specification, falling back to a solid border.
+fn sanitize_border_style(style: BorderStyle) -> BorderStyle {
+    match style {
+        BorderStyle::Dashed(dashes) => {
+            valid_dash_pattern(&dashes).map_or(BorderStyle::Solid, BorderStyle::Dashed)
+        }
+        BorderStyle::Cloudy(intensity) if intensity.is_finite() && intensity > 0.0 => {
+            BorderStyle::Cloudy(intensity.min(2.0))
+        }
+        BorderStyle::Cloudy(_) | BorderStyle::Solid => BorderStyle::Solid,
+    }
+}
+
+/// Check a date string before writing it. Valid PDF dates are written as they
+/// are, while malformed ones are replaced with their canonical form if they
+/// can be understood and left out otherwise.
+fn normalize_date(date: &str) -> Option<String> {
+    if PdfDate::is_valid(date) {
+        return Some(date.to_string());
+    }
+
+    match PdfDate::parse_lenient(date) {
+        Some(parsed) => {
+            warn!("normalized malformed date {date:?}");
+            Some(parsed.to_string())
+        }
+        None => {
+            warn!("dropped malformed date {date:?}");
+            None
+        }
+    }
+}
+
+/// Derive a name in the format of a UUID (version 8) for the `/NM` entry of
+/// an annotation that doesn't have one.
+///
+/// The name is a hash of the page index, the position of the annotation among
+/// the ones added to that page, its object number and its content. Saving the
+/// same annotations again therefore produces the same bytes, while the names
+/// of different annotations still differ.
+fn annotation_name(page_idx: usize, position: usize, annot_ref: Ref, annot: &Annotation) -> String {
+    use core::fmt::{self, Write as _};
+
+    /// Two FNV-1a hashes with different offset bases, which unlike the
+    /// hashers of the standard library are stable across platforms and
+    /// versions.
+    struct NameHasher([u64; 2]);
+
+    impl fmt::Write for NameHasher {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            for hash in &mut self.0 {
+                for byte in s.bytes() {
+                    *hash = (*hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    // Hash the debug representation, so that large embedded files and images
+    // don't need to be formatted into a string first.
+    let mut hasher = NameHasher([0xcbf2_9ce4_8422_2325, 0x6c62_272e_07bb_0142]);
+    let _ = write!(
+        hasher,
+        "{page_idx} {position} {} {annot:?}",
+        annot_ref.get()
+    );
+    let bits = (u128::from(hasher.0[0]) << 64) | u128::from(hasher.0[1]);
+
+    // Set the version (8) and variant (RFC 9562) bits.
+    let bits = (bits & !(0xF << 76) & !(0x3 << 62)) | (0x8 << 76) | (0x2 << 62);
+    let hex = format!("{bits:032x}");
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// The intent written for an annotation that doesn't set one explicitly.
+fn default_intent(annot: &Annotation) -> Option<&'static str> {
+    let is_arrow = |ending: LineEnding| {
+        matches!(
+            ending,
+            LineEnding::OpenArrow
+                | LineEnding::ClosedArrow
+                | LineEnding::ROpenArrow
+                | LineEnding::RClosedArrow
+        )
+    };
+
+    match annot {
+        Annotation::FreeText(a) if a.callout_line.is_some() => Some("FreeTextCallout"),
+        Annotation::Line(a) if a.measure.is_some() => Some("LineDimension"),
+        Annotation::Line(a) if is_arrow(a.start_style) || is_arrow(a.end_style) => {
+            Some("LineArrow")
+        }
+        _ => None,
+    }
+}
+
 fn sanitize_annotation_base(mut base: AnnotationBase) -> AnnotationBase {
     base.rect = normalize_rect(base.rect);
     base.opacity = clamp_unit_interval(base.opacity, 1.0);
@@ -970,11 +3604,18 @@ fn sanitize_annotation_base(mut base: AnnotationBase) -> AnnotationBase {
 }
 
 fn clamp_color(color: AnnotColor) -> AnnotColor {
-    AnnotColor {
-        r: clamp_unit_interval(color.r, 0.0),
-        g: clamp_unit_interval(color.g, 0.0),
-        b: clamp_unit_interval(color.b, 0.0),
-    }
+    color.map(|c| clamp_unit_interval(c, 0.0))
+}
+
+/// Replace `NaN` and infinite coordinates with zero.
+fn finite_or_zero(value: f32) -> f32 {
+    if value.is_finite() { value } else { 0.0 }
+}
+
+/// Whether the rect of an annotation encloses no area, in which case it
+/// cannot be displayed.
+fn is_degenerate_rect(rect: [f32; 4]) -> bool {
+    rect[2] <= rect[0] || rect[3] <= rect[1]
 }
 
 fn clamp_unit_interval(value: f32, default: f32) -> f32 {
@@ -985,6 +3626,58 @@ fn clamp_unit_interval(value: f32, default: f32) -> f32 {
     value.clamp(0.0, 1.0)
 }
 
+/// The intersection of two normalized rects, which may be empty.
+fn intersect_rects(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    let x0 = a[0].max(b[0]);
+    let y0 = a[1].max(b[1]);
+    [x0, y0, a[2].min(b[2]).max(x0), a[3].min(b[3]).max(y0)]
+}
+
+/// Clamp the rect and all points of an annotation, like quad points, ink
+/// paths and vertices, to `bounds`, or return `None` if nothing of it is left.
+fn clamp_annotation(annot: &Annotation, bounds: [f32; 4]) -> Option<Annotation> {
+    let mut annot = annot.clone();
+    let base = annot.base_mut();
+    base.rect = intersect_rects(normalize_rect(base.rect), bounds);
+    if is_degenerate_rect(base.rect) {
+        return None;
+    }
+
+    let quad_points = match &mut annot {
+        Annotation::Highlight(a) => Some(&mut a.quad_points),
+        Annotation::Underline(a) => Some(&mut a.quad_points),
+        Annotation::StrikeOut(a) => Some(&mut a.quad_points),
+        Annotation::Squiggly(a) => Some(&mut a.quad_points),
+        Annotation::Redact(a) => Some(&mut a.quad_points),
+        _ => None,
+    };
+    if let Some(quad_points) = quad_points {
+        for point in quad_points.chunks_exact_mut(2) {
+            point[0] = point[0].clamp(bounds[0], bounds[2]);
+            point[1] = point[1].clamp(bounds[1], bounds[3]);
+        }
+    }
+
+    let clamp_point = |point: &mut [f32; 2]| {
+        point[0] = point[0].clamp(bounds[0], bounds[2]);
+        point[1] = point[1].clamp(bounds[1], bounds[3]);
+    };
+    match &mut annot {
+        Annotation::Ink(a) => a.ink_list.iter_mut().flatten().for_each(clamp_point),
+        Annotation::Line(a) => {
+            clamp_point(&mut a.start);
+            clamp_point(&mut a.end);
+        }
+        Annotation::Polygon(a) | Annotation::PolyLine(a) => {
+            a.vertices.iter_mut().for_each(clamp_point);
+        }
+        Annotation::FreeText(a) => a.callout_line.iter_mut().flatten().for_each(clamp_point),
+        _ => {}
+    }
+
+    Some(annot)
+}
+
 fn normalize_rect(rect: [f32; 4]) -> [f32; 4] {
     let mut x0 = rect[0];
     let mut y0 = rect[1];
@@ -0,0 +1,308 @@
+//! Integration tests for applying redactions.
+
+use hayro_annot::*;
+use hayro_interpret::{InterpreterSettings, extract_text_spans};
+use hayro_syntax::Pdf;
+
+/// Create a single-page PDF with a line of text at y = 700 and one at y = 600.
+fn create_pdf_with_text() -> Vec<u8> {
+    use pdf_writer::{Finish, Name, Pdf, Rect, Ref};
+
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let font_id = Ref::new(4);
+    let content_id = Ref::new(5);
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+
+    let mut page = pdf.page(page_id);
+    page.parent(page_tree_id);
+    page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+    page.contents(content_id);
+    page.resources().fonts().pair(Name(b"F1"), font_id);
+    page.finish();
+
+    pdf.type1_font(font_id).base_font(Name(b"Helvetica"));
+    pdf.stream(
+        content_id,
+        b"BT /F1 12 Tf 72 700 Td (Secret) Tj ET\nBT /F1 12 Tf 72 600 Td (Public) Tj ET",
+    );
+    pdf.finish()
+}
+
+fn page_text(pdf_data: Vec<u8>) -> Vec<(String, [f32; 4])> {
+    let pdf = Pdf::new(pdf_data).expect("redacted PDF should parse");
+
+    extract_text_spans(&pdf.pages()[0], &InterpreterSettings::default())
+        .into_iter()
+        .map(|span| (span.text, span.bbox))
+        .collect()
+}
+
+#[test]
+fn redaction_removes_text_under_rect() {
+    let original = create_pdf_with_text();
+    let redacted = apply_redactions(&original, &[(0, vec![[70.0, 695.0, 120.0, 715.0]])])
+        .expect("redaction should succeed");
+
+    let spans = page_text(redacted);
+
+    assert!(
+        spans
+            .iter()
+            .all(|(_, bbox)| bbox[3] < 695.0 || bbox[1] > 715.0),
+        "no text should remain in the redacted area: {spans:?}"
+    );
+    assert!(spans.iter().all(|(text, _)| !text.contains("Secret")));
+    assert!(spans.iter().any(|(text, _)| text.contains("Public")));
+}
+
+#[test]
+fn redaction_without_rects_keeps_text() {
+    let original = create_pdf_with_text();
+    let output = apply_redactions(&original, &[(0, vec![])]).expect("redaction should succeed");
+
+    let spans = page_text(output);
+
+    assert!(spans.iter().any(|(text, _)| text.contains("Secret")));
+    assert!(spans.iter().any(|(text, _)| text.contains("Public")));
+}
+
+#[test]
+fn redaction_rejects_invalid_page_index() {
+    let original = create_pdf_with_text();
+    let result = apply_redactions(&original, &[(3, vec![[0.0, 0.0, 10.0, 10.0]])]);
+
+    assert!(matches!(result, Err(SaveError::InvalidPageIndex(3))));
+}
+
+#[test]
+fn redaction_annotations_are_applied() {
+    let original = create_pdf_with_text();
+    let marked = save_annotations(
+        &original,
+        &[(
+            0,
+            vec![Annotation::Redact(RedactAnnot {
+                base: AnnotationBase {
+                    rect: [70.0, 695.0, 120.0, 715.0],
+                    ..Default::default()
+                },
+                quad_points: vec![70.0, 715.0, 120.0, 715.0, 70.0, 695.0, 120.0, 695.0],
+                overlay_color: Some(AnnotColor::new(1.0, 0.0, 0.0)),
+            })],
+        )],
+    )
+    .expect("saving the redaction annotation should succeed");
+
+    let marked_pdf = Pdf::new(marked.clone()).expect("marked PDF should parse");
+    assert!(matches!(
+        read_annotations(&marked_pdf, 0).as_slice(),
+        [Annotation::Redact(_)]
+    ));
+    // Marking content for redaction doesn't remove it yet.
+    assert!(
+        page_text(marked.clone())
+            .iter()
+            .any(|(text, _)| text.contains("Secret"))
+    );
+
+    let redacted = apply_redaction_annotations(&marked).expect("redaction should succeed");
+    let redacted_pdf = Pdf::new(redacted.clone()).expect("redacted PDF should parse");
+    assert!(read_annotations(&redacted_pdf, 0).is_empty());
+
+    let content = redacted_pdf.pages()[0].page_stream().unwrap().to_vec();
+    let content = String::from_utf8_lossy(&content);
+    assert!(content.contains("1 0 0 rg 70 695 50 20 re f"), "{content}");
+
+    let spans = page_text(redacted);
+    assert!(spans.iter().all(|(text, _)| !text.contains("Secret")));
+    assert!(spans.iter().any(|(text, _)| text.contains("Public")));
+}
+
+#[test]
+fn redaction_keeps_annotations_outside_redacted_area() {
+    let original = create_pdf_with_text();
+    let annotated = save_annotations(
+        &original,
+        &[(
+            0,
+            vec![
+                HighlightAnnot::builder([70.0, 695.0, 120.0, 715.0])
+                    .contents("Secret")
+                    .build(),
+                ShapeAnnot::square_builder([300.0, 300.0, 400.0, 400.0]).build(),
+            ],
+        )],
+    )
+    .expect("saving the annotations should succeed");
+
+    let redacted = apply_redactions(&annotated, &[(0, vec![[70.0, 695.0, 120.0, 715.0]])])
+        .expect("redaction should succeed");
+    let redacted_pdf = Pdf::new(redacted.clone()).expect("redacted PDF should parse");
+
+    let annotations = read_annotations(&redacted_pdf, 0);
+    assert!(
+        matches!(
+            annotations.as_slice(),
+            [Annotation::Square(square)] if square.base.rect == [300.0, 300.0, 400.0, 400.0]
+        ),
+        "{annotations:?}"
+    );
+    // The removed highlight isn't written at all.
+    assert!(!String::from_utf8_lossy(&redacted).contains("(Secret)"));
+}
+
+/// Create a single-page PDF showing `content` with `font` as the font `/F1`.
+fn create_pdf_with_font(
+    content: &[u8],
+    font: impl FnOnce(&mut pdf_writer::Pdf, pdf_writer::Ref),
+) -> Vec<u8> {
+    use pdf_writer::{Finish, Name, Pdf, Rect, Ref};
+
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let font_id = Ref::new(4);
+    let content_id = Ref::new(5);
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+
+    let mut page = pdf.page(page_id);
+    page.parent(page_tree_id);
+    page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+    page.contents(content_id);
+    page.resources().fonts().pair(Name(b"F1"), font_id);
+    page.finish();
+
+    font(&mut pdf, font_id);
+    pdf.stream(content_id, content);
+    pdf.finish()
+}
+
+fn page_content(pdf_data: Vec<u8>) -> String {
+    let pdf = Pdf::new(pdf_data).expect("redacted PDF should parse");
+    let content = pdf.pages()[0].page_stream().unwrap().to_vec();
+
+    String::from_utf8_lossy(&content).into_owned()
+}
+
+#[test]
+fn redaction_keeps_standard_font_text_around_rect() {
+    let original = create_pdf_with_font(
+        b"BT /F1 12 Tf 72 700 Td (Keep SECRET keep) Tj ET",
+        |pdf, id| {
+            pdf.type1_font(id).base_font(pdf_writer::Name(b"Helvetica"));
+        },
+    );
+    // With Helvetica's widths, "SECRET" spans x = 103.356 to 152.028.
+    let redacted = apply_redactions(&original, &[(0, vec![[105.0, 695.0, 150.0, 715.0]])])
+        .expect("redaction should succeed");
+
+    let content = page_content(redacted.clone());
+    assert!(content.contains("[<4B65657020> "), "{content}");
+    assert!(content.contains(" <206B656570> ] TJ"), "{content}");
+
+    let spans = page_text(redacted);
+    let text: String = spans
+        .iter()
+        .flat_map(|(text, _)| text.chars())
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    assert_eq!(text, "Keepkeep");
+
+    // The text after the removed word stays where it was.
+    let end = spans
+        .iter()
+        .map(|(_, bbox)| bbox[2])
+        .fold(f32::MIN, f32::max);
+    assert!((end - 181.38).abs() < 1.0, "{spans:?}");
+}
+
+#[test]
+fn redaction_reads_one_byte_codes_of_type0_fonts() {
+    use pdf_writer::types::{CidFontType, FontFlags, SystemInfo};
+    use pdf_writer::{Finish, Name, Rect, Ref, Str};
+
+    let cmap = b"/CIDInit /ProcSet findresource begin
+12 dict begin
+begincmap
+/CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> def
+/CMapName /OneByte def
+/CMapType 1 def
+1 begincodespacerange
+<00> <FF>
+endcodespacerange
+1 begincidrange
+<00> <FF> 0
+endcidrange
+endcmap
+CMapName currentdict /CMap defineresource pop
+end
+end";
+
+    // Every glyph is 5 units wide, so "ABCDEF" is laid out from x = 100 in
+    // steps of 5.
+    let original = create_pdf_with_font(
+        b"BT /F1 10 Tf 100 700 Td <414243444546> Tj ET",
+        |pdf, id| {
+            let cid_id = Ref::new(6);
+            let descriptor_id = Ref::new(7);
+            let file_id = Ref::new(8);
+            let cmap_id = Ref::new(9);
+
+            pdf.type0_font(id)
+                .base_font(Name(b"TestFont"))
+                .descendant_font(cid_id)
+                .pair(Name(b"Encoding"), cmap_id);
+
+            let mut cid = pdf.cid_font(cid_id);
+            cid.subtype(CidFontType::Type2);
+            cid.base_font(Name(b"TestFont"));
+            cid.system_info(SystemInfo {
+                registry: Str(b"Adobe"),
+                ordering: Str(b"Identity"),
+                supplement: 0,
+            });
+            cid.font_descriptor(descriptor_id);
+            cid.default_width(500.0);
+            cid.finish();
+
+            pdf.font_descriptor(descriptor_id)
+                .name(Name(b"TestFont"))
+                .flags(FontFlags::SYMBOLIC)
+                .bbox(Rect::new(0.0, -200.0, 1000.0, 800.0))
+                .italic_angle(0.0)
+                .ascent(800.0)
+                .descent(-200.0)
+                .cap_height(700.0)
+                .stem_v(80.0)
+                .font_file2(file_id);
+
+            let font_data = include_bytes!("assets/test-font.ttf");
+            pdf.stream(file_id, font_data)
+                .pair(Name(b"Length1"), font_data.len() as i32);
+
+            let mut cmap_stream = pdf.stream(cmap_id, cmap);
+            cmap_stream.pair(Name(b"Type"), Name(b"CMap"));
+            cmap_stream.pair(Name(b"CMapName"), Name(b"OneByte"));
+            cmap_stream.finish();
+        },
+    );
+    let redacted = apply_redactions(&original, &[(0, vec![[111.0, 695.0, 119.0, 715.0]])])
+        .expect("redaction should succeed");
+
+    // Only "C" and "D" are removed, each replaced by its advance.
+    let content = page_content(redacted);
+    assert!(content.contains("[<4142> "), "{content}");
+    assert!(content.contains(" <4546> ] TJ"), "{content}");
+    assert!(
+        !content.contains("43") && !content.contains("44"),
+        "{content}"
+    );
+}
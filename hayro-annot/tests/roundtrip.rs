@@ -163,6 +163,8 @@ fn roundtrip_ink_annotation() {
             [190.0, 490.0],
         ]],
         line_width: 2.0,
+        smoothing: 0.0,
+        dash: None,
     });
 
     let result = save_annotations(&pdf_data, &[(0, vec![ink])]);
@@ -187,6 +189,9 @@ fn roundtrip_square_annotation() {
         interior_color: Some(AnnotColor::new(0.9, 0.9, 1.0)),
         line_width: 2.0,
         is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
     });
 
     let result = save_annotations(&pdf_data, &[(0, vec![square])]);
@@ -219,6 +224,8 @@ fn roundtrip_multiple_annotations() {
         },
         ink_list: vec![vec![[60.0, 410.0], [100.0, 450.0]]],
         line_width: 2.0,
+        smoothing: 0.0,
+        dash: None,
     });
 
     let square = Annotation::Square(ShapeAnnot {
@@ -230,6 +237,9 @@ fn roundtrip_multiple_annotations() {
         interior_color: None,
         line_width: 1.0,
         is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
     });
 
     let result = save_annotations(&pdf_data, &[(0, vec![highlight, ink, square])]);
@@ -265,6 +275,13 @@ fn roundtrip_freetext_annotation() {
         text: "Hello World".to_string(),
         font_size: 12.0,
         default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+        font_data: None,
+        multiline: false,
+        line_spacing: 1.2,
+        vertical_align: VAlign::Top,
+        callout_line: None,
+        rich_text: None,
+        default_style: None,
     });
 
     let result = save_annotations(&pdf_data, &[(0, vec![freetext])]);
@@ -374,3 +391,539 @@ fn empty_annotations_no_change() {
     let new_pdf = hayro_syntax::Pdf::new(new_pdf_data);
     assert!(new_pdf.is_ok(), "new PDF should be valid");
 }
+
+#[test]
+fn incremental_save_preserves_original_bytes() {
+    let pdf_data = create_pdf_with_nested_page_dict();
+    let highlight = Annotation::Highlight(HighlightAnnot {
+        base: AnnotationBase {
+            rect: [80.0, 680.0, 280.0, 700.0],
+            color: Some(AnnotColor::yellow()),
+            ..Default::default()
+        },
+        quad_points: vec![80.0, 700.0, 280.0, 700.0, 80.0, 680.0, 280.0, 680.0],
+    });
+
+    let new_pdf_data = save_annotations_incremental(&pdf_data, &[(0, vec![highlight])])
+        .expect("incremental save should succeed");
+    assert_startxref_points_to_xref(&new_pdf_data);
+    assert!(
+        new_pdf_data.starts_with(&pdf_data),
+        "original bytes should be kept verbatim"
+    );
+
+    // The page content stream object is byte-identical and is not rewritten.
+    let content_obj = {
+        let start = pdf_data
+            .windows(7)
+            .position(|w| w == b"5 0 obj")
+            .expect("content stream object should exist");
+        let len = pdf_data[start..]
+            .windows(6)
+            .position(|w| w == b"endobj")
+            .expect("content stream object should end")
+            + 6;
+        &pdf_data[start..start + len]
+    };
+    let occurrences = new_pdf_data
+        .windows(content_obj.len())
+        .filter(|w| *w == content_obj)
+        .count();
+    assert_eq!(occurrences, 1);
+
+    let tail = String::from_utf8_lossy(&new_pdf_data[pdf_data.len()..]);
+    assert!(
+        tail.contains("/Prev "),
+        "trailer should point to the old xref"
+    );
+    assert!(
+        tail.contains("3 0 obj"),
+        "page dictionary should be patched"
+    );
+
+    let new_pdf = hayro_syntax::Pdf::new(new_pdf_data).expect("new PDF should be valid");
+    let page = &new_pdf.pages()[0];
+    let annots = page
+        .raw()
+        .get::<hayro_syntax::object::Array<'_>>(hayro_syntax::object::dict::keys::ANNOTS as &[u8])
+        .expect("page should have /Annots array");
+    assert_eq!(annots.raw_iter().count(), 1);
+    assert!(
+        page.raw()
+            .get::<hayro_syntax::object::Dict<'_>>(b"Resources" as &[u8])
+            .is_some(),
+        "existing page entries should be kept"
+    );
+}
+
+#[test]
+fn incremental_save_keeps_all_trailer_entries() {
+    let pdf_data = create_blank_pdf();
+    let trailer_pos = pdf_data
+        .windows(9)
+        .rposition(|w| w == b"trailer\n<")
+        .expect("blank pdf should have a trailer");
+    // The trailer comes after the xref table, so inserting into it keeps all
+    // offsets valid.
+    let mut with_custom_entry = pdf_data[..trailer_pos + 8].to_vec();
+    with_custom_entry.extend_from_slice(b"<< /Custom [(kept) 1 0 R]");
+    with_custom_entry.extend_from_slice(&pdf_data[trailer_pos + 10..]);
+
+    let highlight = Annotation::Highlight(HighlightAnnot::from_rect(
+        [80.0, 680.0, 280.0, 700.0],
+        AnnotColor::yellow(),
+    ));
+    let new_pdf_data = save_annotations_incremental(&with_custom_entry, &[(0, vec![highlight])])
+        .expect("incremental save should succeed");
+
+    let tail = String::from_utf8_lossy(&new_pdf_data[with_custom_entry.len()..]);
+    assert!(tail.contains("/Custom [(kept) 1 0 R]"), "{tail}");
+    assert_eq!(tail.matches("/Size ").count(), 1);
+    assert_eq!(tail.matches("/Root ").count(), 1);
+    hayro_syntax::Pdf::new(new_pdf_data).expect("new PDF should be valid");
+}
+
+#[test]
+fn incremental_save_of_xref_stream_document_writes_xref_stream() {
+    let pdf_data = save_annotations_with_options(
+        &create_blank_pdf(),
+        &[],
+        &WriteOptions {
+            use_object_streams: true,
+            ..Default::default()
+        },
+    )
+    .expect("save should succeed");
+
+    let highlight = Annotation::Highlight(HighlightAnnot::from_rect(
+        [80.0, 680.0, 280.0, 700.0],
+        AnnotColor::yellow(),
+    ));
+    let new_pdf_data = save_annotations_incremental(&pdf_data, &[(0, vec![highlight])])
+        .expect("incremental save should succeed");
+    assert!(new_pdf_data.starts_with(&pdf_data));
+
+    let tail = String::from_utf8_lossy(&new_pdf_data[pdf_data.len()..]);
+    assert!(tail.contains("/Type /XRef"), "{tail}");
+    assert!(tail.contains("/Prev "));
+    assert!(!tail.contains("\nxref\n"));
+    assert!(!tail.contains("trailer"));
+
+    let new_pdf = hayro_syntax::Pdf::new(new_pdf_data).expect("new PDF should be valid");
+    assert_eq!(read_annotations(&new_pdf, 0).len(), 1);
+}
+
+#[test]
+fn streamed_incremental_save_matches_in_memory_save() {
+    let pdf_data = create_pdf_with_nested_page_dict();
+    let page_annotations = [(
+        0,
+        vec![Annotation::Highlight(HighlightAnnot {
+            base: AnnotationBase {
+                rect: [80.0, 680.0, 280.0, 700.0],
+                color: Some(AnnotColor::yellow()),
+                ..Default::default()
+            },
+            quad_points: vec![80.0, 700.0, 280.0, 700.0, 80.0, 680.0, 280.0, 680.0],
+        })],
+    )];
+
+    let in_memory = save_annotations_incremental(&pdf_data, &page_annotations)
+        .expect("incremental save should succeed");
+    let mut cursor = std::io::Cursor::new(Vec::new());
+    save_annotations_to(&mut cursor, &pdf_data, &page_annotations)
+        .expect("streamed save should succeed");
+
+    let streamed = cursor.into_inner();
+    assert_eq!(streamed, in_memory);
+    assert_startxref_points_to_xref(&streamed);
+
+    // Nothing is written if the annotations are invalid.
+    let mut output = Vec::new();
+    let result = save_annotations_to(&mut output, &pdf_data, &[(5, Vec::new())]);
+    assert!(matches!(result, Err(SaveError::InvalidPageIndex(5))));
+    assert!(output.is_empty());
+}
+
+#[test]
+fn remove_annotations_frees_removed_object() {
+    let highlight = Annotation::Highlight(HighlightAnnot {
+        base: AnnotationBase {
+            rect: [80.0, 680.0, 280.0, 700.0],
+            color: Some(AnnotColor::yellow()),
+            ..Default::default()
+        },
+        quad_points: vec![80.0, 700.0, 280.0, 700.0, 80.0, 680.0, 280.0, 680.0],
+    });
+    let square = Annotation::Square(ShapeAnnot {
+        base: AnnotationBase {
+            rect: [100.0, 100.0, 200.0, 200.0],
+            color: Some(AnnotColor::red()),
+            ..Default::default()
+        },
+        interior_color: None,
+        line_width: 1.0,
+        is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
+    });
+
+    let pdf_data = save_annotations_incremental(
+        &create_pdf_with_nested_page_dict(),
+        &[(0, vec![highlight, square])],
+    )
+    .expect("incremental save should succeed");
+    let highlight_id = {
+        let pdf = hayro_syntax::Pdf::new(pdf_data.clone()).expect("PDF should be valid");
+        let annots = pdf.pages()[0]
+            .raw()
+            .get::<hayro_syntax::object::Array<'_>>(
+                hayro_syntax::object::dict::keys::ANNOTS as &[u8],
+            )
+            .expect("page should have /Annots array");
+        annots
+            .raw_iter()
+            .next()
+            .and_then(|entry| entry.as_obj_ref())
+            .expect("annotation should be indirect")
+    };
+
+    let new_pdf_data = remove_annotations(&pdf_data, &[(0, 0)]).expect("removing should succeed");
+    assert_startxref_points_to_xref(&new_pdf_data);
+    assert!(new_pdf_data.starts_with(&pdf_data));
+
+    let tail = String::from_utf8_lossy(&new_pdf_data[pdf_data.len()..]);
+    assert!(
+        tail.contains(&format!("{:010} 65535 f", highlight_id.obj_number)),
+        "free list should start at the removed annotation"
+    );
+    assert!(
+        tail.contains(&format!("0000000000 {:05} f", highlight_id.gen_number + 1)),
+        "removed annotation should be marked free"
+    );
+
+    let new_pdf = hayro_syntax::Pdf::new(new_pdf_data.clone()).expect("new PDF should be valid");
+    let annotations = read_annotations(&new_pdf, 0);
+    assert_eq!(annotations.len(), 1);
+    assert!(matches!(annotations[0], Annotation::Square(_)));
+
+    assert!(matches!(
+        remove_annotations(&new_pdf_data, &[(0, 1)]),
+        Err(SaveError::InvalidAnnotationIndex(0, 1))
+    ));
+}
+
+#[test]
+fn remove_annotations_unlinks_fields_and_replies() {
+    use hayro_syntax::object::dict::keys::{ACRO_FORM, FIELDS, KIDS};
+    use hayro_syntax::object::{Array, Dict, ObjectIdentifier};
+    use pdf_writer::types::AnnotationType;
+    use pdf_writer::{Finish, Name, Pdf, Rect, Ref, TextStr};
+
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let acro_form_id = Ref::new(4);
+    let field_id = Ref::new(5);
+    let kid_ids = [Ref::new(6), Ref::new(7)];
+    let merged_id = Ref::new(8);
+    let note_id = Ref::new(9);
+    let reply_id = Ref::new(10);
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id)
+        .pages(page_tree_id)
+        .pair(Name(b"AcroForm"), acro_form_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+
+    let mut page = pdf.page(page_id);
+    page.parent(page_tree_id);
+    page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+    page.annotations([kid_ids[0], kid_ids[1], merged_id, note_id, reply_id]);
+    page.finish();
+
+    pdf.indirect(acro_form_id)
+        .dict()
+        .insert(Name(b"Fields"))
+        .array()
+        .items([field_id, merged_id]);
+
+    // A text field with two widgets, and one whose widget is merged into it.
+    let mut field = pdf.indirect(field_id).dict();
+    field.pair(Name(b"FT"), Name(b"Tx"));
+    field.pair(Name(b"T"), TextStr("name"));
+    field.insert(Name(b"Kids")).array().items(kid_ids);
+    field.finish();
+
+    for (i, kid_id) in kid_ids.into_iter().enumerate() {
+        let y = 700.0 - 50.0 * i as f32;
+        let mut widget = pdf.annotation(kid_id);
+        widget.pair(Name(b"Subtype"), Name(b"Widget"));
+        widget.rect(Rect::new(100.0, y, 300.0, y + 20.0));
+        widget.pair(Name(b"Parent"), field_id);
+        widget.finish();
+    }
+
+    let mut merged = pdf.annotation(merged_id);
+    merged.pair(Name(b"Subtype"), Name(b"Widget"));
+    merged.rect(Rect::new(100.0, 500.0, 300.0, 520.0));
+    merged.pair(Name(b"FT"), Name(b"Tx"));
+    merged.pair(Name(b"T"), TextStr("merged"));
+    merged.finish();
+
+    let mut note = pdf.annotation(note_id);
+    note.subtype(AnnotationType::Text);
+    note.rect(Rect::new(10.0, 10.0, 34.0, 34.0));
+    note.contents(TextStr("Please check this"));
+    note.finish();
+
+    let mut reply = pdf.annotation(reply_id);
+    reply.subtype(AnnotationType::Text);
+    reply.rect(Rect::new(10.0, 10.0, 34.0, 34.0));
+    reply.contents(TextStr("Done"));
+    reply.pair(Name(b"IRT"), note_id);
+    reply.finish();
+
+    // Remove the first widget of the field, the merged field and the note.
+    let pdf_data = pdf.finish();
+    let new_pdf_data =
+        remove_annotations(&pdf_data, &[(0, 0), (0, 2), (0, 3)]).expect("removing should succeed");
+    assert_startxref_points_to_xref(&new_pdf_data);
+
+    let new_pdf = hayro_syntax::Pdf::new(new_pdf_data).expect("new PDF should be valid");
+    let annotations = read_annotations(&new_pdf, 0);
+    assert!(
+        matches!(
+            annotations.as_slice(),
+            [Annotation::TextField(_), Annotation::Text(_)]
+        ),
+        "{annotations:?}"
+    );
+
+    let obj_numbers = |array: Array<'_>| {
+        array
+            .raw_iter()
+            .filter_map(|entry| entry.as_obj_ref())
+            .map(|r| r.obj_number)
+            .collect::<Vec<_>>()
+    };
+    let xref = new_pdf.xref();
+    let fields = xref
+        .get::<Dict<'_>>(xref.root_id())
+        .and_then(|catalog| catalog.get::<Dict<'_>>(ACRO_FORM))
+        .and_then(|acro_form| acro_form.get::<Array<'_>>(FIELDS))
+        .expect("form should have /Fields");
+    assert_eq!(obj_numbers(fields), vec![field_id.get()]);
+    let kids = xref
+        .get::<Dict<'_>>(ObjectIdentifier::new(field_id.get(), 0))
+        .and_then(|field| field.get::<Array<'_>>(KIDS))
+        .expect("field should have /Kids");
+    assert_eq!(obj_numbers(kids), vec![kid_ids[1].get()]);
+
+    // The reply is now a standalone comment.
+    let comments = extract_comments(&new_pdf);
+    assert_eq!(comments.len(), 1, "{comments:?}");
+    assert_eq!(comments[0].contents.as_deref(), Some("Done"));
+    assert_eq!(comments[0].in_reply_to, None);
+}
+
+#[test]
+fn incremental_save_adds_fields_to_existing_form() {
+    use hayro_syntax::object::dict::keys::{ACRO_FORM, FIELDS};
+    use hayro_syntax::object::{Array, Dict};
+    use pdf_writer::{Finish, Name, Pdf, Rect, Ref, TextStr};
+
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let widget_id = Ref::new(4);
+    let acro_form_id = Ref::new(5);
+
+    // The existing form dictionary is either an object of its own or inlined
+    // into the catalog.
+    for inline_form in [false, true] {
+        let mut pdf = Pdf::new();
+        let mut catalog = pdf.catalog(catalog_id);
+        catalog.pages(page_tree_id);
+        if inline_form {
+            catalog
+                .insert(Name(b"AcroForm"))
+                .dict()
+                .insert(Name(b"Fields"))
+                .array()
+                .item(widget_id);
+        } else {
+            catalog.pair(Name(b"AcroForm"), acro_form_id);
+        }
+        catalog.finish();
+        pdf.pages(page_tree_id).kids([page_id]).count(1);
+
+        let mut page = pdf.page(page_id);
+        page.parent(page_tree_id);
+        page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+        page.annotations([widget_id]);
+        page.finish();
+
+        let mut widget = pdf.annotation(widget_id);
+        widget.pair(Name(b"Subtype"), Name(b"Widget"));
+        widget.rect(Rect::new(100.0, 700.0, 300.0, 720.0));
+        widget.pair(Name(b"FT"), Name(b"Tx"));
+        widget.pair(Name(b"T"), TextStr("existing"));
+        widget.finish();
+
+        if !inline_form {
+            pdf.indirect(acro_form_id)
+                .dict()
+                .insert(Name(b"Fields"))
+                .array()
+                .item(widget_id);
+        }
+
+        let pdf_data = pdf.finish();
+        let new_pdf_data = save_annotations_incremental(
+            &pdf_data,
+            &[(
+                0,
+                vec![TextFieldAnnot::builder([100.0, 600.0, 300.0, 620.0], "added").build()],
+            )],
+        )
+        .expect("incremental save should succeed");
+        assert_startxref_points_to_xref(&new_pdf_data);
+
+        let new_pdf = hayro_syntax::Pdf::new(new_pdf_data).expect("new PDF should be valid");
+        let annotations = read_annotations(&new_pdf, 0);
+        assert!(
+            matches!(
+                annotations.as_slice(),
+                [Annotation::TextField(existing), Annotation::TextField(added)]
+                    if existing.field_name == "existing" && added.field_name == "added"
+            ),
+            "{annotations:?}"
+        );
+
+        let xref = new_pdf.xref();
+        let catalog = xref
+            .get::<Dict<'_>>(xref.root_id())
+            .expect("catalog should exist");
+        if !inline_form {
+            assert_eq!(
+                catalog.get_ref(ACRO_FORM).map(|r| r.obj_number),
+                Some(acro_form_id.get()),
+                "the existing form should be kept"
+            );
+        }
+        let fields = catalog
+            .get::<Dict<'_>>(ACRO_FORM)
+            .and_then(|acro_form| acro_form.get::<Array<'_>>(FIELDS))
+            .expect("form should have /Fields");
+        let field_numbers = fields
+            .raw_iter()
+            .filter_map(|entry| entry.as_obj_ref())
+            .map(|r| r.obj_number)
+            .collect::<Vec<_>>();
+        assert_eq!(field_numbers.len(), 2, "{field_numbers:?}");
+        assert_eq!(field_numbers[0], widget_id.get());
+    }
+}
+
+#[test]
+fn extracted_pages_keep_boxes_rotation_and_annotations() {
+    use hayro_syntax::page::Rotation;
+    use pdf_writer::{Finish, Pdf, Rect, Ref};
+
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_ids = [Ref::new(3), Ref::new(4), Ref::new(5)];
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids(page_ids).count(3);
+    for (i, page_id) in page_ids.into_iter().enumerate() {
+        let mut page = pdf.page(page_id);
+        page.parent(page_tree_id);
+        page.media_box(Rect::new(0.0, 0.0, 300.0 + 100.0 * i as f32, 400.0));
+        if i == 2 {
+            page.crop_box(Rect::new(10.0, 20.0, 310.0, 380.0));
+            page.rotate(90);
+        }
+        page.resources();
+        page.finish();
+    }
+    let pdf_data = pdf.finish();
+
+    let square = Annotation::Square(ShapeAnnot {
+        base: AnnotationBase {
+            rect: [50.0, 50.0, 100.0, 100.0],
+            ..Default::default()
+        },
+        interior_color: None,
+        line_width: 1.0,
+        is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
+    });
+    let pdf_data = save_annotations(&pdf_data, &[(2, vec![square])]).unwrap();
+
+    let extracted = extract_pages(&pdf_data, &[2, 0]).expect("extraction should succeed");
+    let extracted = hayro_syntax::Pdf::new(extracted).expect("extracted PDF should be valid");
+    let pages = extracted.pages();
+    assert_eq!(pages.len(), 2);
+
+    let rotated = &pages[0];
+    assert_eq!(rotated.media_box().x1, 500.0);
+    assert_eq!(
+        [
+            rotated.crop_box().x0,
+            rotated.crop_box().y0,
+            rotated.crop_box().x1,
+            rotated.crop_box().y1
+        ],
+        [10.0, 20.0, 310.0, 380.0]
+    );
+    assert!(matches!(rotated.rotation(), Rotation::Horizontal));
+    assert_eq!(read_annotations(&extracted, 0).len(), 1);
+
+    assert_eq!(pages[1].media_box().x1, 300.0);
+    assert!(matches!(pages[1].rotation(), Rotation::None));
+    assert!(read_annotations(&extracted, 1).is_empty());
+
+    assert!(matches!(
+        extract_pages(&pdf_data, &[3]),
+        Err(SaveError::InvalidPageIndex(3))
+    ));
+}
+
+#[test]
+fn decrypted_pdf_opens_without_password() {
+    let encrypted = load_fixture_pdf("hayro-tests/pdfs/custom/password_encrypted_aes_128.pdf");
+    assert!(hayro_syntax::Pdf::new(encrypted.clone()).is_err());
+    assert!(matches!(
+        decrypt_pdf(&encrypted, "wrong"),
+        Err(SaveError::EncryptedPdf)
+    ));
+
+    let decrypted = decrypt_pdf(&encrypted, "testpw").expect("decryption should succeed");
+    let original = hayro_syntax::Pdf::new_with_password(encrypted, "testpw").unwrap();
+    let pdf = hayro_syntax::Pdf::new(decrypted.clone()).expect("decrypted PDF should open");
+    assert_eq!(pdf.pages().len(), original.pages().len());
+    assert_eq!(
+        pdf.pages()[0].page_stream(),
+        original.pages()[0].page_stream()
+    );
+
+    // Annotations can be added to the decrypted copy.
+    let annot = Annotation::Highlight(HighlightAnnot {
+        base: AnnotationBase {
+            rect: [0.0, 0.0, 100.0, 100.0],
+            ..Default::default()
+        },
+        quad_points: vec![],
+    });
+    assert!(save_annotations(&decrypted, &[(0, vec![annot])]).is_ok());
+
+    // Unencrypted documents are left alone.
+    let blank = create_blank_pdf();
+    assert_eq!(decrypt_pdf(&blank, "testpw").unwrap(), blank);
+}
@@ -2,7 +2,10 @@
 
 use hayro_annot::*;
 use hayro_syntax::object::dict::keys::ANNOTS;
-use hayro_syntax::object::{Array, Dict, MaybeRef, Name, ObjRef, Object, String as PdfString};
+use hayro_syntax::object::{
+    Array, Dict, MaybeRef, Name, ObjRef, Object, Stream, String as PdfString,
+};
+use std::sync::Arc;
 
 fn create_blank_pdf(page_count: usize) -> Vec<u8> {
     use pdf_writer::{Finish, Pdf, Rect, Ref};
@@ -36,6 +39,43 @@ fn create_blank_pdf(page_count: usize) -> Vec<u8> {
     pdf.finish()
 }
 
+/// Create a PDF with one page for each of the given content streams.
+fn create_pdf_with_contents(contents: &[&str]) -> Vec<u8> {
+    use pdf_writer::{Finish, Pdf, Rect, Ref};
+
+    let page_tree_id = Ref::new(2);
+    let page_refs = (0..contents.len())
+        .map(|i| Ref::new(3 + 2 * i as i32))
+        .collect::<Vec<_>>();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(Ref::new(1)).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .kids(page_refs.iter().copied())
+        .count(contents.len() as i32);
+
+    for (page_ref, content) in page_refs.into_iter().zip(contents) {
+        let content_ref = Ref::new(page_ref.get() + 1);
+        let mut page = pdf.page(page_ref);
+        page.parent(page_tree_id);
+        page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+        page.resources();
+        page.contents(content_ref);
+        page.finish();
+        pdf.stream(content_ref, content.as_bytes());
+    }
+
+    pdf.finish()
+}
+
+/// The decoded content stream of each page.
+fn page_contents(pdf: &hayro_syntax::Pdf) -> Vec<String> {
+    pdf.pages()
+        .iter()
+        .map(|page| String::from_utf8_lossy(page.page_stream().unwrap_or_default()).into_owned())
+        .collect()
+}
+
 fn save_and_parse(
     input_pdf: &[u8],
     page_annotations: &[(usize, Vec<Annotation>)],
@@ -83,6 +123,117 @@ fn markup_quadpoints_are_truncated_to_multiple_of_eight() {
     );
 }
 
+#[test]
+fn highlight_from_rect_writes_quad_in_acrobat_order() {
+    let input = create_blank_pdf(1);
+    let highlight = HighlightAnnot::from_rect([100.0, 30.0, 10.0, 10.0], AnnotColor::yellow());
+    assert_eq!(highlight.base.rect, [10.0, 10.0, 100.0, 30.0]);
+
+    let pdf = save_and_parse(&input, &[(0, vec![Annotation::Highlight(highlight)])]);
+    let quad = page_annotation_dicts(&pdf, 0)[0]
+        .get::<Vec<f32>>(b"QuadPoints".as_ref())
+        .expect("highlight should have /QuadPoints");
+    // Upper-left, upper-right, lower-left, lower-right.
+    assert_eq!(quad, [10.0, 30.0, 100.0, 30.0, 10.0, 10.0, 100.0, 10.0]);
+}
+
+#[test]
+fn non_finite_geometry_is_replaced_and_empty_rects_are_skipped() {
+    let input = create_blank_pdf(1);
+    let ink = Annotation::Ink(InkAnnot {
+        base: AnnotationBase {
+            rect: [50.0, 50.0, 180.0, 180.0],
+            ..Default::default()
+        },
+        ink_list: vec![vec![[60.0, f32::NAN], [f32::INFINITY, 80.0], [120.0, 90.0]]],
+        line_width: f32::NAN,
+        smoothing: 0.0,
+        dash: None,
+    });
+    let square = Annotation::Square(ShapeAnnot {
+        base: AnnotationBase {
+            rect: [f32::NAN, f32::NAN, f32::NAN, f32::NAN],
+            ..Default::default()
+        },
+        interior_color: None,
+        line_width: 1.0,
+        is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
+    });
+    let highlight = Annotation::Highlight(HighlightAnnot {
+        base: AnnotationBase {
+            rect: [10.0, 10.0, 100.0, 30.0],
+            ..Default::default()
+        },
+        quad_points: vec![
+            10.0,
+            30.0,
+            f32::NAN,
+            30.0,
+            10.0,
+            10.0,
+            100.0,
+            f32::NEG_INFINITY,
+        ],
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![ink, square, highlight])]);
+    let dicts = page_annotation_dicts(&pdf, 0);
+    let subtypes: Vec<_> = dicts.iter().map(subtype_bytes).collect();
+    assert_eq!(subtypes, [b"Ink".to_vec(), b"Highlight".to_vec()]);
+
+    let ink_list = dicts[0]
+        .get::<Vec<Vec<f32>>>(b"InkList".as_ref())
+        .expect("ink should have /InkList");
+    assert_eq!(ink_list, [vec![60.0, 0.0, 0.0, 80.0, 120.0, 90.0]]);
+    let content = dicts[0]
+        .get::<Dict<'_>>(b"AP".as_ref())
+        .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+        .expect("ink should have an appearance stream")
+        .decoded()
+        .expect("appearance should decode");
+    assert!(!String::from_utf8_lossy(&content).contains("NaN"));
+
+    let quad = dicts[1]
+        .get::<Vec<f32>>(b"QuadPoints".as_ref())
+        .expect("highlight should have /QuadPoints");
+    assert_eq!(quad, [10.0, 30.0, 0.0, 30.0, 10.0, 10.0, 100.0, 0.0]);
+}
+
+#[test]
+fn annotation_flags_combine_into_bitmask() {
+    let flags = AnnotationFlags::PRINT | AnnotationFlags::LOCKED;
+    assert_eq!(flags.bits(), 132);
+    assert_eq!(AnnotationFlags::default(), AnnotationFlags::PRINT);
+    assert_eq!(
+        AnnotationBase::default().annotation_flags(),
+        AnnotationFlags::PRINT
+    );
+
+    let input = create_blank_pdf(1);
+    let text = Annotation::Text(TextAnnot {
+        base: AnnotationBase {
+            rect: [10.0, 10.0, 30.0, 30.0],
+            ..Default::default()
+        }
+        .with_flags(flags),
+        open: false,
+        icon: "Note".to_string(),
+        rich_text: None,
+        default_style: None,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![text])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+    assert_eq!(dict.get::<u32>(b"F".as_ref()), Some(132));
+    assert_eq!(
+        read_annotations(&pdf, 0)[0].base().annotation_flags(),
+        AnnotationFlags::PRINT | AnnotationFlags::LOCKED
+    );
+}
+
 #[test]
 fn markup_subtypes_are_written() {
     let input = create_blank_pdf(1);
@@ -119,6 +270,52 @@ fn markup_subtypes_are_written() {
     assert!(subtypes.iter().any(|s| s.as_slice() == b"Squiggly"));
 }
 
+#[test]
+fn annotations_write_creation_date_and_unique_name() {
+    let input = create_blank_pdf(1);
+    let text = |name: Option<&str>| {
+        Annotation::Text(TextAnnot {
+            base: AnnotationBase {
+                rect: [40.0, 40.0, 60.0, 60.0],
+                created: Some("D:20240102030405Z".to_string()),
+                name: name.map(str::to_string),
+                ..Default::default()
+            },
+            icon: "Note".to_string(),
+            rich_text: None,
+            default_style: None,
+            open: false,
+        })
+    };
+
+    let page_annotations = [(0, vec![text(Some("my-note")), text(None), text(None)])];
+    let output = save_annotations(&input, &page_annotations).expect("save should succeed");
+    // Generated names are derived from the annotations, so saving is reproducible.
+    assert_eq!(
+        save_annotations(&input, &page_annotations).expect("save should succeed"),
+        output
+    );
+    let pdf = hayro_syntax::Pdf::new(output).expect("output should parse");
+    let dicts = page_annotation_dicts(&pdf, 0);
+    let string = |dict: &Dict<'_>, key: &[u8]| {
+        let value = dict
+            .get::<PdfString>(key)
+            .unwrap_or_else(|| panic!("annotation should have /{}", String::from_utf8_lossy(key)));
+        String::from_utf8(value.as_bytes().to_vec()).unwrap()
+    };
+
+    assert_eq!(string(&dicts[0], b"CreationDate"), "D:20240102030405Z");
+    assert_eq!(string(&dicts[0], b"NM"), "my-note");
+
+    let generated = [string(&dicts[1], b"NM"), string(&dicts[2], b"NM")];
+    assert_ne!(generated[0], generated[1]);
+    for name in &generated {
+        let groups = name.split('-').map(str::len).collect::<Vec<_>>();
+        assert_eq!(groups, vec![8, 4, 4, 4, 12], "{name}");
+        assert!(name.chars().all(|c| c == '-' || c.is_ascii_hexdigit()));
+    }
+}
+
 #[test]
 fn freetext_sets_contents_fallback_when_missing() {
     let input = create_blank_pdf(1);
@@ -131,6 +328,13 @@ fn freetext_sets_contents_fallback_when_missing() {
         text: "hello free text".to_string(),
         font_size: 12.0,
         default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+        font_data: None,
+        multiline: false,
+        line_spacing: 1.2,
+        vertical_align: VAlign::Top,
+        callout_line: None,
+        rich_text: None,
+        default_style: None,
     });
 
     let pdf = save_and_parse(&input, &[(0, vec![freetext])]);
@@ -148,596 +352,2878 @@ fn freetext_sets_contents_fallback_when_missing() {
 }
 
 #[test]
-fn text_annotation_writes_icon_and_open_flag() {
+fn text_rich_text_writes_rc_ds_and_plain_contents() {
     let input = create_blank_pdf(1);
     let text = Annotation::Text(TextAnnot {
         base: AnnotationBase {
-            rect: [100.0, 100.0, 124.0, 124.0],
+            rect: [40.0, 40.0, 60.0, 60.0],
             ..Default::default()
         },
-        open: true,
-        icon: "Key".to_string(),
+        open: false,
+        icon: "Comment".to_string(),
+        rich_text: Some("<p><b>Bold</b> &amp; (plain)</p>".to_string()),
+        default_style: Some("font: Helvetica 12pt; color: #FF0000".to_string()),
     });
 
     let pdf = save_and_parse(&input, &[(0, vec![text])]);
     let dict = &page_annotation_dicts(&pdf, 0)[0];
-    assert_eq!(subtype_bytes(dict), b"Text");
-    assert_eq!(dict.get::<bool>(b"Open".as_ref()), Some(true));
-    let icon = dict
-        .get::<Name>(b"Name".as_ref())
-        .expect("Text annotation should include /Name icon");
-    assert_eq!(icon.as_ref(), b"Key");
+
+    let contents = dict
+        .get::<PdfString>(b"Contents".as_ref())
+        .expect("rich text should fall back to plain /Contents");
+    assert_eq!(contents.as_bytes(), b"Bold & (plain)");
+
+    let rc = dict.get::<PdfString>(b"RC".as_ref()).expect("missing /RC");
+    let rc = std::str::from_utf8(rc.as_bytes()).unwrap();
+    assert!(rc.contains("<body xmlns=\"http://www.w3.org/1999/xhtml\""));
+    assert!(rc.contains("<p><b>Bold</b> &amp; (plain)</p></body>"));
+    assert_eq!(
+        dict.get::<PdfString>(b"DS".as_ref()).unwrap().as_bytes(),
+        b"font: Helvetica 12pt; color: #FF0000"
+    );
+
+    let Annotation::Text(read) = &read_annotations(&pdf, 0)[0] else {
+        panic!("expected a text annotation");
+    };
+    assert_eq!(read.rich_text.as_deref(), Some(rc));
+    assert_eq!(
+        read.default_style.as_deref(),
+        Some("font: Helvetica 12pt; color: #FF0000")
+    );
 }
 
 #[test]
-fn ink_annotation_writes_inklist_and_border_style_width() {
+fn freetext_with_font_data_embeds_type0_font() {
     let input = create_blank_pdf(1);
-    let ink = Annotation::Ink(InkAnnot {
+    let freetext = Annotation::FreeText(FreeTextAnnot {
         base: AnnotationBase {
-            rect: [50.0, 50.0, 180.0, 180.0],
+            rect: [40.0, 40.0, 200.0, 90.0],
             ..Default::default()
         },
-        ink_list: vec![vec![[60.0, 60.0], [80.0, 80.0], [120.0, 90.0]]],
-        line_width: 2.5,
+        text: "café 日本語".to_string(),
+        font_size: 12.0,
+        default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+        font_data: Some(Arc::new(include_bytes!("assets/test-font.ttf").to_vec())),
+        multiline: false,
+        line_spacing: 1.2,
+        vertical_align: VAlign::Top,
+        callout_line: None,
+        rich_text: None,
+        default_style: None,
     });
 
-    let pdf = save_and_parse(&input, &[(0, vec![ink])]);
+    let pdf = save_and_parse(&input, &[(0, vec![freetext])]);
     let dict = &page_annotation_dicts(&pdf, 0)[0];
-    assert_eq!(subtype_bytes(dict), b"Ink");
-
-    let ink_list = dict
-        .get::<Array<'_>>(b"InkList".as_ref())
-        .expect("Ink annotation should have /InkList");
-    assert_eq!(ink_list.raw_iter().count(), 1);
+    let appearance = dict
+        .get::<Dict<'_>>(b"AP".as_ref())
+        .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+        .expect("FreeText should have a normal appearance");
 
-    let bs = dict
-        .get::<Dict<'_>>(b"BS".as_ref())
-        .expect("Ink should have /BS");
-    assert_eq!(bs.get::<f32>(b"W".as_ref()), Some(2.5));
-}
+    let content = appearance.decoded().expect("appearance should decode");
+    assert!(
+        content.windows(6).any(|w| w == b"/F0 12"),
+        "appearance should select the embedded font"
+    );
 
-#[test]
-fn square_and_circle_write_ic_and_border_style() {
-    let input = create_blank_pdf(1);
-    let square = Annotation::Square(ShapeAnnot {
-        base: AnnotationBase {
-            rect: [10.0, 10.0, 100.0, 100.0],
-            ..Default::default()
-        },
-        interior_color: Some(AnnotColor::new(0.1, 0.2, 0.3)),
-        line_width: 3.0,
-        is_circle: false,
-    });
-    let circle = Annotation::Circle(ShapeAnnot {
-        base: AnnotationBase {
-            rect: [120.0, 120.0, 200.0, 200.0],
-            ..Default::default()
-        },
-        interior_color: Some(AnnotColor::new(0.4, 0.5, 0.6)),
-        line_width: 1.5,
-        is_circle: true,
-    });
+    let font = appearance
+        .dict()
+        .get::<Dict<'_>>(b"Resources".as_ref())
+        .and_then(|r| r.get::<Dict<'_>>(b"Font".as_ref()))
+        .and_then(|f| f.get::<Dict<'_>>(b"F0".as_ref()))
+        .expect("appearance should reference /F0");
+    assert_eq!(
+        font.get::<Name>(b"Subtype".as_ref()).unwrap().as_ref(),
+        b"Type0"
+    );
+    assert_eq!(
+        font.get::<Name>(b"Encoding".as_ref()).unwrap().as_ref(),
+        b"Identity-H"
+    );
 
-    let pdf = save_and_parse(&input, &[(0, vec![square, circle])]);
-    let dicts = page_annotation_dicts(&pdf, 0);
+    let to_unicode = font
+        .get::<Stream<'_>>(b"ToUnicode".as_ref())
+        .expect("font should have a ToUnicode map")
+        .decoded()
+        .unwrap();
+    let to_unicode = std::str::from_utf8(&to_unicode).unwrap();
+    assert!(to_unicode.contains("<00E9>"), "{to_unicode}");
+    assert!(to_unicode.contains("<65E5>"), "{to_unicode}");
 
-    for dict in dicts {
-        assert!(
-            dict.contains_key(b"IC".as_ref()),
-            "shape annotation should include interior color"
-        );
-        let bs = dict
-            .get::<Dict<'_>>(b"BS".as_ref())
-            .expect("shape should have /BS");
-        assert!(
-            bs.get::<f32>(b"W".as_ref()).is_some(),
-            "shape border style should include width"
-        );
-    }
+    let descendant = font
+        .get::<Array<'_>>(b"DescendantFonts".as_ref())
+        .and_then(|a| a.iter::<Dict<'_>>().next())
+        .expect("font should have a descendant CIDFont");
+    let descriptor = descendant
+        .get::<Dict<'_>>(b"FontDescriptor".as_ref())
+        .expect("CIDFont should have a descriptor");
+    assert!(descriptor.contains_key(b"FontFile2".as_ref()));
 }
 
 #[test]
-fn line_annotation_writes_l_and_border_style() {
+fn stamp_embeds_png_as_image_xobject() {
+    let mut png = Vec::new();
+    image::RgbaImage::from_pixel(4, 3, image::Rgba([255, 0, 0, 128]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+
     let input = create_blank_pdf(1);
-    let line = Annotation::Line(LineAnnot {
+    let stamp = Annotation::Stamp(StampAnnot {
         base: AnnotationBase {
-            rect: [50.0, 50.0, 200.0, 200.0],
+            rect: [100.0, 100.0, 180.0, 160.0],
             ..Default::default()
         },
-        start: [60.0, 60.0],
-        end: [190.0, 180.0],
-        line_width: 4.0,
+        image_data: Arc::new(png),
+        format: ImageFormat::Png,
     });
 
-    let pdf = save_and_parse(&input, &[(0, vec![line])]);
+    let pdf = save_and_parse(&input, &[(0, vec![stamp])]);
     let dict = &page_annotation_dicts(&pdf, 0)[0];
-    assert_eq!(subtype_bytes(dict), b"Line");
+    assert_eq!(subtype_bytes(dict), b"Stamp");
+
+    let appearance = dict
+        .get::<Dict<'_>>(b"AP".as_ref())
+        .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+        .expect("stamp should have a normal appearance");
+    let content = appearance.decoded().expect("appearance should decode");
+    let content = std::str::from_utf8(&content).unwrap();
+    assert!(content.contains("80 0 0 60 0 0 cm"), "{content}");
+    assert!(content.contains("/Im0 Do"), "{content}");
+
+    let image = appearance
+        .dict()
+        .get::<Dict<'_>>(b"Resources".as_ref())
+        .and_then(|r| r.get::<Dict<'_>>(b"XObject".as_ref()))
+        .and_then(|x| x.get::<Stream<'_>>(b"Im0".as_ref()))
+        .expect("appearance should reference /Im0");
+    let image_dict = image.dict();
     assert_eq!(
-        dict.get::<Vec<f32>>(b"L".as_ref())
-            .expect("Line annotation should have /L")
-            .len(),
-        4
+        image_dict
+            .get::<Name>(b"Subtype".as_ref())
+            .unwrap()
+            .as_ref(),
+        b"Image"
     );
-    let bs = dict
-        .get::<Dict<'_>>(b"BS".as_ref())
-        .expect("line should have /BS");
-    assert_eq!(bs.get::<f32>(b"W".as_ref()), Some(4.0));
+    assert_eq!(image_dict.get::<i32>(b"Width".as_ref()), Some(4));
+    assert_eq!(image_dict.get::<i32>(b"Height".as_ref()), Some(3));
+    assert_eq!(image_dict.get::<i32>(b"BitsPerComponent".as_ref()), Some(8));
+    assert_eq!(
+        image_dict
+            .get::<Name>(b"ColorSpace".as_ref())
+            .unwrap()
+            .as_ref(),
+        b"DeviceRGB"
+    );
+    assert_eq!(image.decoded().unwrap().len(), 4 * 3 * 3);
+    assert!(image_dict.contains_key(b"SMask".as_ref()));
 }
 
 #[test]
-fn link_uri_annotation_writes_uri_action() {
+fn file_attachment_embeds_file_and_round_trips() {
     let input = create_blank_pdf(1);
-    let link = Annotation::Link(LinkAnnot {
+    let data = b"date,amount\n2024-01-01,42\n".to_vec();
+    let attachment = Annotation::FileAttachment(FileAttachmentAnnot {
         base: AnnotationBase {
-            rect: [10.0, 10.0, 200.0, 30.0],
+            rect: [100.0, 700.0, 120.0, 724.0],
             ..Default::default()
         },
-        uri: Some("https://example.com".to_string()),
-        dest_page: None,
+        file_name: "report.csv".to_string(),
+        data: data.clone(),
+        icon: "Graph".to_string(),
     });
 
-    let pdf = save_and_parse(&input, &[(0, vec![link])]);
+    let pdf = save_and_parse(&input, &[(0, vec![attachment])]);
     let dict = &page_annotation_dicts(&pdf, 0)[0];
-    assert_eq!(subtype_bytes(dict), b"Link");
-
-    let action = dict
-        .get::<Dict<'_>>(b"A".as_ref())
-        .expect("Link should have /A");
+    assert_eq!(subtype_bytes(dict), b"FileAttachment");
     assert_eq!(
-        action
-            .get::<Name>(b"S".as_ref())
-            .expect("action should have /S")
-            .as_ref(),
-        b"URI"
+        dict.get::<Name>(b"Name".as_ref()).unwrap().as_ref(),
+        b"Graph"
     );
+
+    let file_spec = dict
+        .get::<Dict<'_>>(b"FS".as_ref())
+        .expect("annotation should include /FS");
     assert_eq!(
-        action
-            .get::<PdfString>(b"URI".as_ref())
-            .expect("action should have /URI")
+        file_spec
+            .get::<PdfString>(b"F".as_ref())
+            .unwrap()
             .as_bytes(),
-        b"https://example.com"
+        b"report.csv"
+    );
+    let file = file_spec
+        .get::<Dict<'_>>(b"EF".as_ref())
+        .and_then(|ef| ef.get::<Stream<'_>>(b"F".as_ref()))
+        .expect("file specification should embed the file");
+    assert_eq!(
+        file.dict().get::<Name>(b"Type".as_ref()).unwrap().as_ref(),
+        b"EmbeddedFile"
     );
+    assert_eq!(file.decoded().unwrap(), data);
+    assert!(dict.contains_key(b"AP".as_ref()));
+
+    let Annotation::FileAttachment(read) = &read_annotations(&pdf, 0)[0] else {
+        panic!("expected a file attachment");
+    };
+    assert_eq!(read.file_name, "report.csv");
+    assert_eq!(read.data, data);
+    assert_eq!(read.icon, "Graph");
 }
 
 #[test]
-fn link_destination_page_writes_dest_array() {
-    let input = create_blank_pdf(3);
-    let link = Annotation::Link(LinkAnnot {
+fn blend_mode_is_written_as_named_ext_g_state() {
+    let input = create_blank_pdf(1);
+    let highlight = Annotation::Highlight(HighlightAnnot {
         base: AnnotationBase {
-            rect: [10.0, 10.0, 200.0, 30.0],
+            rect: [10.0, 10.0, 100.0, 30.0],
+            color: Some(AnnotColor::yellow()),
             ..Default::default()
         },
-        uri: None,
-        dest_page: Some(2),
+        quad_points: vec![10.0, 30.0, 100.0, 30.0, 10.0, 10.0, 100.0, 10.0],
     });
+    let square = |blend_mode| {
+        Annotation::Square(ShapeAnnot {
+            base: AnnotationBase {
+                rect: [200.0, 200.0, 300.0, 300.0],
+                color: Some(AnnotColor::red()),
+                blend_mode,
+                ..Default::default()
+            },
+            interior_color: None,
+            line_width: 1.0,
+            is_circle: false,
+            border_style: BorderStyle::Solid,
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
+        })
+    };
 
-    let pdf = save_and_parse(&input, &[(0, vec![link])]);
-    let dict = &page_annotation_dicts(&pdf, 0)[0];
-    assert!(
-        dict.contains_key(b"Dest".as_ref()),
-        "Link should include /Dest"
+    let pdf = save_and_parse(
+        &input,
+        &[(
+            0,
+            vec![highlight, square(Some(BlendMode::Screen)), square(None)],
+        )],
     );
+    let dicts = page_annotation_dicts(&pdf, 0);
+    let blend_mode = |dict: &Dict<'_>| {
+        let appearance = dict
+            .get::<Dict<'_>>(b"AP".as_ref())
+            .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+            .expect("annotation should have a normal appearance");
+        let content = appearance.decoded().expect("appearance should decode");
+        let mode = appearance
+            .dict()
+            .get::<Dict<'_>>(b"Resources".as_ref())
+            .and_then(|r| r.get::<Dict<'_>>(b"ExtGState".as_ref()))
+            .and_then(|states| states.get::<Dict<'_>>(b"GS0".as_ref()))
+            .and_then(|state| state.get::<Name>(b"BM".as_ref()))
+            .map(|bm| bm.as_ref().to_vec());
+        assert_eq!(mode.is_some(), content.starts_with(b"/GS0 gs"));
+        mode
+    };
 
-    let dest = dict
-        .get::<Array<'_>>(b"Dest".as_ref())
-        .expect("Link /Dest should be an array");
-    let mut iter = dest.raw_iter();
-
-    let page_ref = iter
-        .next()
-        .and_then(|r| r.as_obj_ref())
-        .expect("first /Dest entry should be a page reference");
-    let fit = iter.next().expect("second /Dest entry should exist");
-    let MaybeRef::NotRef(Object::Name(fit_name)) = fit else {
-        panic!("second /Dest entry should be /Fit");
-    };
-    assert_eq!(fit_name.as_ref(), b"Fit");
+    assert_eq!(blend_mode(&dicts[0]).as_deref(), Some(b"Multiply".as_ref()));
+    assert_eq!(blend_mode(&dicts[1]).as_deref(), Some(b"Screen".as_ref()));
+    assert_eq!(blend_mode(&dicts[2]), None);
 
-    let expected_page_obj_id = pdf.pages()[2]
-        .raw()
-        .obj_id()
-        .expect("page should be an indirect object");
-    assert_eq!(page_ref, ObjRef::from(expected_page_obj_id));
+    let read = read_annotations(&pdf, 0);
+    assert_eq!(read[0].base().blend_mode, Some(BlendMode::Multiply));
+    assert_eq!(read[1].base().blend_mode, Some(BlendMode::Screen));
+    assert_eq!(read[2].base().blend_mode, None);
 }
 
 #[test]
-fn invalid_destination_page_returns_error() {
+fn shape_fill_opacity_is_written_as_non_stroking_alpha() {
     let input = create_blank_pdf(1);
-    let link = Annotation::Link(LinkAnnot {
+    let square = Annotation::Square(ShapeAnnot {
         base: AnnotationBase {
-            rect: [10.0, 10.0, 200.0, 30.0],
+            rect: [200.0, 200.0, 300.0, 300.0],
+            color: Some(AnnotColor::black()),
             ..Default::default()
         },
-        uri: None,
-        dest_page: Some(9),
+        interior_color: Some(AnnotColor::red()),
+        line_width: 2.0,
+        is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 0.25,
+        corner_radius: 0.0,
     });
 
-    let result = save_annotations(&input, &[(0, vec![link])]);
-    assert!(matches!(result, Err(SaveError::InvalidDestinationPage(9))));
+    let pdf = save_and_parse(&input, &[(0, vec![square])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+    // The border stays opaque.
+    assert!(!dict.contains_key(b"CA".as_ref()));
+
+    let appearance = dict
+        .get::<Dict<'_>>(b"AP".as_ref())
+        .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+        .expect("square should have a normal appearance");
+    let content = appearance.decoded().expect("appearance should decode");
+    assert!(content.starts_with(b"/GS0 gs"));
+    let state = appearance
+        .dict()
+        .get::<Dict<'_>>(b"Resources".as_ref())
+        .and_then(|r| r.get::<Dict<'_>>(b"ExtGState".as_ref()))
+        .and_then(|states| states.get::<Dict<'_>>(b"GS0".as_ref()))
+        .expect("appearance should reference /GS0");
+    assert_eq!(state.get::<f32>(b"ca".as_ref()), Some(0.25));
+    assert!(!state.contains_key(b"CA".as_ref()));
+
+    let Annotation::Square(read) = &read_annotations(&pdf, 0)[0] else {
+        panic!("expected a square");
+    };
+    assert_eq!(read.fill_opacity, 0.25);
 }
 
 #[test]
-fn text_field_creation_writes_acroform_and_fields() {
-    let input = create_blank_pdf(1);
-    let text_field = Annotation::TextField(TextFieldAnnot {
+fn page_rotations_replace_existing_rotate() {
+    use pdf_writer::{Finish, Pdf, Rect, Ref};
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(Ref::new(1)).pages(Ref::new(2));
+    pdf.pages(Ref::new(2))
+        .kids([Ref::new(3), Ref::new(4)])
+        .count(2);
+    for page_ref in [Ref::new(3), Ref::new(4)] {
+        let mut page = pdf.page(page_ref);
+        page.parent(Ref::new(2));
+        page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+        page.rotate(90);
+        page.resources();
+        page.finish();
+    }
+    let input = pdf.finish();
+
+    let options = WriteOptions {
+        page_rotations: [(0, -90)].into(),
+        ..Default::default()
+    };
+    let saved = save_annotations_with_options(&input, &[], &options).expect("save should succeed");
+    let parsed = hayro_syntax::Pdf::new(saved).expect("saved PDF should parse");
+    let rotations = parsed
+        .pages()
+        .iter()
+        .map(|page| page.raw().get::<i32>(b"Rotate".as_ref()))
+        .collect::<Vec<_>>();
+    assert_eq!(rotations, [Some(270), Some(90)]);
+
+    let options = WriteOptions {
+        page_rotations: [(2, 90)].into(),
+        ..Default::default()
+    };
+    assert!(matches!(
+        save_annotations_with_options(&input, &[], &options),
+        Err(SaveError::InvalidPageIndex(2))
+    ));
+}
+
+#[test]
+fn page_order_drops_and_reorders_pages() {
+    use pdf_writer::{Finish, Pdf, Rect, Ref};
+
+    let page_refs = [Ref::new(3), Ref::new(4), Ref::new(5)];
+    let mut pdf = Pdf::new();
+    pdf.catalog(Ref::new(1)).pages(Ref::new(2));
+    pdf.pages(Ref::new(2)).kids(page_refs).count(3);
+    for (i, page_ref) in page_refs.into_iter().enumerate() {
+        let content_ref = Ref::new(6 + i as i32);
+        let mut page = pdf.page(page_ref);
+        page.parent(Ref::new(2));
+        page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+        page.resources();
+        page.contents(content_ref);
+        page.finish();
+        pdf.stream(content_ref, format!("0 0 {} 10 re f", i + 1).as_bytes());
+    }
+    let input = pdf.finish();
+
+    let note = Annotation::Text(TextAnnot {
         base: AnnotationBase {
-            rect: [40.0, 40.0, 220.0, 72.0],
+            rect: [10.0, 10.0, 34.0, 34.0],
             ..Default::default()
         },
-        field_name: "customer_name".to_string(),
-        value: Some("Ada".to_string()),
-        default_value: Some("".to_string()),
-        max_len: Some(64),
-        default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
-        read_only: false,
-        required: true,
-        multiline: false,
+        open: false,
+        icon: "Note".to_string(),
     });
+    let options = WriteOptions {
+        page_order: Some(vec![2, 1]),
+        ..Default::default()
+    };
+    let saved = save_annotations_with_options(&input, &[(1, vec![note])], &options)
+        .expect("save should succeed");
+    let parsed = hayro_syntax::Pdf::new(saved).expect("saved PDF should parse");
 
-    let saved = save_annotations(&input, &[(0, vec![text_field])]).expect("save should succeed");
-    let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+    let pages = parsed.pages();
+    assert_eq!(pages.len(), 2);
+    let contents = pages
+        .iter()
+        .map(|page| page.page_stream().unwrap_or_default().to_vec())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        contents,
+        [b"0 0 3 10 re f".to_vec(), b"0 0 2 10 re f".to_vec()]
+    );
 
-    let root = pdf
-        .xref()
-        .get::<Dict<'_>>(pdf.xref().root_id())
-        .expect("catalog should parse");
-    let acro_form = root
-        .get::<Dict<'_>>(b"AcroForm".as_ref())
-        .expect("catalog should include /AcroForm");
-    let fields = acro_form
-        .get::<Array<'_>>(b"Fields".as_ref())
-        .expect("AcroForm should include /Fields");
-    assert_eq!(fields.raw_iter().count(), 1, "expected one field");
+    // The annotation ends up on the second page of the new document.
+    assert!(read_annotations(&parsed, 0).is_empty());
+    assert_eq!(read_annotations(&parsed, 1).len(), 1);
+}
 
-    let field_dict = fields
-        .iter::<Dict<'_>>()
-        .next()
-        .expect("field entry should resolve to dictionary");
+#[test]
+fn concat_pdfs_appends_pages_under_single_page_tree() {
+    let first = create_pdf_with_contents(&["0 0 1 10 re f", "0 0 2 10 re f"]);
+    let second = create_pdf_with_contents(&["0 0 3 10 re f"]);
+
+    let merged = concat_pdfs(&[&first, &second]).expect("concatenation should succeed");
+    let parsed = hayro_syntax::Pdf::new(merged).expect("merged PDF should parse");
     assert_eq!(
-        field_dict
-            .get::<Name>(b"FT".as_ref())
-            .expect("field should include /FT")
-            .as_ref(),
-        b"Tx"
+        page_contents(&parsed),
+        ["0 0 1 10 re f", "0 0 2 10 re f", "0 0 3 10 re f"]
     );
+
+    let page_tree = parsed.pages()[0]
+        .raw()
+        .get::<Dict<'_>>(b"Parent".as_ref())
+        .and_then(|parent| parent.get::<Dict<'_>>(b"Parent".as_ref()))
+        .expect("pages should be nested under the root page tree");
+    assert_eq!(page_tree.get::<i32>(b"Count".as_ref()), Some(3));
     assert_eq!(
-        field_dict
-            .get::<PdfString>(b"T".as_ref())
-            .expect("field should include /T")
-            .as_bytes(),
-        b"customer_name"
+        page_tree
+            .get::<Array<'_>>(b"Kids".as_ref())
+            .unwrap()
+            .iter::<Dict<'_>>()
+            .count(),
+        2
     );
 }
 
 #[test]
-fn signature_field_sets_sigflags_and_sig_ft() {
+fn freetext_without_font_data_uses_win_ansi_helvetica() {
     let input = create_blank_pdf(1);
-    let signature_field = Annotation::SignatureField(SignatureFieldAnnot {
+    let freetext = Annotation::FreeText(FreeTextAnnot {
         base: AnnotationBase {
-            rect: [40.0, 120.0, 260.0, 180.0],
+            rect: [40.0, 40.0, 200.0, 90.0],
             ..Default::default()
         },
-        field_name: "customer_signature".to_string(),
-        tooltip: Some("Sign here".to_string()),
-        required: true,
+        text: "café".to_string(),
+        font_size: 12.0,
+        default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+        font_data: None,
+        multiline: false,
+        line_spacing: 1.2,
+        vertical_align: VAlign::Top,
+        callout_line: None,
+        rich_text: None,
+        default_style: None,
     });
 
-    let saved =
-        save_annotations(&input, &[(0, vec![signature_field])]).expect("save should succeed");
-    let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+    let pdf = save_and_parse(&input, &[(0, vec![freetext])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+    let appearance = dict
+        .get::<Dict<'_>>(b"AP".as_ref())
+        .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+        .expect("FreeText should have a normal appearance");
 
-    let root = pdf
-        .xref()
-        .get::<Dict<'_>>(pdf.xref().root_id())
-        .expect("catalog should parse");
-    let acro_form = root
-        .get::<Dict<'_>>(b"AcroForm".as_ref())
-        .expect("catalog should include /AcroForm");
-    assert_eq!(acro_form.get::<i32>(b"SigFlags".as_ref()), Some(3));
+    let content = appearance.decoded().expect("appearance should decode");
+    // `é` is the single byte 0xE9, written either raw or as a hex string.
+    assert!(
+        content.windows(4).any(|w| w == b"caf\xE9")
+            || content
+                .windows(8)
+                .any(|w| w.eq_ignore_ascii_case(b"636166E9")),
+        "text should be encoded in WinAnsi: {}",
+        String::from_utf8_lossy(&content)
+    );
 
-    let field_dict = acro_form
-        .get::<Array<'_>>(b"Fields".as_ref())
-        .expect("AcroForm should include /Fields")
-        .iter::<Dict<'_>>()
-        .next()
-        .expect("signature field dictionary should be present");
+    let font = appearance
+        .dict()
+        .get::<Dict<'_>>(b"Resources".as_ref())
+        .and_then(|r| r.get::<Dict<'_>>(b"Font".as_ref()))
+        .and_then(|f| f.get::<Dict<'_>>(b"Helv".as_ref()))
+        .expect("appearance should reference /Helv");
     assert_eq!(
-        field_dict
-            .get::<Name>(b"FT".as_ref())
-            .expect("field should include /FT")
-            .as_ref(),
-        b"Sig"
+        font.get::<Name>(b"Encoding".as_ref()).unwrap().as_ref(),
+        b"WinAnsiEncoding"
     );
 }
 
 #[test]
-fn duplicate_form_field_names_return_error() {
+fn freetext_callout_writes_subject_intent_and_callout_line() {
     let input = create_blank_pdf(1);
-    let first = Annotation::TextField(TextFieldAnnot {
+    let freetext = Annotation::FreeText(FreeTextAnnot {
         base: AnnotationBase {
-            rect: [20.0, 20.0, 120.0, 44.0],
+            rect: [100.0, 100.0, 200.0, 140.0],
+            subject: Some("Review".to_string()),
             ..Default::default()
         },
-        field_name: "same".to_string(),
-        value: None,
-        default_value: None,
-        max_len: None,
-        default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
-        read_only: false,
-        required: false,
+        text: "Look here".to_string(),
+        font_size: 12.0,
+        default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+        font_data: None,
         multiline: false,
+        line_spacing: 1.2,
+        vertical_align: VAlign::Top,
+        callout_line: Some(vec![[40.0, 40.0], [70.0, 120.0], [100.0, 120.0]]),
+        rich_text: None,
+        default_style: None,
     });
-    let second = Annotation::SignatureField(SignatureFieldAnnot {
+    let line = Annotation::Line(LineAnnot {
         base: AnnotationBase {
-            rect: [20.0, 60.0, 120.0, 100.0],
+            rect: [300.0, 300.0, 400.0, 400.0],
             ..Default::default()
         },
-        field_name: "same".to_string(),
-        tooltip: None,
-        required: false,
+        start: [300.0, 300.0],
+        end: [400.0, 400.0],
+        line_width: 1.0,
+        start_style: LineEnding::None,
+        end_style: LineEnding::ClosedArrow,
+        measure: None,
+        dash: None,
     });
 
-    let result = save_annotations(&input, &[(0, vec![first, second])]);
-    assert!(matches!(
-        result,
-        Err(SaveError::DuplicateFieldName(name)) if name == "same"
-    ));
-}
+    let pdf = save_and_parse(&input, &[(0, vec![freetext, line])]);
+    let dicts = page_annotation_dicts(&pdf, 0);
+    let dict = &dicts[0];
+    assert_eq!(
+        dict.get::<PdfString>(b"Subj".as_ref()).unwrap().as_bytes(),
+        b"Review"
+    );
+    assert_eq!(
+        dict.get::<Name>(b"IT".as_ref()).unwrap().as_ref(),
+        b"FreeTextCallout"
+    );
+    assert_eq!(
+        dict.get::<Vec<f32>>(b"CL".as_ref()),
+        Some(vec![40.0, 40.0, 70.0, 120.0, 100.0, 120.0])
+    );
+    assert_eq!(
+        dict.get::<Name>(b"LE".as_ref()).unwrap().as_ref(),
+        b"OpenArrow"
+    );
+    // The rect is grown to the callout line and its arrowhead, and `/RD`
+    // locates the text box.
+    assert_eq!(
+        dict.get::<[f32; 4]>(b"Rect".as_ref()),
+        Some([36.0, 36.0, 200.0, 140.0])
+    );
+    assert_eq!(
+        dict.get::<[f32; 4]>(b"RD".as_ref()),
+        Some([64.0, 64.0, 0.0, 0.0])
+    );
 
-#[test]
-fn empty_form_field_name_returns_error() {
-    let input = create_blank_pdf(1);
-    let field = Annotation::TextField(TextFieldAnnot {
-        base: AnnotationBase {
-            rect: [20.0, 20.0, 120.0, 44.0],
-            ..Default::default()
-        },
-        field_name: "   ".to_string(),
-        value: None,
-        default_value: None,
-        max_len: None,
-        default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
-        read_only: false,
-        required: false,
-        multiline: false,
-    });
+    let appearance = dict
+        .get::<Dict<'_>>(b"AP".as_ref())
+        .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+        .expect("FreeText should have a normal appearance");
+    let content = appearance.decoded().expect("appearance should decode");
+    let content = std::str::from_utf8(&content).unwrap();
+    assert!(
+        content.contains("4 4 m\n34 84 l\n64 84 l\nS"),
+        "callout should run from the target point to the text box: {content}"
+    );
+    assert!(
+        content.contains("\n4 4 l\n"),
+        "the arrowhead should point at the target: {content}"
+    );
+    assert!(content.contains("1 0 0 1 64 64 cm"), "{content}");
 
-    let result = save_annotations(&input, &[(0, vec![field])]);
-    assert!(matches!(result, Err(SaveError::InvalidFieldName)));
+    assert_eq!(
+        dicts[1].get::<Name>(b"IT".as_ref()).unwrap().as_ref(),
+        b"LineArrow"
+    );
+    assert!(!dicts[1].contains_key(b"Subj".as_ref()));
+
+    let Annotation::FreeText(read) = &read_annotations(&pdf, 0)[0] else {
+        panic!("expected a free text annotation");
+    };
+    assert_eq!(read.base.rect, [100.0, 100.0, 200.0, 140.0]);
+    assert_eq!(read.base.subject.as_deref(), Some("Review"));
+    assert_eq!(read.base.intent.as_deref(), Some("FreeTextCallout"));
+    assert_eq!(read.callout_line.as_ref().map(Vec::len), Some(3));
 }
 
 #[test]
-fn modified_author_contents_and_flags_are_serialized() {
+fn text_annotation_writes_icon_and_open_flag() {
     let input = create_blank_pdf(1);
     let text = Annotation::Text(TextAnnot {
         base: AnnotationBase {
-            rect: [10.0, 10.0, 30.0, 30.0],
-            author: Some("qa".to_string()),
-            contents: Some("hello".to_string()),
-            modified: Some("D:20260217120000Z".to_string()),
-            flags: 5,
+            rect: [100.0, 100.0, 124.0, 124.0],
             ..Default::default()
         },
-        open: false,
-        icon: "Note".to_string(),
+        open: true,
+        icon: "Key".to_string(),
+        rich_text: None,
+        default_style: None,
     });
 
     let pdf = save_and_parse(&input, &[(0, vec![text])]);
     let dict = &page_annotation_dicts(&pdf, 0)[0];
-    assert_eq!(
-        dict.get::<PdfString>(b"T".as_ref())
-            .expect("author should be written")
-            .as_bytes(),
-        b"qa"
-    );
-    assert_eq!(
-        dict.get::<PdfString>(b"Contents".as_ref())
-            .expect("contents should be written")
-            .as_bytes(),
-        b"hello"
-    );
-    assert_eq!(
-        dict.get::<PdfString>(b"M".as_ref())
-            .expect("modified date should be written")
-            .as_bytes(),
-        b"D:20260217120000Z"
-    );
-    assert_eq!(dict.get::<i32>(b"F".as_ref()), Some(5));
+    assert_eq!(subtype_bytes(dict), b"Text");
+    assert_eq!(dict.get::<bool>(b"Open".as_ref()), Some(true));
+    let icon = dict
+        .get::<Name>(b"Name".as_ref())
+        .expect("Text annotation should include /Name icon");
+    assert_eq!(icon.as_ref(), b"Key");
 }
 
 #[test]
-fn rect_color_and_opacity_are_normalized_and_clamped() {
+fn ink_annotation_writes_inklist_and_border_style_width() {
     let input = create_blank_pdf(1);
-    let highlight = Annotation::Highlight(HighlightAnnot {
+    let ink = Annotation::Ink(InkAnnot {
         base: AnnotationBase {
-            rect: [150.0, 100.0, 50.0, 20.0],
-            color: Some(AnnotColor::new(-0.5, 2.0, 0.25)),
-            opacity: -0.4,
+            rect: [50.0, 50.0, 180.0, 180.0],
             ..Default::default()
         },
-        quad_points: vec![50.0, 100.0, 150.0, 100.0, 50.0, 20.0, 150.0, 20.0],
+        ink_list: vec![vec![[60.0, 60.0], [80.0, 80.0], [120.0, 90.0]]],
+        line_width: 2.5,
+        smoothing: 0.0,
+        dash: None,
     });
 
-    let pdf = save_and_parse(&input, &[(0, vec![highlight])]);
+    let pdf = save_and_parse(&input, &[(0, vec![ink])]);
     let dict = &page_annotation_dicts(&pdf, 0)[0];
+    assert_eq!(subtype_bytes(dict), b"Ink");
 
+    let ink_list = dict
+        .get::<Array<'_>>(b"InkList".as_ref())
+        .expect("Ink annotation should have /InkList");
+    assert_eq!(ink_list.raw_iter().count(), 1);
+
+    let bs = dict
+        .get::<Dict<'_>>(b"BS".as_ref())
+        .expect("Ink should have /BS");
+    assert_eq!(bs.get::<f32>(b"W".as_ref()), Some(2.5));
+}
+
+#[test]
+fn square_and_circle_write_ic_and_border_style() {
+    let input = create_blank_pdf(1);
+    let square = Annotation::Square(ShapeAnnot {
+        base: AnnotationBase {
+            rect: [10.0, 10.0, 100.0, 100.0],
+            ..Default::default()
+        },
+        interior_color: Some(AnnotColor::new(0.1, 0.2, 0.3)),
+        line_width: 3.0,
+        is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
+    });
+    let circle = Annotation::Circle(ShapeAnnot {
+        base: AnnotationBase {
+            rect: [120.0, 120.0, 200.0, 200.0],
+            ..Default::default()
+        },
+        interior_color: Some(AnnotColor::new(0.4, 0.5, 0.6)),
+        line_width: 1.5,
+        is_circle: true,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![square, circle])]);
+    let dicts = page_annotation_dicts(&pdf, 0);
+
+    for dict in dicts {
+        assert!(
+            dict.contains_key(b"IC".as_ref()),
+            "shape annotation should include interior color"
+        );
+        let bs = dict
+            .get::<Dict<'_>>(b"BS".as_ref())
+            .expect("shape should have /BS");
+        assert!(
+            bs.get::<f32>(b"W".as_ref()).is_some(),
+            "shape border style should include width"
+        );
+    }
+}
+
+#[test]
+fn line_annotation_writes_l_and_border_style() {
+    let input = create_blank_pdf(1);
+    let line = Annotation::Line(LineAnnot {
+        base: AnnotationBase {
+            rect: [50.0, 50.0, 200.0, 200.0],
+            ..Default::default()
+        },
+        start: [60.0, 60.0],
+        end: [190.0, 180.0],
+        line_width: 4.0,
+        start_style: LineEnding::None,
+        end_style: LineEnding::None,
+        measure: None,
+        dash: None,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![line])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+    assert_eq!(subtype_bytes(dict), b"Line");
     assert_eq!(
-        dict.get::<[f32; 4]>(b"Rect".as_ref())
-            .expect("rect should be written in annotation"),
-        [50.0, 20.0, 150.0, 100.0]
-    );
-    assert_eq!(
-        dict.get::<Vec<f32>>(b"C".as_ref())
-            .expect("color should be present for highlight"),
-        vec![0.0, 1.0, 0.25]
+        dict.get::<Vec<f32>>(b"L".as_ref())
+            .expect("Line annotation should have /L")
+            .len(),
+        4
     );
-    assert_eq!(
-        dict.get::<f32>(b"CA".as_ref())
-            .expect("opacity should be present"),
-        0.0
+    let bs = dict
+        .get::<Dict<'_>>(b"BS".as_ref())
+        .expect("line should have /BS");
+    assert_eq!(bs.get::<f32>(b"W".as_ref()), Some(4.0));
+    assert!(
+        !dict.contains_key(b"LE".as_ref()),
+        "plain lines should not write /LE"
     );
 }
 
 #[test]
-fn duplicate_page_entries_are_merged() {
+fn dashed_line_and_ink_write_border_dash_array() {
     let input = create_blank_pdf(1);
-    let first = Annotation::Highlight(HighlightAnnot {
+    let line = Annotation::Line(LineAnnot {
         base: AnnotationBase {
-            rect: [10.0, 10.0, 100.0, 30.0],
+            rect: [50.0, 50.0, 150.0, 60.0],
             ..Default::default()
         },
-        quad_points: vec![10.0, 30.0, 100.0, 30.0, 10.0, 10.0, 100.0, 10.0],
+        start: [50.0, 55.0],
+        end: [150.0, 55.0],
+        line_width: 1.0,
+        start_style: LineEnding::None,
+        end_style: LineEnding::None,
+        measure: None,
+        dash: Some(vec![6.0, 3.0]),
     });
-    let second = Annotation::Underline(UnderlineAnnot {
+    let ink = Annotation::Ink(InkAnnot {
         base: AnnotationBase {
-            rect: [20.0, 40.0, 110.0, 60.0],
+            rect: [50.0, 100.0, 150.0, 200.0],
             ..Default::default()
         },
-        quad_points: vec![20.0, 60.0, 110.0, 60.0, 20.0, 40.0, 110.0, 40.0],
+        ink_list: vec![vec![[60.0, 110.0], [140.0, 190.0]]],
+        line_width: 2.0,
+        smoothing: 0.0,
+        dash: Some(vec![0.0]),
     });
 
-    let pdf = save_and_parse(&input, &[(0, vec![first]), (0, vec![second])]);
+    let pdf = save_and_parse(&input, &[(0, vec![line, ink])]);
     let dicts = page_annotation_dicts(&pdf, 0);
-    assert_eq!(dicts.len(), 2, "both page entries should be preserved");
+
+    let bs = dicts[0]
+        .get::<Dict<'_>>(b"BS".as_ref())
+        .expect("line should have /BS");
+    assert_eq!(bs.get::<Name>(b"S".as_ref()).unwrap().as_ref(), b"D");
+    assert_eq!(bs.get::<Vec<f32>>(b"D".as_ref()), Some(vec![6.0, 3.0]));
+
+    // An all-zero pattern is written as a solid line.
+    let bs = dicts[1]
+        .get::<Dict<'_>>(b"BS".as_ref())
+        .expect("ink should have /BS");
+    assert!(!bs.contains_key(b"D".as_ref()));
+    assert!(!bs.contains_key(b"S".as_ref()));
+
+    let annotations = read_annotations(&pdf, 0);
+    let Annotation::Line(line) = &annotations[0] else {
+        panic!("expected a line, got {:?}", annotations[0]);
+    };
+    assert_eq!(line.dash, Some(vec![6.0, 3.0]));
+    let Annotation::Ink(ink) = &annotations[1] else {
+        panic!("expected ink, got {:?}", annotations[1]);
+    };
+    assert_eq!(ink.dash, None);
 }
 
 #[test]
-fn multi_page_annotations_are_applied_to_correct_pages() {
-    let input = create_blank_pdf(3);
-    let p0 = Annotation::Text(TextAnnot {
+fn measured_line_writes_measure_and_displays_scaled_length() {
+    let input = create_blank_pdf(1);
+    let measure = MeasureScale {
+        ratio: "1 in = 10 ft".to_string(),
+        units_per_point: 10.0 / 72.0,
+        unit: "ft".to_string(),
+        precision: 2,
+    };
+    let line = Annotation::Line(LineAnnot {
         base: AnnotationBase {
-            rect: [10.0, 10.0, 30.0, 30.0],
+            rect: [50.0, 50.0, 150.0, 60.0],
             ..Default::default()
         },
-        open: false,
-        icon: "Note".to_string(),
+        start: [50.0, 55.0],
+        end: [150.0, 55.0],
+        line_width: 1.0,
+        start_style: LineEnding::None,
+        end_style: LineEnding::None,
+        measure: Some(measure.clone()),
+        dash: None,
     });
-    let p2a = Annotation::Square(ShapeAnnot {
+
+    let pdf = save_and_parse(&input, &[(0, vec![line])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+    assert_eq!(
+        dict.get::<Name>(b"IT".as_ref()).unwrap().as_ref(),
+        b"LineDimension"
+    );
+    assert_eq!(dict.get::<bool>(b"Cap".as_ref()), Some(true));
+    assert_eq!(
+        dict.get::<PdfString>(b"Contents".as_ref())
+            .unwrap()
+            .as_bytes(),
+        b"13.89 ft"
+    );
+    assert!(dict.contains_key(b"RC".as_ref()));
+
+    let measure_dict = dict
+        .get::<Dict<'_>>(b"Measure".as_ref())
+        .expect("line should have /Measure");
+    assert_eq!(
+        measure_dict
+            .get::<Name>(b"Subtype".as_ref())
+            .unwrap()
+            .as_ref(),
+        b"RL"
+    );
+    let x = measure_dict
+        .get::<Array<'_>>(b"X".as_ref())
+        .and_then(|x| x.iter::<Dict<'_>>().next())
+        .expect("/Measure should have an /X number format");
+    assert!((x.get::<f32>(b"C".as_ref()).unwrap() - 10.0 / 72.0).abs() < 1e-4);
+    assert_eq!(x.get::<PdfString>(b"U".as_ref()).unwrap().as_bytes(), b"ft");
+    assert_eq!(x.get::<i32>(b"D".as_ref()), Some(100));
+
+    // The rect grows to include the label above the line.
+    let rect = dict.get::<[f32; 4]>(b"Rect".as_ref()).unwrap();
+    assert!(rect[3] > 60.0, "{rect:?}");
+
+    let appearance = dict
+        .get::<Dict<'_>>(b"AP".as_ref())
+        .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+        .expect("line should have a normal appearance");
+    let content = appearance.decoded().expect("appearance should decode");
+    let content = std::str::from_utf8(&content).unwrap();
+    assert!(content.contains("(13.89 ft) Tj"), "{content}");
+
+    let Annotation::Line(read) = &read_annotations(&pdf, 0)[0] else {
+        panic!("expected a line annotation");
+    };
+    let read_measure = read.measure.as_ref().expect("measure should be read");
+    assert_eq!(read_measure.ratio, measure.ratio);
+    assert_eq!(read_measure.unit, measure.unit);
+    assert_eq!(read_measure.precision, 2);
+}
+
+#[test]
+fn line_annotation_writes_line_endings() {
+    let input = create_blank_pdf(1);
+    let line = Annotation::Line(LineAnnot {
         base: AnnotationBase {
-            rect: [50.0, 50.0, 120.0, 120.0],
+            rect: [60.0, 60.0, 190.0, 180.0],
             ..Default::default()
         },
-        interior_color: None,
-        line_width: 1.0,
-        is_circle: false,
+        start: [60.0, 60.0],
+        end: [190.0, 180.0],
+        line_width: 2.0,
+        start_style: LineEnding::Circle,
+        end_style: LineEnding::ClosedArrow,
+        measure: None,
+        dash: None,
     });
-    let p2b = Annotation::Line(LineAnnot {
+
+    let pdf = save_and_parse(&input, &[(0, vec![line])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+    let le = dict
+        .get::<Array<'_>>(b"LE".as_ref())
+        .expect("line should have /LE");
+    let names: Vec<Vec<u8>> = le.iter::<Name>().map(|n| n.as_ref().to_vec()).collect();
+    assert_eq!(names, vec![b"Circle".to_vec(), b"ClosedArrow".to_vec()]);
+
+    // The rect is grown so the endings fit inside it.
+    let rect = dict.get::<[f32; 4]>(b"Rect".as_ref()).expect("/Rect");
+    assert!(rect[0] < 60.0 && rect[3] > 180.0, "rect: {rect:?}");
+}
+
+#[test]
+fn link_uri_annotation_writes_uri_action() {
+    let input = create_blank_pdf(1);
+    let link = Annotation::Link(LinkAnnot {
         base: AnnotationBase {
-            rect: [130.0, 130.0, 230.0, 220.0],
+            rect: [10.0, 10.0, 200.0, 30.0],
             ..Default::default()
         },
-        start: [130.0, 130.0],
-        end: [230.0, 220.0],
-        line_width: 2.0,
+        uri: Some("https://example.com".to_string()),
+        dest_page: None,
     });
 
-    let pdf = save_and_parse(&input, &[(0, vec![p0]), (2, vec![p2a, p2b])]);
-    assert_eq!(page_annotation_dicts(&pdf, 0).len(), 1);
-    assert_eq!(page_annotation_dicts(&pdf, 2).len(), 2);
+    let pdf = save_and_parse(&input, &[(0, vec![link])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+    assert_eq!(subtype_bytes(dict), b"Link");
+
+    let action = dict
+        .get::<Dict<'_>>(b"A".as_ref())
+        .expect("Link should have /A");
+    assert_eq!(
+        action
+            .get::<Name>(b"S".as_ref())
+            .expect("action should have /S")
+            .as_ref(),
+        b"URI"
+    );
+    assert_eq!(
+        action
+            .get::<PdfString>(b"URI".as_ref())
+            .expect("action should have /URI")
+            .as_bytes(),
+        b"https://example.com"
+    );
+}
+
+#[test]
+fn link_destination_page_writes_dest_array() {
+    let input = create_blank_pdf(3);
+    let link = Annotation::Link(LinkAnnot {
+        base: AnnotationBase {
+            rect: [10.0, 10.0, 200.0, 30.0],
+            ..Default::default()
+        },
+        uri: None,
+        dest_page: Some(2),
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![link])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
     assert!(
-        pdf.pages()[1]
-            .raw()
-            .get::<Array<'_>>(ANNOTS as &[u8])
-            .is_none(),
-        "untouched page should not gain /Annots"
+        dict.contains_key(b"Dest".as_ref()),
+        "Link should include /Dest"
     );
+
+    let dest = dict
+        .get::<Array<'_>>(b"Dest".as_ref())
+        .expect("Link /Dest should be an array");
+    let mut iter = dest.raw_iter();
+
+    let page_ref = iter
+        .next()
+        .and_then(|r| r.as_obj_ref())
+        .expect("first /Dest entry should be a page reference");
+    let fit = iter.next().expect("second /Dest entry should exist");
+    let MaybeRef::NotRef(Object::Name(fit_name)) = fit else {
+        panic!("second /Dest entry should be /Fit");
+    };
+    assert_eq!(fit_name.as_ref(), b"Fit");
+
+    let expected_page_obj_id = pdf.pages()[2]
+        .raw()
+        .obj_id()
+        .expect("page should be an indirect object");
+    assert_eq!(page_ref, ObjRef::from(expected_page_obj_id));
 }
 
 #[test]
-fn all_annotation_types_can_be_written_together() {
+fn invalid_destination_page_returns_error() {
     let input = create_blank_pdf(1);
-    let annotations = vec![
-        Annotation::Highlight(HighlightAnnot {
-            base: AnnotationBase {
-                rect: [10.0, 10.0, 100.0, 30.0],
-                ..Default::default()
-            },
-            quad_points: vec![10.0, 30.0, 100.0, 30.0, 10.0, 10.0, 100.0, 10.0],
-        }),
-        Annotation::Underline(UnderlineAnnot {
-            base: AnnotationBase {
-                rect: [20.0, 35.0, 110.0, 45.0],
-                ..Default::default()
-            },
-            quad_points: vec![20.0, 45.0, 110.0, 45.0, 20.0, 35.0, 110.0, 35.0],
+    let link = Annotation::Link(LinkAnnot {
+        base: AnnotationBase {
+            rect: [10.0, 10.0, 200.0, 30.0],
+            ..Default::default()
+        },
+        uri: None,
+        dest_page: Some(9),
+    });
+
+    let result = save_annotations(&input, &[(0, vec![link])]);
+    assert!(matches!(result, Err(SaveError::InvalidDestinationPage(9))));
+}
+
+#[test]
+fn text_field_creation_writes_acroform_and_fields() {
+    let input = create_blank_pdf(1);
+    let text_field = Annotation::TextField(TextFieldAnnot {
+        base: AnnotationBase {
+            rect: [40.0, 40.0, 220.0, 72.0],
+            ..Default::default()
+        },
+        field_name: "customer_name".to_string(),
+        value: Some("Ada".to_string()),
+        default_value: Some("".to_string()),
+        max_len: Some(64),
+        default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
+        read_only: false,
+        required: true,
+        multiline: false,
+        alignment: TextAlign::Left,
+        down_appearance: None,
+        rollover_appearance: None,
+    });
+
+    let saved = save_annotations(&input, &[(0, vec![text_field])]).expect("save should succeed");
+    let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+
+    let root = pdf
+        .xref()
+        .get::<Dict<'_>>(pdf.xref().root_id())
+        .expect("catalog should parse");
+    let acro_form = root
+        .get::<Dict<'_>>(b"AcroForm".as_ref())
+        .expect("catalog should include /AcroForm");
+    let fields = acro_form
+        .get::<Array<'_>>(b"Fields".as_ref())
+        .expect("AcroForm should include /Fields");
+    assert_eq!(fields.raw_iter().count(), 1, "expected one field");
+
+    let field_dict = fields
+        .iter::<Dict<'_>>()
+        .next()
+        .expect("field entry should resolve to dictionary");
+    assert_eq!(
+        field_dict
+            .get::<Name>(b"FT".as_ref())
+            .expect("field should include /FT")
+            .as_ref(),
+        b"Tx"
+    );
+    assert_eq!(
+        field_dict
+            .get::<PdfString>(b"T".as_ref())
+            .expect("field should include /T")
+            .as_bytes(),
+        b"customer_name"
+    );
+}
+
+#[test]
+fn acroform_includes_fields_and_default_resources() {
+    let input = create_blank_pdf(1);
+    let text_field = Annotation::TextField(TextFieldAnnot {
+        base: AnnotationBase {
+            rect: [40.0, 40.0, 220.0, 72.0],
+            ..Default::default()
+        },
+        field_name: "email".to_string(),
+        value: Some("ada@example.com".to_string()),
+        default_value: None,
+        max_len: None,
+        default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
+        read_only: false,
+        required: false,
+        multiline: false,
+        alignment: TextAlign::Left,
+        down_appearance: None,
+        rollover_appearance: None,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![text_field])]);
+    let root = pdf
+        .xref()
+        .get::<Dict<'_>>(pdf.xref().root_id())
+        .expect("catalog should parse");
+    let acro_form = root
+        .get::<Dict<'_>>(b"AcroForm".as_ref())
+        .expect("catalog should include /AcroForm");
+
+    let fields = acro_form
+        .get::<Array<'_>>(b"Fields".as_ref())
+        .expect("AcroForm should include /Fields");
+    assert!(fields.iter::<Dict<'_>>().next().is_some());
+
+    // The field has a generated appearance, so viewers don't need to build one.
+    assert_eq!(
+        acro_form.get::<bool>(b"NeedAppearances".as_ref()),
+        Some(false)
+    );
+
+    let font = acro_form
+        .get::<Dict<'_>>(b"DR".as_ref())
+        .and_then(|dr| dr.get::<Dict<'_>>(b"Font".as_ref()))
+        .and_then(|fonts| fonts.get::<Dict<'_>>(b"Helv".as_ref()))
+        .expect("AcroForm should include Helvetica in /DR");
+    assert_eq!(
+        font.get::<Name>(b"BaseFont".as_ref()).unwrap().as_ref(),
+        b"Helvetica"
+    );
+}
+
+#[test]
+fn text_field_writes_quadding() {
+    let input = create_blank_pdf(1);
+    let text_field = Annotation::TextField(TextFieldAnnot {
+        base: AnnotationBase {
+            rect: [40.0, 40.0, 220.0, 72.0],
+            ..Default::default()
+        },
+        field_name: "total".to_string(),
+        value: Some("42.00".to_string()),
+        default_value: None,
+        max_len: None,
+        default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
+        read_only: false,
+        required: false,
+        multiline: false,
+        alignment: TextAlign::Right,
+        down_appearance: None,
+        rollover_appearance: None,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![text_field])]);
+    let dicts = page_annotation_dicts(&pdf, 0);
+    assert_eq!(dicts[0].get::<i32>(b"Q".as_ref()), Some(2));
+
+    let Annotation::TextField(read) = &read_annotations(&pdf, 0)[0] else {
+        panic!("expected a text field");
+    };
+    assert_eq!(read.alignment, TextAlign::Right);
+}
+
+#[test]
+fn signature_field_sets_sigflags_and_sig_ft() {
+    let input = create_blank_pdf(1);
+    let signature_field = Annotation::SignatureField(SignatureFieldAnnot {
+        base: AnnotationBase {
+            rect: [40.0, 120.0, 260.0, 180.0],
+            ..Default::default()
+        },
+        field_name: "customer_signature".to_string(),
+        tooltip: Some("Sign here".to_string()),
+        required: true,
+        down_appearance: None,
+        rollover_appearance: None,
+    });
+
+    let saved =
+        save_annotations(&input, &[(0, vec![signature_field])]).expect("save should succeed");
+    let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+
+    let root = pdf
+        .xref()
+        .get::<Dict<'_>>(pdf.xref().root_id())
+        .expect("catalog should parse");
+    let acro_form = root
+        .get::<Dict<'_>>(b"AcroForm".as_ref())
+        .expect("catalog should include /AcroForm");
+    assert_eq!(acro_form.get::<i32>(b"SigFlags".as_ref()), Some(3));
+
+    let field_dict = acro_form
+        .get::<Array<'_>>(b"Fields".as_ref())
+        .expect("AcroForm should include /Fields")
+        .iter::<Dict<'_>>()
+        .next()
+        .expect("signature field dictionary should be present");
+    assert_eq!(
+        field_dict
+            .get::<Name>(b"FT".as_ref())
+            .expect("field should include /FT")
+            .as_ref(),
+        b"Sig"
+    );
+}
+
+#[test]
+fn checked_checkbox_selects_export_value_state() {
+    let input = create_blank_pdf(1);
+    let checkbox = Annotation::CheckBox(CheckBoxAnnot {
+        base: AnnotationBase {
+            rect: [40.0, 200.0, 56.0, 216.0],
+            ..Default::default()
+        },
+        field_name: "accept_terms".to_string(),
+        export_value: "Accepted".to_string(),
+        checked: true,
+        read_only: false,
+        required: false,
+        down_appearance: None,
+        rollover_appearance: None,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![checkbox])]);
+    let dicts = page_annotation_dicts(&pdf, 0);
+    let widget = &dicts[0];
+
+    assert_eq!(
+        widget
+            .get::<Name>(b"AS".as_ref())
+            .expect("checkbox should include /AS")
+            .as_ref(),
+        b"Accepted"
+    );
+
+    let normal = widget
+        .get::<Dict<'_>>(b"AP".as_ref())
+        .and_then(|ap| ap.get::<Dict<'_>>(b"N".as_ref()))
+        .expect("checkbox should include an /AP /N state dictionary");
+    assert!(normal.contains_key(b"Accepted".as_ref()));
+    assert!(normal.contains_key(b"Off".as_ref()));
+
+    let field_dict = widget
+        .get::<Dict<'_>>(b"Parent".as_ref())
+        .expect("widget should reference its field");
+    assert_eq!(
+        field_dict
+            .get::<Name>(b"FT".as_ref())
+            .expect("field should include /FT")
+            .as_ref(),
+        b"Btn"
+    );
+    assert_eq!(
+        field_dict
+            .get::<Name>(b"V".as_ref())
+            .expect("field should include /V")
+            .as_ref(),
+        b"Accepted"
+    );
+}
+
+#[test]
+fn unchecked_checkbox_selects_off_state() {
+    let input = create_blank_pdf(1);
+    let checkbox = Annotation::CheckBox(CheckBoxAnnot {
+        base: AnnotationBase {
+            rect: [40.0, 200.0, 56.0, 216.0],
+            ..Default::default()
+        },
+        field_name: "newsletter".to_string(),
+        export_value: "Yes".to_string(),
+        checked: false,
+        read_only: false,
+        required: false,
+        down_appearance: None,
+        rollover_appearance: None,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![checkbox])]);
+    let dicts = page_annotation_dicts(&pdf, 0);
+    let field_dict = dicts[0]
+        .get::<Dict<'_>>(b"Parent".as_ref())
+        .expect("widget should reference its field");
+
+    assert_eq!(
+        dicts[0]
+            .get::<Name>(b"AS".as_ref())
+            .expect("checkbox should include /AS")
+            .as_ref(),
+        b"Off"
+    );
+    assert_eq!(
+        field_dict
+            .get::<Name>(b"V".as_ref())
+            .expect("field should include /V")
+            .as_ref(),
+        b"Off"
+    );
+}
+
+#[test]
+fn dropdown_writes_options_value_and_combo_flag() {
+    let input = create_blank_pdf(1);
+    let dropdown = Annotation::Choice(ChoiceFieldAnnot {
+        base: AnnotationBase {
+            rect: [40.0, 200.0, 160.0, 220.0],
+            ..Default::default()
+        },
+        field_name: "country".to_string(),
+        options: vec!["France".to_string(), "Japan".to_string()],
+        selected: Some("Japan".to_string()),
+        combo: true,
+        read_only: false,
+        required: true,
+        down_appearance: None,
+        rollover_appearance: None,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![dropdown])]);
+    let dicts = page_annotation_dicts(&pdf, 0);
+    let field_dict = dicts[0]
+        .get::<Dict<'_>>(b"Parent".as_ref())
+        .expect("widget should reference its field");
+
+    assert_eq!(
+        field_dict.get::<Name>(b"FT".as_ref()).unwrap().as_ref(),
+        b"Ch"
+    );
+    let options = field_dict
+        .get::<Array<'_>>(b"Opt".as_ref())
+        .expect("field should include /Opt")
+        .iter::<PdfString>()
+        .map(|o| o.as_bytes().to_vec())
+        .collect::<Vec<_>>();
+    assert_eq!(options, [b"France".to_vec(), b"Japan".to_vec()]);
+    for key in [b"V".as_ref(), b"DV".as_ref()] {
+        assert_eq!(
+            field_dict.get::<PdfString>(key).unwrap().as_bytes(),
+            b"Japan"
+        );
+    }
+    assert_eq!(field_dict.get::<i32>(b"Ff".as_ref()), Some((1 << 17) | 2));
+
+    let Annotation::Choice(read) = &read_annotations(&pdf, 0)[0] else {
+        panic!("expected a choice field");
+    };
+    assert_eq!(read.options, ["France", "Japan"]);
+    assert_eq!(read.selected.as_deref(), Some("Japan"));
+    assert!(read.combo && read.required);
+}
+
+#[test]
+fn choice_field_without_options_returns_error() {
+    let input = create_blank_pdf(1);
+    let dropdown = Annotation::Choice(ChoiceFieldAnnot {
+        base: AnnotationBase {
+            rect: [40.0, 200.0, 160.0, 220.0],
+            ..Default::default()
+        },
+        field_name: "empty".to_string(),
+        options: vec![],
+        selected: None,
+        combo: true,
+        read_only: false,
+        required: false,
+        down_appearance: None,
+        rollover_appearance: None,
+    });
+
+    assert!(matches!(
+        save_annotations(&input, &[(0, vec![dropdown])]),
+        Err(SaveError::EmptyChoiceOptions(name)) if name == "empty"
+    ));
+}
+
+#[test]
+fn polygon_and_polyline_write_vertices() {
+    let input = create_blank_pdf(1);
+    let vertices = vec![[10.0, 10.0], [90.0, 10.0], [50.0, 90.0]];
+    let annots = vec![
+        Annotation::Polygon(PolyAnnot {
+            base: AnnotationBase {
+                rect: [10.0, 10.0, 90.0, 90.0],
+                ..Default::default()
+            },
+            vertices: vertices.clone(),
+            line_width: 1.0,
+            interior_color: Some(AnnotColor::yellow()),
+        }),
+        Annotation::PolyLine(PolyAnnot {
+            base: AnnotationBase {
+                rect: [10.0, 10.0, 90.0, 90.0],
+                ..Default::default()
+            },
+            vertices: vec![[10.0, 10.0]],
+            line_width: 1.0,
+            interior_color: None,
+        }),
+    ];
+
+    let pdf = save_and_parse(&input, &[(0, annots)]);
+    let dicts = page_annotation_dicts(&pdf, 0);
+
+    assert_eq!(subtype_bytes(&dicts[0]), b"Polygon");
+    assert_eq!(
+        dicts[0].get::<Vec<f32>>(b"Vertices".as_ref()),
+        Some(vec![10.0, 10.0, 90.0, 10.0, 50.0, 90.0])
+    );
+    assert!(dicts[0].contains_key(b"AP".as_ref()));
+
+    assert_eq!(subtype_bytes(&dicts[1]), b"PolyLine");
+    assert!(
+        !dicts[1].contains_key(b"AP".as_ref()),
+        "a single vertex should not produce an appearance stream"
+    );
+}
+
+#[test]
+fn duplicate_form_field_names_return_error() {
+    let input = create_blank_pdf(1);
+    let first = Annotation::TextField(TextFieldAnnot {
+        base: AnnotationBase {
+            rect: [20.0, 20.0, 120.0, 44.0],
+            ..Default::default()
+        },
+        field_name: "same".to_string(),
+        value: None,
+        default_value: None,
+        max_len: None,
+        default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
+        read_only: false,
+        required: false,
+        multiline: false,
+        alignment: TextAlign::Left,
+        down_appearance: None,
+        rollover_appearance: None,
+    });
+    let second = Annotation::SignatureField(SignatureFieldAnnot {
+        base: AnnotationBase {
+            rect: [20.0, 60.0, 120.0, 100.0],
+            ..Default::default()
+        },
+        field_name: "same".to_string(),
+        tooltip: None,
+        required: false,
+        down_appearance: None,
+        rollover_appearance: None,
+    });
+
+    let result = save_annotations(&input, &[(0, vec![first, second])]);
+    assert!(matches!(
+        result,
+        Err(SaveError::DuplicateFieldName(name)) if name == "same"
+    ));
+}
+
+#[test]
+fn empty_form_field_name_returns_error() {
+    let input = create_blank_pdf(1);
+    let field = Annotation::TextField(TextFieldAnnot {
+        base: AnnotationBase {
+            rect: [20.0, 20.0, 120.0, 44.0],
+            ..Default::default()
+        },
+        field_name: "   ".to_string(),
+        value: None,
+        default_value: None,
+        max_len: None,
+        default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
+        read_only: false,
+        required: false,
+        multiline: false,
+        alignment: TextAlign::Left,
+        down_appearance: None,
+        rollover_appearance: None,
+    });
+
+    let result = save_annotations(&input, &[(0, vec![field])]);
+    assert!(matches!(result, Err(SaveError::InvalidFieldName)));
+}
+
+#[test]
+fn modified_author_contents_and_flags_are_serialized() {
+    let input = create_blank_pdf(1);
+    let text = Annotation::Text(TextAnnot {
+        base: AnnotationBase {
+            rect: [10.0, 10.0, 30.0, 30.0],
+            author: Some("qa".to_string()),
+            contents: Some("hello".to_string()),
+            modified: Some("D:20260217120000Z".to_string()),
+            flags: 5,
+            ..Default::default()
+        },
+        open: false,
+        icon: "Note".to_string(),
+        rich_text: None,
+        default_style: None,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![text])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+    assert_eq!(
+        dict.get::<PdfString>(b"T".as_ref())
+            .expect("author should be written")
+            .as_bytes(),
+        b"qa"
+    );
+    assert_eq!(
+        dict.get::<PdfString>(b"Contents".as_ref())
+            .expect("contents should be written")
+            .as_bytes(),
+        b"hello"
+    );
+    assert_eq!(
+        dict.get::<PdfString>(b"M".as_ref())
+            .expect("modified date should be written")
+            .as_bytes(),
+        b"D:20260217120000Z"
+    );
+    assert_eq!(dict.get::<i32>(b"F".as_ref()), Some(5));
+}
+
+#[test]
+fn malformed_dates_are_normalized_or_dropped() {
+    let input = create_blank_pdf(1);
+    let text = Annotation::Text(TextAnnot {
+        base: AnnotationBase {
+            rect: [10.0, 10.0, 30.0, 30.0],
+            modified: Some("2026-02-17T12:00:00+01:00".to_string()),
+            created: Some("last week".to_string()),
+            ..Default::default()
+        },
+        open: false,
+        icon: "Note".to_string(),
+        rich_text: None,
+        default_style: None,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![text])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+    assert_eq!(
+        dict.get::<PdfString>(b"M".as_ref())
+            .expect("modified date should be written")
+            .as_bytes(),
+        b"D:20260217120000+01'00'"
+    );
+    assert!(!dict.contains_key(b"CreationDate".as_ref()));
+}
+
+#[test]
+fn rect_color_and_opacity_are_normalized_and_clamped() {
+    let input = create_blank_pdf(1);
+    let highlight = Annotation::Highlight(HighlightAnnot {
+        base: AnnotationBase {
+            rect: [150.0, 100.0, 50.0, 20.0],
+            color: Some(AnnotColor::new(-0.5, 2.0, 0.25)),
+            opacity: -0.4,
+            ..Default::default()
+        },
+        quad_points: vec![50.0, 100.0, 150.0, 100.0, 50.0, 20.0, 150.0, 20.0],
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![highlight])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+
+    assert_eq!(
+        dict.get::<[f32; 4]>(b"Rect".as_ref())
+            .expect("rect should be written in annotation"),
+        [50.0, 20.0, 150.0, 100.0]
+    );
+    assert_eq!(
+        dict.get::<Vec<f32>>(b"C".as_ref())
+            .expect("color should be present for highlight"),
+        vec![0.0, 1.0, 0.25]
+    );
+    assert_eq!(
+        dict.get::<f32>(b"CA".as_ref())
+            .expect("opacity should be present"),
+        0.0
+    );
+}
+
+#[test]
+fn cmyk_and_gray_colors_write_matching_arrays_and_operators() {
+    let input = create_blank_pdf(1);
+    let highlight = Annotation::Highlight(HighlightAnnot {
+        base: AnnotationBase {
+            rect: [50.0, 20.0, 150.0, 100.0],
+            color: Some(AnnotColor::cmyk(0.0, 0.0, 1.0, 0.0)),
+            ..Default::default()
+        },
+        quad_points: vec![50.0, 100.0, 150.0, 100.0, 50.0, 20.0, 150.0, 20.0],
+    });
+    let square = Annotation::Square(ShapeAnnot {
+        base: AnnotationBase {
+            rect: [200.0, 200.0, 300.0, 300.0],
+            color: Some(AnnotColor::gray(0.2)),
+            ..Default::default()
+        },
+        interior_color: Some(AnnotColor::gray(0.9)),
+        line_width: 1.0,
+        is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![highlight, square])]);
+    let dicts = page_annotation_dicts(&pdf, 0);
+
+    assert_eq!(
+        dicts[0].get::<Vec<f32>>(b"C".as_ref()),
+        Some(vec![0.0, 0.0, 1.0, 0.0])
+    );
+    assert_eq!(dicts[1].get::<Vec<f32>>(b"C".as_ref()), Some(vec![0.2]));
+    assert_eq!(dicts[1].get::<Vec<f32>>(b"IC".as_ref()), Some(vec![0.9]));
+
+    let appearance = |dict: &Dict<'_>| {
+        let stream = dict
+            .get::<Dict<'_>>(b"AP".as_ref())
+            .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+            .expect("annotation should have a normal appearance");
+        String::from_utf8(stream.decoded().expect("appearance should decode")).unwrap()
+    };
+    let highlight_content = appearance(&dicts[0]);
+    assert!(
+        highlight_content.contains("0 0 1 0 k"),
+        "{highlight_content}"
+    );
+    let square_content = appearance(&dicts[1]);
+    assert!(square_content.contains("0.9 g"), "{square_content}");
+    assert!(square_content.contains("0.2 G"), "{square_content}");
+
+    let read = read_annotations(&pdf, 0);
+    assert_eq!(
+        read[0].base().color,
+        Some(AnnotColor::cmyk(0.0, 0.0, 1.0, 0.0))
+    );
+    assert_eq!(read[1].base().color, Some(AnnotColor::gray(0.2)));
+}
+
+#[test]
+fn annotations_are_clamped_to_the_page() {
+    let input = create_blank_pdf(1);
+    let highlight = Annotation::Highlight(HighlightAnnot {
+        base: AnnotationBase {
+            rect: [500.0, 800.0, 700.0, 900.0],
+            ..Default::default()
+        },
+        quad_points: vec![500.0, 900.0, 700.0, 900.0, 500.0, 800.0, 700.0, 800.0],
+    });
+    let off_page = Annotation::Square(ShapeAnnot {
+        base: AnnotationBase {
+            rect: [-200.0, -200.0, -100.0, -100.0],
+            ..Default::default()
+        },
+        interior_color: None,
+        line_width: 1.0,
+        is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
+    });
+    let annotations = [(0, vec![highlight, off_page])];
+
+    // By default, annotations are written as is.
+    let pdf = save_and_parse(&input, &annotations);
+    assert_eq!(page_annotation_dicts(&pdf, 0).len(), 2);
+
+    let saved = save_annotations_with_options(
+        &input,
+        &annotations,
+        &WriteOptions {
+            clamp_to_page: true,
+            ..Default::default()
+        },
+    )
+    .expect("save should succeed");
+    let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+    let dicts = page_annotation_dicts(&pdf, 0);
+
+    assert_eq!(dicts.len(), 1, "the off-page square should be skipped");
+    assert_eq!(
+        dicts[0].get::<[f32; 4]>(b"Rect".as_ref()),
+        Some([500.0, 800.0, 595.0, 842.0])
+    );
+    assert_eq!(
+        dicts[0].get::<Vec<f32>>(b"QuadPoints".as_ref()),
+        Some(vec![500.0, 842.0, 595.0, 842.0, 500.0, 800.0, 595.0, 800.0])
+    );
+}
+
+#[test]
+fn annotation_geometry_is_clamped_to_the_page() {
+    let input = create_blank_pdf(1);
+    let ink = Annotation::Ink(InkAnnot {
+        base: AnnotationBase {
+            rect: [500.0, 700.0, 700.0, 900.0],
+            ..Default::default()
+        },
+        ink_list: vec![vec![[500.0, 700.0], [700.0, 900.0]]],
+        line_width: 1.0,
+        smoothing: 0.0,
+        dash: None,
+    });
+    let line = Annotation::Line(LineAnnot {
+        base: AnnotationBase {
+            rect: [-100.0, 100.0, 100.0, 200.0],
+            ..Default::default()
+        },
+        start: [-100.0, 100.0],
+        end: [100.0, 200.0],
+        line_width: 1.0,
+        start_style: LineEnding::None,
+        end_style: LineEnding::None,
+        measure: None,
+        dash: None,
+    });
+    let polygon = Annotation::Polygon(PolyAnnot {
+        base: AnnotationBase {
+            rect: [400.0, -50.0, 650.0, 100.0],
+            ..Default::default()
+        },
+        vertices: vec![[400.0, 100.0], [650.0, 100.0], [500.0, -50.0]],
+        line_width: 1.0,
+        interior_color: None,
+    });
+
+    let saved = save_annotations_with_options(
+        &input,
+        &[(0, vec![ink, line, polygon])],
+        &WriteOptions {
+            clamp_to_page: true,
+            ..Default::default()
+        },
+    )
+    .expect("save should succeed");
+    let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+    let read = read_annotations(&pdf, 0);
+    assert_eq!(read.len(), 3);
+
+    let Annotation::Ink(ink) = &read[0] else {
+        panic!("expected an ink annotation");
+    };
+    assert_eq!(ink.ink_list, vec![vec![[500.0, 700.0], [595.0, 842.0]]]);
+
+    let Annotation::Line(line) = &read[1] else {
+        panic!("expected a line annotation");
+    };
+    assert_eq!(line.start, [0.0, 100.0]);
+    assert_eq!(line.end, [100.0, 200.0]);
+
+    let Annotation::Polygon(polygon) = &read[2] else {
+        panic!("expected a polygon annotation");
+    };
+    assert_eq!(
+        polygon.vertices,
+        vec![[400.0, 100.0], [595.0, 100.0], [500.0, 0.0]]
+    );
+}
+
+#[test]
+fn duplicate_page_entries_are_merged() {
+    let input = create_blank_pdf(1);
+    let first = Annotation::Highlight(HighlightAnnot {
+        base: AnnotationBase {
+            rect: [10.0, 10.0, 100.0, 30.0],
+            ..Default::default()
+        },
+        quad_points: vec![10.0, 30.0, 100.0, 30.0, 10.0, 10.0, 100.0, 10.0],
+    });
+    let second = Annotation::Underline(UnderlineAnnot {
+        base: AnnotationBase {
+            rect: [20.0, 40.0, 110.0, 60.0],
+            ..Default::default()
+        },
+        quad_points: vec![20.0, 60.0, 110.0, 60.0, 20.0, 40.0, 110.0, 40.0],
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![first]), (0, vec![second])]);
+    let dicts = page_annotation_dicts(&pdf, 0);
+    assert_eq!(dicts.len(), 2, "both page entries should be preserved");
+}
+
+#[test]
+fn annotations_are_written_in_creation_order() {
+    let input = create_blank_pdf(1);
+    let highlight = Annotation::Highlight(HighlightAnnot::from_rect(
+        [10.0, 10.0, 100.0, 30.0],
+        AnnotColor::yellow(),
+    ));
+    let square = Annotation::Square(ShapeAnnot {
+        base: AnnotationBase {
+            rect: [20.0, 20.0, 80.0, 80.0],
+            ..Default::default()
+        },
+        interior_color: None,
+        line_width: 1.0,
+        is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
+    });
+    let text = Annotation::Text(TextAnnot {
+        base: AnnotationBase {
+            rect: [10.0, 10.0, 30.0, 30.0],
+            ..Default::default()
+        },
+        open: false,
+        icon: "Note".to_string(),
+        rich_text: None,
+        default_style: None,
+    });
+
+    // The square is drawn after the highlight, so it must come later in
+    // `/Annots` to be painted on top of it.
+    let pdf = save_and_parse(&input, &[(0, vec![highlight, square]), (0, vec![text])]);
+    let subtypes: Vec<_> = page_annotation_dicts(&pdf, 0)
+        .iter()
+        .map(subtype_bytes)
+        .collect();
+    assert_eq!(
+        subtypes,
+        [b"Highlight".to_vec(), b"Square".to_vec(), b"Text".to_vec()]
+    );
+}
+
+#[test]
+fn multi_page_annotations_are_applied_to_correct_pages() {
+    let input = create_blank_pdf(3);
+    let p0 = Annotation::Text(TextAnnot {
+        base: AnnotationBase {
+            rect: [10.0, 10.0, 30.0, 30.0],
+            ..Default::default()
+        },
+        open: false,
+        icon: "Note".to_string(),
+        rich_text: None,
+        default_style: None,
+    });
+    let p2a = Annotation::Square(ShapeAnnot {
+        base: AnnotationBase {
+            rect: [50.0, 50.0, 120.0, 120.0],
+            ..Default::default()
+        },
+        interior_color: None,
+        line_width: 1.0,
+        is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
+    });
+    let p2b = Annotation::Line(LineAnnot {
+        base: AnnotationBase {
+            rect: [130.0, 130.0, 230.0, 220.0],
+            ..Default::default()
+        },
+        start: [130.0, 130.0],
+        end: [230.0, 220.0],
+        line_width: 2.0,
+        start_style: LineEnding::None,
+        end_style: LineEnding::None,
+        measure: None,
+        dash: None,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![p0]), (2, vec![p2a, p2b])]);
+    assert_eq!(page_annotation_dicts(&pdf, 0).len(), 1);
+    assert_eq!(page_annotation_dicts(&pdf, 2).len(), 2);
+    assert!(
+        pdf.pages()[1]
+            .raw()
+            .get::<Array<'_>>(ANNOTS as &[u8])
+            .is_none(),
+        "untouched page should not gain /Annots"
+    );
+}
+
+#[test]
+fn all_annotation_types_can_be_written_together() {
+    let input = create_blank_pdf(1);
+    let annotations = vec![
+        Annotation::Highlight(HighlightAnnot {
+            base: AnnotationBase {
+                rect: [10.0, 10.0, 100.0, 30.0],
+                ..Default::default()
+            },
+            quad_points: vec![10.0, 30.0, 100.0, 30.0, 10.0, 10.0, 100.0, 10.0],
+        }),
+        Annotation::Underline(UnderlineAnnot {
+            base: AnnotationBase {
+                rect: [20.0, 35.0, 110.0, 45.0],
+                ..Default::default()
+            },
+            quad_points: vec![20.0, 45.0, 110.0, 45.0, 20.0, 35.0, 110.0, 35.0],
         }),
         Annotation::StrikeOut(StrikeOutAnnot {
             base: AnnotationBase {
-                rect: [20.0, 50.0, 110.0, 60.0],
+                rect: [20.0, 50.0, 110.0, 60.0],
+                ..Default::default()
+            },
+            quad_points: vec![20.0, 60.0, 110.0, 60.0, 20.0, 50.0, 110.0, 50.0],
+        }),
+        Annotation::Squiggly(SquigglyAnnot {
+            base: AnnotationBase {
+                rect: [20.0, 65.0, 110.0, 75.0],
+                ..Default::default()
+            },
+            quad_points: vec![20.0, 75.0, 110.0, 75.0, 20.0, 65.0, 110.0, 65.0],
+        }),
+        Annotation::FreeText(FreeTextAnnot {
+            base: AnnotationBase {
+                rect: [120.0, 20.0, 260.0, 70.0],
+                ..Default::default()
+            },
+            text: "hello".to_string(),
+            font_size: 12.0,
+            default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+            font_data: None,
+            multiline: false,
+            line_spacing: 1.2,
+            vertical_align: VAlign::Top,
+            callout_line: None,
+            rich_text: None,
+            default_style: None,
+        }),
+        Annotation::Ink(InkAnnot {
+            base: AnnotationBase {
+                rect: [20.0, 100.0, 140.0, 180.0],
+                ..Default::default()
+            },
+            ink_list: vec![vec![[30.0, 110.0], [80.0, 160.0], [120.0, 120.0]]],
+            line_width: 1.5,
+            smoothing: 0.0,
+            dash: None,
+        }),
+        Annotation::Square(ShapeAnnot {
+            base: AnnotationBase {
+                rect: [150.0, 100.0, 220.0, 170.0],
+                ..Default::default()
+            },
+            interior_color: None,
+            line_width: 1.0,
+            is_circle: false,
+            border_style: BorderStyle::Solid,
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
+        }),
+        Annotation::Circle(ShapeAnnot {
+            base: AnnotationBase {
+                rect: [230.0, 100.0, 300.0, 170.0],
+                ..Default::default()
+            },
+            interior_color: None,
+            line_width: 1.0,
+            is_circle: true,
+            border_style: BorderStyle::Solid,
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
+        }),
+        Annotation::Line(LineAnnot {
+            base: AnnotationBase {
+                rect: [20.0, 200.0, 160.0, 260.0],
+                ..Default::default()
+            },
+            start: [20.0, 200.0],
+            end: [160.0, 260.0],
+            line_width: 2.0,
+            start_style: LineEnding::None,
+            end_style: LineEnding::None,
+            measure: None,
+            dash: None,
+        }),
+        Annotation::Text(TextAnnot {
+            base: AnnotationBase {
+                rect: [170.0, 200.0, 194.0, 224.0],
+                ..Default::default()
+            },
+            open: false,
+            icon: "Note".to_string(),
+            rich_text: None,
+            default_style: None,
+        }),
+        Annotation::Link(LinkAnnot {
+            base: AnnotationBase {
+                rect: [200.0, 200.0, 320.0, 220.0],
+                ..Default::default()
+            },
+            uri: Some("https://example.com".to_string()),
+            dest_page: None,
+        }),
+    ];
+
+    let pdf = save_and_parse(&input, &[(0, annotations)]);
+    let dicts = page_annotation_dicts(&pdf, 0);
+    assert_eq!(dicts.len(), 11, "all annotations should be present");
+}
+
+#[test]
+fn srgb_output_intent_is_referenced_from_catalog() {
+    let input = create_blank_pdf(1);
+    let options = WriteOptions {
+        add_srgb_output_intent: true,
+        ..Default::default()
+    };
+
+    let saved = save_annotations_with_options(&input, &[], &options).expect("save should succeed");
+    let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+
+    let root = pdf
+        .xref()
+        .get::<Dict<'_>>(pdf.xref().root_id())
+        .expect("catalog should parse");
+    let output_intent = root
+        .get::<Array<'_>>(b"OutputIntents".as_ref())
+        .expect("catalog should include /OutputIntents")
+        .iter::<Dict<'_>>()
+        .next()
+        .expect("output intent dictionary should be present");
+    assert_eq!(
+        output_intent
+            .get::<Name>(b"S".as_ref())
+            .expect("output intent should include /S")
+            .as_ref(),
+        b"GTS_PDFA1"
+    );
+
+    let profile = output_intent
+        .get::<hayro_syntax::object::Stream<'_>>(b"DestOutputProfile".as_ref())
+        .expect("output intent should reference an ICC stream");
+    assert_eq!(profile.dict().get::<i32>(b"N".as_ref()), Some(3));
+    let data = profile.decoded().expect("ICC stream should decode");
+    assert_eq!(
+        &data[36..40],
+        b"acsp",
+        "stream should contain an ICC profile"
+    );
+}
+
+#[test]
+fn appearance_stream_compression_is_configurable() {
+    let input = create_blank_pdf(1);
+    let annotations = [(
+        0,
+        vec![Annotation::Square(ShapeAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 100.0, 200.0, 200.0],
+                color: Some(AnnotColor::red()),
+                ..Default::default()
+            },
+            interior_color: None,
+            line_width: 2.0,
+            is_circle: false,
+            border_style: BorderStyle::Solid,
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
+        })],
+    )];
+
+    let appearance_of = |options: &WriteOptions| {
+        let saved = save_annotations_with_options(&input, &annotations, options)
+            .expect("save should succeed");
+        let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+        let dict = page_annotation_dicts(&pdf, 0).remove(0);
+        let appearance = dict
+            .get::<Dict<'_>>(b"AP".as_ref())
+            .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+            .expect("square should have a normal appearance");
+        (
+            appearance.dict().contains_key(b"Filter".as_ref()),
+            appearance.raw_data().to_vec(),
+            appearance.decoded().expect("appearance should decode"),
+        )
+    };
+
+    let (compressed, _, default_content) = appearance_of(&WriteOptions::default());
+    assert!(compressed, "appearance streams are compressed by default");
+
+    let (compressed, raw, content) = appearance_of(&WriteOptions {
+        compress_streams: false,
+        ..Default::default()
+    });
+    assert!(!compressed, "uncompressed streams shouldn't have a /Filter");
+    assert_eq!(raw, default_content);
+    assert_eq!(content, default_content);
+
+    let (compressed, _, content) = appearance_of(&WriteOptions {
+        compression_level: 9,
+        ..Default::default()
+    });
+    assert!(compressed);
+    assert_eq!(content, default_content);
+}
+
+#[test]
+fn object_streams_pack_annotations_and_write_xref_stream() {
+    let input = create_blank_pdf(2);
+    let text = |i: usize| {
+        Annotation::Text(TextAnnot {
+            base: AnnotationBase {
+                rect: [10.0, 10.0 + i as f32 * 5.0, 30.0, 30.0 + i as f32 * 5.0],
+                contents: Some(format!("Note {i}")),
+                ..Default::default()
+            },
+            open: false,
+            icon: "Note".to_string(),
+            rich_text: None,
+            default_style: None,
+        })
+    };
+    let annotations = [
+        (0, (0..150).map(text).collect::<Vec<_>>()),
+        (1, (0..10).map(text).collect::<Vec<_>>()),
+    ];
+
+    let legacy = save_annotations(&input, &annotations).expect("save should succeed");
+    let packed = save_annotations_with_options(
+        &input,
+        &annotations,
+        &WriteOptions {
+            use_object_streams: true,
+            ..Default::default()
+        },
+    )
+    .expect("save should succeed");
+
+    let contains =
+        |haystack: &[u8], needle: &[u8]| haystack.windows(needle.len()).any(|w| w == needle);
+    assert!(contains(&legacy, b"\nxref\n"));
+    assert!(!contains(&packed, b"\nxref\n"));
+    assert!(contains(&packed, b"/Type /XRef"));
+    assert!(contains(&packed, b"/Type /ObjStm"));
+    assert!(
+        packed.len() < legacy.len(),
+        "{} >= {}",
+        packed.len(),
+        legacy.len()
+    );
+
+    let pdf = hayro_syntax::Pdf::new(packed).expect("packed pdf should parse");
+    assert_eq!(pdf.pages().len(), 2);
+    let first_page = read_annotations(&pdf, 0);
+    assert_eq!(first_page.len(), 150);
+    assert_eq!(read_annotations(&pdf, 1).len(), 10);
+    assert_eq!(first_page[149].base().contents.as_deref(), Some("Note 149"));
+}
+
+#[test]
+fn object_streams_keep_streams_containing_object_headers() {
+    use pdf_writer::{Finish, Pdf, Rect, Ref};
+
+    // Looks like the start of the catalog object to a naive header scan.
+    let content = b"0 0 1 rg\n1 0 obj\n100 100 200 200 re f\nendobj\n";
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(Ref::new(1)).pages(Ref::new(2));
+    pdf.pages(Ref::new(2)).kids([Ref::new(3)]).count(1);
+    let mut page = pdf.page(Ref::new(3));
+    page.parent(Ref::new(2));
+    page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+    page.contents(Ref::new(4));
+    page.resources();
+    page.finish();
+    pdf.stream(Ref::new(4), content);
+    let input = pdf.finish();
+
+    let annotations = [(
+        0,
+        vec![Annotation::Text(TextAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 100.0, 124.0, 124.0],
+                ..Default::default()
+            },
+            open: false,
+            icon: "Note".to_string(),
+            rich_text: None,
+            default_style: None,
+        })],
+    )];
+    let options = WriteOptions {
+        preserve_content_streams: true,
+        compress_streams: false,
+        use_object_streams: true,
+        ..Default::default()
+    };
+    let saved =
+        save_annotations_with_options(&input, &annotations, &options).expect("save should succeed");
+
+    let pdf = hayro_syntax::Pdf::new(saved).expect("packed pdf should parse");
+    assert_eq!(pdf.pages().len(), 1);
+    assert_eq!(pdf.pages()[0].page_stream(), Some(content.as_slice()));
+    assert_eq!(page_annotation_dicts(&pdf, 0).len(), 1);
+}
+
+#[test]
+fn output_intent_is_omitted_by_default() {
+    let input = create_blank_pdf(1);
+    let saved = save_annotations(&input, &[]).expect("save should succeed");
+    let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+
+    let root = pdf
+        .xref()
+        .get::<Dict<'_>>(pdf.xref().root_id())
+        .expect("catalog should parse");
+    assert!(!root.contains_key(b"OutputIntents".as_ref()));
+}
+
+#[test]
+fn annots_array_style_controls_annots_representation() {
+    let input = create_blank_pdf(1);
+    let annotations = [(
+        0,
+        vec![Annotation::Text(TextAnnot {
+            base: AnnotationBase {
+                rect: [100.0, 100.0, 124.0, 124.0],
                 ..Default::default()
             },
-            quad_points: vec![20.0, 60.0, 110.0, 60.0, 20.0, 50.0, 110.0, 50.0],
-        }),
-        Annotation::Squiggly(SquigglyAnnot {
+            open: false,
+            icon: "Note".to_string(),
+            rich_text: None,
+            default_style: None,
+        })],
+    )];
+
+    for (style, expect_indirect) in [
+        (AnnotsArrayStyle::Indirect, true),
+        (AnnotsArrayStyle::Inline, false),
+    ] {
+        let options = WriteOptions {
+            annots_array_style: style,
+            ..Default::default()
+        };
+        let saved = save_annotations_with_options(&input, &annotations, &options)
+            .expect("save should succeed");
+        let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+
+        let page = pdf.pages()[0].raw().clone();
+        assert_eq!(
+            page.get_ref(ANNOTS as &[u8]).is_some(),
+            expect_indirect,
+            "{style:?} should control whether /Annots is a reference"
+        );
+        assert_eq!(page_annotation_dicts(&pdf, 0).len(), 1);
+    }
+}
+
+#[test]
+fn preserve_content_streams_keeps_original_stream_bytes() {
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+    use pdf_writer::{Filter, Finish, Pdf, Rect, Ref};
+    use std::io::Write;
+
+    let content = b"0 0 1 rg 100 100 200 200 re f 1 0 0 rg 150 150 50 50 re f";
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(content).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(Ref::new(1)).pages(Ref::new(2));
+    pdf.pages(Ref::new(2)).kids([Ref::new(3)]).count(1);
+    let mut page = pdf.page(Ref::new(3));
+    page.parent(Ref::new(2));
+    page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+    page.contents(Ref::new(4));
+    page.resources();
+    page.finish();
+    pdf.stream(Ref::new(4), &compressed)
+        .filter(Filter::FlateDecode);
+    let input = pdf.finish();
+
+    let annotations = [(
+        0,
+        vec![Annotation::Text(TextAnnot {
             base: AnnotationBase {
-                rect: [20.0, 65.0, 110.0, 75.0],
+                rect: [100.0, 100.0, 124.0, 124.0],
                 ..Default::default()
             },
-            quad_points: vec![20.0, 75.0, 110.0, 75.0, 20.0, 65.0, 110.0, 65.0],
-        }),
-        Annotation::FreeText(FreeTextAnnot {
+            open: false,
+            icon: "Note".to_string(),
+            rich_text: None,
+            default_style: None,
+        })],
+    )];
+    let options = WriteOptions {
+        preserve_content_streams: true,
+        ..Default::default()
+    };
+    let saved =
+        save_annotations_with_options(&input, &annotations, &options).expect("save should succeed");
+    assert!(
+        saved.windows(compressed.len()).any(|w| w == compressed),
+        "compressed content stream should be copied byte-for-byte"
+    );
+
+    let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+    let page = &pdf.pages()[0];
+    let contents = page
+        .raw()
+        .get::<Stream<'_>>(b"Contents".as_ref())
+        .expect("page should have a content stream");
+    assert_eq!(contents.raw_data().as_ref(), compressed.as_slice());
+    assert_eq!(page.page_stream(), Some(content.as_slice()));
+    assert_eq!(page_annotation_dicts(&pdf, 0).len(), 1);
+}
+
+/// Create a single-page PDF whose page already has a text annotation, with the
+/// `/Annots` array either inline or as a separate object.
+fn create_pdf_with_annotation(indirect_annots: bool) -> Vec<u8> {
+    use pdf_writer::{Finish, Pdf, Rect, Ref};
+
+    let page_id = Ref::new(3);
+    let annot_id = Ref::new(4);
+    let annots_id = Ref::new(5);
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(Ref::new(1)).pages(Ref::new(2));
+    pdf.pages(Ref::new(2)).kids([page_id]).count(1);
+
+    let mut page = pdf.page(page_id);
+    page.parent(Ref::new(2));
+    page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+    page.resources();
+    if indirect_annots {
+        page.pair(pdf_writer::Name(b"Annots"), annots_id);
+    } else {
+        page.annotations([annot_id]);
+    }
+    page.finish();
+
+    let mut annot = pdf.annotation(annot_id);
+    annot.subtype(pdf_writer::types::AnnotationType::Text);
+    annot.rect(Rect::new(10.0, 10.0, 34.0, 34.0));
+    annot.contents(pdf_writer::TextStr("Existing comment"));
+    annot.pair(pdf_writer::Name(b"P"), page_id);
+    annot.finish();
+
+    if indirect_annots {
+        pdf.indirect(annots_id).array().item(annot_id);
+    }
+
+    pdf.finish()
+}
+
+#[test]
+fn new_annotations_are_merged_with_existing_annots() {
+    let highlight = Annotation::Highlight(HighlightAnnot {
+        base: AnnotationBase {
+            rect: [100.0, 700.0, 300.0, 720.0],
+            color: Some(AnnotColor::yellow()),
+            ..Default::default()
+        },
+        quad_points: vec![100.0, 720.0, 300.0, 720.0, 100.0, 700.0, 300.0, 700.0],
+    });
+    let save_fns: [fn(&[u8], &[(usize, Vec<Annotation>)]) -> Result<Vec<u8>, SaveError>; 2] =
+        [save_annotations, save_annotations_incremental];
+
+    for indirect_annots in [false, true] {
+        for save in save_fns {
+            let input = create_pdf_with_annotation(indirect_annots);
+            let saved = save(&input, &[(0, vec![highlight.clone()])]).expect("save should succeed");
+            let pdf = hayro_syntax::Pdf::new(saved).expect("saved pdf should parse");
+
+            let annots = page_annotation_dicts(&pdf, 0);
+            let subtypes = annots.iter().map(subtype_bytes).collect::<Vec<_>>();
+            assert_eq!(
+                subtypes,
+                vec![b"Text".to_vec(), b"Highlight".to_vec()],
+                "indirect /Annots: {indirect_annots}"
+            );
+
+            let contents = annots[0]
+                .get::<PdfString>(b"Contents".as_ref())
+                .expect("existing annotation should keep its contents");
+            assert_eq!(contents.as_bytes(), b"Existing comment");
+        }
+    }
+}
+
+#[test]
+fn read_annotations_maps_written_annotations_back() {
+    let input = create_blank_pdf(2);
+    let annotations = vec![
+        Annotation::Highlight(HighlightAnnot {
             base: AnnotationBase {
-                rect: [120.0, 20.0, 260.0, 70.0],
+                rect: [100.0, 700.0, 300.0, 720.0],
+                color: Some(AnnotColor::yellow()),
+                author: Some("Reviewer".to_string()),
+                contents: Some("Check this".to_string()),
                 ..Default::default()
             },
-            text: "hello".to_string(),
-            font_size: 12.0,
-            default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+            quad_points: vec![100.0, 720.0, 300.0, 720.0, 100.0, 700.0, 300.0, 700.0],
         }),
         Annotation::Ink(InkAnnot {
             base: AnnotationBase {
-                rect: [20.0, 100.0, 140.0, 180.0],
-                ..Default::default()
-            },
-            ink_list: vec![vec![[30.0, 110.0], [80.0, 160.0], [120.0, 120.0]]],
-            line_width: 1.5,
-        }),
-        Annotation::Square(ShapeAnnot {
-            base: AnnotationBase {
-                rect: [150.0, 100.0, 220.0, 170.0],
+                rect: [10.0, 10.0, 60.0, 60.0],
+                color: Some(AnnotColor::red()),
                 ..Default::default()
             },
-            interior_color: None,
-            line_width: 1.0,
-            is_circle: false,
+            ink_list: vec![vec![[20.0, 20.0], [50.0, 50.0]]],
+            line_width: 3.0,
+            smoothing: 0.0,
+            dash: None,
         }),
-        Annotation::Circle(ShapeAnnot {
+        Annotation::Link(LinkAnnot {
             base: AnnotationBase {
-                rect: [230.0, 100.0, 300.0, 170.0],
+                rect: [10.0, 100.0, 60.0, 120.0],
                 ..Default::default()
             },
-            interior_color: None,
-            line_width: 1.0,
-            is_circle: true,
+            uri: None,
+            dest_page: Some(1),
         }),
-        Annotation::Line(LineAnnot {
+        Annotation::TextField(TextFieldAnnot {
             base: AnnotationBase {
-                rect: [20.0, 200.0, 160.0, 260.0],
+                rect: [10.0, 200.0, 160.0, 220.0],
                 ..Default::default()
             },
-            start: [20.0, 200.0],
-            end: [160.0, 260.0],
-            line_width: 2.0,
+            field_name: "name".to_string(),
+            value: Some("Jane Doe".to_string()),
+            default_value: None,
+            max_len: Some(20),
+            default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
+            read_only: false,
+            required: true,
+            multiline: false,
+            alignment: TextAlign::Left,
+            down_appearance: None,
+            rollover_appearance: None,
         }),
-        Annotation::Text(TextAnnot {
-            base: AnnotationBase {
-                rect: [170.0, 200.0, 194.0, 224.0],
-                ..Default::default()
+    ];
+    let pdf = save_and_parse(&input, &[(0, annotations)]);
+
+    let read = read_annotations(&pdf, 0);
+    assert_eq!(read.len(), 4);
+    assert!(read_annotations(&pdf, 1).is_empty());
+    assert!(read_annotations(&pdf, 5).is_empty());
+
+    let Annotation::Highlight(highlight) = &read[0] else {
+        panic!("expected a highlight, got {:?}", read[0]);
+    };
+    assert_eq!(highlight.base.rect, [100.0, 700.0, 300.0, 720.0]);
+    assert_eq!(highlight.base.color, Some(AnnotColor::yellow()));
+    assert_eq!(highlight.base.author.as_deref(), Some("Reviewer"));
+    assert_eq!(highlight.base.contents.as_deref(), Some("Check this"));
+    assert_eq!(highlight.base.flags, 4);
+    assert_eq!(highlight.quad_points.len(), 8);
+
+    let Annotation::Ink(ink) = &read[1] else {
+        panic!("expected ink, got {:?}", read[1]);
+    };
+    assert_eq!(ink.ink_list, vec![vec![[20.0, 20.0], [50.0, 50.0]]]);
+    assert_eq!(ink.line_width, 3.0);
+
+    let Annotation::Link(link) = &read[2] else {
+        panic!("expected a link, got {:?}", read[2]);
+    };
+    assert_eq!(link.dest_page, Some(1));
+
+    let Annotation::TextField(field) = &read[3] else {
+        panic!("expected a text field, got {:?}", read[3]);
+    };
+    assert_eq!(field.field_name, "name");
+    assert_eq!(field.value.as_deref(), Some("Jane Doe"));
+    assert_eq!(field.max_len, Some(20));
+    assert!(field.required);
+    assert!(!field.read_only);
+}
+
+#[test]
+fn page_boxes_reports_distinct_media_and_crop_boxes() {
+    use pdf_writer::{Pdf, Rect, Ref};
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(Ref::new(1)).pages(Ref::new(2));
+    pdf.pages(Ref::new(2))
+        .kids([Ref::new(3)])
+        .count(1)
+        .media_box(Rect::new(0.0, 0.0, 612.0, 792.0));
+    pdf.page(Ref::new(3))
+        .parent(Ref::new(2))
+        .crop_box(Rect::new(36.0, 36.0, 576.0, 756.0))
+        .trim_box(Rect::new(40.0, 40.0, 572.0, 752.0));
+    let pdf_data = pdf.finish();
+
+    let pdf = hayro_syntax::Pdf::new(pdf_data).expect("PDF should parse");
+    let boxes = page_boxes(&pdf.pages()[0]);
+    assert_eq!(boxes.media_box, [0.0, 0.0, 612.0, 792.0]);
+    assert_eq!(boxes.crop_box, [36.0, 36.0, 576.0, 756.0]);
+    assert_eq!(boxes.trim_box, Some([40.0, 40.0, 572.0, 752.0]));
+    assert_eq!(boxes.bleed_box, None);
+    assert_eq!(boxes.art_box, None);
+    assert_eq!(boxes.user_unit, 1.0);
+}
+
+#[test]
+fn page_boxes_report_user_unit_and_inherited_media_box() {
+    use pdf_writer::{Pdf, Rect, Ref};
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(Ref::new(1)).pages(Ref::new(2));
+    pdf.pages(Ref::new(2))
+        .kids([Ref::new(3)])
+        .count(1)
+        .media_box(Rect::new(0.0, 0.0, 1000.0, 500.0));
+    pdf.page(Ref::new(3))
+        .parent(Ref::new(2))
+        .pair(pdf_writer::Name(b"UserUnit"), 2.5);
+    let pdf_data = pdf.finish();
+
+    let pdf = hayro_syntax::Pdf::new(pdf_data).expect("PDF should parse");
+    let boxes = page_boxes(&pdf.pages()[0]);
+    assert_eq!(boxes.media_box, [0.0, 0.0, 1000.0, 500.0]);
+    assert_eq!(boxes.crop_box, boxes.media_box);
+    assert_eq!(boxes.user_unit, 2.5);
+}
+
+#[test]
+fn extract_comments_nests_replies_under_parent() {
+    use pdf_writer::{Finish, Pdf, Rect, Ref, TextStr};
+
+    let page_id = Ref::new(3);
+    let note_id = Ref::new(4);
+    let reply_id = Ref::new(5);
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(Ref::new(1)).pages(Ref::new(2));
+    pdf.pages(Ref::new(2)).kids([page_id]).count(1);
+
+    let mut page = pdf.page(page_id);
+    page.parent(Ref::new(2));
+    page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+    page.resources();
+    page.annotations([note_id, reply_id]);
+    page.finish();
+
+    let mut note = pdf.annotation(note_id);
+    note.subtype(pdf_writer::types::AnnotationType::Text);
+    note.rect(Rect::new(10.0, 10.0, 34.0, 34.0));
+    note.pair(pdf_writer::Name(b"T"), TextStr("Alice"));
+    note.contents(TextStr("Please check this"));
+    note.pair(pdf_writer::Name(b"M"), TextStr("D:20240101120000Z"));
+    note.finish();
+
+    let mut reply = pdf.annotation(reply_id);
+    reply.subtype(pdf_writer::types::AnnotationType::Text);
+    reply.rect(Rect::new(10.0, 10.0, 34.0, 34.0));
+    reply.pair(pdf_writer::Name(b"T"), TextStr("Bob"));
+    reply.contents(TextStr("Done"));
+    reply.pair(pdf_writer::Name(b"IRT"), note_id);
+    reply.finish();
+
+    let pdf = hayro_syntax::Pdf::new(pdf.finish()).expect("PDF should parse");
+    let comments = extract_comments(&pdf);
+
+    assert_eq!(
+        comments.len(),
+        1,
+        "the reply should not be a top-level comment"
+    );
+    let note = &comments[0];
+    assert_eq!(note.author.as_deref(), Some("Alice"));
+    assert_eq!(note.contents.as_deref(), Some("Please check this"));
+    assert_eq!(note.modified.as_deref(), Some("D:20240101120000Z"));
+    assert_eq!(note.subtype, "Text");
+    assert_eq!(note.in_reply_to, None);
+
+    assert_eq!(note.replies.len(), 1);
+    let reply = &note.replies[0];
+    assert_eq!(reply.author.as_deref(), Some("Bob"));
+    assert_eq!(reply.contents.as_deref(), Some("Done"));
+    assert_eq!(reply.index, 1);
+    assert_eq!(reply.in_reply_to, Some((0, 0)));
+}
+
+#[test]
+fn read_outline_resolves_destinations_and_nesting() {
+    use pdf_writer::{Finish, Name, Pdf, Rect, Ref, TextStr};
+
+    let page_refs = [Ref::new(3), Ref::new(4), Ref::new(5)];
+    let outline_id = Ref::new(6);
+    let intro_id = Ref::new(7);
+    let details_id = Ref::new(8);
+    let appendix_id = Ref::new(9);
+
+    let mut pdf = Pdf::new();
+    let mut catalog = pdf.catalog(Ref::new(1));
+    catalog.pages(Ref::new(2));
+    catalog.outlines(outline_id);
+    catalog
+        .insert(Name(b"Dests"))
+        .dict()
+        .insert(Name(b"appendix"))
+        .array()
+        .item(page_refs[1])
+        .item(Name(b"Fit"));
+    catalog.finish();
+    pdf.pages(Ref::new(2)).kids(page_refs).count(3);
+    for page_ref in page_refs {
+        let mut page = pdf.page(page_ref);
+        page.parent(Ref::new(2));
+        page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+        page.resources();
+        page.finish();
+    }
+
+    let mut outline = pdf.indirect(outline_id).dict();
+    outline.pair(Name(b"First"), intro_id);
+    outline.pair(Name(b"Last"), appendix_id);
+    outline.finish();
+
+    // An explicit destination, with a child linking through a `GoTo` action.
+    let mut intro = pdf.indirect(intro_id).dict();
+    intro.pair(Name(b"Title"), TextStr("Introduction"));
+    intro.pair(Name(b"Parent"), outline_id);
+    intro.pair(Name(b"Next"), appendix_id);
+    intro.pair(Name(b"First"), details_id);
+    intro.pair(Name(b"Last"), details_id);
+    intro
+        .insert(Name(b"Dest"))
+        .array()
+        .item(page_refs[0])
+        .item(Name(b"Fit"));
+    intro.finish();
+
+    let mut details = pdf.indirect(details_id).dict();
+    details.pair(Name(b"Title"), TextStr("Details"));
+    details.pair(Name(b"Parent"), intro_id);
+    let mut action = details.insert(Name(b"A")).dict();
+    action.pair(Name(b"S"), Name(b"GoTo"));
+    action
+        .insert(Name(b"D"))
+        .array()
+        .item(page_refs[2])
+        .item(Name(b"Fit"));
+    action.finish();
+    details.finish();
+
+    // A named destination.
+    let mut appendix = pdf.indirect(appendix_id).dict();
+    appendix.pair(Name(b"Title"), TextStr("Appendix"));
+    appendix.pair(Name(b"Parent"), outline_id);
+    appendix.pair(Name(b"Prev"), intro_id);
+    appendix.pair(Name(b"Dest"), Name(b"appendix"));
+    appendix.finish();
+
+    let pdf = hayro_syntax::Pdf::new(pdf.finish()).expect("PDF should parse");
+    let outline = read_outline(&pdf);
+
+    assert_eq!(
+        outline,
+        [
+            OutlineItem {
+                title: "Introduction".to_string(),
+                page: Some(0),
+                children: vec![OutlineItem {
+                    title: "Details".to_string(),
+                    page: Some(2),
+                    children: Vec::new(),
+                }],
             },
-            open: false,
-            icon: "Note".to_string(),
-        }),
-        Annotation::Link(LinkAnnot {
-            base: AnnotationBase {
-                rect: [200.0, 200.0, 320.0, 220.0],
-                ..Default::default()
+            OutlineItem {
+                title: "Appendix".to_string(),
+                page: Some(1),
+                children: Vec::new(),
             },
-            uri: Some("https://example.com".to_string()),
-            dest_page: None,
-        }),
-    ];
+        ]
+    );
+}
 
-    let pdf = save_and_parse(&input, &[(0, annotations)]);
+#[test]
+fn swapped_rect_corners_are_normalized_when_written() {
+    let input = create_blank_pdf(1);
+    let square = Annotation::Square(ShapeAnnot {
+        base: AnnotationBase {
+            rect: [300.0, 200.0, 100.0, 100.0],
+            ..Default::default()
+        },
+        interior_color: None,
+        line_width: 2.0,
+        is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![square])]);
     let dicts = page_annotation_dicts(&pdf, 0);
-    assert_eq!(dicts.len(), 11, "all annotations should be present");
+    assert_eq!(
+        dicts[0].get::<[f32; 4]>(b"Rect".as_ref()),
+        Some([100.0, 100.0, 300.0, 200.0])
+    );
+
+    let appearance = dicts[0]
+        .get::<Dict<'_>>(b"AP".as_ref())
+        .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+        .expect("square should have an appearance stream");
+    assert_eq!(
+        appearance.dict().get::<[f32; 4]>(b"BBox".as_ref()),
+        Some([0.0, 0.0, 200.0, 100.0])
+    );
+    let content = appearance.decoded().expect("appearance should decode");
+    assert!(
+        String::from_utf8_lossy(&content).contains("1 1 198 98 re"),
+        "{}",
+        String::from_utf8_lossy(&content)
+    );
+}
+
+#[test]
+fn rotated_stamp_writes_appearance_matrix_and_grown_rect() {
+    let mut png = Vec::new();
+    image::RgbaImage::from_pixel(4, 4, image::Rgba([0, 0, 255, 255]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+
+    let input = create_blank_pdf(1);
+    let stamp = Annotation::Stamp(StampAnnot {
+        base: AnnotationBase {
+            rect: [100.0, 100.0, 200.0, 200.0],
+            rotation: 45.0,
+            ..Default::default()
+        },
+        image_data: Arc::new(png),
+        format: ImageFormat::Png,
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![stamp])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+
+    // The rotated square covers a square with the length of its diagonal,
+    // around the same center.
+    let half_diagonal = 50.0 * std::f32::consts::SQRT_2;
+    let rect = dict.get::<[f32; 4]>(b"Rect".as_ref()).unwrap();
+    let expected = [
+        150.0 - half_diagonal,
+        150.0 - half_diagonal,
+        150.0 + half_diagonal,
+        150.0 + half_diagonal,
+    ];
+    assert!(
+        rect.iter().zip(expected).all(|(a, b)| (a - b).abs() < 0.01),
+        "{rect:?}"
+    );
+
+    let appearance = dict
+        .get::<Dict<'_>>(b"AP".as_ref())
+        .and_then(|ap| ap.get::<Stream<'_>>(b"N".as_ref()))
+        .expect("stamp should have a normal appearance");
+    assert_eq!(
+        appearance.dict().get::<[f32; 4]>(b"BBox".as_ref()),
+        Some([0.0, 0.0, 100.0, 100.0])
+    );
+    let matrix = appearance
+        .dict()
+        .get::<[f32; 6]>(b"Matrix".as_ref())
+        .expect("rotated appearance should have a /Matrix");
+    let cos = std::f32::consts::FRAC_1_SQRT_2;
+    let expected = [cos, cos, -cos, cos, half_diagonal, 0.0];
+    assert!(
+        matrix
+            .iter()
+            .zip(expected)
+            .all(|(a, b)| (a - b).abs() < 0.01),
+        "{matrix:?}"
+    );
+}
+
+#[test]
+fn widget_down_and_rollover_appearances_are_written() {
+    let input = create_blank_pdf(1);
+    let text_field = Annotation::TextField(TextFieldAnnot {
+        base: AnnotationBase {
+            rect: [40.0, 40.0, 220.0, 72.0],
+            ..Default::default()
+        },
+        field_name: "styled".to_string(),
+        value: None,
+        default_value: None,
+        max_len: None,
+        default_appearance: "0 0 0 rg /Helv 10 Tf".to_string(),
+        read_only: false,
+        required: false,
+        multiline: false,
+        alignment: TextAlign::Left,
+        down_appearance: Some(b"0.5 g 0 0 180 32 re f".to_vec()),
+        rollover_appearance: Some(b"0.9 g 0 0 180 32 re f".to_vec()),
+    });
+
+    let pdf = save_and_parse(&input, &[(0, vec![text_field])]);
+    let dict = &page_annotation_dicts(&pdf, 0)[0];
+    let ap = dict
+        .get::<Dict<'_>>(b"AP".as_ref())
+        .expect("widget should have /AP");
+    let content = |key: &[u8]| {
+        let stream = ap
+            .get::<Stream<'_>>(key)
+            .unwrap_or_else(|| panic!("/AP should have a {key:?} stream"));
+        String::from_utf8(stream.decoded().unwrap().to_vec()).unwrap()
+    };
+
+    assert!(!content(b"N").is_empty());
+    assert!(content(b"D").contains("0.5 g"));
+    assert!(content(b"R").contains("0.9 g"));
+
+    let read = read_annotations(&pdf, 0);
+    let Some(Annotation::TextField(field)) = read.first() else {
+        panic!("expected the text field to be read back, got {read:?}");
+    };
+    assert_eq!(
+        field.down_appearance.as_deref(),
+        Some(b"0.5 g 0 0 180 32 re f".as_ref())
+    );
+}
+
+#[test]
+fn extract_links_resolves_uri_and_page_targets() {
+    use pdf_writer::{Dict as WriterDict, Finish, Name, Pdf, Rect, Ref, Str};
+
+    let page_refs = [Ref::new(3), Ref::new(4)];
+    let link_refs = [
+        Ref::new(5),
+        Ref::new(6),
+        Ref::new(7),
+        Ref::new(8),
+        Ref::new(9),
+    ];
+
+    let mut pdf = Pdf::new();
+    let mut catalog = pdf.catalog(Ref::new(1));
+    catalog.pages(Ref::new(2));
+    catalog
+        .insert(Name(b"Dests"))
+        .dict()
+        .insert(Name(b"second"))
+        .array()
+        .item(page_refs[1])
+        .item(Name(b"Fit"));
+    catalog.finish();
+    pdf.pages(Ref::new(2)).kids(page_refs).count(2);
+    for (i, page_ref) in page_refs.into_iter().enumerate() {
+        let mut page = pdf.page(page_ref);
+        page.parent(Ref::new(2));
+        page.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+        page.resources();
+        if i == 0 {
+            page.annotations(link_refs);
+        }
+        page.finish();
+    }
+
+    let mut link = |id: Ref, rect: [f32; 4], target: &dyn Fn(&mut WriterDict<'_>)| {
+        let mut dict = pdf.indirect(id).dict();
+        dict.pair(Name(b"Type"), Name(b"Annot"));
+        dict.pair(Name(b"Subtype"), Name(b"Link"));
+        dict.insert(Name(b"Rect")).array().items(rect);
+        target(&mut dict);
+        dict.finish();
+    };
+    let action = |dict: &mut WriterDict<'_>, kind: &[u8], write: &dyn Fn(&mut WriterDict<'_>)| {
+        let mut action = dict.insert(Name(b"A")).dict();
+        action.pair(Name(b"S"), Name(kind));
+        write(&mut action);
+        action.finish();
+    };
+
+    // A URI action, with the rect corners swapped.
+    link(link_refs[0], [200.0, 120.0, 100.0, 100.0], &|dict| {
+        action(dict, b"URI", &|a| {
+            a.pair(Name(b"URI"), Str(b"https://example.com"));
+        });
+    });
+    // A `GoTo` action, an explicit and a named destination.
+    link(link_refs[1], [100.0, 200.0, 200.0, 220.0], &|dict| {
+        action(dict, b"GoTo", &|a| {
+            a.insert(Name(b"D"))
+                .array()
+                .item(page_refs[1])
+                .item(Name(b"Fit"));
+        });
+    });
+    link(link_refs[2], [100.0, 300.0, 200.0, 320.0], &|dict| {
+        dict.insert(Name(b"Dest"))
+            .array()
+            .item(page_refs[0])
+            .item(Name(b"Fit"));
+    });
+    link(link_refs[3], [100.0, 400.0, 200.0, 420.0], &|dict| {
+        dict.pair(Name(b"Dest"), Name(b"second"));
+    });
+    // A `Launch` action, which isn't supported.
+    link(link_refs[4], [100.0, 500.0, 200.0, 520.0], &|dict| {
+        action(dict, b"Launch", &|a| {
+            a.pair(Name(b"F"), Str(b"app.exe"));
+        });
+    });
+
+    let pdf = hayro_syntax::Pdf::new(pdf.finish()).expect("PDF should parse");
+    let links = extract_links(&pdf, 0);
+
+    assert_eq!(
+        links,
+        [
+            ExtractedLink {
+                rect: [100.0, 100.0, 200.0, 120.0],
+                target: LinkTarget::Uri("https://example.com".to_string()),
+            },
+            ExtractedLink {
+                rect: [100.0, 200.0, 200.0, 220.0],
+                target: LinkTarget::Page(1),
+            },
+            ExtractedLink {
+                rect: [100.0, 300.0, 200.0, 320.0],
+                target: LinkTarget::Page(0),
+            },
+            ExtractedLink {
+                rect: [100.0, 400.0, 200.0, 420.0],
+                target: LinkTarget::Page(1),
+            },
+        ]
+    );
+    assert!(extract_links(&pdf, 1).is_empty());
 }
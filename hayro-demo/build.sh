@@ -17,6 +17,7 @@ cp www/index.html dist/
 cp www/styles.css dist/
 cp www/index.js dist/
 cp www/viewer_math.js dist/
+cp www/render_worker.js dist/
 
 # Copy generated WASM files
 echo "Copying WASM files..."
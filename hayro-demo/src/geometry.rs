@@ -1,3 +1,5 @@
+use hayro_annot::screen_to_pdf;
+
 pub(crate) fn rect_from_quad_points(quad_points: &[f32]) -> [f32; 4] {
     if quad_points.len() < 8 {
         return [0.0, 0.0, 0.0, 0.0];
@@ -45,6 +47,60 @@ pub(crate) fn rect_from_points(points: &[[f32; 2]], padding: f32) -> [f32; 4] {
     ]
 }
 
+/// Whether any quad of `a` touches any quad of `b`, either as neighbours on
+/// the same line or as consecutive lines.
+pub(crate) fn quad_groups_adjacent(a: &[f32], b: &[f32]) -> bool {
+    a.chunks_exact(8)
+        .any(|qa| b.chunks_exact(8).any(|qb| quads_adjacent(qa, qb)))
+}
+
+fn quads_adjacent(a: &[f32], b: &[f32]) -> bool {
+    let ra = rect_from_quad_points(a);
+    let rb = rect_from_quad_points(b);
+    let tolerance = (ra[3] - ra[1]).min(rb[3] - rb[1]).max(0.0) * 0.5;
+    let x_overlap = ra[2].min(rb[2]) - ra[0].max(rb[0]);
+    let y_overlap = ra[3].min(rb[3]) - ra[1].max(rb[1]);
+
+    // Same line with a small horizontal gap, or stacked lines with a small vertical gap.
+    (y_overlap >= tolerance && x_overlap >= -tolerance)
+        || (x_overlap >= 0.0 && y_overlap >= -tolerance)
+}
+
+/// Convert screen-space line rectangles, as returned by `Range.getClientRects()`,
+/// into a PDF `/QuadPoints` array.
+///
+/// `rects_flat` holds `[left, top, width, height]` for each rectangle, relative to
+/// the rendered page. Every quad lists its corners in the order that highlights
+/// expect: upper-left, upper-right, lower-left, lower-right, where "upper" is the
+/// top of the text as displayed, so the order is also correct on rotated pages.
+pub(crate) fn quad_points_from_client_rects(
+    rects_flat: &[f32],
+    page_width: f32,
+    page_height: f32,
+    crop_box: [f32; 4],
+    rotation: u32,
+    scale: f32,
+) -> Vec<f32> {
+    let to_pdf =
+        |x: f32, y: f32| screen_to_pdf(x, y, page_width, page_height, crop_box, rotation, scale);
+
+    let mut quad_points = Vec::with_capacity(rects_flat.len() * 2);
+    for rect in rects_flat.chunks_exact(4) {
+        let [left, top, width, height] = [rect[0], rect[1], rect[2], rect[3]];
+        if width <= 0.0 || height <= 0.0 {
+            continue;
+        }
+
+        let (right, bottom) = (left + width, top + height);
+        for (x, y) in [(left, top), (right, top), (left, bottom), (right, bottom)] {
+            let (pdf_x, pdf_y) = to_pdf(x, y);
+            quad_points.extend([pdf_x, pdf_y]);
+        }
+    }
+
+    quad_points
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -60,4 +116,35 @@ mod tests {
         let rect = rect_from_points(&[[2.0, 3.0], [10.0, 8.0], [4.0, 7.0]], 2.0);
         assert_eq!(rect, [0.0, 1.0, 12.0, 10.0]);
     }
+
+    #[test]
+    fn quad_groups_adjacent_on_same_line() {
+        let a = [0.0, 10.0, 50.0, 10.0, 0.0, 0.0, 50.0, 0.0];
+        let b = [52.0, 10.0, 90.0, 10.0, 52.0, 0.0, 90.0, 0.0];
+        let far = [200.0, 10.0, 250.0, 10.0, 200.0, 0.0, 250.0, 0.0];
+        assert!(quad_groups_adjacent(&a, &b));
+        assert!(!quad_groups_adjacent(&a, &far));
+    }
+
+    #[test]
+    fn client_rects_become_ordered_quad_groups() {
+        let crop_box = [0.0, 0.0, 612.0, 792.0];
+        let rects = [10.0, 20.0, 100.0, 12.0, 10.0, 40.0, 50.0, 12.0];
+        let quads = quad_points_from_client_rects(&rects, 612.0, 792.0, crop_box, 0, 2.0);
+
+        assert_eq!(
+            quads,
+            vec![
+                5.0, 782.0, 55.0, 782.0, 5.0, 776.0, 55.0, 776.0, // first line
+                5.0, 772.0, 30.0, 772.0, 5.0, 766.0, 30.0, 766.0, // second line
+            ]
+        );
+    }
+
+    #[test]
+    fn client_rects_skip_empty_rects() {
+        let rects = [10.0, 20.0, 0.0, 12.0, 10.0, 20.0];
+        let quads = quad_points_from_client_rects(&rects, 612.0, 792.0, [0.0; 4], 0, 1.0);
+        assert!(quads.is_empty());
+    }
 }
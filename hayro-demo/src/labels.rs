@@ -0,0 +1,151 @@
+use hayro::hayro_syntax::Pdf;
+use hayro::hayro_syntax::object::{Array, Dict, Name, String as PdfString};
+
+/// The maximum depth of the `/PageLabels` number tree, to guard against cycles.
+const MAX_TREE_DEPTH: usize = 32;
+
+/// The page label of the page at `page_idx`, as defined by the document's
+/// `/PageLabels` number tree.
+///
+/// Returns `None` if the document doesn't define labels for the page.
+pub(crate) fn page_label(pdf: &Pdf, page_idx: usize) -> Option<String> {
+    let catalog = pdf.xref().get::<Dict<'_>>(pdf.xref().root_id())?;
+    let tree = catalog.get::<Dict<'_>>(b"PageLabels".as_ref())?;
+
+    let mut ranges = Vec::new();
+    collect_ranges(&tree, 0, &mut ranges);
+    let (start, label) = ranges
+        .into_iter()
+        .filter(|(start, _)| *start <= page_idx)
+        .max_by_key(|(start, _)| *start)?;
+
+    let prefix = label
+        .get::<PdfString>(b"P".as_ref())
+        .map(|p| decode_text_string(p.as_bytes()))
+        .unwrap_or_default();
+    let first = label
+        .get::<i32>(b"St".as_ref())
+        .filter(|st| *st >= 1)
+        .unwrap_or(1) as usize;
+    let number = first + (page_idx - start);
+
+    let numeral = match label.get::<Name>(b"S".as_ref()).as_deref() {
+        Some(b"D") => number.to_string(),
+        Some(b"R") => roman_numeral(number),
+        Some(b"r") => roman_numeral(number).to_lowercase(),
+        Some(b"A") => letter_numeral(number),
+        Some(b"a") => letter_numeral(number).to_lowercase(),
+        // Labels without a style only consist of the prefix.
+        _ => String::new(),
+    };
+
+    Some(prefix + &numeral)
+}
+
+/// Collect the page index and label dictionary of each range in a
+/// `/PageLabels` number tree node.
+fn collect_ranges<'a>(node: &Dict<'a>, depth: usize, ranges: &mut Vec<(usize, Dict<'a>)>) {
+    if depth > MAX_TREE_DEPTH {
+        return;
+    }
+
+    if let Some(nums) = node.get::<Array<'_>>(b"Nums".as_ref()) {
+        let mut iter = nums.flex_iter();
+        while let Some(start) = iter.next::<i32>() {
+            let Some(label) = iter.next::<Dict<'_>>() else {
+                break;
+            };
+            if let Ok(start) = usize::try_from(start) {
+                ranges.push((start, label));
+            }
+        }
+    }
+
+    if let Some(kids) = node.get::<Array<'_>>(b"Kids".as_ref()) {
+        for kid in kids.iter::<Dict<'_>>() {
+            collect_ranges(&kid, depth + 1, ranges);
+        }
+    }
+}
+
+/// Format `number` as an uppercase roman numeral.
+fn roman_numeral(mut number: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+
+    let mut result = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            result.push_str(numeral);
+            number -= value;
+        }
+    }
+
+    result
+}
+
+/// Format `number` with uppercase letters: A to Z for the first 26 pages,
+/// AA to ZZ for the next 26, and so on.
+fn letter_numeral(number: usize) -> String {
+    if number == 0 {
+        return String::new();
+    }
+
+    let letter = char::from(b'A' + ((number - 1) % 26) as u8);
+    std::iter::repeat_n(letter, (number - 1) / 26 + 1).collect()
+}
+
+/// Decode a PDF text string, which is either UTF-16BE with a byte order mark
+/// or (approximately) `PDFDocEncoding`.
+fn decode_text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|b| char::from(*b)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roman_and_letter_numerals() {
+        assert_eq!(roman_numeral(4), "IV");
+        assert_eq!(roman_numeral(1994), "MCMXCIV");
+        assert_eq!(letter_numeral(1), "A");
+        assert_eq!(letter_numeral(26), "Z");
+        assert_eq!(letter_numeral(28), "BB");
+    }
+
+    #[test]
+    fn page_labels_follow_number_tree() {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../hayro-tests/pdfs/load/issue50.pdf");
+        let data = std::fs::read(&path).expect("fixture should exist");
+        let pdf = Pdf::new(data).expect("fixture should parse");
+
+        // The first page is labelled with the prefix "0", the others are
+        // numbered from 1.
+        assert_eq!(page_label(&pdf, 0).as_deref(), Some("0"));
+        assert_eq!(page_label(&pdf, 1).as_deref(), Some("1"));
+        assert_eq!(page_label(&pdf, 4).as_deref(), Some("4"));
+    }
+}
@@ -1,20 +1,34 @@
 mod geometry;
+mod labels;
 mod operations;
+mod render;
 
 use console_error_panic_hook;
-use hayro::RenderSettings;
-use hayro::hayro_interpret::{InterpreterSettings, extract_text_spans};
-use hayro::hayro_syntax::Pdf;
+use hayro::hayro_interpret::{
+    InterpreterSettings, InterpreterWarning, TextSpan, extract_text_spans, layer_names,
+};
+use hayro::hayro_syntax::{DecryptionError, LoadPdfError, Pdf};
+use hayro::{BgMode, RenderSettings, RenderTimings};
 use hayro_annot::{
-    AnnotColor, Annotation, AnnotationBase, FreeTextAnnot, HighlightAnnot, InkAnnot, ShapeAnnot,
-    SignatureFieldAnnot, TextFieldAnnot,
+    AnnotColor, Annotation, AnnotationBase, AnnotationFlags, BorderStyle, ChoiceFieldAnnot,
+    FreeTextAnnot, HighlightAnnot, ImageFormat, InkAnnot, LinkTarget, OutlineItem, ShapeAnnot,
+    SignatureFieldAnnot, StampAnnot, TextAlign, TextFieldAnnot, VAlign, WriteOptions, page_boxes,
+    quads_for_spans,
 };
 use js_sys;
-use operations::{OperationHistory, ViewerOperation};
-use vello_cpu::color::palette::css::WHITE;
+use operations::{OperationHistory, ViewerOperation, remap_pages};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use vello_cpu::color::palette::css::{LIGHT_GRAY, WHITE};
 use wasm_bindgen::prelude::*;
 
-use crate::geometry::{rect_from_points, rect_from_quad_points};
+use crate::geometry::{quad_points_from_client_rects, rect_from_points, rect_from_quad_points};
+use crate::render::{
+    RenderedPage, dpi_scale, interpreter_settings, render_page, render_scale, thumbnail_scale,
+};
+
+pub use crate::render::PageRenderer;
 
 struct ConsoleLogger;
 
@@ -62,13 +76,79 @@ impl log::Log for ConsoleLogger {
 
 static LOGGER: ConsoleLogger = ConsoleLogger;
 
+/// The current time in milliseconds, preferring the high-resolution `performance` clock.
+fn now_ms() -> f64 {
+    web_sys::window()
+        .and_then(|window| window.performance())
+        .map(|performance| performance.now())
+        .unwrap_or_else(js_sys::Date::now)
+}
+
+/// A positioned run of text on a page, in PDF coordinates.
+#[wasm_bindgen(getter_with_clone)]
+pub struct TextSpanJs {
+    /// The text of the span.
+    pub text: String,
+    /// The left edge of the bounding box.
+    pub x0: f32,
+    /// The bottom edge of the bounding box.
+    pub y0: f32,
+    /// The right edge of the bounding box.
+    pub x1: f32,
+    /// The top edge of the bounding box.
+    pub y1: f32,
+    /// The x coordinate of the start of the baseline.
+    pub baseline_x: f32,
+    /// The y coordinate of the start of the baseline.
+    pub baseline_y: f32,
+    /// Whether the text runs vertically, from top to bottom.
+    pub vertical: bool,
+}
+
 #[wasm_bindgen]
 pub struct PdfViewer {
     pdf: Option<Pdf>,
     pdf_data: Vec<u8>,
+    /// The original bytes of a document that was opened with a password.
+    ///
+    /// `pdf_data` holds a decrypted copy for rendering and editing, but only
+    /// these bytes are saved, so that the protection isn't silently removed.
+    encrypted_data: Option<Vec<u8>>,
     current_page: usize,
     total_pages: usize,
+    /// The index in the loaded document of each page, in their current order.
+    ///
+    /// Operations are keyed by these original indices, so that deleting and
+    /// moving pages doesn't affect them.
+    page_order: Vec<usize>,
     history: OperationHistory,
+    freetext_font: Option<Arc<dyn AsRef<[u8]>>>,
+    grayscale: bool,
+    /// Whether transparent regions of pages are shown with a checkerboard
+    /// instead of a white background.
+    checkerboard: bool,
+    /// The names of the layers that are shown, or `None` to use the
+    /// document's default configuration.
+    visible_layers: Option<HashSet<String>>,
+    /// Rendered thumbnails, keyed by the page index and their size.
+    thumbnails: HashMap<(usize, u32), RenderedPage>,
+    /// Extracted text spans, keyed by the page index.
+    text_spans: HashMap<usize, Vec<TextSpan>>,
+    /// While a document is loaded with `append_bytes`, the number of pages
+    /// found in the bytes received so far.
+    lazy_pages: Option<usize>,
+    /// The length of the displayed document after each operation in the
+    /// history, while it consists of the loaded or last rebuilt document
+    /// followed by one incremental update per operation.
+    ///
+    /// The operations the document was rebuilt with all end at the length of
+    /// the rebuilt document.
+    incremental_ends: Vec<usize>,
+    /// The number of operations at the start of the history that are part of
+    /// the last rebuilt document, and can't be undone by truncating it.
+    rebuilt_operations: usize,
+    /// How long the last update of the displayed document took, in milliseconds.
+    last_update_ms: f64,
 }
 
 #[wasm_bindgen]
@@ -84,27 +164,131 @@ impl PdfViewer {
         Self {
             pdf: None,
             pdf_data: Vec::new(),
+            encrypted_data: None,
             current_page: 0,
             total_pages: 0,
+            page_order: Vec::new(),
             history: OperationHistory::default(),
+            freetext_font: None,
+            grayscale: false,
+            checkerboard: false,
+            visible_layers: None,
+            thumbnails: HashMap::new(),
+            text_spans: HashMap::new(),
+            lazy_pages: None,
+            incremental_ends: Vec::new(),
+            rebuilt_operations: 0,
+            last_update_ms: 0.0,
         }
     }
 
     #[wasm_bindgen]
     pub fn load_pdf(&mut self, data: &[u8]) -> Result<(), JsValue> {
-        let pdf = Pdf::new(data.to_vec()).map_err(|_| JsValue::from_str("Failed to parse PDF"))?;
+        self.load_pdf_with_password(data, "")
+    }
+
+    /// Load a PDF that may be encrypted, using its user or owner password.
+    ///
+    /// Fails with "Password required" if the document needs a password and none
+    /// was given, and with "Wrong password" if the given one doesn't open it.
+    #[wasm_bindgen]
+    pub fn load_pdf_with_password(&mut self, data: &[u8], password: &str) -> Result<(), JsValue> {
+        let pdf = Pdf::new_with_password(data.to_vec(), password)
+            .map_err(|e| load_error(&e, password))?;
+
+        // Edits parse the document again without a password, so keep a
+        // decrypted copy of documents that need one.
+        let (pdf, data, encrypted_data) = if password.is_empty() {
+            (pdf, data.to_vec(), None)
+        } else {
+            let decrypted = hayro_annot::decrypt_pdf(data, password)
+                .map_err(|e| JsValue::from_str(&format!("Failed to decrypt PDF: {e}")))?;
+            let pdf = Pdf::new(decrypted.clone())
+                .map_err(|_| JsValue::from_str("Failed to parse PDF"))?;
+            (pdf, decrypted, Some(data.to_vec()))
+        };
 
         let pages = pdf.pages();
 
         self.total_pages = pages.len();
-        self.pdf_data = data.to_vec();
+        self.page_order = (0..pages.len()).collect();
+        self.pdf_data = data;
+        self.encrypted_data = encrypted_data;
         self.pdf = Some(pdf);
         self.current_page = 0;
         self.history.clear();
+        self.visible_layers = None;
+        self.thumbnails.clear();
+        self.text_spans.clear();
+        self.lazy_pages = None;
+        self.incremental_ends.clear();
+        self.rebuilt_operations = 0;
 
         Ok(())
     }
 
+    /// Start loading a PDF whose bytes arrive in chunks, e.g. from a streamed
+    /// fetch. Pass the chunks to `append_bytes` as they come in.
+    #[wasm_bindgen]
+    pub fn load_pdf_lazy(&mut self) {
+        self.pdf = None;
+        self.pdf_data.clear();
+        self.encrypted_data = None;
+        self.total_pages = 0;
+        self.page_order.clear();
+        self.current_page = 0;
+        self.history.clear();
+        self.visible_layers = None;
+        self.thumbnails.clear();
+        self.text_spans.clear();
+        self.lazy_pages = Some(0);
+        self.incremental_ends.clear();
+        self.rebuilt_operations = 0;
+    }
+
+    /// Append the next chunk of a PDF started with `load_pdf_lazy`.
+    ///
+    /// The document is parsed again whenever the received bytes end with an
+    /// end-of-file marker. For linearized files this already happens after the
+    /// first page, so it can be shown while the rest is still downloading.
+    /// Pages found by later parses are appended, and pending annotations and
+    /// page edits are kept. Returns whether a page can be rendered.
+    #[wasm_bindgen]
+    pub fn append_bytes(&mut self, chunk: &[u8]) -> Result<bool, JsValue> {
+        let Some(known_pages) = self.lazy_pages else {
+            return Err(JsValue::from_str("No PDF is being loaded"));
+        };
+
+        self.pdf_data.extend_from_slice(chunk);
+        if !ends_with_eof_marker(&self.pdf_data) {
+            return Ok(self.is_ready());
+        }
+
+        // Encrypted documents need a password, so they can only be loaded at once.
+        let Ok(pdf) = Pdf::new(self.pdf_data.clone()) else {
+            return Ok(self.is_ready());
+        };
+        let pages = pdf.pages().len();
+        if pages > known_pages {
+            self.lazy_pages = Some(pages);
+            self.page_order.extend(known_pages..pages);
+            if self.pdf.is_none() {
+                self.total_pages = pages;
+                self.pdf = Some(pdf);
+            } else {
+                self.update_pages();
+            }
+        }
+
+        Ok(self.is_ready())
+    }
+
+    /// Whether a document is loaded and its pages can be rendered.
+    #[wasm_bindgen]
+    pub fn is_ready(&self) -> bool {
+        self.pdf.is_some() && self.total_pages > 0
+    }
+
     #[wasm_bindgen]
     pub fn render_current_page(
         &self,
@@ -139,6 +323,9 @@ impl PdfViewer {
     }
 
     /// Render a specific page (1-based) at a fixed zoom scale.
+    ///
+    /// A zoom of 1 renders one pixel per point, i.e. at 72 DPI; see
+    /// `render_page_at_dpi` for rendering at a given resolution.
     #[wasm_bindgen]
     pub fn render_page_scaled(
         &self,
@@ -148,6 +335,130 @@ impl PdfViewer {
     ) -> Result<js_sys::Array, JsValue> {
         let page_idx = self.page_index_from_one_based(page)?;
         self.render_page_scaled_internal(page_idx, zoom, device_pixel_ratio)
+            .map(|(result, _)| result)
+    }
+
+    /// Render a specific page (1-based) at a fixed zoom scale like
+    /// `render_page_scaled`, and encode it as a PNG image.
+    ///
+    /// The result can be used for an object URL or downloaded directly.
+    #[wasm_bindgen]
+    pub fn render_page_png(
+        &self,
+        page: usize,
+        zoom: f32,
+        device_pixel_ratio: f32,
+    ) -> Result<Vec<u8>, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let scale = render_scale(zoom, device_pixel_ratio)?;
+        let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
+        let page = pdf.pages().get(page_idx).ok_or("Page out of bounds")?;
+
+        render_page(
+            page,
+            scale,
+            self.bg_mode(device_pixel_ratio),
+            self.grayscale,
+            self.visible_layers.as_ref(),
+            &now_ms,
+        )
+        .0
+        .to_png()
+        .map_err(|e| JsValue::from_str(&format!("Failed to encode PNG: {e}")))
+    }
+
+    /// Render a specific page (1-based) at the given resolution in dots per inch,
+    /// for example 300 DPI for a print-quality export.
+    ///
+    /// A PDF point is 1/72 inch, so this is the same as `render_page_scaled` with a
+    /// zoom of `dpi / 72` and a device pixel ratio of 1. Resolutions that would
+    /// produce gigantic images are rejected with an error.
+    #[wasm_bindgen]
+    pub fn render_page_at_dpi(&self, page: usize, dpi: f32) -> Result<js_sys::Array, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
+        let page = pdf.pages().get(page_idx).ok_or("Page out of bounds")?;
+        let (width, height) = page.render_dimensions();
+        let scale = dpi_scale(width, height, dpi)?;
+
+        Ok(render_page(
+            page,
+            scale,
+            self.bg_mode(1.0),
+            self.grayscale,
+            self.visible_layers.as_ref(),
+            &now_ms,
+        )
+        .0
+        .into_js_array())
+    }
+
+    /// Render every page so that its longest side is `max_dimension` pixels long.
+    ///
+    /// Returns an array with a `[page, width, height, pixel_data]` entry for each
+    /// page, where `page` is 1-based. Thumbnails are cached until the document
+    /// changes, so asking for the same size again doesn't render the pages again.
+    #[wasm_bindgen]
+    pub fn render_thumbnails(&mut self, max_dimension: u32) -> Result<js_sys::Array, JsValue> {
+        let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
+        let result = js_sys::Array::new();
+
+        for (page_idx, page) in pdf.pages().iter().enumerate() {
+            let thumbnail = match self.thumbnails.entry((page_idx, max_dimension)) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let (width, height) = page.render_dimensions();
+                    let scale = thumbnail_scale(width, height, max_dimension)?;
+                    entry.insert(
+                        render_page(
+                            page,
+                            scale,
+                            self.bg_mode(1.0),
+                            self.grayscale,
+                            self.visible_layers.as_ref(),
+                            &now_ms,
+                        )
+                        .0,
+                    )
+                }
+            };
+
+            let entry = thumbnail.clone().into_js_array();
+            entry.unshift(&JsValue::from(page_idx + 1));
+            result.push(&entry);
+        }
+
+        Ok(result)
+    }
+
+    /// Render a specific page (1-based) at a fixed zoom scale, and report how long
+    /// each rendering phase took.
+    ///
+    /// Returns `[width, height, pixel_data, timings]`, where `timings` is an object
+    /// with the fields `parse`, `interpret`, `rasterize` and `total` in milliseconds.
+    #[wasm_bindgen]
+    pub fn render_page_timed(
+        &self,
+        page: usize,
+        zoom: f32,
+        device_pixel_ratio: f32,
+    ) -> Result<js_sys::Array, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let (result, timings) =
+            self.render_page_scaled_internal(page_idx, zoom, device_pixel_ratio)?;
+
+        let timings_obj = js_sys::Object::new();
+        for (key, value) in [
+            ("parse", timings.parse),
+            ("interpret", timings.interpret),
+            ("rasterize", timings.rasterize),
+            ("total", timings.total),
+        ] {
+            js_sys::Reflect::set(&timings_obj, &key.into(), &value.into())?;
+        }
+        result.push(&timings_obj);
+
+        Ok(result)
     }
 
     fn render_page_fit_internal(
@@ -160,7 +471,6 @@ impl PdfViewer {
         let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
         let page = pdf.pages().get(page_idx).ok_or("Page out of bounds")?;
 
-        let interpreter_settings = InterpreterSettings::default();
         let (base_width, base_height) = page.render_dimensions();
 
         // Calculate scale to fit in viewport (accounting for device pixel ratio)
@@ -171,7 +481,16 @@ impl PdfViewer {
         let scale_y = target_height / base_height;
         let scale = scale_x.min(scale_y);
 
-        self.render_page_internal(page, &interpreter_settings, scale)
+        Ok(render_page(
+            page,
+            scale,
+            self.bg_mode(device_pixel_ratio),
+            self.grayscale,
+            self.visible_layers.as_ref(),
+            &now_ms,
+        )
+        .0
+        .into_js_array())
     }
 
     fn render_page_scaled_internal(
@@ -179,46 +498,34 @@ impl PdfViewer {
         page_idx: usize,
         zoom: f32,
         device_pixel_ratio: f32,
-    ) -> Result<js_sys::Array, JsValue> {
-        if !zoom.is_finite() || zoom <= 0.0 {
-            return Err(JsValue::from_str("Zoom must be a positive finite number"));
-        }
-
+    ) -> Result<(js_sys::Array, RenderTimings), JsValue> {
+        let scale = render_scale(zoom, device_pixel_ratio)?;
         let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
         let page = pdf.pages().get(page_idx).ok_or("Page out of bounds")?;
-        let interpreter_settings = InterpreterSettings::default();
-        let scale = zoom * device_pixel_ratio.max(0.1);
 
-        self.render_page_internal(page, &interpreter_settings, scale)
+        let (rendered, timings) = render_page(
+            page,
+            scale,
+            self.bg_mode(device_pixel_ratio),
+            self.grayscale,
+            self.visible_layers.as_ref(),
+            &now_ms,
+        );
+        Ok((rendered.into_js_array(), timings))
     }
 
-    fn render_page_internal(
-        &self,
-        page: &hayro::hayro_syntax::page::Page<'_>,
-        interpreter_settings: &InterpreterSettings,
-        scale: f32,
-    ) -> Result<js_sys::Array, JsValue> {
-        // Render at the calculated scale
-        let render_settings = RenderSettings {
-            x_scale: scale,
-            y_scale: scale,
-            bg_color: WHITE,
-            ..Default::default()
-        };
-
-        let pixmap = hayro::render(page, &interpreter_settings, &render_settings);
-
-        // Return array: [width, height, pixel_data]
-        let result = js_sys::Array::new_with_length(3);
-        result.set(0, JsValue::from(pixmap.width()));
-        result.set(1, JsValue::from(pixmap.height()));
-
-        // Cast Vec<Rgba8> to Vec<u8>
-        let rgba_data = pixmap.take_unpremultiplied();
-        let byte_data: Vec<u8> = bytemuck::cast_vec(rgba_data);
-        result.set(2, JsValue::from(byte_data));
-
-        Ok(result)
+    /// The background of rendered pages. The squares of the checkerboard are
+    /// 8 CSS pixels wide, so they are scaled with the device pixel ratio.
+    fn bg_mode(&self, device_pixel_ratio: f32) -> BgMode {
+        if self.checkerboard {
+            BgMode::Checker {
+                size: (8.0 * device_pixel_ratio).round().max(1.0) as u16,
+                c1: WHITE,
+                c2: LIGHT_GRAY,
+            }
+        } else {
+            BgMode::Solid(WHITE)
+        }
     }
 
     #[wasm_bindgen]
@@ -297,17 +604,83 @@ impl PdfViewer {
         Ok(js_sys::Float32Array::from(&data[..]))
     }
 
+    /// Get the media box of a specific page (1-based), which unlike the crop
+    /// box in `get_page_info_for` also covers areas like the bleed.
+    ///
+    /// Returns `[x0, y0, x1, y1, user_unit]`, where `user_unit` is the size of
+    /// a unit in multiples of 1/72 inch. Pages without a media box inherit it
+    /// from the page tree.
+    #[wasm_bindgen]
+    pub fn get_media_box(&self, page: usize) -> Result<js_sys::Float32Array, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
+        let page = pdf.pages().get(page_idx).ok_or("Page out of bounds")?;
+        let boxes = page_boxes(page);
+
+        let [x0, y0, x1, y1] = boxes.media_box;
+        let data = [x0, y0, x1, y1, boxes.user_unit];
+
+        Ok(js_sys::Float32Array::from(&data[..]))
+    }
+
+    /// Get the boundary boxes of a specific page (1-based).
+    ///
+    /// Returns an object with `media` and `crop` entries and, if the page sets
+    /// them, `bleed`, `trim` and `art` entries, each as `[x0, y0, x1, y1]`.
+    #[wasm_bindgen]
+    pub fn get_boxes(&self, page: usize) -> Result<js_sys::Object, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
+        let page = pdf.pages().get(page_idx).ok_or("Page out of bounds")?;
+        let boxes = page_boxes(page);
+
+        let result = js_sys::Object::new();
+        for (key, value) in [
+            ("media", Some(boxes.media_box)),
+            ("crop", Some(boxes.crop_box)),
+            ("bleed", boxes.bleed_box),
+            ("trim", boxes.trim_box),
+            ("art", boxes.art_box),
+        ] {
+            if let Some(value) = value {
+                let array = js_sys::Float32Array::from(&value[..]);
+                js_sys::Reflect::set(&result, &key.into(), &array.into())?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Extract positioned text spans for a specific page (1-based).
+    #[wasm_bindgen]
+    pub fn text_spans(&mut self, page: usize) -> Result<Vec<TextSpanJs>, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let spans = self.cached_text_spans(page_idx)?;
+
+        Ok(spans
+            .iter()
+            .map(|span| TextSpanJs {
+                text: span.text.clone(),
+                x0: span.bbox[0],
+                y0: span.bbox[1],
+                x1: span.bbox[2],
+                y1: span.bbox[3],
+                baseline_x: span.baseline[0],
+                baseline_y: span.baseline[1],
+                vertical: span.vertical,
+            })
+            .collect())
+    }
+
     /// Extract positioned text spans for a specific page (1-based).
     ///
     /// The return value is an array of items, each item containing:
     /// [text, x0, y0, x1, y1, baseline_x, baseline_y].
+    #[deprecated(note = "use `text_spans`, which returns objects with named fields")]
     #[wasm_bindgen]
-    pub fn get_text_spans(&self, page: usize) -> Result<js_sys::Array, JsValue> {
+    pub fn get_text_spans(&mut self, page: usize) -> Result<js_sys::Array, JsValue> {
         let page_idx = self.page_index_from_one_based(page)?;
-        let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
-        let page_ref = pdf.pages().get(page_idx).ok_or("Page out of bounds")?;
-        let settings = InterpreterSettings::default();
-        let spans = extract_text_spans(page_ref, &settings);
+        let spans = self.cached_text_spans(page_idx)?;
 
         let result = js_sys::Array::new_with_length(spans.len() as u32);
         for (idx, span) in spans.iter().enumerate() {
@@ -325,6 +698,15 @@ impl PdfViewer {
         Ok(result)
     }
 
+    /// Drop the cached text spans of all pages, so that they are extracted
+    /// again on the next request.
+    ///
+    /// The cache is already cleared whenever the document changes.
+    #[wasm_bindgen]
+    pub fn clear_text_cache(&mut self) {
+        self.text_spans.clear();
+    }
+
     /// Add a highlight annotation to the current page.
     /// quad_points: flat array of coordinates [x1,y1,x2,y2,...] in PDF space.
     #[wasm_bindgen]
@@ -333,22 +715,76 @@ impl PdfViewer {
             return false;
         }
 
-        // Calculate bounding rect from quad points
-        let rect = rect_from_quad_points(quad_points);
+        self.add_annotation_to_page(highlight_annotation(quad_points.to_vec(), r, g, b));
+        true
+    }
 
-        let annot = Annotation::Highlight(HighlightAnnot {
-            base: AnnotationBase {
-                rect,
-                color: Some(AnnotColor::new(r, g, b)),
-                flags: 4, // Print
-                opacity: 0.5,
-                ..Default::default()
-            },
-            quad_points: quad_points.to_vec(),
-        });
+    /// Highlight a range of characters on a specific page (1-based).
+    ///
+    /// `start` and `end` (exclusive) are character offsets into the text of the
+    /// spans returned by `text_spans`, concatenated in order. Returns
+    /// `false` if the range doesn't cover any text.
+    #[wasm_bindgen]
+    pub fn highlight_text_range(
+        &mut self,
+        page: usize,
+        start: usize,
+        end: usize,
+        r: f32,
+        g: f32,
+        b: f32,
+    ) -> Result<bool, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let quad_points = quads_for_spans(self.cached_text_spans(page_idx)?, start, end);
+        if quad_points.is_empty() {
+            return Ok(false);
+        }
 
-        self.add_annotation_to_page(annot);
-        true
+        self.push_operation(ViewerOperation::new(
+            self.source_page(page_idx),
+            highlight_annotation(quad_points, r, g, b),
+        ));
+        Ok(true)
+    }
+
+    /// Convert browser selection rectangles into quad points for `add_highlight`.
+    ///
+    /// `rects_flat` holds `[left, top, width, height]` for each rectangle from
+    /// `Range.getClientRects()`, relative to the page canvas, for a page (1-based)
+    /// rendered at the given scale.
+    #[wasm_bindgen]
+    pub fn quad_points_from_client_rects(
+        &self,
+        rects_flat: &[f32],
+        page: usize,
+        scale: f32,
+    ) -> Result<Vec<f32>, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
+        let page = pdf.pages().get(page_idx).ok_or("Page out of bounds")?;
+
+        let (width, height) = page.render_dimensions();
+        let crop = page.intersected_crop_box();
+        let rotation = match page.rotation() {
+            hayro::hayro_syntax::page::Rotation::None => 0,
+            hayro::hayro_syntax::page::Rotation::Horizontal => 90,
+            hayro::hayro_syntax::page::Rotation::Flipped => 180,
+            hayro::hayro_syntax::page::Rotation::FlippedHorizontal => 270,
+        };
+
+        Ok(quad_points_from_client_rects(
+            rects_flat,
+            width,
+            height,
+            [
+                crop.x0 as f32,
+                crop.y0 as f32,
+                crop.x1 as f32,
+                crop.y1 as f32,
+            ],
+            rotation,
+            scale,
+        ))
     }
 
     /// Add an ink (freehand) annotation to the current page.
@@ -366,11 +802,13 @@ impl PdfViewer {
             base: AnnotationBase {
                 rect,
                 color: Some(AnnotColor::new(r, g, b)),
-                flags: 4,
+                flags: AnnotationFlags::PRINT.bits(),
                 ..Default::default()
             },
             ink_list: vec![ink_path],
             line_width,
+            smoothing: 0.0,
+            dash: None,
         });
 
         self.add_annotation_to_page(annot);
@@ -400,18 +838,78 @@ impl PdfViewer {
             base: AnnotationBase {
                 rect,
                 color: Some(AnnotColor::new(r, g, b)),
-                flags: 4,
+                flags: AnnotationFlags::PRINT.bits(),
                 ..Default::default()
             },
             interior_color: None,
             line_width: 2.0,
             is_circle: false,
+            border_style: BorderStyle::Solid,
+            fill_opacity: 1.0,
+            corner_radius: 0.0,
         });
 
         self.add_annotation_to_page(annot);
         true
     }
 
+    /// Set whether pages are rendered in grayscale, e.g. for a print preview.
+    #[wasm_bindgen]
+    pub fn set_grayscale(&mut self, grayscale: bool) {
+        if self.grayscale != grayscale {
+            self.grayscale = grayscale;
+            self.thumbnails.clear();
+        }
+    }
+
+    /// Set whether transparent regions of pages are shown with a
+    /// checkerboard, e.g. to inspect transparency groups.
+    #[wasm_bindgen]
+    pub fn set_checkerboard(&mut self, checkerboard: bool) {
+        if self.checkerboard != checkerboard {
+            self.checkerboard = checkerboard;
+            self.thumbnails.clear();
+        }
+    }
+
+    /// Get the names of the document's optional content groups (layers), as
+    /// listed in `/OCProperties`.
+    #[wasm_bindgen]
+    pub fn get_layers(&self) -> js_sys::Array {
+        let Some(pdf) = self.pdf.as_ref() else {
+            return js_sys::Array::new();
+        };
+
+        layer_names(pdf)
+            .into_iter()
+            .map(|name| JsValue::from_str(&name))
+            .collect()
+    }
+
+    /// Show only the layers with the given names, hiding all others.
+    ///
+    /// Pass `undefined` to go back to the document's default visibility.
+    #[wasm_bindgen]
+    pub fn set_visible_layers(&mut self, names: Option<Vec<String>>) {
+        let visible_layers = names.map(|names| names.into_iter().collect());
+        if self.visible_layers != visible_layers {
+            self.visible_layers = visible_layers;
+            self.thumbnails.clear();
+        }
+    }
+
+    /// Set the TrueType/OpenType font embedded into new free text annotations.
+    ///
+    /// Pass an empty array to go back to the built-in Helvetica font.
+    #[wasm_bindgen]
+    pub fn set_freetext_font(&mut self, data: Vec<u8>) {
+        self.freetext_font = if data.is_empty() {
+            None
+        } else {
+            Some(Arc::new(data))
+        };
+    }
+
     /// Add a free text annotation to the current page.
     #[wasm_bindgen]
     pub fn add_freetext(
@@ -433,12 +931,19 @@ impl PdfViewer {
             base: AnnotationBase {
                 rect,
                 color: Some(AnnotColor::black()),
-                flags: 4,
+                flags: AnnotationFlags::PRINT.bits(),
                 ..Default::default()
             },
             text: text.to_string(),
             font_size,
             default_appearance: format!("0 0 0 rg /Helv {} Tf", font_size),
+            font_data: self.freetext_font.clone(),
+            multiline: true,
+            line_spacing: 1.2,
+            vertical_align: VAlign::Top,
+            callout_line: None,
+            rich_text: None,
+            default_style: None,
         });
 
         self.add_annotation_to_page(annot);
@@ -465,7 +970,7 @@ impl PdfViewer {
             base: AnnotationBase {
                 rect,
                 color: Some(AnnotColor::black()),
-                flags: 4,
+                flags: AnnotationFlags::PRINT.bits(),
                 ..Default::default()
             },
             field_name: field_name.trim().to_string(),
@@ -480,6 +985,9 @@ impl PdfViewer {
             read_only: false,
             required: false,
             multiline: false,
+            alignment: TextAlign::Left,
+            down_appearance: None,
+            rollover_appearance: None,
         });
 
         self.add_annotation_to_page(annot);
@@ -505,23 +1013,195 @@ impl PdfViewer {
             base: AnnotationBase {
                 rect,
                 color: Some(AnnotColor::new(0.1, 0.2, 0.5)),
-                flags: 4,
+                flags: AnnotationFlags::PRINT.bits(),
                 ..Default::default()
             },
             field_name: field_name.trim().to_string(),
             tooltip: Some("Sign here".to_string()),
             required: false,
+            down_appearance: None,
+            rollover_appearance: None,
         });
 
         self.add_annotation_to_page(annot);
         true
     }
 
+    /// Add a dropdown (combo box) form field to the current page.
+    ///
+    /// `selected` must be one of `options`, or empty for no selection. Returns
+    /// `false` if there are no options.
+    #[wasm_bindgen]
+    pub fn add_dropdown(
+        &mut self,
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+        field_name: &str,
+        options: Vec<String>,
+        selected: &str,
+    ) -> bool {
+        if self.pdf.is_none() || field_name.trim().is_empty() || options.is_empty() {
+            return false;
+        }
+
+        let rect = [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)];
+        let selected = options.iter().find(|o| o.as_str() == selected).cloned();
+        let annot = Annotation::Choice(ChoiceFieldAnnot {
+            base: AnnotationBase {
+                rect,
+                color: Some(AnnotColor::black()),
+                flags: AnnotationFlags::PRINT.bits(),
+                ..Default::default()
+            },
+            field_name: field_name.trim().to_string(),
+            options,
+            selected,
+            combo: true,
+            read_only: false,
+            required: false,
+            down_appearance: None,
+            rollover_appearance: None,
+        });
+
+        self.add_annotation_to_page(annot);
+        true
+    }
+
+    /// Add an image stamp to the current page, stretched to fill the given rect.
+    ///
+    /// `format` is either `"png"` or `"jpeg"` (a MIME type such as `"image/png"`
+    /// works as well).
+    #[wasm_bindgen]
+    pub fn add_image_stamp(
+        &mut self,
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+        image_data: &[u8],
+        format: &str,
+    ) -> bool {
+        let format = match format.trim().trim_start_matches("image/") {
+            "png" => ImageFormat::Png,
+            "jpeg" | "jpg" => ImageFormat::Jpeg,
+            _ => return false,
+        };
+        if self.pdf.is_none() || image_data.is_empty() {
+            return false;
+        }
+
+        let rect = [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)];
+        let annot = Annotation::Stamp(StampAnnot {
+            base: AnnotationBase {
+                rect,
+                flags: AnnotationFlags::PRINT.bits(),
+                ..Default::default()
+            },
+            image_data: Arc::new(image_data.to_vec()),
+            format,
+        });
+
+        self.add_annotation_to_page(annot);
+        true
+    }
+
+    /// Set the rotation of a specific page (1-based) in degrees, replacing its
+    /// current rotation.
+    ///
+    /// The rotation is normalized to 0, 90, 180 or 270 degrees and is undone
+    /// like an annotation. `get_page_info_for` reports the new rotation.
+    #[wasm_bindgen]
+    pub fn set_page_rotation(&mut self, page: usize, degrees: i32) -> Result<(), JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        self.push_operation(ViewerOperation::rotation(
+            self.source_page(page_idx),
+            degrees.rem_euclid(360) / 90 * 90,
+        ));
+        Ok(())
+    }
+
+    /// Rotate a specific page (1-based) by the given degrees, on top of its
+    /// current rotation, e.g. by 90 for a "rotate clockwise" button.
+    #[wasm_bindgen]
+    pub fn rotate_page(&mut self, page: usize, degrees: i32) -> Result<(), JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
+        let page_ref = pdf.pages().get(page_idx).ok_or("Page out of bounds")?;
+        let current = match page_ref.rotation() {
+            hayro::hayro_syntax::page::Rotation::None => 0,
+            hayro::hayro_syntax::page::Rotation::Horizontal => 90,
+            hayro::hayro_syntax::page::Rotation::Flipped => 180,
+            hayro::hayro_syntax::page::Rotation::FlippedHorizontal => 270,
+        };
+
+        self.set_page_rotation(page, current + degrees)
+    }
+
+    /// Delete a specific page (1-based) from the document, together with its
+    /// pending annotations.
+    ///
+    /// The last remaining page can't be deleted.
+    #[wasm_bindgen]
+    pub fn delete_page(&mut self, page: usize) -> Result<(), JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        if self.total_pages == 1 {
+            return Err(JsValue::from_str("Can't delete the only page"));
+        }
+
+        self.page_order.remove(page_idx);
+        self.update_pages();
+        Ok(())
+    }
+
+    /// Move a page (1-based) so that it ends up at position `to` (1-based).
+    #[wasm_bindgen]
+    pub fn move_page(&mut self, from: usize, to: usize) -> Result<(), JsValue> {
+        let from_idx = self.page_index_from_one_based(from)?;
+        let to_idx = self.page_index_from_one_based(to)?;
+
+        let page = self.page_order.remove(from_idx);
+        self.page_order.insert(to_idx, page);
+        self.update_pages();
+        Ok(())
+    }
+
+    /// Append all pages of another PDF to the end of the document.
+    ///
+    /// Pending annotations and page edits are kept. The merged document gets a
+    /// new catalog, so the form fields (`/AcroForm`), outlines and incremental
+    /// history of both documents are dropped.
+    #[wasm_bindgen]
+    pub fn append_pdf(&mut self, other: &[u8]) -> Result<(), JsValue> {
+        let Some(pdf) = &self.pdf else {
+            return Err(JsValue::from_str("No PDF loaded"));
+        };
+        let original_pages = pdf.pages().len();
+
+        let merged = hayro_annot::concat_pdfs(&[&self.pdf_data, other])
+            .map_err(|e| JsValue::from_str(&format!("Append failed: {e}")))?;
+        let merged_pages = Pdf::new(merged.clone())
+            .map_err(|_| JsValue::from_str("Failed to parse PDF"))?
+            .pages()
+            .len();
+
+        // The pages of the loaded document keep their indices, so pending
+        // operations still refer to the right pages.
+        self.pdf_data = merged;
+        self.page_order.extend(original_pages..merged_pages);
+        self.update_pages();
+        Ok(())
+    }
+
     /// Remove the last annotation added to the current page (undo).
     #[wasm_bindgen]
     pub fn undo_annotation(&mut self) -> bool {
+        let in_sync = self.is_incremental();
         if self.history.undo() {
-            self.rebuild_pdf_with_operations();
+            if !(in_sync && self.truncate_incremental_update()) {
+                self.rebuild_pdf_with_operations();
+            }
             return true;
         }
 
@@ -531,8 +1211,11 @@ impl PdfViewer {
     /// Re-apply the last undone annotation/form operation.
     #[wasm_bindgen]
     pub fn redo_annotation(&mut self) -> bool {
+        let in_sync = self.is_incremental();
         if self.history.redo() {
-            self.rebuild_pdf_with_operations();
+            if !(in_sync && self.append_incremental_update()) {
+                self.rebuild_pdf_with_operations();
+            }
             return true;
         }
 
@@ -540,23 +1223,45 @@ impl PdfViewer {
     }
 
     /// Save the PDF with all pending annotations and return the bytes.
+    ///
+    /// Documents opened with a password can only be saved without changes,
+    /// since edits can't be encrypted again yet.
     #[wasm_bindgen]
     pub fn save(&self) -> Result<Vec<u8>, JsValue> {
         if self.pdf.is_none() {
             return Err(JsValue::from_str("No PDF loaded"));
         }
 
-        if self.history.is_empty() {
-            // No annotations — return original data
-            return Ok(self.pdf_data.clone());
+        if !self.has_changes() {
+            // No annotations or page edits — return original data
+            return Ok(self
+                .encrypted_data
+                .as_ref()
+                .unwrap_or(&self.pdf_data)
+                .clone());
         }
 
-        let page_annots = self.history.grouped_operations();
+        if self.encrypted_data.is_some() {
+            return Err(JsValue::from_str(
+                "Saving changes to encrypted documents isn't supported",
+            ));
+        }
 
-        hayro_annot::save_annotations(&self.pdf_data, &page_annots)
+        self.save_operations()
             .map_err(|e| JsValue::from_str(&format!("Save failed: {e}")))
     }
 
+    /// Export a specific page (1-based), with its pending annotations, as a
+    /// standalone one-page PDF.
+    #[wasm_bindgen]
+    pub fn extract_page_pdf(&self, page: usize) -> Result<Vec<u8>, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let data = self.save()?;
+
+        hayro_annot::extract_pages(&data, &[page_idx])
+            .map_err(|e| JsValue::from_str(&format!("Export failed: {e}")))
+    }
+
     /// List annotations on a specific page (1-based).
     ///
     /// Returns a JS array where each element is a sub-array:
@@ -564,13 +1269,13 @@ impl PdfViewer {
     #[wasm_bindgen]
     pub fn list_page_annotations(&self, page: usize) -> Result<js_sys::Array, JsValue> {
         let page_idx = self.page_index_from_one_based(page)?;
-        let ops = self.history.page_operations(page_idx);
-        let result = js_sys::Array::new_with_length(ops.len() as u32);
-        for (i, (global_idx, op)) in ops.iter().enumerate() {
-            let rect = op.annotation.base().rect;
+        let annotations = self.history.page_annotations(self.source_page(page_idx));
+        let result = js_sys::Array::new_with_length(annotations.len() as u32);
+        for (i, (global_idx, annotation)) in annotations.iter().enumerate() {
+            let rect = annotation.base().rect;
             let item = js_sys::Array::new_with_length(6);
             item.set(0, JsValue::from(*global_idx as u32));
-            item.set(1, JsValue::from_str(op.annotation.type_name()));
+            item.set(1, JsValue::from_str(annotation.type_name()));
             item.set(2, JsValue::from_f64(rect[0] as f64));
             item.set(3, JsValue::from_f64(rect[1] as f64));
             item.set(4, JsValue::from_f64(rect[2] as f64));
@@ -580,6 +1285,136 @@ impl PdfViewer {
         Ok(result)
     }
 
+    /// Interpret a specific page (1-based) and list the problems encountered,
+    /// like fonts that aren't embedded, for the log panel.
+    ///
+    /// Returns a JS array where each element is a sub-array
+    /// `[severity, message]`, with `severity` being "info", "warning" or "error".
+    #[wasm_bindgen]
+    pub fn get_diagnostics(&self, page: usize) -> Result<js_sys::Array, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
+        let page = pdf.pages().get(page_idx).ok_or("Page out of bounds")?;
+
+        // Interpret the page like it is displayed, e.g. with the same layers.
+        let (_, warnings) = hayro::render_with_warnings(
+            page,
+            &interpreter_settings(self.visible_layers.as_ref()),
+            &RenderSettings::default(),
+        );
+
+        let result = js_sys::Array::new();
+        for warning in warnings {
+            let severity = match warning {
+                InterpreterWarning::FontNotEmbedded(_) => "info",
+                InterpreterWarning::ImageDecodeFailure => "error",
+                _ => "warning",
+            };
+            let entry = js_sys::Array::new();
+            entry.push(&JsValue::from_str(severity));
+            entry.push(&JsValue::from_str(&warning.to_string()));
+            result.push(&entry);
+        }
+
+        Ok(result)
+    }
+
+    /// List the links of a specific page (1-based), so that they can be made
+    /// clickable.
+    ///
+    /// Returns a JS array where each element is a sub-array
+    /// `[x0, y0, x1, y1, uri, targetPage]` with the rect in PDF coordinates.
+    /// Either `uri` or the 1-based `targetPage` is set, the other is `null`.
+    #[wasm_bindgen]
+    pub fn get_links(&self, page: usize) -> Result<js_sys::Array, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
+        let links = hayro_annot::extract_links(pdf, page_idx);
+
+        let result = js_sys::Array::new_with_length(links.len() as u32);
+        for (i, link) in links.iter().enumerate() {
+            let item = js_sys::Array::new_with_length(6);
+            for (j, value) in link.rect.iter().enumerate() {
+                item.set(j as u32, JsValue::from_f64(*value as f64));
+            }
+            let (uri, target_page) = match &link.target {
+                LinkTarget::Uri(uri) => (JsValue::from_str(uri), JsValue::NULL),
+                LinkTarget::Page(idx) => (JsValue::NULL, JsValue::from_f64((idx + 1) as f64)),
+            };
+            item.set(4, uri);
+            item.set(5, target_page);
+            result.set(i as u32, item.into());
+        }
+        Ok(result)
+    }
+
+    /// Get the label of a page (1-based), such as "iv" or "A-3", as defined by
+    /// the document's page labels.
+    ///
+    /// Pages without a label are labelled with their page number. Returns
+    /// `None` if the page doesn't exist.
+    #[wasm_bindgen]
+    pub fn get_page_label(&self, page: usize) -> Option<String> {
+        let page_idx = self.page_index_from_one_based(page).ok()?;
+        // Labels belong to the pages of the loaded document, so they move
+        // along with the pages.
+        let label = Pdf::new(self.pdf_data.clone())
+            .ok()
+            .and_then(|pdf| labels::page_label(&pdf, self.source_page(page_idx)));
+
+        Some(label.unwrap_or_else(|| page.to_string()))
+    }
+
+    /// Get the number of annotations a page (1-based) has in the loaded
+    /// document, not counting pending ones.
+    #[wasm_bindgen]
+    pub fn get_existing_annotation_count(&self, page: usize) -> usize {
+        self.existing_annotations(page)
+            .map_or(0, |annotations| annotations.len())
+    }
+
+    /// List the annotations a page (1-based) has in the loaded document, not
+    /// counting pending ones.
+    ///
+    /// Returns a JS array where each element is a sub-array:
+    /// `[typeString, x0, y0, x1, y1, contents]`, where `contents` is `null` if
+    /// the annotation has none.
+    #[wasm_bindgen]
+    pub fn list_annotations(&self, page: usize) -> Result<js_sys::Array, JsValue> {
+        let annotations = self.existing_annotations(page)?;
+        let result = js_sys::Array::new_with_length(annotations.len() as u32);
+        for (i, annotation) in annotations.iter().enumerate() {
+            let base = annotation.base();
+            let item = js_sys::Array::new_with_length(6);
+            item.set(0, JsValue::from_str(annotation.type_name()));
+            for (j, value) in base.rect.iter().enumerate() {
+                item.set(j as u32 + 1, JsValue::from_f64(*value as f64));
+            }
+            item.set(
+                5,
+                base.contents
+                    .as_deref()
+                    .map_or(JsValue::NULL, JsValue::from_str),
+            );
+            result.set(i as u32, item.into());
+        }
+        Ok(result)
+    }
+
+    /// Get the document outline (bookmarks) for a table of contents.
+    ///
+    /// Returns a JS array where each element is a sub-array:
+    /// `[title, page, childCount, children]`, where `page` is the 1-based page
+    /// the item links to, or `null` if it doesn't link to a page that is still
+    /// in the document, and `children` has the same layout.
+    #[wasm_bindgen]
+    pub fn get_outline(&self) -> js_sys::Array {
+        match Pdf::new(self.pdf_data.clone()) {
+            Ok(pdf) => self.outline_to_js(&hayro_annot::read_outline(&pdf)),
+            Err(_) => js_sys::Array::new(),
+        }
+    }
+
     /// Update the bounding rect of an annotation by its global operation index.
     ///
     /// Coordinates are in PDF space. Internal geometry (quad-points, ink paths,
@@ -613,10 +1448,24 @@ impl PdfViewer {
         }
     }
 
+    /// Merge adjacent same-color highlights on the current page into one.
+    #[wasm_bindgen]
+    pub fn coalesce_highlights(&mut self) -> bool {
+        if self
+            .history
+            .coalesce_highlights(self.source_page(self.current_page))
+        {
+            self.rebuild_pdf_with_operations();
+            true
+        } else {
+            false
+        }
+    }
+
     /// Get the number of pending annotations on the current page.
     #[wasm_bindgen]
     pub fn get_annotation_count(&self) -> usize {
-        self.history.page_count(self.current_page)
+        self.history.page_count(self.source_page(self.current_page))
     }
 
     /// Get total operation count across all pages.
@@ -631,34 +1480,228 @@ impl PdfViewer {
         self.history.redo_count()
     }
 
+    /// How long the last update of the displayed document after an edit took,
+    /// in milliseconds.
+    #[wasm_bindgen]
+    pub fn get_last_update_ms(&self) -> f64 {
+        self.last_update_ms
+    }
+
     fn add_annotation_to_page(&mut self, annot: Annotation) {
-        self.history.push(ViewerOperation {
-            page: self.current_page,
-            annotation: annot,
-        });
+        self.push_operation(ViewerOperation::new(
+            self.source_page(self.current_page),
+            annot,
+        ));
+    }
+
+    /// Add an operation to the history and update the displayed document,
+    /// with an incremental update where possible.
+    fn push_operation(&mut self, operation: ViewerOperation) {
+        let in_sync = self.is_incremental();
+        self.history.push(operation);
+        if !(in_sync && self.append_incremental_update()) {
+            self.rebuild_pdf_with_operations();
+        }
+    }
+
+    /// Whether the displayed document is the loaded or last rebuilt one
+    /// followed by an incremental update for each later operation, so that
+    /// operations can be added and undone by appending and truncating updates.
+    ///
+    /// Page edits reorder the whole document, so they always need a rebuild.
+    fn is_incremental(&self) -> bool {
+        self.pdf.is_some()
+            && self.incremental_ends.len() == self.history.operation_count()
+            && self.page_order.iter().copied().eq(0..self.total_pages)
+    }
+
+    /// Append the annotation of the last operation to the displayed document
+    /// as an incremental update.
+    ///
+    /// This only writes the new annotation objects and patches its page,
+    /// instead of extracting every page again like
+    /// `rebuild_pdf_with_operations`, so its cost doesn't grow with the number
+    /// of pages and operations. Returns `false` if the document has to be
+    /// rebuilt instead, e.g. because it is encrypted or a redo restored several
+    /// operations at once.
+    fn append_incremental_update(&mut self) -> bool {
+        let start = now_ms();
+        if self.incremental_ends.len() + 1 != self.history.operation_count() {
+            return false;
+        }
+        let (Some(pdf), Some(operation)) = (&self.pdf, self.history.last()) else {
+            return false;
+        };
+        let Some(annotation) = operation.annotation() else {
+            return false;
+        };
+
+        let data = match hayro_annot::save_annotations_incremental(
+            pdf.data().as_ref(),
+            &[(operation.page, vec![annotation.clone()])],
+        ) {
+            Ok(data) => data,
+            Err(e) => {
+                log::info!("Falling back to a full rebuild: {e}");
+                return false;
+            }
+        };
+        let end = data.len();
+        let Ok(new_pdf) = Pdf::new(data) else {
+            return false;
+        };
+
+        self.pdf = Some(new_pdf);
+        self.incremental_ends.push(end);
+        self.thumbnails.clear();
+        self.text_spans.clear();
+        self.last_update_ms = now_ms() - start;
+        true
+    }
+
+    /// Remove the incremental update of the operation that was just undone
+    /// from the displayed document.
+    ///
+    /// Returns `false` if the undo restored other operations as well, or the
+    /// undone operation is part of the rebuilt document, in which case the
+    /// document has to be rebuilt.
+    fn truncate_incremental_update(&mut self) -> bool {
+        let start = now_ms();
+        let Some(pdf) = &self.pdf else {
+            return false;
+        };
+        if self.incremental_ends.len() != self.history.operation_count() + 1
+            || self.incremental_ends.len() <= self.rebuilt_operations
+        {
+            return false;
+        }
+
+        self.incremental_ends.pop();
+        let end = self
+            .incremental_ends
+            .last()
+            .copied()
+            .unwrap_or(self.pdf_data.len());
+        let Ok(new_pdf) = Pdf::new(pdf.data().as_ref()[..end].to_vec()) else {
+            return false;
+        };
+
+        self.pdf = Some(new_pdf);
+        self.thumbnails.clear();
+        self.text_spans.clear();
+        self.last_update_ms = now_ms() - start;
+        true
+    }
+
+    /// Rebuild the document after pages were deleted or moved.
+    fn update_pages(&mut self) {
+        self.total_pages = self.page_order.len();
+        self.current_page = self.current_page.min(self.total_pages - 1);
         self.rebuild_pdf_with_operations();
     }
 
     fn rebuild_pdf_with_operations(&mut self) {
-        if self.history.is_empty() {
+        let start = now_ms();
+        self.thumbnails.clear();
+        self.text_spans.clear();
+        self.incremental_ends.clear();
+        self.rebuilt_operations = 0;
+
+        if !self.has_changes() {
             if let Ok(new_pdf) = Pdf::new(self.pdf_data.clone()) {
                 self.pdf = Some(new_pdf);
             }
+            self.last_update_ms = now_ms() - start;
             return;
         }
 
-        let page_annots = self.history.grouped_operations();
-
-        match hayro_annot::save_annotations(&self.pdf_data, &page_annots) {
+        match self.save_operations() {
             Ok(new_data) => {
+                let len = new_data.len();
                 if let Ok(new_pdf) = Pdf::new(new_data) {
                     self.pdf = Some(new_pdf);
+                    // Later operations can be appended to the rebuilt document.
+                    self.rebuilt_operations = self.history.operation_count();
+                    self.incremental_ends = vec![len; self.rebuilt_operations];
                 }
             }
             Err(e) => {
                 log::warn!("Failed to rebuild PDF with annotations: {e}");
             }
         }
+        self.last_update_ms = now_ms() - start;
+    }
+
+    /// Whether there are pending annotations or page edits.
+    fn has_changes(&self) -> bool {
+        !self.history.is_empty() || !self.page_order.iter().copied().eq(0..self.total_pages)
+    }
+
+    /// The index in the loaded document of the page at `page_idx`.
+    fn source_page(&self, page_idx: usize) -> usize {
+        self.page_order.get(page_idx).copied().unwrap_or(page_idx)
+    }
+
+    /// Write the original document with all pending annotations and page edits.
+    fn save_operations(&self) -> Result<Vec<u8>, hayro_annot::SaveError> {
+        let page_annots: Vec<_> = remap_pages(self.history.grouped_operations(), &self.page_order);
+
+        hayro_annot::save_annotations_with_options(
+            &self.pdf_data,
+            &page_annots,
+            &WriteOptions {
+                page_rotations: remap_pages(self.history.page_rotations(), &self.page_order),
+                page_order: Some(self.page_order.clone()),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Read the annotations of a page (1-based) from the loaded document.
+    fn existing_annotations(&self, page: usize) -> Result<Vec<Annotation>, JsValue> {
+        let page_idx = self.page_index_from_one_based(page)?;
+        let pdf = Pdf::new(self.pdf_data.clone())
+            .map_err(|_| JsValue::from_str("Failed to parse PDF"))?;
+
+        Ok(hayro_annot::read_annotations(
+            &pdf,
+            self.source_page(page_idx),
+        ))
+    }
+
+    fn outline_to_js(&self, items: &[OutlineItem]) -> js_sys::Array {
+        let result = js_sys::Array::new_with_length(items.len() as u32);
+        for (i, outline_item) in items.iter().enumerate() {
+            // The outline refers to pages of the loaded document.
+            let page = outline_item
+                .page
+                .and_then(|source| self.page_order.iter().position(|p| *p == source));
+
+            let item = js_sys::Array::new_with_length(4);
+            item.set(0, JsValue::from_str(&outline_item.title));
+            item.set(
+                1,
+                page.map_or(JsValue::NULL, |p| JsValue::from_f64((p + 1) as f64)),
+            );
+            item.set(2, JsValue::from_f64(outline_item.children.len() as f64));
+            item.set(3, self.outline_to_js(&outline_item.children).into());
+            result.set(i as u32, item.into());
+        }
+        result
+    }
+
+    /// The text spans of the page at `page_idx`, extracted on first use.
+    fn cached_text_spans(&mut self, page_idx: usize) -> Result<&[TextSpan], JsValue> {
+        let spans = match self.text_spans.entry(page_idx) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let pdf = self.pdf.as_ref().ok_or("No PDF loaded")?;
+                let page = pdf.pages().get(page_idx).ok_or("Page out of bounds")?;
+                entry.insert(extract_text_spans(page, &InterpreterSettings::default()))
+            }
+        };
+
+        Ok(spans)
     }
 
     fn page_index_from_one_based(&self, page: usize) -> Result<usize, JsValue> {
@@ -669,3 +1712,41 @@ impl PdfViewer {
         }
     }
 }
+
+/// Whether `data` ends with a `%%EOF` marker, as is the case after each
+/// complete section of a linearized or incrementally updated PDF.
+fn ends_with_eof_marker(data: &[u8]) -> bool {
+    let end = data
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace() && *b != 0)
+        .map_or(0, |i| i + 1);
+    data[..end].ends_with(b"%%EOF")
+}
+
+/// The error reported to JavaScript when a document can't be loaded.
+fn load_error(error: &LoadPdfError, password: &str) -> JsValue {
+    let message = match error {
+        LoadPdfError::Decryption(DecryptionError::PasswordProtected) if password.is_empty() => {
+            "Password required"
+        }
+        LoadPdfError::Decryption(DecryptionError::PasswordProtected) => "Wrong password",
+        LoadPdfError::Decryption(_) => "Unsupported encryption",
+        LoadPdfError::Invalid => "Failed to parse PDF",
+    };
+
+    JsValue::from_str(message)
+}
+
+/// Create a semi-transparent highlight annotation covering the given quad points.
+fn highlight_annotation(quad_points: Vec<f32>, r: f32, g: f32, b: f32) -> Annotation {
+    Annotation::Highlight(HighlightAnnot {
+        base: AnnotationBase {
+            rect: rect_from_quad_points(&quad_points),
+            color: Some(AnnotColor::new(r, g, b)),
+            flags: AnnotationFlags::PRINT.bits(),
+            opacity: 0.5,
+            ..Default::default()
+        },
+        quad_points,
+    })
+}
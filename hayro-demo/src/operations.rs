@@ -1,10 +1,64 @@
+use crate::geometry::{quad_groups_adjacent, rect_from_quad_points};
 use hayro_annot::Annotation;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+
+/// What an operation does to its page.
+#[derive(Clone)]
+pub(crate) enum OperationKind {
+    /// Add an annotation.
+    Annotation(Annotation),
+    /// Set the rotation of the page, in degrees.
+    Rotation(i32),
+}
 
 #[derive(Clone)]
 pub(crate) struct ViewerOperation {
     pub(crate) page: usize,
-    pub(crate) annotation: Annotation,
+    pub(crate) kind: OperationKind,
+    /// Operations (with their global indices) that this one replaced, restored on undo.
+    replaced: Vec<(usize, ViewerOperation)>,
+    /// Whether this operation is undone and redone together with the previous one.
+    chained: bool,
+}
+
+impl ViewerOperation {
+    pub(crate) fn new(page: usize, annotation: Annotation) -> Self {
+        Self::with_kind(page, OperationKind::Annotation(annotation))
+    }
+
+    pub(crate) fn rotation(page: usize, degrees: i32) -> Self {
+        Self::with_kind(page, OperationKind::Rotation(degrees))
+    }
+
+    fn with_kind(page: usize, kind: OperationKind) -> Self {
+        Self {
+            page,
+            kind,
+            replaced: Vec::new(),
+            chained: false,
+        }
+    }
+
+    /// The annotation this operation adds, if any.
+    pub(crate) fn annotation(&self) -> Option<&Annotation> {
+        match &self.kind {
+            OperationKind::Annotation(annotation) => Some(annotation),
+            OperationKind::Rotation(_) => None,
+        }
+    }
+}
+
+/// Map entries keyed by the original index of a page to the position of that
+/// page in an edited document, where `page_order` holds the original index of
+/// each page. Entries of pages that were removed are dropped.
+pub(crate) fn remap_pages<T, C: FromIterator<(usize, T)>>(
+    entries: impl IntoIterator<Item = (usize, T)>,
+    page_order: &[usize],
+) -> C {
+    entries
+        .into_iter()
+        .filter_map(|(page, value)| Some((page_order.iter().position(|&p| p == page)?, value)))
+        .collect()
 }
 
 #[derive(Default)]
@@ -25,23 +79,50 @@ impl OperationHistory {
     }
 
     pub(crate) fn undo(&mut self) -> bool {
-        if let Some(operation) = self.operations.pop() {
+        let Some(mut operation) = self.operations.pop() else {
+            return false;
+        };
+
+        loop {
+            for (index, replaced) in &operation.replaced {
+                self.operations.insert(*index, replaced.clone());
+            }
+            let chained = operation.chained;
             self.redo_stack.push(operation);
-            true
-        } else {
-            false
+
+            if !chained {
+                return true;
+            }
+            match self.operations.pop() {
+                Some(previous) => operation = previous,
+                None => return true,
+            }
         }
     }
 
     pub(crate) fn redo(&mut self) -> bool {
-        if let Some(operation) = self.redo_stack.pop() {
+        let Some(mut operation) = self.redo_stack.pop() else {
+            return false;
+        };
+
+        loop {
+            for (index, _) in operation.replaced.iter().rev() {
+                self.operations.remove(*index);
+            }
             self.operations.push(operation);
-            true
-        } else {
-            false
+
+            match self.redo_stack.last() {
+                Some(next) if next.chained => operation = self.redo_stack.pop().unwrap(),
+                _ => return true,
+            }
         }
     }
 
+    /// The most recent operation.
+    pub(crate) fn last(&self) -> Option<&ViewerOperation> {
+        self.operations.last()
+    }
+
     pub(crate) fn operation_count(&self) -> usize {
         self.operations.len()
     }
@@ -50,31 +131,47 @@ impl OperationHistory {
         self.redo_stack.len()
     }
 
+    /// The number of annotations on a page.
     pub(crate) fn page_count(&self, page: usize) -> usize {
-        self.operations.iter().filter(|op| op.page == page).count()
+        self.page_annotations(page).len()
     }
 
     pub(crate) fn is_empty(&self) -> bool {
         self.operations.is_empty()
     }
 
+    /// The annotations of each page, sorted by page.
+    ///
+    /// Within a page, annotations stay in the order they were created in, so
+    /// that later ones are drawn on top when saved.
     pub(crate) fn grouped_operations(&self) -> Vec<(usize, Vec<Annotation>)> {
         let mut grouped = BTreeMap::<usize, Vec<Annotation>>::new();
         for op in &self.operations {
-            grouped
-                .entry(op.page)
-                .or_default()
-                .push(op.annotation.clone());
+            if let Some(annotation) = op.annotation() {
+                grouped.entry(op.page).or_default().push(annotation.clone());
+            }
         }
         grouped.into_iter().collect()
     }
 
-    /// Get all operations for a specific page as (global_index, &ViewerOperation) pairs.
-    pub(crate) fn page_operations(&self, page: usize) -> Vec<(usize, &ViewerOperation)> {
+    /// The rotation set by the latest rotation operation of each page.
+    pub(crate) fn page_rotations(&self) -> HashMap<usize, i32> {
+        self.operations
+            .iter()
+            .filter_map(|op| match op.kind {
+                OperationKind::Rotation(degrees) => Some((op.page, degrees)),
+                OperationKind::Annotation(_) => None,
+            })
+            .collect()
+    }
+
+    /// Get all annotations for a specific page as (global_index, &Annotation) pairs.
+    pub(crate) fn page_annotations(&self, page: usize) -> Vec<(usize, &Annotation)> {
         self.operations
             .iter()
             .enumerate()
             .filter(|(_, op)| op.page == page)
+            .filter_map(|(index, op)| Some((index, op.annotation()?)))
             .collect()
     }
 
@@ -83,11 +180,15 @@ impl OperationHistory {
     /// For annotation types with internal point data (highlights, ink, lines),
     /// the points are transformed to match the new rect.
     pub(crate) fn update_rect_at(&mut self, index: usize, new_rect: [f32; 4]) -> bool {
-        let Some(op) = self.operations.get_mut(index) else {
+        let Some(ViewerOperation {
+            kind: OperationKind::Annotation(annotation),
+            ..
+        }) = self.operations.get_mut(index)
+        else {
             return false;
         };
 
-        let old_rect = op.annotation.base().rect;
+        let old_rect = annotation.base().rect;
         let old_w = (old_rect[2] - old_rect[0]).max(0.001);
         let old_h = (old_rect[3] - old_rect[1]).max(0.001);
         let new_w = new_rect[2] - new_rect[0];
@@ -98,7 +199,7 @@ impl OperationHistory {
         let dy = new_rect[1] - old_rect[1];
 
         // Update type-specific internal geometry before changing the base rect.
-        match &mut op.annotation {
+        match annotation {
             Annotation::Highlight(a) => {
                 for pt in a.quad_points.chunks_exact_mut(2) {
                     pt[0] = new_rect[0] + (pt[0] - old_rect[0]) * sx;
@@ -123,6 +224,12 @@ impl OperationHistory {
                     pt[1] = new_rect[1] + (pt[1] - old_rect[1]) * sy;
                 }
             }
+            Annotation::Redact(a) => {
+                for pt in a.quad_points.chunks_exact_mut(2) {
+                    pt[0] = new_rect[0] + (pt[0] - old_rect[0]) * sx;
+                    pt[1] = new_rect[1] + (pt[1] - old_rect[1]) * sy;
+                }
+            }
             Annotation::Ink(a) => {
                 for path in &mut a.ink_list {
                     for pt in path.iter_mut() {
@@ -131,29 +238,130 @@ impl OperationHistory {
                     }
                 }
             }
+            Annotation::Polygon(a) | Annotation::PolyLine(a) => {
+                for pt in &mut a.vertices {
+                    pt[0] = new_rect[0] + (pt[0] - old_rect[0]) * sx;
+                    pt[1] = new_rect[1] + (pt[1] - old_rect[1]) * sy;
+                }
+            }
             Annotation::Line(a) => {
                 a.start[0] = new_rect[0] + (a.start[0] - old_rect[0]) * sx;
                 a.start[1] = new_rect[1] + (a.start[1] - old_rect[1]) * sy;
                 a.end[0] = new_rect[0] + (a.end[0] - old_rect[0]) * sx;
                 a.end[1] = new_rect[1] + (a.end[1] - old_rect[1]) * sy;
             }
+            Annotation::FreeText(a) => {
+                for pt in a.callout_line.iter_mut().flatten() {
+                    pt[0] = new_rect[0] + (pt[0] - old_rect[0]) * sx;
+                    pt[1] = new_rect[1] + (pt[1] - old_rect[1]) * sy;
+                }
+            }
             // Types that only have a rect — no extra geometry to update.
-            Annotation::FreeText(_)
-            | Annotation::Square(_)
+            Annotation::Square(_)
             | Annotation::Circle(_)
             | Annotation::Text(_)
             | Annotation::Link(_)
             | Annotation::TextField(_)
-            | Annotation::SignatureField(_) => {}
+            | Annotation::SignatureField(_)
+            | Annotation::CheckBox(_)
+            | Annotation::Choice(_)
+            | Annotation::Stamp(_)
+            | Annotation::FileAttachment(_) => {}
         }
 
         // Now update the base rect.
-        op.annotation.base_mut().rect = new_rect;
+        annotation.base_mut().rect = new_rect;
         let _ = (dx, dy); // used implicitly through new_rect[0]-old_rect[0]
         self.redo_stack.clear();
         true
     }
 
+    /// Merge same-color highlights on a page whose quad groups are adjacent.
+    ///
+    /// Each merged highlight combines the quad points of its sources, in the
+    /// order they were created. All merges are undone as a single step.
+    /// Returns `false` if nothing was merged.
+    pub(crate) fn coalesce_highlights(&mut self, page: usize) -> bool {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+
+        for (index, op) in self.operations.iter().enumerate() {
+            if op.page != page || !matches!(op.annotation(), Some(Annotation::Highlight(_))) {
+                continue;
+            }
+
+            // Fold every group this highlight touches into a new group.
+            let mut merged = vec![index];
+            let mut i = 0;
+            while i < groups.len() {
+                if groups[i]
+                    .iter()
+                    .any(|&other| self.highlights_adjacent(other, index))
+                {
+                    merged.extend(groups.remove(i));
+                } else {
+                    i += 1;
+                }
+            }
+            merged.sort_unstable();
+            groups.push(merged);
+        }
+
+        groups.retain(|group| group.len() > 1);
+        if groups.is_empty() {
+            return false;
+        }
+        groups.sort_by_key(|group| group[0]);
+
+        let mut removed: Vec<usize> = groups.iter().flatten().copied().collect();
+        removed.sort_unstable();
+
+        let mut merged_ops = Vec::new();
+        for group in &groups {
+            let mut ops = group
+                .iter()
+                .filter_map(|&i| self.operations[i].annotation());
+            let Some(Annotation::Highlight(first)) = ops.next() else {
+                unreachable!("groups only contain highlights");
+            };
+            let mut merged = first.clone();
+            for annot in ops {
+                if let Annotation::Highlight(h) = annot {
+                    merged.quad_points.extend_from_slice(&h.quad_points);
+                }
+            }
+            merged.base.rect = rect_from_quad_points(&merged.quad_points);
+            merged_ops.push(ViewerOperation {
+                chained: !merged_ops.is_empty(),
+                ..ViewerOperation::new(page, Annotation::Highlight(merged))
+            });
+        }
+
+        // The first merged operation is undone last, so it restores all sources at once.
+        let mut replaced = Vec::with_capacity(removed.len());
+        for &index in removed.iter().rev() {
+            replaced.push((index, self.operations.remove(index)));
+        }
+        replaced.reverse();
+        merged_ops[0].replaced = replaced;
+
+        self.operations.extend(merged_ops);
+        self.redo_stack.clear();
+        true
+    }
+
+    fn highlights_adjacent(&self, a: usize, b: usize) -> bool {
+        let (Some(Annotation::Highlight(a)), Some(Annotation::Highlight(b))) = (
+            self.operations[a].annotation(),
+            self.operations[b].annotation(),
+        ) else {
+            return false;
+        };
+
+        a.base.color == b.base.color
+            && a.base.opacity == b.base.opacity
+            && quad_groups_adjacent(&a.quad_points, &b.quad_points)
+    }
+
     /// Remove the annotation at a global index.
     pub(crate) fn remove_at(&mut self, index: usize) -> bool {
         if index < self.operations.len() {
@@ -169,7 +377,7 @@ impl OperationHistory {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use hayro_annot::{AnnotationBase, FreeTextAnnot};
+    use hayro_annot::{AnnotColor, AnnotationBase, FreeTextAnnot, HighlightAnnot, VAlign};
 
     fn sample_annotation(text: &str) -> Annotation {
         Annotation::FreeText(FreeTextAnnot {
@@ -180,20 +388,21 @@ mod tests {
             text: text.to_string(),
             font_size: 12.0,
             default_appearance: "0 0 0 rg /Helv 12 Tf".to_string(),
+            font_data: None,
+            multiline: false,
+            line_spacing: 1.2,
+            vertical_align: VAlign::Top,
+            callout_line: None,
+            rich_text: None,
+            default_style: None,
         })
     }
 
     #[test]
     fn undo_redo_roundtrip() {
         let mut history = OperationHistory::default();
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("A"),
-        });
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("B"),
-        });
+        history.push(ViewerOperation::new(0, sample_annotation("A")));
+        history.push(ViewerOperation::new(0, sample_annotation("B")));
 
         assert_eq!(history.operation_count(), 2);
         assert!(history.undo());
@@ -207,43 +416,28 @@ mod tests {
     #[test]
     fn new_push_clears_redo_stack() {
         let mut history = OperationHistory::default();
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("A"),
-        });
+        history.push(ViewerOperation::new(0, sample_annotation("A")));
         assert!(history.undo());
         assert_eq!(history.redo_count(), 1);
 
-        history.push(ViewerOperation {
-            page: 1,
-            annotation: sample_annotation("B"),
-        });
+        history.push(ViewerOperation::new(1, sample_annotation("B")));
         assert_eq!(history.redo_count(), 0);
         assert_eq!(history.operation_count(), 1);
     }
 
     #[test]
-    fn page_operations_returns_filtered_entries() {
+    fn page_annotations_returns_filtered_entries() {
         let mut history = OperationHistory::default();
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("A"),
-        });
-        history.push(ViewerOperation {
-            page: 1,
-            annotation: sample_annotation("B"),
-        });
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("C"),
-        });
-
-        let page0 = history.page_operations(0);
+        history.push(ViewerOperation::new(0, sample_annotation("A")));
+        history.push(ViewerOperation::new(1, sample_annotation("B")));
+        history.push(ViewerOperation::new(0, sample_annotation("C")));
+
+        let page0 = history.page_annotations(0);
         assert_eq!(page0.len(), 2);
         assert_eq!(page0[0].0, 0); // global index
         assert_eq!(page0[1].0, 2); // global index
 
-        let page1 = history.page_operations(1);
+        let page1 = history.page_annotations(1);
         assert_eq!(page1.len(), 1);
         assert_eq!(page1[0].0, 1);
     }
@@ -251,27 +445,18 @@ mod tests {
     #[test]
     fn update_rect_at_changes_base_rect() {
         let mut history = OperationHistory::default();
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("A"),
-        });
+        history.push(ViewerOperation::new(0, sample_annotation("A")));
 
         assert!(history.update_rect_at(0, [5.0, 5.0, 20.0, 20.0]));
-        let rect = history.page_operations(0)[0].1.annotation.base().rect;
+        let rect = history.page_annotations(0)[0].1.base().rect;
         assert_eq!(rect, [5.0, 5.0, 20.0, 20.0]);
     }
 
     #[test]
     fn update_rect_at_clears_redo_stack() {
         let mut history = OperationHistory::default();
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("A"),
-        });
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("B"),
-        });
+        history.push(ViewerOperation::new(0, sample_annotation("A")));
+        history.push(ViewerOperation::new(0, sample_annotation("B")));
         assert!(history.undo());
         assert_eq!(history.redo_count(), 1);
 
@@ -288,18 +473,9 @@ mod tests {
     #[test]
     fn remove_at_removes_and_clears_redo() {
         let mut history = OperationHistory::default();
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("A"),
-        });
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("B"),
-        });
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("C"),
-        });
+        history.push(ViewerOperation::new(0, sample_annotation("A")));
+        history.push(ViewerOperation::new(0, sample_annotation("B")));
+        history.push(ViewerOperation::new(0, sample_annotation("C")));
         // Undo last so redo stack has 1
         assert!(history.undo());
         assert_eq!(history.redo_count(), 1);
@@ -318,18 +494,9 @@ mod tests {
     #[test]
     fn grouped_operations_preserve_page_buckets() {
         let mut history = OperationHistory::default();
-        history.push(ViewerOperation {
-            page: 1,
-            annotation: sample_annotation("A"),
-        });
-        history.push(ViewerOperation {
-            page: 0,
-            annotation: sample_annotation("B"),
-        });
-        history.push(ViewerOperation {
-            page: 1,
-            annotation: sample_annotation("C"),
-        });
+        history.push(ViewerOperation::new(1, sample_annotation("A")));
+        history.push(ViewerOperation::new(0, sample_annotation("B")));
+        history.push(ViewerOperation::new(1, sample_annotation("C")));
 
         let grouped = history.grouped_operations();
         assert_eq!(grouped.len(), 2);
@@ -338,4 +505,112 @@ mod tests {
         assert_eq!(grouped[1].0, 1);
         assert_eq!(grouped[1].1.len(), 2);
     }
+
+    #[test]
+    fn grouped_operations_keep_creation_order_within_page() {
+        let mut history = OperationHistory::default();
+        history.push(ViewerOperation::new(0, sample_annotation("A")));
+        history.push(ViewerOperation::new(1, sample_annotation("B")));
+        history.push(ViewerOperation::new(0, sample_annotation("C")));
+        history.push(ViewerOperation::new(0, sample_annotation("D")));
+
+        let grouped = history.grouped_operations();
+        let texts: Vec<_> = grouped[0]
+            .1
+            .iter()
+            .map(|annotation| match annotation {
+                Annotation::FreeText(a) => a.text.as_str(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(texts, ["A", "C", "D"]);
+    }
+
+    #[test]
+    fn remap_pages_follows_page_order() {
+        let mut history = OperationHistory::default();
+        history.push(ViewerOperation::new(0, sample_annotation("A")));
+        history.push(ViewerOperation::new(1, sample_annotation("B")));
+        history.push(ViewerOperation::new(2, sample_annotation("C")));
+        history.push(ViewerOperation::rotation(2, 90));
+
+        // The first page was deleted, and the last one moved to the front.
+        let page_order = [2, 1];
+        let grouped: Vec<_> = remap_pages(history.grouped_operations(), &page_order);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0].0, 1);
+        assert_eq!(grouped[1].0, 0);
+        let rotations: HashMap<_, _> = remap_pages(history.page_rotations(), &page_order);
+        assert_eq!(rotations, HashMap::from([(0, 90)]));
+    }
+
+    #[test]
+    fn page_rotations_use_latest_operation() {
+        let mut history = OperationHistory::default();
+        history.push(ViewerOperation::rotation(0, 90));
+        history.push(ViewerOperation::new(0, sample_annotation("A")));
+        history.push(ViewerOperation::rotation(0, 180));
+        history.push(ViewerOperation::rotation(2, 270));
+
+        assert_eq!(history.page_count(0), 1);
+        assert_eq!(history.grouped_operations().len(), 1);
+        assert_eq!(
+            history.page_rotations(),
+            HashMap::from([(0, 180), (2, 270)])
+        );
+
+        assert!(history.undo());
+        assert!(history.undo());
+        assert_eq!(history.page_rotations(), HashMap::from([(0, 90)]));
+    }
+
+    fn sample_highlight(x0: f32, x1: f32, color: AnnotColor) -> Annotation {
+        let quad_points = vec![x0, 20.0, x1, 20.0, x0, 10.0, x1, 10.0];
+        Annotation::Highlight(HighlightAnnot {
+            base: AnnotationBase {
+                rect: rect_from_quad_points(&quad_points),
+                color: Some(color),
+                ..Default::default()
+            },
+            quad_points,
+        })
+    }
+
+    #[test]
+    fn coalesce_highlights_merges_adjacent_same_color() {
+        let mut history = OperationHistory::default();
+        history.push(ViewerOperation::new(
+            0,
+            sample_highlight(0.0, 50.0, AnnotColor::yellow()),
+        ));
+        history.push(ViewerOperation::new(
+            0,
+            sample_highlight(51.0, 90.0, AnnotColor::yellow()),
+        ));
+        history.push(ViewerOperation::new(
+            0,
+            sample_highlight(91.0, 120.0, AnnotColor::red()),
+        ));
+
+        assert!(history.coalesce_highlights(0));
+        assert_eq!(history.operation_count(), 2);
+
+        let ops = history.page_annotations(0);
+        let Annotation::Highlight(merged) = ops[1].1 else {
+            panic!("expected a merged highlight");
+        };
+        assert_eq!(merged.quad_points.len(), 16);
+        assert_eq!(
+            merged.quad_points[..8],
+            [0.0, 20.0, 50.0, 20.0, 0.0, 10.0, 50.0, 10.0]
+        );
+        assert_eq!(merged.base.rect, [0.0, 10.0, 90.0, 20.0]);
+
+        // A single undo restores both original highlights.
+        assert!(history.undo());
+        assert_eq!(history.operation_count(), 3);
+        assert!(history.redo());
+        assert_eq!(history.operation_count(), 2);
+        assert!(!history.coalesce_highlights(0));
+    }
 }
@@ -0,0 +1,324 @@
+//! Page rendering that doesn't depend on the viewer state.
+//!
+//! `PdfViewer` holds the editing state and isn't `Send`, so rendering lives in
+//! plain functions over a parsed [`Pdf`]. [`PageRenderer`] owns its own parsed
+//! copy of a document, which lets a Web Worker render pages while the main
+//! thread keeps handling input.
+
+use hayro::hayro_interpret::InterpreterSettings;
+use hayro::hayro_syntax::Pdf;
+use hayro::hayro_syntax::page::Page;
+use hayro::{BgMode, RenderSettings, RenderTimings};
+use std::collections::HashSet;
+use vello_cpu::color::palette::css::WHITE;
+use wasm_bindgen::prelude::*;
+
+/// The unpremultiplied RGBA pixels of a rendered page.
+#[derive(Clone)]
+pub(crate) struct RenderedPage {
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+    pub(crate) pixels: Vec<u8>,
+}
+
+impl RenderedPage {
+    /// Convert the page into the `[width, height, pixel_data]` array returned to JavaScript.
+    pub(crate) fn into_js_array(self) -> js_sys::Array {
+        let result = js_sys::Array::new_with_length(3);
+        result.set(0, JsValue::from(self.width));
+        result.set(1, JsValue::from(self.height));
+        result.set(2, JsValue::from(self.pixels));
+        result
+    }
+
+    /// Encode the page as a PNG image.
+    ///
+    /// The pixels are already unpremultiplied, which is what PNG expects, so
+    /// semi-transparent pixels aren't darkened.
+    pub(crate) fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut data = Vec::new();
+        let mut encoder =
+            png::Encoder::new(&mut data, u32::from(self.width), u32::from(self.height));
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+
+        Ok(data)
+    }
+}
+
+/// Render a page at the given scale on the given background, optionally in
+/// grayscale.
+///
+/// If `visible_layers` is given, only the optional content groups with these
+/// names are shown.
+///
+/// `now` returns the current time in milliseconds and is used for the timings.
+pub(crate) fn render_page(
+    page: &Page<'_>,
+    scale: f32,
+    bg_mode: BgMode,
+    grayscale: bool,
+    visible_layers: Option<&HashSet<String>>,
+    now: &dyn Fn() -> f64,
+) -> (RenderedPage, RenderTimings) {
+    let render_settings = RenderSettings {
+        x_scale: scale,
+        y_scale: scale,
+        bg_mode,
+        grayscale,
+        ..Default::default()
+    };
+
+    let (pixmap, timings) = hayro::render_timed(
+        page,
+        &interpreter_settings(visible_layers),
+        &render_settings,
+        now,
+    );
+
+    let width = pixmap.width();
+    let height = pixmap.height();
+    // Cast Vec<Rgba8> to Vec<u8>
+    let pixels: Vec<u8> = bytemuck::cast_vec(pixmap.take_unpremultiplied());
+
+    (
+        RenderedPage {
+            width,
+            height,
+            pixels,
+        },
+        timings,
+    )
+}
+
+/// The interpreter settings for rendering pages, showing only the optional
+/// content groups named in `visible_layers` if given.
+pub(crate) fn interpreter_settings(
+    visible_layers: Option<&HashSet<String>>,
+) -> InterpreterSettings {
+    InterpreterSettings {
+        visible_layers: visible_layers.cloned(),
+        ..Default::default()
+    }
+}
+
+/// Validate a zoom factor and combine it with the device pixel ratio.
+pub(crate) fn render_scale(zoom: f32, device_pixel_ratio: f32) -> Result<f32, JsValue> {
+    if !zoom.is_finite() || zoom <= 0.0 {
+        return Err(JsValue::from_str("Zoom must be a positive finite number"));
+    }
+
+    Ok(zoom * device_pixel_ratio.max(0.1))
+}
+
+/// The scale at which the longest side of a page with the given dimensions
+/// is rendered with `max_dimension` pixels.
+pub(crate) fn thumbnail_scale(width: f32, height: f32, max_dimension: u32) -> Result<f32, JsValue> {
+    if max_dimension == 0 {
+        return Err(JsValue::from_str("Thumbnail size must be positive"));
+    }
+
+    // The rendered size is rounded down, so aim for the middle of the last
+    // pixel to not lose it to floating point errors.
+    Ok((max_dimension as f32 + 0.5) / width.max(height).max(1.0))
+}
+
+/// The largest width or height, in pixels, of a page rendered at a given DPI.
+const MAX_DPI_DIMENSION: f32 = 16_384.0;
+
+/// The largest number of pixels of a page rendered at a given DPI, which
+/// keeps the RGBA buffer at 256 MiB.
+const MAX_DPI_PIXELS: f32 = 67_108_864.0;
+
+/// The scale at which a page with the given dimensions in points is rendered
+/// at `dpi` dots per inch.
+///
+/// A PDF point is 1/72 inch, so this is `dpi / 72`. Fails if the page would
+/// become so large that rendering it would need an unreasonable amount of memory.
+pub(crate) fn dpi_scale(width: f32, height: f32, dpi: f32) -> Result<f32, JsValue> {
+    if !dpi.is_finite() || dpi <= 0.0 {
+        return Err(JsValue::from_str("DPI must be a positive finite number"));
+    }
+
+    let scale = dpi / 72.0;
+    let (pixel_width, pixel_height) = (width * scale, height * scale);
+    if pixel_width.max(pixel_height) > MAX_DPI_DIMENSION
+        || pixel_width * pixel_height > MAX_DPI_PIXELS
+    {
+        return Err(JsValue::from_str(&format!(
+            "Rendering at {dpi} DPI would produce a {}x{} image, which is too large",
+            pixel_width as u32, pixel_height as u32
+        )));
+    }
+
+    Ok(scale)
+}
+
+/// A renderer that holds its own parsed document, meant to live in a Web Worker.
+///
+/// The worker receives the document bytes (for example the result of
+/// `PdfViewer.save()`) in a message, creates a renderer from them and answers
+/// render requests with the pixel data, which can be transferred back to the
+/// main thread without copying.
+#[wasm_bindgen]
+pub struct PageRenderer {
+    pdf: Pdf,
+    grayscale: bool,
+}
+
+#[wasm_bindgen]
+impl PageRenderer {
+    /// Parse the given document bytes.
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[u8]) -> Result<Self, JsValue> {
+        let pdf = Pdf::new(data.to_vec()).map_err(|_| JsValue::from_str("Failed to parse PDF"))?;
+        Ok(Self {
+            pdf,
+            grayscale: false,
+        })
+    }
+
+    /// Set whether pages are rendered in grayscale.
+    #[wasm_bindgen]
+    pub fn set_grayscale(&mut self, grayscale: bool) {
+        self.grayscale = grayscale;
+    }
+
+    /// The number of pages in the document.
+    #[wasm_bindgen]
+    pub fn page_count(&self) -> usize {
+        self.pdf.pages().len()
+    }
+
+    /// Render a specific page (1-based) at a fixed zoom scale.
+    ///
+    /// Returns `[width, height, pixel_data]`, like `PdfViewer.render_page_scaled`.
+    #[wasm_bindgen]
+    pub fn render_page_scaled(
+        &self,
+        page: usize,
+        zoom: f32,
+        device_pixel_ratio: f32,
+    ) -> Result<js_sys::Array, JsValue> {
+        let scale = render_scale(zoom, device_pixel_ratio)?;
+        let pages = self.pdf.pages();
+        let page = page
+            .checked_sub(1)
+            .and_then(|idx| pages.get(idx))
+            .ok_or("Page out of bounds")?;
+
+        Ok(render_page(
+            page,
+            scale,
+            BgMode::Solid(WHITE),
+            self.grayscale,
+            None,
+            &|| 0.0,
+        )
+        .0
+        .into_js_array())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumbnails_fit_longest_side() {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../hayro-tests/pdfs/custom/separation_none.pdf");
+        let data = std::fs::read(&path).expect("fixture should exist");
+        let pdf = Pdf::new(data).expect("fixture should parse");
+        let page = &pdf.pages()[0];
+
+        for max_dimension in [37, 100, 256] {
+            let (width, height) = page.render_dimensions();
+            let scale = thumbnail_scale(width, height, max_dimension).unwrap();
+            let (rendered, _) =
+                render_page(page, scale, BgMode::Solid(WHITE), false, None, &|| 0.0);
+
+            assert_eq!(
+                u32::from(rendered.width.max(rendered.height)),
+                max_dimension
+            );
+        }
+    }
+
+    #[test]
+    fn png_keeps_dimensions_and_colors() {
+        let page = RenderedPage {
+            width: 2,
+            height: 1,
+            pixels: vec![255, 255, 255, 255, 200, 100, 50, 128],
+        };
+        let png = page.to_png().unwrap();
+
+        let mut reader = png::Decoder::new(std::io::Cursor::new(png))
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(pixels, page.pixels);
+    }
+
+    #[test]
+    fn dpi_scale_converts_points_to_pixels() {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../hayro-tests/pdfs/custom/separation_none.pdf");
+        let data = std::fs::read(&path).expect("fixture should exist");
+        let pdf = Pdf::new(data).expect("fixture should parse");
+        let page = &pdf.pages()[0];
+        let (width, height) = page.render_dimensions();
+
+        assert_eq!(dpi_scale(width, height, 72.0).unwrap(), 1.0);
+        assert_eq!(dpi_scale(width, height, 300.0).unwrap(), 300.0 / 72.0);
+
+        let (at_72, _) = render_page(
+            page,
+            dpi_scale(width, height, 72.0).unwrap(),
+            BgMode::Solid(WHITE),
+            false,
+            None,
+            &|| 0.0,
+        );
+        let (at_144, _) = render_page(
+            page,
+            dpi_scale(width, height, 144.0).unwrap(),
+            BgMode::Solid(WHITE),
+            false,
+            None,
+            &|| 0.0,
+        );
+        // Fractional page sizes are rounded, so allow for an off-by-one.
+        assert!(at_144.width.abs_diff(at_72.width * 2) <= 1);
+        assert!(at_144.height.abs_diff(at_72.height * 2) <= 1);
+    }
+
+    #[test]
+    fn pages_can_be_rendered_on_another_thread() {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../hayro-tests/pdfs/custom/separation_none.pdf");
+        let data = std::fs::read(&path).expect("fixture should exist");
+        let pdf = Pdf::new(data).expect("fixture should parse");
+
+        let rendered = std::thread::spawn(move || {
+            let pages = pdf.pages();
+            let (rendered, _) =
+                render_page(&pages[0], 0.5, BgMode::Solid(WHITE), false, None, &|| 0.0);
+            rendered
+        })
+        .join()
+        .expect("rendering thread should not panic");
+
+        assert!(rendered.width > 0 && rendered.height > 0);
+        assert_eq!(
+            rendered.pixels.len(),
+            usize::from(rendered.width) * usize::from(rendered.height) * 4
+        );
+    }
+}
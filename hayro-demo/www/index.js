@@ -954,16 +954,13 @@ function renderTextLayer(page, node) {
     const pageInfo = state.pageInfos[page - 1];
     if (!pageInfo) return;
 
-    const spans = state.pdfViewer.get_text_spans(page);
+    const spans = state.pdfViewer.text_spans(page);
     node.textLayer.innerHTML = '';
     for (const spanData of spans) {
-        const text = spanData[0];
+        const { text, x0, y0, x1, y1 } = spanData;
+        spanData.free();
         if (!text || !String(text).trim()) continue;
 
-        const x0 = spanData[1];
-        const y0 = spanData[2];
-        const x1 = spanData[3];
-        const y1 = spanData[4];
         const [screenRectX0, screenRectY0, screenRectX1, screenRectY1] = pdfRectToScreen(
             pageInfo,
             x0,
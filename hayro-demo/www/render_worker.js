@@ -0,0 +1,39 @@
+// Module worker that renders pages off the main thread.
+//
+// Requests and responses:
+//   { type: 'load', data }                               -> { type: 'loaded', pageCount }
+//   { type: 'render', id, page, zoom, devicePixelRatio } -> { type: 'rendered', id, width, height, pixels }
+// Failures are answered with { type: 'error', id, message }.
+//
+// Create it with `new Worker('./render_worker.js', { type: 'module' })` and send
+// the document bytes (e.g. from `PdfViewer.save()`) whenever they change.
+import init, { PageRenderer } from './hayro_demo.js';
+
+const ready = init();
+let renderer = null;
+
+self.onmessage = async (event) => {
+    await ready;
+    const message = event.data;
+
+    try {
+        if (message.type === 'load') {
+            renderer?.free();
+            renderer = new PageRenderer(message.data);
+            self.postMessage({ type: 'loaded', pageCount: renderer.page_count() });
+        } else if (message.type === 'render') {
+            if (!renderer) {
+                throw new Error('No PDF loaded');
+            }
+            const [width, height, pixels] = renderer.render_page_scaled(
+                message.page,
+                message.zoom,
+                message.devicePixelRatio,
+            );
+            // Transfer the pixel buffer instead of copying it.
+            self.postMessage({ type: 'rendered', id: message.id, width, height, pixels }, [pixels.buffer]);
+        }
+    } catch (error) {
+        self.postMessage({ type: 'error', id: message.id, message: String(error) });
+    }
+};
@@ -0,0 +1,153 @@
+//! This example measures how much faster an image-heavy page is interpreted in
+//! text-only mode, as used by text extraction.
+//!
+//! The page is generated on the fly and contains a line of text between many
+//! large images. It is interpreted with a device that ignores everything, like
+//! a text extractor would ignore images, once with `text_only` disabled and
+//! once with it enabled. Run it in release mode:
+//!
+//! ```sh
+//! cargo run --release -p hayro-interpret --example text_only_benchmark
+//! ```
+
+use hayro_interpret::font::Glyph;
+use hayro_interpret::{
+    BlendMode, ClipPath, Context, Device, GlyphDrawMode, Image, InterpreterSettings, Paint,
+    PathDrawMode, SoftMask, interpret_page,
+};
+use hayro_syntax::Pdf;
+use kurbo::{Affine, BezPath, Rect};
+use pdf_writer::{Finish, Name, Ref};
+use std::time::{Duration, Instant};
+
+const NUM_IMAGES: usize = 50;
+const IMAGE_SIZE: u32 = 512;
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    let pdf = Pdf::new(image_heavy_pdf()).unwrap();
+    let page = &pdf.pages()[0];
+
+    let time = |text_only: bool| {
+        let settings = InterpreterSettings {
+            text_only,
+            ..Default::default()
+        };
+        let start = Instant::now();
+
+        for _ in 0..ITERATIONS {
+            // Pass dummy values for bbox and initial transform, since we don't care about those.
+            let mut context = Context::new(
+                Affine::IDENTITY,
+                Rect::new(0.0, 0.0, 1.0, 1.0),
+                pdf.xref(),
+                settings.clone(),
+            );
+            interpret_page(page, &mut context, &mut NoopDevice);
+        }
+
+        start.elapsed() / ITERATIONS
+    };
+
+    let full = time(false);
+    let text_only = time(true);
+
+    println!("{NUM_IMAGES} images of {IMAGE_SIZE}x{IMAGE_SIZE} pixels, {ITERATIONS} iterations:");
+    println!("  full:      {}", format_duration(full));
+    println!("  text-only: {}", format_duration(text_only));
+    println!(
+        "  speedup:   {:.1}x",
+        full.as_secs_f64() / text_only.as_secs_f64().max(f64::EPSILON)
+    );
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3} ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Create a single-page PDF with `NUM_IMAGES` image `XObjects` and a line of text.
+fn image_heavy_pdf() -> Vec<u8> {
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let font_id = Ref::new(4);
+    let content_id = Ref::new(5);
+    let image_ids = (0..NUM_IMAGES as i32)
+        .map(|i| Ref::new(6 + i))
+        .collect::<Vec<_>>();
+
+    let mut pdf = pdf_writer::Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+
+    let names = (0..NUM_IMAGES)
+        .map(|i| format!("Im{i}"))
+        .collect::<Vec<_>>();
+
+    let mut page = pdf.page(page_id);
+    page.parent(page_tree_id);
+    page.media_box(pdf_writer::Rect::new(0.0, 0.0, 595.0, 842.0));
+    page.contents(content_id);
+    let mut resources = page.resources();
+    resources.fonts().pair(Name(b"F1"), font_id);
+    let mut x_objects = resources.x_objects();
+    for (name, id) in names.iter().zip(&image_ids) {
+        x_objects.pair(Name(name.as_bytes()), *id);
+    }
+    x_objects.finish();
+    resources.finish();
+    page.finish();
+
+    pdf.type1_font(font_id).base_font(Name(b"Helvetica"));
+
+    let mut content = String::new();
+    for name in &names {
+        content.push_str(&format!("q 495 0 0 742 50 50 cm /{name} Do Q\n"));
+    }
+    content.push_str("BT /F1 12 Tf 72 800 Td (Caption) Tj ET\n");
+    pdf.stream(content_id, content.as_bytes());
+
+    for (i, id) in image_ids.iter().enumerate() {
+        let data = (0..IMAGE_SIZE * IMAGE_SIZE * 3)
+            .map(|p| (p as usize + i) as u8)
+            .collect::<Vec<_>>();
+        let mut image = pdf.image_xobject(*id, &data);
+        image.width(IMAGE_SIZE as i32);
+        image.height(IMAGE_SIZE as i32);
+        image.color_space().device_rgb();
+        image.bits_per_component(8);
+    }
+
+    pdf.finish()
+}
+
+/// A device that ignores everything it is given.
+struct NoopDevice;
+
+impl Device<'_> for NoopDevice {
+    fn set_soft_mask(&mut self, _: Option<SoftMask<'_>>) {}
+
+    fn set_blend_mode(&mut self, _: BlendMode) {}
+
+    fn draw_path(&mut self, _: &BezPath, _: Affine, _: &Paint<'_>, _: &PathDrawMode) {}
+
+    fn push_clip_path(&mut self, _: &ClipPath) {}
+
+    fn push_transparency_group(&mut self, _: f32, _: Option<SoftMask<'_>>, _: BlendMode) {}
+
+    fn draw_glyph(
+        &mut self,
+        _: &Glyph<'_>,
+        _: Affine,
+        _: Affine,
+        _: &Paint<'_>,
+        _: &GlyphDrawMode,
+    ) {
+    }
+
+    fn draw_image(&mut self, _: Image<'_, '_>, _: Affine) {}
+
+    fn pop_clip_path(&mut self) {}
+
+    fn pop_transparency_group(&mut self) {}
+}
@@ -5,7 +5,7 @@ use crate::font::Font;
 use crate::interpret::state::{ClipType, State};
 use crate::ocg::OcgState;
 use crate::util::Float64Ext;
-use crate::{ClipPath, Device, FillRule, InterpreterSettings, StrokeProps};
+use crate::{ClipPath, Device, FillRule, InterpreterSettings, InterpreterWarning, StrokeProps};
 use hayro_syntax::content::ops::Transform;
 use hayro_syntax::object::Dict;
 use hayro_syntax::object::Name;
@@ -56,7 +56,9 @@ impl<'a> Context<'a> {
         let ocg_state = {
             let root_ref = xref.root_id();
             xref.get::<Dict<'_>>(root_ref)
-                .map(|catalog| OcgState::from_catalog(&catalog))
+                .map(|catalog| {
+                    OcgState::from_catalog(&catalog, xref, settings.visible_layers.as_ref())
+                })
                 .unwrap_or_default()
         };
 
@@ -76,6 +78,11 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Pass a warning to the warning sink of the settings.
+    pub(crate) fn warn(&self, warning: InterpreterWarning) {
+        (self.settings.warning_sink)(warning);
+    }
+
     pub(crate) fn save_state(&mut self) {
         let Some(cur) = self.states.last().cloned() else {
             warn!("attempted to save state without existing state");
@@ -1,6 +1,7 @@
 use crate::font::blob::{CffFontBlob, OpenTypeFontBlob};
-use crate::font::{FontFlags, read_to_unicode, strip_subset_prefix};
-use crate::{CMapResolverFn, CacheKey};
+use crate::font::fallback::FallbackChain;
+use crate::font::{FallbackFontQuery, FontFlags, read_to_unicode, strip_subset_prefix};
+use crate::{CMapResolverFn, CacheKey, FallbackFontResolverFn};
 use hayro_cmap::{BfString, CMap, WritingMode};
 use hayro_syntax::object::Dict;
 use hayro_syntax::object::Name;
@@ -8,6 +9,7 @@ use hayro_syntax::object::Stream;
 use hayro_syntax::object::dict::keys::*;
 use hayro_syntax::object::{Array, Object};
 use kurbo::{BezPath, Vec2};
+use log::warn;
 use skrifa::attribute::Style;
 use skrifa::{FontRef, GlyphId, MetadataProvider};
 use std::collections::HashMap;
@@ -33,7 +35,11 @@ pub(crate) struct Type0Font {
 }
 
 impl Type0Font {
-    pub(crate) fn new(dict: &Dict<'_>, cmap_resolver: &CMapResolverFn) -> Option<Self> {
+    pub(crate) fn new(
+        dict: &Dict<'_>,
+        fallback_resolver: &FallbackFontResolverFn,
+        cmap_resolver: &CMapResolverFn,
+    ) -> Option<Self> {
         let cmap = read_encoding(&dict.get::<Object<'_>>(ENCODING)?, cmap_resolver)?;
 
         let horizontal = cmap.metadata().writing_mode != Some(WritingMode::Vertical);
@@ -43,7 +49,16 @@ impl Type0Font {
             .iter::<Dict<'_>>()
             .next()?;
         let font_descriptor = descendant_font.get::<Dict<'_>>(FONT_DESC)?;
-        let font_type = FontType::new(&font_descriptor)?;
+        let font_type = FontType::new(&font_descriptor).unwrap_or_else(|| {
+            let query = FallbackFontQuery::new(&descendant_font);
+
+            warn!(
+                "unable to load font {}, falling back to fallback fonts",
+                query.post_script_name.as_deref().unwrap_or("(no name)")
+            );
+
+            FontType::Fallback(FallbackChain::new(query, fallback_resolver))
+        });
 
         let default_width = descendant_font.get::<f32>(DW).unwrap_or(1000.0);
         let dw2 = descendant_font
@@ -115,6 +130,14 @@ impl Type0Font {
                     self.cid_to_gid_map.map(cid as u16)
                 }
             }
+            // Without a font program, the glyph can only be found by the
+            // Unicode code point of its character code.
+            FontType::Fallback(chain) => match self.char_code_to_unicode(code) {
+                Some(BfString::Char(c)) => chain.map_unicode(c),
+                Some(BfString::String(s)) => s.chars().next().and_then(|c| chain.map_unicode(c)),
+                None => None,
+            }
+            .unwrap_or(GlyphId::NOTDEF),
         }
     }
 
@@ -132,13 +155,16 @@ impl Type0Font {
         match &self.font_type {
             FontType::OpenType(t) => t.outline_glyph(glyph),
             FontType::Cff(c) => c.outline_glyph(glyph),
+            FontType::Fallback(chain) => chain.outline_glyph(glyph).unwrap_or_default(),
         }
     }
 
-    pub(crate) fn font_data(&self) -> crate::font::FontData {
+    /// Get the embedded font program, or `None` if the font isn't embedded.
+    pub(crate) fn font_data(&self) -> Option<crate::font::FontData> {
         match &self.font_type {
-            FontType::OpenType(t) => t.font_data(),
-            FontType::Cff(c) => c.font_data(),
+            FontType::OpenType(t) => Some(t.font_data()),
+            FontType::Cff(c) => Some(c.font_data()),
+            FontType::Fallback(_) => None,
         }
     }
 
@@ -156,7 +182,7 @@ impl Type0Font {
                 let weight = t.font_ref().attributes().weight.value().round() as u32;
                 if weight > 0 { Some(weight) } else { None }
             }
-            FontType::Cff(_) => None,
+            FontType::Cff(_) | FontType::Fallback(_) => None,
         }
     }
 
@@ -169,7 +195,7 @@ impl Type0Font {
         }
         match &self.font_type {
             FontType::OpenType(t) => t.font_ref().attributes().style != Style::Normal,
-            FontType::Cff(_) => false,
+            FontType::Cff(_) | FontType::Fallback(_) => false,
         }
     }
 
@@ -196,7 +222,7 @@ impl Type0Font {
                     )
                     .is_monospace
             }
-            FontType::Cff(_) => false,
+            FontType::Cff(_) | FontType::Fallback(_) => false,
         }
     }
 
@@ -269,6 +295,9 @@ enum FontType {
     OpenType(OpenTypeFontBlob),
     /// A CFF font.
     Cff(CffFontBlob),
+    /// A font that isn't embedded, whose glyphs are taken from the fallback
+    /// fonts.
+    Fallback(FallbackChain),
 }
 
 impl FontType {
@@ -0,0 +1,90 @@
+//! Fonts that substitute glyphs which are missing from the font used for a
+//! non-embedded font.
+
+use crate::FallbackFontResolverFn;
+use crate::font::FallbackFontQuery;
+use crate::font::generated::glyph_names;
+use crate::font::standard_font::StandardFontBlob;
+use kurbo::BezPath;
+use skrifa::GlyphId;
+use std::cell::OnceCell;
+use std::fmt::{self, Debug, Formatter};
+
+/// The glyph IDs of fallback fonts are shifted by this many bits and tagged
+/// with the index of their font (plus one), so that they don't collide with
+/// the glyphs of the primary font, which have at most 16 bits.
+const FALLBACK_GLYPH_SHIFT: u32 = 24;
+
+/// An ordered list of fonts that are tried in turn for glyphs that the
+/// primary font doesn't have.
+///
+/// The fonts are only requested from the resolver once a glyph is missing.
+pub(crate) struct FallbackChain {
+    query: FallbackFontQuery,
+    resolver: FallbackFontResolverFn,
+    fonts: OnceCell<Vec<StandardFontBlob>>,
+}
+
+impl FallbackChain {
+    pub(crate) fn new(query: FallbackFontQuery, resolver: &FallbackFontResolverFn) -> Self {
+        Self {
+            query,
+            resolver: resolver.clone(),
+            fonts: OnceCell::new(),
+        }
+    }
+
+    fn fonts(&self) -> &[StandardFontBlob] {
+        self.fonts.get_or_init(|| {
+            (self.resolver)(&self.query)
+                .into_iter()
+                .filter_map(|(data, index)| StandardFontBlob::from_data(data, index))
+                .collect()
+        })
+    }
+
+    /// Find the glyph with the given PostScript name in the first font that
+    /// has it, either by its name or by its Unicode code point.
+    pub(crate) fn map_name(&self, name: &str) -> Option<GlyphId> {
+        let unicode = glyph_names::get(name).and_then(|s| s.chars().next());
+
+        self.find_glyph(|font| {
+            font.name_to_glyph(name)
+                .or_else(|| font.unicode_to_glyph(unicode? as u32))
+        })
+    }
+
+    /// Find the glyph of a Unicode code point in the first font that has it.
+    pub(crate) fn map_unicode(&self, unicode: char) -> Option<GlyphId> {
+        self.find_glyph(|font| font.unicode_to_glyph(unicode as u32))
+    }
+
+    fn find_glyph(&self, lookup: impl Fn(&StandardFontBlob) -> Option<GlyphId>) -> Option<GlyphId> {
+        self.fonts().iter().enumerate().find_map(|(index, font)| {
+            let glyph = lookup(font).filter(|glyph| *glyph != GlyphId::NOTDEF)?;
+
+            Some(GlyphId::new(
+                ((index as u32 + 1) << FALLBACK_GLYPH_SHIFT) | glyph.to_u32(),
+            ))
+        })
+    }
+
+    /// The outline of a glyph returned by [`FallbackChain::map_name`] or
+    /// [`FallbackChain::map_unicode`], or `None` if the glyph belongs to the
+    /// primary font.
+    pub(crate) fn outline_glyph(&self, glyph: GlyphId) -> Option<BezPath> {
+        let index = (glyph.to_u32() >> FALLBACK_GLYPH_SHIFT).checked_sub(1)?;
+        let font = self.fonts().get(index as usize)?;
+        let glyph = GlyphId::new(glyph.to_u32() & ((1 << FALLBACK_GLYPH_SHIFT) - 1));
+
+        Some(font.outline_glyph(glyph))
+    }
+}
+
+impl Debug for FallbackChain {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FallbackChain")
+            .field("query", &self.query)
+            .finish_non_exhaustive()
+    }
+}
@@ -11,7 +11,9 @@ use crate::font::true_type::TrueTypeFont;
 use crate::font::type1::Type1Font;
 use crate::font::type3::Type3;
 use crate::interpret::state::State;
-use crate::{CMapResolverFn, CacheKey, FontResolverFn, InterpreterSettings, Paint};
+use crate::{
+    CMapResolverFn, CacheKey, FallbackFontResolverFn, FontResolverFn, InterpreterSettings, Paint,
+};
 use bitflags::bitflags;
 use hayro_syntax::object::Name;
 use hayro_syntax::object::dict::keys::SUBTYPE;
@@ -19,7 +21,7 @@ use hayro_syntax::object::dict::keys::*;
 use hayro_syntax::object::{Dict, Stream};
 use hayro_syntax::page::Resources;
 use hayro_syntax::xref::XRef;
-use kurbo::{Affine, BezPath, Vec2};
+use kurbo::{Affine, BezPath, Rect, Vec2};
 use log::warn;
 use outline::OutlineFont;
 use skrifa::GlyphId;
@@ -30,6 +32,7 @@ use std::sync::Arc;
 
 mod blob;
 mod cid;
+mod fallback;
 mod generated;
 mod glyph_simulator;
 pub(crate) mod outline;
@@ -85,6 +88,7 @@ impl Glyph<'_> {
     ///
     /// **For Type3 Fonts:**
     /// 1. `ToUnicode` cmap
+    /// 2. Glyph name → Unicode (via Adobe Glyph List)
     ///
     /// Returns `None` if the Unicode value could not be determined.
     ///
@@ -146,7 +150,8 @@ impl OutlineGlyph {
 
     /// Get raw font bytes and metadata for downstream use.
     ///
-    /// Returns `None` for Type1 fonts.
+    /// Returns `None` for Type1 fonts and for composite fonts that aren't
+    /// embedded.
     pub fn font_data(&self) -> Option<OutlineFontData> {
         self.font.font_data()
     }
@@ -164,6 +169,25 @@ impl OutlineGlyph {
         self.font.glyph_advance_width(self.char_code)
     }
 
+    /// The advance width of a space in this glyph's font, if it has one.
+    pub(crate) fn space_advance_width(&self) -> Option<f32> {
+        self.font.space_advance_width()
+    }
+
+    /// The vertical advance of this glyph, or `None` if its font is not in
+    /// vertical writing mode.
+    ///
+    /// The advance is negative, since vertical text runs downwards.
+    pub(crate) fn vertical_advance(&self) -> Option<f32> {
+        self.font.glyph_vertical_advance(self.char_code)
+    }
+
+    /// The displacement of the glyph origin from the text position, in glyph
+    /// space.
+    pub(crate) fn origin_displacement(&self) -> Vec2 {
+        self.font.origin_displacement(self.char_code)
+    }
+
     /// Get the cache key for this glyph's font.
     ///
     /// This identifies the font uniquely, even when `font_data()` returns `None`
@@ -202,10 +226,32 @@ impl<'a> Type3Glyph<'a> {
 
     /// Returns the Unicode code point for this glyph, if available.
     ///
-    /// Note: Type3 fonts can only provide Unicode via `ToUnicode` cmap.
+    /// Note: Type3 fonts can only provide Unicode via `ToUnicode` cmap or
+    /// standard glyph names in their encoding.
     pub fn as_unicode(&self) -> Option<BfString> {
         self.font.char_code_to_unicode(self.char_code)
     }
+
+    /// The bounding box of the glyph in glyph space, if it can be determined
+    /// from its glyph procedure.
+    pub(crate) fn bbox(&self) -> Option<Rect> {
+        self.font.glyph_bbox(self.glyph_id)
+    }
+
+    /// The advance width of the glyph, in the same units as outline glyphs.
+    pub(crate) fn advance_width(&self) -> f32 {
+        self.font.glyph_width(self.char_code as u8)
+    }
+
+    /// The advance width of a space in this glyph's font, if it has one.
+    pub(crate) fn space_advance_width(&self) -> Option<f32> {
+        matches!(
+            self.font.char_code_to_unicode(32),
+            Some(BfString::Char(' '))
+        )
+        .then(|| self.font.glyph_width(32))
+        .filter(|width| *width > 0.0)
+    }
 }
 
 impl CacheKey for Type3Glyph<'_> {
@@ -221,21 +267,29 @@ impl<'a> Font<'a> {
     pub(crate) fn new(
         dict: &Dict<'a>,
         font_resolver: &FontResolverFn,
+        fallback_font_resolver: &FallbackFontResolverFn,
         cmap_resolver: &CMapResolverFn,
     ) -> Option<Self> {
         let f_type = match dict.get::<Name>(SUBTYPE)?.deref() {
-            TYPE1 | MM_TYPE1 => {
-                FontType::Type1(Rc::new(Type1Font::new(dict, font_resolver, cmap_resolver)?))
-            }
+            TYPE1 | MM_TYPE1 => FontType::Type1(Rc::new(Type1Font::new(
+                dict,
+                font_resolver,
+                fallback_font_resolver,
+                cmap_resolver,
+            )?)),
             TRUE_TYPE => TrueTypeFont::new(dict, cmap_resolver)
                 .map(Rc::new)
                 .map(FontType::TrueType)
                 .or_else(|| {
-                    Type1Font::new(dict, font_resolver, cmap_resolver)
+                    Type1Font::new(dict, font_resolver, fallback_font_resolver, cmap_resolver)
                         .map(Rc::new)
                         .map(FontType::Type1)
                 })?,
-            TYPE0 => FontType::Type0(Rc::new(Type0Font::new(dict, cmap_resolver)?)),
+            TYPE0 => FontType::Type0(Rc::new(Type0Font::new(
+                dict,
+                fallback_font_resolver,
+                cmap_resolver,
+            )?)),
             TYPE3 => FontType::Type3(Rc::new(Type3::new(dict, cmap_resolver)?)),
             f => {
                 warn!(
@@ -255,8 +309,9 @@ impl<'a> Font<'a> {
     pub(crate) fn new_standard(
         standard_font: StandardFont,
         font_resolver: &FontResolverFn,
+        fallback_font_resolver: &FallbackFontResolverFn,
     ) -> Option<Self> {
-        let font = Type1Font::new_standard(standard_font, font_resolver)?;
+        let font = Type1Font::new_standard(standard_font, font_resolver, fallback_font_resolver)?;
 
         Some(Self(0, FontType::Type1(Rc::new(font))))
     }
@@ -393,6 +448,56 @@ impl CacheKey for Font<'_> {
     }
 }
 
+/// A font of a resource dictionary, for laying out the glyphs of a text
+/// string without interpreting a content stream.
+///
+/// Fonts are loaded like in the interpreter, so strings are split into
+/// character codes and advanced exactly like when they are drawn.
+#[derive(Clone, Debug)]
+pub struct TextFont<'a>(Font<'a>);
+
+impl<'a> TextFont<'a> {
+    /// Load a font dictionary with the font and `CMap` resolvers of `settings`.
+    pub fn new(dict: &Dict<'a>, settings: &InterpreterSettings) -> Option<Self> {
+        Font::new(
+            dict,
+            &settings.font_resolver,
+            &settings.fallback_font_resolver,
+            &settings.cmap_resolver,
+        )
+        .map(Self)
+    }
+
+    /// Load a standard font, like the interpreter does for font resources that
+    /// don't exist.
+    pub fn new_standard(font: StandardFont, settings: &InterpreterSettings) -> Option<Self> {
+        Font::new_standard(
+            font,
+            &settings.font_resolver,
+            &settings.fallback_font_resolver,
+        )
+        .map(Self)
+    }
+
+    /// Read the character code at `offset` of a string, returning the code and
+    /// its length in bytes.
+    pub fn read_code(&self, bytes: &[u8], offset: usize) -> (u32, usize) {
+        self.0.read_code(bytes, offset)
+    }
+
+    /// The advance of a character code in thousandths of a text space unit.
+    ///
+    /// For vertical fonts, the advance is along the y axis.
+    pub fn code_advance(&self, code: u32) -> Vec2 {
+        self.0.code_advance(code)
+    }
+
+    /// Whether the font is written horizontally.
+    pub fn is_horizontal(&self) -> bool {
+        self.0.is_horizontal()
+    }
+}
+
 #[derive(Clone, Debug)]
 enum FontType<'a> {
     Type1(Rc<Type1Font>),
@@ -4,7 +4,7 @@ use crate::font::true_type::TrueTypeFont;
 use crate::font::type1::Type1Font;
 use hayro_cmap::BfString;
 use hayro_font::OutlineBuilder;
-use kurbo::BezPath;
+use kurbo::{BezPath, Vec2};
 use skrifa::GlyphId;
 use skrifa::outline::OutlinePen;
 use std::rc::Rc;
@@ -148,9 +148,50 @@ impl OutlineFont {
         }
     }
 
+    /// Get the vertical advance for a glyph by character code, or `None` if the
+    /// font is not in vertical writing mode.
+    pub(crate) fn glyph_vertical_advance(&self, char_code: u32) -> Option<f32> {
+        match self {
+            Self::Type0(t) if !t.is_horizontal() => Some(t.code_advance(char_code).y as f32),
+            _ => None,
+        }
+    }
+
+    /// Get the displacement of the glyph origin from the current text
+    /// position, which is only nonzero in vertical writing mode.
+    pub(crate) fn origin_displacement(&self, char_code: u32) -> Vec2 {
+        match self {
+            Self::Type0(t) => t.origin_displacement(char_code),
+            _ => Vec2::default(),
+        }
+    }
+
+    /// Get the advance width of the space character.
+    ///
+    /// Only simple fonts whose character code 32 is a space are considered, since
+    /// the codes of composite fonts don't follow any convention.
+    pub(crate) fn space_advance_width(&self) -> Option<f32> {
+        if matches!(self, Self::Type0(_))
+            || !matches!(self.char_code_to_unicode(32), Some(BfString::Char(' ')))
+        {
+            return None;
+        }
+
+        self.glyph_advance_width(32).filter(|width| *width > 0.0)
+    }
+
+    /// Get the PostScript name of the font, without a subset prefix.
+    pub(crate) fn postscript_name(&self) -> Option<&str> {
+        match self {
+            Self::Type1(t) => t.postscript_name(),
+            Self::TrueType(t) => t.postscript_name(),
+            Self::Type0(t) => t.postscript_name(),
+        }
+    }
+
     /// Get raw font bytes and metadata.
     ///
-    /// Returns None for Type1 fonts.
+    /// Returns None for Type1 fonts and for composite fonts that aren't embedded.
     pub(crate) fn font_data(&self) -> Option<OutlineFontData> {
         match self {
             Self::Type1(_) => None,
@@ -164,7 +205,7 @@ impl OutlineFont {
                 is_monospace: t.is_monospace(),
             }),
             Self::Type0(t) => Some(OutlineFontData {
-                data: t.font_data(),
+                data: t.font_data()?,
                 cache_key: t.cache_key(),
                 postscript_name: t.postscript_name().map(|s| s.to_string()),
                 weight: t.weight(),
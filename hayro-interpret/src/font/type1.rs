@@ -1,14 +1,19 @@
 use crate::font::blob::{CffFontBlob, Type1FontBlob};
+use crate::font::fallback::FallbackChain;
 use crate::font::generated::glyph_names;
 use crate::font::glyph_simulator::GlyphSimulator;
 use crate::font::standard_font::{StandardFont, StandardFontBlob, select_standard_font};
 use crate::font::true_type::{read_encoding, read_widths};
-use crate::font::{Encoding, FallbackFontQuery, FontQuery, glyph_name_to_unicode, read_to_unicode};
-use crate::{CMapResolverFn, CacheKey, FontResolverFn};
+use crate::font::{
+    Encoding, FallbackFontQuery, FontQuery, glyph_name_to_unicode, read_to_unicode,
+    strip_subset_prefix,
+};
+use crate::{CMapResolverFn, CacheKey, FallbackFontResolverFn, FontResolverFn};
 use hayro_cmap::{BfString, CMap};
 use hayro_syntax::object::Dict;
+use hayro_syntax::object::Name;
 use hayro_syntax::object::Stream;
-use hayro_syntax::object::dict::keys::{FONT_DESC, FONT_FILE, FONT_FILE3};
+use hayro_syntax::object::dict::keys::{BASE_FONT, FONT_DESC, FONT_FILE, FONT_FILE3};
 use kurbo::{Affine, BezPath};
 use log::warn;
 use skrifa::GlyphId;
@@ -17,17 +22,21 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 #[derive(Debug)]
-pub(crate) struct Type1Font(u128, Kind, Option<CMap>);
+pub(crate) struct Type1Font(u128, Kind, Option<CMap>, Option<String>);
 
 impl Type1Font {
     pub(crate) fn new(
         dict: &Dict<'_>,
         resolver: &FontResolverFn,
+        fallback_resolver: &FallbackFontResolverFn,
         cmap_resolver: &CMapResolverFn,
     ) -> Option<Self> {
         let cache_key = dict.cache_key();
 
         let to_unicode = read_to_unicode(dict, cmap_resolver);
+        let postscript_name = dict
+            .get::<Name>(BASE_FONT)
+            .map(|n| strip_subset_prefix(n.as_str()).to_string());
 
         let fallback = || {
             // TODO: Actually use fallback fonts
@@ -49,22 +58,29 @@ impl Type1Font {
                     standard_font,
                     true,
                     resolver,
+                    fallback_resolver,
                 )?),
                 to_unicode.clone(),
+                postscript_name.clone(),
             ))
         };
 
-        let inner = if let Some(standard) = StandardKind::new(dict, resolver) {
-            Self(cache_key, Kind::Standard(standard), to_unicode)
+        let inner = if let Some(standard) = StandardKind::new(dict, resolver, fallback_resolver) {
+            Self(
+                cache_key,
+                Kind::Standard(standard),
+                to_unicode,
+                postscript_name,
+            )
         } else if is_cff(dict) {
             if let Some(cff) = CffKind::new(dict) {
-                Self(cache_key, Kind::Cff(cff), to_unicode)
+                Self(cache_key, Kind::Cff(cff), to_unicode, postscript_name)
             } else {
                 return fallback();
             }
         } else if is_type1(dict) {
             if let Some(f) = Type1Kind::new(dict) {
-                Self(cache_key, Kind::Type1(f), to_unicode)
+                Self(cache_key, Kind::Type1(f), to_unicode, postscript_name)
             } else {
                 return fallback();
             }
@@ -75,11 +91,21 @@ impl Type1Font {
         Some(inner)
     }
 
-    pub(crate) fn new_standard(font: StandardFont, resolver: &FontResolverFn) -> Option<Self> {
+    pub(crate) fn new_standard(
+        font: StandardFont,
+        resolver: &FontResolverFn,
+        fallback_resolver: &FallbackFontResolverFn,
+    ) -> Option<Self> {
         let dict = Dict::default();
-        let standard = StandardKind::new_with_standard(&dict, font, true, resolver)?;
-
-        Some(Self(0, Kind::Standard(standard), None))
+        let standard =
+            StandardKind::new_with_standard(&dict, font, true, resolver, fallback_resolver)?;
+
+        Some(Self(
+            0,
+            Kind::Standard(standard),
+            None,
+            Some(font.postscript_name().to_string()),
+        ))
     }
 
     pub(crate) fn map_code(&self, code: u8) -> GlyphId {
@@ -98,6 +124,13 @@ impl Type1Font {
         }
     }
 
+    pub(crate) fn postscript_name(&self) -> Option<&str> {
+        self.3.as_deref().or(match &self.1 {
+            Kind::Standard(s) => Some(s.base_font.postscript_name()),
+            _ => None,
+        })
+    }
+
     pub(crate) fn glyph_width(&self, code: u8) -> Option<f32> {
         match &self.1 {
             Kind::Standard(s) => s.glyph_width(code),
@@ -149,11 +182,23 @@ struct StandardKind {
     fallback: bool,
     glyph_to_code: RefCell<HashMap<GlyphId, u8>>,
     encodings: HashMap<u8, String>,
+    /// Fonts for the glyphs that the standard font doesn't have.
+    fallback_chain: FallbackChain,
 }
 
 impl StandardKind {
-    fn new(dict: &Dict<'_>, resolver: &FontResolverFn) -> Option<Self> {
-        Self::new_with_standard(dict, select_standard_font(dict)?, false, resolver)
+    fn new(
+        dict: &Dict<'_>,
+        resolver: &FontResolverFn,
+        fallback_resolver: &FallbackFontResolverFn,
+    ) -> Option<Self> {
+        Self::new_with_standard(
+            dict,
+            select_standard_font(dict)?,
+            false,
+            resolver,
+            fallback_resolver,
+        )
     }
 
     fn new_with_standard(
@@ -161,6 +206,7 @@ impl StandardKind {
         base_font: StandardFont,
         fallback: bool,
         resolver: &FontResolverFn,
+        fallback_resolver: &FallbackFontResolverFn,
     ) -> Option<Self> {
         let descriptor = dict.get::<Dict<'_>>(FONT_DESC).unwrap_or_default();
         let widths = read_widths(dict, &descriptor)?;
@@ -177,6 +223,7 @@ impl StandardKind {
             glyph_to_code: RefCell::new(HashMap::new()),
             fallback,
             encoding,
+            fallback_chain: FallbackChain::new(FallbackFontQuery::new(dict), fallback_resolver),
         })
     }
 
@@ -204,6 +251,10 @@ impl StandardKind {
                     })
                 })
             })
+            .or_else(|| {
+                self.code_to_ps_name(code)
+                    .and_then(|name| self.fallback_chain.map_name(name))
+            })
             .unwrap_or(GlyphId::NOTDEF);
         self.glyph_to_code.borrow_mut().insert(result, code);
 
@@ -211,6 +262,11 @@ impl StandardKind {
     }
 
     fn outline_glyph(&self, glyph: GlyphId) -> BezPath {
+        // Glyphs from fallback fonts keep their own width.
+        if let Some(path) = self.fallback_chain.outline_glyph(glyph) {
+            return path;
+        }
+
         let path = self.base_font_blob.outline_glyph(glyph);
 
         // If the font was not embedded in the file and we are using a standard font as a substitute,
@@ -3,7 +3,9 @@ use crate::context::Context;
 use crate::device::Device;
 use crate::font::glyph_simulator::GlyphSimulator;
 use crate::font::true_type::{read_encoding, read_widths};
-use crate::font::{Encoding, Glyph, Type3Glyph, UNITS_PER_EM, read_to_unicode};
+use crate::font::{
+    Encoding, Glyph, Type3Glyph, UNITS_PER_EM, glyph_name_to_unicode, read_to_unicode,
+};
 use crate::interpret::state::TextState;
 use crate::soft_mask::SoftMask;
 use crate::util::RectExt;
@@ -77,12 +79,16 @@ impl<'a> Type3<'a> {
     }
 
     pub(crate) fn map_code(&self, code: u8) -> GlyphId {
+        self.code_to_name(code)
+            .map(|g| self.glyph_simulator.string_to_glyph(g))
+            .unwrap_or(GlyphId::NOTDEF)
+    }
+
+    fn code_to_name(&self, code: u8) -> Option<&str> {
         self.encodings
             .get(&code)
             .map(|s| s.as_str())
             .or_else(|| self.encoding.map_code(code))
-            .map(|g| self.glyph_simulator.string_to_glyph(g))
-            .unwrap_or(GlyphId::NOTDEF)
     }
 
     pub(crate) fn glyph_width(&self, code: u8) -> f32 {
@@ -91,10 +97,40 @@ impl<'a> Type3<'a> {
     }
 
     pub(crate) fn char_code_to_unicode(&self, char_code: u32) -> Option<BfString> {
-        // Type3 fonts can only provide Unicode via ToUnicode CMap.
         self.to_unicode
             .as_ref()
             .and_then(|t| t.lookup_bf_string(char_code))
+            .or_else(|| {
+                // Fall back to the glyph name from the encoding, which works
+                // for fonts that use standard glyph names.
+                self.code_to_name(u8::try_from(char_code).ok()?)
+                    .and_then(glyph_name_to_unicode)
+                    .map(BfString::Char)
+            })
+    }
+
+    /// The bounding box of a glyph in glyph space.
+    ///
+    /// This is the box declared by the `d1` operator of the glyph procedure.
+    /// Colored glyphs (`d0`) don't declare one, so their advance width and
+    /// the vertical extent of the font bbox are used instead.
+    pub(crate) fn glyph_bbox(&self, glyph_id: GlyphId) -> Option<Rect> {
+        let name = self.glyph_simulator.glyph_to_string(glyph_id)?;
+        let decoded = self.char_procs.get(&name)?.decoded().ok()?;
+
+        let bbox = match TypedIter::new(decoded.as_ref()).next()? {
+            TypedInstruction::ShapeGlyph(d1) => {
+                Rect::new(d1.2.as_f64(), d1.3.as_f64(), d1.4.as_f64(), d1.5.as_f64())
+            }
+            TypedInstruction::ColorGlyph(d0) => {
+                Rect::new(0.0, self.font_bbox.y0, d0.0.as_f64(), self.font_bbox.y1)
+            }
+            _ => return None,
+        }
+        .abs();
+
+        (bbox.width() > 0.0 && bbox.height() > 0.0)
+            .then(|| (self.matrix * Affine::scale(UNITS_PER_EM as f64)).transform_rect_bbox(bbox))
     }
 
     pub(crate) fn render_glyph(
@@ -2,7 +2,7 @@ use crate::color::ColorSpace;
 use crate::context::Context;
 use crate::convert::{convert_line_cap, convert_line_join};
 use crate::device::Device;
-use crate::font::{Font, FontData, FontQuery, StandardFont};
+use crate::font::{FallbackFontQuery, Font, FontData, FontQuery, StandardFont};
 use crate::interpret::path::{
     close_path, fill_path, fill_path_impl, fill_stroke_path, stroke_path,
 };
@@ -11,8 +11,10 @@ use crate::interpret::text::TextRenderingMode;
 use crate::pattern::{Pattern, ShadingPattern};
 use crate::shading::Shading;
 use crate::util::{OptionLog, RectExt};
+use crate::warning::{font_name, is_font_embedded};
 use crate::x_object::{
     FormXObject, ImageXObject, XObject, draw_form_xobject, draw_image_xobject, draw_xobject,
+    is_image_xobject,
 };
 use crate::{BlendMode, CacheKey, FillRule};
 use hayro_syntax::content::ops::TypedInstruction;
@@ -22,6 +24,7 @@ use hayro_syntax::page::{Page, Resources};
 use kurbo::{Affine, Point, Shape};
 use log::warn;
 use smallvec::smallvec;
+use std::collections::HashSet;
 use std::sync::Arc;
 
 pub(crate) mod path;
@@ -33,6 +36,10 @@ pub(crate) mod text;
 /// The first argument is the raw data, the second argument is the index in case the font
 /// is a TTC, otherwise it should be 0.
 pub type FontResolverFn = Arc<dyn Fn(&FontQuery) -> Option<(FontData, u32)> + Send + Sync>;
+/// A callback function for resolving the fonts that are tried in turn for
+/// glyphs that are missing from the substitute of a non-embedded font.
+pub type FallbackFontResolverFn =
+    Arc<dyn Fn(&FallbackFontQuery) -> Vec<(FontData, u32)> + Send + Sync>;
 /// A callback function for resolving cmap names to their files.
 pub type CMapResolverFn =
     Arc<dyn Fn(hayro_cmap::CMapName<'_>) -> Option<&'static [u8]> + Send + Sync>;
@@ -78,6 +85,20 @@ pub struct InterpreterSettings {
     /// If you don't want having to deal with this, you can just enable the `embed-fonts` feature
     /// and use the default implementation of the callback.
     pub font_resolver: FontResolverFn,
+    /// A callback for resolving an ordered list of fallback fonts for a font
+    /// that isn't embedded.
+    ///
+    /// Non-embedded simple fonts are drawn with one of the standard fonts. When
+    /// a glyph is missing from it, for example for text in another script,
+    /// each of the returned fonts is tried in turn, matching the glyph by its
+    /// name or Unicode code point. The callback is only invoked once a glyph is
+    /// missing.
+    ///
+    /// Non-embedded composite (CID) fonts have no standard font to substitute
+    /// them, so all of their glyphs are taken from the returned fonts. Their
+    /// character codes are mapped to Unicode with the `ToUnicode` map of the
+    /// font. By default, no fallback fonts are used.
+    pub fallback_font_resolver: FallbackFontResolverFn,
     /// A callback for resolving cmaps that aren't embedded.
     ///
     /// When the PDF requires using a cmap that is not directly embedded in the PDF,
@@ -99,6 +120,35 @@ pub struct InterpreterSettings {
     /// Note that this feature is currently not fully implemented yet, so some
     /// annotations might be missing.
     pub render_annotations: bool,
+    /// What the page is rendered for, which determines the annotations that
+    /// are visible based on their flags.
+    pub render_target: RenderTarget,
+    /// Whether only text should be interpreted, skipping images and shadings
+    /// entirely.
+    ///
+    /// This is meant for devices that don't need any pixels, like the one used
+    /// for text extraction, which always enables it. Paths are still
+    /// interpreted, since they can be used for clipping.
+    pub text_only: bool,
+    /// The names of the optional content groups (layers) that should be
+    /// visible. All other groups are hidden, regardless of the document's
+    /// default configuration.
+    ///
+    /// If this is set to `None`, the default configuration in
+    /// `/OCProperties` of the document is used. See [`layer_names`](crate::layer_names)
+    /// for the layers of a document.
+    pub visible_layers: Option<HashSet<String>>,
+}
+
+/// What a page is rendered for.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RenderTarget {
+    /// Displaying the page on screen. Annotations with the `NoView` flag are
+    /// not rendered.
+    #[default]
+    View,
+    /// Printing the page. Only annotations with the `Print` flag are rendered.
+    Print,
 }
 
 impl Default for InterpreterSettings {
@@ -111,18 +161,24 @@ impl Default for InterpreterSettings {
                 FontQuery::Standard(s) => Some(s.get_font_data()),
                 FontQuery::Fallback(f) => Some(f.pick_standard_font().get_font_data()),
             }),
+            fallback_font_resolver: Arc::new(|_| Vec::new()),
             #[cfg(feature = "embed-cmaps")]
             cmap_resolver: Arc::new(hayro_cmap::load_embedded),
             #[cfg(not(feature = "embed-cmaps"))]
             cmap_resolver: Arc::new(|_| None),
             warning_sink: Arc::new(|_| {}),
             render_annotations: true,
+            render_target: RenderTarget::default(),
+            text_only: false,
+            visible_layers: None,
         }
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 /// Warnings that can occur while interpreting a PDF file.
+///
+/// See [`collect_warnings`](crate::collect_warnings) for collecting them.
 pub enum InterpreterWarning {
     /// An unsupported font kind was encountered.
     ///
@@ -130,6 +186,34 @@ pub enum InterpreterWarning {
     UnsupportedFont,
     /// An image failed to decode.
     ImageDecodeFailure,
+    /// The font with the given name couldn't be loaded.
+    FontLoadFailure(String),
+    /// The font with the given name isn't embedded, so it is drawn with a
+    /// substitute.
+    FontNotEmbedded(String),
+    /// The font resource with the given name doesn't exist, so Helvetica is
+    /// used instead.
+    MissingFont(String),
+    /// A shading is unsupported or malformed, and wasn't drawn.
+    ShadingFailure,
+    /// An operator couldn't be read and was skipped.
+    InvalidOperator,
+}
+
+impl core::fmt::Display for InterpreterWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::UnsupportedFont => write!(f, "unsupported font"),
+            Self::ImageDecodeFailure => write!(f, "failed to decode an image"),
+            Self::FontLoadFailure(name) => write!(f, "failed to load font {name}"),
+            Self::FontNotEmbedded(name) => write!(f, "font {name} is not embedded"),
+            Self::MissingFont(name) => {
+                write!(f, "font {name} not found, falling back to Helvetica")
+            }
+            Self::ShadingFailure => write!(f, "unsupported or malformed shading"),
+            Self::InvalidOperator => write!(f, "failed to read an operator"),
+        }
+    }
 }
 
 /// interpret the contents of the page and render them into the device.
@@ -159,8 +243,7 @@ fn render_annotation<'a>(
 ) {
     let flags = annot.get::<u32>(F).unwrap_or(0);
 
-    // Annotation should be hidden (bit 1, value 2).
-    if flags & 2 != 0 {
+    if !is_annotation_visible(flags, context.settings.render_target) {
         return;
     }
 
@@ -230,6 +313,23 @@ fn render_annotation<'a>(
     }
 }
 
+/// Whether an annotation with the given flags is visible for the render target.
+fn is_annotation_visible(flags: u32, target: RenderTarget) -> bool {
+    // See table 167 in the PDF 2.0 specification.
+    const HIDDEN: u32 = 1 << 1;
+    const PRINT: u32 = 1 << 2;
+    const NO_VIEW: u32 = 1 << 5;
+
+    if flags & HIDDEN != 0 {
+        return false;
+    }
+
+    match target {
+        RenderTarget::View => flags & NO_VIEW == 0,
+        RenderTarget::Print => flags & PRINT != 0,
+    }
+}
+
 /// Extract annotation color from /C key, with default fallback.
 fn get_annot_color(annot: &Dict<'_>, def_r: f32, def_g: f32, def_b: f32) -> (f32, f32, f32) {
     if let Some(c_arr) = annot.get::<Array<'_>>(C) {
@@ -649,24 +749,38 @@ pub fn interpret<'a, 'b>(
                 let font = if let Some(font_dict) = resources.get_font(name.clone()) {
                     let cache_key = font_dict.cache_key();
 
-                    context
-                        .font_cache
-                        .entry(cache_key)
-                        .or_insert_with(|| {
-                            Font::new(
-                                &font_dict,
-                                &context.settings.font_resolver,
-                                &context.settings.cmap_resolver,
-                            )
-                        })
-                        .clone()
+                    if !context.font_cache.contains_key(&cache_key) {
+                        let font = Font::new(
+                            &font_dict,
+                            &context.settings.font_resolver,
+                            &context.settings.fallback_font_resolver,
+                            &context.settings.cmap_resolver,
+                        );
+
+                        if font.is_none() {
+                            context
+                                .warn(InterpreterWarning::FontLoadFailure(font_name(&font_dict)));
+                        } else if !is_font_embedded(&font_dict) {
+                            context
+                                .warn(InterpreterWarning::FontNotEmbedded(font_name(&font_dict)));
+                        }
+
+                        context.font_cache.insert(cache_key, font);
+                    }
+
+                    context.font_cache[&cache_key].clone()
                 } else {
                     warn!(
                         "font {:?} not found, falling back to Helvetica",
                         name.as_str()
                     );
+                    context.warn(InterpreterWarning::MissingFont(name.as_str().to_string()));
 
-                    Font::new_standard(StandardFont::Helvetica, &context.settings.font_resolver)
+                    Font::new_standard(
+                        StandardFont::Helvetica,
+                        &context.settings.font_resolver,
+                        &context.settings.fallback_font_resolver,
+                    )
                 };
 
                 context.get_mut().text_state.font_size = t.1.as_f32();
@@ -735,18 +849,26 @@ pub fn interpret<'a, 'b>(
             TypedInstruction::XObject(x) => {
                 let cache = context.object_cache.clone();
                 let transfer_function = context.get().graphics_state.transfer_function.clone();
-                if let Some(x_object) = resources.get_x_object(x.0).and_then(|s| {
-                    XObject::new(
-                        &s,
-                        &context.settings.warning_sink,
-                        &cache,
-                        transfer_function.clone(),
-                    )
-                }) {
+                if let Some(x_object) = resources
+                    .get_x_object(x.0)
+                    .filter(|s| !(context.settings.text_only && is_image_xobject(s)))
+                    .and_then(|s| {
+                        XObject::new(
+                            &s,
+                            &context.settings.warning_sink,
+                            &cache,
+                            transfer_function.clone(),
+                        )
+                    })
+                {
                     draw_xobject(&x_object, resources, context, device);
                 }
             }
             TypedInstruction::InlineImage(i) => {
+                if context.settings.text_only {
+                    continue;
+                }
+
                 let warning_sink = context.settings.warning_sink.clone();
                 let transfer_function = context.get().graphics_state.transfer_function.clone();
                 let cache = context.object_cache.clone();
@@ -765,7 +887,7 @@ pub fn interpret<'a, 'b>(
                 context.get_mut().text_state.rise = t.0.as_f32();
             }
             TypedInstruction::Shading(s) => {
-                if !context.ocg_state.is_visible() {
+                if !context.ocg_state.is_visible() || context.settings.text_only {
                     continue;
                 }
 
@@ -798,6 +920,7 @@ pub fn interpret<'a, 'b>(
                     context.restore_state(device);
                 } else {
                     warn!("failed to process shading");
+                    context.warn(InterpreterWarning::ShadingFailure);
                 }
             }
             TypedInstruction::BeginCompatibility(_) => {}
@@ -811,6 +934,7 @@ pub fn interpret<'a, 'b>(
             }
             _ => {
                 warn!("failed to read an operator");
+                context.warn(InterpreterWarning::InvalidOperator);
             }
         }
     }
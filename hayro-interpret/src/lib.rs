@@ -38,6 +38,7 @@ mod ocg;
 mod soft_mask;
 mod text_extract;
 mod types;
+mod warning;
 mod x_object;
 
 pub mod color;
@@ -54,7 +55,9 @@ pub use function::Function;
 pub use hayro_cmap;
 pub use hayro_syntax;
 pub use interpret::*;
+pub use ocg::layer_names;
 pub use soft_mask::*;
 pub use text_extract::*;
 pub use types::*;
 pub use util::{PageExt, RectExt};
+pub use warning::collect_warnings;
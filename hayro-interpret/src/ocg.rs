@@ -1,7 +1,45 @@
-use hayro_syntax::object::dict::keys::{BASE_STATE, D, OCGS, OCPROPERTIES, OFF, ON};
-use hayro_syntax::object::{Array, Dict, Name, ObjectIdentifier};
+use hayro_syntax::Pdf;
+use hayro_syntax::object::dict::keys::{BASE_STATE, D, NAME, OCGS, OCPROPERTIES, OFF, ON};
+use hayro_syntax::object::{Array, Dict, Name, ObjectIdentifier, String as PdfString};
+use hayro_syntax::xref::XRef;
 use std::collections::HashSet;
 
+/// The names of the optional content groups (layers) of a document, in the
+/// order of `/OCProperties /OCGs`.
+///
+/// These are the names that can be passed in
+/// [`InterpreterSettings::visible_layers`](crate::InterpreterSettings::visible_layers).
+pub fn layer_names(pdf: &Pdf) -> Vec<String> {
+    let xref = pdf.xref();
+    let Some(ocgs) = xref
+        .get::<Dict<'_>>(xref.root_id())
+        .and_then(|catalog| catalog.get::<Dict<'_>>(OCPROPERTIES))
+        .and_then(|properties| properties.get::<Array<'_>>(OCGS))
+    else {
+        return Vec::new();
+    };
+
+    ocgs.iter::<Dict<'_>>().map(|ocg| ocg_name(&ocg)).collect()
+}
+
+/// The name of an optional content group dictionary.
+fn ocg_name(ocg: &Dict<'_>) -> String {
+    let Some(name) = ocg.get::<PdfString>(NAME) else {
+        return String::new();
+    };
+    let bytes = name.as_bytes();
+
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|b| char::from(*b)).collect()
+    }
+}
+
 pub(crate) struct OcgState {
     inactive_ocgs: HashSet<ObjectIdentifier>,
     visibility_stack: Vec<bool>,
@@ -15,7 +53,14 @@ impl OcgState {
         }
     }
 
-    pub(crate) fn from_catalog(catalog: &Dict<'_>) -> Self {
+    /// Read the default visibility of each group from the catalog. If
+    /// `visible_layers` is given, it overrides the default configuration: only
+    /// the groups whose name is contained in it are visible.
+    pub(crate) fn from_catalog(
+        catalog: &Dict<'_>,
+        xref: &XRef,
+        visible_layers: Option<&HashSet<String>>,
+    ) -> Self {
         let Some(oc_properties) = catalog.get::<Dict<'_>>(OCPROPERTIES) else {
             return Self::dummy();
         };
@@ -59,6 +104,23 @@ impl OcgState {
         read_ocg_array(ON, true);
         read_ocg_array(OFF, false);
 
+        if let Some(visible_layers) = visible_layers
+            && let Some(ocgs) = oc_properties.get::<Array<'_>>(OCGS)
+        {
+            for item in ocgs.raw_iter() {
+                if let Some(ref_) = item.as_obj_ref() {
+                    let id: ObjectIdentifier = ref_.into();
+                    let name = xref.get::<Dict<'_>>(id).map(|ocg| ocg_name(&ocg));
+
+                    if name.is_some_and(|name| visible_layers.contains(&name)) {
+                        inactive.remove(&id);
+                    } else {
+                        inactive.insert(id);
+                    }
+                }
+            }
+        }
+
         Self {
             inactive_ocgs: inactive,
             visibility_stack: Vec::new(),
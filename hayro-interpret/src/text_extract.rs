@@ -5,14 +5,15 @@
 //! encountered during interpretation and returns positioned text spans in page
 //! coordinate space.
 
-use crate::font::Glyph;
+use crate::font::{Glyph, UNITS_PER_EM};
+use crate::util::PageExt;
 use crate::{
-    BlendMode, ClipPath, Context, Device, GlyphDrawMode, Image, InterpreterSettings, Paint,
-    PathDrawMode, SoftMask, interpret_page,
+    BlendMode, ClipPath, Context, Device, GlyphDrawMode, Image, InterpreterSettings,
+    InterpreterWarning, Paint, PathDrawMode, SoftMask, collect_warnings, interpret_page,
 };
 use hayro_cmap::BfString;
 use hayro_syntax::page::Page;
-use kurbo::{Affine, BezPath, Point, Rect, Shape};
+use kurbo::{Affine, BezPath, Point, Rect, Shape, Vec2};
 
 /// A text span extracted from a page.
 #[derive(Debug, Clone, PartialEq)]
@@ -23,30 +24,482 @@ pub struct TextSpan {
     pub bbox: [f32; 4],
     /// Baseline anchor in page coordinates `[x, y]`.
     pub baseline: [f32; 2],
+    /// The PostScript name of the font the span is drawn with, or an empty
+    /// string if it is unknown.
+    pub font_name: String,
+    /// The font size in page units, including the scale of the text and
+    /// current transformation matrices.
+    pub font_size: f32,
+    /// The RGB fill color of the span, with components between 0 and 1.
+    ///
+    /// Spans painted with a pattern are reported as black.
+    pub color: [f32; 3],
+    /// Whether the span is set in vertical writing mode.
+    ///
+    /// The glyphs of vertical spans run from top to bottom, and their
+    /// `baseline` is the top of the vertical baseline through the middle of
+    /// the glyphs.
+    pub vertical: bool,
 }
 
+/// A single glyph extracted from a page, before glyphs are merged into spans.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphBox {
+    /// The text the glyph maps to.
+    ///
+    /// This is usually a single character, but a ligature maps to all of the
+    /// characters it stands for, and a combining mark drawn as its own glyph is
+    /// reported separately from its base character.
+    pub text: String,
+    /// The tight bounding box of the glyph outline in page coordinates:
+    /// `[x0, y0, x1, y1]`.
+    pub bbox: [f32; 4],
+    /// The glyph origin on the baseline in page coordinates `[x, y]`.
+    pub baseline: [f32; 2],
+    /// The advance width of the glyph in page units, excluding character and
+    /// word spacing. For vertical text, this is the vertical advance.
+    pub advance: f32,
+}
+
+/// A word extracted from a page, as part of a [`TextLine`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextWord {
+    /// The extracted UTF-8 text.
+    pub text: String,
+    /// The word bounding box in page coordinates: `[x0, y0, x1, y1]`.
+    pub bbox: [f32; 4],
+}
+
+/// A line of text extracted from a page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLine {
+    /// The words of the line, in reading order.
+    pub words: Vec<TextWord>,
+    /// The line bounding box in page coordinates: `[x0, y0, x1, y1]`.
+    pub bbox: [f32; 4],
+    /// Baseline anchor of the first word in page coordinates `[x, y]`.
+    pub baseline: [f32; 2],
+}
+
+/// A match of a text search on a page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    /// The matched text, as it appears on the page.
+    pub text: String,
+    /// The bounding boxes of the match in page coordinates, one
+    /// `[x0, y0, x1, y1]` rectangle per line the match spans.
+    pub rects: Vec<[f32; 4]>,
+}
+
+impl SearchHit {
+    /// Return the rectangles of the hit as quad points, in the layout used by
+    /// the `QuadPoints` entry of text markup annotations.
+    pub fn quad_points(&self) -> Vec<f32> {
+        self.rects
+            .iter()
+            .flat_map(|&[x0, y0, x1, y1]| [x0, y1, x1, y1, x0, y0, x1, y0])
+            .collect()
+    }
+}
+
+/// The gap between two glyphs, relative to the font size, above which they
+/// are considered to belong to different words.
+const WORD_GAP_THRESHOLD: f64 = 0.25;
+
+/// The gap between two glyphs, relative to the width of a space in their
+/// font, above which they are considered to belong to different words.
+///
+/// This is preferred over [`WORD_GAP_THRESHOLD`] when the font has a space,
+/// since it also holds for condensed, wide and horizontally scaled text.
+const SPACE_GAP_THRESHOLD: f64 = 0.5;
+
+/// The relative difference in font size above which two fragments are
+/// considered to have a different style.
+const FONT_SIZE_TOLERANCE: f64 = 0.01;
+
 /// Extract positioned text spans from a page.
 ///
 /// The resulting coordinates are expressed in page space and can be transformed
 /// to screen space by the caller as needed.
 pub fn extract_text_spans(page: &Page<'_>, settings: &InterpreterSettings) -> Vec<TextSpan> {
-    let crop = page.intersected_crop_box();
-    let mut context = Context::new(
-        Affine::IDENTITY,
-        Rect::new(crop.x0, crop.y0, crop.x1, crop.y1),
-        page.xref(),
-        settings.clone(),
-    );
-    let mut extractor = TextExtractor::default();
-    interpret_page(page, &mut context, &mut extractor);
+    extract_text_spans_timed(page, settings, &|| 0.0).0
+}
+
+/// Extract positioned text spans from a page like [`extract_text_spans`], and
+/// return the warnings emitted while interpreting it.
+pub fn extract_text_spans_with_warnings(
+    page: &Page<'_>,
+    settings: &InterpreterSettings,
+) -> (Vec<TextSpan>, Vec<InterpreterWarning>) {
+    collect_warnings(settings, |settings| extract_text_spans(page, settings))
+}
+
+/// Extract positioned text spans from a region of a page.
+///
+/// `rect` is given as `[x0, y0, x1, y1]` in page coordinates, like the
+/// bounding boxes of the returned spans. Page coordinates are not affected by
+/// the rotation of the page, so a rect in display space needs to be mapped
+/// back with the inverse of [`PageExt::initial_transform`] first.
+///
+/// Glyphs whose bounding box doesn't intersect the rect are discarded before
+/// they are merged into spans, so a span never contains text from outside of
+/// the region.
+pub fn extract_text_in_rect(
+    page: &Page<'_>,
+    settings: &InterpreterSettings,
+    rect: [f32; 4],
+) -> Vec<TextSpan> {
+    let [x0, y0, x1, y1] = rect.map(f64::from);
+    let region = Rect::new(x0, y0, x1, y1).abs();
+
+    let mut extractor = TextExtractor::run(page, settings);
+    extractor.fragments.retain(|fragment| {
+        fragment.bbox.x0 <= region.x1
+            && fragment.bbox.x1 >= region.x0
+            && fragment.bbox.y0 <= region.y1
+            && fragment.bbox.y1 >= region.y0
+    });
+
     extractor.into_spans()
 }
 
+/// Durations of the individual phases of extracting text spans from a page.
+///
+/// All values are expressed in the unit of the clock passed to
+/// [`extract_text_spans_timed`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextExtractionTimings {
+    /// Decoding the content stream of the page.
+    pub parse: f64,
+    /// Interpreting the content stream and collecting glyphs.
+    pub interpret: f64,
+    /// Merging glyphs into spans.
+    pub merge: f64,
+    /// The total time spent, which is the sum of all phases.
+    pub total: f64,
+}
+
+/// Extract positioned text spans from a page, recording how much time was spent
+/// in each phase.
+///
+/// `now` should return a monotonic timestamp, for example in milliseconds.
+pub fn extract_text_spans_timed(
+    page: &Page<'_>,
+    settings: &InterpreterSettings,
+    now: &dyn Fn() -> f64,
+) -> (Vec<TextSpan>, TextExtractionTimings) {
+    let start = now();
+    // The decoded content stream is cached, so interpreting it won't decode it again.
+    let _ = page.page_stream();
+    let parsed = now();
+
+    let extractor = TextExtractor::run(page, settings);
+    let interpreted = now();
+
+    let spans = extractor.into_spans();
+    let merged = now();
+
+    let timings = TextExtractionTimings {
+        parse: parsed - start,
+        interpret: interpreted - parsed,
+        merge: merged - interpreted,
+        total: merged - start,
+    };
+
+    (spans, timings)
+}
+
+/// Extract the individual glyphs of a page, in the order they are drawn.
+///
+/// Unlike [`extract_text_spans`], glyphs are not merged, so every entry
+/// corresponds to exactly one glyph drawn by the font. Glyphs that don't map to
+/// any text are skipped.
+pub fn extract_glyphs(page: &Page<'_>, settings: &InterpreterSettings) -> Vec<GlyphBox> {
+    TextExtractor::run(page, settings)
+        .fragments
+        .into_iter()
+        .map(|f| GlyphBox {
+            text: f.text,
+            bbox: rect_to_array(f.bbox),
+            baseline: [f.baseline.x as f32, f.baseline.y as f32],
+            advance: f.advance as f32,
+        })
+        .collect()
+}
+
+/// Extract the text of a page grouped into lines and words.
+///
+/// Glyphs are grouped into lines by their baseline, and sorted by their
+/// position along the baseline, so that each line is in reading order even if
+/// its glyphs were drawn in a different order. Words are split on whitespace
+/// and on gaps between glyphs that are wider than a fraction of the font size.
+pub fn extract_text_lines(page: &Page<'_>, settings: &InterpreterSettings) -> Vec<TextLine> {
+    group_into_lines(TextExtractor::run(page, settings).fragments)
+        .into_iter()
+        .filter_map(|line| {
+            let baseline = line[0].baseline;
+            let words = split_words(line);
+            let bbox = words.iter().map(|(_, bbox)| *bbox).reduce(union_rect)?;
+
+            Some(TextLine {
+                words: words
+                    .into_iter()
+                    .map(|(text, bbox)| TextWord {
+                        text,
+                        bbox: rect_to_array(bbox),
+                    })
+                    .collect(),
+                bbox: rect_to_array(bbox),
+                baseline: [baseline.x as f32, baseline.y as f32],
+            })
+        })
+        .collect()
+}
+
+/// Extract the plain text of a page in reading order.
+///
+/// Spans are sorted top-to-bottom and then left-to-right, as the page is
+/// displayed (i.e. taking its rotation into account). Spans on the same line
+/// are joined with a space if there is a gap between them, lines are separated
+/// by `'\n'` and runs of whitespace are collapsed into a single space.
+pub fn extract_page_text(page: &Page<'_>, settings: &InterpreterSettings) -> String {
+    let display_transform = page.initial_transform(false);
+
+    let mut spans = extract_text_spans(page, settings)
+        .into_iter()
+        .map(|span| {
+            let [x0, y0, x1, y1] = span.bbox.map(f64::from);
+            let [x, y] = span.baseline.map(f64::from);
+            let bbox = display_transform.transform_rect_bbox(Rect::new(x0, y0, x1, y1));
+            (display_transform * Point::new(x, y), bbox, span)
+        })
+        .collect::<Vec<_>>();
+    // Display space is y-up, so the top of the page comes first.
+    spans.sort_by(|(a, ..), (b, ..)| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+
+    let mut lines: Vec<Vec<(Point, Rect, TextSpan)>> = Vec::new();
+    for span in spans {
+        let tolerance = f64::from(span.2.font_size).max(1.0) * 0.5;
+
+        match lines.last_mut() {
+            Some(line) if (line[0].0.y - span.0.y).abs() <= tolerance => line.push(span),
+            _ => lines.push(vec![span]),
+        }
+    }
+
+    lines
+        .into_iter()
+        .filter_map(|mut line| {
+            line.sort_by(|(a, ..), (b, ..)| a.x.total_cmp(&b.x));
+
+            let mut text = String::new();
+            let mut last_end: Option<f64> = None;
+            for (_, bbox, span) in &line {
+                let gap = last_end.map_or(0.0, |end| bbox.x0 - end);
+                if gap > f64::from(span.font_size) * WORD_GAP_THRESHOLD {
+                    text.push(' ');
+                }
+                text.push_str(&span.text);
+                last_end = Some(bbox.x1);
+            }
+
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            (!text.is_empty()).then_some(text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Search the text of a page.
+///
+/// Runs of whitespace in `query` match any whitespace or word gap on the page,
+/// and a match can wrap from the end of one line to the start of the next one,
+/// in which case it has one rectangle per line. Matches don't overlap.
+pub fn search_page(
+    page: &Page<'_>,
+    settings: &InterpreterSettings,
+    query: &str,
+    case_sensitive: bool,
+) -> Vec<SearchHit> {
+    let query = query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .collect::<Vec<_>>();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let haystack = searchable_chars(TextExtractor::run(page, settings).fragments);
+    let chars_match = |a: char, b: char| {
+        if case_sensitive {
+            a == b
+        } else {
+            a.to_lowercase().eq(b.to_lowercase())
+        }
+    };
+
+    let mut hits = Vec::new();
+    let mut start = 0;
+    while start + query.len() <= haystack.len() {
+        let candidate = &haystack[start..start + query.len()];
+        if candidate
+            .iter()
+            .zip(&query)
+            .all(|((c, _), q)| chars_match(*c, *q))
+        {
+            hits.push(search_hit(candidate));
+            start += query.len();
+        } else {
+            start += 1;
+        }
+    }
+
+    hits
+}
+
+/// A character of the searchable text, with the line index and bounding box of
+/// its glyph.
+type SearchChar = (char, Option<(usize, Rect)>);
+
+/// Lay out the text of a page as a single sequence of characters for
+/// searching, in reading order.
+///
+/// Whitespace is collapsed into single spaces, and a space without position is
+/// inserted for word gaps and line breaks.
+fn searchable_chars(fragments: Vec<GlyphFragment>) -> Vec<SearchChar> {
+    let mut lines = group_into_lines(fragments);
+    lines.sort_by(|a, b| b[0].baseline.y.total_cmp(&a[0].baseline.y));
+
+    let mut chars = Vec::new();
+    for (line_idx, line) in lines.iter().enumerate() {
+        push_searchable_char(&mut chars, ' ', None);
+
+        let mut last: Option<&GlyphFragment> = None;
+        for fragment in line {
+            if last.is_some_and(|last| is_word_gap(last, fragment)) {
+                push_searchable_char(&mut chars, ' ', None);
+            }
+
+            for c in fragment.text.chars() {
+                push_searchable_char(&mut chars, c, Some((line_idx, fragment.bbox)));
+            }
+
+            last = Some(fragment);
+        }
+    }
+
+    chars
+}
+
+fn push_searchable_char(chars: &mut Vec<SearchChar>, c: char, position: Option<(usize, Rect)>) {
+    let c = if c.is_whitespace() { ' ' } else { c };
+    // Collapse whitespace, including at the start.
+    if c == ' ' && chars.last().is_none_or(|(last, _)| *last == ' ') {
+        return;
+    }
+
+    chars.push((c, position));
+}
+
+fn search_hit(chars: &[SearchChar]) -> SearchHit {
+    let mut rects: Vec<(usize, Rect)> = Vec::new();
+    for (line_idx, bbox) in chars.iter().filter_map(|(_, position)| *position) {
+        match rects.last_mut() {
+            Some((last_line, rect)) if *last_line == line_idx => *rect = union_rect(*rect, bbox),
+            _ => rects.push((line_idx, bbox)),
+        }
+    }
+
+    SearchHit {
+        text: chars.iter().map(|(c, _)| c).collect(),
+        rects: rects
+            .into_iter()
+            .map(|(_, rect)| rect_to_array(rect))
+            .collect(),
+    }
+}
+
+/// Group glyphs into lines by their baseline, and sort each line by the
+/// position of its glyphs along the baseline.
+///
+/// Vertical text is grouped into columns, which are sorted from top to bottom.
+fn group_into_lines(fragments: Vec<GlyphFragment>) -> Vec<Vec<GlyphFragment>> {
+    let mut lines: Vec<Vec<GlyphFragment>> = Vec::new();
+    for fragment in fragments {
+        if fragment.text.is_empty() {
+            continue;
+        }
+
+        match lines
+            .iter_mut()
+            .rev()
+            .find(|line| on_same_line(&line[0], &fragment))
+        {
+            Some(line) => line.push(fragment),
+            None => lines.push(vec![fragment]),
+        }
+    }
+
+    for line in &mut lines {
+        if line[0].vertical {
+            line.sort_by(|a, b| b.baseline.y.total_cmp(&a.baseline.y));
+        } else {
+            line.sort_by(|a, b| a.baseline.x.total_cmp(&b.baseline.x));
+        }
+    }
+
+    lines
+}
+
+/// Whether the gap between two consecutive glyphs of a line is wide enough
+/// for them to belong to different words.
+fn is_word_gap(last: &GlyphFragment, fragment: &GlyphFragment) -> bool {
+    glyph_gap(last, fragment) > word_gap(last, fragment)
+}
+
+/// Split the sorted glyphs of a line into words.
+fn split_words(line: Vec<GlyphFragment>) -> Vec<(String, Rect)> {
+    let mut words: Vec<(String, Rect)> = Vec::new();
+    let mut last: Option<GlyphFragment> = None;
+
+    for fragment in line {
+        if fragment.text.trim().is_empty() {
+            last = None;
+            continue;
+        }
+
+        let continues_word = last
+            .as_ref()
+            .is_some_and(|last| !is_word_gap(last, &fragment));
+
+        match words.last_mut() {
+            Some((text, bbox)) if continues_word => {
+                text.push_str(&fragment.text);
+                *bbox = union_rect(*bbox, fragment.bbox);
+            }
+            _ => words.push((fragment.text.clone(), fragment.bbox)),
+        }
+
+        last = Some(fragment);
+    }
+
+    words
+}
+
 #[derive(Debug, Clone)]
 struct GlyphFragment {
     text: String,
     bbox: Rect,
     baseline: Point,
+    font_name: String,
+    font_size: f64,
+    color: [f32; 3],
+    advance: f64,
+    /// The width of a space in the glyph's font, if it has one.
+    space_width: Option<f64>,
+    vertical: bool,
 }
 
 #[derive(Default)]
@@ -55,18 +508,35 @@ struct TextExtractor {
 }
 
 impl TextExtractor {
+    /// Interpret the page and collect its glyphs, skipping images and shadings.
+    fn run(page: &Page<'_>, settings: &InterpreterSettings) -> Self {
+        let crop = page.intersected_crop_box();
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            Rect::new(crop.x0, crop.y0, crop.x1, crop.y1),
+            page.xref(),
+            InterpreterSettings {
+                text_only: true,
+                ..settings.clone()
+            },
+        );
+        let mut extractor = Self::default();
+        interpret_page(page, &mut context, &mut extractor);
+
+        extractor
+    }
+
     fn into_spans(self) -> Vec<TextSpan> {
         merge_fragments(self.fragments)
             .into_iter()
             .map(|f| TextSpan {
                 text: f.text,
-                bbox: [
-                    f.bbox.x0 as f32,
-                    f.bbox.y0 as f32,
-                    f.bbox.x1 as f32,
-                    f.bbox.y1 as f32,
-                ],
+                bbox: rect_to_array(f.bbox),
                 baseline: [f.baseline.x as f32, f.baseline.y as f32],
+                font_name: f.font_name,
+                font_size: f.font_size as f32,
+                color: f.color,
+                vertical: f.vertical,
             })
             .collect()
     }
@@ -88,7 +558,7 @@ impl Device<'_> for TextExtractor {
         glyph: &Glyph<'_>,
         transform: Affine,
         glyph_transform: Affine,
-        _: &Paint<'_>,
+        paint: &Paint<'_>,
         _: &GlyphDrawMode,
     ) {
         let text = glyph_to_text(glyph);
@@ -97,13 +567,28 @@ impl Device<'_> for TextExtractor {
         }
 
         let full_transform = transform * glyph_transform;
-        let baseline = full_transform * Point::ZERO;
+        let origin = full_transform * Point::ZERO;
+        // In vertical writing mode, the glyph origin is displaced from the
+        // text position, which is on the vertical baseline.
+        let baseline = full_transform * (Point::ZERO - glyph_origin_displacement(glyph));
         let bbox = glyph_bbox(glyph, full_transform);
+        // Glyph space has `UNITS_PER_EM` units per em.
+        let font_size =
+            (full_transform * Point::new(0.0, f64::from(UNITS_PER_EM)) - origin).hypot();
+        let advance = (full_transform * glyph_advance(glyph).to_point() - origin).hypot();
+        let space_width = glyph_space_advance(glyph)
+            .map(|width| (full_transform * Point::new(f64::from(width), 0.0) - origin).hypot());
 
         self.fragments.push(GlyphFragment {
             text,
             bbox,
             baseline,
+            font_name: glyph_font_name(glyph),
+            font_size,
+            color: paint_to_rgb(paint),
+            advance,
+            space_width,
+            vertical: glyph_vertical_advance(glyph).is_some(),
         });
     }
 
@@ -122,6 +607,68 @@ fn glyph_to_text(glyph: &Glyph<'_>) -> String {
     }
 }
 
+fn glyph_font_name(glyph: &Glyph<'_>) -> String {
+    match glyph {
+        Glyph::Outline(outline) => outline
+            .font
+            .postscript_name()
+            .unwrap_or_default()
+            .to_string(),
+        Glyph::Type3(_) => String::new(),
+    }
+}
+
+/// The advance of a glyph in glyph space, which is vertical in vertical
+/// writing mode.
+fn glyph_advance(glyph: &Glyph<'_>) -> Vec2 {
+    if let Some(advance) = glyph_vertical_advance(glyph) {
+        return Vec2::new(0.0, f64::from(advance));
+    }
+
+    let width = match glyph {
+        Glyph::Outline(outline) => outline.advance_width().unwrap_or(0.0),
+        Glyph::Type3(glyph) => glyph.advance_width(),
+    };
+
+    Vec2::new(f64::from(width), 0.0)
+}
+
+/// The vertical advance of a glyph in glyph space, or `None` if it isn't set
+/// in vertical writing mode.
+fn glyph_vertical_advance(glyph: &Glyph<'_>) -> Option<f32> {
+    match glyph {
+        Glyph::Outline(outline) => outline.vertical_advance(),
+        Glyph::Type3(_) => None,
+    }
+}
+
+/// The displacement of the origin of a glyph from the text position, in glyph
+/// space.
+fn glyph_origin_displacement(glyph: &Glyph<'_>) -> Vec2 {
+    match glyph {
+        Glyph::Outline(outline) => outline.origin_displacement(),
+        Glyph::Type3(_) => Vec2::ZERO,
+    }
+}
+
+/// The advance width of a space in the font of a glyph, in glyph space.
+fn glyph_space_advance(glyph: &Glyph<'_>) -> Option<f32> {
+    match glyph {
+        Glyph::Outline(outline) => outline.space_advance_width(),
+        Glyph::Type3(glyph) => glyph.space_advance_width(),
+    }
+}
+
+fn paint_to_rgb(paint: &Paint<'_>) -> [f32; 3] {
+    match paint {
+        Paint::Color(color) => {
+            let [r, g, b, _] = color.to_rgba().components();
+            [r, g, b]
+        }
+        Paint::Pattern(_) => [0.0, 0.0, 0.0],
+    }
+}
+
 fn glyph_bbox(glyph: &Glyph<'_>, transform: Affine) -> Rect {
     match glyph {
         Glyph::Outline(outline) => {
@@ -133,7 +680,10 @@ fn glyph_bbox(glyph: &Glyph<'_>, transform: Affine) -> Rect {
                 fallback_bbox(transform)
             }
         }
-        Glyph::Type3(_) => fallback_bbox(transform),
+        Glyph::Type3(glyph) => glyph
+            .bbox()
+            .map(|bbox| transform.transform_rect_bbox(bbox))
+            .unwrap_or_else(|| fallback_bbox(transform)),
     }
 }
 
@@ -165,6 +715,9 @@ fn merge_fragments(fragments: Vec<GlyphFragment>) -> Vec<GlyphFragment> {
         };
 
         if should_merge(current, &fragment) {
+            if separated_by_space(current, &fragment) {
+                current.text.push(' ');
+            }
             current.text.push_str(&fragment.text);
             current.bbox = union_rect(current.bbox, fragment.bbox);
         } else {
@@ -175,20 +728,99 @@ fn merge_fragments(fragments: Vec<GlyphFragment>) -> Vec<GlyphFragment> {
     merged
 }
 
+/// Whether two glyphs are on the same line, or in the same column for
+/// vertical text.
+fn on_same_line(lhs: &GlyphFragment, rhs: &GlyphFragment) -> bool {
+    if lhs.vertical != rhs.vertical {
+        return false;
+    }
+
+    let line_tolerance = line_extent(lhs).max(line_extent(rhs)) * 0.5;
+    let offset = if lhs.vertical {
+        lhs.baseline.x - rhs.baseline.x
+    } else {
+        lhs.baseline.y - rhs.baseline.y
+    };
+
+    offset.abs() <= line_tolerance.max(0.5)
+}
+
 fn should_merge(lhs: &GlyphFragment, rhs: &GlyphFragment) -> bool {
-    let line_tolerance = lhs.bbox.height().max(rhs.bbox.height()) * 0.5;
-    let same_line = (lhs.baseline.y - rhs.baseline.y).abs() <= line_tolerance.max(0.5);
-    if !same_line {
+    if !on_same_line(lhs, rhs) || !same_style(lhs, rhs) {
         return false;
     }
 
-    let max_gap = lhs.bbox.height().max(rhs.bbox.height()) * 2.0;
-    let min_gap = -lhs.bbox.height().max(rhs.bbox.height()) * 0.75;
-    let gap = rhs.bbox.x0 - lhs.bbox.x1;
+    // Words of wide fonts can be further apart than their glyphs are high.
+    let extent = line_extent(lhs).max(line_extent(rhs));
+    let max_gap = (extent * 2.0).max(word_gap(lhs, rhs) * 4.0);
+    let min_gap = -extent * 0.75;
+    let gap = glyph_gap(lhs, rhs);
 
     gap >= min_gap && gap <= max_gap
 }
 
+/// The size of a glyph across the direction of writing, i.e. its height for
+/// horizontal text and its width for vertical text.
+fn line_extent(fragment: &GlyphFragment) -> f64 {
+    if fragment.vertical {
+        fragment.bbox.width()
+    } else {
+        fragment.bbox.height()
+    }
+}
+
+/// The gap between two consecutive glyphs in the direction of writing, which
+/// is negative if they overlap.
+fn glyph_gap(lhs: &GlyphFragment, rhs: &GlyphFragment) -> f64 {
+    if lhs.vertical {
+        // Vertical text runs downwards.
+        lhs.bbox.y0 - rhs.bbox.y1
+    } else {
+        rhs.bbox.x0 - lhs.bbox.x1
+    }
+}
+
+/// Whether two fragments are drawn with the same font at the same size and
+/// in the same color, so that merging them keeps the span stylistically
+/// homogeneous.
+fn same_style(lhs: &GlyphFragment, rhs: &GlyphFragment) -> bool {
+    let size_diff = (lhs.font_size - rhs.font_size).abs();
+
+    lhs.font_name == rhs.font_name
+        && lhs.color == rhs.color
+        && size_diff <= lhs.font_size.max(rhs.font_size) * FONT_SIZE_TOLERANCE
+}
+
+fn rect_to_array(rect: Rect) -> [f32; 4] {
+    [
+        rect.x0 as f32,
+        rect.y0 as f32,
+        rect.x1 as f32,
+        rect.y1 as f32,
+    ]
+}
+
+/// Whether a space should be inserted between two merged fragments, because
+/// the gap between them is too wide for adjacent glyphs of the same word.
+fn separated_by_space(lhs: &GlyphFragment, rhs: &GlyphFragment) -> bool {
+    let gap = glyph_gap(lhs, rhs);
+    let already_separated =
+        lhs.text.ends_with(char::is_whitespace) || rhs.text.starts_with(char::is_whitespace);
+
+    !already_separated && gap > word_gap(lhs, rhs)
+}
+
+/// The gap between two glyphs above which they belong to different words.
+///
+/// This is relative to the width of a space, or to the font size for fonts
+/// without one.
+fn word_gap(lhs: &GlyphFragment, rhs: &GlyphFragment) -> f64 {
+    match (lhs.space_width, rhs.space_width) {
+        (Some(lhs), Some(rhs)) => lhs.max(rhs) * SPACE_GAP_THRESHOLD,
+        _ => lhs.font_size.max(rhs.font_size) * WORD_GAP_THRESHOLD,
+    }
+}
+
 fn union_rect(lhs: Rect, rhs: Rect) -> Rect {
     Rect::new(
         lhs.x0.min(rhs.x0),
@@ -205,6 +837,10 @@ mod tests {
     use pdf_writer::{Content, Finish, Name, Pdf as WriterPdf, Rect as WriterRect, Ref};
 
     fn create_single_page_pdf(content: &[u8], rotate: i32) -> Vec<u8> {
+        create_single_page_pdf_with_font(content, rotate, b"Helvetica")
+    }
+
+    fn create_single_page_pdf_with_font(content: &[u8], rotate: i32, base_font: &[u8]) -> Vec<u8> {
         let catalog = Ref::new(1);
         let pages = Ref::new(2);
         let page = Ref::new(3);
@@ -225,7 +861,7 @@ mod tests {
         page_writer.resources().fonts().pair(Name(b"F1"), font);
         page_writer.finish();
 
-        pdf.type1_font(font).base_font(Name(b"Helvetica"));
+        pdf.type1_font(font).base_font(Name(base_font));
         pdf.stream(stream, content);
 
         pdf.finish()
@@ -256,6 +892,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn extracts_individual_glyphs() {
+        let mut content = Content::new();
+        content.begin_text();
+        content.set_font(Name(b"F1"), 12.0);
+        content.next_line(80.0, 760.0);
+        content.show(pdf_writer::Str(b"Hello"));
+        content.end_text();
+        let pdf_data = create_single_page_pdf(content.finish().as_slice(), 0);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+
+        let glyphs = extract_glyphs(page, &InterpreterSettings::default());
+        let text = glyphs.iter().map(|g| g.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(text, ["H", "e", "l", "l", "o"]);
+
+        // Helvetica "H" is 722 units wide.
+        assert!((glyphs[0].advance - 8.664).abs() < 0.01, "{glyphs:?}");
+        assert_eq!(glyphs[0].baseline, [80.0, 760.0]);
+        for pair in glyphs.windows(2) {
+            let [a, b] = pair else { unreachable!() };
+            assert!(
+                (a.baseline[0] + a.advance - b.baseline[0]).abs() < 0.01,
+                "{glyphs:?}"
+            );
+            assert!(a.bbox[2] - a.bbox[0] <= a.advance, "{glyphs:?}");
+        }
+
+        let spans = extract_text_spans(page, &InterpreterSettings::default());
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text, "Hello");
+    }
+
     #[test]
     fn extracts_invisible_text() {
         let content = b"BT /F1 12 Tf 3 Tr 80 760 Td (Invisible) Tj ET";
@@ -307,4 +976,223 @@ mod tests {
             "expected adjacent glyph runs to merge into Hello, got {spans:?}"
         );
     }
+
+    #[test]
+    fn inserts_space_between_distant_fragments() {
+        let content = b"BT /F1 12 Tf 72 700 Td (Hello) Tj 40 0 Td (World) Tj ET";
+        let pdf_data = create_single_page_pdf(content, 0);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+
+        let spans = extract_text_spans(page, &InterpreterSettings::default());
+        assert_eq!(
+            spans.iter().map(|s| s.text.as_str()).collect::<Vec<_>>(),
+            vec!["Hello World"]
+        );
+    }
+
+    #[test]
+    fn splits_words_of_wide_monospace_text_by_space_width() {
+        // At 400% horizontal scaling, the gaps between the narrow glyphs of a
+        // word are much wider than they are high, but still narrower than a space.
+        let content = b"BT /F1 10 Tf 400 Tz 20 700 Td (lil ill) Tj ET";
+        let pdf_data = create_single_page_pdf_with_font(content, 0, b"Courier");
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+
+        let spans = extract_text_spans(page, &InterpreterSettings::default());
+        assert_eq!(
+            spans.iter().map(|s| s.text.as_str()).collect::<Vec<_>>(),
+            vec!["lil ill"]
+        );
+
+        let lines = extract_text_lines(page, &InterpreterSettings::default());
+        let words = lines[0]
+            .words
+            .iter()
+            .map(|word| word.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(words, vec!["lil", "ill"]);
+    }
+
+    #[test]
+    fn groups_text_into_lines_and_words() {
+        // The second word is drawn first, but should still come second.
+        let content =
+            b"BT /F1 12 Tf 140 700 Td (World) Tj -68 0 Td (Hello) Tj 0 -20 Td (Next line) Tj ET";
+        let pdf_data = create_single_page_pdf(content, 0);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+
+        let lines = extract_text_lines(page, &InterpreterSettings::default());
+        let words = lines
+            .iter()
+            .map(|line| {
+                line.words
+                    .iter()
+                    .map(|word| word.text.as_str())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(words, vec![vec!["Hello", "World"], vec!["Next", "line"]]);
+
+        let first = &lines[0];
+        assert!((first.baseline[1] - 700.0).abs() < 0.01);
+        assert!(first.words[0].bbox[2] < first.words[1].bbox[0]);
+        assert!(first.bbox[0] <= first.words[0].bbox[0]);
+        assert!(first.bbox[2] >= first.words[1].bbox[2]);
+    }
+
+    #[test]
+    fn splits_spans_with_different_font_sizes() {
+        let content = b"BT /F1 12 Tf 72 700 Td (Big) Tj /F1 8 Tf (small) Tj ET";
+        let pdf_data = create_single_page_pdf(content, 0);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+
+        let spans = extract_text_spans(page, &InterpreterSettings::default());
+        assert_eq!(
+            spans.iter().map(|s| s.text.as_str()).collect::<Vec<_>>(),
+            vec!["Big", "small"]
+        );
+        assert!((spans[0].font_size - 12.0).abs() < 0.01);
+        assert!((spans[1].font_size - 8.0).abs() < 0.01);
+        assert!(spans.iter().all(|s| s.font_name == "Helvetica"));
+    }
+
+    #[test]
+    fn splits_spans_with_different_colors() {
+        let content = b"BT /F1 12 Tf 72 700 Td 1 0 0 rg (Red) Tj 0 0 1 rg (Blue) Tj ET";
+        let pdf_data = create_single_page_pdf(content, 0);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+
+        let spans = extract_text_spans(page, &InterpreterSettings::default());
+        assert_eq!(
+            spans.iter().map(|s| s.text.as_str()).collect::<Vec<_>>(),
+            vec!["Red", "Blue"]
+        );
+        assert_eq!(spans[0].color, [1.0, 0.0, 0.0]);
+        assert_eq!(spans[1].color, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn invisible_text_keeps_its_color() {
+        let content = b"BT /F1 12 Tf 3 Tr 0 1 0 rg 80 760 Td (Hidden) Tj ET";
+        let pdf_data = create_single_page_pdf(content, 0);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+
+        let spans = extract_text_spans(page, &InterpreterSettings::default());
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].color, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn extracts_page_text_in_reading_order() {
+        // The second line is drawn first, but should still come second.
+        let content = b"BT /F1 12 Tf 72 680 Td (Second   line) Tj 0 20 Td (First) Tj ( line) Tj ET";
+        let pdf_data = create_single_page_pdf(content, 0);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+
+        let text = extract_page_text(page, &InterpreterSettings::default());
+        assert_eq!(text, "First line\nSecond line");
+    }
+
+    #[test]
+    fn extracts_page_text_on_rotated_page() {
+        // On a page rotated by 90 degrees, text running up the page in user
+        // space reads left-to-right once displayed, and a larger x is further
+        // down.
+        let content =
+            b"BT /F1 12 Tf 0 1 -1 0 100 100 Tm (Top) Tj 0 1 -1 0 130 100 Tm (Bottom) Tj ET";
+        let pdf_data = create_single_page_pdf(content, 90);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+
+        let text = extract_page_text(page, &InterpreterSettings::default());
+        assert_eq!(text, "Top\nBottom");
+    }
+
+    #[test]
+    fn extracts_only_text_in_rect() {
+        let content = b"BT /F1 12 Tf 72 700 Td (Inside) Tj 0 -100 Td (Outside) Tj ET";
+        let pdf_data = create_single_page_pdf(content, 0);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+        let settings = InterpreterSettings::default();
+
+        let spans = extract_text_in_rect(page, &settings, [60.0, 690.0, 200.0, 720.0]);
+        assert_eq!(
+            spans.iter().map(|s| s.text.as_str()).collect::<Vec<_>>(),
+            vec!["Inside"]
+        );
+
+        // The corners of the rect may be given in any order.
+        let spans = extract_text_in_rect(page, &settings, [200.0, 620.0, 60.0, 590.0]);
+        assert_eq!(
+            spans.iter().map(|s| s.text.as_str()).collect::<Vec<_>>(),
+            vec!["Outside"]
+        );
+
+        assert!(extract_text_in_rect(page, &settings, [300.0, 300.0, 400.0, 400.0]).is_empty());
+    }
+
+    #[test]
+    fn extracts_text_in_rect_on_rotated_page() {
+        let content =
+            b"BT /F1 12 Tf 0 1 -1 0 100 100 Tm (Top) Tj 0 1 -1 0 130 100 Tm (Bottom) Tj ET";
+        let pdf_data = create_single_page_pdf(content, 90);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+
+        // The rect is in page coordinates, where "Bottom" runs up the page
+        // to the left of x = 130.
+        let spans = extract_text_in_rect(
+            page,
+            &InterpreterSettings::default(),
+            [115.0, 95.0, 132.0, 200.0],
+        );
+        assert_eq!(
+            spans.iter().map(|s| s.text.as_str()).collect::<Vec<_>>(),
+            vec!["Bottom"]
+        );
+    }
+
+    #[test]
+    fn search_matches_across_fragments() {
+        let content = b"BT /F1 12 Tf 72 700 Td (Hel) Tj (lo World) Tj ET";
+        let pdf_data = create_single_page_pdf(content, 0);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+        let settings = InterpreterSettings::default();
+
+        let hits = search_page(page, &settings, "hello", false);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].text, "Hello");
+        assert_eq!(hits[0].rects.len(), 1);
+        assert_eq!(hits[0].quad_points().len(), 8);
+
+        assert!(search_page(page, &settings, "hello", true).is_empty());
+    }
+
+    #[test]
+    fn search_match_wraps_across_lines() {
+        let content = b"BT /F1 12 Tf 72 700 Td (Say Hello) Tj 0 -20 Td (World now) Tj ET";
+        let pdf_data = create_single_page_pdf(content, 0);
+        let (pdf, page_idx) = parse_first_page(pdf_data);
+        let page = &pdf.pages()[page_idx];
+
+        let hits = search_page(page, &InterpreterSettings::default(), "Hello  World", true);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].text, "Hello World");
+
+        let rects = &hits[0].rects;
+        assert_eq!(rects.len(), 2);
+        assert!(
+            rects[0][1] > rects[1][3],
+            "first rect should be above the second"
+        );
+    }
 }
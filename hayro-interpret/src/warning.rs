@@ -0,0 +1,73 @@
+//! Collecting the warnings emitted while interpreting a page.
+
+use crate::{InterpreterSettings, InterpreterWarning};
+use hayro_syntax::object::dict::keys::{
+    BASE_FONT, DESCENDANT_FONTS, FONT_DESC, FONT_FILE, FONT_FILE2, FONT_FILE3, SUBTYPE, TYPE3,
+};
+use hayro_syntax::object::{Array, Dict, Name};
+use std::sync::{Arc, Mutex};
+
+/// Call `f` with a copy of `settings` whose warnings are collected, and return
+/// them along with its result.
+///
+/// The warning sink of `settings` is still called for each of them.
+pub fn collect_warnings<T>(
+    settings: &InterpreterSettings,
+    f: impl FnOnce(&InterpreterSettings) -> T,
+) -> (T, Vec<InterpreterWarning>) {
+    let collected = Arc::new(Mutex::new(Vec::new()));
+    let warning_sink = settings.warning_sink.clone();
+    let collecting = InterpreterSettings {
+        warning_sink: {
+            let collected = collected.clone();
+            Arc::new(move |warning: InterpreterWarning| {
+                if let Ok(mut collected) = collected.lock() {
+                    collected.push(warning.clone());
+                }
+                warning_sink(warning);
+            })
+        },
+        ..settings.clone()
+    };
+
+    let result = f(&collecting);
+    let warnings = collected
+        .lock()
+        .map(|mut collected| std::mem::take(&mut *collected))
+        .unwrap_or_default();
+
+    (result, warnings)
+}
+
+/// The name of a font for warnings.
+pub(crate) fn font_name(font_dict: &Dict<'_>) -> String {
+    font_dict
+        .get::<Name>(BASE_FONT)
+        .map(|name| name.as_str().to_string())
+        .unwrap_or_else(|| "(no name)".to_string())
+}
+
+/// Whether the program of a font is embedded in the document.
+///
+/// Type 3 fonts are defined by content streams, so they are always embedded.
+pub(crate) fn is_font_embedded(font_dict: &Dict<'_>) -> bool {
+    if font_dict.get::<Name>(SUBTYPE).as_deref() == Some(TYPE3) {
+        return true;
+    }
+
+    // Composite fonts have their descriptor in the descendant font.
+    let descendant = font_dict
+        .get::<Array<'_>>(DESCENDANT_FONTS)
+        .and_then(|fonts| fonts.iter::<Dict<'_>>().next());
+    let Some(descriptor) = descendant
+        .as_ref()
+        .unwrap_or(font_dict)
+        .get::<Dict<'_>>(FONT_DESC)
+    else {
+        return false;
+    };
+
+    [FONT_FILE, FONT_FILE2, FONT_FILE3]
+        .into_iter()
+        .any(|key| descriptor.contains_key(key))
+}
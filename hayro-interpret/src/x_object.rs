@@ -52,6 +52,11 @@ impl<'a> XObject<'a> {
     }
 }
 
+/// Whether the `XObject` stream is an image.
+pub(crate) fn is_image_xobject(stream: &Stream<'_>) -> bool {
+    stream.dict().get::<Name>(SUBTYPE).as_deref() == Some(IMAGE)
+}
+
 pub(crate) struct FormXObject<'a> {
     pub(crate) decoded: Vec<u8>,
     pub(crate) matrix: Affine,
@@ -23,7 +23,7 @@ use log::warn;
 struct PagesContext {
     media_box: Option<Rect>,
     crop_box: Option<Rect>,
-    rotate: Option<u32>,
+    rotate: Option<i32>,
 }
 
 impl PagesContext {
@@ -116,7 +116,7 @@ fn resolve_pages<'a>(
         ctx.crop_box = Some(crop_box);
     }
 
-    if let Some(rotate) = pages_dict.get::<u32>(ROTATE) {
+    if let Some(rotate) = pages_dict.get::<i32>(ROTATE) {
         ctx.rotate = Some(rotate);
     }
 
@@ -189,7 +189,13 @@ impl<'a> Page<'a> {
             .or(ctx.crop_box)
             .unwrap_or(media_box);
 
-        let rotation = match dict.get::<u32>(ROTATE).or(ctx.rotate).unwrap_or(0) % 360 {
+        // Negative multiples of 90 are valid as well, for example -90 is the same as 270.
+        let rotation = match dict
+            .get::<i32>(ROTATE)
+            .or(ctx.rotate)
+            .unwrap_or(0)
+            .rem_euclid(360)
+        {
             0 => Rotation::None,
             90 => Rotation::Horizontal,
             180 => Rotation::Flipped,
@@ -264,6 +270,17 @@ impl<'a> Page<'a> {
         self.media_box
     }
 
+    /// Get the size of a default user space unit of the page, in multiples of
+    /// 1/72 inch.
+    ///
+    /// This is 1.0 unless the page sets a positive `/UserUnit`.
+    pub fn user_unit(&self) -> f32 {
+        self.inner
+            .get::<f32>(USER_UNIT)
+            .filter(|u| u.is_finite() && *u > 0.0)
+            .unwrap_or(1.0)
+    }
+
     /// Get the rotation of the page.
     pub fn rotation(&self) -> Rotation {
         self.rotation
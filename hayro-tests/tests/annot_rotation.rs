@@ -0,0 +1,119 @@
+use crate::text_extract::{make_single_page_pdf, parse_page, test_settings};
+use hayro::hayro_syntax::page::{Page, Rotation};
+use hayro::vello_cpu::color::palette::css::WHITE;
+use hayro::{BgMode, RenderSettings, render};
+use hayro_annot::{
+    AnnotColor, Annotation, AnnotationBase, InkAnnot, save_annotations, screen_to_pdf,
+};
+
+/// The screen positions of the stylus, in pixels at scale 1.
+const STROKE: [[f32; 2]; 3] = [[100.0, 150.0], [200.0, 150.0], [200.0, 250.0]];
+
+fn rotation_degrees(page: &Page<'_>) -> u32 {
+    match page.rotation() {
+        Rotation::None => 0,
+        Rotation::Horizontal => 90,
+        Rotation::Flipped => 180,
+        Rotation::FlippedHorizontal => 270,
+    }
+}
+
+/// Draw ink along `STROKE` on a page with the given `/Rotate`, and return the
+/// rendered pixels of the annotated page.
+fn draw_and_render(rotate: i32) -> (u16, Vec<[u8; 4]>) {
+    let original = make_single_page_pdf(b"", rotate);
+    let pdf = parse_page(original.clone());
+    let page = &pdf.pages()[0];
+    let (width, height) = page.render_dimensions();
+    let crop = page.intersected_crop_box();
+    let crop_box = [
+        crop.x0 as f32,
+        crop.y0 as f32,
+        crop.x1 as f32,
+        crop.y1 as f32,
+    ];
+
+    let path: Vec<[f32; 2]> = STROKE
+        .iter()
+        .map(|p| {
+            let (x, y) = screen_to_pdf(
+                p[0],
+                p[1],
+                width,
+                height,
+                crop_box,
+                rotation_degrees(page),
+                1.0,
+            );
+            [x, y]
+        })
+        .collect();
+
+    let line_width = 6.0;
+    let rect = [
+        path.iter().map(|p| p[0]).fold(f32::MAX, f32::min) - line_width,
+        path.iter().map(|p| p[1]).fold(f32::MAX, f32::min) - line_width,
+        path.iter().map(|p| p[0]).fold(f32::MIN, f32::max) + line_width,
+        path.iter().map(|p| p[1]).fold(f32::MIN, f32::max) + line_width,
+    ];
+    let ink = Annotation::Ink(InkAnnot {
+        base: AnnotationBase {
+            rect,
+            color: Some(AnnotColor::red()),
+            ..Default::default()
+        },
+        ink_list: vec![path],
+        line_width,
+        smoothing: 0.0,
+        dash: None,
+    });
+
+    let saved = save_annotations(&original, &[(0, vec![ink])]).expect("save should succeed");
+    let pdf = parse_page(saved);
+    let pixmap = render(
+        &pdf.pages()[0],
+        &test_settings(),
+        &RenderSettings {
+            bg_mode: BgMode::Solid(WHITE),
+            ..Default::default()
+        },
+    );
+
+    let width = pixmap.width();
+    let pixels = pixmap
+        .take_unpremultiplied()
+        .into_iter()
+        .map(|p| [p.r, p.g, p.b, p.a])
+        .collect();
+
+    (width, pixels)
+}
+
+fn is_red(pixel: [u8; 4]) -> bool {
+    pixel[0] > 200 && pixel[1] < 80 && pixel[2] < 80
+}
+
+#[test]
+fn ink_renders_under_stylus_on_rotated_pages() {
+    for rotate in [0, 90, 180, 270, -90] {
+        let (width, pixels) = draw_and_render(rotate);
+        let at = |x: f32, y: f32| pixels[y as usize * width as usize + x as usize];
+
+        // Points along the drawn stroke are inked.
+        for [x, y] in [[150.0, 150.0], [200.0, 200.0], [120.0, 150.0]] {
+            assert!(
+                is_red(at(x, y)),
+                "/Rotate {rotate}: expected ink at ({x}, {y}), got {:?}",
+                at(x, y)
+            );
+        }
+
+        // Mirrored or rotated positions stay blank.
+        for [x, y] in [[150.0, 250.0], [100.0, 200.0], [250.0, 150.0]] {
+            assert!(
+                !is_red(at(x, y)),
+                "/Rotate {rotate}: unexpected ink at ({x}, {y})"
+            );
+        }
+    }
+}
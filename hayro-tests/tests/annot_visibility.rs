@@ -0,0 +1,96 @@
+use crate::text_extract::{make_single_page_pdf, parse_page, test_settings};
+use hayro::hayro_interpret::{InterpreterSettings, RenderTarget};
+use hayro::vello_cpu::color::palette::css::WHITE;
+use hayro::{BgMode, RenderSettings, render};
+use hayro_annot::{
+    AnnotColor, Annotation, AnnotationBase, BorderStyle, ShapeAnnot, save_annotations,
+};
+
+/// Render a page with a red square annotation with the given flags and
+/// opacity, and return the pixel at the center of the square.
+fn render_square(flags: u32, opacity: f32, settings: &InterpreterSettings) -> [u8; 4] {
+    let original = make_single_page_pdf(b"", 0);
+    let square = Annotation::Square(ShapeAnnot {
+        base: AnnotationBase {
+            rect: [100.0, 100.0, 200.0, 200.0],
+            color: Some(AnnotColor::red()),
+            flags,
+            opacity,
+            ..Default::default()
+        },
+        interior_color: Some(AnnotColor::red()),
+        line_width: 1.0,
+        is_circle: false,
+        border_style: BorderStyle::Solid,
+        fill_opacity: 1.0,
+        corner_radius: 0.0,
+    });
+
+    let saved = save_annotations(&original, &[(0, vec![square])]).expect("save should succeed");
+    let pdf = parse_page(saved);
+    let pixmap = render(
+        &pdf.pages()[0],
+        settings,
+        &RenderSettings {
+            bg_mode: BgMode::Solid(WHITE),
+            ..Default::default()
+        },
+    );
+
+    // The center of the square, with the y-axis pointing down.
+    let (x, y) = (150, 842 - 150);
+    let width = usize::from(pixmap.width());
+    let pixel = pixmap.take_unpremultiplied()[y * width + x];
+    [pixel.r, pixel.g, pixel.b, pixel.a]
+}
+
+fn is_red(pixel: [u8; 4]) -> bool {
+    pixel[0] > 200 && pixel[1] < 80 && pixel[2] < 80
+}
+
+fn is_white(pixel: [u8; 4]) -> bool {
+    pixel.iter().all(|&c| c > 240)
+}
+
+#[test]
+fn annotation_flags_determine_visibility() {
+    const HIDDEN: u32 = 2;
+    const PRINT: u32 = 4;
+    const NO_VIEW: u32 = 32;
+
+    let view = test_settings();
+    let print = InterpreterSettings {
+        render_target: RenderTarget::Print,
+        ..test_settings()
+    };
+
+    assert!(is_red(render_square(PRINT, 1.0, &view)));
+    assert!(is_red(render_square(PRINT, 1.0, &print)));
+
+    assert!(is_red(render_square(0, 1.0, &view)));
+    assert!(is_white(render_square(0, 1.0, &print)));
+
+    assert!(is_white(render_square(PRINT | NO_VIEW, 1.0, &view)));
+    assert!(is_red(render_square(PRINT | NO_VIEW, 1.0, &print)));
+
+    assert!(is_white(render_square(PRINT | HIDDEN, 1.0, &view)));
+    assert!(is_white(render_square(PRINT | HIDDEN, 1.0, &print)));
+}
+
+#[test]
+fn annotations_are_not_rendered_when_disabled() {
+    let settings = InterpreterSettings {
+        render_annotations: false,
+        ..test_settings()
+    };
+
+    assert!(is_white(render_square(4, 1.0, &settings)));
+}
+
+#[test]
+fn annotation_opacity_is_respected() {
+    let pixel = render_square(4, 0.5, &test_settings());
+    assert!(pixel[0] > 240, "{pixel:?}");
+    assert!((100..160).contains(&pixel[1]), "{pixel:?}");
+    assert!((100..160).contains(&pixel[2]), "{pixel:?}");
+}
@@ -0,0 +1,59 @@
+use crate::text_extract::{make_single_page_pdf, parse_page, test_settings};
+use hayro::vello_cpu::color::palette::css::{BLUE, WHITE};
+use hayro::{BgMode, RenderSettings, render};
+
+#[test]
+fn checkerboard_is_drawn_behind_page_content() {
+    let pdf = parse_page(make_single_page_pdf(b"1 0 0 rg 0 0 100 100 re f", 0));
+
+    for scale in [1.0, 2.0] {
+        let pixmap = render(
+            &pdf.pages()[0],
+            &test_settings(),
+            &RenderSettings {
+                x_scale: scale,
+                y_scale: scale,
+                bg_mode: BgMode::Checker {
+                    size: 10,
+                    c1: WHITE,
+                    c2: BLUE,
+                },
+                ..Default::default()
+            },
+        );
+
+        let width = usize::from(pixmap.width());
+        let height = usize::from(pixmap.height());
+        let pixels = pixmap.take_unpremultiplied();
+        let at = |x: usize, y: usize| {
+            let p = pixels[y * width + x];
+            [p.r, p.g, p.b, p.a]
+        };
+
+        // The size of the squares is in pixels, independent of the scale.
+        assert_eq!(at(5, 5), [255, 255, 255, 255], "scale {scale}");
+        assert_eq!(at(15, 5), [0, 0, 255, 255], "scale {scale}");
+        assert_eq!(at(5, 15), [0, 0, 255, 255], "scale {scale}");
+        assert_eq!(at(15, 15), [255, 255, 255, 255], "scale {scale}");
+        assert_eq!(at(25, 5), [255, 255, 255, 255], "scale {scale}");
+
+        // The red square in the bottom-left corner covers the background.
+        assert_eq!(at(5, height - 5), [255, 0, 0, 255], "scale {scale}");
+        assert_eq!(at(15, height - 5), [255, 0, 0, 255], "scale {scale}");
+    }
+}
+
+#[test]
+fn transparent_background_is_left_empty() {
+    let pdf = parse_page(make_single_page_pdf(b"", 0));
+    let pixmap = render(
+        &pdf.pages()[0],
+        &test_settings(),
+        &RenderSettings {
+            bg_mode: BgMode::Transparent,
+            ..Default::default()
+        },
+    );
+
+    assert!(pixmap.data().iter().all(|p| p.a == 0));
+}
@@ -0,0 +1,195 @@
+use crate::text_extract::test_settings;
+use hayro::hayro_interpret::InterpreterSettings;
+use hayro::hayro_interpret::font::FontData;
+use hayro::vello_cpu::color::palette::css::WHITE;
+use hayro::{BgMode, RenderSettings, render};
+use hayro_syntax::Pdf;
+use pdf_writer::{Finish, Name, Pdf as WriterPdf, Rect, Ref};
+use std::sync::Arc;
+
+/// A page showing a large "A" in a non-embedded Helvetica whose encoding maps
+/// it to the Greek letter alpha, which Helvetica doesn't have.
+fn make_alpha_pdf() -> Pdf {
+    let catalog = Ref::new(1);
+    let pages = Ref::new(2);
+    let page = Ref::new(3);
+    let font = Ref::new(4);
+    let stream = Ref::new(5);
+
+    let mut pdf = WriterPdf::new();
+    pdf.catalog(catalog).pages(pages);
+    pdf.pages(pages).kids([page]).count(1);
+
+    let mut page_writer = pdf.page(page);
+    page_writer.parent(pages);
+    page_writer.media_box(Rect::new(0.0, 0.0, 300.0, 300.0));
+    page_writer.contents(stream);
+    page_writer.resources().fonts().pair(Name(b"F1"), font);
+    page_writer.finish();
+
+    let mut font_writer = pdf.type1_font(font);
+    font_writer.base_font(Name(b"Helvetica"));
+    font_writer
+        .encoding_custom()
+        .differences()
+        .consecutive(65, [Name(b"alpha")]);
+    font_writer.finish();
+    pdf.stream(stream, b"BT /F1 200 Tf 50 50 Td (A) Tj ET");
+
+    Pdf::new(pdf.finish()).expect("generated test PDF should parse")
+}
+
+/// A page showing "日本語" in a non-embedded composite font, whose character
+/// codes are mapped to Unicode by its `ToUnicode` map.
+fn make_cjk_pdf() -> Pdf {
+    use pdf_writer::Str;
+    use pdf_writer::types::{CidFontType, FontFlags, SystemInfo};
+
+    let catalog = Ref::new(1);
+    let pages = Ref::new(2);
+    let page = Ref::new(3);
+    let font = Ref::new(4);
+    let stream = Ref::new(5);
+    let cid_font = Ref::new(6);
+    let descriptor = Ref::new(7);
+    let to_unicode = Ref::new(8);
+
+    let mut pdf = WriterPdf::new();
+    pdf.catalog(catalog).pages(pages);
+    pdf.pages(pages).kids([page]).count(1);
+
+    let mut page_writer = pdf.page(page);
+    page_writer.parent(pages);
+    page_writer.media_box(Rect::new(0.0, 0.0, 400.0, 300.0));
+    page_writer.contents(stream);
+    page_writer.resources().fonts().pair(Name(b"F1"), font);
+    page_writer.finish();
+
+    pdf.type0_font(font)
+        .base_font(Name(b"MSung-Light"))
+        .encoding_predefined(Name(b"Identity-H"))
+        .descendant_font(cid_font)
+        .to_unicode(to_unicode);
+
+    let mut cid = pdf.cid_font(cid_font);
+    cid.subtype(CidFontType::Type0);
+    cid.base_font(Name(b"MSung-Light"));
+    cid.system_info(SystemInfo {
+        registry: Str(b"Adobe"),
+        ordering: Str(b"CNS1"),
+        supplement: 0,
+    });
+    cid.font_descriptor(descriptor);
+    cid.default_width(1000.0);
+    cid.finish();
+
+    pdf.font_descriptor(descriptor)
+        .name(Name(b"MSung-Light"))
+        .flags(FontFlags::SYMBOLIC)
+        .bbox(Rect::new(0.0, -120.0, 1000.0, 880.0))
+        .italic_angle(0.0)
+        .ascent(880.0)
+        .descent(-120.0)
+        .cap_height(880.0)
+        .stem_v(80.0);
+
+    pdf.stream(
+        to_unicode,
+        b"/CIDInit /ProcSet findresource begin
+12 dict begin
+begincmap
+/CMapName /Adobe-Identity-UCS def
+/CMapType 2 def
+1 begincodespacerange
+<0000> <FFFF>
+endcodespacerange
+3 beginbfchar
+<0001> <65E5>
+<0002> <672C>
+<0003> <8A9E>
+endbfchar
+endcmap
+CMapName currentdict /CMap defineresource pop
+end
+end",
+    );
+    pdf.stream(stream, b"BT /F1 100 Tf 20 100 Td <000100020003> Tj ET");
+
+    Pdf::new(pdf.finish()).expect("generated test PDF should parse")
+}
+
+/// The number of pixels that aren't white.
+fn ink(pdf: &Pdf, settings: &InterpreterSettings) -> usize {
+    let pixmap = render(
+        &pdf.pages()[0],
+        settings,
+        &RenderSettings {
+            bg_mode: BgMode::Solid(WHITE),
+            ..Default::default()
+        },
+    );
+
+    pixmap
+        .take_unpremultiplied()
+        .into_iter()
+        .filter(|p| p.r < 128)
+        .count()
+}
+
+#[test]
+fn missing_glyphs_are_taken_from_the_next_fallback_font() {
+    let pdf = make_alpha_pdf();
+    let sans: FontData =
+        Arc::new(&include_bytes!("../../hayro-interpret/assets/FoxitSans.pfb")[..]);
+    let symbol: FontData =
+        Arc::new(&include_bytes!("../../hayro-interpret/assets/FoxitSymbol.pfb")[..]);
+
+    // Without fallback fonts, nothing is drawn for the missing glyph.
+    assert_eq!(ink(&pdf, &test_settings()), 0);
+
+    // The first fallback font doesn't have an alpha either.
+    let only_sans = InterpreterSettings {
+        fallback_font_resolver: Arc::new({
+            let sans = sans.clone();
+            move |_| vec![(sans.clone(), 0)]
+        }),
+        ..test_settings()
+    };
+    assert_eq!(ink(&pdf, &only_sans), 0);
+
+    // The second one does, so it is used instead.
+    let chain = InterpreterSettings {
+        fallback_font_resolver: Arc::new(move |_| vec![(sans.clone(), 0), (symbol.clone(), 0)]),
+        ..test_settings()
+    };
+    assert!(ink(&pdf, &chain) > 1000);
+}
+
+#[test]
+fn glyphs_of_non_embedded_cid_fonts_are_taken_from_fallback_fonts() {
+    let pdf = make_cjk_pdf();
+    let sans: FontData =
+        Arc::new(&include_bytes!("../../hayro-interpret/assets/FoxitSans.pfb")[..]);
+    let cjk: FontData =
+        Arc::new(&include_bytes!("../../hayro-annot/tests/assets/test-font.ttf")[..]);
+
+    // Without fallback fonts, the font has no glyphs at all.
+    assert_eq!(ink(&pdf, &test_settings()), 0);
+
+    // The Latin fallback font doesn't cover the text.
+    let only_sans = InterpreterSettings {
+        fallback_font_resolver: Arc::new({
+            let sans = sans.clone();
+            move |_| vec![(sans.clone(), 0)]
+        }),
+        ..test_settings()
+    };
+    assert_eq!(ink(&pdf, &only_sans), 0);
+
+    // The CJK one does, so its glyphs are found by their Unicode code points.
+    let chain = InterpreterSettings {
+        fallback_font_resolver: Arc::new(move |_| vec![(sans.clone(), 0), (cjk.clone(), 0)]),
+        ..test_settings()
+    };
+    assert!(ink(&pdf, &chain) > 1000);
+}
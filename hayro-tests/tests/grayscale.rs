@@ -0,0 +1,48 @@
+use crate::text_extract::{make_single_page_pdf, parse_page, test_settings};
+use hayro::vello_cpu::color::palette::css::BLUE;
+use hayro::{BgMode, RenderSettings, render};
+
+const CONTENT: &[u8] = b"1 0 0 rg 50 50 200 200 re f";
+
+fn render_pixels(grayscale: bool) -> (usize, Vec<[u8; 4]>) {
+    let pdf = parse_page(make_single_page_pdf(CONTENT, 0));
+    let pixmap = render(
+        &pdf.pages()[0],
+        &test_settings(),
+        &RenderSettings {
+            bg_mode: BgMode::Solid(BLUE),
+            grayscale,
+            ..Default::default()
+        },
+    );
+
+    let width = usize::from(pixmap.width());
+    let pixels = pixmap
+        .take_unpremultiplied()
+        .into_iter()
+        .map(|p| [p.r, p.g, p.b, p.a])
+        .collect();
+
+    (width, pixels)
+}
+
+#[test]
+fn grayscale_converts_content_and_background() {
+    let (width, pixels) = render_pixels(true);
+    let at = |x: usize, y: usize| pixels[y * width + x];
+
+    // Inside the red rectangle and on the blue background, with the y-axis
+    // pointing down.
+    assert_eq!(at(150, 842 - 150), [54, 54, 54, 255]);
+    assert_eq!(at(400, 100), [18, 18, 18, 255]);
+    assert!(pixels.iter().all(|p| p[0] == p[1] && p[1] == p[2]));
+}
+
+#[test]
+fn color_is_kept_by_default() {
+    let (width, pixels) = render_pixels(false);
+    let at = |x: usize, y: usize| pixels[y * width + x];
+
+    assert_eq!(at(150, 842 - 150), [255, 0, 0, 255]);
+    assert_eq!(at(400, 100), [0, 0, 255, 255]);
+}
@@ -0,0 +1,106 @@
+use hayro::hayro_interpret::{InterpreterSettings, layer_names};
+use hayro::vello_cpu::color::palette::css::WHITE;
+use hayro::{BgMode, RenderSettings, render};
+use hayro_syntax::Pdf;
+use pdf_writer::{Finish, Name, Pdf as WriterPdf, Rect, Ref, Str};
+
+/// A page with a blue square in the layer "Base" and a red square in the
+/// layer "Notes", which is hidden by default.
+fn make_layered_pdf() -> Vec<u8> {
+    let catalog = Ref::new(1);
+    let pages = Ref::new(2);
+    let page = Ref::new(3);
+    let stream = Ref::new(4);
+    let base = Ref::new(5);
+    let notes = Ref::new(6);
+
+    let mut pdf = WriterPdf::new();
+    let mut catalog_writer = pdf.catalog(catalog);
+    catalog_writer.pages(pages);
+    let mut properties = catalog_writer.insert(Name(b"OCProperties")).dict();
+    properties
+        .insert(Name(b"OCGs"))
+        .array()
+        .items([base, notes]);
+    properties
+        .insert(Name(b"D"))
+        .dict()
+        .insert(Name(b"OFF"))
+        .array()
+        .item(notes);
+    properties.finish();
+    catalog_writer.finish();
+    pdf.pages(pages).kids([page]).count(1);
+
+    let mut page_writer = pdf.page(page);
+    page_writer.parent(pages);
+    page_writer.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+    page_writer.contents(stream);
+    page_writer
+        .resources()
+        .insert(Name(b"Properties"))
+        .dict()
+        .pair(Name(b"L1"), base)
+        .pair(Name(b"L2"), notes);
+    page_writer.finish();
+
+    for (id, name) in [(base, b"Base".as_slice()), (notes, b"Notes".as_slice())] {
+        pdf.indirect(id)
+            .dict()
+            .pair(Name(b"Type"), Name(b"OCG"))
+            .pair(Name(b"Name"), Str(name));
+    }
+    pdf.stream(
+        stream,
+        b"/OC /L1 BDC 0 0 1 rg 100 100 100 100 re f EMC \
+          /OC /L2 BDC 1 0 0 rg 300 100 100 100 re f EMC",
+    );
+    pdf.finish()
+}
+
+/// Render the page and return the color at the center of both squares.
+fn square_colors(pdf: &Pdf, settings: &InterpreterSettings) -> [[u8; 3]; 2] {
+    let pixmap = render(
+        &pdf.pages()[0],
+        settings,
+        &RenderSettings {
+            bg_mode: BgMode::Solid(WHITE),
+            ..Default::default()
+        },
+    );
+    let width = usize::from(pixmap.width());
+    let pixels = pixmap.take_unpremultiplied();
+    let at = |x: usize| {
+        let p = pixels[(842 - 150) * width + x];
+        [p.r, p.g, p.b]
+    };
+
+    [at(150), at(350)]
+}
+
+#[test]
+fn visible_layers_override_default_configuration() {
+    let pdf = Pdf::new(make_layered_pdf()).expect("generated test PDF should parse");
+    assert_eq!(layer_names(&pdf), ["Base", "Notes"]);
+
+    const BLUE: [u8; 3] = [0, 0, 255];
+    const RED: [u8; 3] = [255, 0, 0];
+    const BLANK: [u8; 3] = [255, 255, 255];
+
+    assert_eq!(
+        square_colors(&pdf, &InterpreterSettings::default()),
+        [BLUE, BLANK]
+    );
+
+    let only_notes = InterpreterSettings {
+        visible_layers: Some(["Notes".to_string()].into_iter().collect()),
+        ..Default::default()
+    };
+    assert_eq!(square_colors(&pdf, &only_notes), [BLANK, RED]);
+
+    let none = InterpreterSettings {
+        visible_layers: Some(Default::default()),
+        ..Default::default()
+    };
+    assert_eq!(square_colors(&pdf, &none), [BLANK, BLANK]);
+}
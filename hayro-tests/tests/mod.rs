@@ -13,12 +13,19 @@ use std::ops::RangeInclusive;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+mod annot_rotation;
+mod annot_visibility;
+mod background;
+mod font_fallback;
+mod grayscale;
+mod layers;
 #[rustfmt::skip]
 #[allow(non_snake_case)]
 mod render;
 mod load;
 mod svg;
 mod text_extract;
+mod timing;
 mod write;
 
 const REPLACE: Option<&str> = option_env!("REPLACE");
@@ -1,10 +1,14 @@
 use hayro::hayro_interpret::font::{FontData, FontQuery, StandardFont};
-use hayro::hayro_interpret::{InterpreterSettings, extract_text_spans};
+use hayro::hayro_interpret::{
+    InterpreterSettings, InterpreterWarning, extract_text_spans, extract_text_spans_with_warnings,
+};
+use hayro::vello_cpu::color::palette::css::WHITE;
+use hayro::{BgMode, RenderSettings, render, render_with_warnings};
 use hayro_syntax::Pdf;
 use pdf_writer::{Finish, Name, Pdf as WriterPdf, Rect, Ref};
 use std::sync::Arc;
 
-fn make_single_page_pdf(content: &[u8], rotate: i32) -> Vec<u8> {
+pub(crate) fn make_single_page_pdf(content: &[u8], rotate: i32) -> Vec<u8> {
     let catalog = Ref::new(1);
     let pages = Ref::new(2);
     let page = Ref::new(3);
@@ -30,11 +34,11 @@ fn make_single_page_pdf(content: &[u8], rotate: i32) -> Vec<u8> {
     pdf.finish()
 }
 
-fn parse_page(pdf_data: Vec<u8>) -> Pdf {
+pub(crate) fn parse_page(pdf_data: Vec<u8>) -> Pdf {
     Pdf::new(pdf_data).expect("generated test PDF should parse")
 }
 
-fn test_settings() -> InterpreterSettings {
+pub(crate) fn test_settings() -> InterpreterSettings {
     let pick = |font: &StandardFont| -> FontData {
         let bytes: &'static [u8] = match font {
             StandardFont::Helvetica => {
@@ -148,3 +152,218 @@ fn text_extraction_empty_content_yields_no_spans() {
     let spans = extract_text_spans(&pdf.pages()[0], &test_settings());
     assert!(spans.is_empty(), "expected empty spans, got {spans:?}");
 }
+
+#[test]
+fn text_extraction_groups_vertical_text_into_columns() {
+    // Uses an embedded CJK font with the `Identity-V` encoding.
+    let pdf = Pdf::new(include_bytes!("../pdfs/custom/font_vertical.pdf").to_vec())
+        .expect("fixture should parse");
+
+    let spans = extract_text_spans(&pdf.pages()[0], &test_settings());
+    assert!(
+        spans.iter().all(|span| span.vertical),
+        "expected only vertical spans, got {spans:?}"
+    );
+
+    let span = spans
+        .iter()
+        .find(|span| span.text == "中黃實")
+        .unwrap_or_else(|| panic!("expected a column with the first string, got {spans:?}"));
+    let [x0, y0, x1, y1] = span.bbox;
+    assert!(
+        y1 - y0 > 2.0 * (x1 - x0),
+        "expected a tall column, got {span:?}"
+    );
+    // The vertical baseline runs through the middle of the column and starts
+    // at its top.
+    let [baseline_x, baseline_y] = span.baseline;
+    assert!(x0 < baseline_x && baseline_x < x1, "{span:?}");
+    assert!(baseline_y >= y1 - 1.0, "{span:?}");
+}
+
+/// A page with a Type3 font `/T3` whose glyphs `A` and `W` are 200 and 800
+/// units wide rectangles, without a `ToUnicode` cmap.
+fn make_type3_pdf(content: &[u8]) -> Vec<u8> {
+    let catalog = Ref::new(1);
+    let pages = Ref::new(2);
+    let page = Ref::new(3);
+    let font = Ref::new(4);
+    let stream = Ref::new(5);
+    let narrow = Ref::new(6);
+    let wide = Ref::new(7);
+
+    let mut pdf = WriterPdf::new();
+    pdf.catalog(catalog).pages(pages);
+    pdf.pages(pages).kids([page]).count(1);
+
+    let mut page_writer = pdf.page(page);
+    page_writer.parent(pages);
+    page_writer.media_box(Rect::new(0.0, 0.0, 595.0, 842.0));
+    page_writer.contents(stream);
+    page_writer.resources().fonts().pair(Name(b"T3"), font);
+    page_writer.finish();
+
+    let mut font_dict = pdf.indirect(font).dict();
+    font_dict.pair(Name(b"Type"), Name(b"Font"));
+    font_dict.pair(Name(b"Subtype"), Name(b"Type3"));
+    font_dict
+        .insert(Name(b"FontBBox"))
+        .array()
+        .items([0.0_f32, 0.0, 1000.0, 1000.0]);
+    font_dict
+        .insert(Name(b"FontMatrix"))
+        .array()
+        .items([0.001_f32, 0.0, 0.0, 0.001, 0.0, 0.0]);
+    font_dict.pair(Name(b"FirstChar"), 65);
+    font_dict.pair(Name(b"LastChar"), 66);
+    font_dict
+        .insert(Name(b"Widths"))
+        .array()
+        .items([300.0_f32, 900.0]);
+    font_dict
+        .insert(Name(b"Encoding"))
+        .dict()
+        .insert(Name(b"Differences"))
+        .array()
+        .item(65)
+        .item(Name(b"A"))
+        .item(Name(b"W"));
+    let mut char_procs = font_dict.insert(Name(b"CharProcs")).dict();
+    char_procs.pair(Name(b"A"), narrow);
+    char_procs.pair(Name(b"W"), wide);
+    char_procs.finish();
+    font_dict.finish();
+
+    pdf.stream(narrow, b"300 0 0 0 200 700 d1 0 0 200 700 re f");
+    pdf.stream(wide, b"900 0 0 0 800 700 d1 0 0 800 700 re f");
+    pdf.stream(stream, content);
+    pdf.finish()
+}
+
+#[test]
+fn text_extraction_uses_type3_glyph_bbox() {
+    let pdf = parse_page(make_type3_pdf(
+        b"BT /T3 20 Tf 100 100 Td (A) Tj ET BT /T3 20 Tf 100 300 Td (B) Tj ET",
+    ));
+
+    let spans = extract_text_spans(&pdf.pages()[0], &test_settings());
+    let span = |text: &str| {
+        spans
+            .iter()
+            .find(|span| span.text == text)
+            .unwrap_or_else(|| panic!("expected a span with {text:?}, got {spans:?}"))
+    };
+
+    // The glyphs are 200 and 800 units wide and 700 units high at 20pt, rather
+    // than the 500 by 1000 unit fallback.
+    let narrow = span("A");
+    assert!((narrow.bbox[0] - 100.0).abs() < 0.01, "{narrow:?}");
+    assert!(
+        (narrow.bbox[2] - narrow.bbox[0] - 4.0).abs() < 0.01,
+        "{narrow:?}"
+    );
+    assert!(
+        (narrow.bbox[3] - narrow.bbox[1] - 14.0).abs() < 0.01,
+        "{narrow:?}"
+    );
+
+    let wide = span("W");
+    assert!(
+        (wide.bbox[2] - wide.bbox[0] - 16.0).abs() < 0.01,
+        "{wide:?}"
+    );
+}
+
+#[test]
+fn text_extraction_honors_text_rise_and_horizontal_scaling() {
+    let pdf = parse_page(make_single_page_pdf(
+        b"BT /F1 12 Tf 72 700 Td (Normal) Tj ET \
+          BT /F1 8 Tf 6 Ts 72 600 Td (Raised) Tj ET \
+          BT /F1 12 Tf 50 Tz 72 500 Td (Normal) Tj ET",
+        0,
+    ));
+
+    let spans = extract_text_spans(&pdf.pages()[0], &test_settings());
+    let span_at = |y: f32| {
+        spans
+            .iter()
+            .find(|span| (span.bbox[1]..=span.bbox[3]).contains(&y))
+            .unwrap_or_else(|| panic!("expected a span around y={y}, got {spans:?}"))
+    };
+
+    // The rise moves the baseline and the glyphs up.
+    let raised = span_at(608.0);
+    assert_eq!(raised.text, "Raised");
+    assert!((raised.baseline[1] - 606.0).abs() < 0.01, "{raised:?}");
+    assert!(raised.bbox[1] > 600.0, "{raised:?}");
+
+    // Horizontal scaling condenses the text, but doesn't change its height.
+    let normal = span_at(704.0);
+    let condensed = span_at(504.0);
+    let width = |span: &hayro::hayro_interpret::TextSpan| span.bbox[2] - span.bbox[0];
+    assert!(
+        (width(condensed) - width(normal) / 2.0).abs() < 0.5,
+        "{normal:?} {condensed:?}"
+    );
+    assert!((condensed.font_size - normal.font_size).abs() < 0.01);
+}
+
+#[test]
+fn text_only_interpretation_skips_images() {
+    let pdf = parse_page(make_single_page_pdf(
+        b"q 200 0 0 200 50 50 cm BI /W 1 /H 1 /CS /G /BPC 8 ID \x00 EI Q \
+          BT /F1 12 Tf 72 700 Td (Caption) Tj ET",
+        0,
+    ));
+    let page = &pdf.pages()[0];
+    let pixel_in_image = |settings: &InterpreterSettings| {
+        let pixmap = render(
+            page,
+            settings,
+            &RenderSettings {
+                bg_mode: BgMode::Solid(WHITE),
+                ..Default::default()
+            },
+        );
+        let width = usize::from(pixmap.width());
+        let p = pixmap.take_unpremultiplied()[(842 - 150) * width + 150];
+        [p.r, p.g, p.b]
+    };
+
+    assert_eq!(pixel_in_image(&test_settings()), [0, 0, 0]);
+    let text_only = InterpreterSettings {
+        text_only: true,
+        ..test_settings()
+    };
+    assert_eq!(pixel_in_image(&text_only), [255, 255, 255]);
+
+    let spans = extract_text_spans(page, &text_only);
+    assert_eq!(spans.len(), 1, "{spans:?}");
+    assert_eq!(spans[0].text, "Caption");
+}
+
+#[test]
+fn warnings_report_missing_and_unembedded_fonts() {
+    let pdf = parse_page(make_single_page_pdf(
+        b"BT /F1 12 Tf 72 700 Td (Hello) Tj /F9 12 Tf (World) Tj /F1 12 Tf (!) Tj ET",
+        0,
+    ));
+    let page = &pdf.pages()[0];
+
+    let (spans, warnings) = extract_text_spans_with_warnings(page, &test_settings());
+    assert!(!spans.is_empty());
+    assert_eq!(
+        warnings,
+        [
+            InterpreterWarning::FontNotEmbedded("Helvetica".to_string()),
+            InterpreterWarning::MissingFont("F9".to_string()),
+        ]
+    );
+    assert_eq!(
+        warnings[1].to_string(),
+        "font F9 not found, falling back to Helvetica"
+    );
+
+    let (_, rendered) = render_with_warnings(page, &test_settings(), &RenderSettings::default());
+    assert_eq!(rendered, warnings);
+}
@@ -0,0 +1,57 @@
+use crate::text_extract::{make_single_page_pdf, parse_page, test_settings};
+use hayro::hayro_interpret::extract_text_spans_timed;
+use hayro::vello_cpu::color::palette::css::WHITE;
+use hayro::{BgMode, RenderSettings, render_timed};
+use std::time::Instant;
+
+const CONTENT: &[u8] = b"BT /F1 12 Tf 72 700 Td (Timing) Tj ET 0 0 1 rg 50 50 200 200 re f";
+
+fn assert_sums_to_total(phases: &[f64], total: f64) {
+    assert!(phases.iter().all(|p| *p >= 0.0), "phases: {phases:?}");
+
+    let sum = phases.iter().sum::<f64>();
+    assert!(
+        (sum - total).abs() <= total * 0.05 + 1e-6,
+        "phases {phases:?} should sum to roughly {total}"
+    );
+}
+
+#[test]
+fn render_timings_sum_to_total() {
+    let pdf = parse_page(make_single_page_pdf(CONTENT, 0));
+    let start = Instant::now();
+    let now = || start.elapsed().as_secs_f64() * 1000.0;
+
+    let (pixmap, timings) = render_timed(
+        &pdf.pages()[0],
+        &test_settings(),
+        &RenderSettings {
+            bg_mode: BgMode::Solid(WHITE),
+            ..Default::default()
+        },
+        &now,
+    );
+
+    assert_eq!(pixmap.width(), 595);
+    assert!(timings.total > 0.0);
+    assert_sums_to_total(
+        &[timings.parse, timings.interpret, timings.rasterize],
+        timings.total,
+    );
+}
+
+#[test]
+fn text_extraction_timings_sum_to_total() {
+    let pdf = parse_page(make_single_page_pdf(CONTENT, 0));
+    let start = Instant::now();
+    let now = || start.elapsed().as_secs_f64() * 1000.0;
+
+    let (spans, timings) = extract_text_spans_timed(&pdf.pages()[0], &test_settings(), &now);
+
+    assert!(spans.iter().any(|span| span.text.contains("Timing")));
+    assert!(timings.total > 0.0);
+    assert_sums_to_total(
+        &[timings.parse, timings.interpret, timings.merge],
+        timings.total,
+    );
+}
@@ -14,12 +14,13 @@ use crate::primitive::{WriteDirect, WriteIndirect};
 use flate2::Compression;
 use flate2::write::ZlibEncoder;
 use hayro_syntax::Pdf;
+use hayro_syntax::content::UntypedIter;
 use hayro_syntax::object::Dict;
 use hayro_syntax::object::Object;
 use hayro_syntax::object::dict::keys::{
-    COLORSPACE, EXT_G_STATE, FONT, GROUP, PATTERN, PROPERTIES, SHADING, XOBJECT,
+    ANNOTS, COLORSPACE, CONTENTS, EXT_G_STATE, FONT, GROUP, PATTERN, PROPERTIES, SHADING, XOBJECT,
 };
-use hayro_syntax::object::{MaybeRef, ObjRef};
+use hayro_syntax::object::{Array, MaybeRef, ObjRef};
 use hayro_syntax::page::{Page, Resources, Rotation};
 use kurbo::Affine;
 use log::warn;
@@ -35,20 +36,67 @@ pub fn extract<'a>(
     pdf: &Pdf,
     new_ref: Box<dyn FnMut() -> Ref + 'a>,
     queries: &[ExtractionQuery],
+) -> Result<ExtractionResult, ExtractionError> {
+    extract_with_options(pdf, new_ref, queries, &ExtractionOptions::default())
+}
+
+/// Apply the extraction queries to the given PDF with custom options and return the results.
+pub fn extract_with_options<'a>(
+    pdf: &Pdf,
+    new_ref: Box<dyn FnMut() -> Ref + 'a>,
+    queries: &[ExtractionQuery],
+    options: &ExtractionOptions,
 ) -> Result<ExtractionResult, ExtractionError> {
     let pages = pdf.pages();
     let mut ctx = ExtractionContext::new(new_ref, pdf);
 
-    for query in queries {
+    let root_refs = queries.iter().map(|_| ctx.new_ref()).collect::<Vec<_>>();
+
+    // Map the original page objects to the extracted ones, so that references to them
+    // from annotations (such as `/P` or link destinations) don't pull in the original pages.
+    for (query, root_ref) in queries.iter().zip(&root_refs) {
+        if options.include_annotations
+            && let ExtractionQueryType::Page = query.query_type
+            && let Some(id) = pages.get(query.page_index).and_then(|p| p.raw().obj_id())
+        {
+            let obj_ref = ObjRef::new(id.obj_number, id.gen_number);
+            ctx.ref_map.entry(obj_ref).or_insert(*root_ref);
+            ctx.visited_objects.insert(obj_ref);
+        }
+    }
+
+    // Mark removed annotations as invalid before anything references them.
+    if options.include_annotations {
+        for (page_idx, positions) in &options.removed_annotations {
+            let Some(annots) = pages
+                .get(*page_idx)
+                .and_then(|p| p.raw().get::<Array<'_>>(ANNOTS))
+            else {
+                continue;
+            };
+
+            for (position, entry) in annots.raw_iter().enumerate() {
+                if let MaybeRef::Ref(r) = entry
+                    && positions.contains(&position)
+                {
+                    ctx.valid_ref_cache.insert(r, false);
+                }
+            }
+        }
+    }
+
+    for (query, root_ref) in queries.iter().zip(root_refs) {
         let page = pages
             .get(query.page_index)
             .ok_or(ExtractionError::InvalidPageIndex(query.page_index))?;
 
-        let root_ref = ctx.new_ref();
-
         let res = match query.query_type {
-            ExtractionQueryType::XObject => write_xobject(page, root_ref, &mut ctx),
-            ExtractionQueryType::Page => write_page(page, root_ref, query.page_index, &mut ctx),
+            ExtractionQueryType::XObject => {
+                write_xobject(page, root_ref, query.page_index, &mut ctx, options)
+            }
+            ExtractionQueryType::Page => {
+                write_page(page, root_ref, query.page_index, &mut ctx, options)
+            }
         };
 
         ctx.root_refs.push(res.map(|_| root_ref));
@@ -106,6 +154,37 @@ impl ExtractionQuery {
     }
 }
 
+/// Options that customize how pages are extracted.
+#[derive(Clone, Debug, Default)]
+pub struct ExtractionOptions {
+    /// Decoded content streams that replace the original content of the page
+    /// with the given index.
+    ///
+    /// `XObject` resources that are no longer invoked by a replaced content stream
+    /// are not written, so that the data they contain doesn't end up in the output.
+    pub content_overrides: HashMap<usize, Vec<u8>>,
+    /// Rotations in degrees that replace the `/Rotate` entry of the page with the
+    /// given index.
+    ///
+    /// This only applies to pages extracted as pages. Rotations are normalized to
+    /// 0, 90, 180 or 270 degrees, rounding down to a multiple of 90.
+    pub rotation_overrides: HashMap<usize, i32>,
+    /// Whether the content streams of extracted pages are copied with their
+    /// original, still encoded data instead of being decoded and re-compressed.
+    ///
+    /// This only applies to pages extracted as pages and without a content override.
+    pub preserve_content_streams: bool,
+    /// Whether the existing annotations of pages extracted as pages are kept.
+    pub include_annotations: bool,
+    /// Positions in the `/Annots` array of the page with the given index whose
+    /// annotations are not kept.
+    ///
+    /// Other references to these annotations, like the `/Parent` of a popup,
+    /// are replaced with null, so that the removed annotations don't end up in
+    /// the output. This only applies if `include_annotations` is set.
+    pub removed_annotations: HashMap<usize, HashSet<usize>>,
+}
+
 /// An error that occurred during page extraction.
 #[derive(Debug, Copy, Clone)]
 pub enum ExtractionError {
@@ -286,47 +365,99 @@ fn write_page(
     page_ref: Ref,
     page_idx: usize,
     ctx: &mut ExtractionContext<'_>,
+    options: &ExtractionOptions,
 ) -> Result<(), ExtractionError> {
+    let content_override = options.content_overrides.get(&page_idx);
+    let raw_dict = page.raw();
     let mut chunk = Chunk::new();
+
+    // The original `/Contents` entry, if it should be copied as-is. The referenced streams
+    // are written as dependencies, which keeps their encoded data untouched.
+    let preserved_contents = if options.preserve_content_streams && content_override.is_none() {
+        raw_dict
+            .get_raw::<Object<'_>>(CONTENTS)
+            .filter(|c| matches!(c, MaybeRef::Ref(_) | MaybeRef::NotRef(Object::Array(_))))
+    } else {
+        None
+    };
+
     // Note: We can cache content stream references, but _not_ the page references themselves.
     // Acrobat for some reason doesn't like duplicate page references in the page tree.
-    let stream_ref = if let Some(cached) = ctx.cached_content_streams.get(&page_idx) {
-        *cached
+    let stream_ref = if preserved_contents.is_some() {
+        None
+    } else if let Some(cached) = ctx.cached_content_streams.get(&page_idx) {
+        Some(*cached)
     } else {
         let stream_ref = ctx.new_ref();
 
+        let content = match content_override {
+            Some(content) => content.as_slice(),
+            None => page.page_stream().unwrap_or(b""),
+        };
+
         chunk
-            .stream(
-                stream_ref,
-                &deflate_encode(page.page_stream().unwrap_or(b"")),
-            )
+            .stream(stream_ref, &deflate_encode(content))
             .filter(Filter::FlateDecode);
         ctx.cached_content_streams.insert(page_idx, stream_ref);
 
-        stream_ref
+        Some(stream_ref)
     };
 
     let mut pdf_page = chunk.page(page_ref);
 
-    pdf_page
-        .media_box(convert_rect(&page.media_box()))
-        .crop_box(convert_rect(&page.crop_box()))
-        .rotate(match page.rotation() {
+    let rotation = match options.rotation_overrides.get(&page_idx) {
+        Some(degrees) => degrees.rem_euclid(360) / 90 * 90,
+        None => match page.rotation() {
             Rotation::None => 0,
             Rotation::Horizontal => 90,
             Rotation::Flipped => 180,
             Rotation::FlippedHorizontal => 270,
-        })
-        .parent(ctx.page_tree_parent_ref)
-        .contents(stream_ref);
+        },
+    };
 
-    let raw_dict = page.raw();
+    pdf_page
+        .media_box(convert_rect(&page.media_box()))
+        .crop_box(convert_rect(&page.crop_box()))
+        .rotate(rotation)
+        .parent(ctx.page_tree_parent_ref);
+
+    if let Some(stream_ref) = stream_ref {
+        pdf_page.contents(stream_ref);
+    } else if let Some(contents) = preserved_contents {
+        contents.write_direct(pdf_page.insert(Name(CONTENTS)), ctx);
+    }
 
     if let Some(group) = raw_dict.get_raw::<Object<'_>>(GROUP) {
         group.write_direct(pdf_page.insert(Name(GROUP)), ctx);
     }
 
-    serialize_resources(page.resources(), ctx, &mut pdf_page);
+    if options.include_annotations {
+        match options.removed_annotations.get(&page_idx) {
+            Some(removed) => {
+                if let Some(annots) = raw_dict.get::<Array<'_>>(ANNOTS) {
+                    let mut array = pdf_page.insert(Name(ANNOTS)).array();
+                    for (position, entry) in annots.raw_iter().enumerate() {
+                        if !removed.contains(&position) {
+                            entry.write_direct(array.push(), ctx);
+                        }
+                    }
+                }
+            }
+            None => {
+                if let Some(annots) = raw_dict.get_raw::<Object<'_>>(ANNOTS) {
+                    annots.write_direct(pdf_page.insert(Name(ANNOTS)), ctx);
+                }
+            }
+        }
+    }
+
+    let used_x_objects = content_override.map(|c| used_x_objects(c));
+    serialize_resources(
+        page.resources(),
+        ctx,
+        &mut pdf_page,
+        used_x_objects.as_ref(),
+    );
 
     pdf_page.finish();
 
@@ -338,10 +469,17 @@ fn write_page(
 fn write_xobject(
     page: &Page<'_>,
     xobj_ref: Ref,
+    page_idx: usize,
     ctx: &mut ExtractionContext<'_>,
+    options: &ExtractionOptions,
 ) -> Result<(), ExtractionError> {
+    let content_override = options.content_overrides.get(&page_idx);
     let mut chunk = Chunk::new();
-    let encoded_stream = deflate_encode(page.page_stream().unwrap_or(b""));
+    let content = match content_override {
+        Some(content) => content.as_slice(),
+        None => page.page_stream().unwrap_or(b""),
+    };
+    let encoded_stream = deflate_encode(content);
     let mut x_object = chunk.form_xobject(xobj_ref, &encoded_stream);
     x_object.deref_mut().filter(Filter::FlateDecode);
 
@@ -365,7 +503,13 @@ fn write_xobject(
         i[5] as f32,
     ]);
 
-    serialize_resources(page.resources(), ctx, &mut x_object);
+    let used_x_objects = content_override.map(|c| used_x_objects(c));
+    serialize_resources(
+        page.resources(),
+        ctx,
+        &mut x_object,
+        used_x_objects.as_ref(),
+    );
 
     x_object.finish();
     ctx.chunks.push(chunk);
@@ -377,11 +521,15 @@ fn serialize_resources(
     resources: &Resources<'_>,
     ctx: &mut ExtractionContext<'_>,
     writer: &mut impl ResourcesExt,
+    used_x_objects: Option<&HashSet<Vec<u8>>>,
 ) {
     let ext_g_states = collect_resources(resources, |r| r.ext_g_states.clone());
     let shadings = collect_resources(resources, |r| r.shadings.clone());
     let patterns = collect_resources(resources, |r| r.patterns.clone());
-    let x_objects = collect_resources(resources, |r| r.x_objects.clone());
+    let mut x_objects = collect_resources(resources, |r| r.x_objects.clone());
+    if let Some(used) = used_x_objects {
+        x_objects.retain(|name, _| used.contains(name.deref()));
+    }
     let color_spaces = collect_resources(resources, |r| r.color_spaces.clone());
     let fonts = collect_resources(resources, |r| r.fonts.clone());
     let properties = collect_resources(resources, |r| r.properties.clone());
@@ -446,6 +594,15 @@ fn collect_resources_inner<'a>(
     }
 }
 
+/// Collect the names of all `XObjects` invoked via `Do` in a content stream.
+fn used_x_objects(content: &[u8]) -> HashSet<Vec<u8>> {
+    UntypedIter::new(content)
+        .filter(|op| &*op.operator == b"Do")
+        .filter_map(|op| op.operands().next().and_then(|o| o.into_name()))
+        .map(|name| name.deref().to_vec())
+        .collect()
+}
+
 pub(crate) fn deflate_encode(data: &[u8]) -> Vec<u8> {
     use std::io::Write;
 
@@ -2,7 +2,7 @@
 
 use hayro::hayro_interpret::InterpreterSettings;
 use hayro::hayro_syntax::Pdf;
-use hayro::{RenderSettings, render};
+use hayro::{BgMode, RenderSettings, render};
 use vello_cpu::color::palette::css::WHITE;
 
 fn main() {
@@ -27,7 +27,7 @@ fn main() {
     let render_settings = RenderSettings {
         x_scale: scale,
         y_scale: scale,
-        bg_color: WHITE,
+        bg_mode: BgMode::Solid(WHITE),
         ..Default::default()
     };
 
@@ -42,6 +42,7 @@ use hayro_interpret::hayro_syntax::page::Page;
 use hayro_interpret::util::{PageExt, RectExt};
 use hayro_interpret::{BlendMode, Context};
 use hayro_interpret::{ClipPath, interpret_page};
+use hayro_interpret::{InterpreterWarning, collect_warnings};
 use kurbo::{Affine, Rect, Shape};
 use std::ops::RangeInclusive;
 
@@ -51,12 +52,35 @@ pub use vello_cpu;
 
 use vello_cpu::color::AlphaColor;
 use vello_cpu::color::Srgb;
-use vello_cpu::color::palette::css::TRANSPARENT;
 use vello_cpu::color::palette::css::WHITE;
 use vello_cpu::{Level, Pixmap, RenderMode};
 
 mod renderer;
 
+/// The background of a rendered page.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BgMode {
+    /// Fill the background with a single color.
+    Solid(AlphaColor<Srgb>),
+    /// Fill the background with a checkerboard, which makes transparent
+    /// regions of the page visible.
+    Checker {
+        /// The side length of each square, in pixels of the pixmap.
+        ///
+        /// This is not affected by the scale, so to get the same pattern on a
+        /// high-density display, it needs to be multiplied with the device
+        /// pixel ratio.
+        size: u16,
+        /// The color of the top-left square and every other square after it.
+        c1: AlphaColor<Srgb>,
+        /// The color of the remaining squares.
+        c2: AlphaColor<Srgb>,
+    },
+    /// Leave the background transparent.
+    #[default]
+    Transparent,
+}
+
 /// Settings to apply during rendering.
 #[derive(Clone, Copy)]
 pub struct RenderSettings {
@@ -70,9 +94,13 @@ pub struct RenderSettings {
     /// The height of the viewport. If this is set to `None`, the height will be chosen
     /// automatically based on the scale factor and the dimensions of the PDF.
     pub height: Option<u16>,
-    /// The background color. Determines the color of the base
-    /// rectangle during rendering to a pixmap.
-    pub bg_color: AlphaColor<Srgb>,
+    /// How the base rectangle is filled before the page is drawn onto it.
+    pub bg_mode: BgMode,
+    /// Whether the page should be rendered in grayscale.
+    ///
+    /// The final composite, including the background, is converted to its
+    /// luminance using the Rec. 709 weights. The pixmap still has four channels.
+    pub grayscale: bool,
 }
 
 impl Default for RenderSettings {
@@ -82,17 +110,64 @@ impl Default for RenderSettings {
             y_scale: 1.0,
             width: None,
             height: None,
-            bg_color: TRANSPARENT,
+            bg_mode: BgMode::default(),
+            grayscale: false,
         }
     }
 }
 
+/// Durations of the individual phases of rendering a page.
+///
+/// All values are expressed in the unit of the clock passed to [`render_timed`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderTimings {
+    /// Decoding the content stream of the page.
+    pub parse: f64,
+    /// Interpreting the content stream and building the render commands.
+    pub interpret: f64,
+    /// Rasterizing the render commands into the pixmap.
+    pub rasterize: f64,
+    /// The total time spent, which is the sum of all phases.
+    pub total: f64,
+}
+
 /// Render the page with the given settings to a pixmap.
 pub fn render(
     page: &Page<'_>,
     interpreter_settings: &InterpreterSettings,
     render_settings: &RenderSettings,
 ) -> Pixmap {
+    render_timed(page, interpreter_settings, render_settings, &|| 0.0).0
+}
+
+/// Render the page with the given settings to a pixmap like [`render`], and
+/// return the warnings emitted while interpreting it.
+pub fn render_with_warnings(
+    page: &Page<'_>,
+    interpreter_settings: &InterpreterSettings,
+    render_settings: &RenderSettings,
+) -> (Pixmap, Vec<InterpreterWarning>) {
+    collect_warnings(interpreter_settings, |settings| {
+        render(page, settings, render_settings)
+    })
+}
+
+/// Render the page with the given settings to a pixmap, recording how much time
+/// was spent in each phase.
+///
+/// `now` should return a monotonic timestamp, for example in milliseconds. It is passed
+/// in by the caller since there is no portable clock on all targets (like `wasm32`).
+pub fn render_timed(
+    page: &Page<'_>,
+    interpreter_settings: &InterpreterSettings,
+    render_settings: &RenderSettings,
+    now: &dyn Fn() -> f64,
+) -> (Pixmap, RenderTimings) {
+    let start = now();
+    // The decoded content stream is cached, so interpreting it later on won't decode it again.
+    let _ = page.page_stream();
+    let parsed = now();
+
     let (x_scale, y_scale) = (render_settings.x_scale, render_settings.y_scale);
     let (width, height) = page.render_dimensions();
     let (scaled_width, scaled_height) = ((width * x_scale) as f64, (height * y_scale) as f64);
@@ -120,10 +195,7 @@ pub fn render(
 
     let mut device = Renderer::new(pix_width, pix_height, vc_settings);
 
-    device.ctx.set_paint(render_settings.bg_color);
-    device
-        .ctx
-        .fill_rect(&Rect::new(0.0, 0.0, pix_width as f64, pix_height as f64));
+    fill_background(&mut device, render_settings.bg_mode, pix_width, pix_height);
     let mut clip_path = page.intersected_crop_box().to_kurbo().to_path(0.1);
     clip_path.apply_affine(initial_transform);
     device.push_clip_path(&ClipPath {
@@ -137,11 +209,69 @@ pub fn render(
     device.pop_transparency_group();
 
     device.pop_clip_path();
+    let interpreted = now();
 
     let mut pixmap = Pixmap::new(pix_width, pix_height);
     device.ctx.render_to_pixmap(&mut pixmap);
+    if render_settings.grayscale {
+        convert_to_grayscale(&mut pixmap);
+    }
+    let rasterized = now();
+
+    let timings = RenderTimings {
+        parse: parsed - start,
+        interpret: interpreted - parsed,
+        rasterize: rasterized - interpreted,
+        total: rasterized - start,
+    };
+
+    (pixmap, timings)
+}
+
+/// Fill the base rectangle of the pixmap according to the background mode.
+fn fill_background(device: &mut Renderer, bg_mode: BgMode, width: u16, height: u16) {
+    let base = Rect::new(0.0, 0.0, f64::from(width), f64::from(height));
+
+    match bg_mode {
+        BgMode::Transparent => {}
+        BgMode::Solid(color) => {
+            device.ctx.set_paint(color);
+            device.ctx.fill_rect(&base);
+        }
+        BgMode::Checker { size, c1, c2 } => {
+            device.ctx.set_paint(c1);
+            device.ctx.fill_rect(&base);
 
-    pixmap
+            // Only the squares of the second color need to be drawn on top.
+            let (size, width, height) =
+                (u32::from(size.max(1)), u32::from(width), u32::from(height));
+            device.ctx.set_paint(c2);
+            for y in (0..height).step_by(size as usize) {
+                let first_col = 1 - (y / size) % 2;
+                for x in (first_col * size..width).step_by(2 * size as usize) {
+                    device.ctx.fill_rect(&Rect::new(
+                        f64::from(x),
+                        f64::from(y),
+                        f64::from((x + size).min(width)),
+                        f64::from((y + size).min(height)),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Replace the color of each pixel by its luminance.
+fn convert_to_grayscale(pixmap: &mut Pixmap) {
+    for pixel in pixmap.data_mut() {
+        // The weights sum to one, so this works on premultiplied colors as well.
+        let luminance =
+            0.2126 * f32::from(pixel.r) + 0.7152 * f32::from(pixel.g) + 0.0722 * f32::from(pixel.b);
+        let gray = (luminance.round() as u8).min(pixel.a);
+        pixel.r = gray;
+        pixel.g = gray;
+        pixel.b = gray;
+    }
 }
 
 // Just a convenience method for testing.
@@ -167,7 +297,7 @@ pub fn render_pdf(
                 &RenderSettings {
                     x_scale: scale,
                     y_scale: scale,
-                    bg_color: WHITE,
+                    bg_mode: BgMode::Solid(WHITE),
                     ..Default::default()
                 },
             );